@@ -0,0 +1,61 @@
+package app.tally.sharesheet
+
+import android.app.Activity
+import android.content.Intent
+import androidx.activity.result.ActivityResult
+import androidx.core.content.FileProvider
+import app.tauri.annotation.ActivityCallback
+import app.tauri.annotation.Command
+import app.tauri.annotation.InvokeArg
+import app.tauri.annotation.TauriPlugin
+import app.tauri.plugin.Invoke
+import app.tauri.plugin.JSObject
+import app.tauri.plugin.Plugin
+import java.io.File
+
+@InvokeArg
+class ShareFileArgs {
+    lateinit var path: String
+    lateinit var mimeType: String
+    lateinit var title: String
+}
+
+// Must match the files-path entry in res/xml/share_sheet_paths.xml
+private const val REPORTS_DIRECTORY = "TallyTaxReports"
+
+@TauriPlugin
+class ShareSheetPlugin(private val activity: Activity) : Plugin(activity) {
+    // Reports are kept in app storage, where the FileProvider can reach them
+    @Command
+    fun reportsDirectory(invoke: Invoke) {
+        val ret = JSObject()
+        ret.put("path", File(activity.filesDir, REPORTS_DIRECTORY).absolutePath)
+        invoke.resolve(ret)
+    }
+
+    @Command
+    fun shareFile(invoke: Invoke) {
+        val args = invoke.parseArgs(ShareFileArgs::class.java)
+        val uri = try {
+            FileProvider.getUriForFile(activity, "${activity.packageName}.sharesheet.fileprovider", File(args.path))
+        } catch (e: IllegalArgumentException) {
+            invoke.reject("Can't share ${args.path}: ${e.message}")
+            return
+        }
+        val send = Intent(Intent.ACTION_SEND).apply {
+            type = args.mimeType
+            putExtra(Intent.EXTRA_STREAM, uri)
+            putExtra(Intent.EXTRA_SUBJECT, args.title)
+            addFlags(Intent.FLAG_GRANT_READ_URI_PERMISSION)
+        }
+        startActivityForResult(invoke, Intent.createChooser(send, args.title), "shareResult")
+    }
+
+    // The chooser doesn't report whether a destination was picked
+    @ActivityCallback
+    fun shareResult(invoke: Invoke, result: ActivityResult) {
+        val ret = JSObject()
+        ret.put("completed", true)
+        invoke.resolve(ret)
+    }
+}
@@ -0,0 +1,9 @@
+// The share sheet is only called from Rust, so no commands are exposed
+const COMMANDS: &[&str] = &[];
+
+fn main() {
+    tauri_plugin::Builder::new(COMMANDS)
+        .android_path("android")
+        .ios_path("ios")
+        .build();
+}
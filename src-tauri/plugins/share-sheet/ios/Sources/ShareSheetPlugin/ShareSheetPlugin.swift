@@ -0,0 +1,52 @@
+import SwiftRs
+import Tauri
+import UIKit
+import WebKit
+
+class ShareFileArgs: Decodable {
+  let path: String
+  let mimeType: String
+  let title: String
+}
+
+class ShareSheetPlugin: Plugin {
+  // Reports are kept in the app's Documents folder, which the Files app can show
+  @objc public func reportsDirectory(_ invoke: Invoke) throws {
+    let documents = try FileManager.default.url(
+      for: .documentDirectory, in: .userDomainMask, appropriateFor: nil, create: true)
+    invoke.resolve(["path": documents.appendingPathComponent("TallyTaxReports").path])
+  }
+
+  @objc public func shareFile(_ invoke: Invoke) throws {
+    let args = try invoke.parseArgs(ShareFileArgs.self)
+    let url = URL(fileURLWithPath: args.path)
+
+    DispatchQueue.main.async {
+      guard let presenter = self.manager.viewController else {
+        invoke.reject("No view to present the share sheet from")
+        return
+      }
+      let controller = UIActivityViewController(activityItems: [url], applicationActivities: nil)
+      controller.setValue(args.title, forKey: "subject")
+      controller.completionWithItemsHandler = { _, completed, _, error in
+        if let error = error {
+          invoke.reject(error.localizedDescription)
+        } else {
+          invoke.resolve(["completed": completed])
+        }
+      }
+      // iPad shows the sheet as a popover, which needs an anchor
+      if let popover = controller.popoverPresentationController {
+        popover.sourceView = presenter.view
+        popover.sourceRect = CGRect(x: presenter.view.bounds.midX, y: presenter.view.bounds.midY, width: 0, height: 0)
+        popover.permittedArrowDirections = []
+      }
+      presenter.present(controller, animated: true)
+    }
+  }
+}
+
+@_cdecl("init_plugin_share_sheet")
+func initPlugin() -> Plugin {
+  return ShareSheetPlugin()
+}
@@ -0,0 +1,125 @@
+//! Share Sheet Plugin
+//!
+//! Presents the platform share sheet for a file: `UIActivityViewController`
+//! on iOS and the `ACTION_SEND` chooser on Android. Other platforms have no
+//! share sheet and get an error.
+//!
+//! Android only lets the share sheet read files under the FileProvider roots
+//! in `share_sheet_paths.xml`, so reports must be saved in the directory
+//! given by `reports_directory`.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::plugin::{Builder, TauriPlugin};
+use tauri::{Manager, Runtime};
+
+#[cfg(target_os = "ios")]
+tauri::ios_plugin_binding!(init_plugin_share_sheet);
+
+/// A file to hand to the share sheet
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareFile {
+    /// Absolute path of the file
+    pub path: String,
+    pub mime_type: String,
+    /// Shown as the share sheet title and used as the email subject
+    pub title: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(not(any(target_os = "android", target_os = "ios")), allow(dead_code))]
+struct ShareResponse {
+    completed: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(not(any(target_os = "android", target_os = "ios")), allow(dead_code))]
+struct DirectoryResponse {
+    path: PathBuf,
+}
+
+/// Handle to the native share sheet, held in managed state
+pub struct ShareSheet<R: Runtime> {
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    handle: tauri::plugin::PluginHandle<R>,
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    _runtime: std::marker::PhantomData<fn() -> R>,
+}
+
+impl<R: Runtime> ShareSheet<R> {
+    /// Present the share sheet and wait until it is dismissed, returning
+    /// whether the file was shared. Android doesn't say whether a destination
+    /// was picked, so there a chooser that returns counts as shared.
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    pub fn share_file(&self, file: &ShareFile) -> Result<bool, String> {
+        self.handle
+            .run_mobile_plugin::<ShareResponse>("shareFile", file)
+            .map(|response| response.completed)
+            .map_err(|e| format!("Share sheet failed: {}", e))
+    }
+
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    pub fn share_file(&self, _file: &ShareFile) -> Result<bool, String> {
+        Err("This platform has no share sheet".to_string())
+    }
+
+    /// Directory in app storage that reports must be saved in to be shared
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    pub fn reports_directory(&self) -> Result<PathBuf, String> {
+        self.handle
+            .run_mobile_plugin::<DirectoryResponse>("reportsDirectory", ())
+            .map(|response| response.path)
+            .map_err(|e| format!("Could not determine reports directory: {}", e))
+    }
+
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    pub fn reports_directory(&self) -> Result<PathBuf, String> {
+        Err("This platform has no share sheet".to_string())
+    }
+}
+
+/// Access to the share sheet from the app or any window
+pub trait ShareSheetExt<R: Runtime> {
+    fn share_sheet(&self) -> &ShareSheet<R>;
+}
+
+impl<R: Runtime, T: Manager<R>> ShareSheetExt<R> for T {
+    fn share_sheet(&self) -> &ShareSheet<R> {
+        self.state::<ShareSheet<R>>().inner()
+    }
+}
+
+pub fn init<R: Runtime>() -> TauriPlugin<R> {
+    Builder::new("share-sheet")
+        .setup(|app, _api| {
+            #[cfg(target_os = "android")]
+            let sheet = ShareSheet {
+                handle: _api.register_android_plugin("app.tally.sharesheet", "ShareSheetPlugin")?,
+            };
+            #[cfg(target_os = "ios")]
+            let sheet = ShareSheet {
+                handle: _api.register_ios_plugin(init_plugin_share_sheet)?,
+            };
+            #[cfg(not(any(target_os = "android", target_os = "ios")))]
+            let sheet = ShareSheet::<R> {
+                _runtime: std::marker::PhantomData,
+            };
+            app.manage(sheet);
+            Ok(())
+        })
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_reports_directory_is_a_file_provider_root() {
+        let plugin =
+            include_str!("../android/src/main/java/app/tally/sharesheet/ShareSheetPlugin.kt");
+        let paths = include_str!("../android/src/main/res/xml/share_sheet_paths.xml");
+        assert!(plugin.contains("File(activity.filesDir, REPORTS_DIRECTORY)"));
+        assert!(plugin.contains(r#"REPORTS_DIRECTORY = "TallyTaxReports""#));
+        assert!(paths.contains(r#"<files-path name="reports" path="TallyTaxReports/" />"#));
+    }
+}
@@ -0,0 +1,143 @@
+//! Australian Business Register Lookup Module
+//!
+//! Enriches vendors with ABR details. Falls back to names already seen on
+//! stored invoices when privacy mode is on, no ABR GUID is configured, or the
+//! service cannot be reached.
+
+use serde::{Deserialize, Serialize};
+
+use crate::documents;
+use crate::invoice::InvoiceParser;
+use crate::network::{self, NetworkFeature, Sourced};
+use crate::settings;
+
+/// Business details for an ABN
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct AbnDetails {
+    pub abn: String,
+    pub entity_name: Option<String>,
+    /// e.g. "Active" or "Cancelled"
+    pub abn_status: Option<String>,
+    pub gst_registered: Option<bool>,
+    pub state: Option<String>,
+    pub postcode: Option<String>,
+}
+
+/// Response shape of the ABR JSON `AbnDetails` endpoint
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "PascalCase", default)]
+struct AbrResponse {
+    abn: String,
+    abn_status: Option<String>,
+    entity_name: Option<String>,
+    address_state: Option<String>,
+    address_postcode: Option<String>,
+    gst: Option<String>,
+    message: Option<String>,
+}
+
+/// Look up an ABN, degrading to offline data when the network is unavailable
+pub async fn lookup_abn(abn: &str) -> Result<Sourced<AbnDetails>, String> {
+    let abn = abn.replace(' ', "");
+    if !InvoiceParser::validate_abn(&abn) {
+        return Err(format!("ABN {} failed checksum validation", abn));
+    }
+
+    let settings = settings::load_settings()?;
+    let access = network::check_access(NetworkFeature::AbnLookup, &settings);
+    if !access.allowed {
+        let reason = access.reason.unwrap_or_default();
+        return Ok(Sourced::offline(offline_details(&abn), &reason));
+    }
+    let Some(guid) = settings.abr_guid.filter(|g| !g.trim().is_empty()) else {
+        return Ok(Sourced::offline(offline_details(&abn), "No ABR GUID configured"));
+    };
+
+    match fetch_abn_details(&abn, &guid).await {
+        Ok(details) => Ok(Sourced::network(details)),
+        Err(e) => Ok(Sourced::offline(offline_details(&abn), &e)),
+    }
+}
+
+async fn fetch_abn_details(abn: &str, guid: &str) -> Result<AbnDetails, String> {
+    use tauri_plugin_http::reqwest;
+
+    let url = format!(
+        "https://abr.business.gov.au/json/AbnDetails.aspx?abn={}&guid={}",
+        abn, guid
+    );
+    let body = reqwest::get(&url)
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("ABR request failed: {}", e))?
+        .text()
+        .await
+        .map_err(|e| format!("ABR response error: {}", e))?;
+
+    parse_abr_response(&body)
+}
+
+/// Parse the JSONP payload returned by the ABR (`callback({...})`)
+fn parse_abr_response(body: &str) -> Result<AbnDetails, String> {
+    let start = body.find('{').ok_or("Malformed ABR response")?;
+    let end = body.rfind('}').ok_or("Malformed ABR response")?;
+    let response: AbrResponse = serde_json::from_str(&body[start..=end])
+        .map_err(|e| format!("Failed to parse ABR response: {}", e))?;
+
+    if response.abn.is_empty() {
+        return Err(response
+            .message
+            .filter(|m| !m.is_empty())
+            .unwrap_or_else(|| "ABN not found".to_string()));
+    }
+
+    let non_empty = |s: Option<String>| s.filter(|v| !v.trim().is_empty());
+    Ok(AbnDetails {
+        abn: response.abn,
+        entity_name: non_empty(response.entity_name),
+        abn_status: non_empty(response.abn_status),
+        gst_registered: Some(non_empty(response.gst).is_some()),
+        state: non_empty(response.address_state),
+        postcode: non_empty(response.address_postcode),
+    })
+}
+
+/// Best-effort details from stored invoices carrying the same ABN
+fn offline_details(abn: &str) -> Option<AbnDetails> {
+    let vendor = documents::with_store(|store| {
+        Ok(store
+            .list(&Default::default())
+            .into_iter()
+            .filter(|d| {
+                d.invoice
+                    .as_ref()
+                    .and_then(|i| i.abn.as_ref())
+                    .is_some_and(|a| a.value == abn)
+            })
+            .find_map(|d| d.vendor))
+    })
+    .ok()??;
+
+    Some(AbnDetails {
+        abn: abn.to_string(),
+        entity_name: Some(vendor),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_abr_response() {
+        let body = r#"callback({"Abn":"51824753556","AbnStatus":"Active","AddressPostcode":"2600","AddressState":"ACT","EntityName":"AUSTRALIAN TAXATION OFFICE","Gst":"2000-07-01","Message":""})"#;
+        let details = parse_abr_response(body).unwrap();
+        assert_eq!(details.entity_name.as_deref(), Some("AUSTRALIAN TAXATION OFFICE"));
+        assert_eq!(details.gst_registered, Some(true));
+        assert_eq!(details.state.as_deref(), Some("ACT"));
+
+        let missing = r#"callback({"Abn":"","Message":"Search text is not a valid ABN or ACN"})"#;
+        assert!(parse_abr_response(missing).is_err());
+    }
+}
@@ -0,0 +1,297 @@
+//! Address Module
+//!
+//! Parses Australian street addresses into unit, street, suburb, state and
+//! postcode. Used for vendor address blocks on invoices and receipts and for
+//! entity profiles, so documents can be reported by state (payroll tax,
+//! travel apportionment).
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use crate::documents::StoredDocument;
+use crate::money::Money;
+
+/// Australian states and territories
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum AustralianState {
+    Nsw,
+    Vic,
+    Qld,
+    Sa,
+    Wa,
+    Tas,
+    Nt,
+    Act,
+}
+
+impl AustralianState {
+    /// Parse an abbreviation or full name
+    pub fn from_label(label: &str) -> Option<Self> {
+        let label = label.trim().trim_end_matches('.').to_lowercase();
+        Some(match label.as_str() {
+            "nsw" | "new south wales" => Self::Nsw,
+            "vic" | "victoria" => Self::Vic,
+            "qld" | "queensland" => Self::Qld,
+            "sa" | "south australia" => Self::Sa,
+            "wa" | "western australia" => Self::Wa,
+            "tas" | "tasmania" => Self::Tas,
+            "nt" | "northern territory" => Self::Nt,
+            "act" | "australian capital territory" => Self::Act,
+            _ => return None,
+        })
+    }
+
+    /// State a postcode belongs to, using Australia Post's ranges
+    pub fn from_postcode(postcode: &str) -> Option<Self> {
+        if postcode.len() != 4 {
+            return None;
+        }
+        let code: u32 = postcode.parse().ok()?;
+        Some(match code {
+            200..=299 | 2600..=2618 | 2900..=2920 => Self::Act,
+            800..=999 => Self::Nt,
+            1000..=2599 | 2619..=2899 | 2921..=2999 => Self::Nsw,
+            3000..=3999 | 8000..=8999 => Self::Vic,
+            4000..=4999 | 9000..=9999 => Self::Qld,
+            5000..=5999 => Self::Sa,
+            6000..=6999 => Self::Wa,
+            7000..=7999 => Self::Tas,
+            _ => return None,
+        })
+    }
+}
+
+/// A parsed Australian address
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Address {
+    /// Unit, shop, suite or level
+    pub unit: Option<String>,
+    /// Street number and name, or a PO box
+    pub street: Option<String>,
+    pub suburb: Option<String>,
+    pub state: Option<AustralianState>,
+    pub postcode: Option<String>,
+}
+
+impl Address {
+    /// Fill in a missing state from the postcode
+    pub fn infer_state(&mut self) {
+        if self.state.is_none() {
+            self.state = self.postcode.as_deref().and_then(AustralianState::from_postcode);
+        }
+    }
+}
+
+/// Street types used to find where the street ends and the suburb begins
+const STREET_TYPES: [&str; 34] = [
+    "st", "street", "rd", "road", "ave", "av", "avenue", "dr", "drive", "hwy", "highway", "pde", "parade", "pl", "place",
+    "ct", "court", "cres", "crescent", "tce", "terrace", "blvd", "boulevard", "ln", "lane", "way", "cct", "circuit",
+    "cl", "close", "esp", "esplanade", "sq", "square",
+];
+
+const STATE_NAMES: &str = "nsw|vic|qld|sa|wa|tas|nt|act|new south wales|victoria|queensland|south australia|western australia|tasmania|northern territory|australian capital territory";
+
+fn tail_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(&format!(
+            r"(?i)^(?P<head>.*?)[\s,]+(?:(?P<state>{})\.?[\s,]+)?(?P<postcode>\d{{4}})(?:[\s,]+australia)?\s*$",
+            STATE_NAMES
+        ))
+        .unwrap()
+    })
+}
+
+fn state_line_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(&format!(r"(?i)\b(?:{})\.?[\s,]+\d{{4}}(?:[\s,]+australia)?\s*$", STATE_NAMES)).unwrap()
+    })
+}
+
+fn unit_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"(?i)^(?:(?:unit|u|shop|suite|level|lvl)\.?\s*(?P<named>[\w-]*\d[\w-]*)[\s,]+|(?P<slash>\w+)\s*/\s*)(?P<rest>.+)$")
+            .unwrap()
+    })
+}
+
+fn clean(part: &str) -> Option<String> {
+    let part = part.trim().trim_matches(',').trim();
+    (!part.is_empty()).then(|| part.to_string())
+}
+
+/// Parse a single address written on one line or across several
+pub fn parse_address(text: &str) -> Option<Address> {
+    let joined = text
+        .lines()
+        .map(|l| l.trim().trim_end_matches(','))
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let caps = tail_pattern().captures(&joined)?;
+    let head = caps.name("head").map_or("", |m| m.as_str());
+    if !head.chars().any(|c| c.is_alphabetic()) {
+        return None;
+    }
+
+    let mut address = Address {
+        state: caps.name("state").and_then(|m| AustralianState::from_label(m.as_str())),
+        postcode: Some(caps["postcode"].to_string()),
+        ..Default::default()
+    };
+
+    // Split the head into street and suburb
+    let parts: Vec<&str> = head.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()).collect();
+    let (street, suburb) = match parts.as_slice() {
+        [] => (None, None),
+        [only] => split_street_and_suburb(only),
+        [rest @ .., last] => (clean(&rest.join(", ")), clean(last)),
+    };
+
+    address.street = match street.as_deref().and_then(|s| unit_pattern().captures(s)) {
+        Some(caps) => {
+            address.unit = caps.name("named").or(caps.name("slash")).map(|m| m.as_str().to_string());
+            clean(&caps["rest"])
+        }
+        None => street,
+    };
+    address.suburb = suburb;
+
+    // Without a state, only trust the postcode when there is a street too
+    if address.state.is_none() && address.street.is_none() {
+        return None;
+    }
+    address.infer_state();
+    Some(address)
+}
+
+/// Split "12 Smith St Newtown" after the last street type
+fn split_street_and_suburb(text: &str) -> (Option<String>, Option<String>) {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let street_end = words.iter().rposition(|w| {
+        let w = w.trim_end_matches(['.', ',']).to_lowercase();
+        STREET_TYPES.contains(&w.as_str())
+    });
+
+    match street_end {
+        Some(end) if end + 1 < words.len() => (clean(&words[..=end].join(" ")), clean(&words[end + 1..].join(" "))),
+        Some(_) => (clean(text), None),
+        None if text.chars().next().is_some_and(|c| c.is_ascii_digit()) => (clean(text), None),
+        None => (None, clean(text)),
+    }
+}
+
+fn looks_like_street(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    line.chars().next().is_some_and(|c| c.is_ascii_digit())
+        || lower.starts_with("po box")
+        || lower.starts_with("unit")
+        || lower.starts_with("shop")
+        || lower.starts_with("level")
+}
+
+/// Find the first address in a block of text, such as an invoice header.
+/// Only lines ending in a state and postcode are considered, joined with the
+/// street line above when the street is written separately.
+pub fn find_address(lines: &[&str]) -> Option<Address> {
+    let lines: Vec<&str> = lines.iter().map(|l| l.trim()).filter(|l| !l.is_empty()).collect();
+    for (i, line) in lines.iter().enumerate() {
+        if !state_line_pattern().is_match(line) {
+            continue;
+        }
+        let candidate = match i.checked_sub(1).map(|p| lines[p]) {
+            Some(previous) if !looks_like_street(line) && looks_like_street(previous) => {
+                format!("{}\n{}", previous, line)
+            }
+            _ => line.to_string(),
+        };
+        if let Some(address) = parse_address(&candidate) {
+            return Some(address);
+        }
+    }
+    None
+}
+
+/// State a document's vendor is located in
+pub fn document_state(doc: &StoredDocument) -> Option<AustralianState> {
+    if let Some(address) = doc.invoice.as_ref().and_then(|i| i.vendor_address.as_ref()) {
+        return address.value.state;
+    }
+    let receipt = doc.receipt.as_ref()?;
+    // Store addresses are printed in the receipt header
+    let header: Vec<&str> = receipt.raw_text.lines().take(8).collect();
+    find_address(&header).and_then(|a| a.state)
+}
+
+/// Document totals for one state
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct StateTotal {
+    /// `None` groups documents with no known vendor address
+    pub state: Option<AustralianState>,
+    pub document_count: usize,
+    pub total: Money,
+    pub gst: Money,
+}
+
+/// Group document totals by the state of each vendor
+pub fn state_totals(docs: &[StoredDocument]) -> Vec<StateTotal> {
+    let mut totals: BTreeMap<Option<AustralianState>, StateTotal> = BTreeMap::new();
+    for doc in docs {
+        let state = document_state(doc);
+        let entry = totals.entry(state).or_insert_with(|| StateTotal {
+            state,
+            ..Default::default()
+        });
+        entry.document_count += 1;
+        entry.total += doc.total.unwrap_or_default();
+        entry.gst += doc.gst.unwrap_or_default();
+    }
+
+    // Known states first, unknown last
+    let mut totals: Vec<StateTotal> = totals.into_values().collect();
+    totals.sort_by_key(|t| (t.state.is_none(), t.state));
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_address_forms() {
+        let address = parse_address("Unit 4, 12 Smith St, Newtown NSW 2042").unwrap();
+        assert_eq!(address.unit.as_deref(), Some("4"));
+        assert_eq!(address.street.as_deref(), Some("12 Smith St"));
+        assert_eq!(address.suburb.as_deref(), Some("Newtown"));
+        assert_eq!(address.state, Some(AustralianState::Nsw));
+        assert_eq!(address.postcode.as_deref(), Some("2042"));
+
+        let address = parse_address("3/45 High Street Kew East Victoria 3102").unwrap();
+        assert_eq!(address.unit.as_deref(), Some("3"));
+        assert_eq!(address.street.as_deref(), Some("45 High Street"));
+        assert_eq!(address.suburb.as_deref(), Some("Kew East"));
+        assert_eq!(address.state, Some(AustralianState::Vic));
+
+        // State inferred from the postcode
+        let address = parse_address("PO Box 99\nBraddon 2612").unwrap();
+        assert_eq!(address.street.as_deref(), Some("PO Box 99"));
+        assert_eq!(address.state, Some(AustralianState::Act));
+
+        assert!(parse_address("Invoice 2024").is_none());
+    }
+
+    #[test]
+    fn test_find_address_in_header() {
+        let header = ["Acme Plumbing Pty Ltd", "ABN 51 824 753 556", "88 Queen St", "Brisbane QLD 4000", "Invoice 1234"];
+        let address = find_address(&header).unwrap();
+        assert_eq!(address.street.as_deref(), Some("88 Queen St"));
+        assert_eq!(address.suburb.as_deref(), Some("Brisbane"));
+        assert_eq!(address.state, Some(AustralianState::Qld));
+    }
+}
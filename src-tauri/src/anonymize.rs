@@ -0,0 +1,366 @@
+//! Dataset Anonymization Module
+//!
+//! Writes a copy of stored documents that can be shared to reproduce parser
+//! bugs without exposing the owner's finances. Replacements are derived from
+//! a salted hash, so the same vendor or ABN becomes the same pseudonym in
+//! every document and in the OCR text, and exporting again with the same
+//! salt gives the same dataset:
+//! - Vendor and recipient names become `Business <hash>`, keeping any
+//!   company suffix such as "Pty Ltd" the parser relies on
+//! - ABNs and ACNs become other numbers that still pass their checksums, with
+//!   a company's ABN still ending in its ACN
+//! - Amounts in each document are scaled by one factor, so totals, GST and
+//!   line items still add up
+//! - Addresses, card details, scanned codes and file names are removed
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::documents::StoredDocument;
+use crate::invoice::{self, InvoiceParser};
+use crate::money::round_cents;
+use crate::storage;
+
+/// Company suffixes kept on pseudonyms
+const COMPANY_SUFFIXES: [&str; 6] = ["pty ltd", "pty. ltd.", "limited", "ltd", "inc", "co"];
+
+/// Keys holding business names
+const NAME_KEYS: [&str; 3] = ["vendor", "vendor_name", "bill_to_name"];
+
+/// Keys holding ABNs or ACNs
+const NUMBER_KEYS: [&str; 3] = ["abn", "bill_to_abn", "acn"];
+
+/// Keys holding amounts of money
+const MONEY_KEYS: [&str; 11] = [
+    "total",
+    "gst",
+    "total_amount",
+    "gst_amount",
+    "amount",
+    "unit_price",
+    "subtotal",
+    "subtotal_amount",
+    "tip",
+    "foreign_fee",
+    "difference",
+];
+
+/// Keys holding OCR or PDF text
+const TEXT_KEYS: [&str; 2] = ["raw_text", "text"];
+
+/// Optional fields removed outright
+const REMOVED_KEYS: [&str; 5] = ["vendor_address", "vendor_contact", "payment_details", "reference", "archived_path"];
+
+/// Result of an anonymized export
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AnonymizedExport {
+    pub file_path: String,
+    pub documents: usize,
+    /// Distinct business names replaced
+    pub names_replaced: usize,
+    /// Pass back to reproduce the same pseudonyms in a later export
+    pub salt: String,
+}
+
+/// Salted pseudonyms for one export
+struct Anonymizer {
+    salt: String,
+}
+
+impl Anonymizer {
+    fn hash(&self, kind: &str, value: &str) -> [u8; 32] {
+        Sha256::digest(format!("{}:{}:{}", self.salt, kind, value).as_bytes()).into()
+    }
+
+    /// `Business <hash>`, keeping the company suffix
+    fn name(&self, name: &str) -> String {
+        let lower = name.trim().to_lowercase();
+        let hash = self.hash("name", &lower);
+        let label: String = hash[..3].iter().map(|b| format!("{:02x}", b)).collect();
+        let suffix = COMPANY_SUFFIXES
+            .iter()
+            .find(|suffix| lower.ends_with(&format!(" {}", suffix)))
+            .map(|suffix| &name.trim()[name.trim().len() - suffix.len()..]);
+        match suffix {
+            Some(suffix) => format!("Business {} {}", label, suffix),
+            None => format!("Business {}", label),
+        }
+    }
+
+    /// Another valid ACN for a nine-digit company number
+    fn acn(&self, digits: &str) -> String {
+        let hash = self.hash("acn", digits);
+        let mut acn: String = hash[..8].iter().map(|b| char::from(b'0' + b % 10)).collect();
+        let sum: u32 = acn.chars().zip((1..=8).rev()).map(|(c, w)| c.to_digit(10).unwrap_or(0) * w).sum();
+        acn.push(char::from(b'0' + ((10 - sum % 10) % 10) as u8));
+        acn
+    }
+
+    /// Another valid ABN, whose last nine digits are the ACN replacing the
+    /// original's last nine
+    fn abn(&self, digits: &str) -> String {
+        let body = self.acn(&digits[digits.len().saturating_sub(9)..]);
+        (10..100)
+            .map(|prefix| format!("{}{}", prefix, body))
+            .find(|abn| InvoiceParser::validate_abn(abn))
+            .unwrap_or_else(|| format!("00{}", body))
+    }
+
+    fn number(&self, digits: &str) -> String {
+        if digits.len() == 11 {
+            self.abn(digits)
+        } else {
+            self.acn(digits)
+        }
+    }
+
+    /// Factor between 0.5 and 1.5 applied to every amount in a document
+    fn scale(&self, document_id: &str) -> f64 {
+        let hash = self.hash("scale", document_id);
+        0.5 + u16::from_be_bytes([hash[0], hash[1]]) as f64 / u16::MAX as f64
+    }
+}
+
+/// Names, numbers and amount factor used to rewrite one document
+struct DocumentRewrite<'a> {
+    anonymizer: &'a Anonymizer,
+    /// Printed forms of names and numbers with their replacements, longest first
+    replacements: Vec<(Regex, String)>,
+    scale: f64,
+}
+
+fn amount_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\b\d{1,3}(?:,\d{3})+\.\d{2}\b|\b\d+\.\d{2}\b").expect("valid amount regex"))
+}
+
+/// `51824753556` as printed on invoices, `51 824 753 556`, or an ACN as `051 775 556`
+fn spaced(digits: &str) -> String {
+    let groups: Vec<&str> = if digits.len() == 11 {
+        vec![&digits[..2], &digits[2..5], &digits[5..8], &digits[8..]]
+    } else {
+        vec![&digits[..3], &digits[3..6], &digits[6..]]
+    };
+    groups.join(" ")
+}
+
+impl<'a> DocumentRewrite<'a> {
+    fn new(anonymizer: &'a Anonymizer, doc: &StoredDocument, names: &mut HashSet<String>) -> Self {
+        let invoice = doc.invoice.as_ref();
+        let receipt = doc.receipt.as_ref();
+        let mut replacements: Vec<(String, String)> = Vec::new();
+
+        let doc_names = [
+            doc.vendor.clone(),
+            invoice.and_then(|inv| inv.vendor_name.as_ref()).map(|f| f.value.clone()),
+            invoice.and_then(|inv| inv.bill_to_name.as_ref()).map(|f| f.value.clone()),
+            receipt.map(|rec| rec.vendor.value.clone()),
+        ];
+        for name in doc_names.into_iter().flatten().filter(|n| n.trim().len() > 1) {
+            names.insert(name.trim().to_lowercase());
+            replacements.push((regex::escape(name.trim()), anonymizer.name(&name)));
+        }
+
+        let numbers = [
+            invoice.and_then(|inv| inv.abn.as_ref()).map(|f| &f.value),
+            invoice.and_then(|inv| inv.acn.as_ref()).map(|f| &f.value),
+            invoice.and_then(|inv| inv.bill_to_abn.as_ref()).map(|f| &f.value),
+            receipt.and_then(|rec| rec.abn.as_ref()).map(|f| &f.value),
+        ];
+        for number in numbers.into_iter().flatten() {
+            let digits: String = number.chars().filter(|c| c.is_ascii_digit()).collect();
+            if digits.len() != 9 && digits.len() != 11 {
+                continue;
+            }
+            let replacement = anonymizer.number(&digits);
+            replacements.push((regex::escape(&spaced(&digits)), spaced(&replacement)));
+            replacements.push((regex::escape(&digits), replacement));
+        }
+
+        replacements.sort_by_key(|(pattern, _)| std::cmp::Reverse(pattern.len()));
+        Self {
+            anonymizer,
+            replacements: replacements
+                .into_iter()
+                .filter_map(|(pattern, replacement)| {
+                    Some((Regex::new(&format!("(?i){}", pattern)).ok()?, replacement))
+                })
+                .collect(),
+            scale: anonymizer.scale(&doc.id),
+        }
+    }
+
+    fn amount(&self, value: f64) -> f64 {
+        round_cents(value * self.scale)
+    }
+
+    fn text(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (pattern, replacement) in &self.replacements {
+            text = pattern.replace_all(&text, regex::NoExpand(replacement)).into_owned();
+        }
+        amount_pattern()
+            .replace_all(&text, |caps: &regex::Captures| {
+                let found = caps.get(0).expect("whole match");
+                let printed = found.as_str();
+                // Part of a date such as 12.03.2024
+                let dotted = text[..found.start()].ends_with('.') || text[found.end()..].starts_with('.');
+                let Ok(value) = printed.replace(',', "").parse::<f64>() else {
+                    return printed.to_string();
+                };
+                if dotted {
+                    return printed.to_string();
+                }
+                let scaled = format!("{:.2}", self.amount(value));
+                if printed.contains(',') {
+                    invoice::with_thousands(&scaled)
+                } else {
+                    scaled
+                }
+            })
+            .into_owned()
+    }
+
+    /// Rewrite a value according to the key it is stored under
+    fn value(&self, key: &str, value: &mut Value) {
+        match value {
+            Value::Array(items) => items.iter_mut().for_each(|item| self.value(key, item)),
+            Value::Object(map) if map.contains_key("value") && map.contains_key("currency") => {
+                // An amount in a currency: rewrite its value as the amount itself
+                if let Some(inner) = map.get_mut("value") {
+                    self.value(key, inner);
+                }
+            }
+            Value::Object(map) if map.contains_key("value") && map.contains_key("confidence") => {
+                // An extracted field: rewrite its value and printed text as the field itself
+                if let Some(inner) = map.get_mut("value") {
+                    self.value(key, inner);
+                }
+                if let Some(Value::String(raw)) = map.get_mut("raw") {
+                    *raw = self.text(raw);
+                }
+            }
+            Value::Object(map) => self.object(map),
+            Value::String(s) if NAME_KEYS.contains(&key) => *s = self.anonymizer.name(s),
+            Value::String(s) if NUMBER_KEYS.contains(&key) => {
+                let digits: String = s.chars().filter(|c| c.is_ascii_digit()).collect();
+                if digits.len() == 9 || digits.len() == 11 {
+                    *s = self.anonymizer.number(&digits);
+                }
+            }
+            Value::String(s) if TEXT_KEYS.contains(&key) => *s = self.text(s),
+            Value::Number(n) if MONEY_KEYS.contains(&key) => {
+                if let Some(scaled) = n.as_f64().and_then(|v| serde_json::Number::from_f64(self.amount(v))) {
+                    *n = scaled;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn object(&self, map: &mut Map<String, Value>) {
+        for (key, value) in map.iter_mut() {
+            match key.as_str() {
+                k if REMOVED_KEYS.contains(&k) => *value = Value::Null,
+                "codes" => *value = Value::Array(Vec::new()),
+                "source_path" => {
+                    if let Value::String(path) = value {
+                        let extension = Path::new(path.as_str()).extension().and_then(|e| e.to_str());
+                        *path = format!("original.{}", extension.unwrap_or("bin"));
+                    }
+                }
+                k => self.value(k, value),
+            }
+        }
+    }
+}
+
+/// Write anonymized copies of `documents` to `anonymized_documents.json` in
+/// `destination`. A random salt is used unless one is given.
+pub fn export_anonymized(
+    documents: &[StoredDocument],
+    destination: &Path,
+    salt: Option<String>,
+) -> Result<AnonymizedExport, String> {
+    fs::create_dir_all(destination).map_err(|e| format!("Failed to create directory: {}", e))?;
+    let anonymizer = Anonymizer {
+        salt: salt.filter(|s| !s.is_empty()).unwrap_or_else(|| storage::generate_id("salt")),
+    };
+
+    let mut names = HashSet::new();
+    let mut output = Vec::with_capacity(documents.len());
+    for doc in documents {
+        let rewrite = DocumentRewrite::new(&anonymizer, doc, &mut names);
+        let mut value = serde_json::to_value(doc).map_err(|e| format!("Failed to serialize document: {}", e))?;
+        if let Value::Object(map) = &mut value {
+            rewrite.object(map);
+        }
+        output.push(value);
+    }
+
+    let target = destination.join("anonymized_documents.json");
+    let json = serde_json::to_string_pretty(&output).map_err(|e| format!("Failed to serialize documents: {}", e))?;
+    fs::write(&target, json).map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
+
+    Ok(AnonymizedExport {
+        file_path: target.to_string_lossy().to_string(),
+        documents: output.len(),
+        names_replaced: names.len(),
+        salt: anonymizer.salt,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents::DocumentKind;
+    use crate::invoice::DocumentType;
+    use crate::money::Money;
+
+    #[test]
+    fn test_anonymized_documents_still_parse_the_same_way() {
+        let text = "Acme Plumbing Pty Ltd\nABN 51 824 753 556\nInvoice #A-100\nPlumbing repairs 1,000.00\n\
+            GST: $100.00\nTotal: $1,100.00";
+        let invoice = InvoiceParser::new().unwrap().parse_from_text(text, DocumentType::Pdf).unwrap();
+        let doc = StoredDocument {
+            id: "doc-1".to_string(),
+            kind: DocumentKind::Invoice,
+            vendor: Some("Acme Plumbing Pty Ltd".to_string()),
+            total: Some(Money::from_cents(110000)),
+            gst: Some(Money::from_cents(10000)),
+            source_path: Some("/home/me/Invoices/acme-april.pdf".to_string()),
+            invoice: Some(invoice),
+            ..StoredDocument::default()
+        };
+        let dir = storage::test_dir();
+
+        let first = export_anonymized(std::slice::from_ref(&doc), &dir, Some("seed".to_string())).unwrap();
+        let json = fs::read_to_string(&first.file_path).unwrap();
+        assert!(!json.contains("Acme") && !json.contains("51824753556") && !json.contains("acme-april"));
+        assert_eq!(first.names_replaced, 1);
+
+        let exported: Vec<StoredDocument> = serde_json::from_str(&json).unwrap();
+        let copy = &exported[0];
+        let vendor = copy.vendor.clone().unwrap();
+        assert!(vendor.starts_with("Business ") && vendor.ends_with("Pty Ltd"), "{}", vendor);
+        let (total, gst) = (copy.total.unwrap(), copy.gst.unwrap());
+        assert!((total - gst.times(11.0)).abs() < Money::from_cents(10), "{} {}", total, gst);
+
+        // The anonymized text parses to the anonymized fields
+        let copied = copy.invoice.as_ref().unwrap();
+        let reparsed = InvoiceParser::new().unwrap().parse_from_text(&copied.raw_text, DocumentType::Pdf).unwrap();
+        assert_eq!(reparsed.abn.unwrap().value, copied.abn.as_ref().unwrap().value);
+        assert_eq!(reparsed.total_amount.unwrap().value, copied.total_amount.as_ref().unwrap().value);
+        assert_eq!(reparsed.vendor_name.unwrap().value, vendor);
+
+        // The same salt gives the same dataset
+        let again = export_anonymized(&[doc], &dir.join("again"), Some("seed".to_string())).unwrap();
+        assert_eq!(fs::read_to_string(&again.file_path).unwrap(), json);
+    }
+}
@@ -0,0 +1,426 @@
+//! BAS Reporting Module
+//!
+//! Business Activity Statement figures derived from stored documents.
+//! Supports:
+//! - GST credit timing: documents entered after their own BAS period was
+//!   due, and the adjustment figures to claim in a later statement
+//! - Corrections to documents in lodged quarters, reported in the quarter
+//!   they were recorded for while the lodged quarter keeps its figures
+//! - Purchase labels for a quarter, with imported services and
+//!   reverse-charged GST kept apart from standard GST credits
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::documents::StoredDocument;
+use crate::gst_treatment::{self, GstTreatment};
+use crate::money::Money;
+use crate::period_locks::AdjustmentEntry;
+use crate::periods::{self, BasPeriod};
+
+/// A document whose GST credit missed its original BAS period
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LateEnteredDocument {
+    pub document_id: String,
+    pub vendor: Option<String>,
+    pub document_date: String,
+    pub entered_date: String,
+    /// BAS period the document is dated in
+    pub original_period: BasPeriod,
+    pub total: Money,
+    pub gst: Money,
+}
+
+/// Adjustments to include in one BAS statement
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PeriodAdjustment {
+    /// Statement the adjustments should be claimed in
+    pub period: BasPeriod,
+    pub period_label: String,
+    pub documents: Vec<LateEnteredDocument>,
+    /// Corrections to documents in lodged quarters
+    pub corrections: Vec<AdjustmentEntry>,
+    /// Additional purchases for label G11
+    pub purchases_adjustment: Money,
+    /// Additional GST credits for label 1B
+    pub gst_credit_adjustment: Money,
+    /// G11 adjustment in whole dollars, as entered on the statement
+    pub purchases_label: i64,
+    /// 1B adjustment in whole dollars, as entered on the statement
+    pub gst_credit_label: i64,
+}
+
+/// GST credit timing report for a financial year
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GstCreditTimingReport {
+    pub financial_year: i32,
+    pub periods: Vec<PeriodAdjustment>,
+    pub total_gst_credit_adjustment: Money,
+}
+
+/// Identify documents entered after their BAS period's lodgement due date,
+/// grouped with corrections to lodged quarters by the statement in which
+/// they should be claimed
+pub fn gst_credit_timing_report(
+    documents: &[StoredDocument],
+    corrections: &[AdjustmentEntry],
+    financial_year: i32,
+) -> GstCreditTimingReport {
+    let mut by_period: BTreeMap<BasPeriod, (Vec<LateEnteredDocument>, Vec<AdjustmentEntry>)> = BTreeMap::new();
+
+    for doc in documents {
+        let (Some(doc_date), Some(entered)) = (
+            doc.date.as_deref().and_then(periods::parse_date),
+            periods::parse_date(&doc.created_at),
+        ) else {
+            continue;
+        };
+
+        let original_period = BasPeriod::of(doc_date);
+        if entered <= original_period.lodgement_due() {
+            continue;
+        }
+
+        // Later corrections are reported through their own entries
+        let doc = as_lodged(doc, corrections, original_period);
+        let gst = gst_treatment::creditable_gst(&doc);
+        if gst <= Money::ZERO {
+            continue;
+        }
+
+        // The credit is claimed in the statement covering the entry date
+        let claim_period = BasPeriod::of(entered);
+        if claim_period.financial_year != financial_year {
+            continue;
+        }
+
+        by_period.entry(claim_period).or_default().0.push(LateEnteredDocument {
+            document_id: doc.id,
+            vendor: doc.vendor,
+            document_date: doc_date.format("%Y-%m-%d").to_string(),
+            entered_date: entered.format("%Y-%m-%d").to_string(),
+            original_period,
+            total: doc.total.unwrap_or_default(),
+            gst,
+        });
+    }
+
+    for correction in corrections {
+        if correction.adjustment_period.financial_year == financial_year {
+            by_period.entry(correction.adjustment_period).or_default().1.push(correction.clone());
+        }
+    }
+
+    let periods: Vec<PeriodAdjustment> = by_period
+        .into_iter()
+        .map(|(period, (documents, corrections))| {
+            let purchases = documents.iter().map(|d| d.total).sum::<Money>()
+                + corrections.iter().map(|c| c.purchases_delta()).sum::<Money>();
+            let gst_credits = documents.iter().map(|d| d.gst).sum::<Money>()
+                + corrections.iter().map(|c| c.gst_delta()).sum::<Money>();
+            PeriodAdjustment {
+                period,
+                period_label: period.label(),
+                purchases_adjustment: purchases,
+                gst_credit_adjustment: gst_credits,
+                purchases_label: purchases.bas_whole_dollars(),
+                gst_credit_label: gst_credits.bas_whole_dollars(),
+                documents,
+                corrections,
+            }
+        })
+        .collect();
+
+    GstCreditTimingReport {
+        financial_year,
+        total_gst_credit_adjustment: periods.iter().map(|p| p.gst_credit_adjustment).sum(),
+        periods,
+    }
+}
+
+/// Documents of one GST treatment within a quarter
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TreatmentTotal {
+    pub treatment: GstTreatment,
+    pub document_count: usize,
+    pub total: Money,
+    /// GST shown on the documents, whether or not it can be claimed
+    pub gst: Money,
+}
+
+/// Purchase-side BAS labels for one quarter
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PurchaseLabels {
+    pub period: BasPeriod,
+    pub period_label: String,
+    /// Purchases for label G11, including imported services and corrections
+    /// recorded for this quarter
+    pub purchases: Money,
+    /// GST payable under the reverse charge, included at 1A
+    pub reverse_charge_gst: Money,
+    /// GST credits for label 1B, including reverse-charged GST and
+    /// corrections recorded for this quarter
+    pub gst_credits: Money,
+    /// GST charged by offshore suppliers that can't be claimed
+    pub non_claimable_gst: Money,
+    /// G11 in whole dollars, as entered on the statement
+    pub purchases_label: i64,
+    /// Reverse charge part of 1A in whole dollars
+    pub reverse_charge_label: i64,
+    /// 1B in whole dollars, as entered on the statement
+    pub gst_credit_label: i64,
+    pub treatments: Vec<TreatmentTotal>,
+    /// Documents in another currency, left off the labels until their
+    /// amounts are converted to Australian dollars
+    #[serde(default)]
+    pub foreign_currency_documents: Vec<String>,
+    /// Corrections to lodged quarters reported in this one
+    #[serde(default)]
+    pub corrections: Vec<AdjustmentEntry>,
+}
+
+/// A document as it stood when its quarter was lodged, before the first
+/// correction recorded against it
+fn as_lodged(doc: &StoredDocument, corrections: &[AdjustmentEntry], period: BasPeriod) -> StoredDocument {
+    let mut lodged = doc.clone();
+    if let Some(first) = corrections.iter().find(|c| c.document_id == doc.id && c.original_period == period) {
+        lodged.total = Some(first.previous_total);
+        lodged.gst = Some(first.previous_gst);
+    }
+    lodged
+}
+
+/// Purchase labels for the documents dated in a quarter. Documents corrected
+/// after the quarter was lodged count at their lodged figures; corrections
+/// are added to the quarter they were recorded for instead.
+pub fn purchase_labels(
+    documents: &[StoredDocument],
+    corrections: &[AdjustmentEntry],
+    period: BasPeriod,
+) -> PurchaseLabels {
+    let in_period: Vec<StoredDocument> = documents
+        .iter()
+        .filter(|d| d.date.as_deref().and_then(periods::parse_date).is_some_and(|date| BasPeriod::of(date) == period))
+        .map(|d| as_lodged(d, corrections, period))
+        .collect();
+    let (foreign, in_period): (Vec<&StoredDocument>, Vec<&StoredDocument>) =
+        in_period.iter().partition(|d| d.is_foreign_currency());
+
+    let mut by_treatment: BTreeMap<GstTreatment, (usize, Money, Money)> = BTreeMap::new();
+    let (mut purchases, mut reverse_charge, mut credits, mut non_claimable) =
+        (Money::ZERO, Money::ZERO, Money::ZERO, Money::ZERO);
+    for doc in in_period {
+        let total = doc.total.unwrap_or_default();
+        let entry = by_treatment.entry(gst_treatment::treatment_of(doc)).or_default();
+        entry.0 += 1;
+        entry.1 += total;
+        entry.2 += doc.gst.unwrap_or_default();
+
+        purchases += total;
+        reverse_charge += gst_treatment::reverse_charge_gst(doc);
+        credits += gst_treatment::creditable_gst(doc);
+        non_claimable += gst_treatment::non_claimable_gst(doc);
+    }
+    let corrections: Vec<AdjustmentEntry> =
+        corrections.iter().filter(|c| c.adjustment_period == period).cloned().collect();
+    for correction in &corrections {
+        purchases += correction.purchases_delta();
+        credits += correction.gst_delta();
+    }
+
+    PurchaseLabels {
+        period,
+        period_label: period.label(),
+        purchases,
+        reverse_charge_gst: reverse_charge,
+        gst_credits: credits,
+        non_claimable_gst: non_claimable,
+        purchases_label: purchases.bas_whole_dollars(),
+        reverse_charge_label: reverse_charge.bas_whole_dollars(),
+        gst_credit_label: credits.bas_whole_dollars(),
+        treatments: by_treatment
+            .into_iter()
+            .map(|(treatment, (document_count, total, gst))| TreatmentTotal {
+                treatment,
+                document_count,
+                total,
+                gst,
+            })
+            .collect(),
+        foreign_currency_documents: foreign.iter().map(|d| d.id.clone()).collect(),
+        corrections,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(id: &str, date: &str, created_at: &str, gst: f64) -> StoredDocument {
+        StoredDocument {
+            id: id.to_string(),
+            date: Some(date.to_string()),
+            created_at: created_at.to_string(),
+            total: Some(Money::from_dollars(gst).times(11.0)),
+            gst: Some(Money::from_dollars(gst)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_late_entered_documents_grouped_by_claim_period() {
+        let docs = vec![
+            // Entered before the Q1 lodgement due date: claimed normally
+            doc("on-time", "2023-08-10", "2023-10-05T09:00:00+10:00", 10.0),
+            // Q1 invoice entered in November: claim in Q2
+            doc("late-q1", "2023-09-20", "2023-11-02T09:00:00+11:00", 5.0),
+            // Q2 invoice entered in April: claim in Q4
+            doc("late-q2", "2023-12-01", "2024-04-15T09:00:00+10:00", 2.5),
+        ];
+
+        let report = gst_credit_timing_report(&docs, &[], 2024);
+        assert_eq!(report.periods.len(), 2);
+        assert_eq!(report.periods[0].period, BasPeriod { financial_year: 2024, quarter: 2 });
+        assert_eq!(report.periods[0].documents[0].document_id, "late-q1");
+        assert_eq!(report.periods[0].gst_credit_adjustment, Money::from_cents(500));
+        assert_eq!(report.periods[0].purchases_label, 55);
+        assert_eq!(report.periods[1].period, BasPeriod { financial_year: 2024, quarter: 4 });
+        assert_eq!(report.total_gst_credit_adjustment, Money::from_cents(750));
+    }
+
+    #[test]
+    fn test_corrections_to_lodged_quarters_are_reported_when_recorded() {
+        let docs = vec![
+            doc("on-time", "2023-08-10", "2023-10-05T09:00:00+10:00", 10.0),
+            doc("late-q1", "2023-09-20", "2023-11-02T09:00:00+11:00", 5.0),
+        ];
+        // A credit note against a lodged Q1 document, recorded in Q2
+        let correction = AdjustmentEntry {
+            id: "adj-1".to_string(),
+            document_id: "on-time".to_string(),
+            entity_id: None,
+            vendor: None,
+            original_period: BasPeriod { financial_year: 2024, quarter: 1 },
+            adjustment_period: BasPeriod { financial_year: 2024, quarter: 2 },
+            previous_total: Money::from_cents(11000),
+            previous_gst: Money::from_cents(1000),
+            total: Money::from_cents(9900),
+            gst: Money::from_cents(900),
+            reason: "Credit note".to_string(),
+            created_at: "2023-11-20T09:00:00+11:00".to_string(),
+        };
+
+        let report = gst_credit_timing_report(&docs, &[correction], 2024);
+        assert_eq!(report.periods.len(), 1);
+        assert_eq!(report.periods[0].period, BasPeriod { financial_year: 2024, quarter: 2 });
+        assert_eq!(report.periods[0].documents[0].document_id, "late-q1");
+        assert_eq!(report.periods[0].corrections.len(), 1);
+        assert_eq!(report.periods[0].gst_credit_adjustment, Money::from_cents(400));
+        assert_eq!(report.periods[0].purchases_label, 44);
+        assert_eq!(report.total_gst_credit_adjustment, Money::from_cents(400));
+    }
+
+    #[test]
+    fn test_corrected_late_entries_are_not_counted_twice() {
+        // A Q1 invoice entered in November, then corrected from $55 to $77
+        let mut late = doc("late-q1", "2023-09-20", "2023-11-02T09:00:00+11:00", 7.0);
+        late.total = Some(Money::from_cents(7700));
+        let correction = AdjustmentEntry {
+            id: "adj-1".to_string(),
+            document_id: "late-q1".to_string(),
+            entity_id: None,
+            vendor: None,
+            original_period: BasPeriod { financial_year: 2024, quarter: 1 },
+            adjustment_period: BasPeriod { financial_year: 2024, quarter: 2 },
+            previous_total: Money::from_cents(5500),
+            previous_gst: Money::from_cents(500),
+            total: Money::from_cents(7700),
+            gst: Money::from_cents(700),
+            reason: "Wrong total".to_string(),
+            created_at: "2023-11-20T09:00:00+11:00".to_string(),
+        };
+
+        let report = gst_credit_timing_report(&[late], &[correction], 2024);
+        assert_eq!(report.periods.len(), 1);
+        let entry = &report.periods[0].documents[0];
+        assert_eq!((entry.total, entry.gst), (Money::from_cents(5500), Money::from_cents(500)));
+        assert_eq!(report.periods[0].corrections.len(), 1);
+        assert_eq!(report.periods[0].gst_credit_adjustment, Money::from_cents(700));
+        assert_eq!(report.periods[0].purchases_label, 77);
+    }
+
+    #[test]
+    fn test_purchase_labels_separate_imported_services() {
+        let mut local = doc("local", "2024-02-10", "2024-02-10T09:00:00+11:00", 10.0);
+        local.vendor = Some("Officeworks".to_string());
+        let mut imported = doc("imported", "2024-03-01", "2024-03-01T09:00:00+11:00", 0.0);
+        imported.total = Some(Money::from_cents(25000));
+        imported.gst_treatment = Some(GstTreatment::ReverseCharge);
+        let mut offshore = doc("offshore", "2024-03-05", "2024-03-05T09:00:00+11:00", 2.0);
+        offshore.gst_treatment = Some(GstTreatment::OffshoreConsumerGst);
+        let next_quarter = doc("later", "2024-04-02", "2024-04-02T09:00:00+10:00", 5.0);
+
+        let documents = [local, imported, offshore, next_quarter];
+        let labels = purchase_labels(&documents, &[], BasPeriod { financial_year: 2024, quarter: 3 });
+        assert_eq!(labels.purchases, Money::from_cents(38200));
+        assert_eq!(labels.reverse_charge_gst, Money::from_cents(2500));
+        assert_eq!(labels.gst_credits, Money::from_cents(3500));
+        assert_eq!(labels.non_claimable_gst, Money::from_cents(200));
+        assert_eq!(labels.treatments.len(), 3);
+        assert_eq!(labels.treatments[0].treatment, GstTreatment::Standard);
+    }
+
+    #[test]
+    fn test_foreign_currency_documents_are_left_off_the_labels() {
+        let local = doc("local", "2024-02-10", "2024-02-10T09:00:00+11:00", 10.0);
+        let mut overseas = doc("overseas", "2024-03-01", "2024-03-01T09:00:00+11:00", 9.0);
+        overseas.currency = Some("USD".to_string());
+        let documents = [local, overseas];
+
+        let labels = purchase_labels(&documents, &[], BasPeriod { financial_year: 2024, quarter: 3 });
+        assert_eq!((labels.purchases, labels.gst_credits), (Money::from_cents(11000), Money::from_cents(1000)));
+        assert_eq!(labels.foreign_currency_documents, vec!["overseas".to_string()]);
+
+        // Once converted, the amount counts
+        let mut converted = documents[1].clone();
+        converted.total = Some(Money::from_cents(15000));
+        converted.gst = Some(Money::from_cents(1364));
+        converted.currency = None;
+        let documents = [documents[0].clone(), converted];
+        let labels = purchase_labels(&documents, &[], BasPeriod { financial_year: 2024, quarter: 3 });
+        assert_eq!((labels.purchases, labels.gst_credits), (Money::from_cents(26000), Money::from_cents(2364)));
+        assert!(labels.foreign_currency_documents.is_empty());
+    }
+
+    #[test]
+    fn test_corrections_leave_the_lodged_quarter_alone() {
+        // Corrected from $110 to $165 after Q1 was lodged, recorded in Q2
+        let mut corrected = doc("corrected", "2023-08-10", "2023-08-10T09:00:00+10:00", 15.0);
+        corrected.total = Some(Money::from_cents(16500));
+        let correction = AdjustmentEntry {
+            id: "adj-1".to_string(),
+            document_id: "corrected".to_string(),
+            entity_id: None,
+            vendor: None,
+            original_period: BasPeriod { financial_year: 2024, quarter: 1 },
+            adjustment_period: BasPeriod { financial_year: 2024, quarter: 2 },
+            previous_total: Money::from_cents(11000),
+            previous_gst: Money::from_cents(1000),
+            total: Money::from_cents(16500),
+            gst: Money::from_cents(1500),
+            reason: "Missed second page".to_string(),
+            created_at: "2023-11-20T09:00:00+11:00".to_string(),
+        };
+        let q2 = doc("q2", "2023-11-01", "2023-11-01T09:00:00+11:00", 2.0);
+        let documents = [corrected, q2];
+        let corrections = [correction];
+
+        let q1 = purchase_labels(&documents, &corrections, BasPeriod { financial_year: 2024, quarter: 1 });
+        assert_eq!((q1.purchases, q1.gst_credits), (Money::from_cents(11000), Money::from_cents(1000)));
+        assert!(q1.corrections.is_empty());
+
+        let q2 = purchase_labels(&documents, &corrections, BasPeriod { financial_year: 2024, quarter: 2 });
+        assert_eq!((q2.purchases, q2.gst_credits), (Money::from_cents(7700), Money::from_cents(700)));
+        assert_eq!(q2.corrections.len(), 1);
+    }
+}
@@ -0,0 +1,430 @@
+//! PDF Bundle Module
+//!
+//! Merges documents into a single PDF for review or lodgement. Bundles open
+//! with a contents page and carry a navigable outline (bookmarks) grouped by
+//! category, then month, then document. Image originals are placed on their
+//! own A4 page.
+
+use image::codecs::jpeg::JpegEncoder;
+use image::DynamicImage;
+use lopdf::{dictionary, Bookmark, Document, Object, ObjectId, Stream};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::documents::StoredDocument;
+use crate::export::SkippedExport;
+use crate::imaging::{self, DecodeLimits};
+use crate::report_builder::{Align, Column, ReportBuilder};
+
+/// A4 page size in points
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const IMAGE_MARGIN: f32 = 36.0;
+
+/// Attributes a page may inherit from its ancestors in the page tree
+const INHERITED_KEYS: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// A PDF to append and its position in the outline, outermost first
+pub struct BundlePart {
+    pub pdf: Document,
+    pub outline: Vec<String>,
+}
+
+/// Result of writing a bundle
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BundleResult {
+    pub file_path: String,
+    pub document_count: usize,
+    pub page_count: usize,
+    pub skipped: Vec<SkippedExport>,
+}
+
+fn pdf_error(e: impl std::fmt::Display) -> String {
+    format!("Failed to build PDF bundle: {}", e)
+}
+
+/// Load a PDF or image file as a document ready for merging
+pub fn load_part(path: &Path) -> Result<Document, String> {
+    let is_pdf = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("pdf"));
+    if is_pdf {
+        return Document::load(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e));
+    }
+    let image = imaging::load_image(path, &DecodeLimits::default())?;
+    image_page(&image)
+}
+
+/// A single A4 page showing an image scaled to fit
+pub fn image_page(image: &DynamicImage) -> Result<Document, String> {
+    let rgb = image.to_rgb8();
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, 85)
+        .encode_image(&rgb)
+        .map_err(pdf_error)?;
+
+    let (width, height) = (rgb.width() as f32, rgb.height() as f32);
+    let scale = ((PAGE_WIDTH - 2.0 * IMAGE_MARGIN) / width)
+        .min((PAGE_HEIGHT - 2.0 * IMAGE_MARGIN) / height)
+        .min(1.0);
+    let (drawn_width, drawn_height) = (width * scale, height * scale);
+    let x = (PAGE_WIDTH - drawn_width) / 2.0;
+    let y = PAGE_HEIGHT - IMAGE_MARGIN - drawn_height;
+
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let image_id = doc.add_object(Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => rgb.width() as i64,
+            "Height" => rgb.height() as i64,
+            "ColorSpace" => "DeviceRGB",
+            "BitsPerComponent" => 8,
+            "Filter" => "DCTDecode",
+        },
+        jpeg,
+    ));
+    let content = format!(
+        "q {:.2} 0 0 {:.2} {:.2} {:.2} cm /Im1 Do Q",
+        drawn_width, drawn_height, x, y
+    );
+    let content_id = doc.add_object(Stream::new(dictionary! {}, content.into_bytes()));
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "Contents" => content_id,
+        "Resources" => dictionary! { "XObject" => dictionary! { "Im1" => image_id } },
+        "MediaBox" => vec![0.into(), 0.into(), PAGE_WIDTH.into(), PAGE_HEIGHT.into()],
+    });
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+        }),
+    );
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog_id);
+    Ok(doc)
+}
+
+/// Copy attributes a page inherits from its parents onto the page itself,
+/// since merging flattens the page tree
+fn inline_inherited_attributes(doc: &mut Document, page_id: ObjectId) {
+    let mut inherited = Vec::new();
+    let mut parent = doc
+        .get_dictionary(page_id)
+        .ok()
+        .and_then(|page| page.get(b"Parent").ok())
+        .and_then(|p| p.as_reference().ok());
+    let mut depth = 0;
+    while let Some(id) = parent {
+        let Ok(node) = doc.get_dictionary(id) else { break };
+        for key in INHERITED_KEYS {
+            if let Ok(value) = node.get(key) {
+                inherited.push((key, value.clone()));
+            }
+        }
+        parent = node.get(b"Parent").ok().and_then(|p| p.as_reference().ok());
+        depth += 1;
+        if depth > 32 {
+            break;
+        }
+    }
+
+    if let Ok(page) = doc.get_dictionary_mut(page_id) {
+        // Nearest ancestor wins, so only fill keys that are still missing
+        for (key, value) in inherited {
+            if !page.has(key) {
+                page.set(key.to_vec(), value);
+            }
+        }
+    }
+}
+
+/// Merge parts in order, building an outline from each part's path.
+/// Consecutive parts sharing a path prefix share those outline entries.
+pub fn merge_parts(parts: Vec<BundlePart>) -> Result<Document, String> {
+    if parts.is_empty() {
+        return Err("No documents to bundle".to_string());
+    }
+
+    let mut merged = Document::with_version("1.5");
+    let mut max_id = 1;
+    let mut pages = BTreeMap::new();
+    let mut objects = BTreeMap::new();
+    // Outline path of the previous part with the bookmark IDs along it
+    let mut open: Vec<(String, u32)> = Vec::new();
+
+    for part in parts {
+        let mut doc = part.pdf;
+        doc.renumber_objects_with(max_id);
+        max_id = doc.max_id + 1;
+
+        let page_ids: Vec<ObjectId> = doc.get_pages().into_values().collect();
+        for &page_id in &page_ids {
+            inline_inherited_attributes(&mut doc, page_id);
+            if let Ok(page) = doc.get_object(page_id) {
+                pages.insert(page_id, page.clone());
+            }
+        }
+        objects.extend(doc.objects);
+
+        let Some(&first_page) = page_ids.first() else {
+            continue;
+        };
+        let shared = open
+            .iter()
+            .zip(&part.outline)
+            .take_while(|((title, _), next)| title == *next)
+            .count()
+            // The last level is always a new entry
+            .min(part.outline.len().saturating_sub(1));
+        open.truncate(shared);
+        for (depth, title) in part.outline.iter().enumerate().skip(shared) {
+            let is_group = depth + 1 < part.outline.len();
+            let parent = open.last().map(|(_, id)| *id);
+            let bookmark = Bookmark::new(title.clone(), [0.0, 0.0, 0.0], if is_group { 2 } else { 0 }, first_page);
+            open.push((title.clone(), merged.add_bookmark(bookmark, parent)));
+        }
+    }
+
+    let mut catalog: Option<(ObjectId, Object)> = None;
+    let mut pages_root: Option<(ObjectId, Object)> = None;
+    for (id, object) in objects {
+        match object.type_name().unwrap_or("") {
+            "Catalog" => {
+                if catalog.is_none() {
+                    catalog = Some((id, object));
+                }
+            }
+            "Pages" => {
+                if pages_root.is_none() {
+                    pages_root = Some((id, object));
+                }
+            }
+            // Pages are added below; source outlines are replaced by ours
+            "Page" | "Outlines" | "Outline" => {}
+            _ => {
+                merged.objects.insert(id, object);
+            }
+        }
+    }
+    let (catalog_id, catalog) = catalog.ok_or_else(|| pdf_error("no document catalog"))?;
+    let (pages_id, pages_root) = pages_root.ok_or_else(|| pdf_error("no page tree"))?;
+
+    let page_count = pages.len();
+    let mut kids = Vec::with_capacity(page_count);
+    for (id, page) in pages {
+        if let Object::Dictionary(mut page) = page {
+            page.set("Parent", pages_id);
+            merged.objects.insert(id, Object::Dictionary(page));
+            kids.push(Object::Reference(id));
+        }
+    }
+
+    let mut pages_root = pages_root.as_dict().map_err(pdf_error)?.clone();
+    pages_root.set("Count", page_count as u32);
+    pages_root.set("Kids", kids);
+    for key in INHERITED_KEYS {
+        pages_root.remove(key);
+    }
+    pages_root.remove(b"Parent");
+    merged.objects.insert(pages_id, Object::Dictionary(pages_root));
+
+    let mut catalog = catalog.as_dict().map_err(pdf_error)?.clone();
+    catalog.set("Pages", pages_id);
+    catalog.set("PageMode", "UseOutlines");
+    catalog.remove(b"Outlines");
+    merged.objects.insert(catalog_id, Object::Dictionary(catalog));
+    merged.trailer.set("Root", catalog_id);
+
+    merged.max_id = merged.objects.keys().map(|(id, _)| *id).max().unwrap_or(0);
+    merged.renumber_objects();
+    merged.adjust_zero_pages();
+    if let Some(outline_id) = merged.build_outline() {
+        if let Ok(catalog) = merged.get_dictionary_mut(catalog_id) {
+            catalog.set("Outlines", Object::Reference(outline_id));
+        }
+    }
+    Ok(merged)
+}
+
+fn bundle_outline(doc: &StoredDocument) -> Vec<String> {
+    let category = doc.category.clone().unwrap_or_else(|| "Uncategorised".to_string());
+    let month = doc
+        .date
+        .as_deref()
+        .and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+        .map(|d| d.format("%B %Y").to_string())
+        .unwrap_or_else(|| "Undated".to_string());
+    let mut title = format!(
+        "{} {}",
+        doc.date.as_deref().unwrap_or(""),
+        doc.vendor.as_deref().unwrap_or("Unknown vendor")
+    );
+    if let Some(total) = doc.total {
+        title.push_str(&format!(" ${}", total));
+    }
+    vec![category, month, title.trim().to_string()]
+}
+
+fn contents_page(rows: &[Vec<String>]) -> Vec<u8> {
+    let mut builder = ReportBuilder::new("Document bundle");
+    builder.heading("Contents").space(6.0).table(
+        &[
+            Column::new("Category", 130.0, Align::Left),
+            Column::new("Month", 90.0, Align::Left),
+            Column::new("Document", 230.0, Align::Left),
+            Column::new("Page", 45.0, Align::Right),
+        ],
+        rows,
+    );
+    builder.build()
+}
+
+/// Merge stored documents into one PDF, ordered by category then date,
+/// with a contents page and a bookmark outline
+pub fn create_bundle(documents: &[StoredDocument], output_path: &Path) -> Result<BundleResult, String> {
+    let mut ordered: Vec<&StoredDocument> = documents.iter().collect();
+    ordered.sort_by_key(|doc| (doc.category.is_none(), doc.category.clone(), doc.date.clone()));
+
+    let mut skipped = Vec::new();
+    let mut loaded: Vec<(Vec<String>, Document)> = Vec::new();
+    for doc in ordered {
+        let Some(source) = doc.source_path.as_deref() else {
+            skipped.push(SkippedExport {
+                document_id: doc.id.clone(),
+                reason: "No original file".to_string(),
+            });
+            continue;
+        };
+        match load_part(Path::new(source)) {
+            Ok(pdf) => loaded.push((bundle_outline(doc), pdf)),
+            Err(reason) => skipped.push(SkippedExport {
+                document_id: doc.id.clone(),
+                reason,
+            }),
+        }
+    }
+    if loaded.is_empty() {
+        return Err("None of the selected documents could be bundled".to_string());
+    }
+
+    // Page numbers depend on how long the contents are, so lay them out
+    // once to measure and again with the final numbers
+    let rows_for = |offset: usize| -> Vec<Vec<String>> {
+        let mut page = offset + 1;
+        loaded
+            .iter()
+            .map(|(outline, pdf)| {
+                let row = vec![outline[0].clone(), outline[1].clone(), outline[2].clone(), page.to_string()];
+                page += pdf.get_pages().len();
+                row
+            })
+            .collect()
+    };
+    let draft = Document::load_mem(&contents_page(&rows_for(1))).map_err(pdf_error)?;
+    let contents_pages = draft.get_pages().len();
+    let contents = Document::load_mem(&contents_page(&rows_for(contents_pages))).map_err(pdf_error)?;
+
+    let document_count = loaded.len();
+    let parts = std::iter::once(BundlePart {
+        pdf: contents,
+        outline: vec!["Contents".to_string()],
+    })
+    .chain(loaded.into_iter().map(|(outline, pdf)| BundlePart { pdf, outline }))
+    .collect();
+
+    let mut merged = merge_parts(parts)?;
+    let page_count = merged.get_pages().len();
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    merged
+        .save(output_path)
+        .map_err(|e| format!("Failed to write bundle: {}", e))?;
+
+    Ok(BundleResult {
+        file_path: output_path.to_string_lossy().to_string(),
+        document_count,
+        page_count,
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage;
+
+    fn outline_titles(doc: &Document, first: ObjectId, depth: usize, out: &mut Vec<String>) {
+        let mut next = Some(first);
+        while let Some(id) = next {
+            let item = doc.get_dictionary(id).unwrap();
+            let title = item.get(b"Title").unwrap().as_str().unwrap();
+            out.push(format!("{}{}", "-".repeat(depth), String::from_utf8_lossy(title)));
+            if let Ok(child) = item.get(b"First").and_then(|c| c.as_reference()) {
+                outline_titles(doc, child, depth + 1, out);
+            }
+            next = item.get(b"Next").and_then(|n| n.as_reference()).ok();
+        }
+    }
+
+    #[test]
+    fn test_bundle_outline_groups_by_category_and_month() {
+        let dir = storage::test_dir();
+
+        let mut docs = Vec::new();
+        for (i, (category, date)) in [("Office", "2024-02-01"), ("Office", "2024-02-20"), ("Travel", "2024-03-05")]
+            .iter()
+            .enumerate()
+        {
+            let path = dir.join(format!("{}.png", i));
+            DynamicImage::new_rgb8(40, 60).save(&path).unwrap();
+            docs.push(StoredDocument {
+                id: i.to_string(),
+                category: Some(category.to_string()),
+                date: Some(date.to_string()),
+                vendor: Some(format!("Vendor {}", i)),
+                source_path: Some(path.to_string_lossy().to_string()),
+                ..Default::default()
+            });
+        }
+        docs.push(StoredDocument {
+            id: "missing".to_string(),
+            ..Default::default()
+        });
+
+        let output = dir.join("bundle.pdf");
+        let result = create_bundle(&docs, &output).unwrap();
+        assert_eq!(result.document_count, 3);
+        assert_eq!(result.page_count, 4);
+        assert_eq!(result.skipped.len(), 1);
+
+        let pdf = Document::load(&output).unwrap();
+        let catalog = pdf.catalog().unwrap();
+        let outlines = pdf
+            .get_dictionary(catalog.get(b"Outlines").unwrap().as_reference().unwrap())
+            .unwrap();
+        let mut titles = Vec::new();
+        outline_titles(&pdf, outlines.get(b"First").unwrap().as_reference().unwrap(), 0, &mut titles);
+        assert_eq!(
+            titles,
+            vec![
+                "Contents",
+                "Office",
+                "-February 2024",
+                "--2024-02-01 Vendor 0",
+                "--2024-02-20 Vendor 1",
+                "Travel",
+                "-March 2024",
+                "--2024-03-05 Vendor 2",
+            ]
+        );
+    }
+}
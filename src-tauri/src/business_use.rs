@@ -0,0 +1,221 @@
+//! Business Use Module
+//!
+//! Splits a document's total into its deductible and private shares when it
+//! falls in a partly deductible category, such as fuel for a car that is
+//! also driven privately. The document store runs every save through
+//! `ImportDefaults`: new and re-extracted documents without a category take
+//! one from the category rules or the entity's packs, and the split is
+//! worked out from the category's business-use default straight away, so
+//! review screens show the net deduction rather than the gross amount.
+
+use serde::{Deserialize, Serialize};
+
+use crate::category_packs::{self, CategorySuggestion};
+use crate::category_rules::{self, CategoryRule};
+use crate::documents::StoredDocument;
+use crate::entities::{self, Entity};
+use crate::money::Money;
+use crate::settings;
+
+/// A total divided by the business-use share of its category
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DeductionSplit {
+    /// Category the share belongs to; the split is dropped if the document
+    /// moves to another
+    pub category: String,
+    /// 0-100
+    pub business_use_percent: f64,
+    pub deductible: Money,
+    pub non_deductible: Money,
+}
+
+impl DeductionSplit {
+    /// Split `total` by `percent`; `None` for a fully deductible category
+    pub fn new(category: &str, percent: f64, total: Option<Money>) -> Option<Self> {
+        if percent >= 100.0 {
+            return None;
+        }
+        let total = total.unwrap_or_default();
+        let deductible = total.times(percent.max(0.0) / 100.0);
+        Some(Self {
+            category: category.to_string(),
+            business_use_percent: percent,
+            deductible,
+            non_deductible: total - deductible,
+        })
+    }
+}
+
+/// Category rules and entities used to categorise documents as they are saved
+#[derive(Default)]
+pub struct ImportDefaults {
+    rules: Vec<CategoryRule>,
+    entities: Vec<Entity>,
+    active_entity_id: Option<String>,
+}
+
+impl ImportDefaults {
+    pub fn new(rules: Vec<CategoryRule>, entities: Vec<Entity>, active_entity_id: Option<String>) -> Self {
+        Self {
+            rules,
+            entities,
+            active_entity_id,
+        }
+    }
+
+    /// Current rules, entities and active entity
+    pub fn load() -> Result<Self, String> {
+        Ok(Self::new(
+            category_rules::with_rules(|store| Ok(store.list()))?,
+            entities::with_entities(|store| Ok(store.list()))?,
+            settings::load_settings()?.active_entity_id,
+        ))
+    }
+
+    fn entity(&self, doc: &StoredDocument) -> Option<&Entity> {
+        let entity_id = doc.entity_id.as_ref().or(self.active_entity_id.as_ref())?;
+        self.entities.iter().find(|e| &e.id == entity_id)
+    }
+
+    fn suggest(&self, doc: &StoredDocument) -> Option<CategorySuggestion> {
+        let entity = self.entity(doc);
+        match category_rules::evaluate(&self.rules, doc, entity.map(|e| e.id.as_str())) {
+            Some(rule_match) => Some(category_rules::suggestion(&rule_match, entity)),
+            None => entity.and_then(|entity| category_packs::suggest_category(entity, doc)),
+        }
+    }
+
+    /// Business-use share of a category for a document. A rule's own share
+    /// wins over the category's default in the entity's packs.
+    fn business_use_percent(&self, doc: &StoredDocument, category: &str) -> f64 {
+        self.suggest(doc)
+            .filter(|s| s.category == category)
+            .map(|s| s.business_use_percent)
+            .or_else(|| {
+                let categories = category_packs::entity_categories(self.entity(doc)?);
+                categories.into_iter().find(|c| c.name == category).map(|c| c.business_use_percent)
+            })
+            .unwrap_or(100.0)
+    }
+
+    /// Work the split out again as a document is saved over `previous`, or
+    /// saved for the first time. Amounts follow the total and a share set
+    /// for the category is kept; a new category takes its own default.
+    pub fn refresh(&self, doc: &mut StoredDocument, previous: Option<&StoredDocument>) {
+        let Some(category) = doc.category.clone() else {
+            doc.deduction = None;
+            return;
+        };
+        let changed = previous.map_or(true, |previous| previous.category.as_ref() != Some(&category));
+        let percent = match doc.deduction.take() {
+            Some(split) if split.category == category => Some(split.business_use_percent),
+            Some(_) => Some(self.business_use_percent(doc, &category)),
+            None => changed.then(|| self.business_use_percent(doc, &category)),
+        };
+        doc.deduction = percent.and_then(|percent| DeductionSplit::new(&category, percent, doc.total));
+    }
+
+    /// Prepare a document being saved over `previous`. One that is new,
+    /// finishing a quick capture or freshly re-extracted is given a suggested
+    /// category if it has none; then its split is refreshed.
+    pub fn apply(&self, doc: &mut StoredDocument, previous: Option<&StoredDocument>) {
+        let extracted = previous.map_or(true, |previous| previous.provisional || previous.ocr_text() != doc.ocr_text());
+        if extracted && doc.category.is_none() {
+            doc.category = self.suggest(doc).map(|s| s.category);
+        }
+        self.refresh(doc, previous);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents::DocumentStore;
+
+    #[test]
+    fn test_imports_are_split_by_category_defaults() {
+        let entity = Entity {
+            id: "driver".to_string(),
+            category_packs: vec!["rideshare".to_string()],
+            ..Default::default()
+        };
+        let defaults = ImportDefaults::new(Vec::new(), vec![entity], Some("driver".to_string()));
+        let mut fuel = StoredDocument {
+            vendor: Some("Shell Coles Express".to_string()),
+            total: Some(Money::from_cents(8005)),
+            ..Default::default()
+        };
+        defaults.apply(&mut fuel, None);
+        assert_eq!(fuel.category.as_deref(), Some("Fuel"));
+        let split = fuel.deduction.clone().unwrap();
+        assert_eq!((split.deductible, split.non_deductible), (Money::from_cents(6404), Money::from_cents(1601)));
+
+        // A category the user already chose keeps its pack default
+        let mut phone = StoredDocument {
+            vendor: Some("Telstra".to_string()),
+            category: Some("Mobile phone and data".to_string()),
+            total: Some(Money::from_cents(9900)),
+            ..Default::default()
+        };
+        defaults.apply(&mut phone, None);
+        assert_eq!(phone.deduction.as_ref().unwrap().deductible, Money::from_cents(4950));
+
+        let mut uber = StoredDocument {
+            vendor: Some("Uber".to_string()),
+            total: Some(Money::from_cents(1250)),
+            ..Default::default()
+        };
+        defaults.apply(&mut uber, None);
+        assert_eq!((uber.category.as_deref(), &uber.deduction), (Some("Platform service fees"), &None));
+
+        // Edits after import keep the split in step
+        let before = fuel.clone();
+        fuel.total = Some(Money::from_cents(10000));
+        defaults.refresh(&mut fuel, Some(&before));
+        assert_eq!(fuel.deduction.as_ref().unwrap().deductible, Money::from_cents(8000));
+        let before = fuel.clone();
+        fuel.category = Some("Tolls".to_string());
+        defaults.refresh(&mut fuel, Some(&before));
+        assert!(fuel.deduction.is_none());
+
+        // A new category takes its own default rather than losing the split
+        let before = fuel.clone();
+        fuel.category = Some("Mobile phone and data".to_string());
+        defaults.refresh(&mut fuel, Some(&before));
+        assert_eq!(fuel.deduction.as_ref().unwrap().deductible, Money::from_cents(5000));
+
+        // Saving again without a split, as after the user removed it, keeps it off
+        let before = fuel.clone();
+        fuel.deduction = None;
+        defaults.refresh(&mut fuel, Some(&before));
+        assert!(fuel.deduction.is_none());
+    }
+
+    #[test]
+    fn test_store_categorises_documents_as_they_are_saved() {
+        let dir = crate::storage::test_dir();
+        let entity = Entity {
+            id: "driver".to_string(),
+            category_packs: vec!["rideshare".to_string()],
+            ..Default::default()
+        };
+        let defaults = ImportDefaults::new(Vec::new(), vec![entity], Some("driver".to_string()));
+        let mut store = DocumentStore::open(&dir.join("documents.json")).unwrap().with_import_defaults(defaults);
+
+        // A parsed receipt saved from the review screen, as after any import
+        let saved = store.upsert(StoredDocument {
+            vendor: Some("Shell Coles Express".to_string()),
+            total: Some(Money::from_cents(8005)),
+            ..Default::default()
+        });
+        assert_eq!(saved.category.as_deref(), Some("Fuel"));
+        assert_eq!(saved.deduction.as_ref().unwrap().deductible, Money::from_cents(6404));
+
+        // Clearing the category by hand isn't undone on the next save
+        let cleared = store.upsert(StoredDocument {
+            category: None,
+            ..saved
+        });
+        assert_eq!((cleared.category, cleared.deduction), (None, None));
+    }
+}
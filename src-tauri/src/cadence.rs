@@ -0,0 +1,260 @@
+//! Billing Cadence Module
+//!
+//! Learns each supplier's billing cadence from stored invoices and flags
+//! vendors whose next expected invoice has not arrived within a grace window,
+//! so bills lost in spam are caught before they become overdue.
+
+use chrono::{Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::documents::{DocumentKind, StoredDocument};
+use crate::money::Money;
+
+/// Minimum number of invoices needed before a cadence is predicted
+const MIN_HISTORY: usize = 3;
+
+/// Fraction an interval may deviate from the cadence and still count as regular
+const INTERVAL_TOLERANCE: f64 = 0.2;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum BillingCadence {
+    Weekly,
+    Fortnightly,
+    Monthly,
+    Bimonthly,
+    Quarterly,
+    HalfYearly,
+    Annual,
+}
+
+impl BillingCadence {
+    const ALL: [BillingCadence; 7] = [
+        BillingCadence::Weekly,
+        BillingCadence::Fortnightly,
+        BillingCadence::Monthly,
+        BillingCadence::Bimonthly,
+        BillingCadence::Quarterly,
+        BillingCadence::HalfYearly,
+        BillingCadence::Annual,
+    ];
+
+    /// Typical number of days between invoices
+    pub fn nominal_days(&self) -> f64 {
+        match self {
+            BillingCadence::Weekly => 7.0,
+            BillingCadence::Fortnightly => 14.0,
+            BillingCadence::Monthly => 30.4,
+            BillingCadence::Bimonthly => 60.9,
+            BillingCadence::Quarterly => 91.3,
+            BillingCadence::HalfYearly => 182.6,
+            BillingCadence::Annual => 365.25,
+        }
+    }
+
+    /// Match an interval in days to the closest cadence within tolerance
+    fn from_interval(days: f64) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|c| (days - c.nominal_days()).abs() <= c.nominal_days() * INTERVAL_TOLERANCE)
+    }
+
+    /// Date of the invoice expected after `last`
+    fn next_after(&self, last: NaiveDate) -> Option<NaiveDate> {
+        let months = match self {
+            BillingCadence::Weekly => return last.checked_add_days(chrono::Days::new(7)),
+            BillingCadence::Fortnightly => return last.checked_add_days(chrono::Days::new(14)),
+            BillingCadence::Monthly => 1,
+            BillingCadence::Bimonthly => 2,
+            BillingCadence::Quarterly => 3,
+            BillingCadence::HalfYearly => 6,
+            BillingCadence::Annual => 12,
+        };
+        last.checked_add_months(Months::new(months))
+    }
+}
+
+/// Display name and dated totals for one vendor
+type VendorHistory = (String, Vec<(NaiveDate, Option<f64>)>);
+
+/// Learned billing pattern for a vendor
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VendorCadence {
+    pub vendor: String,
+    pub cadence: BillingCadence,
+    /// Share of observed intervals that match the cadence (0.0 - 1.0)
+    pub regularity: f64,
+    pub invoice_count: usize,
+    pub last_invoice_date: String,
+    pub next_expected_date: String,
+    /// Median invoice total
+    pub typical_amount: Option<f64>,
+}
+
+/// An expected invoice that has not arrived
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MissingInvoiceAlert {
+    pub vendor: String,
+    pub cadence: BillingCadence,
+    pub last_invoice_date: String,
+    pub expected_date: String,
+    /// Days past the expected date, beyond the grace window
+    pub days_overdue: i64,
+    pub typical_amount: Option<f64>,
+}
+
+/// Learn billing cadences for every vendor with enough invoice history
+pub fn predict_cadences(documents: &[StoredDocument]) -> Vec<VendorCadence> {
+    let mut by_vendor: BTreeMap<String, VendorHistory> = BTreeMap::new();
+
+    for doc in documents.iter().filter(|d| d.kind == DocumentKind::Invoice) {
+        let (Some(vendor), Some(date)) = (doc.vendor.as_deref(), doc.date.as_deref()) else {
+            continue;
+        };
+        let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") else {
+            continue;
+        };
+        by_vendor
+            .entry(vendor.trim().to_lowercase())
+            .or_insert_with(|| (vendor.trim().to_string(), Vec::new()))
+            .1
+            .push((date, doc.total.map(Money::to_dollars)));
+    }
+
+    let mut cadences = Vec::new();
+    for (_, (vendor, mut invoices)) in by_vendor {
+        invoices.sort_by_key(|(date, _)| *date);
+        invoices.dedup_by_key(|(date, _)| *date);
+        if invoices.len() < MIN_HISTORY {
+            continue;
+        }
+
+        let intervals: Vec<f64> = invoices
+            .windows(2)
+            .map(|w| (w[1].0 - w[0].0).num_days() as f64)
+            .collect();
+        let Some(cadence) = BillingCadence::from_interval(median(&intervals)) else {
+            continue;
+        };
+
+        let nominal = cadence.nominal_days();
+        let regular = intervals
+            .iter()
+            .filter(|&&d| (d - nominal).abs() <= nominal * INTERVAL_TOLERANCE)
+            .count();
+        let last = invoices.last().map(|(date, _)| *date).unwrap();
+        let Some(next) = cadence.next_after(last) else {
+            continue;
+        };
+        let amounts: Vec<f64> = invoices.iter().filter_map(|(_, total)| *total).collect();
+
+        cadences.push(VendorCadence {
+            vendor,
+            cadence,
+            regularity: regular as f64 / intervals.len() as f64,
+            invoice_count: invoices.len(),
+            last_invoice_date: last.format("%Y-%m-%d").to_string(),
+            next_expected_date: next.format("%Y-%m-%d").to_string(),
+            typical_amount: if amounts.is_empty() { None } else { Some(median(&amounts)) },
+        });
+    }
+
+    cadences
+}
+
+/// Find vendors whose expected invoice is overdue by more than `grace_days`
+pub fn missing_invoice_alerts(
+    documents: &[StoredDocument],
+    today: NaiveDate,
+    grace_days: i64,
+) -> Vec<MissingInvoiceAlert> {
+    let mut alerts: Vec<MissingInvoiceAlert> = predict_cadences(documents)
+        .into_iter()
+        // Irregular billers produce too many false alarms
+        .filter(|c| c.regularity >= 0.5)
+        .filter_map(|c| {
+            let expected = NaiveDate::parse_from_str(&c.next_expected_date, "%Y-%m-%d").ok()?;
+            let days_overdue = (today - expected).num_days() - grace_days;
+            (days_overdue > 0).then_some(MissingInvoiceAlert {
+                vendor: c.vendor,
+                cadence: c.cadence,
+                last_invoice_date: c.last_invoice_date,
+                expected_date: c.next_expected_date,
+                days_overdue,
+                typical_amount: c.typical_amount,
+            })
+        })
+        .collect();
+
+    alerts.sort_by_key(|a| std::cmp::Reverse(a.days_overdue));
+    alerts
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invoice(vendor: &str, date: &str, total: f64) -> StoredDocument {
+        StoredDocument {
+            kind: DocumentKind::Invoice,
+            vendor: Some(vendor.to_string()),
+            date: Some(date.to_string()),
+            total: Some(Money::from_dollars(total)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_predicts_monthly_and_quarterly_cadences() {
+        let docs = vec![
+            invoice("Telstra", "2024-01-05", 89.0),
+            invoice("Telstra", "2024-02-05", 89.0),
+            invoice("Telstra", "2024-03-06", 95.0),
+            invoice("City Council", "2023-07-20", 410.0),
+            invoice("City Council", "2023-10-19", 410.0),
+            invoice("City Council", "2024-01-18", 410.0),
+            invoice("One-off Plumber", "2024-02-01", 250.0),
+        ];
+
+        let cadences = predict_cadences(&docs);
+        assert_eq!(cadences.len(), 2);
+
+        let telstra = cadences.iter().find(|c| c.vendor == "Telstra").unwrap();
+        assert_eq!(telstra.cadence, BillingCadence::Monthly);
+        assert_eq!(telstra.next_expected_date, "2024-04-06");
+        assert_eq!(telstra.typical_amount, Some(89.0));
+
+        let council = cadences.iter().find(|c| c.vendor == "City Council").unwrap();
+        assert_eq!(council.cadence, BillingCadence::Quarterly);
+    }
+
+    #[test]
+    fn test_missing_invoice_alert_respects_grace_window() {
+        let docs = vec![
+            invoice("Telstra", "2024-01-05", 89.0),
+            invoice("Telstra", "2024-02-05", 89.0),
+            invoice("Telstra", "2024-03-05", 89.0),
+        ];
+
+        let within_grace = NaiveDate::from_ymd_opt(2024, 4, 10).unwrap();
+        assert!(missing_invoice_alerts(&docs, within_grace, 7).is_empty());
+
+        let overdue = NaiveDate::from_ymd_opt(2024, 4, 20).unwrap();
+        let alerts = missing_invoice_alerts(&docs, overdue, 7);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].expected_date, "2024-04-05");
+        assert_eq!(alerts[0].days_overdue, 8);
+    }
+}
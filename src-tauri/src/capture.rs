@@ -0,0 +1,245 @@
+//! Quick Capture Module
+//!
+//! Two-phase receipt capture for mobile. The photo is copied into the data
+//! directory and saved as a provisional document straight away, so the
+//! user can keep scanning. OCR then runs on a background worker, one
+//! capture at a time, and patches the document when it finishes. Photos
+//! taken sideways are turned upright in the archive once OCR has found
+//! their orientation. Extracted captures are categorised and split by their
+//! category's business-use default before anyone reviews them.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::OnceLock;
+use std::thread;
+use tauri::{AppHandle, Emitter};
+
+use crate::capture_metadata;
+use crate::documents::{self, DocumentKind, DocumentStore, DocumentSummary, Extraction, StoredDocument};
+use crate::ocr;
+use crate::settings;
+use crate::storage;
+use crate::straight_through;
+
+/// Event emitted when a capture's extraction finishes or fails
+pub const CAPTURE_EVENT: &str = "capture://completed";
+
+/// Returned as soon as the photo is stored
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QuickCaptureResult {
+    pub document_id: String,
+    /// Copy of the photo in the data directory
+    pub source_path: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureStatus {
+    Completed,
+    Failed,
+}
+
+/// Payload of `CAPTURE_EVENT`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CaptureEvent {
+    pub document_id: String,
+    pub status: CaptureStatus,
+    pub error: Option<String>,
+    /// The patched document; missing if it was deleted while processing
+    pub document: Option<DocumentSummary>,
+}
+
+/// Directory captured photos are kept in
+pub fn captures_directory() -> Result<PathBuf, String> {
+    let dir = storage::get_data_directory()?.join("captures");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create captures directory: {}", e))?;
+    Ok(dir)
+}
+
+/// Copy a photo into the captures directory under a fresh name
+fn store_photo(image_path: &Path, dir: &Path) -> Result<PathBuf, String> {
+    let extension = image_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_else(|| "jpg".to_string());
+    let target = dir.join(format!("{}.{}", storage::generate_id("capture"), extension));
+    std::fs::copy(image_path, &target).map_err(|e| format!("Failed to store {}: {}", image_path.display(), e))?;
+    Ok(target)
+}
+
+/// A receipt waiting for extraction
+pub fn provisional_document(source_path: &Path, entity_id: Option<String>) -> StoredDocument {
+    StoredDocument {
+        kind: DocumentKind::Receipt,
+        source_path: Some(source_path.to_string_lossy().to_string()),
+        entity_id,
+        provisional: true,
+        capture: capture_metadata::read_capture_metadata(source_path),
+        ..Default::default()
+    }
+}
+
+/// Patch a provisional document with its extraction result. A failed
+/// extraction still clears the provisional flag so the receipt can be
+/// entered by hand.
+pub fn finish_capture(
+    store: &mut DocumentStore,
+    document_id: &str,
+    extraction: Result<Extraction, String>,
+) -> Option<StoredDocument> {
+    let mut document = store.get(document_id).cloned()?;
+    if let Ok(extraction) = extraction {
+        document.apply_extraction(extraction);
+    }
+    document.provisional = false;
+    Some(store.upsert(document))
+}
+
+/// Run extraction for a stored capture and save the result
+fn process_capture(document_id: &str) -> Result<(Option<StoredDocument>, Option<String>), String> {
+    let Some(document) = documents::with_store(|store| Ok(store.get(document_id).cloned()))? else {
+        return Ok((None, None));
+    };
+    let mut extraction = documents::extract_from_source(&document);
+    if let (Ok(Extraction::Receipt(receipt)), Some(source)) = (&mut extraction, document.source_path.as_deref()) {
+        if let Err(e) = ocr::persist_orientation(Path::new(source), receipt) {
+            log::warn!("Failed to store upright copy of {}: {}", source, e);
+        }
+    }
+    let error = extraction.as_ref().err().cloned();
+    let saved = documents::with_store(|store| {
+        let saved = finish_capture(store, document_id, extraction);
+        store.save()?;
+        Ok(saved)
+    })?;
+    let saved = match (saved, &error) {
+        (Some(document), None) => Some(auto_process(document)),
+        (saved, _) => saved,
+    };
+    Ok((saved, error))
+}
+
+/// Accept a capture without review when straight-through processing allows it
+fn auto_process(document: StoredDocument) -> StoredDocument {
+    match straight_through::run(std::slice::from_ref(&document.id)) {
+        Ok(items) if !items.is_empty() => documents::with_store(|store| Ok(store.get(&document.id).cloned()))
+            .ok()
+            .flatten()
+            .unwrap_or(document),
+        Ok(_) => document,
+        Err(e) => {
+            log::warn!("Straight-through processing of {} failed: {}", document.id, e);
+            document
+        }
+    }
+}
+
+fn run_job(app: &AppHandle, document_id: String) {
+    let (document, error) = match process_capture(&document_id) {
+        Ok(result) => result,
+        Err(e) => (None, Some(e)),
+    };
+    if let Some(e) = &error {
+        log::warn!("Capture {} extraction failed: {}", document_id, e);
+    }
+    let event = CaptureEvent {
+        document_id,
+        status: if error.is_some() { CaptureStatus::Failed } else { CaptureStatus::Completed },
+        error,
+        document: document.as_ref().map(DocumentSummary::from),
+    };
+    if let Err(e) = app.emit(CAPTURE_EVENT, event) {
+        log::warn!("Failed to emit capture event: {}", e);
+    }
+}
+
+/// Queue a capture for background extraction, starting the worker on first use
+pub fn enqueue(app: &AppHandle, document_id: String) -> Result<(), String> {
+    static QUEUE: OnceLock<Sender<(AppHandle, String)>> = OnceLock::new();
+    let queue = QUEUE.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<(AppHandle, String)>();
+        thread::spawn(move || {
+            for (app, document_id) in receiver {
+                run_job(&app, document_id);
+            }
+        });
+        sender
+    });
+    queue
+        .send((app.clone(), document_id))
+        .map_err(|_| "Capture worker has stopped".to_string())
+}
+
+/// Store a photo as a provisional receipt and queue it for extraction
+pub fn quick_capture(app: &AppHandle, image_path: &Path) -> Result<QuickCaptureResult, String> {
+    let source = store_photo(image_path, &captures_directory()?)?;
+    let entity_id = settings::load_settings()?.active_entity_id;
+    let saved = documents::with_store(|store| {
+        let saved = store.upsert(provisional_document(&source, entity_id));
+        store.save()?;
+        Ok(saved)
+    })?;
+    enqueue(app, saved.id.clone())?;
+    Ok(QuickCaptureResult {
+        document_id: saved.id,
+        source_path: source.to_string_lossy().to_string(),
+    })
+}
+
+/// Queue captures left provisional when the app last closed
+pub fn resume_pending(app: &AppHandle) -> Result<usize, String> {
+    let pending: Vec<String> = documents::with_store(|store| {
+        Ok(store
+            .list(&Default::default())
+            .into_iter()
+            .filter(|d| d.provisional)
+            .map(|d| d.id)
+            .collect())
+    })?;
+    for id in &pending {
+        enqueue(app, id.clone())?;
+    }
+    Ok(pending.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+
+    #[test]
+    fn test_provisional_capture_is_patched_by_extraction() {
+        let dir = storage::test_dir();
+        let photo = dir.join("receipt.png");
+        image::DynamicImage::new_luma8(64, 64).save(&photo).unwrap();
+
+        let source = store_photo(&photo, &dir).unwrap();
+        assert!(source.is_file() && source != photo);
+
+        let mut store = DocumentStore::open(&dir.join("documents.json")).unwrap();
+        let provisional = store.upsert(provisional_document(&source, None));
+        assert!(provisional.provisional && provisional.vendor.is_none());
+
+        let tsv = [
+            "header",
+            "5\t1\t1\t1\t1\t1\t0\t0\t90\t20\t91\tOfficeworks",
+            "5\t1\t1\t1\t2\t1\t0\t30\t90\t20\t88\tTOTAL",
+            "5\t1\t1\t1\t2\t2\t100\t30\t60\t20\t88\t$45.50",
+        ]
+        .join("\n");
+        let receipt = ocr::receipt_from_lines(&ocr::parse_tsv(&tsv));
+        let done = finish_capture(&mut store, &provisional.id, Ok(Extraction::Receipt(Box::new(receipt)))).unwrap();
+        assert!(!done.provisional);
+        assert_eq!(done.vendor.as_deref(), Some("Officeworks"));
+        assert_eq!(done.total, Some(Money::from_cents(4550)));
+        assert_eq!(done.source_path, provisional.source_path);
+
+        // Failures still leave a reviewable document; deleted ones are skipped
+        let other = store.upsert(provisional_document(&source, None));
+        let failed = finish_capture(&mut store, &other.id, Err("unreadable".to_string())).unwrap();
+        assert!(!failed.provisional && failed.total.is_none());
+        assert!(finish_capture(&mut store, "missing", Err("gone".to_string())).is_none());
+    }
+}
@@ -0,0 +1,374 @@
+//! Capture Metadata Module
+//!
+//! Reads the EXIF GPS position and capture time from receipt photos. The
+//! location is used to suggest a vendor from earlier receipts taken nearby,
+//! and receipts captured far from home are offered as travel-diary entries.
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::documents::StoredDocument;
+use crate::money::Money;
+
+/// Receipts within this distance of an earlier one may share its vendor
+pub const MERCHANT_RADIUS_KM: f64 = 0.15;
+
+/// Mean radius of the Earth used for distances
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Largest photo read looking for EXIF data
+const MAX_READ_BYTES: u64 = 64 * 1024 * 1024;
+
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_GPS_LATITUDE_REF: u16 = 0x0001;
+const TAG_GPS_LATITUDE: u16 = 0x0002;
+const TAG_GPS_LONGITUDE_REF: u16 = 0x0003;
+const TAG_GPS_LONGITUDE: u16 = 0x0004;
+
+/// A position in decimal degrees
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct GeoPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl GeoPoint {
+    /// Great-circle distance in kilometres
+    pub fn distance_km(&self, other: &GeoPoint) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.longitude - self.longitude).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
+
+/// Where and when a receipt photo was taken
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct CaptureMetadata {
+    /// Camera clock time (YYYY-MM-DDTHH:MM:SS, no time zone)
+    pub taken_at: Option<String>,
+    pub location: Option<GeoPoint>,
+}
+
+/// Read capture metadata from a photo, or `None` when it carries none
+pub fn read_capture_metadata(path: &Path) -> Option<CaptureMetadata> {
+    let metadata = std::fs::metadata(path).ok()?;
+    if !metadata.is_file() || metadata.len() > MAX_READ_BYTES {
+        return None;
+    }
+    parse_exif(&std::fs::read(path).ok()?)
+}
+
+/// Find the EXIF block in a JPEG or HEIC file and read it. Both store the
+/// TIFF structure after an `Exif\0\0` marker.
+pub fn parse_exif(bytes: &[u8]) -> Option<CaptureMetadata> {
+    let marker = bytes.windows(6).position(|w| w == b"Exif\0\0")?;
+    let tiff = Tiff::new(&bytes[marker + 6..])?;
+    let ifd0 = tiff.u32_at(4)? as usize;
+
+    let taken_at = tiff
+        .pointer(ifd0, TAG_EXIF_IFD)
+        .and_then(|exif| tiff.ascii(exif, TAG_DATE_TIME_ORIGINAL))
+        .or_else(|| tiff.ascii(ifd0, TAG_DATE_TIME))
+        .and_then(|text| NaiveDateTime::parse_from_str(text.trim(), "%Y:%m:%d %H:%M:%S").ok())
+        .map(|time| time.format("%Y-%m-%dT%H:%M:%S").to_string());
+    let location = tiff.pointer(ifd0, TAG_GPS_IFD).and_then(|gps| {
+        let latitude = tiff.coordinate(gps, TAG_GPS_LATITUDE, TAG_GPS_LATITUDE_REF, 'S')?;
+        let longitude = tiff.coordinate(gps, TAG_GPS_LONGITUDE, TAG_GPS_LONGITUDE_REF, 'W')?;
+        // Cameras without a fix often write zeros
+        let valid = latitude.abs() <= 90.0 && longitude.abs() <= 180.0 && (latitude, longitude) != (0.0, 0.0);
+        valid.then_some(GeoPoint { latitude, longitude })
+    });
+
+    if taken_at.is_none() && location.is_none() {
+        return None;
+    }
+    Some(CaptureMetadata { taken_at, location })
+}
+
+/// A TIFF structure in either byte order
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+/// One IFD entry: field type, value count and the value's byte offset
+struct Entry {
+    kind: u16,
+    count: usize,
+    offset: usize,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(..4)? {
+            [b'I', b'I', 42, 0] => true,
+            [b'M', b'M', 0, 42] => false,
+            _ => return None,
+        };
+        Some(Self { data, little_endian })
+    }
+
+    fn u16_at(&self, offset: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    }
+
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    }
+
+    fn entry(&self, ifd: usize, tag: u16) -> Option<Entry> {
+        let count = self.u16_at(ifd)? as usize;
+        (0..count).find_map(|i| {
+            let at = ifd + 2 + i * 12;
+            if self.u16_at(at)? != tag {
+                return None;
+            }
+            let kind = self.u16_at(at + 2)?;
+            let count = self.u32_at(at + 4)? as usize;
+            let unit = match kind {
+                1 | 2 | 7 => 1,
+                3 => 2,
+                4 | 9 => 4,
+                5 | 10 => 8,
+                _ => return None,
+            };
+            // Values of four bytes or fewer are stored in the entry itself
+            let offset = if unit * count <= 4 { at + 8 } else { self.u32_at(at + 8)? as usize };
+            Some(Entry { kind, count, offset })
+        })
+    }
+
+    fn pointer(&self, ifd: usize, tag: u16) -> Option<usize> {
+        let entry = self.entry(ifd, tag)?;
+        Some(self.u32_at(entry.offset)? as usize)
+    }
+
+    fn ascii(&self, ifd: usize, tag: u16) -> Option<&'a str> {
+        let entry = self.entry(ifd, tag).filter(|e| e.kind == 2)?;
+        let bytes = self.data.get(entry.offset..entry.offset + entry.count)?;
+        std::str::from_utf8(bytes).ok().map(|text| text.trim_end_matches('\0'))
+    }
+
+    fn rational(&self, offset: usize) -> Option<f64> {
+        let denominator = self.u32_at(offset + 4)?;
+        if denominator == 0 {
+            return None;
+        }
+        Some(self.u32_at(offset)? as f64 / denominator as f64)
+    }
+
+    /// Degrees, minutes and seconds as signed decimal degrees
+    fn coordinate(&self, ifd: usize, tag: u16, reference: u16, negative: char) -> Option<f64> {
+        let entry = self.entry(ifd, tag).filter(|e| e.kind == 5 && e.count == 3)?;
+        let degrees = self.rational(entry.offset)?;
+        let minutes = self.rational(entry.offset + 8)?;
+        let seconds = self.rational(entry.offset + 16)?;
+        let value = degrees + minutes / 60.0 + seconds / 3600.0;
+        let is_negative = self.ascii(ifd, reference).is_some_and(|r| r.starts_with(negative));
+        Some(if is_negative { -value } else { value })
+    }
+}
+
+/// Vendor of the closest earlier receipt taken nearby
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct VendorSuggestion {
+    pub vendor: String,
+    pub distance_km: f64,
+    /// Receipt the vendor was taken from
+    pub document_id: String,
+}
+
+/// Suggest a vendor for a receipt taken at `location` from the nearest
+/// other document within `MERCHANT_RADIUS_KM` that has a vendor
+pub fn nearest_merchant(
+    documents: &[StoredDocument],
+    location: &GeoPoint,
+    exclude_id: Option<&str>,
+) -> Option<VendorSuggestion> {
+    documents
+        .iter()
+        .filter(|doc| Some(doc.id.as_str()) != exclude_id)
+        .filter_map(|doc| {
+            let vendor = doc.vendor.as_deref().map(str::trim).filter(|v| !v.is_empty())?;
+            let point = doc.capture.as_ref()?.location?;
+            let distance_km = location.distance_km(&point);
+            (distance_km <= MERCHANT_RADIUS_KM).then(|| VendorSuggestion {
+                vendor: vendor.to_string(),
+                distance_km,
+                document_id: doc.id.clone(),
+            })
+        })
+        .min_by(|a, b| a.distance_km.total_cmp(&b.distance_km))
+}
+
+/// Where home is, for spotting expenses incurred while travelling
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct TravelSettings {
+    pub home: Option<GeoPoint>,
+    /// Receipts captured at least this far from home count as travel
+    pub min_distance_km: f64,
+}
+
+impl Default for TravelSettings {
+    fn default() -> Self {
+        Self {
+            home: None,
+            min_distance_km: 100.0,
+        }
+    }
+}
+
+/// A receipt captured away from home, ready to copy into the travel diary
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TravelDiaryEntry {
+    pub document_id: String,
+    /// Capture date, or the receipt date when the photo has no time (YYYY-MM-DD)
+    pub date: Option<String>,
+    pub taken_at: Option<String>,
+    pub vendor: Option<String>,
+    pub amount: Option<Money>,
+    pub category: Option<String>,
+    pub location: GeoPoint,
+    pub distance_from_home_km: f64,
+}
+
+/// Travel-diary entries for receipts captured at least
+/// `min_distance_km` from home, in date order
+pub fn travel_diary_entries(documents: &[StoredDocument], settings: &TravelSettings) -> Vec<TravelDiaryEntry> {
+    let Some(home) = settings.home else {
+        return Vec::new();
+    };
+    let mut entries: Vec<TravelDiaryEntry> = documents
+        .iter()
+        .filter_map(|doc| {
+            let capture = doc.capture.as_ref()?;
+            let location = capture.location?;
+            let distance_from_home_km = home.distance_km(&location);
+            if distance_from_home_km < settings.min_distance_km {
+                return None;
+            }
+            let captured_on = capture.taken_at.as_ref().and_then(|t| t.get(..10)).map(str::to_string);
+            Some(TravelDiaryEntry {
+                document_id: doc.id.clone(),
+                date: captured_on.or_else(|| doc.date.clone()),
+                taken_at: capture.taken_at.clone(),
+                vendor: doc.vendor.clone(),
+                amount: doc.total,
+                category: doc.category.clone(),
+                location,
+                distance_from_home_km,
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| {
+        (a.date.as_deref(), a.taken_at.as_deref(), &a.document_id).cmp(&(
+            b.date.as_deref(),
+            b.taken_at.as_deref(),
+            &b.document_id,
+        ))
+    });
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Little-endian EXIF block with a capture time and a GPS position
+    fn exif_block(latitude: [u32; 3], longitude: [u32; 3]) -> Vec<u8> {
+        fn entry(out: &mut Vec<u8>, tag: u16, kind: u16, count: u32, value: u32) {
+            out.extend(tag.to_le_bytes());
+            out.extend(kind.to_le_bytes());
+            out.extend(count.to_le_bytes());
+            out.extend(value.to_le_bytes());
+        }
+        let mut tiff = b"II\x2a\x00".to_vec();
+        tiff.extend(8u32.to_le_bytes());
+        // IFD0 at 8: two pointers, 2 + 24 + 4 bytes
+        tiff.extend(2u16.to_le_bytes());
+        entry(&mut tiff, TAG_EXIF_IFD, 4, 1, 38);
+        entry(&mut tiff, TAG_GPS_IFD, 4, 1, 56);
+        tiff.extend(0u32.to_le_bytes());
+        // Exif IFD at 38, date string at 110
+        tiff.extend(1u16.to_le_bytes());
+        entry(&mut tiff, TAG_DATE_TIME_ORIGINAL, 2, 20, 110);
+        tiff.extend(0u32.to_le_bytes());
+        // GPS IFD at 56, rationals at 130 and 154
+        tiff.extend(4u16.to_le_bytes());
+        entry(&mut tiff, TAG_GPS_LATITUDE_REF, 2, 2, u32::from_le_bytes(*b"S\0\0\0"));
+        entry(&mut tiff, TAG_GPS_LATITUDE, 5, 3, 130);
+        entry(&mut tiff, TAG_GPS_LONGITUDE_REF, 2, 2, u32::from_le_bytes(*b"E\0\0\0"));
+        entry(&mut tiff, TAG_GPS_LONGITUDE, 5, 3, 154);
+        tiff.extend(0u32.to_le_bytes());
+        assert_eq!(tiff.len(), 110);
+        tiff.extend(b"2024:03:15 12:34:56\0");
+        for value in latitude.iter().chain(&longitude) {
+            tiff.extend(value.to_le_bytes());
+            tiff.extend(1u32.to_le_bytes());
+        }
+
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        jpeg.extend(((tiff.len() + 8) as u16).to_be_bytes());
+        jpeg.extend(b"Exif\0\0");
+        jpeg.extend(tiff);
+        jpeg.extend([0xFF, 0xD9]);
+        jpeg
+    }
+
+    fn receipt(id: &str, vendor: Option<&str>, capture: CaptureMetadata) -> StoredDocument {
+        StoredDocument {
+            id: id.to_string(),
+            vendor: vendor.map(str::to_string),
+            total: Some(Money::from_cents(1250)),
+            capture: Some(capture),
+            ..StoredDocument::default()
+        }
+    }
+
+    #[test]
+    fn test_exif_location_suggests_vendor_and_travel_entries() {
+        // Sydney Opera House, 33°51'26"S 151°12'54"E
+        let capture = parse_exif(&exif_block([33, 51, 26], [151, 12, 54])).unwrap();
+        assert_eq!(capture.taken_at.as_deref(), Some("2024-03-15T12:34:56"));
+        let here = capture.location.unwrap();
+        assert!((here.latitude + 33.857_222).abs() < 1e-5);
+        assert!((here.longitude - 151.215).abs() < 1e-5);
+        assert!(parse_exif(b"\xFF\xD8\xFF\xD9").is_none());
+
+        let at = |latitude, longitude| CaptureMetadata {
+            taken_at: None,
+            location: Some(GeoPoint { latitude, longitude }),
+        };
+        let docs = vec![
+            receipt("new", None, capture.clone()),
+            receipt("cafe", Some("Opera Bar"), at(-33.8575, 151.2152)),
+            receipt("kiosk", Some("Circular Quay Kiosk"), at(-33.8610, 151.2110)),
+            receipt("melbourne", Some("Hotel Windsor"), at(-37.8119, 144.9728)),
+        ];
+        let suggestion = nearest_merchant(&docs, &here, Some("new")).unwrap();
+        assert_eq!(suggestion.vendor, "Opera Bar");
+        assert_eq!(suggestion.document_id, "cafe");
+        assert!(nearest_merchant(&docs, &GeoPoint { latitude: -35.0, longitude: 149.0 }, None).is_none());
+
+        // From a Sydney home only the Melbourne receipt is travel
+        let settings = TravelSettings {
+            home: Some(GeoPoint { latitude: -33.87, longitude: 151.2 }),
+            ..TravelSettings::default()
+        };
+        let entries = travel_diary_entries(&docs, &settings);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].document_id, "melbourne");
+        assert!((entries[0].distance_from_home_km - 713.0).abs() < 10.0);
+        assert!(travel_diary_entries(&docs, &TravelSettings::default()).is_empty());
+    }
+}
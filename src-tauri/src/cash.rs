@@ -0,0 +1,225 @@
+//! Cash Expense Journal Module
+//!
+//! Records small cash expenses that have no receipt. The ATO lets individual
+//! expenses of $10 or less go unsubstantiated as long as they total no more
+//! than $200 for the financial year; entries outside those limits are rejected
+//! so the journal only ever holds claimable amounts.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::money::{round_cents, sum_dollars};
+use crate::pagination::{self, Page, PageRequest, SortKey};
+use crate::periods;
+use crate::storage;
+
+static JOURNAL_LOCK: Mutex<()> = Mutex::new(());
+
+/// Largest single expense that can be claimed without a receipt
+pub const MAX_UNSUBSTANTIATED_EXPENSE: f64 = 10.0;
+
+/// Largest yearly total of unsubstantiated small expenses
+pub const MAX_UNSUBSTANTIATED_TOTAL: f64 = 200.0;
+
+/// A cash expense without a receipt
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct CashExpense {
+    pub id: String,
+    /// Expense date (YYYY-MM-DD)
+    pub date: String,
+    pub amount: f64,
+    pub purpose: String,
+    pub category: Option<String>,
+    pub created_at: String,
+}
+
+/// New journal entry from the quick-entry form
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NewCashExpense {
+    pub date: String,
+    pub amount: f64,
+    pub purpose: String,
+    pub category: Option<String>,
+}
+
+/// Cash expense totals for one financial year
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CashYearTotals {
+    pub financial_year: i32,
+    pub count: usize,
+    pub total: f64,
+    /// Unsubstantiated allowance left for the year
+    pub remaining_allowance: f64,
+}
+
+/// Field a page of cash expenses is sorted on
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CashSortField {
+    #[default]
+    Date,
+    Amount,
+    Purpose,
+    Category,
+    CreatedAt,
+}
+
+impl CashSortField {
+    fn key(self, expense: &CashExpense) -> SortKey {
+        match self {
+            CashSortField::Date => SortKey::text(&expense.date),
+            CashSortField::Amount => SortKey::Number(expense.amount),
+            CashSortField::Purpose => SortKey::text(&expense.purpose),
+            CashSortField::Category => SortKey::optional_text(expense.category.as_deref()),
+            CashSortField::CreatedAt => SortKey::text(&expense.created_at),
+        }
+    }
+}
+
+/// JSON-backed cash expense journal
+pub struct CashJournal {
+    path: PathBuf,
+    expenses: Vec<CashExpense>,
+}
+
+impl CashJournal {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            expenses: storage::load_json(path)?,
+        })
+    }
+
+    pub fn open_default() -> Result<Self, String> {
+        Self::open(&storage::get_data_directory()?.join("cash_journal.json"))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        storage::save_json(&self.path, &self.expenses)
+    }
+
+    /// Expenses in a financial year (or all), oldest first
+    pub fn list(&self, financial_year: Option<i32>) -> Vec<CashExpense> {
+        let mut expenses: Vec<CashExpense> = self
+            .expenses
+            .iter()
+            .filter(|e| financial_year.is_none() || expense_year(e) == financial_year)
+            .cloned()
+            .collect();
+        expenses.sort_by(|a, b| a.date.cmp(&b.date));
+        expenses
+    }
+
+    /// One page of the expenses in a financial year (or all)
+    pub fn page(
+        &self,
+        financial_year: Option<i32>,
+        request: &PageRequest<CashSortField>,
+    ) -> Result<Page<CashExpense>, String> {
+        pagination::paginate(self.list(financial_year), request, |e, field| field.key(e), |e| &e.id)
+    }
+
+    pub fn totals(&self, financial_year: i32) -> CashYearTotals {
+        let expenses = self.list(Some(financial_year));
+        let total = sum_dollars(expenses.iter().map(|e| e.amount));
+        CashYearTotals {
+            financial_year,
+            count: expenses.len(),
+            total,
+            remaining_allowance: round_cents((MAX_UNSUBSTANTIATED_TOTAL - total).max(0.0)),
+        }
+    }
+
+    /// Add an expense after checking the substantiation limits
+    pub fn add(&mut self, entry: NewCashExpense) -> Result<CashExpense, String> {
+        let date = periods::parse_date(&entry.date)
+            .ok_or_else(|| format!("Invalid date: {}", entry.date))?;
+        if entry.purpose.trim().is_empty() {
+            return Err("A purpose is required for cash expenses".to_string());
+        }
+        if entry.amount <= 0.0 {
+            return Err("Amount must be greater than zero".to_string());
+        }
+        if entry.amount > MAX_UNSUBSTANTIATED_EXPENSE {
+            return Err(format!(
+                "Cash expenses over ${:.2} need a receipt; scan it as a document instead",
+                MAX_UNSUBSTANTIATED_EXPENSE
+            ));
+        }
+
+        let financial_year = periods::financial_year_of(date);
+        let totals = self.totals(financial_year);
+        if entry.amount > totals.remaining_allowance + 0.005 {
+            return Err(format!(
+                "Only ${:.2} of the ${:.2} unsubstantiated allowance remains for {}",
+                totals.remaining_allowance,
+                MAX_UNSUBSTANTIATED_TOTAL,
+                periods::financial_year_label(financial_year)
+            ));
+        }
+
+        let expense = CashExpense {
+            id: storage::generate_id("cash"),
+            date: date.format("%Y-%m-%d").to_string(),
+            amount: round_cents(entry.amount),
+            purpose: entry.purpose.trim().to_string(),
+            category: entry.category,
+            created_at: storage::now_timestamp(),
+        };
+        self.expenses.push(expense.clone());
+        Ok(expense)
+    }
+
+    pub fn delete(&mut self, id: &str) -> bool {
+        let before = self.expenses.len();
+        self.expenses.retain(|e| e.id != id);
+        self.expenses.len() != before
+    }
+}
+
+fn expense_year(expense: &CashExpense) -> Option<i32> {
+    periods::parse_date(&expense.date).map(periods::financial_year_of)
+}
+
+/// Run a closure against the default journal while holding its lock
+pub fn with_journal<R>(f: impl FnOnce(&mut CashJournal) -> Result<R, String>) -> Result<R, String> {
+    let _guard = JOURNAL_LOCK.lock().map_err(|_| "Cash journal lock poisoned".to_string())?;
+    let mut journal = CashJournal::open_default()?;
+    f(&mut journal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(date: &str, amount: f64) -> NewCashExpense {
+        NewCashExpense {
+            date: date.to_string(),
+            amount,
+            purpose: "Parking".to_string(),
+            category: Some("Travel".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_small_expense_limits_enforced() {
+        let dir = storage::test_dir();
+        let path = dir.join("cash_journal.json");
+        let mut journal = CashJournal::open(&path).unwrap();
+
+        assert!(journal.add(entry("2024-03-01", 10.50)).is_err());
+        for _ in 0..20 {
+            journal.add(entry("2024-03-01", 10.0)).unwrap();
+        }
+        // FY2024 allowance is used up, but FY2025 starts fresh
+        assert!(journal.add(entry("2024-06-30", 1.0)).is_err());
+        assert!(journal.add(entry("2024-07-01", 1.0)).is_ok());
+
+        let totals = journal.totals(2024);
+        assert_eq!(totals.count, 20);
+        assert_eq!(totals.total, 200.0);
+        assert_eq!(totals.remaining_allowance, 0.0);
+    }
+}
@@ -0,0 +1,257 @@
+//! Category Clustering Module
+//!
+//! Groups uncategorized documents that look alike so one confirmation can
+//! categorize the whole group. Documents are compared as sets of words from
+//! their vendor name and item descriptions: the same vendor, or enough words
+//! in common, puts two documents in one cluster. Each cluster proposes the
+//! category the user most often gave similar documents, falling back to the
+//! entity's category packs when there is no history to go on.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::category_packs;
+use crate::documents::StoredDocument;
+use crate::entities::Entity;
+use crate::money::Money;
+
+/// Share of words two documents must have in common to be clustered
+const MIN_SIMILARITY: f64 = 0.5;
+
+/// Words found on too many documents to say what they were for
+const STOP_WORDS: [&str; 16] = [
+    "pty", "ltd", "limited", "the", "and", "of", "co", "inc", "incl", "store", "shop", "gst", "tax", "qty", "each", "total",
+];
+
+/// Where a cluster's proposed category came from
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProposalSource {
+    /// Similar documents the user already categorized
+    History,
+    /// Keyword rules from the entity's installed category packs
+    CategoryPack,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CategoryProposal {
+    pub category: String,
+    pub source: ProposalSource,
+    /// Share of the matching documents or rules that agree, 0-1
+    pub confidence: f64,
+}
+
+/// Uncategorized documents similar enough to share a category
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DocumentCluster {
+    /// The most common vendor in the cluster
+    pub label: String,
+    pub document_ids: Vec<String>,
+    pub vendors: Vec<String>,
+    pub entity_id: Option<String>,
+    pub total: Money,
+    pub proposal: Option<CategoryProposal>,
+}
+
+/// Words a document is compared by
+struct Profile {
+    vendor: BTreeSet<String>,
+    words: BTreeSet<String>,
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|w| w.chars().count() > 1 && !w.chars().all(|c| c.is_ascii_digit()))
+        .filter(|w| !STOP_WORDS.contains(&w.as_str()))
+}
+
+fn profile(doc: &StoredDocument) -> Profile {
+    let vendor: BTreeSet<String> = words(doc.vendor.as_deref().unwrap_or("")).collect();
+    let mut all = vendor.clone();
+    if let Some(invoice) = &doc.invoice {
+        for item in &invoice.line_items {
+            all.extend(words(&item.description));
+        }
+    }
+    if let Some(receipt) = &doc.receipt {
+        for item in &receipt.items {
+            all.extend(words(&item.name));
+        }
+    }
+    Profile { vendor, words: all }
+}
+
+/// 1 for the same vendor, otherwise the share of words in common
+fn similarity(a: &Profile, b: &Profile) -> f64 {
+    if !a.vendor.is_empty() && a.vendor == b.vendor {
+        return 1.0;
+    }
+    let union = a.words.union(&b.words).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.words.intersection(&b.words).count() as f64 / union as f64
+}
+
+/// Whether a document still needs a category
+pub fn is_uncategorized(doc: &StoredDocument) -> bool {
+    doc.category.as_deref().map_or(true, |c| c.trim().is_empty())
+}
+
+/// Most common value and the share of `counts` it holds; ties go to the
+/// name that sorts first
+fn most_common(counts: &BTreeMap<String, usize>) -> Option<(String, f64)> {
+    let total: usize = counts.values().sum();
+    let (best, count) = counts.iter().fold(None, |best: Option<(&String, usize)>, (name, &count)| match best {
+        Some((_, best_count)) if best_count >= count => best,
+        _ => Some((name, count)),
+    })?;
+    Some((best.clone(), count as f64 / total as f64))
+}
+
+fn find(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[i] = root;
+    root
+}
+
+/// Cluster the uncategorized documents among `documents` and propose a
+/// category for each cluster. Categorized documents of the same entity are
+/// the history proposals are drawn from; `entity_for` supplies the packs
+/// used when there is none. Largest clusters come first.
+pub fn cluster_documents<'a>(
+    documents: &[StoredDocument],
+    entity_for: impl Fn(&StoredDocument) -> Option<&'a Entity>,
+) -> Vec<DocumentCluster> {
+    let (pending, history): (Vec<&StoredDocument>, Vec<&StoredDocument>) =
+        documents.iter().partition(|doc| is_uncategorized(doc));
+    let pending_profiles: Vec<Profile> = pending.iter().map(|doc| profile(doc)).collect();
+    let history_profiles: Vec<Profile> = history.iter().map(|doc| profile(doc)).collect();
+
+    let mut parents: Vec<usize> = (0..pending.len()).collect();
+    for i in 0..pending.len() {
+        for j in (i + 1)..pending.len() {
+            if pending[i].entity_id == pending[j].entity_id
+                && similarity(&pending_profiles[i], &pending_profiles[j]) >= MIN_SIMILARITY
+            {
+                let (a, b) = (find(&mut parents, i), find(&mut parents, j));
+                parents[b] = a;
+            }
+        }
+    }
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..pending.len() {
+        let root = find(&mut parents, i);
+        groups.entry(root).or_default().push(i);
+    }
+
+    let mut clusters: Vec<DocumentCluster> = groups
+        .into_values()
+        .map(|members| {
+            let entity_id = pending[members[0]].entity_id.clone();
+            let mut vendors: BTreeMap<String, usize> = BTreeMap::new();
+            for &i in &members {
+                if let Some(vendor) = pending[i].vendor.as_deref().filter(|v| !v.trim().is_empty()) {
+                    *vendors.entry(vendor.trim().to_string()).or_default() += 1;
+                }
+            }
+
+            let mut votes: BTreeMap<String, usize> = BTreeMap::new();
+            for (doc, doc_profile) in history.iter().zip(&history_profiles) {
+                let similar = doc.entity_id == entity_id
+                    && members
+                        .iter()
+                        .any(|&i| similarity(&pending_profiles[i], doc_profile) >= MIN_SIMILARITY);
+                if let (true, Some(category)) = (similar, doc.category.as_ref()) {
+                    *votes.entry(category.trim().to_string()).or_default() += 1;
+                }
+            }
+            let mut proposal = most_common(&votes).map(|(category, confidence)| CategoryProposal {
+                category,
+                source: ProposalSource::History,
+                confidence,
+            });
+
+            if proposal.is_none() {
+                let mut suggested: BTreeMap<String, usize> = BTreeMap::new();
+                for &i in &members {
+                    let suggestion = entity_for(pending[i]).and_then(|e| category_packs::suggest_category(e, pending[i]));
+                    if let Some(suggestion) = suggestion {
+                        *suggested.entry(suggestion.category).or_default() += 1;
+                    }
+                }
+                let suggestions: usize = suggested.values().sum();
+                proposal = most_common(&suggested).map(|(category, share)| CategoryProposal {
+                    category,
+                    source: ProposalSource::CategoryPack,
+                    // Members without a suggestion count against it
+                    confidence: share * suggestions as f64 / members.len() as f64,
+                });
+            }
+
+            DocumentCluster {
+                label: most_common(&vendors).map_or_else(|| "Unknown vendor".to_string(), |(vendor, _)| vendor),
+                document_ids: members.iter().map(|&i| pending[i].id.clone()).collect(),
+                vendors: vendors.into_keys().collect(),
+                entity_id,
+                total: members.iter().filter_map(|&i| pending[i].total).sum(),
+                proposal,
+            }
+        })
+        .collect();
+    clusters.sort_by(|a, b| b.document_ids.len().cmp(&a.document_ids.len()).then_with(|| a.label.cmp(&b.label)));
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(id: &str, vendor: &str, category: Option<&str>) -> StoredDocument {
+        StoredDocument {
+            id: id.to_string(),
+            vendor: Some(vendor.to_string()),
+            category: category.map(str::to_string),
+            total: Some(Money::from_cents(1000)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_similar_uncategorized_documents_share_a_proposal() {
+        let mut documents = vec![
+            doc("fuel-1", "BP Connect Ringwood", None),
+            doc("fuel-2", "BP Connect Ringwood", Some(" ")),
+            doc("fuel-3", "bp connect ringwood", None),
+            doc("old-fuel", "BP Connect Ringwood", Some("Vehicle expenses")),
+            doc("old-fuel-2", "BP Connect Ringwood Pty Ltd", Some("Vehicle expenses")),
+            doc("old-fuel-3", "BP Connect", Some("Travel")),
+            doc("software", "Atlassian", None),
+            doc("other-entity", "BP Connect Ringwood", None),
+        ];
+        documents[7].entity_id = Some("farm".to_string());
+        let farm = Entity {
+            id: "farm".to_string(),
+            category_packs: vec!["rideshare".to_string()],
+            ..Default::default()
+        };
+
+        let clusters = cluster_documents(&documents, |d| (d.entity_id.as_deref() == Some("farm")).then_some(&farm));
+        assert_eq!(clusters.len(), 3);
+        let fuel = &clusters[0];
+        assert_eq!(fuel.document_ids, vec!["fuel-1", "fuel-2", "fuel-3"]);
+        assert_eq!(fuel.total, Money::from_cents(3000));
+        let proposal = fuel.proposal.as_ref().unwrap();
+        assert_eq!((proposal.category.as_str(), proposal.source), ("Vehicle expenses", ProposalSource::History));
+        assert!((proposal.confidence - 2.0 / 3.0).abs() < 1e-9);
+
+        assert!(clusters.iter().any(|c| c.label == "Atlassian" && c.proposal.is_none()));
+        let farm_fuel = clusters.iter().find(|c| c.entity_id.as_deref() == Some("farm")).unwrap();
+        let proposal = farm_fuel.proposal.as_ref().unwrap();
+        assert_eq!((proposal.category.as_str(), proposal.source), ("Fuel", ProposalSource::CategoryPack));
+    }
+}
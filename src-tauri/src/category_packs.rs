@@ -0,0 +1,357 @@
+//! Category Packs Module
+//!
+//! Installable category and rule packs for particular kinds of taxpayer
+//! (rideshare drivers, tradies, landlords, primary producers). A pack adds
+//! tailored categories with business-use defaults and vendor keyword rules.
+//! Packs are installed per entity, so a household and a farm business can
+//! categorize the same vendor differently.
+
+use serde::{Deserialize, Serialize};
+
+use crate::documents::StoredDocument;
+use crate::entities::Entity;
+
+/// A category added by a pack
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PackCategory {
+    pub name: String,
+    /// Where the expense is reported on the return
+    pub ato_label: String,
+    /// Default business-use share, 0-100
+    pub business_use_percent: f64,
+}
+
+/// Assigns a category when a keyword appears in a document
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KeywordRule {
+    pub keyword: String,
+    pub category: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CategoryPack {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub categories: Vec<PackCategory>,
+    pub rules: Vec<KeywordRule>,
+}
+
+/// A category suggested by an installed pack's rules
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CategorySuggestion {
+    pub category: String,
+    pub ato_label: String,
+    pub business_use_percent: f64,
+    pub pack_id: String,
+    pub matched_keyword: String,
+    /// Whether the keyword matched the vendor name rather than the body text
+    pub matched_vendor: bool,
+    /// User category rule that matched; `pack_id` and `matched_keyword`
+    /// are empty when set
+    #[serde(default)]
+    pub rule_id: Option<String>,
+}
+
+fn pack(id: &str, name: &str, description: &str, categories: &[(&str, &str, f64)], rules: &[(&str, &str)]) -> CategoryPack {
+    CategoryPack {
+        id: id.to_string(),
+        name: name.to_string(),
+        description: description.to_string(),
+        categories: categories
+            .iter()
+            .map(|(name, label, percent)| PackCategory {
+                name: name.to_string(),
+                ato_label: label.to_string(),
+                business_use_percent: *percent,
+            })
+            .collect(),
+        rules: rules
+            .iter()
+            .map(|(keyword, category)| KeywordRule {
+                keyword: keyword.to_string(),
+                category: category.to_string(),
+            })
+            .collect(),
+    }
+}
+
+/// Packs shipped with the app
+pub fn built_in_packs() -> Vec<CategoryPack> {
+    const BUSINESS: &str = "Business schedule: All other expenses";
+    vec![
+        pack(
+            "rideshare",
+            "Rideshare driver",
+            "Vehicle running costs, platform fees and passenger amenities for Uber, DiDi and Ola drivers",
+            &[
+                ("Fuel", "Business schedule: Motor vehicle expenses", 80.0),
+                ("Vehicle servicing and repairs", "Business schedule: Motor vehicle expenses", 80.0),
+                ("Tyres", "Business schedule: Motor vehicle expenses", 80.0),
+                ("Registration and insurance", "Business schedule: Motor vehicle expenses", 80.0),
+                ("Tolls", "Business schedule: Motor vehicle expenses", 100.0),
+                ("Parking", "Business schedule: All other expenses", 100.0),
+                ("Car cleaning", "Business schedule: Motor vehicle expenses", 100.0),
+                ("Platform service fees", "Business schedule: All other expenses", 100.0),
+                ("Mobile phone and data", "Business schedule: All other expenses", 50.0),
+                ("Passenger amenities", "Business schedule: All other expenses", 100.0),
+            ],
+            &[
+                ("uber", "Platform service fees"),
+                ("didi", "Platform service fees"),
+                ("ola cabs", "Platform service fees"),
+                ("shell", "Fuel"),
+                ("caltex", "Fuel"),
+                ("ampol", "Fuel"),
+                ("bp ", "Fuel"),
+                ("7-eleven", "Fuel"),
+                ("united petroleum", "Fuel"),
+                ("linkt", "Tolls"),
+                ("e-toll", "Tolls"),
+                ("eastlink", "Tolls"),
+                ("beaurepaires", "Tyres"),
+                ("bob jane", "Tyres"),
+                ("tyrepower", "Tyres"),
+                ("mycar", "Vehicle servicing and repairs"),
+                ("ultra tune", "Vehicle servicing and repairs"),
+                ("car wash", "Car cleaning"),
+                ("nrma", "Registration and insurance"),
+                ("racv", "Registration and insurance"),
+                ("service nsw", "Registration and insurance"),
+                ("vicroads", "Registration and insurance"),
+                ("wilson parking", "Parking"),
+                ("secure parking", "Parking"),
+                ("telstra", "Mobile phone and data"),
+                ("optus", "Mobile phone and data"),
+                ("vodafone", "Mobile phone and data"),
+            ],
+        ),
+        pack(
+            "tradie",
+            "Tradie",
+            "Tools, materials, workwear and licences for trades businesses",
+            &[
+                ("Tools and equipment", BUSINESS, 100.0),
+                ("Materials and supplies", "Business schedule: Purchases and other costs", 100.0),
+                ("Workwear and PPE", BUSINESS, 100.0),
+                ("Licences and tickets", BUSINESS, 100.0),
+                ("Trade insurance", BUSINESS, 100.0),
+                ("Work vehicle fuel", "Business schedule: Motor vehicle expenses", 80.0),
+                ("Mobile phone and data", BUSINESS, 60.0),
+            ],
+            &[
+                ("total tools", "Tools and equipment"),
+                ("sydney tools", "Tools and equipment"),
+                ("makita", "Tools and equipment"),
+                ("milwaukee", "Tools and equipment"),
+                ("dewalt", "Tools and equipment"),
+                ("bunnings", "Materials and supplies"),
+                ("mitre 10", "Materials and supplies"),
+                ("reece", "Materials and supplies"),
+                ("tradelink", "Materials and supplies"),
+                ("middys", "Materials and supplies"),
+                ("rsea", "Workwear and PPE"),
+                ("hard yakka", "Workwear and PPE"),
+                ("blundstone", "Workwear and PPE"),
+                ("workwear", "Workwear and PPE"),
+                ("white card", "Licences and tickets"),
+                ("licence renewal", "Licences and tickets"),
+                ("public liability", "Trade insurance"),
+                ("shell", "Work vehicle fuel"),
+                ("caltex", "Work vehicle fuel"),
+                ("ampol", "Work vehicle fuel"),
+                ("telstra", "Mobile phone and data"),
+                ("optus", "Mobile phone and data"),
+            ],
+        ),
+        pack(
+            "landlord",
+            "Landlord",
+            "Rental property expenses for the rental schedule",
+            &[
+                ("Council rates", "Rental schedule: Council rates", 100.0),
+                ("Water charges", "Rental schedule: Water charges", 100.0),
+                ("Land tax", "Rental schedule: Land tax", 100.0),
+                ("Property agent fees", "Rental schedule: Property agent fees/commission", 100.0),
+                ("Body corporate fees", "Rental schedule: Body corporate fees and charges", 100.0),
+                ("Landlord insurance", "Rental schedule: Insurance", 100.0),
+                ("Repairs and maintenance", "Rental schedule: Repairs and maintenance", 100.0),
+                ("Pest control", "Rental schedule: Pest control", 100.0),
+                ("Advertising for tenants", "Rental schedule: Advertising for tenants", 100.0),
+                ("Loan interest", "Rental schedule: Interest on loans", 100.0),
+            ],
+            &[
+                ("council", "Council rates"),
+                ("sydney water", "Water charges"),
+                ("yarra valley water", "Water charges"),
+                ("urban utilities", "Water charges"),
+                ("water corporation", "Water charges"),
+                ("land tax", "Land tax"),
+                ("revenue nsw", "Land tax"),
+                ("state revenue office", "Land tax"),
+                ("property management", "Property agent fees"),
+                ("management fee", "Property agent fees"),
+                ("ray white", "Property agent fees"),
+                ("lj hooker", "Property agent fees"),
+                ("harcourts", "Property agent fees"),
+                ("strata", "Body corporate fees"),
+                ("body corporate", "Body corporate fees"),
+                ("owners corporation", "Body corporate fees"),
+                ("landlord insurance", "Landlord insurance"),
+                ("terri scheer", "Landlord insurance"),
+                ("pest", "Pest control"),
+                ("realestate.com.au", "Advertising for tenants"),
+                ("domain.com.au", "Advertising for tenants"),
+                ("plumbing", "Repairs and maintenance"),
+                ("electrical", "Repairs and maintenance"),
+            ],
+        ),
+        pack(
+            "primary_producer",
+            "Primary producer",
+            "Farm inputs, livestock and machinery for primary production businesses",
+            &[
+                ("Fodder and feed", "Business schedule: Purchases and other costs", 100.0),
+                ("Fertiliser", "Business schedule: Purchases and other costs", 100.0),
+                ("Seed", "Business schedule: Purchases and other costs", 100.0),
+                ("Farm chemicals", "Business schedule: Purchases and other costs", 100.0),
+                ("Veterinary", BUSINESS, 100.0),
+                ("Livestock purchases", "Business schedule: Purchases and other costs", 100.0),
+                ("Farm fuel", "Business schedule: All other expenses", 100.0),
+                ("Machinery repairs", "Business schedule: Repairs and maintenance", 100.0),
+                ("Fencing and water facilities", "Primary production: Fencing and water facility assets", 100.0),
+                ("Farm merchandise", "Business schedule: Purchases and other costs", 100.0),
+            ],
+            &[
+                ("ridley", "Fodder and feed"),
+                ("stockfeed", "Fodder and feed"),
+                ("hay", "Fodder and feed"),
+                ("incitec", "Fertiliser"),
+                ("fertiliser", "Fertiliser"),
+                ("seed", "Seed"),
+                ("glyphosate", "Farm chemicals"),
+                ("chemical", "Farm chemicals"),
+                ("veterinary", "Veterinary"),
+                ("vet clinic", "Veterinary"),
+                ("saleyard", "Livestock purchases"),
+                ("livestock", "Livestock purchases"),
+                ("diesel", "Farm fuel"),
+                ("john deere", "Machinery repairs"),
+                ("kubota", "Machinery repairs"),
+                ("case ih", "Machinery repairs"),
+                ("waratah", "Fencing and water facilities"),
+                ("fencing", "Fencing and water facilities"),
+                ("water tank", "Fencing and water facilities"),
+                ("elders", "Farm merchandise"),
+                ("nutrien", "Farm merchandise"),
+            ],
+        ),
+    ]
+}
+
+/// Find a built-in pack by ID
+pub fn find_pack(id: &str) -> Option<CategoryPack> {
+    built_in_packs().into_iter().find(|p| p.id == id)
+}
+
+/// Categories added by an entity's installed packs
+pub fn entity_categories(entity: &Entity) -> Vec<PackCategory> {
+    let mut categories: Vec<PackCategory> = Vec::new();
+    for pack in entity.category_packs.iter().filter_map(|id| find_pack(id)) {
+        for category in pack.categories {
+            if !categories.iter().any(|c| c.name == category.name) {
+                categories.push(category);
+            }
+        }
+    }
+    categories
+}
+
+/// Suggest a category for a document from the entity's installed packs.
+/// Vendor matches win over body text matches, and longer keywords over
+/// shorter ones.
+pub fn suggest_category(entity: &Entity, doc: &StoredDocument) -> Option<CategorySuggestion> {
+    let vendor = doc.vendor.as_deref().unwrap_or("").to_lowercase();
+    let body = doc
+        .invoice
+        .as_ref()
+        .map(|i| i.raw_text.as_str())
+        .or(doc.receipt.as_ref().map(|r| r.raw_text.as_str()))
+        .unwrap_or("")
+        .to_lowercase();
+    let vendor = format!(" {} ", vendor);
+    let body = format!(" {} ", body);
+
+    let mut best: Option<(bool, usize, CategorySuggestion)> = None;
+    for pack in entity.category_packs.iter().filter_map(|id| find_pack(id)) {
+        for rule in &pack.rules {
+            let matched_vendor = vendor.contains(&rule.keyword);
+            if !matched_vendor && !body.contains(&rule.keyword) {
+                continue;
+            }
+            let rank = (matched_vendor, rule.keyword.len());
+            if best.as_ref().is_some_and(|(v, len, _)| (*v, *len) >= rank) {
+                continue;
+            }
+            let Some(category) = pack.categories.iter().find(|c| c.name == rule.category) else {
+                continue;
+            };
+            best = Some((
+                matched_vendor,
+                rule.keyword.len(),
+                CategorySuggestion {
+                    category: category.name.clone(),
+                    ato_label: category.ato_label.clone(),
+                    business_use_percent: category.business_use_percent,
+                    pack_id: pack.id.clone(),
+                    matched_keyword: rule.keyword.trim().to_string(),
+                    matched_vendor,
+                    rule_id: None,
+                },
+            ));
+        }
+    }
+    best.map(|(_, _, suggestion)| suggestion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules_reference_pack_categories() {
+        for pack in built_in_packs() {
+            for rule in &pack.rules {
+                assert!(
+                    pack.categories.iter().any(|c| c.name == rule.category),
+                    "{}: rule '{}' targets unknown category '{}'",
+                    pack.id,
+                    rule.keyword,
+                    rule.category
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_suggestions_depend_on_entity_packs() {
+        let doc = StoredDocument {
+            vendor: Some("Shell Coles Express".to_string()),
+            ..Default::default()
+        };
+        let mut entity = Entity {
+            category_packs: vec!["rideshare".to_string()],
+            ..Default::default()
+        };
+
+        let suggestion = suggest_category(&entity, &doc).unwrap();
+        assert_eq!(suggestion.category, "Fuel");
+        assert_eq!(suggestion.business_use_percent, 80.0);
+
+        entity.category_packs = vec!["tradie".to_string()];
+        assert_eq!(suggest_category(&entity, &doc).unwrap().category, "Work vehicle fuel");
+
+        entity.category_packs.clear();
+        assert!(suggest_category(&entity, &doc).is_none());
+    }
+}
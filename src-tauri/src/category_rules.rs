@@ -0,0 +1,488 @@
+//! Category Rules Module
+//!
+//! User-defined categorization rules such as "vendor contains BP and total
+//! under $200 is Vehicle:Fuel at 100% business use". Every condition of a
+//! rule must hold. Rules are tried in priority order (lowest number first)
+//! and the first that matches sets the category, ahead of any keyword rules
+//! from installed category packs. A dry run reports how each rule fared
+//! against a document without changing it.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::category_packs::{self, CategorySuggestion};
+use crate::documents::{DocumentKind, StoredDocument};
+use crate::entities::Entity;
+use crate::storage;
+
+static RULES_LOCK: Mutex<()> = Mutex::new(());
+
+/// Document value a condition tests
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleField {
+    Vendor,
+    /// OCR or PDF text of the document
+    Text,
+    Total,
+    Gst,
+    /// Vendor ABN from an invoice, digits only
+    Abn,
+    /// `receipt` or `invoice`
+    Kind,
+}
+
+impl RuleField {
+    fn is_numeric(self) -> bool {
+        matches!(self, RuleField::Total | RuleField::Gst)
+    }
+
+    fn value(self, doc: &StoredDocument) -> Option<String> {
+        match self {
+            RuleField::Vendor => doc.vendor.clone(),
+            RuleField::Text => doc
+                .invoice
+                .as_ref()
+                .map(|i| i.raw_text.clone())
+                .or_else(|| doc.receipt.as_ref().map(|r| r.raw_text.clone())),
+            RuleField::Total => doc.total.map(|t| t.to_string()),
+            RuleField::Gst => doc.gst.map(|g| g.to_string()),
+            RuleField::Abn => doc.invoice.as_ref()?.abn.as_ref().map(|abn| abn.value.replace(' ', "")),
+            RuleField::Kind => Some(
+                match doc.kind {
+                    DocumentKind::Receipt => "receipt",
+                    DocumentKind::Invoice => "invoice",
+                }
+                .to_string(),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleOperator {
+    Contains,
+    NotContains,
+    Equals,
+    StartsWith,
+    LessThan,
+    LessOrEqual,
+    GreaterThan,
+    GreaterOrEqual,
+}
+
+impl RuleOperator {
+    fn is_numeric(self) -> bool {
+        matches!(
+            self,
+            RuleOperator::LessThan | RuleOperator::LessOrEqual | RuleOperator::GreaterThan | RuleOperator::GreaterOrEqual
+        )
+    }
+}
+
+/// One test against a document; text comparisons ignore case
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RuleCondition {
+    pub field: RuleField,
+    pub operator: RuleOperator,
+    pub value: String,
+}
+
+impl RuleCondition {
+    fn validate(&self) -> Result<(), String> {
+        if self.value.trim().is_empty() {
+            return Err("Rule conditions need a value".to_string());
+        }
+        let numeric_comparison = self.operator.is_numeric() || self.operator == RuleOperator::Equals;
+        let mismatched = if self.field.is_numeric() { !numeric_comparison } else { self.operator.is_numeric() };
+        if mismatched {
+            return Err(format!("{:?} cannot be compared with {:?}", self.field, self.operator));
+        }
+        if self.field.is_numeric() {
+            self.value
+                .trim()
+                .trim_start_matches('$')
+                .parse::<f64>()
+                .map_err(|_| format!("'{}' is not an amount", self.value))?;
+        }
+        Ok(())
+    }
+
+    /// Whether the document's value passes; a missing value never does,
+    /// except for `NotContains`
+    fn test(&self, actual: Option<&str>) -> bool {
+        let Some(actual) = actual else {
+            return self.operator == RuleOperator::NotContains;
+        };
+        if self.field.is_numeric() {
+            let (Ok(actual), Ok(expected)) =
+                (actual.parse::<f64>(), self.value.trim().trim_start_matches('$').parse::<f64>())
+            else {
+                return false;
+            };
+            return match self.operator {
+                RuleOperator::LessThan => actual < expected,
+                RuleOperator::LessOrEqual => actual <= expected,
+                RuleOperator::GreaterThan => actual > expected,
+                RuleOperator::GreaterOrEqual => actual >= expected,
+                _ => (actual - expected).abs() < 0.005,
+            };
+        }
+        let actual = actual.to_lowercase();
+        let expected = self.value.trim().to_lowercase();
+        match self.operator {
+            RuleOperator::Contains => actual.contains(&expected),
+            RuleOperator::NotContains => !actual.contains(&expected),
+            RuleOperator::Equals => actual.trim() == expected,
+            RuleOperator::StartsWith => actual.trim_start().starts_with(&expected),
+            _ => false,
+        }
+    }
+}
+
+/// A user-defined categorization rule
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct CategoryRule {
+    /// Empty when creating a rule
+    pub id: String,
+    pub name: String,
+    /// Lower numbers are tried first
+    pub priority: i32,
+    pub enabled: bool,
+    /// Only applies to this entity's documents; all documents when unset
+    pub entity_id: Option<String>,
+    /// All must hold for the rule to fire
+    pub conditions: Vec<RuleCondition>,
+    pub category: String,
+    /// Business-use share, 0-100; the category's pack default when unset
+    pub business_use_percent: Option<f64>,
+    pub created_at: String,
+}
+
+impl Default for CategoryRule {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            name: String::new(),
+            priority: 0,
+            enabled: true,
+            entity_id: None,
+            conditions: Vec::new(),
+            category: String::new(),
+            business_use_percent: None,
+            created_at: String::new(),
+        }
+    }
+}
+
+impl CategoryRule {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.category.trim().is_empty() {
+            return Err("A rule needs a category".to_string());
+        }
+        if self.conditions.is_empty() {
+            return Err("A rule needs at least one condition".to_string());
+        }
+        if self.business_use_percent.is_some_and(|p| !(0.0..=100.0).contains(&p)) {
+            return Err("Business use must be between 0 and 100%".to_string());
+        }
+        self.conditions.iter().try_for_each(RuleCondition::validate)
+    }
+
+    fn applies_to(&self, doc: &StoredDocument, entity_id: Option<&str>) -> bool {
+        self.entity_id.is_none() || self.entity_id.as_deref() == doc.entity_id.as_deref().or(entity_id)
+    }
+
+    fn to_match(&self) -> RuleMatch {
+        RuleMatch {
+            rule_id: self.id.clone(),
+            rule_name: self.name.clone(),
+            category: self.category.clone(),
+            business_use_percent: self.business_use_percent,
+        }
+    }
+}
+
+/// The rule that sets a document's category
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RuleMatch {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub category: String,
+    pub business_use_percent: Option<f64>,
+}
+
+/// How one condition fared in a dry run
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ConditionOutcome {
+    pub condition: RuleCondition,
+    /// The document's value for the field
+    pub actual: Option<String>,
+    pub passed: bool,
+}
+
+/// How one rule fared in a dry run
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RuleOutcome {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub priority: i32,
+    /// Why the rule wasn't tested (disabled or for another entity)
+    pub skipped: Option<String>,
+    pub conditions: Vec<ConditionOutcome>,
+    pub matched: bool,
+    /// The first matching rule, which sets the category
+    pub fired: bool,
+}
+
+/// Which rule would categorize a document, and why the others wouldn't
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RuleDryRun {
+    pub document_id: String,
+    pub fired: Option<RuleMatch>,
+    /// Every rule in priority order
+    pub rules: Vec<RuleOutcome>,
+}
+
+/// Test every rule against a document. `entity_id` stands in for the
+/// document's entity when it has none.
+pub fn dry_run(rules: &[CategoryRule], doc: &StoredDocument, entity_id: Option<&str>) -> RuleDryRun {
+    let mut fired = None;
+    let outcomes = rules
+        .iter()
+        .map(|rule| {
+            let skipped = if !rule.enabled {
+                Some("Rule is disabled".to_string())
+            } else if !rule.applies_to(doc, entity_id) {
+                Some("Rule is for another entity".to_string())
+            } else {
+                None
+            };
+            let conditions: Vec<ConditionOutcome> = match skipped {
+                Some(_) => Vec::new(),
+                None => rule
+                    .conditions
+                    .iter()
+                    .map(|condition| {
+                        let actual = condition.field.value(doc);
+                        ConditionOutcome {
+                            passed: condition.test(actual.as_deref()),
+                            condition: condition.clone(),
+                            actual,
+                        }
+                    })
+                    .collect(),
+            };
+            let matched = skipped.is_none() && !conditions.is_empty() && conditions.iter().all(|c| c.passed);
+            let fires = matched && fired.is_none();
+            if fires {
+                fired = Some(rule.to_match());
+            }
+            RuleOutcome {
+                rule_id: rule.id.clone(),
+                rule_name: rule.name.clone(),
+                priority: rule.priority,
+                skipped,
+                conditions,
+                matched,
+                fired: fires,
+            }
+        })
+        .collect();
+    RuleDryRun {
+        document_id: doc.id.clone(),
+        fired,
+        rules: outcomes,
+    }
+}
+
+/// The first rule, in priority order, that matches a document
+pub fn evaluate(rules: &[CategoryRule], doc: &StoredDocument, entity_id: Option<&str>) -> Option<RuleMatch> {
+    rules
+        .iter()
+        .find(|rule| {
+            rule.enabled
+                && rule.applies_to(doc, entity_id)
+                && !rule.conditions.is_empty()
+                && rule.conditions.iter().all(|c| c.test(c.field.value(doc).as_deref()))
+        })
+        .map(CategoryRule::to_match)
+}
+
+/// Category suggestion for a rule match. A rule without its own business-use
+/// share takes the default of the same-named category in the entity's packs,
+/// else 100%.
+pub fn suggestion(rule_match: &RuleMatch, entity: Option<&Entity>) -> CategorySuggestion {
+    let pack_category = entity
+        .map(category_packs::entity_categories)
+        .and_then(|categories| categories.into_iter().find(|c| c.name == rule_match.category));
+    CategorySuggestion {
+        category: rule_match.category.clone(),
+        ato_label: pack_category.as_ref().map(|c| c.ato_label.clone()).unwrap_or_default(),
+        business_use_percent: rule_match
+            .business_use_percent
+            .or(pack_category.map(|c| c.business_use_percent))
+            .unwrap_or(100.0),
+        pack_id: String::new(),
+        matched_keyword: String::new(),
+        matched_vendor: false,
+        rule_id: Some(rule_match.rule_id.clone()),
+    }
+}
+
+/// JSON-backed list of category rules
+pub struct RuleStore {
+    path: PathBuf,
+    rules: Vec<CategoryRule>,
+}
+
+impl RuleStore {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            rules: storage::load_json(path)?,
+        })
+    }
+
+    pub fn open_default() -> Result<Self, String> {
+        Self::open(&storage::get_data_directory()?.join("category_rules.json"))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        storage::save_json(&self.path, &self.rules)
+    }
+
+    /// Rules in the order they are tried
+    pub fn list(&self) -> Vec<CategoryRule> {
+        let mut rules = self.rules.clone();
+        rules.sort_by(|a, b| {
+            a.priority
+                .cmp(&b.priority)
+                .then_with(|| a.created_at.cmp(&b.created_at))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        rules
+    }
+
+    /// Add a rule, or replace the one with the same ID
+    pub fn upsert(&mut self, mut rule: CategoryRule) -> Result<CategoryRule, String> {
+        rule.validate()?;
+        rule.name = rule.name.trim().to_string();
+        rule.category = rule.category.trim().to_string();
+        if rule.name.is_empty() {
+            rule.name = rule.category.clone();
+        }
+        match self.rules.iter_mut().find(|r| !rule.id.is_empty() && r.id == rule.id) {
+            Some(existing) => {
+                rule.created_at = existing.created_at.clone();
+                *existing = rule.clone();
+            }
+            None => {
+                rule.id = storage::generate_id("rule");
+                rule.created_at = storage::now_timestamp();
+                self.rules.push(rule.clone());
+            }
+        }
+        Ok(rule)
+    }
+
+    pub fn delete(&mut self, id: &str) -> bool {
+        let before = self.rules.len();
+        self.rules.retain(|r| r.id != id);
+        self.rules.len() != before
+    }
+}
+
+/// Run a closure against the default rule store while holding its lock
+pub fn with_rules<R>(f: impl FnOnce(&mut RuleStore) -> Result<R, String>) -> Result<R, String> {
+    let _guard = RULES_LOCK.lock().map_err(|_| "Category rules lock poisoned".to_string())?;
+    let mut store = RuleStore::open_default()?;
+    f(&mut store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+
+    fn condition(field: RuleField, operator: RuleOperator, value: &str) -> RuleCondition {
+        RuleCondition {
+            field,
+            operator,
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_rules_fire_in_priority_order_with_all_conditions() {
+        let dir = storage::test_dir();
+        let path = dir.join("rules.json");
+        let mut store = RuleStore::open(&path).unwrap();
+        let fuel = store
+            .upsert(CategoryRule {
+                name: "BP fuel".to_string(),
+                priority: 10,
+                conditions: vec![
+                    condition(RuleField::Vendor, RuleOperator::Contains, "BP"),
+                    condition(RuleField::Total, RuleOperator::LessThan, "200"),
+                ],
+                category: "Vehicle:Fuel".to_string(),
+                business_use_percent: Some(100.0),
+                ..CategoryRule::default()
+            })
+            .unwrap();
+        store
+            .upsert(CategoryRule {
+                priority: 20,
+                conditions: vec![condition(RuleField::Kind, RuleOperator::Equals, "receipt")],
+                category: "General".to_string(),
+                ..CategoryRule::default()
+            })
+            .unwrap();
+        let bad = CategoryRule {
+            conditions: vec![condition(RuleField::Vendor, RuleOperator::LessThan, "5")],
+            category: "Oops".to_string(),
+            ..CategoryRule::default()
+        };
+        assert!(store.upsert(bad).is_err());
+        store.save().unwrap();
+        let rules = RuleStore::open(&path).unwrap().list();
+        assert_eq!(rules.len(), 2);
+
+        let mut doc = StoredDocument {
+            id: "doc".to_string(),
+            vendor: Some("BP Connect Parramatta".to_string()),
+            total: Some(Money::from_cents(8540)),
+            ..StoredDocument::default()
+        };
+        let fired = evaluate(&rules, &doc, None).unwrap();
+        assert_eq!(fired.rule_id, fuel.id);
+        assert_eq!(fired.category, "Vehicle:Fuel");
+        let rideshare = Entity {
+            category_packs: vec!["rideshare".to_string()],
+            ..Entity::default()
+        };
+        let suggested = suggestion(&fired, Some(&rideshare));
+        assert_eq!(suggested.business_use_percent, 100.0);
+        assert_eq!(suggested.rule_id.as_deref(), Some(fuel.id.as_str()));
+
+        // Over $200 the fuel rule fails its second condition and the next rule fires
+        doc.total = Some(Money::from_cents(25000));
+        let run = dry_run(&rules, &doc, None);
+        assert_eq!(run.fired.unwrap().category, "General");
+        assert!(!run.rules[0].matched);
+        assert_eq!(run.rules[0].conditions.iter().map(|c| c.passed).collect::<Vec<_>>(), vec![true, false]);
+        assert_eq!(run.rules[0].conditions[1].actual.as_deref(), Some("250.00"));
+        assert!(run.rules[1].fired);
+
+        doc.entity_id = Some("household".to_string());
+        let mut scoped = rules.clone();
+        scoped[1].entity_id = Some("business".to_string());
+        let run = dry_run(&scoped, &doc, None);
+        assert!(run.fired.is_none());
+        assert!(run.rules[1].skipped.is_some());
+    }
+}
@@ -0,0 +1,269 @@
+//! Pre-import Classifier Module
+//!
+//! Cheap keyword and structure scoring of a document's first page, run
+//! before OCR or extraction. Contracts, newsletters and other non-financial
+//! files picked up by bulk imports are routed to a skipped list instead of
+//! being processed and stored.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::invoice;
+use crate::storage;
+
+static SKIPPED_LOCK: Mutex<()> = Mutex::new(());
+
+/// Documents scoring below this are treated as non-financial
+const FINANCIAL_THRESHOLD: f64 = 1.0;
+
+/// Only the start of long documents is scored
+const MAX_SCORED_CHARS: usize = 4_000;
+
+const FINANCIAL_TERMS: [(&str, f64); 14] = [
+    ("tax invoice", 2.0),
+    ("invoice", 1.0),
+    ("receipt", 1.0),
+    ("abn", 1.0),
+    ("gst", 1.0),
+    ("subtotal", 1.0),
+    ("total", 0.5),
+    ("amount due", 1.0),
+    ("balance due", 1.0),
+    ("eftpos", 1.0),
+    ("paid", 0.5),
+    ("qty", 0.5),
+    ("statement", 0.5),
+    ("bpay", 1.0),
+];
+
+const NON_FINANCIAL_TERMS: [(&str, f64); 12] = [
+    ("unsubscribe", 2.0),
+    ("view in browser", 1.5),
+    ("newsletter", 1.5),
+    ("agreement", 1.0),
+    ("hereby", 1.0),
+    ("whereas", 1.5),
+    ("clause", 1.0),
+    ("the parties", 1.0),
+    ("terms and conditions", 0.5),
+    ("privacy policy", 0.5),
+    ("dear", 0.5),
+    ("signature", 0.5),
+];
+
+/// Outcome of scoring a document
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Classification {
+    pub likely_financial: bool,
+    pub score: f64,
+    /// Terms and structures that suggested a financial document
+    pub financial_signals: Vec<String>,
+    /// Terms that suggested something else
+    pub non_financial_signals: Vec<String>,
+}
+
+/// A file held back from import
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SkippedImport {
+    pub id: String,
+    pub path: String,
+    pub score: f64,
+    pub reason: String,
+    pub skipped_at: String,
+}
+
+/// Files to import and files set aside
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PreImportResult {
+    pub accepted: Vec<String>,
+    pub skipped: Vec<SkippedImport>,
+}
+
+fn amount_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\$\s?\d[\d,]*\.\d{2}\b").unwrap())
+}
+
+/// Score the first page of text
+pub fn classify_text(text: &str) -> Classification {
+    let first_page = text.split('\x0c').next().unwrap_or("");
+    let sample: String = first_page.chars().take(MAX_SCORED_CHARS).collect();
+    let lower = sample.to_lowercase();
+
+    let mut score = 0.0;
+    let mut financial_signals = Vec::new();
+    let mut non_financial_signals = Vec::new();
+
+    for (term, weight) in FINANCIAL_TERMS {
+        if lower.contains(term) {
+            score += weight;
+            financial_signals.push(term.to_string());
+        }
+    }
+    for (term, weight) in NON_FINANCIAL_TERMS {
+        if lower.contains(term) {
+            score -= weight;
+            non_financial_signals.push(term.to_string());
+        }
+    }
+
+    // Several currency amounts are a strong structural signal
+    let amounts = amount_pattern().find_iter(&sample).count();
+    if amounts > 0 {
+        score += (amounts as f64 * 0.5).min(2.0);
+        financial_signals.push(format!("{} amounts", amounts));
+    }
+
+    // Long prose with few amounts reads like a letter or contract
+    let words = sample.split_whitespace().count();
+    if words > 400 && amounts < 2 {
+        score -= 1.0;
+        non_financial_signals.push("long prose".to_string());
+    }
+
+    Classification {
+        likely_financial: score >= FINANCIAL_THRESHOLD,
+        score,
+        financial_signals,
+        non_financial_signals,
+    }
+}
+
+/// Classify a file before import. Files without a readable text layer
+/// (such as photos) are always let through to OCR.
+pub fn classify_file(path: &Path) -> Result<Option<Classification>, String> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+
+    let text = match extension.as_str() {
+        "pdf" => match invoice::extract_pdf_text(&path.to_string_lossy()) {
+            Ok(text) => text,
+            Err(_) => return Ok(None),
+        },
+        "txt" | "eml" | "html" | "htm" => {
+            let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            String::from_utf8_lossy(&bytes).to_string()
+        }
+        _ => return Ok(None),
+    };
+
+    if text.trim().is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(classify_text(&text)))
+}
+
+/// JSON-backed list of files skipped at import
+pub struct SkippedStore {
+    path: PathBuf,
+    skipped: Vec<SkippedImport>,
+}
+
+impl SkippedStore {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            skipped: storage::load_json(path)?,
+        })
+    }
+
+    pub fn open_default() -> Result<Self, String> {
+        Self::open(&storage::get_data_directory()?.join("skipped_imports.json"))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        storage::save_json(&self.path, &self.skipped)
+    }
+
+    pub fn list(&self) -> Vec<SkippedImport> {
+        self.skipped.clone()
+    }
+
+    /// Record a skipped file, replacing any earlier entry for the same path
+    pub fn add(&mut self, path: &str, classification: &Classification) -> SkippedImport {
+        self.skipped.retain(|s| s.path != path);
+        let signals = if classification.non_financial_signals.is_empty() {
+            "no invoice or receipt terms found".to_string()
+        } else {
+            classification.non_financial_signals.join(", ")
+        };
+        let skipped = SkippedImport {
+            id: storage::generate_id("skipped"),
+            path: path.to_string(),
+            score: classification.score,
+            reason: format!("Likely not a financial document ({})", signals),
+            skipped_at: storage::now_timestamp(),
+        };
+        self.skipped.push(skipped.clone());
+        skipped
+    }
+
+    /// Remove an entry so the file can be imported after all
+    pub fn restore(&mut self, id: &str) -> Option<SkippedImport> {
+        let index = self.skipped.iter().position(|s| s.id == id)?;
+        Some(self.skipped.remove(index))
+    }
+}
+
+/// Run a closure against the default skipped list while holding its lock
+pub fn with_skipped<R>(f: impl FnOnce(&mut SkippedStore) -> Result<R, String>) -> Result<R, String> {
+    let _guard = SKIPPED_LOCK.lock().map_err(|_| "Skipped list lock poisoned".to_string())?;
+    let mut store = SkippedStore::open_default()?;
+    f(&mut store)
+}
+
+/// Split candidate files into those worth importing and those to skip
+pub fn preclassify(paths: &[String], store: &mut SkippedStore) -> Result<PreImportResult, String> {
+    let mut result = PreImportResult::default();
+    for path in paths {
+        match classify_file(Path::new(path))? {
+            Some(classification) if !classification.likely_financial => {
+                result.skipped.push(store.add(path, &classification));
+            }
+            _ => result.accepted.push(path.clone()),
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classifies_invoice_and_newsletter() {
+        let invoice = "TAX INVOICE\nAcme Pty Ltd ABN 51 824 753 556\nSubtotal $100.00\nGST $10.00\nTotal $110.00";
+        let result = classify_text(invoice);
+        assert!(result.likely_financial);
+        assert!(result.financial_signals.contains(&"tax invoice".to_string()));
+
+        let newsletter = "Dear customer,\nOur spring newsletter is here! View in browser.\nUnsubscribe | Privacy policy";
+        let result = classify_text(newsletter);
+        assert!(!result.likely_financial);
+        assert!(result.non_financial_signals.contains(&"unsubscribe".to_string()));
+    }
+
+    #[test]
+    fn test_preclassify_routes_to_skipped_list() {
+        let dir = storage::test_dir();
+        let contract = dir.join("lease.txt");
+        fs::write(&contract, "This agreement is made between the parties. Whereas the lessor hereby agrees...").unwrap();
+        let photo = dir.join("receipt.jpg");
+
+        let mut store = SkippedStore::open(&dir.join("skipped_imports.json")).unwrap();
+        let paths = vec![contract.to_string_lossy().to_string(), photo.to_string_lossy().to_string()];
+        let result = preclassify(&paths, &mut store).unwrap();
+
+        assert_eq!(result.accepted, vec![paths[1].clone()]);
+        assert_eq!(result.skipped.len(), 1);
+        assert!(store.restore(&result.skipped[0].id).is_some());
+        assert!(store.list().is_empty());
+    }
+}
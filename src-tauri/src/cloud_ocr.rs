@@ -0,0 +1,510 @@
+//! Cloud OCR Module
+//!
+//! Optional cloud recognition for receipts the device reads poorly. Azure AI
+//! Vision and Google Cloud Vision are built in with the `ocr-azure` and
+//! `ocr-google` features, and used as a fallback only when one is chosen in
+//! settings, its API key is saved and the network policy allows it. The
+//! photo itself is uploaded, so the fallback is off by default. API keys are
+//! kept in the keychain, never in settings.json. Providers make blocking
+//! requests and are only attached to engines running on OCR job threads;
+//! each request gives up after `REQUEST_TIMEOUT` so a stalled connection
+//! can't hold a pooled engine.
+
+#![cfg_attr(not(any(feature = "ocr-azure", feature = "ocr-google")), allow(dead_code))]
+
+use serde::{Deserialize, Serialize};
+
+use crate::keychain::Keychain;
+use crate::network::{self, NetworkFeature};
+use crate::ocr::{OcrLine, OcrProvider, OcrWord};
+use crate::settings::AppSettings;
+
+/// Local results below this overall confidence are re-read in the cloud
+const DEFAULT_MIN_CONFIDENCE: f64 = 0.6;
+
+/// Keychain service cloud OCR API keys are stored under, keyed by backend
+pub const KEYCHAIN_SERVICE: &str = "tally-cloud-ocr";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CloudOcrBackend {
+    Azure,
+    Google,
+}
+
+impl CloudOcrBackend {
+    fn account(self) -> &'static str {
+        match self {
+            Self::Azure => "azure",
+            Self::Google => "google",
+        }
+    }
+}
+
+/// Cloud fallback settings
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct CloudOcrSettings {
+    /// Service to retry with; `None` keeps every scan on the device
+    pub backend: Option<CloudOcrBackend>,
+    /// Retry when the local result's overall confidence is below this
+    pub min_confidence: f64,
+    /// Azure AI Vision resource endpoint, e.g.
+    /// `https://my-resource.cognitiveservices.azure.com`
+    pub azure_endpoint: Option<String>,
+}
+
+impl Default for CloudOcrSettings {
+    fn default() -> Self {
+        Self {
+            backend: None,
+            min_confidence: DEFAULT_MIN_CONFIDENCE,
+            azure_endpoint: None,
+        }
+    }
+}
+
+/// Which backends have an API key saved in the keychain
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct CloudOcrKeys {
+    pub azure: bool,
+    pub google: bool,
+}
+
+/// Save a backend's API key to the keychain, or remove it when `key` is
+/// `None` or empty
+pub fn save_key(keychain: &dyn Keychain, backend: CloudOcrBackend, key: Option<&str>) -> Result<(), String> {
+    match key.map(str::trim).filter(|k| !k.is_empty()) {
+        Some(key) => keychain.set(KEYCHAIN_SERVICE, backend.account(), key),
+        None => keychain.delete(KEYCHAIN_SERVICE, backend.account()),
+    }
+}
+
+/// Whether each backend has an API key saved
+pub fn saved_keys(keychain: &dyn Keychain) -> Result<CloudOcrKeys, String> {
+    let saved = |backend: CloudOcrBackend| keychain.get(KEYCHAIN_SERVICE, backend.account()).map(|k| k.is_some());
+    Ok(CloudOcrKeys {
+        azure: saved(CloudOcrBackend::Azure)?,
+        google: saved(CloudOcrBackend::Google)?,
+    })
+}
+
+#[cfg(any(feature = "ocr-azure", feature = "ocr-google"))]
+fn saved_key(keychain: &dyn Keychain, backend: CloudOcrBackend) -> Option<String> {
+    keychain
+        .get(KEYCHAIN_SERVICE, backend.account())
+        .map_err(|e| log::warn!("Failed to read the cloud OCR key: {}", e))
+        .ok()
+        .flatten()
+}
+
+#[cfg(feature = "ocr-azure")]
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+}
+
+/// The configured cloud provider, if it is built in, has an API key and
+/// may use the network
+#[cfg_attr(not(any(feature = "ocr-azure", feature = "ocr-google")), allow(unused_variables))]
+pub fn fallback_provider(settings: &AppSettings, keychain: &dyn Keychain) -> Option<Box<dyn OcrProvider>> {
+    let backend = settings.cloud_ocr.backend?;
+    if !network::check_access(NetworkFeature::CloudOcr, settings).allowed {
+        return None;
+    }
+    match backend {
+        #[cfg(feature = "ocr-azure")]
+        CloudOcrBackend::Azure => Some(Box::new(azure::AzureVision {
+            endpoint: non_empty(&settings.cloud_ocr.azure_endpoint)?,
+            key: saved_key(keychain, CloudOcrBackend::Azure)?,
+        })),
+        #[cfg(feature = "ocr-google")]
+        CloudOcrBackend::Google => Some(Box::new(google::GoogleVision {
+            api_key: saved_key(keychain, CloudOcrBackend::Google)?,
+        })),
+        // Chosen in settings but not built in
+        #[cfg(not(feature = "ocr-azure"))]
+        CloudOcrBackend::Azure => None,
+        #[cfg(not(feature = "ocr-google"))]
+        CloudOcrBackend::Google => None,
+    }
+}
+
+/// Longest a cloud request may take, upload and response included
+#[cfg(any(feature = "ocr-azure", feature = "ocr-google"))]
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// HTTP agent shared by the cloud providers
+#[cfg(any(feature = "ocr-azure", feature = "ocr-google"))]
+fn agent() -> &'static ureq::Agent {
+    static AGENT: std::sync::OnceLock<ureq::Agent> = std::sync::OnceLock::new();
+    AGENT.get_or_init(|| ureq::Agent::config_builder().timeout_global(Some(REQUEST_TIMEOUT)).build().into())
+}
+
+/// Describe a failed request, reporting a timeout like any other failure
+#[cfg(any(feature = "ocr-azure", feature = "ocr-google"))]
+fn request_error(provider: &str, error: ureq::Error) -> String {
+    match error {
+        ureq::Error::Timeout(_) => {
+            format!("{} didn't respond within {} seconds", provider, REQUEST_TIMEOUT.as_secs())
+        }
+        e => format!("{} request failed: {}", provider, e),
+    }
+}
+
+/// Word box from the corners of a polygon: left, top, width, height
+fn polygon_box(points: impl IntoIterator<Item = (f64, f64)>) -> [u32; 4] {
+    let (mut left, mut top, mut right, mut bottom) = (f64::MAX, f64::MAX, 0.0f64, 0.0f64);
+    for (x, y) in points {
+        (left, top, right, bottom) = (left.min(x), top.min(y), right.max(x), bottom.max(y));
+    }
+    if left > right || top > bottom {
+        return [0; 4];
+    }
+    let (left, top) = (left.max(0.0), top.max(0.0));
+    [left as u32, top as u32, (right - left) as u32, (bottom - top) as u32]
+}
+
+/// A line from its words, with the mean word confidence
+fn line_from_words(words: Vec<OcrWord>) -> Option<OcrLine> {
+    if words.is_empty() {
+        return None;
+    }
+    let confidence = words.iter().map(|w| w.confidence).sum::<f64>() / words.len() as f64;
+    Some(OcrLine {
+        text: words.iter().map(|w| w.text.as_str()).collect::<Vec<_>>().join(" "),
+        confidence,
+        words,
+    })
+}
+
+/// Azure AI Vision Image Analysis 4.0 `read` results
+#[cfg(any(feature = "ocr-azure", test))]
+mod azure {
+    use serde::Deserialize;
+
+    use super::{line_from_words, polygon_box};
+    use crate::ocr::{OcrLine, OcrWord};
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct AnalyzeResponse {
+        read_result: Option<ReadResult>,
+    }
+
+    #[derive(Deserialize)]
+    struct ReadResult {
+        #[serde(default)]
+        blocks: Vec<Block>,
+    }
+
+    #[derive(Deserialize)]
+    struct Block {
+        #[serde(default)]
+        lines: Vec<Line>,
+    }
+
+    #[derive(Deserialize)]
+    struct Line {
+        #[serde(default)]
+        words: Vec<Word>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Word {
+        text: String,
+        confidence: f64,
+        #[serde(default)]
+        bounding_polygon: Vec<Point>,
+    }
+
+    #[derive(Deserialize)]
+    struct Point {
+        x: f64,
+        y: f64,
+    }
+
+    pub fn parse_lines(body: &str) -> Result<Vec<OcrLine>, String> {
+        let response: AnalyzeResponse =
+            serde_json::from_str(body).map_err(|e| format!("Failed to parse Azure Vision response: {}", e))?;
+        let blocks = response.read_result.map(|r| r.blocks).unwrap_or_default();
+        Ok(blocks
+            .into_iter()
+            .flat_map(|block| block.lines)
+            .filter_map(|line| {
+                let words = line.words.into_iter().map(|word| OcrWord {
+                    bbox: polygon_box(word.bounding_polygon.iter().map(|p| (p.x, p.y))),
+                    text: word.text,
+                    confidence: word.confidence,
+                });
+                line_from_words(words.collect())
+            })
+            .collect())
+    }
+
+    #[cfg(feature = "ocr-azure")]
+    pub struct AzureVision {
+        pub endpoint: String,
+        pub key: String,
+    }
+
+    #[cfg(feature = "ocr-azure")]
+    impl crate::ocr::OcrProvider for AzureVision {
+        fn name(&self) -> &'static str {
+            "azure"
+        }
+
+        fn scan(&mut self, path: &str) -> Result<crate::ocr::ExtractedReceipt, String> {
+            let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            let url = format!(
+                "{}/computervision/imageanalysis:analyze?features=read&api-version=2023-10-01",
+                self.endpoint.trim_end_matches('/')
+            );
+            let body = super::agent()
+                .post(&url)
+                .header("Ocp-Apim-Subscription-Key", &self.key)
+                .header("Content-Type", "application/octet-stream")
+                .send(&bytes[..])
+                .and_then(|mut response| response.body_mut().read_to_string())
+                .map_err(|e| super::request_error("Azure Vision", e))?;
+            Ok(crate::ocr::receipt_from_lines(&parse_lines(&body)?))
+        }
+    }
+}
+
+/// Google Cloud Vision `DOCUMENT_TEXT_DETECTION` results
+#[cfg(any(feature = "ocr-google", test))]
+mod google {
+    use serde::Deserialize;
+
+    use super::{line_from_words, polygon_box};
+    use crate::ocr::{OcrLine, OcrWord};
+
+    #[derive(Deserialize)]
+    struct AnnotateResponse {
+        #[serde(default)]
+        responses: Vec<ImageResponse>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ImageResponse {
+        full_text_annotation: Option<TextAnnotation>,
+        error: Option<Status>,
+    }
+
+    #[derive(Deserialize)]
+    struct Status {
+        #[serde(default)]
+        message: String,
+    }
+
+    #[derive(Deserialize)]
+    struct TextAnnotation {
+        #[serde(default)]
+        pages: Vec<Page>,
+    }
+
+    #[derive(Deserialize)]
+    struct Page {
+        #[serde(default)]
+        blocks: Vec<Block>,
+    }
+
+    #[derive(Deserialize)]
+    struct Block {
+        #[serde(default)]
+        paragraphs: Vec<Paragraph>,
+    }
+
+    #[derive(Deserialize)]
+    struct Paragraph {
+        #[serde(default)]
+        words: Vec<Word>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Word {
+        #[serde(default)]
+        symbols: Vec<Symbol>,
+        #[serde(default)]
+        confidence: f64,
+        bounding_box: Option<Poly>,
+    }
+
+    #[derive(Deserialize)]
+    struct Symbol {
+        text: String,
+        property: Option<Property>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Property {
+        detected_break: Option<Break>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Break {
+        #[serde(rename = "type")]
+        kind: String,
+    }
+
+    #[derive(Deserialize)]
+    struct Poly {
+        #[serde(default)]
+        vertices: Vec<Vertex>,
+    }
+
+    /// Vertices at zero omit the coordinate
+    #[derive(Deserialize)]
+    struct Vertex {
+        #[serde(default)]
+        x: f64,
+        #[serde(default)]
+        y: f64,
+    }
+
+    /// Words are given per paragraph; a line ends at a word whose last
+    /// symbol is followed by a line break
+    pub fn parse_lines(body: &str) -> Result<Vec<OcrLine>, String> {
+        let response: AnnotateResponse =
+            serde_json::from_str(body).map_err(|e| format!("Failed to parse Google Vision response: {}", e))?;
+        let Some(image) = response.responses.into_iter().next() else {
+            return Ok(Vec::new());
+        };
+        if let Some(error) = image.error {
+            return Err(format!("Google Vision error: {}", error.message));
+        }
+
+        let mut lines = Vec::new();
+        let mut current = Vec::new();
+        let paragraphs = image
+            .full_text_annotation
+            .into_iter()
+            .flat_map(|a| a.pages)
+            .flat_map(|p| p.blocks)
+            .flat_map(|b| b.paragraphs);
+        for paragraph in paragraphs {
+            for word in paragraph.words {
+                let ends_line = word
+                    .symbols
+                    .last()
+                    .and_then(|s| s.property.as_ref())
+                    .and_then(|p| p.detected_break.as_ref())
+                    .is_some_and(|b| b.kind == "EOL_SURE_SPACE" || b.kind == "LINE_BREAK");
+                let vertices = word.bounding_box.map(|b| b.vertices).unwrap_or_default();
+                current.push(OcrWord {
+                    text: word.symbols.iter().map(|s| s.text.as_str()).collect(),
+                    confidence: word.confidence,
+                    bbox: polygon_box(vertices.iter().map(|v| (v.x, v.y))),
+                });
+                if ends_line {
+                    lines.extend(line_from_words(std::mem::take(&mut current)));
+                }
+            }
+            lines.extend(line_from_words(std::mem::take(&mut current)));
+        }
+        Ok(lines)
+    }
+
+    #[cfg(feature = "ocr-google")]
+    pub struct GoogleVision {
+        pub api_key: String,
+    }
+
+    #[cfg(feature = "ocr-google")]
+    fn base64(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+        for chunk in bytes.chunks(3) {
+            let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | ((b as u32) << (16 - 8 * i)));
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    out.push(ALPHABET[((n >> (18 - 6 * i)) & 63) as usize] as char);
+                } else {
+                    out.push('=');
+                }
+            }
+        }
+        out
+    }
+
+    #[cfg(feature = "ocr-google")]
+    impl crate::ocr::OcrProvider for GoogleVision {
+        fn name(&self) -> &'static str {
+            "google"
+        }
+
+        fn scan(&mut self, path: &str) -> Result<crate::ocr::ExtractedReceipt, String> {
+            let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            let request = serde_json::json!({
+                "requests": [{
+                    "image": { "content": base64(&bytes) },
+                    "features": [{ "type": "DOCUMENT_TEXT_DETECTION" }],
+                }]
+            });
+            let url = format!("https://vision.googleapis.com/v1/images:annotate?key={}", self.api_key);
+            let body = super::agent()
+                .post(&url)
+                .header("Content-Type", "application/json")
+                .send(request.to_string())
+                .and_then(|mut response| response.body_mut().read_to_string())
+                .map_err(|e| super::request_error("Google Vision", e))?;
+            Ok(crate::ocr::receipt_from_lines(&parse_lines(&body)?))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keychain::MemoryKeychain;
+
+    #[test]
+    fn test_cloud_responses_become_lines() {
+        let azure_body = r#"{"readResult":{"blocks":[{"lines":[
+            {"text":"TOTAL $12.50","words":[
+                {"text":"TOTAL","confidence":0.9,"boundingPolygon":[{"x":10,"y":40},{"x":60,"y":40},{"x":60,"y":52},{"x":10,"y":52}]},
+                {"text":"$12.50","confidence":0.7,"boundingPolygon":[{"x":70,"y":40},{"x":120,"y":41},{"x":120,"y":53},{"x":70,"y":52}]}
+            ]}]}]}}"#;
+        let lines = azure::parse_lines(azure_body).unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].text, "TOTAL $12.50");
+        assert!((lines[0].confidence - 0.8).abs() < 1e-9);
+        assert_eq!(lines[0].words[1].bbox, [70, 40, 50, 13]);
+
+        let google_body = r#"{"responses":[{"fullTextAnnotation":{"pages":[{"blocks":[{"paragraphs":[{"words":[
+            {"confidence":0.95,"symbols":[{"text":"C"},{"text":"a"},{"text":"f"},{"text":"e","property":{"detectedBreak":{"type":"LINE_BREAK"}}}],
+             "boundingBox":{"vertices":[{"y":5},{"x":30,"y":5},{"x":30,"y":15},{"y":15}]}},
+            {"confidence":0.85,"symbols":[{"text":"$"},{"text":"4"}]}
+        ]}]}]}]}}]}"#;
+        let lines = google::parse_lines(google_body).unwrap();
+        assert_eq!(lines.iter().map(|l| l.text.as_str()).collect::<Vec<_>>(), vec!["Cafe", "$4"]);
+        assert_eq!(lines[0].words[0].bbox, [0, 5, 30, 10]);
+        assert!(google::parse_lines(r#"{"responses":[{"error":{"message":"API key not valid"}}]}"#).is_err());
+
+        // Nothing is uploaded unless a backend is chosen and the network is allowed
+        let keychain = MemoryKeychain::default();
+        let mut settings = AppSettings::default();
+        assert!(fallback_provider(&settings, &keychain).is_none());
+        settings.cloud_ocr.backend = Some(CloudOcrBackend::Google);
+        save_key(&keychain, CloudOcrBackend::Google, Some("key")).unwrap();
+        settings.privacy_mode = true;
+        assert!(fallback_provider(&settings, &keychain).is_none());
+    }
+
+    #[test]
+    fn test_api_keys_are_kept_in_the_keychain() {
+        let keychain = MemoryKeychain::default();
+        save_key(&keychain, CloudOcrBackend::Azure, Some(" secret ")).unwrap();
+        assert_eq!(keychain.get(KEYCHAIN_SERVICE, "azure").unwrap().as_deref(), Some("secret"));
+        assert_eq!(saved_keys(&keychain).unwrap(), CloudOcrKeys { azure: true, google: false });
+
+        save_key(&keychain, CloudOcrBackend::Azure, Some("")).unwrap();
+        assert_eq!(saved_keys(&keychain).unwrap(), CloudOcrKeys::default());
+        assert!(!serde_json::to_string(&AppSettings::default()).unwrap().contains("key"));
+    }
+}
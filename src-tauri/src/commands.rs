@@ -0,0 +1,1495 @@
+//! Tauri Commands
+//!
+//! Thin command wrappers exposing the library to the frontend. Each command
+//! validates its input, then calls into the business logic modules.
+
+use tauri::{Emitter, State};
+
+use crate::documents::{
+    BulkOperation, BulkOperationResult, DocumentFilter, DocumentSortField, DocumentStatus, DocumentSummary, ManualEntry,
+    SaveError, StoredDocument,
+};
+use crate::invoice::{ExtractedInvoice, InvoiceValidationResult};
+use crate::tax_report::TaxReportSaveResult;
+use crate::settings::AppSettings;
+use crate::network::{NetworkFeature, NetworkStatus, Sourced};
+use crate::abr::AbnDetails;
+use crate::drafts::DocumentDraft;
+use crate::cadence::{MissingInvoiceAlert, VendorCadence};
+use crate::bas::{GstCreditTimingReport, PurchaseLabels};
+use crate::export::{OriginalsExportResult, TextExportFormat, TextExportResult};
+use crate::digest::DigestSaveResult;
+use crate::cash::{CashExpense, CashSortField, CashYearTotals, NewCashExpense};
+use crate::summary::FinancialYearSummary;
+use crate::rollover::RolloverResult;
+use crate::heuristics::ProfileComparison;
+use crate::package::{PackageResult, PackageVerification};
+use crate::entities::{Entity, RecipientCheck};
+use crate::time_tracking::{DraftInvoiceRequest, IssuedInvoice, NewTimeEntry, TimeEntry};
+use crate::classifier::{PreImportResult, SkippedImport};
+use crate::address::{Address, StateTotal};
+use crate::bundle::BundleResult;
+use crate::category_packs::{CategoryPack, CategorySuggestion, PackCategory};
+use crate::category_rules::{CategoryRule, RuleDryRun};
+use crate::custom_patterns::{CustomPattern, CustomPatternGroup};
+use crate::capture::QuickCaptureResult;
+use crate::csv_import::{CsvImportConfig, CsvImportResult, CsvPreview};
+use crate::timeline::{CalendarDay, TimelineEvent};
+use crate::straight_through::{AutoProcessedDigest, AutoProcessedItem};
+use crate::money::Money;
+use crate::period_locks::{AdjustmentEntry, LodgedPeriod};
+use crate::periods::BasPeriod;
+use crate::stock::{NewStockAdjustment, NewStockValue, StockAdjustment, StockValue};
+use crate::text_diff::{TextDiff, TextSource};
+use crate::saved_reports::{RegenerationResult, ReportParameters, ReportSortField, SavedReport};
+use crate::pagination::{Page, PageRequest};
+use crate::anonymize::AnonymizedExport;
+use crate::capture_metadata::{TravelDiaryEntry, VendorSuggestion};
+use crate::keychain::KeychainState;
+use crate::upload::{UploadResult, UploadTarget};
+use crate::reminders::{EscalationSchedule, InvoiceReminder, ReminderSettings};
+use crate::gst_treatment::GstTreatment;
+use crate::thumbnails::ThumbnailQueueResult;
+use crate::finance_export::{FinanceExportResult, FinanceFormat};
+use crate::share::ShareResult;
+use crate::category_clusters::DocumentCluster;
+use crate::vendor_templates::VendorTemplate;
+use crate::fy_comparison::{FyComparison, FyComparisonSaveResult};
+use crate::entity_groups::{EntityGroup, GroupReport};
+use crate::resource_limits::PowerState;
+use crate::verification::DocumentToCheck;
+use crate::cloud_ocr::{CloudOcrBackend, CloudOcrKeys};
+use crate::{
+    abr, address, anonymize, bas, bundle, cadence, capture, capture_metadata, cash, category_clusters, category_packs,
+    category_rules, classifier, cloud_ocr, csv_import, custom_patterns, digest, documents, drafts, email_import,
+    entities, entity_groups, export, finance_export, fy_comparison, heuristics, invoice, network, package, period_locks,
+    receipt, reminders, resource_limits, sandbox, saved_reports, settings, share, stock, storage, straight_through,
+    summary, timeline, tax_report, text_diff, thumbnails, time_tracking, upload, vendor_templates, verification,
+};
+
+/// Tauri command to parse a PDF invoice
+#[tauri::command]
+pub async fn parse_invoice_pdf_command(app: tauri::AppHandle, pdf_path: String) -> Result<ExtractedInvoice, String> {
+    sandbox::validate_path(&app, &pdf_path)?;
+    invoice::parse_invoice_pdf(&pdf_path)
+}
+
+/// Tauri command to parse an image invoice (via OCR text extraction)
+/// In production, this would first run OCR then parse the extracted text
+#[tauri::command]
+pub async fn parse_invoice_image_command(app: tauri::AppHandle, image_path: String) -> Result<ExtractedInvoice, String> {
+    sandbox::validate_path(&app, &image_path)?;
+    invoice::parse_invoice_image(&image_path)
+}
+
+/// Tauri command to validate extracted invoice data
+#[tauri::command]
+pub async fn validate_invoice_command(invoice: ExtractedInvoice) -> InvoiceValidationResult {
+    invoice::validate_invoice(&invoice)
+}
+
+/// Tauri command to save a tax report PDF
+#[tauri::command]
+pub async fn save_tax_report_pdf_command(
+    filename: String,
+    pdf_data: Vec<u8>,
+) -> Result<TaxReportSaveResult, String> {
+    tax_report::save_tax_report_pdf(filename, pdf_data).await
+}
+
+/// Tauri command to merge multiple PDFs into one
+#[tauri::command]
+pub async fn merge_pdfs_command(
+    app: tauri::AppHandle,
+    pdf_paths: Vec<String>,
+    output_filename: String,
+) -> Result<TaxReportSaveResult, String> {
+    sandbox::validate_paths(&app, &pdf_paths)?;
+    tax_report::merge_pdfs(pdf_paths, output_filename).await
+}
+
+/// Tauri command to open the platform share sheet for a saved report
+#[tauri::command]
+pub async fn share_report_command(app: tauri::AppHandle, file_path: String) -> Result<ShareResult, String> {
+    // The share sheet stays open until the user picks a destination
+    tauri::async_runtime::spawn_blocking(move || share::share_report(&app, &file_path))
+        .await
+        .map_err(|e| format!("Share task failed: {}", e))?
+}
+
+/// Tauri command to delete a saved report from the reports directory
+#[tauri::command]
+pub async fn delete_report_command(file_path: String) -> Result<(), String> {
+    tax_report::delete_report(file_path)
+}
+
+/// Tauri command to list reports that can be regenerated, most recent first
+#[tauri::command]
+pub async fn list_saved_reports_command() -> Result<Vec<SavedReport>, String> {
+    saved_reports::with_register(|register| Ok(register.list()))
+}
+
+/// Tauri command to fetch one sorted page of saved reports
+#[tauri::command]
+pub async fn list_saved_reports_page_command(
+    title_contains: Option<String>,
+    page: PageRequest<ReportSortField>,
+) -> Result<Page<SavedReport>, String> {
+    saved_reports::with_register(|register| register.page(title_contains.as_deref(), &page))
+}
+
+/// Tauri command to re-run a saved report against current data as a new version
+#[tauri::command]
+pub async fn regenerate_report_command(report_id: String) -> Result<RegenerationResult, String> {
+    saved_reports::regenerate(&report_id)
+}
+
+/// Tauri command to list summaries of stored documents matching a filter
+#[tauri::command]
+pub async fn list_documents_command(filter: DocumentFilter) -> Result<Vec<DocumentSummary>, String> {
+    documents::with_store(|store| Ok(store.summaries(&filter)))
+}
+
+/// Tauri command to fetch one sorted page of document summaries matching a filter
+#[tauri::command]
+pub async fn list_documents_page_command(
+    filter: DocumentFilter,
+    page: PageRequest<DocumentSortField>,
+) -> Result<Page<DocumentSummary>, String> {
+    documents::with_store(|store| store.summary_page(&filter, &page))
+}
+
+/// Tauri command to queue background thumbnails for documents without one
+#[tauri::command]
+pub async fn queue_thumbnails_command(
+    app: tauri::AppHandle,
+    document_ids: Option<Vec<String>>,
+) -> Result<ThumbnailQueueResult, String> {
+    thumbnails::queue_documents(&app, document_ids, false)
+}
+
+/// Tauri command to get thumbnails for the documents on screen, making missing ones first
+#[tauri::command]
+pub async fn request_thumbnails_command(
+    app: tauri::AppHandle,
+    document_ids: Vec<String>,
+) -> Result<ThumbnailQueueResult, String> {
+    thumbnails::queue_documents(&app, Some(document_ids), true)
+}
+
+/// Tauri command to fetch the full record for one document
+#[tauri::command]
+pub async fn get_document_command(document_id: String) -> Result<StoredDocument, String> {
+    documents::with_store(|store| {
+        store
+            .get(&document_id)
+            .cloned()
+            .ok_or_else(|| format!("Document not found: {}", document_id))
+    })
+}
+
+/// Tauri command to create or replace a stored document. An edit to a copy
+/// older than the stored revision fails with a conflict holding both versions.
+#[tauri::command]
+pub async fn save_document_command(
+    app: tauri::AppHandle,
+    mut document: StoredDocument,
+) -> Result<StoredDocument, SaveError> {
+    let stored_path = documents::with_store(|store| Ok(store.get(&document.id).and_then(|d| d.source_path.clone())))?;
+    document.source_path =
+        sandbox::validate_source_path(&app, document.source_path.as_deref(), stored_path.as_deref())?;
+    // The path has now been checked, here or when it was first saved
+    if document.capture.is_none() {
+        document.capture = document
+            .source_path
+            .as_deref()
+            .and_then(|path| capture_metadata::read_capture_metadata(std::path::Path::new(path)));
+    }
+    let saved = documents::with_store(|store| {
+        Ok(store.update_checked(document).and_then(|saved| {
+            store.save()?;
+            Ok(saved)
+        }))
+    })??;
+
+    // A reviewed invoice teaches its supplier's template where each field is printed
+    if saved.status != DocumentStatus::PendingReview {
+        let order = settings::load_settings()?.date_order;
+        let learned = vendor_templates::with_templates(|store| {
+            if store.learn_fields(&saved, order).is_some() {
+                store.save()?;
+            }
+            Ok(())
+        });
+        if let Err(e) = learned {
+            log::warn!("Failed to learn field labels from {}: {}", saved.id, e);
+        }
+    }
+
+    // The saved document supersedes any in-progress review edits
+    drafts::with_drafts(|store| {
+        if store.discard(&saved.id) {
+            store.save()?;
+        }
+        Ok(())
+    })?;
+
+    Ok(saved)
+}
+
+/// Tauri command to record a document's values by hand, either as a new
+/// document or over an existing one that couldn't be parsed. Given the
+/// revision the entry was made against, a newer save is a conflict.
+#[tauri::command]
+pub async fn save_manual_entry_command(
+    app: tauri::AppHandle,
+    document_id: Option<String>,
+    mut entry: ManualEntry,
+    revision: Option<u64>,
+) -> Result<StoredDocument, SaveError> {
+    let saved = documents::with_store(|store| {
+        let mut document = match &document_id {
+            Some(id) => store.get(id).cloned().ok_or_else(|| format!("Document not found: {}", id))?,
+            None => StoredDocument::default(),
+        };
+        entry.source_path =
+            sandbox::validate_source_path(&app, entry.source_path.as_deref(), document.source_path.as_deref())?;
+        document.revision = revision.unwrap_or(document.revision);
+        document.apply_manual_entry(entry)?;
+        Ok(store.update_checked(document).and_then(|saved| {
+            store.save()?;
+            Ok(saved)
+        }))
+    })??;
+
+    drafts::with_drafts(|store| {
+        if store.discard(&saved.id) {
+            store.save()?;
+        }
+        Ok(())
+    })?;
+
+    Ok(saved)
+}
+
+/// Tauri command to preview how the first rows of a spreadsheet CSV will be
+/// imported
+#[tauri::command]
+pub async fn preview_csv_import_command(
+    app: tauri::AppHandle,
+    path: String,
+    config: CsvImportConfig,
+    rows: Option<usize>,
+) -> Result<CsvPreview, String> {
+    let text = csv_import::read_file(&sandbox::validate_path(&app, &path)?)?;
+    csv_import::preview(&text, &config, rows.unwrap_or(csv_import::DEFAULT_PREVIEW_ROWS))
+}
+
+/// Tauri command to record every valid row of a spreadsheet CSV as a
+/// manual entry
+#[tauri::command]
+pub async fn import_csv_expenses_command(
+    app: tauri::AppHandle,
+    path: String,
+    config: CsvImportConfig,
+) -> Result<CsvImportResult, String> {
+    let text = csv_import::read_file(&sandbox::validate_path(&app, &path)?)?;
+    documents::with_store(|store| {
+        let result = csv_import::import(store, &text, &config)?;
+        store.save()?;
+        Ok(result)
+    })
+}
+
+/// Tauri command to import invoices from an email saved as an `.eml` file:
+/// its PDF attachments, or an invoice written in the body
+#[tauri::command]
+pub async fn import_email_command(app: tauri::AppHandle, path: String) -> Result<Vec<StoredDocument>, String> {
+    let path = sandbox::validate_path(&app, &path)?;
+    let raw = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let entity_id = settings::load_settings()?.active_entity_id;
+    let dir = email_import::emails_directory()?;
+    let parsed = tauri::async_runtime::spawn_blocking(move || email_import::documents_from_email(&raw, &dir, entity_id))
+        .await
+        .map_err(|e| format!("Email import task failed: {}", e))??;
+    let written: Vec<String> = parsed.iter().filter_map(|document| document.source_path.clone()).collect();
+    let saved = documents::with_store(|store| {
+        let saved = parsed.into_iter().map(|document| store.upsert(document)).collect();
+        store.save()?;
+        Ok(saved)
+    });
+    if saved.is_err() {
+        // Nothing refers to the originals unless the store was saved
+        for path in &written {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+    saved
+}
+
+/// Tauri command to apply one change to every document matching a filter
+#[tauri::command]
+pub async fn bulk_update_documents_command(
+    filter: DocumentFilter,
+    operation: BulkOperation,
+) -> Result<BulkOperationResult, String> {
+    if !matches!(operation, BulkOperation::Reextract) {
+        return documents::with_store(|store| Ok(store.apply_bulk(&filter, &operation)));
+    }
+    let selected = documents::with_store(|store| Ok(store.list(&filter)))?;
+    let extracted = tauri::async_runtime::spawn_blocking(move || documents::extract_all(&selected))
+        .await
+        .map_err(|e| format!("Re-extraction task failed: {}", e))?;
+    documents::with_store(|store| Ok(store.apply_reextraction(&filter, extracted)))
+}
+
+/// Tauri command to count documents and spend per day for the calendar heat map
+#[tauri::command]
+pub async fn get_calendar_heat_command(date_from: String, date_to: String) -> Result<Vec<CalendarDay>, String> {
+    let (from, to) = timeline::parse_range(&date_from, &date_to)?;
+    documents::with_store(|store| timeline::calendar_heat(&store.list(&DocumentFilter::default()), from, to))
+}
+
+/// Tauri command to list documents, payments and generated reports in date order
+#[tauri::command]
+pub async fn get_timeline_command(date_from: String, date_to: String) -> Result<Vec<TimelineEvent>, String> {
+    let (from, to) = timeline::parse_range(&date_from, &date_to)?;
+    let documents = documents::with_store(|store| Ok(store.list(&DocumentFilter::default())))?;
+    let reports = saved_reports::with_register(|register| Ok(register.list()))?;
+    Ok(timeline::build(&documents, &reports, from, to))
+}
+
+/// Tauri command to accept, without review, the given documents that clear
+/// every straight-through guardrail
+#[tauri::command]
+pub async fn run_straight_through_command(document_ids: Vec<String>) -> Result<Vec<AutoProcessedItem>, String> {
+    straight_through::run(&document_ids)
+}
+
+/// Tauri command to list the documents accepted without review on a day,
+/// today unless a date is given
+#[tauri::command]
+pub async fn get_auto_processed_digest_command(date: Option<String>) -> Result<AutoProcessedDigest, String> {
+    let date = match date {
+        Some(date) => timeline::parse_range(&date, &date)?.0,
+        None => chrono::Local::now().date_naive(),
+    };
+    straight_through::with_log(|log| Ok(log.digest(date)))
+}
+
+/// Tauri command to send an auto-processed document back for review
+#[tauri::command]
+pub async fn undo_auto_processed_command(item_id: String) -> Result<StoredDocument, String> {
+    documents::with_store(|store| {
+        straight_through::with_log(|log| {
+            let document = straight_through::undo(store, log, &item_id)?;
+            store.save()?;
+            log.save()?;
+            Ok(document)
+        })
+    })
+}
+
+/// Tauri command to read app settings
+#[tauri::command]
+pub async fn get_settings_command() -> Result<AppSettings, String> {
+    settings::load_settings()
+}
+
+/// Tauri command to replace app settings
+#[tauri::command]
+pub async fn update_settings_command(settings: AppSettings) -> Result<AppSettings, String> {
+    invoice::compile_skip_patterns(&settings.line_item_skip_patterns)?;
+    settings.straight_through.validate()?;
+    settings::save_settings(&settings)?;
+    Ok(settings)
+}
+
+/// Tauri command to list which cloud OCR backends have an API key saved
+#[tauri::command]
+pub async fn get_cloud_ocr_keys_command(keychain: State<'_, KeychainState>) -> Result<CloudOcrKeys, String> {
+    cloud_ocr::saved_keys(keychain.inner().0.as_ref())
+}
+
+/// Tauri command to save a cloud OCR backend's API key to the system
+/// keychain, or remove it when no key is given
+#[tauri::command]
+pub async fn set_cloud_ocr_key_command(
+    keychain: State<'_, KeychainState>,
+    backend: CloudOcrBackend,
+    key: Option<String>,
+) -> Result<CloudOcrKeys, String> {
+    cloud_ocr::save_key(keychain.inner().0.as_ref(), backend, key.as_deref())?;
+    cloud_ocr::saved_keys(keychain.inner().0.as_ref())
+}
+
+/// Tauri command to read the user's invoice line-item skip patterns
+#[tauri::command]
+pub async fn get_line_item_skip_patterns_command() -> Result<Vec<String>, String> {
+    Ok(settings::load_settings()?.line_item_skip_patterns)
+}
+
+/// Tauri command to replace the user's invoice line-item skip patterns
+#[tauri::command]
+pub async fn set_line_item_skip_patterns_command(patterns: Vec<String>) -> Result<Vec<String>, String> {
+    invoice::compile_skip_patterns(&patterns)?;
+    let mut settings = settings::load_settings()?;
+    settings.line_item_skip_patterns = patterns.clone();
+    settings::save_settings(&settings)?;
+    Ok(patterns)
+}
+
+/// Tauri command to report which features may use the network
+#[tauri::command]
+pub async fn get_network_status_command() -> Result<NetworkStatus, String> {
+    Ok(network::network_status(&settings::load_settings()?))
+}
+
+/// Tauri command to report the device's battery state for the battery saver
+#[tauri::command]
+pub async fn report_power_state_command(state: PowerState) -> Result<(), String> {
+    resource_limits::set_power_state(state);
+    Ok(())
+}
+
+/// Tauri command to look up ABN details, falling back to offline data
+#[tauri::command]
+pub async fn lookup_abn_command(abn: String) -> Result<Sourced<AbnDetails>, String> {
+    abr::lookup_abn(&abn).await
+}
+
+/// Tauri command to auto-save partial field edits for a document under review
+#[tauri::command]
+pub async fn save_draft_command(
+    document_id: String,
+    fields: std::collections::BTreeMap<String, serde_json::Value>,
+) -> Result<DocumentDraft, String> {
+    drafts::with_drafts(|store| {
+        let draft = store.update(&document_id, fields);
+        store.save()?;
+        Ok(draft)
+    })
+}
+
+/// Tauri command to fetch the draft for a document, if one exists
+#[tauri::command]
+pub async fn get_draft_command(document_id: String) -> Result<Option<DocumentDraft>, String> {
+    drafts::with_drafts(|store| Ok(store.get(&document_id).cloned()))
+}
+
+/// Tauri command to list all drafts awaiting resumption
+#[tauri::command]
+pub async fn list_drafts_command() -> Result<Vec<DocumentDraft>, String> {
+    drafts::with_drafts(|store| Ok(store.list()))
+}
+
+/// Tauri command to throw away a document's draft
+#[tauri::command]
+pub async fn discard_draft_command(document_id: String) -> Result<bool, String> {
+    drafts::with_drafts(|store| {
+        let discarded = store.discard(&document_id);
+        store.save()?;
+        Ok(discarded)
+    })
+}
+
+/// Tauri command to list learned vendor billing cadences
+#[tauri::command]
+pub async fn get_vendor_cadences_command() -> Result<Vec<VendorCadence>, String> {
+    documents::with_store(|store| Ok(cadence::predict_cadences(&store.list(&DocumentFilter::default()))))
+}
+
+/// Tauri command to list unpaid invoices whose reminders have started, most overdue first
+#[tauri::command]
+pub async fn get_invoices_needing_attention_command() -> Result<Vec<InvoiceReminder>, String> {
+    let settings = settings::load_settings()?;
+    let today = chrono::Local::now().date_naive();
+    documents::with_store(|store| {
+        Ok(reminders::needing_attention(
+            &store.list(&DocumentFilter::default()),
+            &settings.invoice_reminders,
+            today,
+        ))
+    })
+}
+
+/// Tauri command to set the reminder schedule for a vendor, or the global one
+/// when no vendor is given; a vendor without a schedule goes back to the global one
+#[tauri::command]
+pub async fn set_reminder_schedule_command(
+    vendor: Option<String>,
+    schedule: Option<EscalationSchedule>,
+) -> Result<ReminderSettings, String> {
+    if let Some(schedule) = &schedule {
+        schedule.validate()?;
+    }
+    let mut settings = settings::load_settings()?;
+    let reminders = &mut settings.invoice_reminders;
+    match vendor.map(|v| v.trim().to_string()).filter(|v| !v.is_empty()) {
+        Some(vendor) => {
+            reminders.vendor_schedules.retain(|name, _| !name.eq_ignore_ascii_case(&vendor));
+            if let Some(schedule) = schedule {
+                reminders.vendor_schedules.insert(vendor, schedule);
+            }
+        }
+        None => reminders.schedule = schedule.unwrap_or_default(),
+    }
+    settings::save_settings(&settings)?;
+    Ok(settings.invoice_reminders)
+}
+
+/// Tauri command to suggest a vendor for a receipt from the nearest earlier
+/// receipt captured at the same place
+#[tauri::command]
+pub async fn suggest_vendor_from_location_command(document_id: String) -> Result<Option<VendorSuggestion>, String> {
+    documents::with_store(|store| {
+        let document = store
+            .get(&document_id)
+            .ok_or_else(|| format!("Document not found: {}", document_id))?;
+        let Some(location) = document.capture.as_ref().and_then(|c| c.location) else {
+            return Ok(None);
+        };
+        let documents = store.list(&DocumentFilter::default());
+        Ok(capture_metadata::nearest_merchant(&documents, &location, Some(&document_id)))
+    })
+}
+
+/// Tauri command to list receipts matching a filter that were captured away
+/// from home, for prefilling the travel diary
+#[tauri::command]
+pub async fn get_travel_diary_entries_command(filter: DocumentFilter) -> Result<Vec<TravelDiaryEntry>, String> {
+    let settings = settings::load_settings()?;
+    documents::with_store(|store| Ok(capture_metadata::travel_diary_entries(&store.list(&filter), &settings.travel)))
+}
+
+/// Tauri command to find expected invoices that have not arrived
+#[tauri::command]
+pub async fn get_missing_invoice_alerts_command(
+    grace_days: Option<i64>,
+) -> Result<Vec<MissingInvoiceAlert>, String> {
+    let today = chrono::Local::now().date_naive();
+    documents::with_store(|store| {
+        Ok(cadence::missing_invoice_alerts(
+            &store.list(&DocumentFilter::default()),
+            today,
+            grace_days.unwrap_or(7),
+        ))
+    })
+}
+
+/// Tauri command to lock or unlock document fields against re-extraction
+#[tauri::command]
+pub async fn set_field_locks_command(
+    document_id: String,
+    fields: Vec<String>,
+    locked: bool,
+) -> Result<StoredDocument, String> {
+    documents::with_store(|store| {
+        let mut document = store
+            .get(&document_id)
+            .cloned()
+            .ok_or_else(|| format!("Document not found: {}", document_id))?;
+        document.set_locked(&fields, locked)?;
+        let saved = store.upsert(document);
+        store.save()?;
+        Ok(saved)
+    })
+}
+
+/// Tauri command to re-parse a document's original file, keeping locked fields
+#[tauri::command]
+pub async fn reextract_document_command(document_id: String) -> Result<StoredDocument, String> {
+    let original = documents::with_store(|store| {
+        store
+            .get(&document_id)
+            .cloned()
+            .ok_or_else(|| format!("Document not found: {}", document_id))
+    })?;
+    let revision = original.revision;
+    let extraction = tauri::async_runtime::spawn_blocking(move || documents::extract_from_source(&original))
+        .await
+        .map_err(|e| format!("Re-extraction task failed: {}", e))??;
+    documents::with_store(|store| {
+        let mut document = store
+            .get(&document_id)
+            .cloned()
+            .ok_or_else(|| format!("Document not found: {}", document_id))?;
+        if document.revision != revision {
+            return Err("Document changed while it was being re-extracted; try again".to_string());
+        }
+        document.apply_extraction(extraction);
+        let saved = store.update(document)?;
+        store.save()?;
+        Ok(saved)
+    })
+}
+
+/// Tauri command to diff the OCR text of two documents or two extraction versions
+#[tauri::command]
+pub async fn diff_document_text_command(left: TextSource, right: TextSource) -> Result<TextDiff, String> {
+    documents::with_store(|store| {
+        let text = |source: &TextSource| {
+            let doc = store
+                .get(&source.document_id)
+                .ok_or_else(|| format!("Document not found: {}", source.document_id))?;
+            text_diff::source_text(doc, source.version)
+        };
+        Ok(text_diff::diff_lines(&text(&left)?, &text(&right)?))
+    })
+}
+
+/// Tauri command to report GST credits entered after their BAS period
+#[tauri::command]
+pub async fn get_gst_credit_timing_report_command(
+    financial_year: i32,
+) -> Result<GstCreditTimingReport, String> {
+    let corrections = period_locks::with_period_locks(|locks| Ok(locks.adjustments().to_vec()))?;
+    documents::with_store(|store| {
+        Ok(bas::gst_credit_timing_report(
+            &store.list(&DocumentFilter::default()),
+            &corrections,
+            financial_year,
+        ))
+    })
+}
+
+/// Tauri command to total a quarter's purchases at the BAS labels, by GST treatment
+#[tauri::command]
+pub async fn get_bas_purchase_labels_command(period: BasPeriod) -> Result<PurchaseLabels, String> {
+    let corrections = period_locks::with_period_locks(|locks| Ok(locks.adjustments().to_vec()))?;
+    documents::with_store(|store| {
+        Ok(bas::purchase_labels(
+            &store.list(&DocumentFilter::default()),
+            &corrections,
+            period,
+        ))
+    })
+}
+
+/// Tauri command to override how a document's GST is reported; `None` reclassifies it
+#[tauri::command]
+pub async fn set_gst_treatment_command(
+    document_id: String,
+    treatment: Option<GstTreatment>,
+) -> Result<StoredDocument, String> {
+    documents::with_store(|store| {
+        let mut document = store
+            .get(&document_id)
+            .cloned()
+            .ok_or_else(|| format!("Document not found: {}", document_id))?;
+        document.gst_treatment = treatment;
+        let saved = store.update(document)?;
+        store.save()?;
+        Ok(saved)
+    })
+}
+
+/// Tauri command to copy originals out with canonical filenames
+#[tauri::command]
+pub async fn export_originals_command(
+    app: tauri::AppHandle,
+    filter: DocumentFilter,
+    destination_dir: String,
+) -> Result<OriginalsExportResult, String> {
+    let destination = sandbox::validate_path(&app, &destination_dir)?;
+    let documents = documents::with_store(|store| Ok(store.list(&filter)))?;
+    export::export_originals(&documents, &destination)
+}
+
+/// Tauri command to export the OCR text of matching documents for searching outside the app
+#[tauri::command]
+pub async fn export_ocr_text_command(
+    app: tauri::AppHandle,
+    filter: DocumentFilter,
+    destination_dir: String,
+    format: TextExportFormat,
+) -> Result<TextExportResult, String> {
+    let destination = sandbox::validate_path(&app, &destination_dir)?;
+    let documents = documents::with_store(|store| Ok(store.list(&filter)))?;
+    export::export_text(&documents, &destination, format)
+}
+
+/// Tauri command to export an anonymized copy of matching documents for sharing in bug reports
+#[tauri::command]
+pub async fn export_anonymized_dataset_command(
+    app: tauri::AppHandle,
+    filter: DocumentFilter,
+    destination_dir: String,
+    salt: Option<String>,
+) -> Result<AnonymizedExport, String> {
+    let destination = sandbox::validate_path(&app, &destination_dir)?;
+    let documents = documents::with_store(|store| Ok(store.list(&filter)))?;
+    anonymize::export_anonymized(&documents, &destination, salt)
+}
+
+/// Tauri command to generate the weekly digest; defaults to the week ending yesterday
+#[tauri::command]
+pub async fn generate_weekly_digest_command(week_ending: Option<String>) -> Result<DigestSaveResult, String> {
+    let week_ending = match week_ending {
+        Some(date) => chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+            .map_err(|e| format!("Invalid week ending date: {}", e))?,
+        None => chrono::Local::now().date_naive() - chrono::Days::new(1),
+    };
+    digest::generate_and_save(week_ending)
+}
+
+/// Tauri command to quick-enter a cash expense without a receipt
+#[tauri::command]
+pub async fn add_cash_expense_command(expense: NewCashExpense) -> Result<CashExpense, String> {
+    cash::with_journal(|journal| {
+        let added = journal.add(expense)?;
+        journal.save()?;
+        Ok(added)
+    })
+}
+
+/// Tauri command to list cash expenses, optionally for one financial year
+#[tauri::command]
+pub async fn list_cash_expenses_command(financial_year: Option<i32>) -> Result<Vec<CashExpense>, String> {
+    cash::with_journal(|journal| Ok(journal.list(financial_year)))
+}
+
+/// Tauri command to fetch one sorted page of cash expenses, optionally for one financial year
+#[tauri::command]
+pub async fn list_cash_expenses_page_command(
+    financial_year: Option<i32>,
+    page: PageRequest<CashSortField>,
+) -> Result<Page<CashExpense>, String> {
+    cash::with_journal(|journal| journal.page(financial_year, &page))
+}
+
+/// Tauri command to remove a cash expense
+#[tauri::command]
+pub async fn delete_cash_expense_command(id: String) -> Result<bool, String> {
+    cash::with_journal(|journal| {
+        let deleted = journal.delete(&id);
+        if deleted {
+            journal.save()?;
+        }
+        Ok(deleted)
+    })
+}
+
+/// Tauri command to get a year's cash expense totals and remaining allowance
+#[tauri::command]
+pub async fn get_cash_totals_command(financial_year: i32) -> Result<CashYearTotals, String> {
+    cash::with_journal(|journal| Ok(journal.totals(financial_year)))
+}
+
+/// Tauri command to summarise a financial year's expenses
+#[tauri::command]
+pub async fn get_financial_year_summary_command(financial_year: i32) -> Result<FinancialYearSummary, String> {
+    let documents = documents::with_store(|store| Ok(store.list(&DocumentFilter::default())))?;
+    let cash_expenses = cash::with_journal(|journal| Ok(journal.list(Some(financial_year))))?;
+    let trading_stock = stock::with_stock_ledger(|ledger| Ok(ledger.year_summary(financial_year)))?;
+    Ok(summary::financial_year_summary(&documents, &cash_expenses, trading_stock, financial_year))
+}
+
+/// Tauri command to compare a financial year's category totals with the prior year
+#[tauri::command]
+pub async fn get_fy_comparison_command(financial_year: i32) -> Result<FyComparison, String> {
+    fy_comparison::build_comparison(financial_year)
+}
+
+/// Tauri command to list unreviewed documents in a financial year's totals that should be checked by hand
+#[tauri::command]
+pub async fn get_documents_to_check_command(financial_year: i32) -> Result<Vec<DocumentToCheck>, String> {
+    verification::for_financial_year(financial_year)
+}
+
+/// Tauri command to save the year-over-year comparison as a PDF in the reports directory
+#[tauri::command]
+pub async fn export_fy_comparison_pdf_command(
+    financial_year: i32,
+    output_filename: String,
+) -> Result<FyComparisonSaveResult, String> {
+    let output_path = tax_report::get_reports_directory()?.join(sandbox::validate_file_name(&output_filename)?);
+    let result = fy_comparison::save_comparison(financial_year, &output_path)?;
+    let totals = fy_comparison::comparison_totals(&result.comparison);
+    saved_reports::record(ReportParameters::FyComparison { financial_year }, &result.file_path, totals)?;
+    Ok(result)
+}
+
+/// Tauri command to record a year's opening or closing stock value
+#[tauri::command]
+pub async fn set_stock_value_command(value: NewStockValue) -> Result<StockValue, String> {
+    stock::with_stock_ledger(|ledger| {
+        let saved = ledger.set_value(value)?;
+        ledger.save()?;
+        Ok(saved)
+    })
+}
+
+/// Tauri command to list stocktake values, optionally for one financial year
+#[tauri::command]
+pub async fn list_stock_values_command(financial_year: Option<i32>) -> Result<Vec<StockValue>, String> {
+    stock::with_stock_ledger(|ledger| Ok(ledger.values(financial_year)))
+}
+
+/// Tauri command to record stock removed other than by sale
+#[tauri::command]
+pub async fn add_stock_adjustment_command(adjustment: NewStockAdjustment) -> Result<StockAdjustment, String> {
+    stock::with_stock_ledger(|ledger| {
+        let added = ledger.add_adjustment(adjustment)?;
+        ledger.save()?;
+        Ok(added)
+    })
+}
+
+/// Tauri command to list stock adjustments, optionally for one financial year
+#[tauri::command]
+pub async fn list_stock_adjustments_command(financial_year: Option<i32>) -> Result<Vec<StockAdjustment>, String> {
+    stock::with_stock_ledger(|ledger| Ok(ledger.adjustments(financial_year)))
+}
+
+/// Tauri command to remove a stock adjustment
+#[tauri::command]
+pub async fn delete_stock_adjustment_command(id: String) -> Result<bool, String> {
+    stock::with_stock_ledger(|ledger| {
+        let deleted = ledger.delete_adjustment(&id);
+        if deleted {
+            ledger.save()?;
+        }
+        Ok(deleted)
+    })
+}
+
+/// Tauri command to carry unfinished items from a closing financial year into the next
+#[tauri::command]
+pub async fn rollover_financial_year_command(closing_year: i32) -> Result<RolloverResult, String> {
+    documents::with_store(|store| {
+        let result = store.roll_forward(closing_year);
+        store.save()?;
+        Ok(result)
+    })
+}
+
+/// Tauri command to parse a stored invoice under two heuristic profiles and diff the results
+#[tauri::command]
+pub async fn compare_heuristic_profiles_command(
+    document_id: String,
+    profile_a: String,
+    profile_b: String,
+) -> Result<ProfileComparison, String> {
+    let document = documents::with_store(|store| {
+        store
+            .get(&document_id)
+            .cloned()
+            .ok_or_else(|| format!("Document not found: {}", document_id))
+    })?;
+    let text = heuristics::comparison_text(document.invoice.as_ref(), document.source_path.as_deref())?;
+    heuristics::compare_profiles(&settings::load_settings()?, &text, &profile_a, &profile_b)
+}
+
+/// Tauri command to list the amounts a document should match on bank statements
+#[tauri::command]
+pub async fn get_bank_match_amounts_command(document_id: String) -> Result<Vec<Money>, String> {
+    documents::with_store(|store| {
+        let document = store
+            .get(&document_id)
+            .ok_or_else(|| format!("Document not found: {}", document_id))?;
+        Ok(match document.receipt {
+            Some(ref receipt) => receipt::bank_match_amounts(receipt),
+            None => document.total.into_iter().collect(),
+        })
+    })
+}
+
+/// Tauri command to export documents as a handover ZIP with index and checksum manifest
+#[tauri::command]
+pub async fn export_handover_package_command(
+    app: tauri::AppHandle,
+    filter: DocumentFilter,
+    package_path: String,
+) -> Result<PackageResult, String> {
+    let path = sandbox::validate_path(&app, &package_path)?;
+    let documents = documents::with_store(|store| Ok(store.list(&filter)))?;
+    package::create_package(&documents, &path)
+}
+
+/// Tauri command to re-validate a handover package against its manifest
+#[tauri::command]
+pub async fn verify_package_command(app: tauri::AppHandle, package_path: String) -> Result<PackageVerification, String> {
+    let path = sandbox::validate_path(&app, &package_path)?;
+    package::verify_package(&path)
+}
+
+/// Tauri command to list the accountant portals packages can be uploaded to
+#[tauri::command]
+pub async fn list_upload_targets_command() -> Result<Vec<UploadTarget>, String> {
+    Ok(settings::load_settings()?.upload_targets)
+}
+
+/// Tauri command to add or replace an upload target, saving its password to
+/// the system keychain when one is given
+#[tauri::command]
+pub async fn save_upload_target_command(
+    keychain: State<'_, KeychainState>,
+    target: UploadTarget,
+    password: Option<String>,
+) -> Result<UploadTarget, String> {
+    let mut settings = settings::load_settings()?;
+    let target = upload::save_target(
+        &mut settings.upload_targets,
+        keychain.inner().0.as_ref(),
+        target,
+        password.as_deref(),
+    )?;
+    settings::save_settings(&settings)?;
+    Ok(target)
+}
+
+/// Tauri command to remove an upload target and its saved password
+#[tauri::command]
+pub async fn delete_upload_target_command(
+    keychain: State<'_, KeychainState>,
+    target_id: String,
+) -> Result<bool, String> {
+    let mut settings = settings::load_settings()?;
+    let deleted = upload::delete_target(&mut settings.upload_targets, keychain.inner().0.as_ref(), &target_id)?;
+    if deleted {
+        settings::save_settings(&settings)?;
+    }
+    Ok(deleted)
+}
+
+/// Tauri command to upload a handover package to an accountant's portal. The
+/// package is verified against its manifest first; progress is emitted as
+/// `upload://progress` events and transient failures are retried.
+#[tauri::command]
+pub async fn upload_package_command(
+    app: tauri::AppHandle,
+    keychain: State<'_, KeychainState>,
+    target_id: String,
+    package_path: String,
+) -> Result<UploadResult, String> {
+    let path = sandbox::validate_path(&app, &package_path)?;
+    let settings = settings::load_settings()?;
+    let access = network::check_access(NetworkFeature::PortalUpload, &settings);
+    if !access.allowed {
+        return Err(access.reason.unwrap_or_else(|| "Uploads are disabled".to_string()));
+    }
+    let target = settings
+        .upload_targets
+        .into_iter()
+        .find(|t| t.id == target_id)
+        .ok_or_else(|| format!("Upload target not found: {}", target_id))?;
+    let verification = package::verify_package(&path)?;
+    if !verification.valid {
+        return Err("The package no longer matches its manifest; export it again before uploading".to_string());
+    }
+    let secret = upload::saved_password(keychain.inner().0.as_ref(), &target.id)?;
+
+    let transport = upload::transport_for(target.protocol()?);
+    let upload_id = storage::generate_id("upload");
+    tauri::async_runtime::spawn_blocking(move || {
+        upload::upload_with_retry(
+            transport.as_ref(),
+            &target,
+            secret.as_deref(),
+            &path,
+            &upload_id,
+            upload::RETRY_DELAY,
+            &mut |progress| {
+                if let Err(e) = app.emit(upload::UPLOAD_EVENT, progress) {
+                    log::warn!("Failed to emit upload progress: {}", e);
+                }
+            },
+        )
+    })
+    .await
+    .map_err(|e| format!("Upload task failed: {}", e))?
+}
+
+/// Tauri command to list entities
+#[tauri::command]
+pub async fn list_entities_command() -> Result<Vec<Entity>, String> {
+    entities::with_entities(|store| Ok(store.list()))
+}
+
+/// Tauri command to create or update an entity
+#[tauri::command]
+pub async fn save_entity_command(entity: Entity) -> Result<Entity, String> {
+    entities::with_entities(|store| {
+        let saved = store.upsert(entity)?;
+        store.save()?;
+        Ok(saved)
+    })
+}
+
+/// Tauri command to delete an entity
+#[tauri::command]
+pub async fn delete_entity_command(entity_id: String) -> Result<bool, String> {
+    entities::with_entities(|store| {
+        let deleted = store.delete(&entity_id);
+        if deleted {
+            store.save()?;
+        }
+        Ok(deleted)
+    })
+}
+
+/// Tauri command to list entity groups
+#[tauri::command]
+pub async fn list_entity_groups_command() -> Result<Vec<EntityGroup>, String> {
+    entity_groups::with_groups(|store| Ok(store.list()))
+}
+
+/// Tauri command to create or update a group of related entities
+#[tauri::command]
+pub async fn save_entity_group_command(group: EntityGroup) -> Result<EntityGroup, String> {
+    let known = entities::with_entities(|store| Ok(store.list()))?;
+    if let Some(missing) = group.entity_ids.iter().find(|id| !known.iter().any(|e| &e.id == *id)) {
+        return Err(format!("Entity not found: {}", missing));
+    }
+    entity_groups::with_groups(|store| {
+        let saved = store.upsert(group)?;
+        store.save()?;
+        Ok(saved)
+    })
+}
+
+/// Tauri command to delete an entity group; its entities are kept
+#[tauri::command]
+pub async fn delete_entity_group_command(group_id: String) -> Result<bool, String> {
+    entity_groups::with_groups(|store| {
+        let deleted = store.delete(&group_id);
+        if deleted {
+            store.save()?;
+        }
+        Ok(deleted)
+    })
+}
+
+/// Tauri command to consolidate a group's financial year, with per-entity BAS figures
+#[tauri::command]
+pub async fn get_entity_group_report_command(group_id: String, financial_year: i32) -> Result<GroupReport, String> {
+    let group = entity_groups::with_groups(|store| {
+        store
+            .get(&group_id)
+            .cloned()
+            .ok_or_else(|| format!("Entity group not found: {}", group_id))
+    })?;
+    let entities = entities::with_entities(|store| Ok(store.list()))?;
+    let documents = documents::with_store(|store| Ok(store.list(&DocumentFilter::default())))?;
+    let corrections = period_locks::with_period_locks(|locks| Ok(locks.adjustments().to_vec()))?;
+    entity_groups::group_report(&group, &entities, &documents, &corrections, financial_year)
+}
+
+/// Tauri command to check an invoice's Bill To details against an entity
+/// (the active entity when none is given)
+#[tauri::command]
+pub async fn check_invoice_recipient_command(
+    invoice: ExtractedInvoice,
+    entity_id: Option<String>,
+) -> Result<RecipientCheck, String> {
+    let entity_id = match entity_id {
+        Some(id) => id,
+        None => settings::load_settings()?
+            .active_entity_id
+            .ok_or_else(|| "No active entity selected".to_string())?,
+    };
+    entities::with_entities(|store| {
+        let entity = store
+            .get(&entity_id)
+            .ok_or_else(|| format!("Entity not found: {}", entity_id))?;
+        Ok(entities::check_recipient(&invoice, entity))
+    })
+}
+
+/// Tauri command to record billable time
+#[tauri::command]
+pub async fn add_time_entry_command(entry: NewTimeEntry) -> Result<TimeEntry, String> {
+    time_tracking::with_ledger(|ledger| {
+        let added = ledger.add_entry(entry)?;
+        ledger.save()?;
+        Ok(added)
+    })
+}
+
+/// Tauri command to list time entries, optionally for one client and unbilled only
+#[tauri::command]
+pub async fn list_time_entries_command(
+    client: Option<String>,
+    unbilled_only: bool,
+) -> Result<Vec<TimeEntry>, String> {
+    time_tracking::with_ledger(|ledger| Ok(ledger.entries(client.as_deref(), unbilled_only)))
+}
+
+/// Tauri command to delete an unbilled time entry
+#[tauri::command]
+pub async fn delete_time_entry_command(entry_id: String) -> Result<bool, String> {
+    time_tracking::with_ledger(|ledger| {
+        let deleted = ledger.delete_entry(&entry_id)?;
+        if deleted {
+            ledger.save()?;
+        }
+        Ok(deleted)
+    })
+}
+
+/// Tauri command to convert a client's unbilled time into a draft invoice
+#[tauri::command]
+pub async fn create_draft_invoice_from_time_command(request: DraftInvoiceRequest) -> Result<IssuedInvoice, String> {
+    time_tracking::with_ledger(|ledger| {
+        let invoice = ledger.create_draft_invoice(&request)?;
+        ledger.save()?;
+        Ok(invoice)
+    })
+}
+
+/// Tauri command to list invoices issued from tracked time
+#[tauri::command]
+pub async fn list_issued_invoices_command() -> Result<Vec<IssuedInvoice>, String> {
+    time_tracking::with_ledger(|ledger| Ok(ledger.invoices()))
+}
+
+/// Tauri command to render an issued invoice to PDF in the reports folder
+#[tauri::command]
+pub async fn generate_issued_invoice_pdf_command(invoice_id: String) -> Result<TaxReportSaveResult, String> {
+    let invoice = time_tracking::with_ledger(|ledger| {
+        ledger
+            .invoice(&invoice_id)
+            .cloned()
+            .ok_or_else(|| format!("Issued invoice not found: {}", invoice_id))
+    })?;
+
+    let issuer = match settings::load_settings()?.active_entity_id {
+        Some(id) => entities::with_entities(|store| Ok(store.get(&id).cloned()))?,
+        None => None,
+    };
+
+    let pdf = time_tracking::render_invoice_pdf(&invoice, issuer.as_ref());
+    tax_report::save_tax_report_pdf(format!("{}.pdf", invoice.invoice_number), pdf).await
+}
+
+/// Tauri command to screen files before import, setting aside likely non-financial documents
+#[tauri::command]
+pub async fn preclassify_import_command(app: tauri::AppHandle, paths: Vec<String>) -> Result<PreImportResult, String> {
+    sandbox::validate_paths(&app, &paths)?;
+    classifier::with_skipped(|store| {
+        let result = classifier::preclassify(&paths, store)?;
+        if !result.skipped.is_empty() {
+            store.save()?;
+        }
+        Ok(result)
+    })
+}
+
+/// Tauri command to list files skipped at import
+#[tauri::command]
+pub async fn list_skipped_imports_command() -> Result<Vec<SkippedImport>, String> {
+    classifier::with_skipped(|store| Ok(store.list()))
+}
+
+/// Tauri command to take a file off the skipped list so it can be imported
+#[tauri::command]
+pub async fn restore_skipped_import_command(skipped_id: String) -> Result<SkippedImport, String> {
+    classifier::with_skipped(|store| {
+        let restored = store
+            .restore(&skipped_id)
+            .ok_or_else(|| format!("Skipped import not found: {}", skipped_id))?;
+        store.save()?;
+        Ok(restored)
+    })
+}
+
+/// Tauri command to parse a free-form Australian address
+#[tauri::command]
+pub async fn parse_address_command(text: String) -> Result<Option<Address>, String> {
+    Ok(address::parse_address(&text))
+}
+
+/// Tauri command to total documents by the state of each vendor
+#[tauri::command]
+pub async fn get_state_totals_command(filter: DocumentFilter) -> Result<Vec<StateTotal>, String> {
+    let documents = documents::with_store(|store| Ok(store.list(&filter)))?;
+    Ok(address::state_totals(&documents))
+}
+
+/// Tauri command to merge documents into a bookmarked PDF bundle in the reports directory
+#[tauri::command]
+pub async fn export_document_bundle_command(filter: DocumentFilter, output_filename: String) -> Result<BundleResult, String> {
+    let documents = documents::with_store(|store| Ok(store.list(&filter)))?;
+    let output_path = tax_report::get_reports_directory()?.join(sandbox::validate_file_name(&output_filename)?);
+    let result = bundle::create_bundle(&documents, &output_path)?;
+    let totals = saved_reports::bundle_totals(&result);
+    saved_reports::record(ReportParameters::DocumentBundle { filter }, &result.file_path, totals)?;
+    Ok(result)
+}
+
+/// Tauri command to export documents as an OFX or QIF file in the reports directory
+#[tauri::command]
+pub async fn export_finance_file_command(
+    filter: DocumentFilter,
+    format: FinanceFormat,
+    output_filename: String,
+) -> Result<FinanceExportResult, String> {
+    let documents = documents::with_store(|store| Ok(store.list(&filter)))?;
+    let output_path = tax_report::get_reports_directory()?.join(sandbox::validate_file_name(&output_filename)?);
+    finance_export::export_documents(&documents, format, &output_path)
+}
+
+/// Tauri command to list the category packs available to install
+#[tauri::command]
+pub async fn list_category_packs_command() -> Result<Vec<CategoryPack>, String> {
+    Ok(category_packs::built_in_packs())
+}
+
+/// Tauri command to install or remove a category pack for an entity
+#[tauri::command]
+pub async fn set_category_pack_installed_command(
+    entity_id: String,
+    pack_id: String,
+    installed: bool,
+) -> Result<Entity, String> {
+    if category_packs::find_pack(&pack_id).is_none() {
+        return Err(format!("Unknown category pack: {}", pack_id));
+    }
+    entities::with_entities(|store| {
+        let entity = store.set_pack(&entity_id, &pack_id, installed)?;
+        store.save()?;
+        Ok(entity)
+    })
+}
+
+/// Tauri command to list the categories an entity's packs provide
+#[tauri::command]
+pub async fn list_entity_categories_command(entity_id: String) -> Result<Vec<PackCategory>, String> {
+    entities::with_entities(|store| {
+        let entity = store
+            .get(&entity_id)
+            .ok_or_else(|| format!("Entity not found: {}", entity_id))?;
+        Ok(category_packs::entity_categories(entity))
+    })
+}
+
+/// Tauri command to suggest a category for a document from the user's
+/// category rules, falling back to its entity's packs (the active entity when
+/// the document has none)
+#[tauri::command]
+pub async fn suggest_document_category_command(document_id: String) -> Result<Option<CategorySuggestion>, String> {
+    let document = documents::with_store(|store| {
+        store
+            .get(&document_id)
+            .cloned()
+            .ok_or_else(|| format!("Document not found: {}", document_id))
+    })?;
+    let entity_id = match document.entity_id.clone() {
+        Some(id) => Some(id),
+        None => settings::load_settings()?.active_entity_id,
+    };
+    let rule_match = category_rules::with_rules(|store| {
+        Ok(category_rules::evaluate(&store.list(), &document, entity_id.as_deref()))
+    })?;
+    entities::with_entities(|store| {
+        let entity = entity_id.as_deref().and_then(|id| store.get(id));
+        Ok(match rule_match {
+            Some(rule_match) => Some(category_rules::suggestion(&rule_match, entity)),
+            None => entity.and_then(|entity| category_packs::suggest_category(entity, &document)),
+        })
+    })
+}
+
+/// Tauri command to list category rules in the order they are tried
+#[tauri::command]
+pub async fn list_category_rules_command() -> Result<Vec<CategoryRule>, String> {
+    category_rules::with_rules(|store| Ok(store.list()))
+}
+
+/// Tauri command to create a category rule, or replace the one with the same ID
+#[tauri::command]
+pub async fn save_category_rule_command(rule: CategoryRule) -> Result<CategoryRule, String> {
+    category_rules::with_rules(|store| {
+        let saved = store.upsert(rule)?;
+        store.save()?;
+        Ok(saved)
+    })
+}
+
+/// Tauri command to delete a category rule
+#[tauri::command]
+pub async fn delete_category_rule_command(rule_id: String) -> Result<bool, String> {
+    category_rules::with_rules(|store| {
+        let deleted = store.delete(&rule_id);
+        if deleted {
+            store.save()?;
+        }
+        Ok(deleted)
+    })
+}
+
+/// Tauri command to add a user-defined regex for an invoice or custom field,
+/// tried before the built-in patterns
+#[tauri::command]
+pub async fn register_custom_pattern_command(
+    field: String,
+    pattern: String,
+    description: Option<String>,
+) -> Result<CustomPattern, String> {
+    custom_patterns::with_patterns(|store| {
+        let registered = store.register(&field, &pattern, description)?;
+        store.save()?;
+        Ok(registered)
+    })
+}
+
+/// Tauri command to list custom patterns by field with their match statistics
+#[tauri::command]
+pub async fn list_custom_patterns_command() -> Result<Vec<CustomPatternGroup>, String> {
+    custom_patterns::with_patterns(|store| Ok(store.groups()))
+}
+
+/// Tauri command to delete a custom pattern
+#[tauri::command]
+pub async fn delete_custom_pattern_command(pattern_id: String) -> Result<bool, String> {
+    custom_patterns::with_patterns(|store| {
+        let deleted = store.delete(&pattern_id);
+        if deleted {
+            store.save()?;
+        }
+        Ok(deleted)
+    })
+}
+
+/// Tauri command to show which category rule would fire for a document, and
+/// how each rule's conditions fared, without changing the document
+#[tauri::command]
+pub async fn dry_run_category_rules_command(document_id: String) -> Result<RuleDryRun, String> {
+    let document = documents::with_store(|store| {
+        store
+            .get(&document_id)
+            .cloned()
+            .ok_or_else(|| format!("Document not found: {}", document_id))
+    })?;
+    let active_entity_id = settings::load_settings()?.active_entity_id;
+    category_rules::with_rules(|store| {
+        Ok(category_rules::dry_run(&store.list(), &document, active_entity_id.as_deref()))
+    })
+}
+
+/// Tauri command to group uncategorized documents matching a filter by
+/// similarity and propose a category for each group. A proposal is applied
+/// with `bulk_update_documents_command` on the group's document IDs.
+#[tauri::command]
+pub async fn suggest_category_clusters_command(filter: DocumentFilter) -> Result<Vec<DocumentCluster>, String> {
+    // Every categorized document is history, whatever the filter
+    let documents: Vec<StoredDocument> = documents::with_store(|store| Ok(store.list(&DocumentFilter::default())))?
+        .into_iter()
+        .filter(|doc| !category_clusters::is_uncategorized(doc) || filter.matches(doc))
+        .collect();
+    let active_entity_id = settings::load_settings()?.active_entity_id;
+    entities::with_entities(|store| {
+        Ok(category_clusters::cluster_documents(&documents, |doc| {
+            doc.entity_id.as_ref().or(active_entity_id.as_ref()).and_then(|id| store.get(id))
+        }))
+    })
+}
+
+/// Tauri command to list the invoice layouts learned for each vendor
+#[tauri::command]
+pub async fn list_vendor_templates_command() -> Result<Vec<VendorTemplate>, String> {
+    vendor_templates::with_templates(|store| Ok(store.list()))
+}
+
+/// Tauri command to choose the scoring profile a vendor's invoices are
+/// parsed with; `None` uses the active profile
+#[tauri::command]
+pub async fn set_vendor_template_profile_command(
+    template_id: String,
+    profile: Option<String>,
+) -> Result<VendorTemplate, String> {
+    if let Some(name) = &profile {
+        if settings::load_settings()?.heuristic_weights(name).is_none() {
+            return Err(format!("Unknown scoring profile: {}", name));
+        }
+    }
+    vendor_templates::with_templates(|store| {
+        let template = store.set_profile(&template_id, profile)?;
+        store.save()?;
+        Ok(template)
+    })
+}
+
+/// Tauri command to forget a vendor's learned layouts
+#[tauri::command]
+pub async fn delete_vendor_template_command(template_id: String) -> Result<bool, String> {
+    vendor_templates::with_templates(|store| {
+        let deleted = store.delete(&template_id);
+        if deleted {
+            store.save()?;
+        }
+        Ok(deleted)
+    })
+}
+
+/// Tauri command to store a photo as a provisional receipt and extract it in
+/// the background, emitting `capture://completed` when done
+#[tauri::command]
+pub async fn quick_capture_command(app: tauri::AppHandle, image_path: String) -> Result<QuickCaptureResult, String> {
+    let path = sandbox::validate_path(&app, &image_path)?;
+    capture::quick_capture(&app, &path)
+}
+
+/// Tauri command to list lodged BAS quarters
+#[tauri::command]
+pub async fn list_lodged_periods_command() -> Result<Vec<LodgedPeriod>, String> {
+    period_locks::with_period_locks(|locks| Ok(locks.lodged().to_vec()))
+}
+
+/// Tauri command to mark a BAS quarter lodged, locking its documents
+#[tauri::command]
+pub async fn lock_bas_period_command(
+    period: BasPeriod,
+    entity_id: Option<String>,
+    reference: Option<String>,
+) -> Result<LodgedPeriod, String> {
+    period_locks::with_period_locks(|locks| {
+        let lodged = locks.lock(period, entity_id, reference)?;
+        locks.save()?;
+        Ok(lodged)
+    })
+}
+
+/// Tauri command to reopen a quarter that was marked lodged by mistake
+#[tauri::command]
+pub async fn unlock_bas_period_command(period: BasPeriod, entity_id: Option<String>) -> Result<bool, String> {
+    period_locks::with_period_locks(|locks| {
+        let removed = locks.unlock(period, &entity_id);
+        if removed {
+            locks.save()?;
+        }
+        Ok(removed)
+    })
+}
+
+/// Tauri command to correct a document in a lodged quarter, recording an
+/// adjustment for the next open quarter
+#[tauri::command]
+pub async fn record_period_adjustment_command(
+    document_id: String,
+    total: Money,
+    gst: Money,
+    reason: String,
+) -> Result<AdjustmentEntry, String> {
+    period_locks::with_period_locks(|locks| {
+        documents::with_store(|store| {
+            let mut document = store
+                .get(&document_id)
+                .cloned()
+                .ok_or_else(|| format!("Document not found: {}", document_id))?;
+            let entry = locks.record_adjustment(&mut document, total, gst, &reason, chrono::Local::now().date_naive())?;
+            // The adjustment is saved first so a corrected document always has
+            // one; if the document can't be saved the adjustment is taken back
+            locks.save()?;
+            // The lock check is bypassed; the adjustment entry records the change
+            store.upsert(document);
+            if let Err(e) = store.save() {
+                locks.discard_adjustment(&entry.id);
+                locks.save()?;
+                return Err(e);
+            }
+            Ok(entry)
+        })
+    })
+}
@@ -0,0 +1,337 @@
+//! CSV Import Module
+//!
+//! Brings in historical expenses kept in a spreadsheet. The user maps
+//! columns to fields and previews the first rows as they will be read, then
+//! every valid row is recorded as a manual entry. Rows with an unreadable
+//! date or amount are reported by line number and left out.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::dates::{self, DateOrder};
+use crate::documents::{DocumentKind, DocumentStore, ManualEntry, StoredDocument};
+use crate::money::Money;
+
+/// Rows shown in a preview unless a count is given
+pub const DEFAULT_PREVIEW_ROWS: usize = 20;
+
+/// Which column holds each field. Columns are named by their header,
+/// ignoring case, or by 1-based position when the file has no header row.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ColumnMapping {
+    pub date: String,
+    pub vendor: String,
+    pub total: String,
+    pub gst: Option<String>,
+    pub category: Option<String>,
+}
+
+/// How to read a spreadsheet export
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct CsvImportConfig {
+    pub columns: ColumnMapping,
+    /// Field separator, such as `;` or a tab for European exports
+    pub delimiter: char,
+    pub has_header: bool,
+    /// How to read numeric dates such as 03/04/2024
+    pub date_order: DateOrder,
+    /// Kind given to every imported record
+    pub kind: DocumentKind,
+    pub entity_id: Option<String>,
+}
+
+impl Default for CsvImportConfig {
+    fn default() -> Self {
+        Self {
+            columns: ColumnMapping::default(),
+            delimiter: ',',
+            has_header: true,
+            date_order: DateOrder::default(),
+            kind: DocumentKind::default(),
+            entity_id: None,
+        }
+    }
+}
+
+/// One data row as it will be imported
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ParsedRow {
+    /// Line of the file the row starts on, counting from 1
+    pub line: usize,
+    /// `None` when the row has errors
+    pub entry: Option<ManualEntry>,
+    pub errors: Vec<String>,
+}
+
+/// The first rows of a file, read with a column mapping
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CsvPreview {
+    pub headers: Vec<String>,
+    pub rows: Vec<ParsedRow>,
+    /// Data rows in the whole file
+    pub total_rows: usize,
+    /// Rows in the whole file that would be imported
+    pub valid_rows: usize,
+}
+
+/// Result of importing a file
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CsvImportResult {
+    pub imported: usize,
+    pub document_ids: Vec<String>,
+    /// Rows left out, with their errors
+    pub skipped: Vec<ParsedRow>,
+}
+
+/// Read a CSV file as text, dropping the byte order mark Excel writes
+pub fn read_file(path: &Path) -> Result<String, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let text = String::from_utf8_lossy(&bytes);
+    Ok(text.trim_start_matches('\u{feff}').to_string())
+}
+
+/// Split CSV text into records, each with the line it starts on. Quoted
+/// fields may hold the delimiter, doubled quotes and line breaks. Blank
+/// records are dropped.
+fn split_records(text: &str, delimiter: char) -> Vec<(usize, Vec<String>)> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut start = 1;
+    let mut chars = text.chars().peekable();
+
+    let mut finish = |fields: &mut Vec<String>, field: &mut String, start: usize| {
+        fields.push(std::mem::take(field));
+        if fields.iter().any(|f| !f.trim().is_empty()) {
+            records.push((start, std::mem::take(fields)));
+        }
+        fields.clear();
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if in_quotes => in_quotes = false,
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                in_quotes = true;
+            }
+            '\r' if !in_quotes => {}
+            '\n' => {
+                line += 1;
+                if in_quotes {
+                    field.push(c);
+                } else {
+                    finish(&mut fields, &mut field, start);
+                    start = line;
+                }
+            }
+            c if c == delimiter && !in_quotes => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    finish(&mut fields, &mut field, start);
+    records
+}
+
+/// Column indexes for each mapped field
+struct Columns {
+    date: usize,
+    vendor: usize,
+    total: usize,
+    gst: Option<usize>,
+    category: Option<usize>,
+}
+
+impl Columns {
+    fn resolve(mapping: &ColumnMapping, headers: &[String], has_header: bool) -> Result<Self, String> {
+        let find = |name: &str| -> Result<usize, String> {
+            let name = name.trim();
+            if name.is_empty() {
+                return Err("Date, vendor and total columns must be mapped".to_string());
+            }
+            if has_header {
+                headers.iter().position(|h| h.trim().eq_ignore_ascii_case(name))
+            } else {
+                name.parse::<usize>().ok().filter(|&n| n >= 1).map(|n| n - 1)
+            }
+            .ok_or_else(|| format!("Column '{}' not found", name))
+        };
+        let optional = |name: &Option<String>| name.as_deref().filter(|n| !n.trim().is_empty()).map(find).transpose();
+        Ok(Self {
+            date: find(&mapping.date)?,
+            vendor: find(&mapping.vendor)?,
+            total: find(&mapping.total)?,
+            gst: optional(&mapping.gst)?,
+            category: optional(&mapping.category)?,
+        })
+    }
+}
+
+fn parse_row(line: usize, fields: &[String], columns: &Columns, config: &CsvImportConfig) -> ParsedRow {
+    let cell = |index: usize| fields.get(index).map(|f| f.trim()).unwrap_or("");
+    let mut errors = Vec::new();
+
+    let date = dates::normalize(cell(columns.date), config.date_order).iso;
+    if date.is_none() {
+        errors.push(format!("Invalid date '{}'", cell(columns.date)));
+    }
+    let total = Money::parse(cell(columns.total));
+    if total.is_none() {
+        errors.push(format!("Invalid total '{}'", cell(columns.total)));
+    }
+    let gst = match columns.gst.map(cell).filter(|gst| !gst.is_empty()) {
+        Some(raw) => Money::parse(raw).or_else(|| {
+            errors.push(format!("Invalid GST '{}'", raw));
+            None
+        }),
+        None => None,
+    };
+
+    let entry = match (date, total) {
+        (Some(date), Some(total)) if errors.is_empty() => Some(ManualEntry {
+            kind: config.kind,
+            vendor: cell(columns.vendor).to_string(),
+            date,
+            total,
+            gst,
+            category: columns.category.map(cell).filter(|c| !c.is_empty()).map(str::to_string),
+            entity_id: config.entity_id.clone(),
+            source_path: None,
+        }),
+        _ => None,
+    };
+    // Vendor, total and GST get the same checks as a typed-in entry
+    let entry = entry.and_then(|entry| match StoredDocument::default().apply_manual_entry(entry.clone()) {
+        Ok(()) => Some(entry),
+        Err(e) => {
+            errors.push(e);
+            None
+        }
+    });
+    ParsedRow { line, entry, errors }
+}
+
+/// Read every data row of a file, returning the headers and the rows
+pub fn read_rows(text: &str, config: &CsvImportConfig) -> Result<(Vec<String>, Vec<ParsedRow>), String> {
+    let mut records = split_records(text, config.delimiter).into_iter();
+    let headers = if config.has_header {
+        records.next().map(|(_, fields)| fields).ok_or_else(|| "The file is empty".to_string())?
+    } else {
+        Vec::new()
+    };
+    let columns = Columns::resolve(&config.columns, &headers, config.has_header)?;
+    let rows = records.map(|(line, fields)| parse_row(line, &fields, &columns, config)).collect();
+    Ok((headers, rows))
+}
+
+/// Parse the first `limit` rows, counting how many in the file are valid
+pub fn preview(text: &str, config: &CsvImportConfig, limit: usize) -> Result<CsvPreview, String> {
+    let (headers, rows) = read_rows(text, config)?;
+    Ok(CsvPreview {
+        headers,
+        total_rows: rows.len(),
+        valid_rows: rows.iter().filter(|row| row.entry.is_some()).count(),
+        rows: rows.into_iter().take(limit).collect(),
+    })
+}
+
+/// Record every valid row as a manual entry. The caller saves the store.
+pub fn import(store: &mut DocumentStore, text: &str, config: &CsvImportConfig) -> Result<CsvImportResult, String> {
+    let (_, rows) = read_rows(text, config)?;
+    let mut result = CsvImportResult {
+        imported: 0,
+        document_ids: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for mut row in rows {
+        let Some(entry) = row.entry.clone() else {
+            result.skipped.push(row);
+            continue;
+        };
+        let mut document = StoredDocument::default();
+        match document.apply_manual_entry(entry).and_then(|()| store.update(document)) {
+            Ok(saved) => {
+                result.imported += 1;
+                result.document_ids.push(saved.id);
+            }
+            Err(e) => {
+                row.errors.push(e);
+                result.skipped.push(row);
+            }
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents::DocumentFilter;
+    use crate::storage;
+
+    #[test]
+    fn test_rows_are_previewed_validated_and_imported() {
+        let text = "Date,Payee,Amount,GST,Category\r\n\
+            03/04/2023,\"Officeworks, Richmond\",\"$1,045.00\",95.00,Office\r\n\
+            \r\n\
+            31/02/2023,Telstra,89.00,,Phone\n\
+            2023-07-01,\"Bunnings \"\"Trade\"\"\",abc,,\n\
+            15/08/2023,Caltex,-20.00,,Fuel\n\
+            2023-09-30,Qantas,412.50,37.50,\n";
+        let config = CsvImportConfig {
+            columns: ColumnMapping {
+                date: "date".to_string(),
+                vendor: "Payee".to_string(),
+                total: "Amount".to_string(),
+                gst: Some("GST".to_string()),
+                category: Some("Category".to_string()),
+            },
+            ..Default::default()
+        };
+
+        let shown = preview(text, &config, 2).unwrap();
+        assert_eq!(shown.headers, vec!["Date", "Payee", "Amount", "GST", "Category"]);
+        assert_eq!((shown.total_rows, shown.valid_rows, shown.rows.len()), (5, 2, 2));
+        let first = shown.rows[0].entry.as_ref().unwrap();
+        assert_eq!(first.vendor, "Officeworks, Richmond");
+        assert_eq!(first.date, "2023-04-03");
+        assert_eq!((first.total, first.gst), (Money::from_cents(104500), Some(Money::from_cents(9500))));
+        assert_eq!(shown.rows[1].line, 4);
+        assert_eq!(shown.rows[1].errors, vec!["Invalid date '31/02/2023'"]);
+
+        let missing = CsvImportConfig {
+            columns: ColumnMapping {
+                total: "Cost".to_string(),
+                ..config.columns.clone()
+            },
+            ..config.clone()
+        };
+        assert_eq!(preview(text, &missing, 2).unwrap_err(), "Column 'Cost' not found");
+
+        let dir = storage::test_dir();
+        let path = dir.join("documents.json");
+        let mut store = DocumentStore::open(&path).unwrap();
+        let result = import(&mut store, text, &config).unwrap();
+        assert_eq!(result.imported, 2);
+        let skipped: Vec<_> = result.skipped.iter().map(|row| (row.line, row.errors[0].as_str())).collect();
+        assert_eq!(
+            skipped,
+            vec![
+                (4, "Invalid date '31/02/2023'"),
+                (5, "Invalid total 'abc'"),
+                (6, "Total must be greater than zero"),
+            ]
+        );
+        let documents = store.list(&DocumentFilter::default());
+        assert!(documents.iter().all(|doc| doc.manual_entry));
+        assert!(documents.iter().any(|doc| doc.vendor.as_deref() == Some("Qantas") && doc.category.is_none()));
+    }
+}
@@ -0,0 +1,159 @@
+//! Currency Module
+//!
+//! Invoice amounts carry the currency they were printed in. Most suppliers
+//! bill in Australian dollars, but overseas ones print USD, EUR, NZD and
+//! others, by ISO code (`USD 1,100.00`) or by symbol (`US$`, `€`). Amounts
+//! with no currency marked are taken to be Australian dollars.
+
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::sync::OnceLock;
+
+use crate::invoice::ExtractedField;
+use crate::money::Money;
+
+/// Currency BAS figures are reported in
+pub const HOME_CURRENCY: &str = "AUD";
+
+/// ISO codes recognised on invoices, as a regex alternation
+pub const ISO_CODES: &str = "AUD|USD|EUR|GBP|NZD|CAD|SGD|HKD|JPY|CNY|CHF|INR";
+
+/// Prefixes of dollar signs, such as `NZ` in `NZ$`
+const DOLLAR_PREFIXES: &str = "AU|US|NZ|CA|HK|A|C|S";
+
+/// Optional currency marker before an amount, such as `USD ` or `NZ$`, for
+/// use inside amount patterns
+pub fn amount_prefix() -> String {
+    format!(r"(?:(?:{})\s*|(?:{})\$\s*)?", ISO_CODES, DOLLAR_PREFIXES)
+}
+
+/// Confidence of a currency marked on the total line
+const TOTAL_LINE_CONFIDENCE: f64 = 0.9;
+
+/// Confidence of a currency marked elsewhere on the invoice
+const ELSEWHERE_CONFIDENCE: f64 = 0.7;
+
+/// An amount of money and the ISO 4217 code of its currency
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct CurrencyAmount {
+    pub value: Money,
+    pub currency: String,
+}
+
+impl CurrencyAmount {
+    pub fn new(value: Money, currency: &str) -> Self {
+        Self {
+            value,
+            currency: currency.to_string(),
+        }
+    }
+
+    pub fn aud(value: Money) -> Self {
+        Self::new(value, HOME_CURRENCY)
+    }
+
+    pub fn is_foreign(&self) -> bool {
+        self.currency != HOME_CURRENCY
+    }
+}
+
+/// Amounts saved before currencies were recorded are bare numbers
+impl<'de> Deserialize<'de> for CurrencyAmount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Stored {
+            Amount { value: Money, currency: String },
+            Bare(Money),
+        }
+        Ok(match Stored::deserialize(deserializer)? {
+            Stored::Amount { value, currency } => Self { value, currency },
+            Stored::Bare(value) => Self::aud(value),
+        })
+    }
+}
+
+fn marker_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(&format!(r"\b(?:{})\b|\b(?:{})\$|[€£¥]", ISO_CODES, DOLLAR_PREFIXES)).unwrap()
+    })
+}
+
+fn total_line_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?i)\b(?:total|amount\s*(?:due|payable)|balance\s*due)\b").unwrap())
+}
+
+/// ISO code of a printed marker
+fn code_for(marker: &str) -> &str {
+    match marker {
+        "AU$" | "A$" => "AUD",
+        "US$" => "USD",
+        "NZ$" => "NZD",
+        "CA$" | "C$" => "CAD",
+        "S$" => "SGD",
+        "HK$" => "HKD",
+        "€" => "EUR",
+        "£" => "GBP",
+        "¥" => "JPY",
+        code => code,
+    }
+}
+
+/// Currency the invoice is billed in: the one marked on the total line, or
+/// else the one marked most often. `None` when no currency is marked.
+pub fn detect_currency(text: &str) -> Option<ExtractedField<String>> {
+    let markers = |line: &str| -> Vec<String> {
+        marker_pattern().find_iter(line).map(|m| m.as_str().to_string()).collect()
+    };
+    let field = |marker: &str, confidence: f64| ExtractedField {
+        raw: Some(marker.to_string()),
+        ..ExtractedField::new(code_for(marker).to_string(), confidence, "currency_regex")
+    };
+
+    if let Some(marker) = text
+        .lines()
+        .filter(|line| total_line_pattern().is_match(line))
+        .find_map(|line| markers(line).into_iter().next())
+    {
+        return Some(field(&marker, TOTAL_LINE_CONFIDENCE));
+    }
+
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for marker in markers(text) {
+        let code = code_for(&marker).to_string();
+        match counts.iter_mut().find(|(counted, _)| *counted == code) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((code, 1)),
+        }
+    }
+    // Earliest marked wins a tie
+    let (code, _) = counts.into_iter().rev().max_by_key(|(_, count)| *count)?;
+    let marker = markers(text).into_iter().find(|marker| code_for(marker) == code)?;
+    Some(field(&marker, ELSEWHERE_CONFIDENCE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_currency_is_detected_and_old_amounts_load() {
+        let invoice = "Cloudhost Inc.\nHosting plan  USD 90.00\nAll prices in EUR\nTotal due: US$ 99.00";
+        let currency = detect_currency(invoice).unwrap();
+        assert_eq!((currency.value.as_str(), currency.raw.as_deref()), ("USD", Some("US$")));
+        assert_eq!(currency.confidence, TOTAL_LINE_CONFIDENCE);
+
+        let currency = detect_currency("Kiwi Design Ltd\nLogo NZ$ 400.00\nFonts NZD 50.00\nNote: €5 fee waived").unwrap();
+        assert_eq!((currency.value.as_str(), currency.confidence), ("NZD", ELSEWHERE_CONFIDENCE));
+        assert!(detect_currency("Acme Pty Ltd\nTotal: $110.00").is_none());
+
+        let stored: CurrencyAmount = serde_json::from_str("110.5").unwrap();
+        assert_eq!(stored, CurrencyAmount::aud(Money::from_cents(11050)));
+        let amount = CurrencyAmount::new(Money::from_cents(9900), "USD");
+        let json = serde_json::to_string(&amount).unwrap();
+        assert_eq!(json, r#"{"value":99.0,"currency":"USD"}"#);
+        assert!(serde_json::from_str::<CurrencyAmount>(&json).unwrap().is_foreign());
+    }
+}
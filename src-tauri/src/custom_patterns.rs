@@ -0,0 +1,358 @@
+//! Custom Patterns Module
+//!
+//! Regexes users add for fields the built-in patterns miss, such as one
+//! supplier's job numbers. Each pattern names the field it fills: one of the
+//! invoice's own fields, or any other name, which is kept as a custom field.
+//! A field's patterns are tried in the order they were added and the first
+//! match wins over the built-in patterns, vendor templates and the layout
+//! model. The pattern's first capture group
+//! is the value, or the whole match when it has none. Every parse counts
+//! which patterns matched, so weak ones can be spotted and removed.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::currency::CurrencyAmount;
+use crate::dates::{self, DateOrder};
+use crate::invoice::{ExtractedField, ExtractedInvoice};
+use crate::money::Money;
+use crate::storage;
+
+static PATTERNS_LOCK: Mutex<()> = Mutex::new(());
+
+/// Confidence of a value read by a user's pattern
+const PATTERN_CONFIDENCE: f64 = 0.95;
+
+/// Prefix of the source of a field read by a user's pattern, before its ID
+const SOURCE_PREFIX: &str = "custom_pattern:";
+
+/// Invoice fields a pattern can fill; any other name is a custom field
+pub const INVOICE_FIELDS: [&str; 12] = [
+    "abn",
+    "invoice_number",
+    "purchase_order_number",
+    "quote_number",
+    "invoice_date",
+    "due_date",
+    "vendor_name",
+    "payment_terms",
+    "bill_to_name",
+    "subtotal_amount",
+    "gst_amount",
+    "total_amount",
+];
+
+/// A user's regex for one field, with how often it has matched
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CustomPattern {
+    pub id: String,
+    /// Field name in snake_case, such as `invoice_number` or `job_number`
+    pub field: String,
+    pub pattern: String,
+    pub description: Option<String>,
+    pub created_at: String,
+    /// Invoices the pattern was tried on
+    #[serde(default)]
+    pub checked_count: u64,
+    /// Invoices it filled its field on
+    #[serde(default)]
+    pub match_count: u64,
+    #[serde(default)]
+    pub last_matched_at: Option<String>,
+}
+
+/// A field's patterns in the order they are tried
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CustomPatternGroup {
+    pub field: String,
+    /// Whether the field is one of the invoice's own
+    pub invoice_field: bool,
+    pub patterns: Vec<CustomPattern>,
+}
+
+/// A pattern ready to run
+#[derive(Debug, Clone)]
+pub struct CompiledPattern {
+    id: String,
+    field: String,
+    regex: Regex,
+}
+
+fn field_name_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^[a-z][a-z0-9_]*$").unwrap())
+}
+
+fn compile(pattern: &CustomPattern) -> Result<CompiledPattern, String> {
+    let regex = Regex::new(&pattern.pattern).map_err(|e| format!("Invalid pattern '{}': {}", pattern.pattern, e))?;
+    Ok(CompiledPattern {
+        id: pattern.id.clone(),
+        field: pattern.field.clone(),
+        regex,
+    })
+}
+
+/// Put a matched value in its field; false when it can't be read as one
+fn fill(invoice: &mut ExtractedInvoice, field: &str, value: &str, source: &str, order: DateOrder) -> bool {
+    let text = || Some(ExtractedField::new(value.to_string(), PATTERN_CONFIDENCE, source));
+    let date = || {
+        let normalized = dates::normalize(value, order);
+        normalized.iso.map(|iso| ExtractedField {
+            raw: Some(value.to_string()),
+            ambiguous: normalized.ambiguous,
+            ..ExtractedField::new(iso, PATTERN_CONFIDENCE, source)
+        })
+    };
+    let currency = invoice.currency_code().to_string();
+    let amount = || {
+        Money::parse(value)
+            .map(|amount| ExtractedField::new(CurrencyAmount::new(amount, &currency), PATTERN_CONFIDENCE, source))
+    };
+    let filled = match field {
+        "abn" => {
+            let digits: String = value.chars().filter(char::is_ascii_digit).collect();
+            (digits.len() == 11).then(|| invoice.abn = Some(ExtractedField::new(digits, PATTERN_CONFIDENCE, source)))
+        }
+        "invoice_number" => text().map(|f| invoice.invoice_number = Some(f)),
+        "purchase_order_number" => text().map(|f| invoice.purchase_order_number = Some(f)),
+        "quote_number" => text().map(|f| invoice.quote_number = Some(f)),
+        "invoice_date" => date().map(|f| invoice.invoice_date = Some(f)),
+        "due_date" => date().map(|f| invoice.due_date = Some(f)),
+        "vendor_name" => text().map(|f| invoice.vendor_name = Some(f)),
+        "payment_terms" => text().map(|f| invoice.payment_terms = Some(f)),
+        "bill_to_name" => text().map(|f| invoice.bill_to_name = Some(f)),
+        "subtotal_amount" => amount().map(|f| invoice.subtotal_amount = Some(f)),
+        "gst_amount" => amount().map(|f| invoice.gst_amount = Some(f)),
+        "total_amount" => amount().map(|f| invoice.total_amount = Some(f)),
+        other => text().map(|f| {
+            invoice.custom_fields.insert(other.to_string(), f);
+        }),
+    };
+    filled.is_some()
+}
+
+/// Fill fields of a parsed invoice from the first of each field's patterns
+/// to match `text`, replacing what the built-in patterns found
+pub fn apply(patterns: &[CompiledPattern], text: &str, invoice: &mut ExtractedInvoice, order: DateOrder) {
+    let mut filled: Vec<&str> = Vec::new();
+    for pattern in patterns {
+        if filled.contains(&pattern.field.as_str()) {
+            continue;
+        }
+        let Some(captures) = pattern.regex.captures(text) else {
+            continue;
+        };
+        let Some(value) = captures.get(1).or_else(|| captures.get(0)).map(|m| m.as_str().trim()) else {
+            continue;
+        };
+        let source = format!("{}{}", SOURCE_PREFIX, pattern.id);
+        if !value.is_empty() && fill(invoice, &pattern.field, value, &source, order) {
+            filled.push(&pattern.field);
+        }
+    }
+}
+
+/// IDs of the patterns whose values an invoice holds
+pub fn matched_pattern_ids(invoice: &ExtractedInvoice) -> Vec<String> {
+    let text_sources = [
+        &invoice.abn,
+        &invoice.invoice_number,
+        &invoice.purchase_order_number,
+        &invoice.quote_number,
+        &invoice.invoice_date,
+        &invoice.due_date,
+        &invoice.vendor_name,
+        &invoice.payment_terms,
+        &invoice.bill_to_name,
+    ]
+    .into_iter()
+    .filter_map(|field| field.as_ref().map(|f| f.source.as_str()));
+    let amount_sources = [&invoice.subtotal_amount, &invoice.gst_amount, &invoice.total_amount]
+        .into_iter()
+        .filter_map(|field| field.as_ref().map(|f| f.source.as_str()));
+    text_sources
+        .chain(amount_sources)
+        .chain(invoice.custom_fields.values().map(|f| f.source.as_str()))
+        .filter_map(|source| source.strip_prefix(SOURCE_PREFIX))
+        .map(str::to_string)
+        .collect()
+}
+
+/// JSON-backed list of custom patterns
+pub struct PatternStore {
+    path: PathBuf,
+    patterns: Vec<CustomPattern>,
+}
+
+impl PatternStore {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            patterns: storage::load_json(path)?,
+        })
+    }
+
+    pub fn open_default() -> Result<Self, String> {
+        Self::open(&storage::get_data_directory()?.join("custom_patterns.json"))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        storage::save_json(&self.path, &self.patterns)
+    }
+
+    /// Add a pattern after the field's existing ones
+    pub fn register(
+        &mut self,
+        field: &str,
+        pattern: &str,
+        description: Option<String>,
+    ) -> Result<CustomPattern, String> {
+        let field = field.trim().to_lowercase();
+        if !field_name_pattern().is_match(&field) {
+            return Err(format!("Field name '{}' must be letters, digits and underscores", field));
+        }
+        if pattern.trim().is_empty() {
+            return Err("Pattern is empty".to_string());
+        }
+        let pattern = CustomPattern {
+            id: storage::generate_id("pattern"),
+            field,
+            pattern: pattern.to_string(),
+            description: description.map(|d| d.trim().to_string()).filter(|d| !d.is_empty()),
+            created_at: storage::now_timestamp(),
+            checked_count: 0,
+            match_count: 0,
+            last_matched_at: None,
+        };
+        compile(&pattern)?;
+        self.patterns.push(pattern.clone());
+        Ok(pattern)
+    }
+
+    pub fn delete(&mut self, id: &str) -> bool {
+        let before = self.patterns.len();
+        self.patterns.retain(|p| p.id != id);
+        self.patterns.len() != before
+    }
+
+    /// Patterns grouped by field name, alphabetically
+    pub fn groups(&self) -> Vec<CustomPatternGroup> {
+        let mut groups: Vec<CustomPatternGroup> = Vec::new();
+        for pattern in &self.patterns {
+            match groups.iter_mut().find(|g| g.field == pattern.field) {
+                Some(group) => group.patterns.push(pattern.clone()),
+                None => groups.push(CustomPatternGroup {
+                    field: pattern.field.clone(),
+                    invoice_field: INVOICE_FIELDS.contains(&pattern.field.as_str()),
+                    patterns: vec![pattern.clone()],
+                }),
+            }
+        }
+        groups.sort_by(|a, b| a.field.cmp(&b.field));
+        groups
+    }
+
+    /// Every pattern that compiles, in the order they are tried. One that
+    /// no longer compiles, such as after a hand edit of the file, is logged
+    /// and skipped so the others still apply.
+    pub fn compiled(&self) -> Vec<CompiledPattern> {
+        self.patterns
+            .iter()
+            .filter_map(|pattern| {
+                compile(pattern)
+                    .map_err(|e| log::warn!("Skipping custom pattern {} for {}: {}", pattern.id, pattern.field, e))
+                    .ok()
+            })
+            .collect()
+    }
+
+    /// Count a parse of an invoice towards each pattern's statistics
+    pub fn record_parse(&mut self, invoice: &ExtractedInvoice) {
+        let matched = matched_pattern_ids(invoice);
+        let now = storage::now_timestamp();
+        for pattern in &mut self.patterns {
+            pattern.checked_count += 1;
+            if matched.contains(&pattern.id) {
+                pattern.match_count += 1;
+                pattern.last_matched_at = Some(now.clone());
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+}
+
+/// Run the default store under its lock
+pub fn with_patterns<R>(f: impl FnOnce(&mut PatternStore) -> Result<R, String>) -> Result<R, String> {
+    let _guard = PATTERNS_LOCK.lock().map_err(|_| "Custom patterns lock poisoned".to_string())?;
+    let mut store = PatternStore::open_default()?;
+    f(&mut store)
+}
+
+/// Count a parse towards the default store's statistics. Failing to save
+/// them doesn't fail the parse.
+pub fn record_parse(invoice: &ExtractedInvoice) {
+    let result = with_patterns(|store| {
+        if store.is_empty() {
+            return Ok(());
+        }
+        store.record_parse(invoice);
+        store.save()
+    });
+    if let Err(e) = result {
+        log::warn!("Failed to record custom pattern matches: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invoice::{DocumentType, InvoiceParser};
+
+    #[test]
+    fn test_custom_patterns_fill_fields_before_built_ins() {
+        let dir = storage::test_dir();
+        let mut store = PatternStore::open(&dir.join("custom_patterns.json")).unwrap();
+        assert!(store.register("job number", r"\d+", None).is_err());
+        assert!(store.register("job_number", r"Job (\d+", None).is_err());
+        let job = store.register("job_number", r"Job No\.?\s*(J-\d+)", Some("Acme jobs".to_string())).unwrap();
+        let missed = store.register("invoice_number", r"Ref#\s*(\w+)", None).unwrap();
+        let reference = store.register("invoice_number", r"Our ref:\s*(\S+)", None).unwrap();
+        store.register("invoice_number", r"Docket\s*(\d+)", None).unwrap();
+        let groups = store.groups();
+        assert_eq!(groups.iter().map(|g| g.field.as_str()).collect::<Vec<_>>(), ["invoice_number", "job_number"]);
+        assert!(groups[0].invoice_field && !groups[1].invoice_field);
+
+        let text = "Acme Plumbing Pty Ltd\nTAX INVOICE\nInvoice Number: INV-1001\nOur ref: AP/778\n\
+            Docket 42\nJob No. J-5521\nSubtotal 100.00\nGST 10.00\nTotal 110.00";
+        // A pattern broken outside the app is skipped rather than failing the parse
+        let mut broken = store.patterns[0].clone();
+        broken.id = storage::generate_id("pattern");
+        broken.pattern = r"Job (\d+".to_string();
+        store.patterns.insert(0, broken.clone());
+        assert_eq!(store.compiled().len(), 4);
+        assert!(store.delete(&broken.id));
+
+        let parser = InvoiceParser::new().unwrap().with_custom_patterns(store.compiled());
+        let invoice = parser.parse_from_text(text, DocumentType::Pdf).unwrap();
+        let number = invoice.invoice_number.as_ref().unwrap();
+        assert_eq!(number.value, "AP/778");
+        assert_eq!(number.source, format!("custom_pattern:{}", reference.id));
+        assert_eq!(invoice.custom_fields["job_number"].value, "J-5521");
+        assert_eq!(invoice.total_amount.as_ref().unwrap().value.value, Money::from_cents(11000));
+
+        store.record_parse(&invoice);
+        let stats = |id: &str| {
+            let pattern = store.patterns.iter().find(|p| p.id == id).unwrap();
+            (pattern.checked_count, pattern.match_count)
+        };
+        assert_eq!((stats(&job.id), stats(&reference.id), stats(&missed.id)), ((1, 1), (1, 1), (1, 0)));
+        assert!(store.delete(&missed.id));
+        store.save().unwrap();
+        assert_eq!(PatternStore::open(&dir.join("custom_patterns.json")).unwrap().groups()[0].patterns.len(), 2);
+    }
+}
@@ -0,0 +1,116 @@
+//! Date Normalization Module
+//!
+//! Converts dates read from receipts and invoices to ISO 8601, resolving
+//! numeric dates that could be either day-first or month-first.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+/// How to read numeric dates such as 03/04/2024 when either field could be
+/// the month
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DateOrder {
+    /// 03/04/2024 is 3 April, as in Australia
+    #[default]
+    DayFirst,
+    /// 03/04/2024 is 4 March, as on US invoices
+    MonthFirst,
+}
+
+/// A date as printed alongside its ISO 8601 form
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct NormalizedDate {
+    pub raw: String,
+    /// `YYYY-MM-DD`, or `None` when the text is not a valid date
+    pub iso: Option<String>,
+    /// Day and month could be swapped; `iso` follows the configured order
+    pub ambiguous: bool,
+}
+
+/// Formats naming the month
+const MONTH_NAME_FORMATS: [&str; 4] = ["%d %B %Y", "%d %b %Y", "%B %d %Y", "%b %d %Y"];
+
+/// Normalize a printed date, reading numeric dates in `order` when both
+/// readings are valid
+pub fn normalize(raw: &str, order: DateOrder) -> NormalizedDate {
+    let text = raw.trim();
+    let named = MONTH_NAME_FORMATS
+        .iter()
+        .find_map(|fmt| NaiveDate::parse_from_str(&text.replace(',', ""), fmt).ok());
+    let (date, ambiguous) = match named {
+        Some(date) => (Some(date), false),
+        None => numeric_date(text, order),
+    };
+    NormalizedDate {
+        raw: text.to_string(),
+        iso: date.map(|d| d.format("%Y-%m-%d").to_string()),
+        ambiguous,
+    }
+}
+
+/// Read `a/b/y` (or with `-` or `.`), using whichever order gives a valid
+/// date and falling back to `order` when both do. A four-digit first field
+/// is always year-month-day.
+fn numeric_date(text: &str, order: DateOrder) -> (Option<NaiveDate>, bool) {
+    let parts: Vec<&str> = text.split(['/', '-', '.']).collect();
+    let [a, b, y] = parts[..] else {
+        return (None, false);
+    };
+    if a.len() == 4 {
+        let ymd = (a.parse(), b.parse(), y.parse());
+        let date = match ymd {
+            (Ok(year), Ok(month), Ok(day)) => NaiveDate::from_ymd_opt(year, month, day),
+            _ => None,
+        };
+        return (date, false);
+    }
+    let (Ok(a), Ok(b), Ok(year)) = (a.parse::<u32>(), b.parse::<u32>(), y.parse::<i32>()) else {
+        return (None, false);
+    };
+    let year = match y.len() {
+        2 => 2000 + year,
+        4 => year,
+        _ => return (None, false),
+    };
+    let day_first = NaiveDate::from_ymd_opt(year, b, a);
+    let month_first = NaiveDate::from_ymd_opt(year, a, b);
+    match (day_first, month_first) {
+        (Some(d), Some(m)) if d != m => match order {
+            DateOrder::DayFirst => (Some(d), true),
+            DateOrder::MonthFirst => (Some(m), true),
+        },
+        (Some(d), _) => (Some(d), false),
+        (None, m) => (m, false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ambiguous_dates_follow_order_and_are_flagged() {
+        let au = normalize("03/04/2024", DateOrder::DayFirst);
+        assert_eq!(au.iso.as_deref(), Some("2024-04-03"));
+        assert!(au.ambiguous);
+        let us = normalize("03/04/2024", DateOrder::MonthFirst);
+        assert_eq!(us.iso.as_deref(), Some("2024-03-04"));
+        assert!(us.ambiguous);
+
+        // Only one reading is a real date
+        let late = normalize("25/12/24", DateOrder::MonthFirst);
+        assert_eq!(late.iso.as_deref(), Some("2024-12-25"));
+        assert!(!late.ambiguous);
+        let us_only = normalize("12/25/2024", DateOrder::DayFirst);
+        assert_eq!(us_only.iso.as_deref(), Some("2024-12-25"));
+        assert!(!us_only.ambiguous);
+
+        assert!(!normalize("05/05/2024", DateOrder::DayFirst).ambiguous);
+        assert_eq!(normalize("3 April 2024", DateOrder::MonthFirst).iso.as_deref(), Some("2024-04-03"));
+        assert_eq!(normalize("2024-04-03", DateOrder::MonthFirst).iso.as_deref(), Some("2024-04-03"));
+        let invalid = normalize("31/31/2024", DateOrder::DayFirst);
+        assert_eq!(invalid.iso, None);
+        assert_eq!(invalid.raw, "31/31/2024");
+    }
+}
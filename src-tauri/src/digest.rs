@@ -0,0 +1,385 @@
+//! Weekly Digest Module
+//!
+//! Summarises a week's activity (documents imported, totals, items awaiting
+//! review and upcoming due dates) into a compact HTML report. Digests can be
+//! generated on demand or automatically on a chosen weekday at startup.
+
+use chrono::{Datelike, Days, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cadence::{self, MissingInvoiceAlert};
+use crate::documents::{self, DocumentFilter, DocumentStatus, StoredDocument};
+use crate::money::Money;
+use crate::periods;
+use crate::saved_reports::{self, ReportParameters, ReportTotal};
+use crate::settings::{self, AppSettings};
+use crate::storage;
+
+/// How far past the end of the week due dates are listed
+const DUE_WINDOW_DAYS: u64 = 14;
+
+/// Grace period used for missing invoice alerts in the digest
+const MISSING_GRACE_DAYS: i64 = 7;
+
+/// Schedule for automatic digest generation
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct DigestSettings {
+    pub enabled: bool,
+    /// Day of the week the digest is generated for the preceding week
+    /// (0 = Monday ... 6 = Sunday)
+    pub weekday: u32,
+    /// Week-ending date of the last generated digest
+    pub last_week_ending: Option<String>,
+}
+
+/// Spend for one category within the week
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CategoryTotal {
+    pub category: String,
+    pub count: usize,
+    pub total: Money,
+}
+
+/// A document listed in the digest
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DigestItem {
+    pub document_id: String,
+    pub vendor: Option<String>,
+    pub date: Option<String>,
+    pub amount: Option<Money>,
+}
+
+/// One week of activity
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WeeklyDigest {
+    pub week_start: String,
+    pub week_ending: String,
+    pub imported_count: usize,
+    pub imported_total: Money,
+    pub imported_gst: Money,
+    pub categories: Vec<CategoryTotal>,
+    pub awaiting_review: Vec<DigestItem>,
+    /// Invoices due within two weeks of the end of the week
+    pub upcoming_due: Vec<DigestItem>,
+    pub missing_invoices: Vec<MissingInvoiceAlert>,
+}
+
+/// A digest written to disk
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DigestSaveResult {
+    pub digest: WeeklyDigest,
+    pub file_path: String,
+}
+
+/// Assemble the digest for the seven days ending on `week_ending`
+pub fn build_weekly_digest(documents: &[StoredDocument], week_ending: NaiveDate) -> WeeklyDigest {
+    let week_start = week_ending - Days::new(6);
+    let due_until = week_ending + Days::new(DUE_WINDOW_DAYS);
+
+    let imported: Vec<&StoredDocument> = documents
+        .iter()
+        .filter(|d| {
+            periods::parse_date(&d.created_at).is_some_and(|c| c >= week_start && c <= week_ending)
+        })
+        .collect();
+
+    let mut by_category: BTreeMap<String, (usize, Money)> = BTreeMap::new();
+    for doc in &imported {
+        let entry = by_category
+            .entry(doc.category.clone().unwrap_or_else(|| "Uncategorised".to_string()))
+            .or_default();
+        entry.0 += 1;
+        entry.1 += doc.aud_total().unwrap_or_default();
+    }
+
+    let awaiting_review = documents
+        .iter()
+        .filter(|d| d.status == DocumentStatus::PendingReview)
+        .map(|d| item(d, d.date.clone()))
+        .collect();
+
+    let mut upcoming_due: Vec<DigestItem> = documents
+        .iter()
+        .filter_map(|d| {
+            let raw = d.invoice.as_ref()?.due_date.as_ref()?;
+            let due = documents::normalize_date(&raw.value)?;
+            let due_date = periods::parse_date(&due)?;
+            (due_date > week_ending && due_date <= due_until).then(|| item(d, Some(due)))
+        })
+        .collect();
+    upcoming_due.sort_by(|a, b| a.date.cmp(&b.date));
+
+    WeeklyDigest {
+        week_start: week_start.format("%Y-%m-%d").to_string(),
+        week_ending: week_ending.format("%Y-%m-%d").to_string(),
+        imported_count: imported.len(),
+        imported_total: imported.iter().filter_map(|d| d.aud_total()).sum(),
+        imported_gst: imported.iter().filter_map(|d| d.aud_gst()).sum(),
+        categories: by_category
+            .into_iter()
+            .map(|(category, (count, total))| CategoryTotal {
+                category,
+                count,
+                total,
+            })
+            .collect(),
+        awaiting_review,
+        upcoming_due,
+        missing_invoices: cadence::missing_invoice_alerts(documents, week_ending, MISSING_GRACE_DAYS),
+    }
+}
+
+fn item(doc: &StoredDocument, date: Option<String>) -> DigestItem {
+    DigestItem {
+        document_id: doc.id.clone(),
+        vendor: doc.vendor.clone(),
+        date,
+        amount: doc.total,
+    }
+}
+
+/// Render the digest as a standalone HTML page
+pub fn render_html(digest: &WeeklyDigest) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">");
+    html.push_str(&format!(
+        "<title>Tally weekly digest {}</title>",
+        escape(&digest.week_ending)
+    ));
+    html.push_str(
+        "<style>body{font-family:sans-serif;max-width:640px;margin:auto}\
+         table{width:100%;border-collapse:collapse}\
+         td,th{padding:4px;border-bottom:1px solid #ddd;text-align:left}\
+         .num{text-align:right}</style></head><body>",
+    );
+    html.push_str(&format!(
+        "<h1>Week of {} to {}</h1>",
+        escape(&digest.week_start),
+        escape(&digest.week_ending)
+    ));
+    html.push_str(&format!(
+        "<p>{} documents imported totalling {} (GST {}).</p>",
+        digest.imported_count,
+        money(Some(digest.imported_total)),
+        money(Some(digest.imported_gst))
+    ));
+
+    if !digest.categories.is_empty() {
+        html.push_str("<h2>By category</h2><table><tr><th>Category</th><th class=\"num\">Count</th><th class=\"num\">Total</th></tr>");
+        for c in &digest.categories {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td></tr>",
+                escape(&c.category),
+                c.count,
+                money(Some(c.total))
+            ));
+        }
+        html.push_str("</table>");
+    }
+
+    push_items(&mut html, "Awaiting review", "Date", &digest.awaiting_review);
+    push_items(&mut html, "Upcoming due dates", "Due", &digest.upcoming_due);
+
+    if !digest.missing_invoices.is_empty() {
+        html.push_str("<h2>Expected invoices not received</h2><ul>");
+        for alert in &digest.missing_invoices {
+            html.push_str(&format!(
+                "<li>{}: expected {} ({} days overdue)</li>",
+                escape(&alert.vendor),
+                escape(&alert.expected_date),
+                alert.days_overdue
+            ));
+        }
+        html.push_str("</ul>");
+    }
+
+    html.push_str("</body></html>\n");
+    html
+}
+
+fn push_items(html: &mut String, heading: &str, date_heading: &str, items: &[DigestItem]) {
+    if items.is_empty() {
+        return;
+    }
+    html.push_str(&format!(
+        "<h2>{} ({})</h2><table><tr><th>Vendor</th><th>{}</th><th class=\"num\">Amount</th></tr>",
+        heading,
+        items.len(),
+        date_heading
+    ));
+    for item in items {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td class=\"num\">{}</td></tr>",
+            escape(item.vendor.as_deref().unwrap_or("Unknown vendor")),
+            escape(item.date.as_deref().unwrap_or("-")),
+            money(item.amount)
+        ));
+    }
+    html.push_str("</table>");
+}
+
+fn money(amount: Option<Money>) -> String {
+    amount.map(|a| format!("${}", a)).unwrap_or_else(|| "-".to_string())
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Directory digests are written to
+pub fn get_digest_directory() -> Result<PathBuf, String> {
+    Ok(storage::get_data_directory()?.join("digests"))
+}
+
+/// Build the digest for the week ending on `week_ending`, write it to disk
+/// and record it in the saved report register
+pub fn generate_and_save(week_ending: NaiveDate) -> Result<DigestSaveResult, String> {
+    let file_path = get_digest_directory()?.join(format!("weekly-digest-{}.html", week_ending.format("%Y-%m-%d")));
+    let result = save_digest(week_ending, &file_path)?;
+
+    let parameters = ReportParameters::WeeklyDigest {
+        week_ending: result.digest.week_ending.clone(),
+    };
+    if let Err(e) = saved_reports::record(parameters, &result.file_path, digest_totals(&result.digest)) {
+        log::warn!("Failed to record weekly digest: {}", e);
+    }
+    Ok(result)
+}
+
+/// Build the digest for the week ending on `week_ending` and write it to `file_path`
+pub fn save_digest(week_ending: NaiveDate, file_path: &Path) -> Result<DigestSaveResult, String> {
+    let documents = documents::with_store(|store| Ok(store.list(&DocumentFilter::default())))?;
+    let digest = build_weekly_digest(&documents, week_ending);
+
+    if let Some(dir) = file_path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    fs::write(file_path, render_html(&digest)).map_err(|e| format!("Failed to write digest: {}", e))?;
+
+    Ok(DigestSaveResult {
+        digest,
+        file_path: file_path.to_string_lossy().to_string(),
+    })
+}
+
+/// Headline figures compared between versions of a saved digest
+pub fn digest_totals(digest: &WeeklyDigest) -> Vec<ReportTotal> {
+    let mut totals = vec![
+        ReportTotal::new("Documents imported", digest.imported_count as f64),
+        ReportTotal::new("Imported total", digest.imported_total.to_dollars()),
+        ReportTotal::new("Imported GST", digest.imported_gst.to_dollars()),
+        ReportTotal::new("Awaiting review", digest.awaiting_review.len() as f64),
+        ReportTotal::new("Upcoming due", digest.upcoming_due.len() as f64),
+    ];
+    totals.extend(
+        digest
+            .categories
+            .iter()
+            .map(|c| ReportTotal::new(&format!("Category: {}", c.category), c.total.to_dollars())),
+    );
+    totals
+}
+
+/// Week-ending date of the scheduled digest due on or before `today`,
+/// if it has not been generated yet
+pub fn scheduled_week_ending(schedule: &DigestSettings, today: NaiveDate) -> Option<NaiveDate> {
+    if !schedule.enabled {
+        return None;
+    }
+
+    let days_since = (7 + today.weekday().num_days_from_monday() - schedule.weekday % 7) % 7;
+    let week_ending = today - Days::new(days_since as u64 + 1);
+
+    let already_done = schedule
+        .last_week_ending
+        .as_deref()
+        .and_then(periods::parse_date)
+        .is_some_and(|last| last >= week_ending);
+    (!already_done).then_some(week_ending)
+}
+
+/// Generate any scheduled digest that is due, recording it in settings
+pub fn run_scheduled(today: NaiveDate) -> Result<Option<DigestSaveResult>, String> {
+    let mut settings: AppSettings = settings::load_settings()?;
+    let Some(week_ending) = scheduled_week_ending(&settings.digest, today) else {
+        return Ok(None);
+    };
+
+    let result = generate_and_save(week_ending)?;
+    settings.digest.last_week_ending = Some(result.digest.week_ending.clone());
+    settings::save_settings(&settings)?;
+    Ok(Some(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invoice::{ExtractedField, ExtractedInvoice};
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_weekly_digest_contents() {
+        let docs = vec![
+            StoredDocument {
+                id: "a".to_string(),
+                vendor: Some("Officeworks".to_string()),
+                category: Some("Office".to_string()),
+                total: Some(Money::from_cents(5500)),
+                gst: Some(Money::from_cents(500)),
+                status: DocumentStatus::PendingReview,
+                created_at: "2024-03-12T10:00:00+11:00".to_string(),
+                ..Default::default()
+            },
+            StoredDocument {
+                id: "b".to_string(),
+                vendor: Some("AGL".to_string()),
+                total: Some(Money::from_cents(22000)),
+                status: DocumentStatus::Reviewed,
+                created_at: "2024-03-01T10:00:00+11:00".to_string(),
+                invoice: Some(ExtractedInvoice {
+                    due_date: Some(ExtractedField::new("20/03/2024".to_string(), 0.8, "test")),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        ];
+
+        let digest = build_weekly_digest(&docs, date(2024, 3, 17));
+        assert_eq!(digest.week_start, "2024-03-11");
+        assert_eq!(digest.imported_count, 1);
+        assert_eq!(digest.imported_total, Money::from_cents(5500));
+        assert_eq!(digest.categories[0].category, "Office");
+        assert_eq!(digest.awaiting_review.len(), 1);
+        assert_eq!(digest.upcoming_due[0].date.as_deref(), Some("2024-03-20"));
+
+        let html = render_html(&digest);
+        assert!(html.contains("Officeworks"));
+        assert!(html.contains("$55.00"));
+    }
+
+    #[test]
+    fn test_scheduled_week_ending() {
+        let mut schedule = DigestSettings {
+            enabled: true,
+            ..Default::default()
+        };
+
+        // Wednesday 20 March 2024: the Monday digest covers the week ending Sunday 17th
+        assert_eq!(scheduled_week_ending(&schedule, date(2024, 3, 20)), Some(date(2024, 3, 17)));
+
+        schedule.last_week_ending = Some("2024-03-17".to_string());
+        assert_eq!(scheduled_week_ending(&schedule, date(2024, 3, 20)), None);
+        assert_eq!(scheduled_week_ending(&schedule, date(2024, 3, 25)), Some(date(2024, 3, 24)));
+    }
+}
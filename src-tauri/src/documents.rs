@@ -0,0 +1,1008 @@
+//! Document Store Module
+//!
+//! Persists processed receipts and invoices so the backend can operate on
+//! them directly. Supports:
+//! - Filtering stored documents
+//! - Batched mutations (recategorize, retag, move entity, delete, re-extract)
+//!   applied in a single write with per-document results
+//! - Per-field locks that protect user corrections from re-extraction
+//! - Manual entries for documents that couldn't be parsed
+//! - Revision numbers, so an edit made to an out-of-date copy is refused
+//!   rather than overwriting a newer save
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::business_use::{DeductionSplit, ImportDefaults};
+use crate::capture_metadata::CaptureMetadata;
+use crate::currency;
+use crate::dates::{self, DateOrder};
+use crate::gst_treatment::GstTreatment;
+use crate::invoice::{self, ExtractedInvoice};
+use crate::money::Money;
+use crate::pagination::{self, Page, PageRequest, SortKey};
+use crate::ocr::{ExtractedReceipt, OcrEngine};
+use crate::period_locks::{self, LodgedPeriod};
+use crate::periods;
+use crate::settings;
+use crate::rollover::{self, RolloverResult};
+use crate::storage;
+
+/// Fields that can be locked against re-extraction
+pub const LOCKABLE_FIELDS: [&str; 5] = ["vendor", "date", "total", "gst", "category"];
+
+/// Earlier OCR texts kept per document for comparing extraction versions
+pub const MAX_TEXT_VERSIONS: usize = 5;
+
+/// Serializes read-modify-write cycles against the documents file
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+/// A processed receipt or invoice
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct StoredDocument {
+    pub id: String,
+    pub kind: DocumentKind,
+    pub vendor: Option<String>,
+    /// Document date (YYYY-MM-DD)
+    pub date: Option<String>,
+    pub total: Option<Money>,
+    pub gst: Option<Money>,
+    /// ISO 4217 code of `total` and `gst` when they aren't Australian
+    /// dollars. Such amounts are left out of AUD totals and the BAS until
+    /// the user enters the converted amount.
+    pub currency: Option<String>,
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+    pub entity_id: Option<String>,
+    pub status: DocumentStatus,
+    /// Whether an invoice has been paid
+    pub paid: bool,
+    /// When the invoice was first saved as paid
+    pub paid_at: Option<String>,
+    pub confidence: f64,
+    /// Path of the original file the document was extracted from
+    pub source_path: Option<String>,
+    pub invoice: Option<ExtractedInvoice>,
+    pub receipt: Option<ExtractedReceipt>,
+    /// Fields corrected by the user that re-extraction must not overwrite
+    pub locked_fields: Vec<String>,
+    /// Quick-captured photo still waiting for extraction
+    pub provisional: bool,
+    /// OCR text from earlier extractions, oldest first
+    pub text_versions: Vec<TextVersion>,
+    /// BAS treatment chosen by the user; `None` classifies automatically
+    pub gst_treatment: Option<GstTreatment>,
+    /// Typed in by the user rather than extracted; `source_path` is the
+    /// attached original, if any
+    pub manual_entry: bool,
+    /// EXIF capture time and location of the source photo
+    pub capture: Option<CaptureMetadata>,
+    /// Deductible share of a partly deductible category
+    pub deduction: Option<DeductionSplit>,
+    /// Incremented on every save; edits must be based on the current one
+    pub revision: u64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Why an edit to a document wasn't saved
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SaveError {
+    /// The document was saved elsewhere after the edited copy was read.
+    /// The edits can be merged onto `current` and saved at its revision.
+    Conflict {
+        attempted: Box<StoredDocument>,
+        current: Box<StoredDocument>,
+    },
+    Failed { message: String },
+}
+
+impl From<String> for SaveError {
+    fn from(message: String) -> Self {
+        SaveError::Failed { message }
+    }
+}
+
+/// OCR text replaced by a later extraction
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TextVersion {
+    pub text: String,
+    /// When the text was replaced
+    pub replaced_at: String,
+}
+
+/// Lightweight view of a document for list and search results.
+///
+/// Omits extracted payloads and raw OCR text; fetch the full record with
+/// `DocumentStore::get` when a document is opened.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DocumentSummary {
+    pub id: String,
+    pub kind: DocumentKind,
+    pub vendor: Option<String>,
+    pub date: Option<String>,
+    pub total: Option<Money>,
+    /// Currency of `total` when it isn't Australian dollars
+    #[serde(default)]
+    pub currency: Option<String>,
+    pub category: Option<String>,
+    pub status: DocumentStatus,
+    pub confidence: f64,
+    pub provisional: bool,
+    pub manual_entry: bool,
+    /// Net deduction when only part of the total is deductible
+    #[serde(default)]
+    pub deductible: Option<Money>,
+}
+
+impl From<&StoredDocument> for DocumentSummary {
+    fn from(doc: &StoredDocument) -> Self {
+        Self {
+            id: doc.id.clone(),
+            kind: doc.kind,
+            vendor: doc.vendor.clone(),
+            date: doc.date.clone(),
+            total: doc.total,
+            currency: doc.currency.clone(),
+            category: doc.category.clone(),
+            status: doc.status,
+            confidence: doc.confidence,
+            provisional: doc.provisional,
+            manual_entry: doc.manual_entry,
+            deductible: doc.deduction.as_ref().map(|split| split.deductible),
+        }
+    }
+}
+
+/// Values typed in for a document that couldn't be parsed
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ManualEntry {
+    pub kind: DocumentKind,
+    pub vendor: String,
+    /// Document date; any format `normalize_date` accepts
+    pub date: String,
+    pub total: Money,
+    pub gst: Option<Money>,
+    pub category: Option<String>,
+    pub entity_id: Option<String>,
+    /// Original file to keep with the entry
+    pub source_path: Option<String>,
+}
+
+/// Freshly extracted data for a document
+#[derive(Debug, Clone)]
+pub enum Extraction {
+    Invoice(Box<ExtractedInvoice>),
+    Receipt(Box<ExtractedReceipt>),
+}
+
+impl StoredDocument {
+    pub fn is_locked(&self, field: &str) -> bool {
+        self.locked_fields.iter().any(|f| f == field)
+    }
+
+    /// Lock or unlock fields, rejecting names that cannot be locked
+    pub fn set_locked(&mut self, fields: &[String], locked: bool) -> Result<(), String> {
+        if let Some(unknown) = fields.iter().find(|f| !LOCKABLE_FIELDS.contains(&f.as_str())) {
+            return Err(format!("Field '{}' cannot be locked", unknown));
+        }
+        for field in fields {
+            if locked && !self.is_locked(field) {
+                self.locked_fields.push(field.clone());
+            } else if !locked {
+                self.locked_fields.retain(|f| f != field);
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the document's values with a manual entry. The fields are
+    /// locked so re-extracting an attached original can't overwrite them.
+    pub fn apply_manual_entry(&mut self, entry: ManualEntry) -> Result<(), String> {
+        let vendor = entry.vendor.trim();
+        if vendor.is_empty() {
+            return Err("Vendor is required".to_string());
+        }
+        let date = normalize_date(&entry.date).ok_or_else(|| format!("Invalid date: {}", entry.date))?;
+        if entry.total <= Money::ZERO {
+            return Err("Total must be greater than zero".to_string());
+        }
+        if entry.gst.is_some_and(|gst| gst < Money::ZERO || gst > entry.total) {
+            return Err("GST must be between zero and the total".to_string());
+        }
+
+        self.kind = entry.kind;
+        self.vendor = Some(vendor.to_string());
+        self.date = Some(date);
+        self.total = Some(entry.total);
+        self.gst = entry.gst;
+        self.currency = None;
+        self.category = entry.category.filter(|c| !c.trim().is_empty());
+        if entry.entity_id.is_some() {
+            self.entity_id = entry.entity_id;
+        }
+        if entry.source_path.is_some() {
+            self.source_path = entry.source_path;
+        }
+        self.manual_entry = true;
+        self.provisional = false;
+        self.confidence = 1.0;
+        self.status = DocumentStatus::Reviewed;
+        self.locked_fields = LOCKABLE_FIELDS.iter().map(|f| f.to_string()).collect();
+        Ok(())
+    }
+
+    /// Whether the amounts are in a currency other than Australian dollars
+    pub fn is_foreign_currency(&self) -> bool {
+        self.currency.as_deref().is_some_and(|code| code != currency::HOME_CURRENCY)
+    }
+
+    /// Total in Australian dollars; `None` for a foreign-currency total
+    pub fn aud_total(&self) -> Option<Money> {
+        self.total.filter(|_| !self.is_foreign_currency())
+    }
+
+    /// GST in Australian dollars; `None` for foreign-currency amounts
+    pub fn aud_gst(&self) -> Option<Money> {
+        self.gst.filter(|_| !self.is_foreign_currency())
+    }
+
+    /// Raw text of the current extraction
+    pub fn ocr_text(&self) -> Option<&str> {
+        match self.kind {
+            DocumentKind::Invoice => self.invoice.as_ref().map(|inv| inv.raw_text.as_str()),
+            DocumentKind::Receipt => self.receipt.as_ref().map(|rec| rec.raw_text.as_str()),
+        }
+    }
+
+    /// Keep the current text as a version when an extraction replaces it
+    fn archive_text(&mut self, new_text: &str) {
+        let Some(text) = self.ocr_text().filter(|t| *t != new_text).map(str::to_string) else {
+            return;
+        };
+        self.text_versions.push(TextVersion {
+            text,
+            replaced_at: storage::now_timestamp(),
+        });
+        let excess = self.text_versions.len().saturating_sub(MAX_TEXT_VERSIONS);
+        self.text_versions.drain(..excess);
+    }
+
+    /// Copy extracted values onto the document, leaving locked fields untouched
+    pub fn apply_extraction(&mut self, extraction: Extraction) {
+        let (vendor, date, total, gst, currency, confidence) = match &extraction {
+            Extraction::Invoice(inv) => (
+                inv.vendor_name.as_ref().map(|f| f.value.clone()),
+                inv.invoice_date.as_ref().and_then(|f| normalize_date(&f.value)),
+                inv.total_amount.as_ref().map(|f| f.value.value),
+                inv.gst_amount.as_ref().map(|f| f.value.value),
+                inv.is_foreign_currency().then(|| inv.currency_code().to_string()),
+                inv.overall_confidence,
+            ),
+            Extraction::Receipt(rec) => (
+                Some(rec.vendor.value.clone()),
+                normalize_date(&rec.date.value),
+                Some(rec.total_amount.value),
+                None,
+                None,
+                rec.overall_confidence,
+            ),
+        };
+
+        if !self.is_locked("vendor") {
+            self.vendor = vendor;
+        }
+        if !self.is_locked("date") {
+            self.date = date;
+        }
+        // A corrected total is in the currency the user entered it in
+        if !self.is_locked("total") {
+            self.total = total;
+            self.currency = currency;
+        }
+        if !self.is_locked("gst") {
+            self.gst = gst;
+        }
+        self.confidence = confidence;
+
+        self.archive_text(match &extraction {
+            Extraction::Invoice(inv) => &inv.raw_text,
+            Extraction::Receipt(rec) => &rec.raw_text,
+        });
+        match extraction {
+            Extraction::Invoice(inv) => {
+                self.kind = DocumentKind::Invoice;
+                self.invoice = Some(*inv);
+            }
+            Extraction::Receipt(rec) => {
+                self.kind = DocumentKind::Receipt;
+                self.receipt = Some(*rec);
+            }
+        }
+    }
+}
+
+/// Re-run extraction against the document's original file. OCR and PDF
+/// parsing are slow, so call this without the store lock held and save the
+/// result afterwards.
+pub fn extract_from_source(doc: &StoredDocument) -> Result<Extraction, String> {
+    let source = doc
+        .source_path
+        .as_deref()
+        .ok_or_else(|| "Document has no original file to re-extract from".to_string())?;
+
+    match doc.kind {
+        DocumentKind::Invoice => {
+            let is_pdf = Path::new(source)
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| e.eq_ignore_ascii_case("pdf"));
+            let invoice = if is_pdf {
+                invoice::parse_invoice_pdf(source)?
+            } else {
+                invoice::parse_invoice_image(source)?
+            };
+            Ok(Extraction::Invoice(Box::new(invoice)))
+        }
+        DocumentKind::Receipt => {
+            let mut engine = OcrEngine::from_settings(&settings::load_settings()?)?;
+            Ok(Extraction::Receipt(Box::new(engine.process_receipt(source)?)))
+        }
+    }
+}
+
+/// Extractions made outside the store lock, by document ID, with the
+/// revision each document was read at
+pub type PendingExtractions = HashMap<String, (u64, Result<Extraction, String>)>;
+
+/// Re-extract copies of documents from their originals. OCR and PDF parsing
+/// are slow, so this runs without the store lock held and the results are
+/// applied afterwards with [`DocumentStore::apply_reextraction`].
+pub fn extract_all(docs: &[StoredDocument]) -> PendingExtractions {
+    docs.iter()
+        .map(|doc| (doc.id.clone(), (doc.revision, extract_from_source(doc))))
+        .collect()
+}
+
+/// Convert common Australian date formats to YYYY-MM-DD, reading numeric
+/// dates day-first
+pub(crate) fn normalize_date(raw: &str) -> Option<String> {
+    dates::normalize(raw, DateOrder::DayFirst).iso
+}
+
+/// Field a page of documents is sorted on
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentSortField {
+    #[default]
+    Date,
+    Vendor,
+    Total,
+    Category,
+    Confidence,
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl DocumentSortField {
+    fn key(self, doc: &StoredDocument) -> SortKey {
+        match self {
+            DocumentSortField::Date => SortKey::optional_text(doc.date.as_deref()),
+            DocumentSortField::Vendor => SortKey::optional_text(doc.vendor.as_deref()),
+            DocumentSortField::Total => SortKey::optional_number(doc.total.map(Money::to_dollars)),
+            DocumentSortField::Category => SortKey::optional_text(doc.category.as_deref()),
+            DocumentSortField::Confidence => SortKey::Number(doc.confidence),
+            DocumentSortField::CreatedAt => SortKey::text(&doc.created_at),
+            DocumentSortField::UpdatedAt => SortKey::text(&doc.updated_at),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DocumentKind {
+    #[default]
+    Receipt,
+    Invoice,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DocumentStatus {
+    #[default]
+    PendingReview,
+    Reviewed,
+    Accepted,
+}
+
+/// Criteria for selecting stored documents; unset fields match everything
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct DocumentFilter {
+    /// Explicit document IDs to select
+    pub ids: Option<Vec<String>>,
+    pub kind: Option<DocumentKind>,
+    pub status: Option<DocumentStatus>,
+    /// Exact category match
+    pub category: Option<String>,
+    /// Only documents without a category
+    pub uncategorized: bool,
+    /// Case-insensitive substring of the vendor name
+    pub vendor_contains: Option<String>,
+    pub tag: Option<String>,
+    pub entity_id: Option<String>,
+    /// Inclusive lower bound (YYYY-MM-DD)
+    pub date_from: Option<String>,
+    /// Inclusive upper bound (YYYY-MM-DD)
+    pub date_to: Option<String>,
+    /// Documents dated in this financial year, plus unfinished items
+    /// carried in from the previous year
+    pub financial_year: Option<i32>,
+}
+
+impl DocumentFilter {
+    pub fn matches(&self, doc: &StoredDocument) -> bool {
+        if let Some(ref ids) = self.ids {
+            if !ids.contains(&doc.id) {
+                return false;
+            }
+        }
+        if self.kind.is_some_and(|kind| kind != doc.kind) {
+            return false;
+        }
+        if self.status.is_some_and(|status| status != doc.status) {
+            return false;
+        }
+        if self.category.is_some() && self.category != doc.category {
+            return false;
+        }
+        if self.uncategorized && doc.category.is_some() {
+            return false;
+        }
+        if let Some(ref needle) = self.vendor_contains {
+            let vendor = doc.vendor.as_deref().unwrap_or("").to_lowercase();
+            if !vendor.contains(&needle.to_lowercase()) {
+                return false;
+            }
+        }
+        if let Some(ref tag) = self.tag {
+            if !doc.tags.contains(tag) {
+                return false;
+            }
+        }
+        if self.entity_id.is_some() && self.entity_id != doc.entity_id {
+            return false;
+        }
+        if self.date_from.is_some() || self.date_to.is_some() {
+            // ISO dates compare correctly as strings
+            let Some(ref date) = doc.date else {
+                return false;
+            };
+            if self.date_from.as_ref().is_some_and(|from| date < from) {
+                return false;
+            }
+            if self.date_to.as_ref().is_some_and(|to| date > to) {
+                return false;
+            }
+        }
+        if let Some(fy) = self.financial_year {
+            let in_year = doc
+                .date
+                .as_deref()
+                .and_then(periods::parse_date)
+                .is_some_and(|d| periods::financial_year_of(d) == fy);
+            if !in_year && !doc.tags.contains(&rollover::carried_tag(fy - 1)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// A change applied to every document selected by a filter
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum BulkOperation {
+    Recategorize { category: Option<String> },
+    Retag {
+        #[serde(default)]
+        add: Vec<String>,
+        #[serde(default)]
+        remove: Vec<String>,
+    },
+    MoveEntity { entity_id: Option<String> },
+    /// Re-parse the original file, respecting locked fields
+    Reextract,
+    Delete,
+}
+
+/// Outcome of a bulk operation for a single document
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BulkItemResult {
+    pub document_id: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// Result of a bulk operation
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BulkOperationResult {
+    pub processed: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub results: Vec<BulkItemResult>,
+}
+
+/// JSON-backed collection of stored documents
+pub struct DocumentStore {
+    path: PathBuf,
+    documents: Vec<StoredDocument>,
+    /// Lodged BAS quarters whose documents can't be edited
+    lodged: Vec<LodgedPeriod>,
+    /// Categorises new and re-extracted documents as they are saved
+    import_defaults: ImportDefaults,
+}
+
+impl DocumentStore {
+    /// Open the store at the given path, starting empty if it does not exist
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            documents: storage::load_json(path)?,
+            lodged: Vec::new(),
+            import_defaults: ImportDefaults::default(),
+        })
+    }
+
+    /// Open the store in the app data directory, enforcing lodged quarters
+    /// and categorising with the current rules and entities
+    pub fn open_default() -> Result<Self, String> {
+        let import_defaults = ImportDefaults::load().unwrap_or_else(|e| {
+            log::warn!("Failed to load category defaults: {}", e);
+            ImportDefaults::default()
+        });
+        Ok(Self::open(&storage::get_data_directory()?.join("documents.json"))?
+            .with_lodged_periods(period_locks::lodged_periods()?)
+            .with_import_defaults(import_defaults))
+    }
+
+    /// Refuse edits to the BAS figures of documents in these quarters
+    pub fn with_lodged_periods(mut self, lodged: Vec<LodgedPeriod>) -> Self {
+        self.lodged = lodged;
+        self
+    }
+
+    /// Categorise and split documents by these rules and entities on save
+    pub fn with_import_defaults(mut self, import_defaults: ImportDefaults) -> Self {
+        self.import_defaults = import_defaults;
+        self
+    }
+
+    /// Persist all documents in a single atomic write
+    pub fn save(&self) -> Result<(), String> {
+        storage::save_json(&self.path, &self.documents)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&StoredDocument> {
+        self.documents.iter().find(|d| d.id == id)
+    }
+
+    /// List documents matching the filter
+    pub fn list(&self, filter: &DocumentFilter) -> Vec<StoredDocument> {
+        self.documents
+            .iter()
+            .filter(|d| filter.matches(d))
+            .cloned()
+            .collect()
+    }
+
+    /// Summaries of documents matching the filter
+    pub fn summaries(&self, filter: &DocumentFilter) -> Vec<DocumentSummary> {
+        self.documents
+            .iter()
+            .filter(|d| filter.matches(d))
+            .map(DocumentSummary::from)
+            .collect()
+    }
+
+    /// One page of summaries of documents matching the filter
+    pub fn summary_page(
+        &self,
+        filter: &DocumentFilter,
+        request: &PageRequest<DocumentSortField>,
+    ) -> Result<Page<DocumentSummary>, String> {
+        let matching: Vec<&StoredDocument> = self.documents.iter().filter(|d| filter.matches(d)).collect();
+        let page = pagination::paginate(matching, request, |doc, field| field.key(doc), |doc| &doc.id)?;
+        Ok(page.map(DocumentSummary::from))
+    }
+
+    /// Tag unfinished documents from a closing financial year as carried forward
+    pub fn roll_forward(&mut self, closing_year: i32) -> RolloverResult {
+        rollover::roll_forward(&mut self.documents, closing_year)
+    }
+
+    /// Insert a new document or replace the one with the same ID
+    pub fn upsert(&mut self, mut document: StoredDocument) -> StoredDocument {
+        let now = storage::now_timestamp();
+        if document.id.is_empty() {
+            document.id = storage::generate_id("doc");
+        }
+        if document.created_at.is_empty() {
+            document.created_at = now.clone();
+        }
+        if !document.paid {
+            document.paid_at = None;
+        } else if document.paid_at.is_none() {
+            document.paid_at = Some(now.clone());
+        }
+        document.updated_at = now;
+        let previous = self.get(&document.id).cloned();
+        self.import_defaults.apply(&mut document, previous.as_ref());
+
+        match self.documents.iter_mut().find(|d| d.id == document.id) {
+            Some(existing) => {
+                document.revision = existing.revision + 1;
+                *existing = document.clone();
+            }
+            None => {
+                document.revision = 1;
+                self.documents.push(document.clone());
+            }
+        }
+        document
+    }
+
+    /// Save an edited or new document, refusing BAS changes to documents
+    /// in a lodged quarter
+    pub fn update(&mut self, document: StoredDocument) -> Result<StoredDocument, String> {
+        if let Some(existing) = self.get(&document.id) {
+            period_locks::check_edit(&self.lodged, existing, Some(&document))?;
+        }
+        Ok(self.upsert(document))
+    }
+
+    /// Save an edit made to a copy read at `document.revision`, refusing it
+    /// when the document has been saved since
+    pub fn update_checked(&mut self, document: StoredDocument) -> Result<StoredDocument, SaveError> {
+        if let Some(current) = self.get(&document.id).filter(|current| current.revision != document.revision) {
+            return Err(SaveError::Conflict {
+                attempted: Box::new(document),
+                current: Box::new(current.clone()),
+            });
+        }
+        Ok(self.update(document)?)
+    }
+
+    /// Apply an operation to every matching document.
+    ///
+    /// All changes are written together; if the write fails nothing is
+    /// committed and every document is reported as failed. Re-extraction
+    /// goes through [`DocumentStore::apply_reextraction`] instead.
+    pub fn apply_bulk(
+        &mut self,
+        filter: &DocumentFilter,
+        operation: &BulkOperation,
+    ) -> BulkOperationResult {
+        self.apply_bulk_with(filter, operation, PendingExtractions::new())
+    }
+
+    /// Apply extractions made by [`extract_all`] to every matching document.
+    /// A document saved again since it was read, or not read at all, fails.
+    pub fn apply_reextraction(
+        &mut self,
+        filter: &DocumentFilter,
+        extracted: PendingExtractions,
+    ) -> BulkOperationResult {
+        self.apply_bulk_with(filter, &BulkOperation::Reextract, extracted)
+    }
+
+    fn apply_bulk_with(
+        &mut self,
+        filter: &DocumentFilter,
+        operation: &BulkOperation,
+        mut extracted: PendingExtractions,
+    ) -> BulkOperationResult {
+        let mut working = self.documents.clone();
+        let mut results = Vec::new();
+        let now = storage::now_timestamp();
+
+        // Requested IDs that do not exist are reported rather than ignored
+        if let Some(ref ids) = filter.ids {
+            for id in ids {
+                if !working.iter().any(|d| &d.id == id) {
+                    results.push(BulkItemResult {
+                        document_id: id.clone(),
+                        success: false,
+                        error: Some("Document not found".to_string()),
+                    });
+                }
+            }
+        }
+
+        let mut deleted = Vec::new();
+        for doc in working.iter_mut().filter(|d| filter.matches(d)) {
+            let before = doc.clone();
+            let outcome = apply_operation(doc, operation, &mut extracted).and_then(|()| {
+                let after = (!matches!(operation, BulkOperation::Delete)).then_some(&*doc);
+                period_locks::check_edit(&self.lodged, &before, after)
+            });
+            if outcome.is_err() {
+                *doc = before.clone();
+            }
+            match outcome {
+                Ok(()) => {
+                    if matches!(operation, BulkOperation::Delete) {
+                        deleted.push(doc.id.clone());
+                    } else {
+                        doc.updated_at = now.clone();
+                        doc.revision += 1;
+                        self.import_defaults.apply(doc, Some(&before));
+                    }
+                    results.push(BulkItemResult {
+                        document_id: doc.id.clone(),
+                        success: true,
+                        error: None,
+                    });
+                }
+                Err(e) => results.push(BulkItemResult {
+                    document_id: doc.id.clone(),
+                    success: false,
+                    error: Some(e),
+                }),
+            }
+        }
+        working.retain(|d| !deleted.contains(&d.id));
+
+        let previous = std::mem::replace(&mut self.documents, working);
+        if let Err(e) = self.save() {
+            self.documents = previous;
+            for result in results.iter_mut().filter(|r| r.success) {
+                result.success = false;
+                result.error = Some(e.clone());
+            }
+        }
+
+        let succeeded = results.iter().filter(|r| r.success).count();
+        BulkOperationResult {
+            processed: results.len(),
+            succeeded,
+            failed: results.len() - succeeded,
+            results,
+        }
+    }
+}
+
+fn apply_operation(
+    doc: &mut StoredDocument,
+    operation: &BulkOperation,
+    extracted: &mut PendingExtractions,
+) -> Result<(), String> {
+    match operation {
+        BulkOperation::Recategorize { category } => {
+            if category.as_ref().is_some_and(|c| c.trim().is_empty()) {
+                return Err("Category cannot be blank".to_string());
+            }
+            doc.category = category.clone();
+        }
+        BulkOperation::Retag { add, remove } => {
+            doc.tags.retain(|t| !remove.contains(t));
+            for tag in add {
+                let tag = tag.trim();
+                if !tag.is_empty() && !doc.tags.iter().any(|t| t == tag) {
+                    doc.tags.push(tag.to_string());
+                }
+            }
+        }
+        BulkOperation::MoveEntity { entity_id } => {
+            doc.entity_id = entity_id.clone();
+        }
+        BulkOperation::Reextract => {
+            let (revision, extraction) = extracted
+                .remove(&doc.id)
+                .ok_or_else(|| "Document was not re-extracted".to_string())?;
+            if revision != doc.revision {
+                return Err("Document changed while it was being re-extracted".to_string());
+            }
+            doc.apply_extraction(extraction?);
+        }
+        BulkOperation::Delete => {}
+    }
+    Ok(())
+}
+
+/// Run a closure against the default store while holding the store lock
+pub fn with_store<R>(f: impl FnOnce(&mut DocumentStore) -> Result<R, String>) -> Result<R, String> {
+    let _guard = STORE_LOCK.lock().map_err(|_| "Document store lock poisoned".to_string())?;
+    let mut store = DocumentStore::open_default()?;
+    f(&mut store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_store(dir: &Path) -> DocumentStore {
+        let mut store = DocumentStore::open(&dir.join("documents.json")).unwrap();
+
+        for (id, vendor, category) in [
+            ("a", "Bunnings Warehouse", Some("Tools")),
+            ("b", "BP Connect", None),
+            ("c", "Officeworks", Some("Stationery")),
+        ] {
+            store.upsert(StoredDocument {
+                id: id.to_string(),
+                vendor: Some(vendor.to_string()),
+                date: Some("2024-03-15".to_string()),
+                category: category.map(String::from),
+                ..Default::default()
+            });
+        }
+        store
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let dir = storage::test_dir();
+        let store = test_store(&dir);
+
+        let filter = DocumentFilter {
+            vendor_contains: Some("bunnings".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(store.list(&filter).len(), 1);
+        assert_eq!(store.summaries(&filter)[0].vendor.as_deref(), store.list(&filter)[0].vendor.as_deref());
+
+        let filter = DocumentFilter {
+            uncategorized: true,
+            ..Default::default()
+        };
+        assert_eq!(store.list(&filter)[0].id, "b");
+
+        let filter = DocumentFilter {
+            date_from: Some("2024-04-01".to_string()),
+            ..Default::default()
+        };
+        assert!(store.list(&filter).is_empty());
+    }
+
+    #[test]
+    fn test_bulk_recategorize_reports_missing_ids() {
+        let dir = storage::test_dir();
+        let mut store = test_store(&dir);
+
+        let filter = DocumentFilter {
+            ids: Some(vec!["a".to_string(), "b".to_string(), "missing".to_string()]),
+            ..Default::default()
+        };
+        let result = store.apply_bulk(
+            &filter,
+            &BulkOperation::Recategorize { category: Some("Repairs".to_string()) },
+        );
+
+        assert_eq!(result.succeeded, 2);
+        assert_eq!(result.failed, 1);
+        assert_eq!(store.get("b").unwrap().category.as_deref(), Some("Repairs"));
+        assert_eq!(store.get("c").unwrap().category.as_deref(), Some("Stationery"));
+
+        // Changes were persisted in a single write
+        let reopened = DocumentStore::open(&store.path).unwrap();
+        assert_eq!(reopened.get("a").unwrap().category.as_deref(), Some("Repairs"));
+    }
+
+    #[test]
+    fn test_apply_extraction_respects_locked_fields() {
+        let mut doc = StoredDocument {
+            vendor: Some("Bunnings (corrected)".to_string()),
+            total: Some(Money::from_cents(9900)),
+            ..Default::default()
+        };
+        doc.set_locked(&["vendor".to_string()], true).unwrap();
+        assert!(doc.set_locked(&["raw_text".to_string()], true).is_err());
+
+        let parser = crate::invoice::InvoiceParser::new().unwrap();
+        let invoice = parser
+            .parse_from_text(
+                "Bunnings Group Ltd\nInvoice #INV-1\nDate: 15/01/2024\nTotal: $110.00",
+                crate::invoice::DocumentType::Pdf,
+            )
+            .unwrap();
+        doc.apply_extraction(Extraction::Invoice(Box::new(invoice)));
+
+        assert_eq!(doc.vendor.as_deref(), Some("Bunnings (corrected)"));
+        assert_eq!(doc.total, Some(Money::from_cents(11000)));
+        assert_eq!(doc.date.as_deref(), Some("2024-01-15"));
+        assert_eq!(doc.kind, DocumentKind::Invoice);
+    }
+
+    #[test]
+    fn test_manual_entry_replaces_an_unparseable_document() {
+        let mut doc = StoredDocument {
+            source_path: Some("/scans/faded.jpg".to_string()),
+            provisional: true,
+            confidence: 0.1,
+            ..Default::default()
+        };
+        let entry = ManualEntry {
+            vendor: " Corner Hardware ".to_string(),
+            date: "03/02/2024".to_string(),
+            total: Money::from_cents(5500),
+            gst: Some(Money::from_cents(500)),
+            category: Some("Tools".to_string()),
+            ..Default::default()
+        };
+        assert!(doc.apply_manual_entry(ManualEntry { gst: Some(Money::from_cents(6000)), ..entry.clone() }).is_err());
+        assert!(doc.apply_manual_entry(ManualEntry { date: "soon".to_string(), ..entry.clone() }).is_err());
+        assert!(doc.vendor.is_none());
+
+        doc.apply_manual_entry(entry).unwrap();
+        assert_eq!(doc.vendor.as_deref(), Some("Corner Hardware"));
+        let (total, gst) = (Some(Money::from_cents(5500)), Some(Money::from_cents(500)));
+        assert_eq!((doc.date.as_deref(), doc.total, doc.gst), (Some("2024-02-03"), total, gst));
+        assert_eq!(doc.source_path.as_deref(), Some("/scans/faded.jpg"));
+        assert!(doc.manual_entry && !doc.provisional);
+        assert!(LOCKABLE_FIELDS.iter().all(|f| doc.is_locked(f)));
+        assert!(DocumentSummary::from(&doc).manual_entry);
+    }
+
+    #[test]
+    fn test_stale_edits_conflict_with_newer_saves() {
+        let dir = storage::test_dir();
+        let mut store = test_store(&dir);
+        let read = store.get("b").unwrap().clone();
+        assert_eq!(read.revision, 1);
+
+        // A background matcher saves first
+        let matched = StoredDocument {
+            category: Some("Fuel".to_string()),
+            ..read.clone()
+        };
+        assert_eq!(store.update_checked(matched).unwrap().revision, 2);
+
+        let edited = StoredDocument {
+            tags: vec!["travel".to_string()],
+            ..read
+        };
+        let Err(SaveError::Conflict { attempted, current }) = store.update_checked(edited) else {
+            panic!("expected a conflict");
+        };
+        assert_eq!((attempted.tags.len(), current.category.as_deref()), (1, Some("Fuel")));
+
+        let merged = StoredDocument {
+            tags: attempted.tags,
+            ..*current
+        };
+        let saved = store.update_checked(merged).unwrap();
+        assert_eq!((saved.revision, saved.category.as_deref()), (3, Some("Fuel")));
+        let json = serde_json::to_value(SaveError::from("Disk full".to_string())).unwrap();
+        assert_eq!(json, serde_json::json!({ "kind": "failed", "message": "Disk full" }));
+    }
+
+    #[test]
+    fn test_bulk_reextract_without_source_fails_per_document() {
+        let dir = storage::test_dir();
+        let mut store = test_store(&dir);
+
+        let extracted = extract_all(&store.list(&DocumentFilter::default()));
+        let result = store.apply_reextraction(&DocumentFilter::default(), extracted);
+        assert_eq!(result.failed, 3);
+        assert_eq!(store.get("a").unwrap().vendor.as_deref(), Some("Bunnings Warehouse"));
+    }
+
+    #[test]
+    fn test_bulk_delete() {
+        let dir = storage::test_dir();
+        let mut store = test_store(&dir);
+
+        let filter = DocumentFilter {
+            category: Some("Tools".to_string()),
+            ..Default::default()
+        };
+        let result = store.apply_bulk(&filter, &BulkOperation::Delete);
+
+        assert_eq!(result.succeeded, 1);
+        assert!(store.get("a").is_none());
+        assert_eq!(store.list(&DocumentFilter::default()).len(), 2);
+    }
+}
@@ -0,0 +1,132 @@
+//! Review Drafts Module
+//!
+//! Keeps partial field edits for documents that are part-way through review,
+//! so corrections survive the app closing and can be resumed on next open.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::storage;
+
+static DRAFTS_LOCK: Mutex<()> = Mutex::new(());
+
+/// Unsaved field edits for a single document
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DocumentDraft {
+    pub document_id: String,
+    /// Edited values keyed by field name
+    pub fields: BTreeMap<String, serde_json::Value>,
+    pub updated_at: String,
+}
+
+/// JSON-backed collection of drafts keyed by document ID
+pub struct DraftStore {
+    path: PathBuf,
+    drafts: BTreeMap<String, DocumentDraft>,
+}
+
+impl DraftStore {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            drafts: storage::load_json(path)?,
+        })
+    }
+
+    pub fn open_default() -> Result<Self, String> {
+        Self::open(&storage::get_data_directory()?.join("drafts.json"))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        storage::save_json(&self.path, &self.drafts)
+    }
+
+    pub fn get(&self, document_id: &str) -> Option<&DocumentDraft> {
+        self.drafts.get(document_id)
+    }
+
+    /// Drafts ordered by most recently edited
+    pub fn list(&self) -> Vec<DocumentDraft> {
+        let mut drafts: Vec<DocumentDraft> = self.drafts.values().cloned().collect();
+        drafts.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        drafts
+    }
+
+    /// Merge field edits into the document's draft.
+    ///
+    /// A `null` value removes that field from the draft.
+    pub fn update(
+        &mut self,
+        document_id: &str,
+        fields: BTreeMap<String, serde_json::Value>,
+    ) -> DocumentDraft {
+        let draft = self
+            .drafts
+            .entry(document_id.to_string())
+            .or_insert_with(|| DocumentDraft {
+                document_id: document_id.to_string(),
+                ..Default::default()
+            });
+
+        for (field, value) in fields {
+            if value.is_null() {
+                draft.fields.remove(&field);
+            } else {
+                draft.fields.insert(field, value);
+            }
+        }
+        draft.updated_at = storage::now_timestamp();
+
+        let draft = draft.clone();
+        if draft.fields.is_empty() {
+            self.drafts.remove(document_id);
+        }
+        draft
+    }
+
+    pub fn discard(&mut self, document_id: &str) -> bool {
+        self.drafts.remove(document_id).is_some()
+    }
+}
+
+/// Run a closure against the default draft store while holding its lock
+pub fn with_drafts<R>(f: impl FnOnce(&mut DraftStore) -> Result<R, String>) -> Result<R, String> {
+    let _guard = DRAFTS_LOCK.lock().map_err(|_| "Draft store lock poisoned".to_string())?;
+    let mut store = DraftStore::open_default()?;
+    f(&mut store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_draft_edits_merge_and_persist() {
+        let dir = storage::test_dir();
+        let path = dir.join("drafts.json");
+        let mut store = DraftStore::open(&path).unwrap();
+
+        store.update("doc-1", BTreeMap::from([("vendor".to_string(), json!("Bunnings"))]));
+        store.update("doc-1", BTreeMap::from([("total".to_string(), json!(42.5))]));
+        store.save().unwrap();
+
+        let reopened = DraftStore::open(&path).unwrap();
+        let draft = reopened.get("doc-1").unwrap();
+        assert_eq!(draft.fields.len(), 2);
+        assert_eq!(draft.fields["vendor"], json!("Bunnings"));
+    }
+
+    #[test]
+    fn test_clearing_all_fields_removes_draft() {
+        let dir = storage::test_dir();
+        let path = dir.join("drafts.json");
+        let mut store = DraftStore::open(&path).unwrap();
+
+        store.update("doc-1", BTreeMap::from([("vendor".to_string(), json!("BP"))]));
+        store.update("doc-1", BTreeMap::from([("vendor".to_string(), serde_json::Value::Null)]));
+        assert!(store.get("doc-1").is_none());
+    }
+}
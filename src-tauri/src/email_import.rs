@@ -0,0 +1,468 @@
+//! Email Import Module
+//!
+//! Brings in invoices that arrive by email, from a message saved as an
+//! `.eml` file. PDF attachments are filed and parsed as if they had been
+//! uploaded. Some suppliers write the invoice in the HTML body instead of
+//! attaching it; then the body is stripped to text for the invoice parser
+//! and a PDF render of the email is archived as the original document.
+
+use mail_parser::{MessageParser, MimeHeaders, PartType};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::documents::{DocumentKind, Extraction, StoredDocument};
+use crate::invoice::{self, ExtractedInvoice};
+use crate::report_builder::ReportBuilder;
+use crate::storage;
+
+/// Longest body line drawn on one line of the PDF render
+const WRAP_WIDTH: usize = 95;
+
+/// A file attached to an email
+#[derive(Debug)]
+struct Attachment {
+    /// Lowercase media type without parameters, such as `application/pdf`
+    mime: String,
+    filename: Option<String>,
+    body: Vec<u8>,
+}
+
+impl Attachment {
+    fn is_pdf(&self) -> bool {
+        self.mime == "application/pdf"
+            || self.filename.as_deref().is_some_and(|name| name.to_ascii_lowercase().ends_with(".pdf"))
+    }
+}
+
+/// A parsed email message
+#[derive(Debug)]
+pub struct EmailMessage {
+    pub subject: Option<String>,
+    pub from: Option<String>,
+    pub date: Option<String>,
+    /// Inline HTML body, if the message has one
+    html: Option<String>,
+    /// Inline plain text body
+    plain: Option<String>,
+    attachments: Vec<Attachment>,
+}
+
+impl EmailMessage {
+    /// The body as plain text, from its HTML version when there is one
+    pub fn body_text(&self) -> Option<String> {
+        match (&self.html, &self.plain) {
+            (Some(html), _) => Some(html_to_text(html)),
+            (None, Some(plain)) => Some(plain.clone()),
+            (None, None) => None,
+        }
+        .filter(|text| !text.trim().is_empty())
+    }
+}
+
+/// Directory emailed invoices are kept in
+pub fn emails_directory() -> Result<PathBuf, String> {
+    let dir = storage::get_data_directory()?.join("emails");
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create emails directory: {}", e))?;
+    Ok(dir)
+}
+
+/// Parse a raw RFC 822 message. MIME decoding is left to `mail-parser`,
+/// which walks nested parts without recursing, so a hostile message can't
+/// exhaust the stack.
+pub fn parse_message(raw: &[u8]) -> Result<EmailMessage, String> {
+    let message = MessageParser::default()
+        .parse(raw)
+        .ok_or_else(|| "The file is not an email message".to_string())?;
+    let from = message.from().and_then(|address| address.first()).map(|sender| {
+        match (sender.name.as_deref(), sender.address.as_deref()) {
+            (Some(name), Some(address)) => format!("{} <{}>", name, address),
+            (name, address) => name.or(address).unwrap_or_default().to_string(),
+        }
+    });
+
+    // When there is no HTML alternative the first HTML body part is the plain one
+    let (mut html, mut plain) = (None, None);
+    match message.html_part(0).map(|part| &part.body) {
+        Some(PartType::Html(body)) => html = Some(body.to_string()),
+        Some(PartType::Text(body)) => plain = Some(body.to_string()),
+        _ => {}
+    }
+    if plain.is_none() {
+        plain = message.text_part(0).and_then(|part| match &part.body {
+            PartType::Text(body) => Some(body.to_string()),
+            _ => None,
+        });
+    }
+
+    let attachments = message
+        .attachments()
+        .filter(|part| matches!(part.body, PartType::Binary(_) | PartType::InlineBinary(_)))
+        .map(|part| Attachment {
+            mime: part
+                .content_type()
+                .map(|ctype| match ctype.subtype() {
+                    Some(subtype) => format!("{}/{}", ctype.ctype(), subtype),
+                    None => ctype.ctype().to_string(),
+                })
+                .unwrap_or_default()
+                .to_ascii_lowercase(),
+            filename: part.attachment_name().map(str::to_string),
+            body: part.contents().to_vec(),
+        })
+        .collect();
+
+    Ok(EmailMessage {
+        subject: message.subject().map(str::to_string),
+        from,
+        date: message.date().map(|date| date.to_rfc822()),
+        html,
+        plain,
+        attachments,
+    })
+}
+
+fn hidden_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"(?is)<!--.*?-->|<head\b.*?</head\s*>|<style\b.*?</style\s*>|<script\b.*?</script\s*>").unwrap()
+    })
+}
+
+fn tag_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"<(/?)([a-zA-Z][a-zA-Z0-9]*)?[^>]*>").unwrap())
+}
+
+fn entity_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);").unwrap())
+}
+
+fn decode_entities(text: &str) -> String {
+    entity_pattern()
+        .replace_all(text, |caps: &regex::Captures| {
+            let name = &caps[1];
+            let decoded = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                "dollar" => Some('$'),
+                "euro" => Some('€'),
+                "pound" => Some('£'),
+                _ => match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                    None => name.strip_prefix('#').and_then(|n| n.parse().ok()).and_then(char::from_u32),
+                },
+            };
+            decoded.map_or_else(|| caps[0].to_string(), |c| c.to_string())
+        })
+        .into_owned()
+}
+
+/// Gap left between table columns, wider than the parser's cell gap
+const COLUMN_GAP: &str = "   ";
+
+/// A table's rows of cell text, while its HTML is being read
+type Table = Vec<Vec<String>>;
+
+/// Lay a table out as text. Rows whose cells each fit on one line are
+/// padded into aligned columns; a row holding a paragraph or a nested
+/// table, as layout tables do, has its cells one after another.
+fn render_table(rows: &Table) -> String {
+    let rows: Vec<Vec<&str>> = rows
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.trim()).collect())
+        .filter(|row: &Vec<&str>| row.iter().any(|cell| !cell.is_empty()))
+        .collect();
+    let flat = |row: &Vec<&str>| row.iter().all(|cell| !cell.contains('\n'));
+    let mut widths: Vec<usize> = Vec::new();
+    for row in rows.iter().filter(|row| flat(row)) {
+        for (i, cell) in row.iter().enumerate() {
+            match widths.get_mut(i) {
+                Some(width) => *width = (*width).max(cell.chars().count()),
+                None => widths.push(cell.chars().count()),
+            }
+        }
+    }
+
+    let mut lines = Vec::new();
+    for row in &rows {
+        if flat(row) {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            lines.push(cells.join(COLUMN_GAP).trim_end().to_string());
+        } else {
+            lines.extend(row.iter().filter(|cell| !cell.is_empty()).map(|cell| cell.to_string()));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Text read so far, with the tables still open around the current point
+#[derive(Default)]
+struct HtmlText {
+    out: String,
+    tables: Vec<Table>,
+}
+
+impl HtmlText {
+    /// Where text goes: the open table cell, or the page
+    fn sink(&mut self) -> &mut String {
+        match self.tables.last_mut() {
+            Some(table) => {
+                if table.is_empty() {
+                    table.push(Vec::new());
+                }
+                let row = table.last_mut().unwrap();
+                if row.is_empty() {
+                    row.push(String::new());
+                }
+                row.last_mut().unwrap()
+            }
+            None => &mut self.out,
+        }
+    }
+
+    fn push_text(&mut self, raw: &str) {
+        let collapsed = raw.split_whitespace().collect::<Vec<_>>().join(" ");
+        if collapsed.is_empty() {
+            return;
+        }
+        let sink = self.sink();
+        if raw.starts_with(char::is_whitespace) && !sink.is_empty() && !sink.ends_with([' ', '\n']) {
+            sink.push(' ');
+        }
+        sink.push_str(&decode_entities(&collapsed).replace('\u{a0}', " "));
+        if raw.ends_with(char::is_whitespace) {
+            sink.push(' ');
+        }
+    }
+
+    fn tag(&mut self, name: &str, closing: bool) {
+        match name {
+            "table" if !closing => self.tables.push(Table::new()),
+            "table" => {
+                if let Some(table) = self.tables.pop() {
+                    let text = format!("\n{}\n", render_table(&table));
+                    self.sink().push_str(&text);
+                }
+            }
+            "tr" if !closing => {
+                if let Some(table) = self.tables.last_mut() {
+                    table.push(Vec::new());
+                }
+            }
+            "td" | "th" if !closing => {
+                if let Some(table) = self.tables.last_mut() {
+                    match table.last_mut() {
+                        Some(row) => row.push(String::new()),
+                        None => table.push(vec![String::new()]),
+                    }
+                }
+            }
+            "br" | "p" | "div" | "li" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => self.sink().push('\n'),
+            _ => {}
+        }
+    }
+}
+
+/// Strip an HTML body to text. Block elements start a new line and tables
+/// are laid out in aligned columns, so an item table reads the way the
+/// invoice parser expects from a PDF.
+pub fn html_to_text(html: &str) -> String {
+    let html = hidden_pattern().replace_all(html, " ");
+    let mut text = HtmlText::default();
+    let mut last = 0;
+    for caps in tag_pattern().captures_iter(&html) {
+        let tag = caps.get(0).unwrap();
+        text.push_text(&html[last..tag.start()]);
+        last = tag.end();
+        if let Some(name) = caps.get(2) {
+            text.tag(&name.as_str().to_ascii_lowercase(), !caps[1].is_empty());
+        }
+    }
+    text.push_text(&html[last..]);
+    // Tables left open at the end of the body
+    while !text.tables.is_empty() {
+        text.tag("table", true);
+    }
+
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.out.lines().map(str::trim_end) {
+        // Keep at most one blank line between paragraphs
+        if !line.trim().is_empty() || lines.last().is_some_and(|last| !last.is_empty()) {
+            lines.push(if line.trim().is_empty() { "" } else { line.trim_start() });
+        }
+    }
+    lines.join("\n").trim().to_string()
+}
+
+/// Break a long line at spaces so it fits the page
+fn wrap(line: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    for word in line.split(' ') {
+        let current = lines.last_mut().unwrap();
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+            lines.push(word.to_string());
+        } else {
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+    }
+    lines
+}
+
+/// A PDF of the email: its subject, sender and date, then the body text
+pub fn render_pdf(message: &EmailMessage, body: &str) -> Vec<u8> {
+    let subject = message.subject.as_deref().unwrap_or("(no subject)");
+    let mut report = ReportBuilder::new(subject);
+    report.heading(subject);
+    for (label, value) in [("From", &message.from), ("Date", &message.date)] {
+        if let Some(value) = value {
+            report.text(&format!("{}: {}", label, value));
+        }
+    }
+    report.space(12.0);
+    for line in body.lines() {
+        if line.trim().is_empty() {
+            report.space(8.0);
+            continue;
+        }
+        for piece in wrap(line, WRAP_WIDTH) {
+            report.text(&piece);
+        }
+    }
+    report.build()
+}
+
+fn invoice_document(path: &Path, invoice: ExtractedInvoice, entity_id: Option<String>) -> StoredDocument {
+    let mut document = StoredDocument {
+        kind: DocumentKind::Invoice,
+        source_path: Some(path.to_string_lossy().to_string()),
+        entity_id,
+        ..Default::default()
+    };
+    document.apply_extraction(Extraction::Invoice(Box::new(invoice)));
+    document
+}
+
+/// Invoice documents from an email, not yet stored: one per PDF attachment
+/// that reads as an invoice, or else one for an invoice in the body. Each
+/// original is written to `dir`.
+pub fn documents_from_email(raw: &[u8], dir: &Path, entity_id: Option<String>) -> Result<Vec<StoredDocument>, String> {
+    let message = parse_message(raw)?;
+    let write = |bytes: &[u8]| -> Result<PathBuf, String> {
+        let path = dir.join(format!("{}.pdf", storage::generate_id("email")));
+        fs::write(&path, bytes).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(path)
+    };
+
+    let mut documents = Vec::new();
+    for part in message.attachments.iter().filter(|part| part.is_pdf()) {
+        let path = write(&part.body)?;
+        match invoice::parse_invoice_pdf(&path.to_string_lossy()) {
+            Ok(invoice) if invoice.total_amount.is_some() => {
+                documents.push(invoice_document(&path, invoice, entity_id.clone()));
+                continue;
+            }
+            Ok(_) => log::info!("Attachment {:?} is not an invoice", part.filename),
+            Err(e) => log::warn!("Failed to read attachment {:?}: {}", part.filename, e),
+        }
+        let _ = fs::remove_file(&path);
+    }
+    if !documents.is_empty() {
+        return Ok(documents);
+    }
+
+    let body = message
+        .body_text()
+        .ok_or_else(|| "The email has no body or PDF attachment".to_string())?;
+    let invoice = invoice::parse_invoice_text(&body)
+        .ok()
+        .filter(|invoice| invoice.total_amount.is_some())
+        .ok_or_else(|| "No invoice found in the email".to_string())?;
+    let path = write(&render_pdf(&message, &body))?;
+    documents.push(invoice_document(&path, invoice, entity_id));
+    Ok(documents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents;
+    use crate::money::Money;
+
+    #[test]
+    fn test_inline_html_invoice_is_parsed_and_archived() {
+        let raw = "From: Acme Plumbing <accounts@acmeplumbing.com.au>\r\n\
+            Subject: =?UTF-8?Q?Invoice_INV-1001_=E2=80=93_Acme?=\r\n\
+            Date: Tue, 12 Mar 2024 09:30:00 +1100\r\n\
+            MIME-Version: 1.0\r\n\
+            Content-Type: multipart/alternative; boundary=\"b1\"\r\n\
+            \r\n\
+            --b1\r\n\
+            Content-Type: text/plain; charset=utf-8\r\n\
+            \r\n\
+            Please view this email in HTML.\r\n\
+            --b1\r\n\
+            Content-Type: text/html; charset=utf-8\r\n\
+            Content-Transfer-Encoding: quoted-printable\r\n\
+            \r\n\
+            <html><head><style>td { padding: 4px; }</style></head><body>\r\n\
+            <p>Acme Plumbing Pty Ltd<br>ABN: 51 824 753 556</p><h2>TAX INVOICE</h2>\r\n\
+            <p>Invoice Number: INV-1001<br>Invoice Date: 12/03/2024</p>\r\n\
+            <table><tr><th>Description</th><th>Qty</th><th>Unit Price</th><th>Amount</th></tr>\r\n\
+            <tr><td>Callout fee</td><td>1</td><td>100.00</td><td>100.00</td></tr></table>\r\n\
+            <p>Subtotal 100.00<br>GST 10.00<br>Total&nbsp;110.00</p><p>Thanks &amp; =\r\n\
+            regards</p></body></html>\r\n\
+            --b1--\r\n";
+        let message = parse_message(raw.as_bytes()).unwrap();
+        assert_eq!(message.subject.as_deref(), Some("Invoice INV-1001 – Acme"));
+        let body = message.body_text().unwrap();
+        assert!(body.contains("Description   Qty   Unit Price   Amount\nCallout fee   1     100.00       100.00\n"));
+        assert!(body.ends_with("Total 110.00\n\nThanks & regards"));
+
+        let dir = storage::test_dir();
+        let docs = documents_from_email(raw.as_bytes(), &dir, None).unwrap();
+        assert_eq!(docs.len(), 1);
+        assert_eq!((docs[0].kind, docs[0].total), (DocumentKind::Invoice, Some(Money::from_cents(11000))));
+        let invoice = docs[0].invoice.as_ref().unwrap();
+        assert_eq!(invoice.line_items[0].description, "Callout fee");
+
+        // The archived render reads back as the same invoice
+        let source = docs[0].source_path.as_deref().unwrap();
+        assert!(fs::read(source).unwrap().starts_with(b"%PDF"));
+        let Extraction::Invoice(reread) = documents::extract_from_source(&docs[0]).unwrap() else {
+            panic!("expected an invoice");
+        };
+        assert_eq!(reread.total_amount.unwrap().value, invoice.total_amount.clone().unwrap().value);
+
+        assert!(documents_from_email(b"Subject: Hi\r\n\r\nSee you Friday", &dir, None).is_err());
+    }
+
+    #[test]
+    fn test_deeply_nested_parts_do_not_exhaust_the_stack() {
+        let depth = 20_000;
+        let mut raw = String::from("Subject: Nested\r\n");
+        for level in 0..depth {
+            raw.push_str(&format!("Content-Type: multipart/mixed; boundary=\"b{}\"\r\n\r\n--b{}\r\n", level, level));
+        }
+        raw.push_str("Content-Type: text/plain\r\n\r\nTotal 110.00\r\n");
+        for level in (0..depth).rev() {
+            raw.push_str(&format!("--b{}--\r\n", level));
+        }
+        let message = parse_message(raw.as_bytes()).unwrap();
+        assert_eq!(message.subject.as_deref(), Some("Nested"));
+        assert_eq!(message.body_text().as_deref().map(str::trim), Some("Total 110.00"));
+    }
+}
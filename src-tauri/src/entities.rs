@@ -0,0 +1,250 @@
+//! Entities Module
+//!
+//! The people and businesses documents are claimed under (e.g. a sole trader
+//! business and a household). Invoices are checked against the active entity
+//! so a bill addressed to someone else is flagged before it is claimed.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::address::Address;
+use crate::invoice::ExtractedInvoice;
+use crate::storage;
+
+static ENTITIES_LOCK: Mutex<()> = Mutex::new(());
+
+/// Words ignored when comparing business names
+const NAME_NOISE: [&str; 8] = ["pty", "ltd", "limited", "the", "trust", "as", "trustee", "for"];
+
+/// A person or business that documents belong to
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Entity {
+    pub id: String,
+    pub name: String,
+    pub abn: Option<String>,
+    /// Other names invoices may be addressed to (trading names, personal names)
+    pub aliases: Vec<String>,
+    /// Business or home address, used for state-based reporting
+    pub address: Option<Address>,
+    /// IDs of installed category packs
+    pub category_packs: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RecipientStatus {
+    /// Bill To matches the entity
+    Match,
+    /// Bill To names or ABN belong to someone else
+    Mismatch,
+    /// No Bill To details were found
+    Unknown,
+}
+
+/// Outcome of checking an invoice's recipient against an entity
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecipientCheck {
+    pub status: RecipientStatus,
+    pub entity_id: String,
+    pub entity_name: String,
+    pub bill_to_name: Option<String>,
+    pub bill_to_abn: Option<String>,
+    pub warning: Option<String>,
+}
+
+/// JSON-backed list of entities
+pub struct EntityStore {
+    path: PathBuf,
+    entities: Vec<Entity>,
+}
+
+impl EntityStore {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            entities: storage::load_json(path)?,
+        })
+    }
+
+    pub fn open_default() -> Result<Self, String> {
+        Self::open(&storage::get_data_directory()?.join("entities.json"))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        storage::save_json(&self.path, &self.entities)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Entity> {
+        self.entities.iter().find(|e| e.id == id)
+    }
+
+    pub fn list(&self) -> Vec<Entity> {
+        self.entities.clone()
+    }
+
+    /// Insert a new entity or replace the one with the same ID
+    pub fn upsert(&mut self, mut entity: Entity) -> Result<Entity, String> {
+        if entity.name.trim().is_empty() {
+            return Err("Entity name is required".to_string());
+        }
+        entity.abn = entity
+            .abn
+            .map(|abn| abn.chars().filter(|c| c.is_ascii_digit()).collect::<String>())
+            .filter(|abn| !abn.is_empty());
+        if let Some(address) = entity.address.as_mut() {
+            address.infer_state();
+        }
+        if entity.id.is_empty() {
+            entity.id = storage::generate_id("entity");
+        }
+
+        match self.entities.iter_mut().find(|e| e.id == entity.id) {
+            Some(existing) => *existing = entity.clone(),
+            None => self.entities.push(entity.clone()),
+        }
+        Ok(entity)
+    }
+
+    /// Install or remove a category pack for an entity
+    pub fn set_pack(&mut self, entity_id: &str, pack_id: &str, installed: bool) -> Result<Entity, String> {
+        let entity = self
+            .entities
+            .iter_mut()
+            .find(|e| e.id == entity_id)
+            .ok_or_else(|| format!("Entity not found: {}", entity_id))?;
+        entity.category_packs.retain(|p| p != pack_id);
+        if installed {
+            entity.category_packs.push(pack_id.to_string());
+        }
+        Ok(entity.clone())
+    }
+
+    pub fn delete(&mut self, id: &str) -> bool {
+        let before = self.entities.len();
+        self.entities.retain(|e| e.id != id);
+        self.entities.len() != before
+    }
+}
+
+/// Run a closure against the default entity store while holding its lock
+pub fn with_entities<R>(f: impl FnOnce(&mut EntityStore) -> Result<R, String>) -> Result<R, String> {
+    let _guard = ENTITIES_LOCK.lock().map_err(|_| "Entity store lock poisoned".to_string())?;
+    let mut store = EntityStore::open_default()?;
+    f(&mut store)
+}
+
+fn name_tokens(name: &str) -> Vec<String> {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty() && !NAME_NOISE.contains(t))
+        .map(|t| t.to_string())
+        .collect()
+}
+
+/// Whether two names refer to the same party, ignoring case, punctuation
+/// and company suffixes
+fn names_match(a: &str, b: &str) -> bool {
+    let (a, b) = (name_tokens(a), name_tokens(b));
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    let (shorter, longer) = if a.len() <= b.len() { (&a, &b) } else { (&b, &a) };
+    shorter.iter().all(|t| longer.contains(t))
+}
+
+/// Whether a supplier's name or ABN is the entity's own. An ABN is
+/// conclusive; otherwise the name is compared with the entity's names.
+pub fn is_entity(entity: &Entity, name: Option<&str>, abn: Option<&str>) -> bool {
+    let abn = abn.map(|abn| abn.chars().filter(|c| c.is_ascii_digit()).collect::<String>());
+    if let (Some(abn), Some(own)) = (&abn, &entity.abn) {
+        return abn == own;
+    }
+    name.is_some_and(|name| {
+        std::iter::once(&entity.name)
+            .chain(entity.aliases.iter())
+            .any(|own| names_match(name, own))
+    })
+}
+
+/// Check an invoice's Bill To details against an entity
+pub fn check_recipient(invoice: &ExtractedInvoice, entity: &Entity) -> RecipientCheck {
+    let bill_to_name = invoice.bill_to_name.as_ref().map(|f| f.value.clone());
+    let bill_to_abn = invoice.bill_to_abn.as_ref().map(|f| f.value.clone());
+
+    let abn_result = match (&bill_to_abn, &entity.abn) {
+        (Some(billed), Some(own)) => Some(billed == own),
+        _ => None,
+    };
+    let name_result = bill_to_name.as_ref().map(|billed| {
+        std::iter::once(&entity.name)
+            .chain(entity.aliases.iter())
+            .any(|name| names_match(billed, name))
+    });
+
+    // An ABN is conclusive; otherwise fall back to the name
+    let status = match (abn_result, name_result) {
+        (Some(true), _) => RecipientStatus::Match,
+        (Some(false), _) => RecipientStatus::Mismatch,
+        (None, Some(true)) => RecipientStatus::Match,
+        (None, Some(false)) => RecipientStatus::Mismatch,
+        (None, None) => RecipientStatus::Unknown,
+    };
+
+    let warning = (status == RecipientStatus::Mismatch).then(|| {
+        let billed = bill_to_name
+            .clone()
+            .or_else(|| bill_to_abn.as_ref().map(|abn| format!("ABN {}", abn)))
+            .unwrap_or_default();
+        format!(
+            "Invoice appears to be addressed to {} rather than {}",
+            billed, entity.name
+        )
+    });
+
+    RecipientCheck {
+        status,
+        entity_id: entity.id.clone(),
+        entity_name: entity.name.clone(),
+        bill_to_name,
+        bill_to_abn,
+        warning,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invoice::ExtractedField;
+
+    fn invoice(name: Option<&str>, abn: Option<&str>) -> ExtractedInvoice {
+        ExtractedInvoice {
+            bill_to_name: name.map(|n| ExtractedField::new(n.to_string(), 0.7, "test")),
+            bill_to_abn: abn.map(|a| ExtractedField::new(a.to_string(), 0.9, "test")),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_check_recipient() {
+        let entity = Entity {
+            id: "e1".to_string(),
+            name: "Smith Consulting Pty Ltd".to_string(),
+            abn: Some("53004085616".to_string()),
+            aliases: vec!["Jane Smith".to_string()],
+            address: None,
+            category_packs: Vec::new(),
+        };
+
+        assert_eq!(check_recipient(&invoice(Some("SMITH CONSULTING"), None), &entity).status, RecipientStatus::Match);
+        assert_eq!(check_recipient(&invoice(Some("Jane Smith"), None), &entity).status, RecipientStatus::Match);
+        assert_eq!(check_recipient(&invoice(None, None), &entity).status, RecipientStatus::Unknown);
+
+        let spouse = check_recipient(&invoice(Some("Smith Consulting"), Some("51824753556")), &entity);
+        assert_eq!(spouse.status, RecipientStatus::Mismatch);
+
+        let other = check_recipient(&invoice(Some("Brown Design Studio"), None), &entity);
+        assert_eq!(other.status, RecipientStatus::Mismatch);
+        assert!(other.warning.unwrap().contains("Brown Design Studio"));
+    }
+}
@@ -0,0 +1,340 @@
+//! Entity Groups Module
+//!
+//! Related entities reported on together, such as a family trust and the
+//! company that trades for it. A group report consolidates the members'
+//! expense summaries while keeping each member's BAS figures separate, as
+//! each lodges its own. Purchases one member made from another, and the
+//! same document stored under two members, are flagged as inter-entity and
+//! left out of the consolidated totals so they aren't counted twice.
+//!
+//! Cash journal entries and trading stock aren't recorded per entity, so
+//! group figures cover stored documents only.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::bas::{self, PurchaseLabels};
+use crate::documents::StoredDocument;
+use crate::entities::{self, Entity};
+use crate::gst_treatment;
+use crate::money::Money;
+use crate::period_locks::AdjustmentEntry;
+use crate::periods::{self, BasPeriod};
+use crate::storage;
+use crate::summary::{self, FinancialYearSummary};
+
+static GROUPS_LOCK: Mutex<()> = Mutex::new(());
+
+/// Entities reported on together
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct EntityGroup {
+    pub id: String,
+    pub name: String,
+    pub entity_ids: Vec<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InterEntityKind {
+    /// The supplier is another member of the group
+    RelatedSupplier,
+    /// The same document is stored under another member
+    Duplicate,
+}
+
+/// A document left out of the consolidated totals
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InterEntityTransaction {
+    pub document_id: String,
+    /// Member the document is stored under
+    pub entity_id: String,
+    /// Supplier, or holder of the other copy
+    pub counterparty_entity_id: String,
+    pub kind: InterEntityKind,
+    pub vendor: Option<String>,
+    pub date: Option<String>,
+    pub total: Option<Money>,
+}
+
+/// One member's figures, unaffected by the rest of the group
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EntityReport {
+    pub entity_id: String,
+    pub entity_name: String,
+    pub summary: FinancialYearSummary,
+    /// Purchase labels for each quarter of the year
+    pub bas: Vec<PurchaseLabels>,
+}
+
+/// Group figures for a financial year
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GroupReport {
+    pub group_id: String,
+    pub group_name: String,
+    pub financial_year: i32,
+    pub entities: Vec<EntityReport>,
+    /// Members' documents combined, less inter-entity transactions
+    pub consolidated: FinancialYearSummary,
+    pub inter_entity: Vec<InterEntityTransaction>,
+    /// Total of the inter-entity documents dated in the year
+    pub eliminated_total: Money,
+}
+
+/// JSON-backed list of entity groups
+pub struct EntityGroupStore {
+    path: PathBuf,
+    groups: Vec<EntityGroup>,
+}
+
+impl EntityGroupStore {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            groups: storage::load_json(path)?,
+        })
+    }
+
+    pub fn open_default() -> Result<Self, String> {
+        Self::open(&storage::get_data_directory()?.join("entity_groups.json"))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        storage::save_json(&self.path, &self.groups)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&EntityGroup> {
+        self.groups.iter().find(|g| g.id == id)
+    }
+
+    pub fn list(&self) -> Vec<EntityGroup> {
+        self.groups.clone()
+    }
+
+    /// Insert a new group or replace the one with the same ID
+    pub fn upsert(&mut self, mut group: EntityGroup) -> Result<EntityGroup, String> {
+        if group.name.trim().is_empty() {
+            return Err("Group name is required".to_string());
+        }
+        let mut seen = HashSet::new();
+        group.entity_ids.retain(|id| seen.insert(id.clone()));
+        if group.entity_ids.len() < 2 {
+            return Err("A group needs at least two entities".to_string());
+        }
+        if group.id.is_empty() {
+            group.id = storage::generate_id("group");
+            group.created_at = storage::now_timestamp();
+        }
+
+        match self.groups.iter_mut().find(|g| g.id == group.id) {
+            Some(existing) => *existing = group.clone(),
+            None => self.groups.push(group.clone()),
+        }
+        Ok(group)
+    }
+
+    pub fn delete(&mut self, id: &str) -> bool {
+        let before = self.groups.len();
+        self.groups.retain(|g| g.id != id);
+        self.groups.len() != before
+    }
+}
+
+/// Run a closure against the default group store while holding its lock
+pub fn with_groups<R>(f: impl FnOnce(&mut EntityGroupStore) -> Result<R, String>) -> Result<R, String> {
+    let _guard = GROUPS_LOCK.lock().map_err(|_| "Entity group store lock poisoned".to_string())?;
+    let mut store = EntityGroupStore::open_default()?;
+    f(&mut store)
+}
+
+/// Documents that are the same purchase: same date, total and vendor
+fn same_purchase(a: &StoredDocument, b: &StoredDocument) -> bool {
+    let vendor = |d: &StoredDocument| d.vendor.as_deref().map(|v| v.trim().to_lowercase());
+    a.date.is_some()
+        && a.date == b.date
+        && a.total == b.total
+        && vendor(a).is_some()
+        && vendor(a) == vendor(b)
+}
+
+/// Flag members' documents that move money within the group. Of two copies
+/// of one document, the later stored is flagged.
+pub fn find_inter_entity(members: &[&Entity], documents: &[&StoredDocument]) -> Vec<InterEntityTransaction> {
+    let mut flagged = Vec::new();
+    for doc in documents {
+        let Some(entity_id) = doc.entity_id.as_deref() else {
+            continue;
+        };
+        let flag = |counterparty: &str, kind| InterEntityTransaction {
+            document_id: doc.id.clone(),
+            entity_id: entity_id.to_string(),
+            counterparty_entity_id: counterparty.to_string(),
+            kind,
+            vendor: doc.vendor.clone(),
+            date: doc.date.clone(),
+            total: doc.total,
+        };
+
+        let abn = gst_treatment::supplier_abn(doc);
+        let supplier = members
+            .iter()
+            .find(|m| m.id != entity_id && entities::is_entity(m, doc.vendor.as_deref(), abn));
+        if let Some(supplier) = supplier {
+            flagged.push(flag(&supplier.id, InterEntityKind::RelatedSupplier));
+            continue;
+        }
+
+        let earlier_copy = documents.iter().find(|other| {
+            other.entity_id.as_deref().is_some_and(|id| id != entity_id)
+                && (other.created_at.as_str(), other.id.as_str()) < (doc.created_at.as_str(), doc.id.as_str())
+                && same_purchase(doc, other)
+        });
+        if let Some(other) = earlier_copy {
+            flagged.push(flag(other.entity_id.as_deref().unwrap_or_default(), InterEntityKind::Duplicate));
+        }
+    }
+    flagged
+}
+
+/// Build the group report for a financial year from every stored document
+/// and the corrections recorded against lodged quarters
+pub fn group_report(
+    group: &EntityGroup,
+    entities: &[Entity],
+    documents: &[StoredDocument],
+    corrections: &[AdjustmentEntry],
+    financial_year: i32,
+) -> Result<GroupReport, String> {
+    let members: Vec<&Entity> = group
+        .entity_ids
+        .iter()
+        .map(|id| {
+            entities
+                .iter()
+                .find(|e| &e.id == id)
+                .ok_or_else(|| format!("Entity not found: {}", id))
+        })
+        .collect::<Result<_, _>>()?;
+    let member_documents: Vec<&StoredDocument> = documents
+        .iter()
+        .filter(|d| d.entity_id.as_ref().is_some_and(|id| group.entity_ids.contains(id)))
+        .collect();
+
+    let entity_reports = members
+        .iter()
+        .map(|entity| {
+            let own: Vec<StoredDocument> = member_documents
+                .iter()
+                .filter(|d| d.entity_id.as_deref() == Some(entity.id.as_str()))
+                .map(|d| (*d).clone())
+                .collect();
+            let own_corrections: Vec<AdjustmentEntry> = corrections
+                .iter()
+                .filter(|c| c.entity_id.as_deref() == Some(entity.id.as_str()))
+                .cloned()
+                .collect();
+            EntityReport {
+                entity_id: entity.id.clone(),
+                entity_name: entity.name.clone(),
+                summary: summary::financial_year_summary(&own, &[], None, financial_year),
+                bas: (1..=4)
+                    .map(|quarter| bas::purchase_labels(&own, &own_corrections, BasPeriod { financial_year, quarter }))
+                    .collect(),
+            }
+        })
+        .collect();
+
+    let in_year = |date: Option<&str>| {
+        date.and_then(periods::parse_date)
+            .is_some_and(|d| periods::financial_year_of(d) == financial_year)
+    };
+    let inter_entity: Vec<InterEntityTransaction> = find_inter_entity(&members, &member_documents)
+        .into_iter()
+        .filter(|t| in_year(t.date.as_deref()))
+        .collect();
+    let eliminated: HashSet<&str> = inter_entity.iter().map(|t| t.document_id.as_str()).collect();
+    let consolidated: Vec<StoredDocument> = member_documents
+        .iter()
+        .filter(|d| !eliminated.contains(d.id.as_str()))
+        .map(|d| (*d).clone())
+        .collect();
+    let eliminated_total = inter_entity.iter().filter_map(|t| t.total).sum();
+
+    Ok(GroupReport {
+        group_id: group.id.clone(),
+        group_name: group.name.clone(),
+        financial_year,
+        entities: entity_reports,
+        consolidated: summary::financial_year_summary(&consolidated, &[], None, financial_year),
+        inter_entity,
+        eliminated_total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(id: &str, entity_id: &str, vendor: &str, total: f64, created_at: &str) -> StoredDocument {
+        StoredDocument {
+            id: id.to_string(),
+            entity_id: Some(entity_id.to_string()),
+            vendor: Some(vendor.to_string()),
+            date: Some("2023-09-01".to_string()),
+            total: Some(Money::from_dollars(total)),
+            gst: Some(Money::from_dollars(total).gst_in_inclusive()),
+            category: Some("Supplies".to_string()),
+            created_at: created_at.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_group_report_eliminates_inter_entity_documents() {
+        let entity = |id: &str, name: &str| Entity {
+            id: id.to_string(),
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let entities = vec![
+            entity("trust", "Smith Family Trust"),
+            entity("company", "Smith Trading Pty Ltd"),
+            entity("outsider", "Jones Pty Ltd"),
+        ];
+        let documents = vec![
+            doc("paper", "trust", "Officeworks", 110.0, "2023-09-02T09:00:00+10:00"),
+            // The company bills the trust for management services
+            doc("fees", "trust", "Smith Trading", 550.0, "2023-09-03T09:00:00+10:00"),
+            doc("fuel", "company", "Ampol", 88.0, "2023-09-02T09:00:00+10:00"),
+            // The same Ampol receipt filed again under the trust
+            doc("fuel-copy", "trust", "Ampol", 88.0, "2023-09-05T09:00:00+10:00"),
+            doc("other", "outsider", "Bunnings", 33.0, "2023-09-02T09:00:00+10:00"),
+        ];
+        let group = EntityGroup {
+            id: "group-1".to_string(),
+            name: "Smith group".to_string(),
+            entity_ids: vec!["trust".to_string(), "company".to_string()],
+            ..Default::default()
+        };
+
+        let report = group_report(&group, &entities, &documents, &[], 2024).unwrap();
+        let flagged: Vec<(&str, InterEntityKind)> =
+            report.inter_entity.iter().map(|t| (t.document_id.as_str(), t.kind)).collect();
+        assert_eq!(
+            flagged,
+            vec![("fees", InterEntityKind::RelatedSupplier), ("fuel-copy", InterEntityKind::Duplicate)]
+        );
+        assert_eq!(report.inter_entity[0].counterparty_entity_id, "company");
+        assert_eq!(report.eliminated_total, Money::from_cents(63800));
+        assert_eq!(report.consolidated.substantiated_total.to_dollars(), 198.0);
+
+        // Each member's own figures, including inter-entity purchases, are untouched
+        assert_eq!(report.entities[0].summary.substantiated_total.to_dollars(), 748.0);
+        assert_eq!(report.entities[1].summary.substantiated_total.to_dollars(), 88.0);
+        assert_eq!(report.entities[0].bas[0].purchases, 748.0);
+        assert_eq!(report.entities[0].bas.len(), 4);
+    }
+}
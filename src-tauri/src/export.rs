@@ -0,0 +1,356 @@
+//! Document Export Module
+//!
+//! Copies archived originals out of the app for handover. Supports:
+//! - Canonical filenames derived from extracted data
+//!   (`YYYY-MM-DD_vendor_$total_invno.ext`) instead of camera names
+//! - Bulk export of every document's OCR and PDF text, as plain text files
+//!   or JSONL keyed by document ID, for keyword searching outside the app
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::documents::{DocumentKind, StoredDocument, TextVersion};
+use crate::money::Money;
+
+/// A document copied to the export folder
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportedOriginal {
+    pub document_id: String,
+    pub source_path: String,
+    pub exported_path: String,
+}
+
+/// A document that could not be exported
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SkippedExport {
+    pub document_id: String,
+    pub reason: String,
+}
+
+/// Result of exporting originals
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OriginalsExportResult {
+    pub destination: String,
+    pub exported: Vec<ExportedOriginal>,
+    pub skipped: Vec<SkippedExport>,
+}
+
+/// Layout of an OCR text export
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TextExportFormat {
+    /// A `<document id>.txt` file per document
+    PlainText,
+    /// One JSON record per document in a single `ocr_text.jsonl` file
+    Jsonl,
+}
+
+/// A document's text as written to a JSONL export
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TextExportRecord {
+    pub document_id: String,
+    pub kind: DocumentKind,
+    pub vendor: Option<String>,
+    pub date: Option<String>,
+    pub total: Option<Money>,
+    pub source_path: Option<String>,
+    pub text: String,
+    /// Text from earlier extractions, oldest first
+    pub text_versions: Vec<TextVersion>,
+}
+
+/// Result of exporting OCR text
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TextExportResult {
+    pub destination: String,
+    pub format: TextExportFormat,
+    /// Files written
+    pub files: Vec<String>,
+    /// IDs of the documents whose text was exported
+    pub exported: Vec<String>,
+    pub skipped: Vec<SkippedExport>,
+}
+
+/// Build the canonical filename stem for a document (without extension)
+pub fn canonical_stem(doc: &StoredDocument) -> String {
+    let mut parts = vec![
+        doc.date.clone().unwrap_or_else(|| "undated".to_string()),
+        slugify(doc.vendor.as_deref().unwrap_or("unknown-vendor"), 40),
+    ];
+
+    if let Some(total) = doc.total {
+        parts.push(format!("${}", total));
+    }
+
+    let invoice_number = doc
+        .invoice
+        .as_ref()
+        .and_then(|i| i.invoice_number.as_ref())
+        .map(|n| slugify(&n.value, 30).to_uppercase())
+        .filter(|n| !n.is_empty());
+    if let Some(number) = invoice_number {
+        parts.push(number);
+    }
+
+    parts.join("_")
+}
+
+/// Build the canonical filename for a document, keeping the original extension
+pub fn canonical_filename(doc: &StoredDocument) -> String {
+    let extension = doc
+        .source_path
+        .as_deref()
+        .and_then(|p| Path::new(p).extension())
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+
+    match extension {
+        Some(ext) => format!("{}.{}", canonical_stem(doc), ext),
+        None => canonical_stem(doc),
+    }
+}
+
+/// Lowercase, hyphen-separated ASCII version of a name, safe for filenames
+pub fn slugify(value: &str, max_len: usize) -> String {
+    let mut slug = String::new();
+    for c in value.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let slug: String = slug.chars().take(max_len).collect();
+    slug.trim_matches('-').to_string()
+}
+
+/// Copy each document's original into `destination` under its canonical name
+pub fn export_originals(
+    documents: &[StoredDocument],
+    destination: &Path,
+) -> Result<OriginalsExportResult, String> {
+    fs::create_dir_all(destination)
+        .map_err(|e| format!("Failed to create directory: {}", e))?;
+
+    let mut used_names = HashSet::new();
+    let mut exported = Vec::new();
+    let mut skipped = Vec::new();
+
+    for doc in documents {
+        let Some(source) = doc.source_path.as_deref() else {
+            skipped.push(SkippedExport {
+                document_id: doc.id.clone(),
+                reason: "No original file".to_string(),
+            });
+            continue;
+        };
+        if !Path::new(source).exists() {
+            skipped.push(SkippedExport {
+                document_id: doc.id.clone(),
+                reason: format!("Original file missing: {}", source),
+            });
+            continue;
+        }
+
+        let filename = unique_name(&canonical_filename(doc), destination, &mut used_names);
+        let target = destination.join(&filename);
+        match fs::copy(source, &target) {
+            Ok(_) => exported.push(ExportedOriginal {
+                document_id: doc.id.clone(),
+                source_path: source.to_string(),
+                exported_path: target.to_string_lossy().to_string(),
+            }),
+            Err(e) => skipped.push(SkippedExport {
+                document_id: doc.id.clone(),
+                reason: format!("Failed to copy original: {}", e),
+            }),
+        }
+    }
+
+    Ok(OriginalsExportResult {
+        destination: destination.to_string_lossy().to_string(),
+        exported,
+        skipped,
+    })
+}
+
+/// Write the current and earlier OCR text of each document to
+/// `destination`. Plain text files are named by document ID and replace
+/// any from an earlier export; a JSONL file never overwrites an earlier one.
+pub fn export_text(
+    documents: &[StoredDocument],
+    destination: &Path,
+    format: TextExportFormat,
+) -> Result<TextExportResult, String> {
+    fs::create_dir_all(destination)
+        .map_err(|e| format!("Failed to create directory: {}", e))?;
+
+    let mut records = Vec::new();
+    let mut skipped = Vec::new();
+    for doc in documents {
+        let text = doc.ocr_text().unwrap_or_default();
+        if text.trim().is_empty() && doc.text_versions.is_empty() {
+            skipped.push(SkippedExport {
+                document_id: doc.id.clone(),
+                reason: "No extracted text".to_string(),
+            });
+            continue;
+        }
+        records.push(TextExportRecord {
+            document_id: doc.id.clone(),
+            kind: doc.kind,
+            vendor: doc.vendor.clone(),
+            date: doc.date.clone(),
+            total: doc.total,
+            source_path: doc.source_path.clone(),
+            text: text.to_string(),
+            text_versions: doc.text_versions.clone(),
+        });
+    }
+
+    let mut files = Vec::new();
+    match format {
+        TextExportFormat::PlainText => {
+            for record in &records {
+                let mut contents = record.text.clone();
+                for version in &record.text_versions {
+                    contents.push_str(&format!("\n\n--- Earlier extraction, replaced {} ---\n", version.replaced_at));
+                    contents.push_str(&version.text);
+                }
+                let target = destination.join(format!("{}.txt", file_safe(&record.document_id)));
+                fs::write(&target, contents).map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
+                files.push(target.to_string_lossy().to_string());
+            }
+        }
+        TextExportFormat::Jsonl => {
+            let mut contents = String::new();
+            for record in &records {
+                let line = serde_json::to_string(record).map_err(|e| format!("Failed to serialize text: {}", e))?;
+                contents.push_str(&line);
+                contents.push('\n');
+            }
+            let target = destination.join(unique_name("ocr_text.jsonl", destination, &mut HashSet::new()));
+            fs::write(&target, contents).map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
+            files.push(target.to_string_lossy().to_string());
+        }
+    }
+
+    Ok(TextExportResult {
+        destination: destination.to_string_lossy().to_string(),
+        format,
+        files,
+        exported: records.into_iter().map(|r| r.document_id).collect(),
+        skipped,
+    })
+}
+
+/// Document ID with anything unsafe in a filename replaced
+fn file_safe(id: &str) -> String {
+    id.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// Append `_2`, `_3`, ... until the name is unused in this export and on disk
+fn unique_name(filename: &str, destination: &Path, used: &mut HashSet<String>) -> String {
+    let path = Path::new(filename);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(filename);
+    let extension = path.extension().and_then(|e| e.to_str());
+
+    let mut candidate = filename.to_string();
+    let mut counter = 2;
+    while used.contains(&candidate) || destination.join(&candidate).exists() {
+        candidate = match extension {
+            Some(ext) => format!("{}_{}.{}", stem, counter, ext),
+            None => format!("{}_{}", stem, counter),
+        };
+        counter += 1;
+    }
+
+    used.insert(candidate.clone());
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invoice::{ExtractedField, ExtractedInvoice};
+
+    #[test]
+    fn test_canonical_filename() {
+        let doc = StoredDocument {
+            vendor: Some("Bunnings Warehouse Pty. Ltd.".to_string()),
+            date: Some("2024-01-15".to_string()),
+            total: Some(Money::from_cents(11000)),
+            source_path: Some("/photos/IMG_4312.JPG".to_string()),
+            invoice: Some(ExtractedInvoice {
+                invoice_number: Some(ExtractedField::new("inv/2024-001".to_string(), 0.85, "test")),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            canonical_filename(&doc),
+            "2024-01-15_bunnings-warehouse-pty-ltd_$110.00_INV-2024-001.jpg"
+        );
+
+        let sparse = StoredDocument::default();
+        assert_eq!(canonical_filename(&sparse), "undated_unknown-vendor");
+    }
+
+    #[test]
+    fn test_unique_name_appends_counter() {
+        let destination = std::env::temp_dir().join("tally-test-nonexistent-export");
+        let mut used = HashSet::new();
+
+        assert_eq!(unique_name("a.pdf", &destination, &mut used), "a.pdf");
+        assert_eq!(unique_name("a.pdf", &destination, &mut used), "a_2.pdf");
+        assert_eq!(unique_name("a.pdf", &destination, &mut used), "a_3.pdf");
+    }
+
+    #[test]
+    fn test_text_export_keys_text_by_document_id() {
+        let destination = crate::storage::test_dir();
+        let documents = vec![
+            StoredDocument {
+                id: "doc-1".to_string(),
+                kind: DocumentKind::Invoice,
+                vendor: Some("Officeworks".to_string()),
+                invoice: Some(ExtractedInvoice {
+                    raw_text: "OFFICEWORKS\nTOTAL $42.50".to_string(),
+                    ..Default::default()
+                }),
+                text_versions: vec![TextVersion {
+                    text: "0FFICEW0RKS".to_string(),
+                    replaced_at: "2024-01-02T09:00:00+10:00".to_string(),
+                }],
+                ..Default::default()
+            },
+            StoredDocument {
+                id: "manual".to_string(),
+                manual_entry: true,
+                ..Default::default()
+            },
+        ];
+
+        let plain = export_text(&documents, &destination, TextExportFormat::PlainText).unwrap();
+        assert_eq!(plain.exported, vec!["doc-1"]);
+        assert_eq!(plain.skipped[0].document_id, "manual");
+        let text = fs::read_to_string(destination.join("doc-1.txt")).unwrap();
+        assert!(text.starts_with("OFFICEWORKS\nTOTAL $42.50"));
+        assert!(text.ends_with("replaced 2024-01-02T09:00:00+10:00 ---\n0FFICEW0RKS"));
+
+        let jsonl = export_text(&documents, &destination, TextExportFormat::Jsonl).unwrap();
+        let contents = fs::read_to_string(&jsonl.files[0]).unwrap();
+        let record: TextExportRecord = serde_json::from_str(contents.lines().next().unwrap()).unwrap();
+        assert_eq!(record.document_id, "doc-1");
+        assert_eq!(record.text_versions.len(), 1);
+        // A second export is written alongside the first
+        let again = export_text(&documents, &destination, TextExportFormat::Jsonl).unwrap();
+        assert!(again.files[0].ends_with("ocr_text_2.jsonl"));
+    }
+}
@@ -0,0 +1,239 @@
+//! Finance Export Module
+//!
+//! Writes stored expenses as OFX or QIF files for personal finance tools
+//! such as GnuCash and Banktivity, so they can be imported instead of typed
+//! in again. Each document becomes one withdrawal with the vendor as payee
+//! and the category, GST and invoice number in the memo. OFX transactions
+//! use the document ID as their unique ID, so importing an overlapping
+//! export again doesn't duplicate them.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::documents::StoredDocument;
+use crate::export::SkippedExport;
+use crate::money::Money;
+use crate::periods;
+
+/// OFX limits the payee name to 32 characters
+const OFX_NAME_LENGTH: usize = 32;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FinanceFormat {
+    Ofx,
+    Qif,
+}
+
+impl FinanceFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FinanceFormat::Ofx => "ofx",
+            FinanceFormat::Qif => "qif",
+        }
+    }
+}
+
+/// Result of writing an OFX or QIF file
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FinanceExportResult {
+    pub file_path: String,
+    pub format: FinanceFormat,
+    pub exported: usize,
+    pub skipped: Vec<SkippedExport>,
+}
+
+/// An expense ready to be written as a transaction
+struct Transaction<'a> {
+    doc: &'a StoredDocument,
+    date: NaiveDate,
+    amount: Money,
+}
+
+impl Transaction<'_> {
+    fn payee(&self) -> &str {
+        self.doc.vendor.as_deref().filter(|v| !v.trim().is_empty()).unwrap_or("Unknown vendor")
+    }
+
+    fn memo(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(category) = &self.doc.category {
+            parts.push(category.clone());
+        }
+        if let Some(gst) = self.doc.gst.filter(|gst| *gst > Money::ZERO) {
+            parts.push(format!("GST {}", gst));
+        }
+        if let Some(number) = self.doc.invoice.as_ref().and_then(|inv| inv.invoice_number.as_ref()) {
+            parts.push(format!("Invoice {}", number.value));
+        }
+        parts.join(" | ")
+    }
+}
+
+/// Dated expenses with a total, oldest first, and the documents left out
+fn transactions(documents: &[StoredDocument]) -> (Vec<Transaction<'_>>, Vec<SkippedExport>) {
+    let mut transactions = Vec::new();
+    let mut skipped = Vec::new();
+    for doc in documents {
+        let date = doc.date.as_deref().and_then(periods::parse_date);
+        let amount = doc.total.filter(|total| *total > Money::ZERO);
+        match (date, amount) {
+            (Some(date), Some(amount)) => transactions.push(Transaction { doc, date, amount }),
+            _ => skipped.push(SkippedExport {
+                document_id: doc.id.clone(),
+                reason: "Missing date or total".to_string(),
+            }),
+        }
+    }
+    transactions.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.doc.id.cmp(&b.doc.id)));
+    (transactions, skipped)
+}
+
+/// Single-line text for QIF fields, which end at a newline
+fn qif_text(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Render expenses as a QIF bank register. Dates are day-first, as written
+/// by Australian banks.
+pub fn render_qif(documents: &[StoredDocument]) -> (String, Vec<SkippedExport>) {
+    let (transactions, skipped) = transactions(documents);
+    let mut qif = String::from("!Type:Bank\n");
+    for transaction in &transactions {
+        qif.push_str(&format!("D{}\n", transaction.date.format("%d/%m/%Y")));
+        qif.push_str(&format!("T-{}\n", transaction.amount));
+        qif.push_str(&format!("P{}\n", qif_text(transaction.payee())));
+        if let Some(category) = &transaction.doc.category {
+            qif.push_str(&format!("L{}\n", qif_text(category)));
+        }
+        let memo = transaction.memo();
+        if !memo.is_empty() {
+            qif.push_str(&format!("M{}\n", qif_text(&memo)));
+        }
+        qif.push_str("^\n");
+    }
+    (qif, skipped)
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Render expenses as an OFX 2.2 bank statement in Australian dollars
+pub fn render_ofx(documents: &[StoredDocument], generated_at: chrono::NaiveDateTime) -> (String, Vec<SkippedExport>) {
+    let (transactions, skipped) = transactions(documents);
+    let ofx_date = |date: NaiveDate| date.format("%Y%m%d").to_string();
+    let generated = generated_at.format("%Y%m%d%H%M%S").to_string();
+    let start = transactions.first().map_or_else(|| generated.clone(), |t| ofx_date(t.date));
+    let end = transactions.last().map_or_else(|| generated.clone(), |t| ofx_date(t.date));
+
+    let mut ofx = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n\
+         <?OFX OFXHEADER=\"200\" VERSION=\"220\" SECURITY=\"NONE\" OLDFILEUID=\"NONE\" NEWFILEUID=\"NONE\"?>\n\
+         <OFX>\n",
+    );
+    ofx.push_str(&format!(
+        "<SIGNONMSGSRSV1><SONRS><STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>\
+         <DTSERVER>{}</DTSERVER><LANGUAGE>ENG</LANGUAGE></SONRS></SIGNONMSGSRSV1>\n",
+        generated
+    ));
+    ofx.push_str(
+        "<BANKMSGSRSV1><STMTTRNRS><TRNUID>1</TRNUID><STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>\n\
+         <STMTRS><CURDEF>AUD</CURDEF>\n\
+         <BANKACCTFROM><BANKID>TALLY</BANKID><ACCTID>TALLY-EXPENSES</ACCTID>\
+         <ACCTTYPE>CHECKING</ACCTTYPE></BANKACCTFROM>\n",
+    );
+    ofx.push_str(&format!("<BANKTRANLIST><DTSTART>{}</DTSTART><DTEND>{}</DTEND>\n", start, end));
+    for transaction in &transactions {
+        let name: String = transaction.payee().chars().take(OFX_NAME_LENGTH).collect();
+        ofx.push_str(&format!(
+            "<STMTTRN><TRNTYPE>DEBIT</TRNTYPE><DTPOSTED>{}</DTPOSTED><TRNAMT>-{}</TRNAMT>\
+             <FITID>{}</FITID><NAME>{}</NAME>",
+            ofx_date(transaction.date),
+            transaction.amount,
+            escape(&transaction.doc.id),
+            escape(name.trim())
+        ));
+        let memo = transaction.memo();
+        if !memo.is_empty() {
+            ofx.push_str(&format!("<MEMO>{}</MEMO>", escape(&memo)));
+        }
+        ofx.push_str("</STMTTRN>\n");
+    }
+    ofx.push_str(&format!(
+        "</BANKTRANLIST>\n<LEDGERBAL><BALAMT>0.00</BALAMT><DTASOF>{}</DTASOF></LEDGERBAL>\n\
+         </STMTRS></STMTTRNRS></BANKMSGSRSV1>\n</OFX>\n",
+        generated
+    ));
+    (ofx, skipped)
+}
+
+/// Write documents to `output_path` in the chosen format
+pub fn export_documents(
+    documents: &[StoredDocument],
+    format: FinanceFormat,
+    output_path: &Path,
+) -> Result<FinanceExportResult, String> {
+    let (contents, skipped) = match format {
+        FinanceFormat::Ofx => render_ofx(documents, chrono::Local::now().naive_local()),
+        FinanceFormat::Qif => render_qif(documents),
+    };
+    if let Some(dir) = output_path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    fs::write(output_path, contents).map_err(|e| format!("Failed to write {} file: {}", format.extension(), e))?;
+
+    Ok(FinanceExportResult {
+        file_path: output_path.to_string_lossy().to_string(),
+        format,
+        exported: documents.len() - skipped.len(),
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(id: &str, vendor: &str, date: Option<&str>, total: f64) -> StoredDocument {
+        StoredDocument {
+            id: id.to_string(),
+            vendor: Some(vendor.to_string()),
+            date: date.map(str::to_string),
+            total: Some(Money::from_dollars(total)),
+            gst: Some(Money::from_dollars(total).gst_in_inclusive()),
+            category: Some("Office Supplies".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_expenses_written_as_withdrawals() {
+        let documents = vec![
+            doc("doc-2", "Officeworks & Co <Sydney>", Some("2024-03-14"), 22.0),
+            doc("doc-1", "Bunnings", Some("2024-03-02"), 110.0),
+            doc("doc-3", "Undated", None, 5.0),
+        ];
+
+        let (qif, skipped) = render_qif(&documents);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].document_id, "doc-3");
+        assert!(qif.starts_with(
+            "!Type:Bank\nD02/03/2024\nT-110.00\nPBunnings\nLOffice Supplies\nMOffice Supplies | GST 10.00\n^\n"
+        ));
+
+        let generated = NaiveDate::from_ymd_opt(2024, 4, 1).unwrap().and_hms_opt(9, 30, 0).unwrap();
+        let (ofx, _) = render_ofx(&documents, generated);
+        assert!(ofx.contains("<DTSTART>20240302</DTSTART><DTEND>20240314</DTEND>"));
+        assert!(ofx.contains("<FITID>doc-2</FITID><NAME>Officeworks &amp; Co &lt;Sydney&gt;</NAME>"));
+        assert!(ofx.contains("<TRNAMT>-22.00</TRNAMT>"));
+        assert_eq!(ofx.matches("<STMTTRN>").count(), 2);
+        assert!(ofx.contains("<DTSERVER>20240401093000</DTSERVER>"));
+    }
+}
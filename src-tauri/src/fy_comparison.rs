@@ -0,0 +1,222 @@
+//! Financial Year Comparison Module
+//!
+//! Year-over-year view of expenses: each category's total for a financial
+//! year beside the prior year's, with the change in dollars and as a
+//! percentage. The same structure feeds the dashboard and the PDF report,
+//! which ends with an appendix of unreviewed documents to check by hand.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::cash;
+use crate::documents::{self, DocumentFilter};
+use crate::money::{round_cents, Money};
+use crate::report_builder::{Align, Column, ReportBuilder};
+use crate::saved_reports::ReportTotal;
+use crate::stock;
+use crate::summary::{self, FinancialYearSummary};
+use crate::verification::{self, DocumentToCheck};
+
+/// One category's totals across the two years
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CategoryComparison {
+    pub category: String,
+    pub current: f64,
+    pub prior: f64,
+    /// Current less prior
+    pub delta: f64,
+    /// Change relative to the prior year; `None` when the prior year had nothing
+    pub percent_change: Option<f64>,
+}
+
+impl CategoryComparison {
+    fn new(category: &str, current: Money, prior: Money) -> Self {
+        let delta = current - prior;
+        Self {
+            category: category.to_string(),
+            current: current.to_dollars(),
+            prior: prior.to_dollars(),
+            delta: delta.to_dollars(),
+            percent_change: percent_change(current.to_dollars(), prior.to_dollars()),
+        }
+    }
+}
+
+/// Expenses for a financial year against the one before it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FyComparison {
+    pub financial_year: i32,
+    pub label: String,
+    pub prior_label: String,
+    /// Every category used in either year, alphabetically
+    pub categories: Vec<CategoryComparison>,
+    /// Total expenses, including any trading stock adjustment
+    pub total: CategoryComparison,
+    /// Documents in the current year's totals whose figures are uncertain
+    #[serde(default)]
+    pub to_check: Vec<DocumentToCheck>,
+}
+
+/// Saved comparison PDF
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FyComparisonSaveResult {
+    pub comparison: FyComparison,
+    pub file_path: String,
+}
+
+fn percent_change(current: f64, prior: f64) -> Option<f64> {
+    (prior != 0.0).then(|| round_cents((current - prior) / prior.abs() * 100.0))
+}
+
+/// Compare two year summaries category by category
+pub fn compare(current: &FinancialYearSummary, prior: &FinancialYearSummary) -> FyComparison {
+    let mut totals: BTreeMap<&str, (Money, Money)> = BTreeMap::new();
+    for c in &current.categories {
+        totals.entry(&c.category).or_default().0 = c.total;
+    }
+    for c in &prior.categories {
+        totals.entry(&c.category).or_default().1 = c.total;
+    }
+
+    FyComparison {
+        financial_year: current.financial_year,
+        label: current.label.clone(),
+        prior_label: prior.label.clone(),
+        categories: totals
+            .into_iter()
+            .map(|(category, (current, prior))| CategoryComparison::new(category, current, prior))
+            .collect(),
+        total: CategoryComparison::new("Total expenses", current.total_expenses, prior.total_expenses),
+        to_check: Vec::new(),
+    }
+}
+
+fn year_summary(financial_year: i32) -> Result<FinancialYearSummary, String> {
+    let documents = documents::with_store(|store| Ok(store.list(&DocumentFilter::default())))?;
+    let cash_expenses = cash::with_journal(|journal| Ok(journal.list(Some(financial_year))))?;
+    let trading_stock = stock::with_stock_ledger(|ledger| Ok(ledger.year_summary(financial_year)))?;
+    Ok(summary::financial_year_summary(&documents, &cash_expenses, trading_stock, financial_year))
+}
+
+/// Compare a financial year with the prior year using current data
+pub fn build_comparison(financial_year: i32) -> Result<FyComparison, String> {
+    let mut comparison = compare(&year_summary(financial_year)?, &year_summary(financial_year - 1)?);
+    comparison.to_check = verification::for_financial_year(financial_year)?;
+    Ok(comparison)
+}
+
+fn format_change(row: &CategoryComparison) -> (String, String) {
+    let delta = if row.delta < 0.0 {
+        format!("-${:.2}", -row.delta)
+    } else {
+        format!("+${:.2}", row.delta)
+    };
+    let percent = row.percent_change.map_or_else(|| "new".to_string(), |p| format!("{:+.1}%", p));
+    (delta, percent)
+}
+
+/// Render a comparison as a PDF
+pub fn render_pdf(comparison: &FyComparison) -> Vec<u8> {
+    let title = format!("{} vs {}", comparison.label, comparison.prior_label);
+    let mut report = ReportBuilder::new(&format!("Financial year comparison {}", title));
+    report
+        .heading("Financial Year Comparison")
+        .text(&title)
+        .space(12.0);
+
+    let rows: Vec<Vec<String>> = comparison
+        .categories
+        .iter()
+        .chain(std::iter::once(&comparison.total))
+        .map(|row| {
+            let (delta, percent) = format_change(row);
+            vec![
+                row.category.clone(),
+                format!("${:.2}", row.current),
+                format!("${:.2}", row.prior),
+                delta,
+                percent,
+            ]
+        })
+        .collect();
+    report.table(
+        &[
+            Column::new("Category", 185.0, Align::Left),
+            Column::new(&comparison.label, 85.0, Align::Right),
+            Column::new(&comparison.prior_label, 85.0, Align::Right),
+            Column::new("Change", 85.0, Align::Right),
+            Column::new("%", 75.0, Align::Right),
+        ],
+        &rows,
+    );
+    verification::render_appendix(&mut report, &comparison.to_check);
+    report.build()
+}
+
+/// Build the comparison for a financial year and write it as a PDF
+pub fn save_comparison(financial_year: i32, file_path: &Path) -> Result<FyComparisonSaveResult, String> {
+    let comparison = build_comparison(financial_year)?;
+    if let Some(dir) = file_path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    fs::write(file_path, render_pdf(&comparison)).map_err(|e| format!("Failed to write comparison: {}", e))?;
+    Ok(FyComparisonSaveResult {
+        comparison,
+        file_path: file_path.to_string_lossy().to_string(),
+    })
+}
+
+/// Headline figures compared between versions of a saved comparison
+pub fn comparison_totals(comparison: &FyComparison) -> Vec<ReportTotal> {
+    let mut totals = vec![
+        ReportTotal::new("Total expenses", comparison.total.current),
+        ReportTotal::new("Prior year total", comparison.total.prior),
+    ];
+    totals.extend(
+        comparison
+            .categories
+            .iter()
+            .map(|c| ReportTotal::new(&format!("Category: {}", c.category), c.current)),
+    );
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cash::CashExpense;
+    use crate::documents::StoredDocument;
+
+    #[test]
+    fn test_categories_are_compared_across_years() {
+        let doc = |date: &str, category: &str, total: f64| StoredDocument {
+            date: Some(date.to_string()),
+            category: Some(category.to_string()),
+            total: Some(Money::from_dollars(total)),
+            ..Default::default()
+        };
+        let docs = vec![
+            doc("2023-08-01", "Travel", 200.0),
+            doc("2023-09-01", "Software", 50.0),
+            doc("2024-08-01", "Travel", 250.0),
+            doc("2024-10-01", "Training", 99.95),
+        ];
+        let cash: Vec<CashExpense> = Vec::new();
+        let current = summary::financial_year_summary(&docs, &cash, None, 2025);
+        let prior = summary::financial_year_summary(&docs, &cash, None, 2024);
+        let comparison = compare(&current, &prior);
+
+        let by_name = |name: &str| comparison.categories.iter().find(|c| c.category == name).unwrap();
+        assert_eq!(comparison.categories.len(), 3);
+        assert_eq!(by_name("Travel").delta, 50.0);
+        assert_eq!(by_name("Travel").percent_change, Some(25.0));
+        assert_eq!(by_name("Software").current, 0.0);
+        assert_eq!(by_name("Software").percent_change, Some(-100.0));
+        assert_eq!(by_name("Training").percent_change, None);
+        assert_eq!(comparison.total.delta, 99.95);
+
+        assert!(render_pdf(&comparison).starts_with(b"%PDF"));
+    }
+}
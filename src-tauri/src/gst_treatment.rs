@@ -0,0 +1,195 @@
+//! GST Treatment Module
+//!
+//! Works out how the GST on a purchase is reported on the BAS. Purchases
+//! with an Australian tax invoice are claimed as standard GST credits, but
+//! services bought from overseas suppliers (cloud hosting, software
+//! subscriptions, advertising) follow different rules:
+//! - A supplier that isn't registered for GST charges none, so there is no
+//!   credit to claim; the purchase is still reported at G11
+//! - Under the reverse charge the buyer accounts for the GST itself: 10% of
+//!   the price is reported as GST payable at 1A and claimed back at 1B
+//! - Suppliers registered under the simplified offshore scheme (they quote
+//!   an ARN rather than an ABN) charge GST meant for consumers. A business
+//!   can't claim it as a credit; it should give the supplier its ABN and
+//!   ask for a refund.
+//!
+//! Documents are classified from their supplier ABN, vendor name and OCR
+//! text; the user can override the result per document.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+use crate::documents::StoredDocument;
+use crate::invoice::InvoiceParser;
+use crate::money::Money;
+
+/// Overseas suppliers commonly billing Australian businesses without an ABN.
+/// Their Australian entities quote an ABN and are classified as standard.
+const OFFSHORE_SUPPLIERS: &[&str] = &[
+    "amazon web services",
+    "aws",
+    "google",
+    "microsoft",
+    "meta platforms",
+    "facebook",
+    "linkedin",
+    "adobe",
+    "github",
+    "dropbox",
+    "slack",
+    "zoom",
+    "openai",
+    "digitalocean",
+    "figma",
+    "notion",
+    "shopify",
+    "mailchimp",
+    "squarespace",
+];
+
+/// How GST on a purchase is reported
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum GstTreatment {
+    /// Australian tax invoice; GST claimed at 1B
+    #[default]
+    Standard,
+    /// Overseas supplier charging no GST; nothing to claim
+    ImportedService,
+    /// Buyer accounts for the GST at 1A and claims it back at 1B
+    ReverseCharge,
+    /// GST charged under the offshore consumer rules; not claimable
+    OffshoreConsumerGst,
+}
+
+fn arn_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?i)\bARN\b[:#\s]*(?:\d\s?){12}").unwrap())
+}
+
+fn foreign_currency_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?i)\b(?:USD|EUR|GBP|NZD|SGD)\b|\bUS\s?\$").unwrap())
+}
+
+/// Valid supplier ABN extracted from the document, if any
+pub(crate) fn supplier_abn(doc: &StoredDocument) -> Option<&str> {
+    let invoice_abn = doc.invoice.as_ref().and_then(|inv| inv.abn.as_ref()).map(|f| f.value.as_str());
+    let receipt_abn = doc.receipt.as_ref().and_then(|rec| rec.abn.as_ref()).map(|f| f.value.as_str());
+    invoice_abn.or(receipt_abn).filter(|abn| InvoiceParser::validate_abn(&abn.replace(' ', "")))
+}
+
+fn is_offshore_supplier(vendor: &str) -> bool {
+    let vendor = vendor.to_lowercase();
+    OFFSHORE_SUPPLIERS.iter().any(|name| {
+        vendor
+            .strip_prefix(name)
+            .is_some_and(|rest| rest.chars().next().map_or(true, |c| !c.is_alphanumeric()))
+    })
+}
+
+/// Classify a document from its contents, ignoring any user override
+pub fn classify(doc: &StoredDocument) -> GstTreatment {
+    let text = doc.ocr_text().unwrap_or("");
+    if text.to_lowercase().contains("reverse charge") {
+        return GstTreatment::ReverseCharge;
+    }
+    // An ABN means an Australian tax invoice, even from a foreign group
+    if supplier_abn(doc).is_some() {
+        return GstTreatment::Standard;
+    }
+
+    let offshore = arn_pattern().is_match(text)
+        || doc.vendor.as_deref().is_some_and(is_offshore_supplier)
+        || foreign_currency_pattern().is_match(text);
+    // Negative on credit notes
+    let gst_charged = doc.gst.is_some_and(|gst| !gst.is_zero());
+    match (offshore, gst_charged) {
+        (false, _) => GstTreatment::Standard,
+        (true, true) => GstTreatment::OffshoreConsumerGst,
+        (true, false) => GstTreatment::ImportedService,
+    }
+}
+
+/// The user's choice for a document, or its classification
+pub fn treatment_of(doc: &StoredDocument) -> GstTreatment {
+    doc.gst_treatment.unwrap_or_else(|| classify(doc))
+}
+
+/// GST the buyer accounts for under the reverse charge: 10% of the price
+pub fn reverse_charge_gst(doc: &StoredDocument) -> Money {
+    if treatment_of(doc) != GstTreatment::ReverseCharge || doc.is_foreign_currency() {
+        return Money::ZERO;
+    }
+    doc.total.unwrap_or_default().gst_on_exclusive()
+}
+
+/// GST credit claimable at 1B for a document. Reverse-charged GST is
+/// claimed back in full, assuming the purchase is wholly for the business.
+/// Foreign-currency amounts count for nothing until converted.
+pub fn creditable_gst(doc: &StoredDocument) -> Money {
+    if doc.is_foreign_currency() {
+        return Money::ZERO;
+    }
+    match treatment_of(doc) {
+        GstTreatment::Standard => doc.gst.unwrap_or_default(),
+        GstTreatment::ReverseCharge => reverse_charge_gst(doc),
+        GstTreatment::ImportedService | GstTreatment::OffshoreConsumerGst => Money::ZERO,
+    }
+}
+
+/// GST paid that can't be claimed and should be refunded by the supplier
+pub fn non_claimable_gst(doc: &StoredDocument) -> Money {
+    match treatment_of(doc) {
+        GstTreatment::OffshoreConsumerGst => doc.aud_gst().unwrap_or_default(),
+        _ => Money::ZERO,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents::DocumentKind;
+    use crate::invoice::{ExtractedField, ExtractedInvoice};
+
+    fn doc(vendor: &str, text: &str, total: f64, gst: Option<f64>, abn: Option<&str>) -> StoredDocument {
+        StoredDocument {
+            kind: DocumentKind::Invoice,
+            vendor: Some(vendor.to_string()),
+            total: Some(Money::from_dollars(total)),
+            gst: gst.map(Money::from_dollars),
+            invoice: Some(ExtractedInvoice {
+                raw_text: text.to_string(),
+                abn: abn.map(|abn| ExtractedField::new(abn.to_string(), 0.9, "test")),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_overseas_services_are_not_standard_credits() {
+        let aws_au = doc("Amazon Web Services Australia", "Tax Invoice", 110.0, Some(10.0), Some("51 824 753 556"));
+        assert_eq!(classify(&aws_au), GstTreatment::Standard);
+        assert_eq!(creditable_gst(&aws_au).to_dollars(), 10.0);
+
+        let aws_us = doc("Amazon Web Services, Inc.", "Invoice total USD 64.00", 100.0, None, None);
+        assert_eq!(classify(&aws_us), GstTreatment::ImportedService);
+        assert_eq!(creditable_gst(&aws_us), Money::ZERO);
+
+        let offshore = doc("Figma Inc", "ARN 3000 1234 5678\nGST 4.50", 49.5, Some(4.5), None);
+        assert_eq!(classify(&offshore), GstTreatment::OffshoreConsumerGst);
+        assert_eq!((creditable_gst(&offshore), non_claimable_gst(&offshore).to_dollars()), (Money::ZERO, 4.5));
+
+        let mut reverse = doc("Consulting GmbH", "VAT reverse charge applies", 200.0, None, None);
+        assert_eq!(classify(&reverse), GstTreatment::ReverseCharge);
+        assert_eq!((reverse_charge_gst(&reverse).to_dollars(), creditable_gst(&reverse).to_dollars()), (20.0, 20.0));
+
+        reverse.gst_treatment = Some(GstTreatment::ImportedService);
+        assert_eq!(creditable_gst(&reverse), Money::ZERO);
+
+        // Vendor names only match whole words
+        assert_eq!(classify(&doc("Awsome Cafe", "", 11.0, Some(1.0), None)), GstTreatment::Standard);
+    }
+}
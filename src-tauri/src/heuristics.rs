@@ -0,0 +1,142 @@
+//! Heuristic Profile Comparison Module
+//!
+//! Parses the same document under two scoring profiles and reports where the
+//! results differ, so changes to the parser weights can be judged on real
+//! documents rather than guesswork.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::invoice::{self, DocumentType, ExtractedInvoice, InvoiceParser};
+use crate::settings::AppSettings;
+
+/// A field whose value or confidence differs between the two profiles
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FieldDifference {
+    pub field: String,
+    pub value_a: Value,
+    pub value_b: Value,
+    pub confidence_a: Option<f64>,
+    pub confidence_b: Option<f64>,
+}
+
+/// Side-by-side parse of one document under two profiles
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProfileComparison {
+    pub profile_a: String,
+    pub profile_b: String,
+    pub overall_confidence_a: f64,
+    pub overall_confidence_b: f64,
+    pub differences: Vec<FieldDifference>,
+    pub invoice_a: ExtractedInvoice,
+    pub invoice_b: ExtractedInvoice,
+}
+
+/// Text to re-parse: the stored raw text, or the original PDF
+pub fn comparison_text(invoice: Option<&ExtractedInvoice>, source_path: Option<&str>) -> Result<String, String> {
+    if let Some(text) = invoice.map(|i| i.raw_text.as_str()).filter(|t| !t.trim().is_empty()) {
+        return Ok(text.to_string());
+    }
+    let source = source_path.ok_or_else(|| "Document has no text or original file to parse".to_string())?;
+    invoice::extract_pdf_text(source)
+}
+
+/// Parse `text` under two named profiles and diff the results
+pub fn compare_profiles(
+    settings: &AppSettings,
+    text: &str,
+    profile_a: &str,
+    profile_b: &str,
+) -> Result<ProfileComparison, String> {
+    let parse = |name: &str| -> Result<ExtractedInvoice, String> {
+        let weights = settings
+            .heuristic_weights(name)
+            .ok_or_else(|| format!("Unknown heuristic profile: {}", name))?;
+        InvoiceParser::with_weights(weights)?
+            .with_skip_patterns(&settings.line_item_skip_patterns)?
+            .parse_from_text(text, DocumentType::Pdf)
+    };
+    let invoice_a = parse(profile_a)?;
+    let invoice_b = parse(profile_b)?;
+
+    Ok(ProfileComparison {
+        profile_a: profile_a.to_string(),
+        profile_b: profile_b.to_string(),
+        overall_confidence_a: invoice_a.overall_confidence,
+        overall_confidence_b: invoice_b.overall_confidence,
+        differences: diff_invoices(&invoice_a, &invoice_b),
+        invoice_a,
+        invoice_b,
+    })
+}
+
+/// Compare the extracted fields of two invoices
+pub fn diff_invoices(a: &ExtractedInvoice, b: &ExtractedInvoice) -> Vec<FieldDifference> {
+    const FIELDS: [&str; 8] = [
+        "abn",
+        "invoice_number",
+        "invoice_date",
+        "due_date",
+        "vendor_name",
+        "total_amount",
+        "gst_amount",
+        "payment_terms",
+    ];
+
+    let a = serde_json::to_value(a).unwrap_or_default();
+    let b = serde_json::to_value(b).unwrap_or_default();
+
+    let mut differences: Vec<FieldDifference> = FIELDS
+        .iter()
+        .filter_map(|&field| {
+            let (field_a, field_b) = (&a[field], &b[field]);
+            (field_a != field_b).then(|| FieldDifference {
+                field: field.to_string(),
+                value_a: field_a["value"].clone(),
+                value_b: field_b["value"].clone(),
+                confidence_a: field_a["confidence"].as_f64(),
+                confidence_b: field_b["confidence"].as_f64(),
+            })
+        })
+        .collect();
+
+    if a["line_items"] != b["line_items"] {
+        differences.push(FieldDifference {
+            field: "line_items".to_string(),
+            value_a: a["line_items"].clone(),
+            value_b: b["line_items"].clone(),
+            confidence_a: None,
+            confidence_b: None,
+        });
+    }
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invoice::{HeuristicWeights, TotalSelection};
+
+    #[test]
+    fn test_compare_profiles_reports_total_difference() {
+        let mut settings = AppSettings::default();
+        settings.heuristic_profiles.insert(
+            "labelled".to_string(),
+            HeuristicWeights {
+                total_selection: TotalSelection::LabelledFirst,
+                ..Default::default()
+            },
+        );
+
+        let text = "Acme Pty Ltd\nTotal due: $110.00\nPrevious balance $250.00";
+        let comparison = compare_profiles(&settings, text, "default", "labelled").unwrap();
+
+        assert_eq!(comparison.differences.len(), 1);
+        let diff = &comparison.differences[0];
+        assert_eq!(diff.field, "total_amount");
+        assert_eq!(diff.value_a, serde_json::json!({ "value": 250.0, "currency": "AUD" }));
+        assert_eq!(diff.value_b, serde_json::json!({ "value": 110.0, "currency": "AUD" }));
+
+        assert!(compare_profiles(&settings, text, "default", "missing").is_err());
+    }
+}
@@ -0,0 +1,409 @@
+//! Image Loading Module
+//!
+//! Safe decoding of receipt images before OCR. Enforces configurable limits
+//! on file size, dimensions and decoder allocations so a corrupt or hostile
+//! image cannot exhaust memory, and downscales oversized inputs to a
+//! workable resolution. Low-resolution receipts are upscaled for OCR.
+//!
+//! HEIC/HEIF photos (the iPhone camera default) are detected from their
+//! header and decoded with libheif when built with the `heif` feature.
+
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageReader};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Formats `load_image` can decode, for error messages
+pub fn supported_formats() -> Vec<&'static str> {
+    let mut formats = vec!["JPEG", "PNG", "WebP", "TIFF", "BMP"];
+    if cfg!(feature = "heif") {
+        formats.push("HEIC/HEIF");
+    }
+    formats
+}
+
+/// Limits applied when decoding images
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct DecodeLimits {
+    /// Largest accepted file size in bytes
+    pub max_file_bytes: u64,
+    /// Largest accepted image width in pixels
+    pub max_width: u32,
+    /// Largest accepted image height in pixels
+    pub max_height: u32,
+    /// Largest allocation the decoder may make in bytes
+    pub max_alloc_bytes: u64,
+    /// Images with a longer edge than this are downscaled after decoding
+    pub downscale_above: u32,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_file_bytes: 50 * 1024 * 1024,
+            max_width: 20_000,
+            max_height: 20_000,
+            max_alloc_bytes: 512 * 1024 * 1024,
+            downscale_above: 4_000,
+        }
+    }
+}
+
+/// Upscaling applied to low-resolution images before OCR
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct UpscaleSettings {
+    pub enabled: bool,
+    /// Resolution the image is scaled up to
+    pub target_dpi: u32,
+    /// Images estimated below this resolution are upscaled
+    pub min_dpi: u32,
+    /// Fixed scale factor, used instead of the DPI estimate when set
+    pub factor: Option<f32>,
+    /// Largest scale factor applied
+    pub max_factor: f32,
+    /// Physical width assumed for the image when estimating its resolution
+    /// (80mm thermal receipt paper by default)
+    pub assumed_width_mm: f32,
+}
+
+impl Default for UpscaleSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            target_dpi: 300,
+            min_dpi: 200,
+            factor: None,
+            max_factor: 4.0,
+            assumed_width_mm: 80.0,
+        }
+    }
+}
+
+/// Errors raised while loading an image
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub enum ImageDecodeError {
+    NotFound { path: String },
+    FileTooLarge { size: u64, limit: u64 },
+    DimensionsTooLarge { width: u32, height: u32, max_width: u32, max_height: u32 },
+    MemoryLimitExceeded { limit: u64 },
+    UnsupportedFormat { message: String },
+    Decode { message: String },
+}
+
+impl fmt::Display for ImageDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound { path } => write!(f, "Image file not found: {}", path),
+            Self::FileTooLarge { size, limit } => write!(
+                f,
+                "Image file is {} bytes, exceeding the {} byte limit",
+                size, limit
+            ),
+            Self::DimensionsTooLarge { width, height, max_width, max_height } => write!(
+                f,
+                "Image is {}x{} pixels, exceeding the {}x{} limit",
+                width, height, max_width, max_height
+            ),
+            Self::MemoryLimitExceeded { limit } => write!(
+                f,
+                "Decoding the image would exceed the {} byte memory limit",
+                limit
+            ),
+            Self::UnsupportedFormat { message } => write!(
+                f,
+                "Unsupported image format: {}. Supported formats: {}",
+                message,
+                supported_formats().join(", ")
+            ),
+            Self::Decode { message } => write!(f, "Failed to decode image: {}", message),
+        }
+    }
+}
+
+impl From<ImageDecodeError> for String {
+    fn from(error: ImageDecodeError) -> Self {
+        error.to_string()
+    }
+}
+
+impl From<image::ImageError> for ImageDecodeError {
+    fn from(error: image::ImageError) -> Self {
+        match error {
+            image::ImageError::Unsupported(e) => Self::UnsupportedFormat { message: e.to_string() },
+            e => Self::Decode { message: e.to_string() },
+        }
+    }
+}
+
+/// Decode an image from disk, enforcing the given limits
+pub fn load_image(path: &Path, limits: &DecodeLimits) -> Result<DynamicImage, ImageDecodeError> {
+    let metadata = fs::metadata(path).map_err(|_| ImageDecodeError::NotFound {
+        path: path.to_string_lossy().to_string(),
+    })?;
+    if metadata.len() > limits.max_file_bytes {
+        return Err(ImageDecodeError::FileTooLarge {
+            size: metadata.len(),
+            limit: limits.max_file_bytes,
+        });
+    }
+
+    // Check the header before allocating anything for the pixel data
+    let (width, height) = image_dimensions(path)?;
+    if width > limits.max_width || height > limits.max_height {
+        return Err(ImageDecodeError::DimensionsTooLarge {
+            width,
+            height,
+            max_width: limits.max_width,
+            max_height: limits.max_height,
+        });
+    }
+
+    if is_heif(path) {
+        return Ok(downscale_to_fit(decode_heif(path, limits)?, limits.downscale_above));
+    }
+
+    let mut reader = open_reader(path)?;
+    let mut decoder_limits = image::Limits::default();
+    decoder_limits.max_image_width = Some(limits.max_width);
+    decoder_limits.max_image_height = Some(limits.max_height);
+    decoder_limits.max_alloc = Some(limits.max_alloc_bytes);
+    reader.limits(decoder_limits);
+
+    let image = reader.decode().map_err(|e| match e {
+        image::ImageError::Limits(_) => ImageDecodeError::MemoryLimitExceeded {
+            limit: limits.max_alloc_bytes,
+        },
+        e => e.into(),
+    })?;
+
+    Ok(downscale_to_fit(image, limits.downscale_above))
+}
+
+/// Width and height of an image, read from its header
+pub fn image_dimensions(path: &Path) -> Result<(u32, u32), ImageDecodeError> {
+    if is_heif(path) {
+        return heif_dimensions(path);
+    }
+    Ok(open_reader(path)?.into_dimensions()?)
+}
+
+/// HEIF brands written by phone cameras, found in the file's `ftyp` box
+const HEIF_BRANDS: &[&[u8; 4]] = &[b"heic", b"heix", b"hevc", b"hevx", b"heim", b"heis", b"mif1", b"msf1"];
+
+/// Whether a file is a HEIC/HEIF image, judged by its header rather than
+/// its extension
+pub fn is_heif(path: &Path) -> bool {
+    let mut header = [0u8; 12];
+    let read = fs::File::open(path).and_then(|mut f| std::io::Read::read_exact(&mut f, &mut header));
+    read.is_ok() && &header[4..8] == b"ftyp" && HEIF_BRANDS.iter().any(|brand| &header[8..12] == *brand)
+}
+
+#[cfg(feature = "heif")]
+fn heif_error(error: libheif_rs::HeifError) -> ImageDecodeError {
+    ImageDecodeError::Decode { message: error.to_string() }
+}
+
+#[cfg(feature = "heif")]
+fn heif_dimensions(path: &Path) -> Result<(u32, u32), ImageDecodeError> {
+    let context = libheif_rs::HeifContext::read_from_file(&path.to_string_lossy()).map_err(heif_error)?;
+    let handle = context.primary_image_handle().map_err(heif_error)?;
+    Ok((handle.width(), handle.height()))
+}
+
+/// Decode the primary image of a HEIF file to RGB
+#[cfg(feature = "heif")]
+fn decode_heif(path: &Path, limits: &DecodeLimits) -> Result<DynamicImage, ImageDecodeError> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let context = HeifContext::read_from_file(&path.to_string_lossy()).map_err(heif_error)?;
+    let handle = context.primary_image_handle().map_err(heif_error)?;
+    let (width, height) = (handle.width(), handle.height());
+    if width as u64 * height as u64 * 3 > limits.max_alloc_bytes {
+        return Err(ImageDecodeError::MemoryLimitExceeded { limit: limits.max_alloc_bytes });
+    }
+
+    let decoded = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)
+        .map_err(heif_error)?;
+    let plane = decoded
+        .planes()
+        .interleaved
+        .ok_or_else(|| ImageDecodeError::Decode { message: "HEIF image has no RGB plane".to_string() })?;
+
+    // Rows may be padded, so copy them one at a time
+    let row_bytes = width as usize * 3;
+    let mut pixels = Vec::with_capacity(row_bytes * height as usize);
+    for row in plane.data.chunks(plane.stride).take(height as usize) {
+        pixels.extend_from_slice(&row[..row_bytes]);
+    }
+    image::RgbImage::from_raw(width, height, pixels)
+        .map(DynamicImage::ImageRgb8)
+        .ok_or_else(|| ImageDecodeError::Decode { message: "HEIF image data is truncated".to_string() })
+}
+
+#[cfg(not(feature = "heif"))]
+fn heif_unsupported() -> ImageDecodeError {
+    ImageDecodeError::UnsupportedFormat {
+        message: "HEIC/HEIF needs the heif feature; export the photo as JPEG".to_string(),
+    }
+}
+
+#[cfg(not(feature = "heif"))]
+fn heif_dimensions(_path: &Path) -> Result<(u32, u32), ImageDecodeError> {
+    Err(heif_unsupported())
+}
+
+#[cfg(not(feature = "heif"))]
+fn decode_heif(_path: &Path, _limits: &DecodeLimits) -> Result<DynamicImage, ImageDecodeError> {
+    Err(heif_unsupported())
+}
+
+fn open_reader(path: &Path) -> Result<ImageReader<std::io::BufReader<fs::File>>, ImageDecodeError> {
+    ImageReader::open(path)
+        .and_then(|r| r.with_guessed_format())
+        .map_err(|e| ImageDecodeError::Decode { message: e.to_string() })
+}
+
+/// Downscale an image so its longer edge is at most `max_edge` pixels
+pub fn downscale_to_fit(image: DynamicImage, max_edge: u32) -> DynamicImage {
+    if max_edge == 0 || image.width().max(image.height()) <= max_edge {
+        return image;
+    }
+    image.resize(max_edge, max_edge, FilterType::Triangle)
+}
+
+/// Downscale an image so it has at most `max_pixels` pixels
+pub fn downscale_to_pixels(image: DynamicImage, max_pixels: u64) -> DynamicImage {
+    let pixels = image.width() as u64 * image.height() as u64;
+    if max_pixels == 0 || pixels <= max_pixels {
+        return image;
+    }
+    let scale = (max_pixels as f64 / pixels as f64).sqrt();
+    let width = ((image.width() as f64 * scale).floor() as u32).max(1);
+    let height = ((image.height() as f64 * scale).floor() as u32).max(1);
+    image.resize_exact(width, height, FilterType::Triangle)
+}
+
+/// Estimated resolution of an image spanning `width_mm` of paper
+pub fn estimate_dpi(image: &DynamicImage, width_mm: f32) -> f32 {
+    if width_mm <= 0.0 {
+        return 0.0;
+    }
+    image.width() as f32 / (width_mm / 25.4)
+}
+
+/// Scale factor to apply before OCR, or `None` when no upscaling is needed
+pub fn upscale_factor(image: &DynamicImage, settings: &UpscaleSettings) -> Option<f32> {
+    if !settings.enabled {
+        return None;
+    }
+
+    let factor = match settings.factor {
+        Some(factor) => factor,
+        None => {
+            let dpi = estimate_dpi(image, settings.assumed_width_mm);
+            if dpi <= 0.0 || dpi >= settings.min_dpi as f32 {
+                return None;
+            }
+            settings.target_dpi as f32 / dpi
+        }
+    };
+
+    let factor = factor.min(settings.max_factor);
+    (factor > 1.0).then_some(factor)
+}
+
+/// Upscale a low-resolution image with Lanczos resampling for OCR
+pub fn upscale_for_ocr(image: DynamicImage, settings: &UpscaleSettings) -> DynamicImage {
+    let Some(factor) = upscale_factor(&image, settings) else {
+        return image;
+    };
+    let width = (image.width() as f32 * factor).round() as u32;
+    let height = (image.height() as f32 * factor).round() as u32;
+    image.resize_exact(width, height, FilterType::Lanczos3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage;
+
+    fn write_test_png(dir: &Path, width: u32, height: u32) -> std::path::PathBuf {
+        let path = dir.join("receipt.png");
+        DynamicImage::new_luma8(width, height).save(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_rejects_oversized_dimensions() {
+        let dir = storage::test_dir();
+        let path = write_test_png(&dir, 300, 200);
+        let limits = DecodeLimits {
+            max_width: 100,
+            ..Default::default()
+        };
+
+        let err = load_image(&path, &limits).unwrap_err();
+        assert!(matches!(err, ImageDecodeError::DimensionsTooLarge { width: 300, .. }));
+    }
+
+    #[test]
+    fn test_downscales_large_images() {
+        let dir = storage::test_dir();
+        let path = write_test_png(&dir, 800, 400);
+        let limits = DecodeLimits {
+            downscale_above: 200,
+            ..Default::default()
+        };
+
+        let image = load_image(&path, &limits).unwrap();
+        assert_eq!((image.width(), image.height()), (200, 100));
+    }
+
+    #[test]
+    fn test_upscales_low_resolution_receipts() {
+        let settings = UpscaleSettings::default();
+
+        // 80mm at ~150 DPI is upscaled to 300 DPI
+        let small = DynamicImage::new_luma8(472, 1000);
+        let upscaled = upscale_for_ocr(small, &settings);
+        assert_eq!(upscaled.width(), 945);
+
+        // Already above the threshold
+        let sharp = DynamicImage::new_luma8(945, 2000);
+        assert!(upscale_factor(&sharp, &settings).is_none());
+
+        // Fixed factors are capped at the maximum
+        let fixed = UpscaleSettings {
+            factor: Some(10.0),
+            ..Default::default()
+        };
+        assert_eq!(upscale_factor(&sharp, &fixed), Some(4.0));
+    }
+
+    #[test]
+    fn test_detects_heif_by_header() {
+        let dir = storage::test_dir();
+        // Named .jpg, as iPhones sometimes share them, but the header says HEIC
+        let path = dir.join("IMG_0001.jpg");
+        fs::write(&path, b"\x00\x00\x00\x18ftypheic\x00\x00\x00\x00mif1heic").unwrap();
+        assert!(is_heif(&path));
+        assert!(!is_heif(&write_test_png(&dir, 4, 4)));
+
+        #[cfg(not(feature = "heif"))]
+        {
+            let message = load_image(&path, &DecodeLimits::default()).unwrap_err().to_string();
+            assert!(message.contains("HEIC") && message.contains("JPEG, PNG"), "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let err = load_image(Path::new("/nonexistent/receipt.jpg"), &DecodeLimits::default());
+        assert!(matches!(err, Err(ImageDecodeError::NotFound { .. })));
+    }
+}
@@ -4,21 +4,45 @@
 //! - PDF text extraction
 //! - OCR for image-based invoices  
 //! - Australian Business Number (ABN) extraction
+//! - Australian Company Number (ACN) extraction, checked against the ABN
 //! - Invoice number parsing
 //! - Line item extraction
 //! - Payment terms identification
+//! - Bank transfer, PayID and BPAY payment details
+//! - User-defined patterns, tried before the built-in ones
 
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
 use std::path::Path;
 use regex::Regex;
 
+use crate::address::{self, Address};
+use crate::currency::{self, CurrencyAmount};
+use crate::custom_patterns::{self, CompiledPattern};
+use crate::dates::{self, DateOrder};
+use crate::money::Money;
+use crate::invoice_table;
+use crate::text_normalize;
+
+/// Labels that open the recipient block
+const BILL_TO_LABELS: [&str; 5] = ["bill to", "billed to", "invoice to", "sold to", "customer"];
+
 /// Extracted invoice data
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ExtractedInvoice {
     /// Australian Business Number
     pub abn: Option<ExtractedField<String>>,
+    /// Australian Company Number issued by ASIC
+    #[serde(default)]
+    pub acn: Option<ExtractedField<String>>,
     /// Invoice number
     pub invoice_number: Option<ExtractedField<String>>,
+    /// The customer's purchase order number ("PO #", "Your ref")
+    #[serde(default)]
+    pub purchase_order_number: Option<ExtractedField<String>>,
+    /// Number of the quote or estimate the invoice follows
+    #[serde(default)]
+    pub quote_number: Option<ExtractedField<String>>,
     /// Invoice date
     pub invoice_date: Option<ExtractedField<String>>,
     /// Due date
@@ -26,19 +50,184 @@ pub struct ExtractedInvoice {
     /// Vendor/Business name
     pub vendor_name: Option<ExtractedField<String>>,
     /// Total amount
-    pub total_amount: Option<ExtractedField<f64>>,
+    pub total_amount: Option<ExtractedField<CurrencyAmount>>,
     /// GST amount
-    pub gst_amount: Option<ExtractedField<f64>>,
+    pub gst_amount: Option<ExtractedField<CurrencyAmount>>,
+    /// Amount before GST, from a "Subtotal" or "Total ex GST" line
+    #[serde(default)]
+    pub subtotal_amount: Option<ExtractedField<CurrencyAmount>>,
+    /// ISO 4217 code of the currency printed on the invoice; `None` when
+    /// none is marked and the amounts are taken to be Australian dollars
+    #[serde(default)]
+    pub currency: Option<ExtractedField<String>>,
     /// Payment terms
     pub payment_terms: Option<ExtractedField<String>>,
+    /// Recipient name from the "Bill To" block
+    #[serde(default)]
+    pub bill_to_name: Option<ExtractedField<String>>,
+    /// Recipient ABN from the "Bill To" block
+    #[serde(default)]
+    pub bill_to_abn: Option<ExtractedField<String>>,
+    /// Vendor address from the letterhead
+    #[serde(default)]
+    pub vendor_address: Option<ExtractedField<Address>>,
+    /// Vendor email, phone and address
+    #[serde(default)]
+    pub vendor_contact: Option<VendorContact>,
     /// Line items
     pub line_items: Vec<LineItem>,
+    /// Bank account, PayID or BPAY details for paying the invoice
+    #[serde(default)]
+    pub payment_details: Option<PaymentDetails>,
+    /// Pages in the source PDF; 0 when parsed from plain text
+    #[serde(default)]
+    pub page_count: u32,
     /// Raw extracted text
     pub raw_text: String,
     /// Overall confidence score (0.0 - 1.0)
     pub overall_confidence: f64,
     /// Document type
     pub document_type: DocumentType,
+    /// What the document says it is; credit notes and decreasing adjustment
+    /// notes carry negative amounts
+    #[serde(default)]
+    pub subtype: DocumentSubtype,
+    /// Fields read by the user's own patterns that the invoice has no place
+    /// for, such as a supplier's job number, by field name
+    #[serde(default)]
+    pub custom_fields: BTreeMap<String, ExtractedField<String>>,
+}
+
+impl ExtractedInvoice {
+    /// ISO 4217 code the amounts are in
+    pub fn currency_code(&self) -> &str {
+        self.currency.as_ref().map_or(currency::HOME_CURRENCY, |field| field.value.as_str())
+    }
+
+    pub fn is_foreign_currency(&self) -> bool {
+        self.currency_code() != currency::HOME_CURRENCY
+    }
+
+    /// Whether the document reduces the amount owed: a credit note, or an
+    /// adjustment note that says it records a decrease. An increasing
+    /// adjustment note keeps the sign it was printed with.
+    pub fn is_credit(&self) -> bool {
+        match self.subtype {
+            DocumentSubtype::CreditNote => true,
+            DocumentSubtype::AdjustmentNote => is_decreasing_adjustment(&self.raw_text),
+            _ => false,
+        }
+    }
+
+    /// Make the total, GST and line items of a credit note or decreasing
+    /// adjustment note negative. Amounts already negative are left alone, so
+    /// this can run again after fields are merged or re-read.
+    pub fn apply_subtype_sign(&mut self) {
+        if !self.is_credit() {
+            return;
+        }
+        for field in [&mut self.total_amount, &mut self.gst_amount, &mut self.subtotal_amount].into_iter().flatten() {
+            field.value.value = -field.value.value.abs();
+        }
+        for item in &mut self.line_items {
+            item.total = -item.total.abs();
+            item.unit_price = item.unit_price.map(|price| -price.abs());
+            item.gst = item.gst.map(|gst| -gst.abs());
+        }
+    }
+}
+
+/// How to pay an invoice, as printed in its remittance section
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct PaymentDetails {
+    /// Bank-State-Branch number as `XXX-XXX`
+    pub bsb: Option<ExtractedField<String>>,
+    /// Digits only
+    pub account_number: Option<ExtractedField<String>>,
+    pub account_name: Option<ExtractedField<String>>,
+    /// Email, phone number or ABN registered as a PayID
+    pub payid: Option<ExtractedField<String>>,
+    pub bpay_biller_code: Option<ExtractedField<String>>,
+    /// Customer reference number to quote with the biller code
+    pub bpay_reference: Option<ExtractedField<String>>,
+}
+
+impl PaymentDetails {
+    fn is_empty(&self) -> bool {
+        self.bsb.is_none()
+            && self.account_number.is_none()
+            && self.account_name.is_none()
+            && self.payid.is_none()
+            && self.bpay_biller_code.is_none()
+    }
+}
+
+/// How to reach the vendor, for completing the vendor record
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct VendorContact {
+    pub email: Option<ExtractedField<String>>,
+    /// Digits only, in national form (`0298765432`, `1300123456`)
+    pub phone: Option<ExtractedField<String>>,
+    /// Street or postal address from the letterhead
+    pub address: Option<ExtractedField<Address>>,
+}
+
+impl VendorContact {
+    fn is_empty(&self) -> bool {
+        self.email.is_none() && self.phone.is_none() && self.address.is_none()
+    }
+}
+
+/// Regexes for the vendor's contact details
+struct ContactPatterns {
+    email: Regex,
+    phone: Regex,
+    email_label: Regex,
+    phone_label: Regex,
+    /// Ends the text before a fax number
+    fax_label: Regex,
+    /// Lines whose numbers and emails belong to payment details or registrations
+    excluded_line: Regex,
+}
+
+impl ContactPatterns {
+    fn new() -> Result<Self, String> {
+        let compile = |pattern: &str| Regex::new(pattern).map_err(|e| e.to_string());
+        Ok(Self {
+            email: compile(r"\b[\w.+-]+@[\w-]+(?:\.[\w-]+)+\b")?,
+            phone: compile(r"(?:\+61[\s-]?\(?0?\)?[\s-]?[2-478]|\(?\b0[2-478]\)?|\b1[38]00)(?:[\s-]?\d){6,8}\b")?,
+            email_label: compile(r"(?i)\b(?:e-?mail|e)\s*:")?,
+            phone_label: compile(r"(?i)\b(?:phone|ph|tel|telephone|mob(?:ile)?|m|p|t|call)\s*:")?,
+            fax_label: compile(r"(?i)\bfax\s*:?\s*$")?,
+            excluded_line: compile(r"(?i)\b(?:pay\s*id|bsb|account|acc|a/c|biller|crn|abn|acn)\b")?,
+        })
+    }
+}
+
+/// Regexes for the remittance section of an invoice
+struct PaymentPatterns {
+    bsb: Regex,
+    account_number: Regex,
+    account_name: Regex,
+    payid: Regex,
+    biller_code: Regex,
+    bpay_reference: Regex,
+}
+
+impl PaymentPatterns {
+    fn new() -> Result<Self, String> {
+        let compile = |pattern: &str| Regex::new(pattern).map_err(|e| e.to_string());
+        Ok(Self {
+            bsb: compile(r"(?i)\bbsb(?:\s*(?:no\.?|number))?[:\s#]*(\d{3})[-\s]?(\d{3})\b")?,
+            account_number: compile(r"(?i)\b(?:account|acc|a/c)\s*(?:no\.?|number|#)[:\s#]*(\d(?:[\d -]{3,12})\d)\b")?,
+            account_name: compile(r"(?i)\b(?:account|acc|a/c)\s*name[:\s]*([^\n]+)")?,
+            payid: compile(r"(?i)\bpay\s*id[:\s]*([\w.+-]+@[\w-]+(?:\.[\w-]+)+|(?:\+?61|0)[\d ]{8,12}\d)")?,
+            biller_code: compile(r"(?i)\bbiller\s*code[:\s#]*(\d{3,10})\b")?,
+            bpay_reference: compile(r"(?i)\b(?:ref(?:erence)?|crn|customer\s*reference(?:\s*number)?)(?:\s*(?:no\.?|number))?[:\s#]*(\d{2,20})\b")?,
+        })
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -46,6 +235,15 @@ pub struct ExtractedField<T> {
     pub value: T,
     pub confidence: f64,
     pub source: String,
+    /// 1-based page of a multi-page PDF the value was found on
+    #[serde(default)]
+    pub page: Option<u32>,
+    /// Text as printed, when `value` was normalized from it
+    #[serde(default)]
+    pub raw: Option<String>,
+    /// The printed date could be read day-first or month-first
+    #[serde(default)]
+    pub ambiguous: bool,
 }
 
 impl<T> ExtractedField<T> {
@@ -54,17 +252,46 @@ impl<T> ExtractedField<T> {
             value,
             confidence,
             source: source.to_string(),
+            page: None,
+            raw: None,
+            ambiguous: false,
+        }
+    }
+
+    /// The same field with its value converted
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> ExtractedField<U> {
+        ExtractedField {
+            value: f(self.value),
+            confidence: self.confidence,
+            source: self.source,
+            page: self.page,
+            raw: self.raw,
+            ambiguous: self.ambiguous,
         }
     }
 }
 
+/// An amount read as a bare number, in the invoice's currency
+fn in_currency(field: Option<ExtractedField<f64>>, code: &str) -> Option<ExtractedField<CurrencyAmount>> {
+    field.map(|field| field.map(|value| CurrencyAmount::new(Money::from_dollars(value), code)))
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct LineItem {
     pub description: String,
     pub quantity: Option<f64>,
-    pub unit_price: Option<f64>,
-    pub total: f64,
+    pub unit_price: Option<Money>,
+    pub total: Money,
     pub confidence: f64,
+    /// 1-based page the item is listed on
+    #[serde(default)]
+    pub page: Option<u32>,
+    /// GST on the line, from a GST amount column
+    #[serde(default)]
+    pub gst: Option<Money>,
+    /// Tax code printed on the line, such as `GST`, `FRE` or `N-T`
+    #[serde(default)]
+    pub gst_code: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -75,18 +302,188 @@ pub enum DocumentType {
     Image,
 }
 
+/// Kind of document, from the title printed in its header
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentSubtype {
+    #[default]
+    TaxInvoice,
+    CreditNote,
+    /// Adjustment note correcting the GST on an earlier tax invoice
+    AdjustmentNote,
+    Receipt,
+    Statement,
+    Quote,
+}
+
+/// Words marking an adjustment note as reducing the original invoice
+const DECREASING_ADJUSTMENT_WORDS: [&str; 5] = ["decreasing", "decrease", "reduction", "refund", "returned"];
+
+/// Whether an adjustment note's text says it records a decrease
+fn is_decreasing_adjustment(text: &str) -> bool {
+    let text = text.to_lowercase();
+    DECREASING_ADJUSTMENT_WORDS.iter().any(|word| text.contains(word))
+}
+
+/// Header lines checked for a document title
+const TITLE_LINES: usize = 15;
+
+/// Words after a title keyword that make it a field label ("Invoice To",
+/// "Quote No.") rather than the document's title
+const TITLE_LABEL_WORDS: [&str; 12] =
+    ["to", "date", "due", "valid", "period", "ref", "no", "no.", "number", "#", "total", "terms"];
+
+/// Classify a document from its header. A credit or adjustment note title
+/// anywhere in the header wins, since these are often printed beside "Tax
+/// Invoice"; otherwise the first title line decides.
+pub fn detect_subtype(text: &str) -> DocumentSubtype {
+    let header: Vec<String> = text
+        .lines()
+        .map(|line| line.trim().to_lowercase())
+        .filter(|line| !line.is_empty())
+        .take(TITLE_LINES)
+        .collect();
+    if header.iter().any(|line| line.contains("adjustment note")) {
+        return DocumentSubtype::AdjustmentNote;
+    }
+    if header.iter().any(|line| line.contains("credit note") || line.contains("credit memo")) {
+        return DocumentSubtype::CreditNote;
+    }
+
+    const TITLES: [(&str, DocumentSubtype); 8] = [
+        ("tax invoice", DocumentSubtype::TaxInvoice),
+        ("invoice", DocumentSubtype::TaxInvoice),
+        ("tax receipt", DocumentSubtype::Receipt),
+        ("receipt", DocumentSubtype::Receipt),
+        ("statement", DocumentSubtype::Statement),
+        ("quotation", DocumentSubtype::Quote),
+        ("quote", DocumentSubtype::Quote),
+        ("estimate", DocumentSubtype::Quote),
+    ];
+    header
+        .iter()
+        .find_map(|line| {
+            TITLES.iter().find_map(|(title, subtype)| {
+                let rest = line.strip_prefix(title)?;
+                if rest.starts_with(|c: char| c.is_alphanumeric()) {
+                    return None;
+                }
+                let next = rest.trim_start_matches([':', '-', ' ']).split_whitespace().next().unwrap_or("");
+                let next = next.trim_end_matches(':');
+                (!TITLE_LABEL_WORDS.contains(&next)).then_some(*subtype)
+            })
+        })
+        .unwrap_or_default()
+}
+
+/// How the invoice total is chosen among extracted amounts
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TotalSelection {
+    /// The largest amount on the document
+    #[default]
+    Largest,
+    /// An amount labelled as the total (e.g. "Total due"), else the largest
+    LabelledFirst,
+}
+
+/// Scoring constants used by the parser heuristics.
+///
+/// Saved as named profiles in settings so they can be tuned and compared.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct HeuristicWeights {
+    pub abn_confidence: f64,
+    pub invoice_number_confidence: f64,
+    pub date_confidence: f64,
+    /// Vendor lines containing a business suffix such as "Pty Ltd"
+    pub vendor_suffix_confidence: f64,
+    pub vendor_plain_confidence: f64,
+    pub amount_confidence: f64,
+    pub payment_terms_confidence: f64,
+    pub line_item_with_quantity_confidence: f64,
+    pub line_item_confidence: f64,
+    /// Line items read from a table under a recognised header row
+    pub table_line_item_confidence: f64,
+    /// Added to overall confidence when ABN, invoice number and total are all found
+    pub key_fields_boost: f64,
+    /// Largest fraction of the total an amount may be to count as GST
+    pub gst_max_ratio: f64,
+    pub total_selection: TotalSelection,
+}
+
+impl Default for HeuristicWeights {
+    fn default() -> Self {
+        Self {
+            abn_confidence: 0.90,
+            invoice_number_confidence: 0.85,
+            date_confidence: 0.80,
+            vendor_suffix_confidence: 0.90,
+            vendor_plain_confidence: 0.70,
+            amount_confidence: 0.75,
+            payment_terms_confidence: 0.75,
+            line_item_with_quantity_confidence: 0.70,
+            line_item_confidence: 0.50,
+            table_line_item_confidence: 0.85,
+            key_fields_boost: 0.1,
+            gst_max_ratio: 0.2,
+            total_selection: TotalSelection::Largest,
+        }
+    }
+}
+
+/// Lines that carry an amount but are never line items: page numbers,
+/// payment and bank details, and the summary lines (subtotal, GST, total)
+const DEFAULT_LINE_ITEM_SKIP_PATTERNS: &[&str] = &[
+    r"(?i)\bpage\s+\d+\s*(?:of|/)\s*\d+\b",
+    r"(?i)\b(?:bsb|account\s*(?:no|number|name)|acc(?:ount)?\s*#|swift|iban|bpay|biller\s*code|crn|bank\s*(?:name|details|account))\b",
+    r"(?i)^\s*(?:sub\s*-?\s*total|total|amount\s*(?:due|paid|payable)|balance(?:\s*due)?|less\s*paid|payments?\s*received)\b",
+    r"(?i)^\s*(?:gst|tax)\b(?:\s*@?\s*\(?10\s*%\)?|\s*amount|\s*total)?\s*[:$]?\s*[\d,]+\.\d{2}",
+    r"(?i)\b(?:total|gst)\s*\(?(?:inc|incl|ex|excl)\b",
+    r"(?i)\b(?:abn|acn|invoice\s*(?:date|no|number|#)|due\s*date|phone|ph|tel|fax)\b",
+];
+
+/// Compile line-item skip patterns, naming the first invalid one
+pub fn compile_skip_patterns(patterns: &[String]) -> Result<Vec<Regex>, String> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern).map_err(|e| format!("Invalid line item skip pattern '{}': {}", pattern, e))
+        })
+        .collect()
+}
+
 /// Invoice parser for extracting structured data from documents
 pub struct InvoiceParser {
     /// Regex patterns for ABN validation and extraction
     abn_patterns: Vec<Regex>,
+    /// Regex patterns for ACN extraction
+    acn_patterns: Vec<Regex>,
     /// Regex patterns for invoice numbers
     invoice_number_patterns: Vec<Regex>,
+    /// Regex patterns for purchase order numbers
+    purchase_order_patterns: Vec<Regex>,
+    /// Regex patterns for quote and estimate numbers
+    quote_number_patterns: Vec<Regex>,
     /// Regex patterns for dates
     date_patterns: Vec<Regex>,
     /// Regex patterns for amounts
     amount_patterns: Vec<Regex>,
+    /// Regex patterns for the amount before GST
+    subtotal_patterns: Vec<Regex>,
     /// Regex patterns for payment terms
     payment_terms_patterns: Vec<Regex>,
+    /// Regex patterns for bank, PayID and BPAY details
+    payment_patterns: PaymentPatterns,
+    /// Regex patterns for vendor email and phone numbers
+    contact_patterns: ContactPatterns,
+    /// Lines matching any of these are never line items
+    line_item_skip_patterns: Vec<Regex>,
+    /// The user's patterns, whose matches replace the built-in ones
+    custom_patterns: Vec<CompiledPattern>,
+    /// Scoring constants
+    weights: HeuristicWeights,
+    /// Reading of numeric dates that could be day-first or month-first
+    date_order: DateOrder,
 }
 
 impl InvoiceParser {
@@ -99,6 +496,11 @@ impl InvoiceParser {
             Regex::new(r"\b(\d{11})\b").map_err(|e| e.to_string())?,
         ];
 
+        // ACN pattern: 9 digits, usually grouped in threes
+        let acn_patterns = vec![
+            Regex::new(r"(?i)\b(?:acn|a\.c\.n\.?|australian company number)[:\s]*(\d{3}\s*\d{3}\s*\d{3})\b").map_err(|e| e.to_string())?,
+        ];
+
         // Invoice number patterns
         let invoice_number_patterns = vec![
             Regex::new(r"(?i)(?:invoice\s*(?:#|no\.?|number)?|inv\.?|tax\s*invoice)[:\s#]*(\w[\w\-]*)").map_err(|e| e.to_string())?,
@@ -106,6 +508,16 @@ impl InvoiceParser {
             Regex::new(r"(?i)(?:reference|ref)[:\s#]*(INV[\w\-]*)").map_err(|e| e.to_string())?,
         ];
 
+        // Purchase order and quote patterns; matches without a digit are skipped
+        let purchase_order_patterns = vec![
+            Regex::new(r"(?i)\bpurchase\s*order(?:\s*(?:#|no\.?|number))?[:\s#]*(\w[\w/\-]*)").map_err(|e| e.to_string())?,
+            Regex::new(r"(?i)\bp\.?\s?o\.?\s*(?:#|no\.?|number)[:\s#]*(\w[\w/\-]*)").map_err(|e| e.to_string())?,
+            Regex::new(r"(?i)\byour\s*(?:ref(?:erence)?|order(?:\s*(?:#|no\.?|number))?)[:\s#]*(\w[\w/\-]*)").map_err(|e| e.to_string())?,
+        ];
+        let quote_number_patterns = vec![
+            Regex::new(r"(?i)\b(?:quote|quotation|estimate)\s*(?:#|no\.?|number|ref(?:erence)?)?[:\s#]*(\w[\w/\-]*)").map_err(|e| e.to_string())?,
+        ];
+
         // Date patterns (Australian format preferred: DD/MM/YYYY, but also accept ISO)
         let date_patterns = vec![
             Regex::new(r"(?i)(?:invoice\s*date|date)[:\s]*(\d{1,2}[/-]\d{1,2}[/-]\d{2,4})").map_err(|e| e.to_string())?,
@@ -115,13 +527,19 @@ impl InvoiceParser {
             Regex::new(r"\b(\d{4}-\d{2}-\d{2})\b").map_err(|e| e.to_string())?,
         ];
 
-        // Amount patterns
+        // Amount patterns, allowing a currency code or symbol before the amount
+        let prefix = currency::amount_prefix();
         let amount_patterns = vec![
-            Regex::new(r"(?i)(?:total\s*amount|total\s*due|amount\s*due|total\s*\(inc\.?\s*gst\)|total\s*\(gst\s*inc\.?\)|grand\s*total)[:\s]*[$€£]?\s*([\d,]+\.\d{2})").map_err(|e| e.to_string())?,
-            Regex::new(r"(?i)(?:total)[:\s]*[$€£]?\s*([\d,]+\.\d{2})").map_err(|e| e.to_string())?,
-            Regex::new(r"(?i)(?:gst|tax)[:\s]*[$€£]?\s*([\d,]+\.\d{2})").map_err(|e| e.to_string())?,
-            Regex::new(r"(?i)(?:balance\s*due)[:\s]*[$€£]?\s*([\d,]+\.\d{2})").map_err(|e| e.to_string())?,
-            Regex::new(r"[$€£]\s*([\d,]+\.\d{2})").map_err(|e| e.to_string())?,
+            Regex::new(&format!(r"(?i)(?:total\s*amount|total\s*due|amount\s*due|total\s*\(inc\.?\s*gst\)|total\s*\(gst\s*inc\.?\)|grand\s*total)[:\s]*{}[$€£¥]?\s*([\d,]+\.\d{{2}})", prefix)).map_err(|e| e.to_string())?,
+            Regex::new(&format!(r"(?i)(?:total)[:\s]*{}[$€£¥]?\s*([\d,]+\.\d{{2}})", prefix)).map_err(|e| e.to_string())?,
+            Regex::new(&format!(r"(?i)(?:gst|tax)[:\s]*{}[$€£¥]?\s*([\d,]+\.\d{{2}})", prefix)).map_err(|e| e.to_string())?,
+            Regex::new(&format!(r"(?i)(?:balance\s*due)[:\s]*{}[$€£¥]?\s*([\d,]+\.\d{{2}})", prefix)).map_err(|e| e.to_string())?,
+            Regex::new(r"[$€£¥]\s*([\d,]+\.\d{2})").map_err(|e| e.to_string())?,
+            Regex::new(&format!(r"\b(?:{})\s*([\d,]+\.\d{{2}})", currency::ISO_CODES)).map_err(|e| e.to_string())?,
+        ];
+        let subtotal_patterns = vec![
+            Regex::new(&format!(r"(?i)\bsub\s*-?\s*total[:\s]*{}[$€£¥]?\s*([\d,]+\.\d{{2}})", prefix)).map_err(|e| e.to_string())?,
+            Regex::new(&format!(r"(?i)\b(?:total|amount)\s*\(?(?:ex|excl|excluding)\.?\s*gst\)?[:\s]*{}[$€£¥]?\s*([\d,]+\.\d{{2}})", prefix)).map_err(|e| e.to_string())?,
         ];
 
         // Payment terms patterns
@@ -134,18 +552,73 @@ impl InvoiceParser {
             Regex::new(r"(?i)(?:14|30|60|90)\s*days?").map_err(|e| e.to_string())?,
         ];
 
+        let line_item_skip_patterns = DEFAULT_LINE_ITEM_SKIP_PATTERNS
+            .iter()
+            .map(|pattern| Regex::new(pattern).map_err(|e| e.to_string()))
+            .collect::<Result<_, _>>()?;
+
         Ok(Self {
             abn_patterns,
+            acn_patterns,
             invoice_number_patterns,
+            purchase_order_patterns,
+            quote_number_patterns,
             date_patterns,
             amount_patterns,
+            subtotal_patterns,
             payment_terms_patterns,
+            payment_patterns: PaymentPatterns::new()?,
+            contact_patterns: ContactPatterns::new()?,
+            line_item_skip_patterns,
+            custom_patterns: Vec::new(),
+            weights: HeuristicWeights::default(),
+            date_order: DateOrder::default(),
+        })
+    }
+
+    /// Set how ambiguous numeric dates such as 03/04/2024 are read
+    pub fn with_date_order(mut self, order: DateOrder) -> Self {
+        self.date_order = order;
+        self
+    }
+
+    /// Skip lines matching user-defined patterns as well as the built-in ones
+    pub fn with_skip_patterns(mut self, patterns: &[String]) -> Result<Self, String> {
+        self.line_item_skip_patterns.extend(compile_skip_patterns(patterns)?);
+        Ok(self)
+    }
+
+    /// Fill fields from the user's patterns in preference to the built-in ones
+    pub fn with_custom_patterns(mut self, patterns: Vec<CompiledPattern>) -> Self {
+        self.custom_patterns = patterns;
+        self
+    }
+
+    /// Apply the user's patterns again after later steps, such as vendor
+    /// templates and the layout model, have replaced fields, so a pattern's
+    /// match still wins
+    pub fn apply_custom_patterns(&self, text: &str, invoice: &mut ExtractedInvoice) {
+        custom_patterns::apply(&self.custom_patterns, text, invoice, self.date_order);
+        invoice.apply_subtype_sign();
+    }
+
+    /// Whether a line is known not to be a line item
+    pub fn is_non_item_line(&self, line: &str) -> bool {
+        self.line_item_skip_patterns.iter().any(|pattern| pattern.is_match(line))
+    }
+
+    /// Create a parser using the given scoring constants
+    pub fn with_weights(weights: HeuristicWeights) -> Result<Self, String> {
+        Ok(Self {
+            weights,
+            ..Self::new()?
         })
     }
 
     /// Parse an invoice from text content
     pub fn parse_from_text(&self, text: &str, document_type: DocumentType) -> Result<ExtractedInvoice, String> {
-        let text = text.trim();
+        let normalized = text_normalize::normalize(text);
+        let text = normalized.trim();
         
         if text.is_empty() {
             return Err("Empty text content".to_string());
@@ -166,6 +639,9 @@ impl InvoiceParser {
         if let Some(inv_num) = self.extract_invoice_number(text) {
             invoice.invoice_number = Some(inv_num);
         }
+        invoice.purchase_order_number =
+            self.extract_reference(text, &self.purchase_order_patterns, "purchase_order_regex");
+        invoice.quote_number = self.extract_reference(text, &self.quote_number_patterns, "quote_number_regex");
 
         // Extract dates
         let dates = self.extract_dates(text);
@@ -181,35 +657,119 @@ impl InvoiceParser {
             invoice.vendor_name = Some(vendor);
         }
 
-        // Extract amounts
-        let amounts = self.extract_amounts(text);
-        if !amounts.is_empty() {
-            // The largest amount is likely the total
-            invoice.total_amount = Some(amounts[0].clone());
-            
-            // Look for GST amount in remaining amounts
-            for amount in &amounts[1..] {
-                if text.to_lowercase().contains("gst") && amount.value < amounts[0].value * 0.2 {
-                    invoice.gst_amount = Some(amount.clone());
-                    break;
-                }
+        // Extract amounts in the currency they are printed in
+        invoice.currency = currency::detect_currency(text);
+        let code = invoice.currency_code().to_string();
+        let (total, gst) = self.extract_total_and_gst(text);
+        invoice.total_amount = in_currency(total, &code);
+        invoice.gst_amount = in_currency(gst, &code);
+        invoice.subtotal_amount = in_currency(self.extract_subtotal(text), &code);
+
+        // Extract the recipient, making sure their ABN isn't taken as the vendor's
+        let (bill_to_name, bill_to_abn) = self.extract_bill_to(text);
+        if let (Some(vendor_abn), Some(recipient_abn)) = (&invoice.abn, &bill_to_abn) {
+            if vendor_abn.value == recipient_abn.value {
+                invoice.abn = self.extract_abn_excluding(text, &recipient_abn.value);
             }
         }
+        invoice.acn = self.extract_acn(text, invoice.abn.as_ref().map(|abn| abn.value.as_str()));
+        invoice.bill_to_name = bill_to_name;
+        invoice.bill_to_abn = bill_to_abn;
+        invoice.vendor_address = self.extract_vendor_address(text);
+        invoice.vendor_contact = self.extract_vendor_contact(text, invoice.vendor_address.clone());
 
         // Extract payment terms
         if let Some(terms) = self.extract_payment_terms(text) {
             invoice.payment_terms = Some(terms);
         }
+        invoice.payment_details = self.extract_payment_details(text);
 
         // Extract line items
         invoice.line_items = self.extract_line_items(text);
 
+        custom_patterns::apply(&self.custom_patterns, text, &mut invoice, self.date_order);
+
+        invoice.subtype = detect_subtype(text);
+        invoice.apply_subtype_sign();
+
         // Calculate overall confidence
         invoice.overall_confidence = self.calculate_confidence(&invoice);
 
         Ok(invoice)
     }
 
+    /// Total and GST among the amounts in `text`
+    fn extract_total_and_gst(&self, text: &str) -> (Option<ExtractedField<f64>>, Option<ExtractedField<f64>>) {
+        let amounts = self.extract_amounts(text);
+        if amounts.is_empty() {
+            return (None, None);
+        }
+        // The largest amount is likely the total, unless one is labelled
+        let total = match self.weights.total_selection {
+            TotalSelection::Largest => amounts[0].clone(),
+            TotalSelection::LabelledFirst => self.extract_labelled_total(text).unwrap_or_else(|| amounts[0].clone()),
+        };
+
+        // Look for GST amount in remaining amounts
+        let mentions_gst = text.to_lowercase().contains("gst");
+        let gst = amounts
+            .iter()
+            .filter(|a| a.value != total.value)
+            .find(|a| mentions_gst && a.value < total.value * self.weights.gst_max_ratio)
+            .cloned();
+        (Some(total), gst)
+    }
+
+    /// Parse an invoice from the text of each page of a PDF. Line items are
+    /// read page by page, leaving out headers and footers repeated on every
+    /// page; the total comes from the page holding the summary; and each
+    /// field records the page it was found on.
+    pub fn parse_pages(&self, pages: &[String], document_type: DocumentType) -> Result<ExtractedInvoice, String> {
+        let pages: Vec<String> = pages.iter().map(|p| text_normalize::normalize(p).trim().to_string()).collect();
+        let mut invoice = self.parse_from_text(&pages.join("\n\n"), document_type)?;
+        invoice.page_count = pages.len() as u32;
+
+        let repeated = repeated_lines(&pages);
+        let mut items = Vec::new();
+        for (number, page) in (1..).zip(&pages) {
+            let body: Vec<&str> = page.lines().filter(|l| !repeated.contains(l.trim())).collect();
+            items.extend(self.extract_line_items(&body.join("\n")).into_iter().map(|item| LineItem {
+                page: Some(number),
+                ..item
+            }));
+        }
+        items.truncate(50);
+        invoice.line_items = items;
+
+        if let Some(summary) = self.summary_page(&pages) {
+            if let (Some(total), gst) = self.extract_total_and_gst(&pages[summary]) {
+                let code = invoice.currency_code().to_string();
+                let on_summary = |field: ExtractedField<f64>| ExtractedField {
+                    page: Some(summary as u32 + 1),
+                    ..field
+                };
+                invoice.total_amount = in_currency(Some(on_summary(total)), &code);
+                invoice.gst_amount = in_currency(gst.map(on_summary), &code);
+            }
+        }
+        // The user's patterns win over the summary page too
+        custom_patterns::apply(&self.custom_patterns, &pages.join("\n\n"), &mut invoice, self.date_order);
+
+        invoice.apply_subtype_sign();
+        locate_field_pages(&mut invoice, &pages);
+        invoice.overall_confidence = self.calculate_confidence(&invoice);
+        Ok(invoice)
+    }
+
+    /// Index of the last page with a labelled total, which on multi-page
+    /// invoices follows the line items
+    fn summary_page(&self, pages: &[String]) -> Option<usize> {
+        if pages.len() < 2 {
+            return None;
+        }
+        pages.iter().rposition(|page| self.extract_labelled_total(page).is_some())
+    }
+
     /// Extract ABN from text
     fn extract_abn(&self, text: &str) -> Option<ExtractedField<String>> {
         for pattern in &self.abn_patterns {
@@ -217,7 +777,7 @@ impl InvoiceParser {
                 if let Some(abn_match) = caps.get(1) {
                     let abn = abn_match.as_str().replace(" ", "");
                     if Self::validate_abn(&abn) {
-                        return Some(ExtractedField::new(abn, 0.90, "abn_regex"));
+                        return Some(ExtractedField::new(abn, self.weights.abn_confidence, "abn_regex"));
                     }
                 }
             }
@@ -225,6 +785,82 @@ impl InvoiceParser {
         None
     }
 
+    /// First valid ABN in the text other than `exclude`
+    fn extract_abn_excluding(&self, text: &str, exclude: &str) -> Option<ExtractedField<String>> {
+        self.abn_patterns
+            .iter()
+            .flat_map(|pattern| pattern.captures_iter(text))
+            .filter_map(|caps| caps.get(1).map(|m| m.as_str().replace(" ", "")))
+            .find(|abn| abn != exclude && Self::validate_abn(abn))
+            .map(|abn| ExtractedField::new(abn, self.weights.abn_confidence, "abn_regex"))
+    }
+
+    /// Extract the ACN, preferring the one the vendor's ABN was issued for
+    /// when the invoice shows several
+    fn extract_acn(&self, text: &str, abn: Option<&str>) -> Option<ExtractedField<String>> {
+        let acns: Vec<String> = self
+            .acn_patterns
+            .iter()
+            .flat_map(|pattern| pattern.captures_iter(text))
+            .filter_map(|caps| caps.get(1).map(|m| m.as_str().replace(" ", "")))
+            .filter(|acn| Self::validate_acn(acn))
+            .collect();
+        let matching = acns.iter().find(|acn| abn.is_some_and(|abn| acn_matches_abn(acn, abn)));
+        matching
+            .or(acns.first())
+            .map(|acn| ExtractedField::new(acn.clone(), self.weights.abn_confidence, "acn_regex"))
+    }
+
+    /// Extract the vendor's address from the text above any "Bill To" block
+    fn extract_vendor_address(&self, text: &str) -> Option<ExtractedField<Address>> {
+        let header: Vec<&str> = text
+            .lines()
+            .take_while(|line| {
+                let lower = line.trim().to_lowercase();
+                !BILL_TO_LABELS.iter().any(|label| lower.starts_with(label))
+            })
+            .collect();
+        address::find_address(&header)
+            .map(|address| ExtractedField::new(address, self.weights.vendor_plain_confidence, "address_block"))
+    }
+
+    /// Extract the recipient name and ABN from a "Bill To" block
+    fn extract_bill_to(&self, text: &str) -> (Option<ExtractedField<String>>, Option<ExtractedField<String>>) {
+        const BLOCK_END: [&str; 4] = ["ship to", "deliver to", "invoice", "date"];
+
+        let lines: Vec<&str> = text.lines().map(|l| l.trim()).filter(|l| !l.is_empty()).collect();
+        let Some((start, label_len)) = lines.iter().enumerate().find_map(|(i, line)| {
+            let lower = line.to_lowercase();
+            BILL_TO_LABELS.iter().find(|label| lower.starts_with(*label)).map(|label| (i, label.len()))
+        }) else {
+            return (None, None);
+        };
+
+        let rest = lines[start][label_len..].trim_start_matches([':', ' ', '-']).trim();
+        let block: Vec<&str> = std::iter::once(rest)
+            .chain(lines[start + 1..].iter().copied().take_while(|line| {
+                let lower = line.to_lowercase();
+                !BLOCK_END.iter().any(|end| lower.starts_with(end))
+            }))
+            .filter(|line| !line.is_empty())
+            .take(5)
+            .collect();
+
+        let name = block
+            .iter()
+            .find(|line| !line.to_lowercase().starts_with("abn") && line.chars().any(|c| c.is_alphabetic()))
+            .map(|line| ExtractedField::new(line.to_string(), self.weights.vendor_plain_confidence, "bill_to_block"));
+
+        let abn = block.iter().find_map(|line| {
+            let lower = line.to_lowercase();
+            let digits: String = lower.strip_prefix("abn")?.chars().filter(|c| c.is_ascii_digit()).collect();
+            Self::validate_abn(&digits)
+                .then(|| ExtractedField::new(digits, self.weights.abn_confidence, "bill_to_block"))
+        });
+
+        (name, abn)
+    }
+
     /// Validate ABN using checksum algorithm
     pub fn validate_abn(abn: &str) -> bool {
         if abn.len() != 11 {
@@ -253,6 +889,16 @@ impl InvoiceParser {
         sum % 89 == 0
     }
 
+    /// Validate an ACN using the ASIC check digit
+    pub fn validate_acn(acn: &str) -> bool {
+        let digits: Vec<u32> = acn.chars().filter_map(|c| c.to_digit(10)).collect();
+        if acn.len() != 9 || digits.len() != 9 {
+            return false;
+        }
+        let sum: u32 = digits[..8].iter().zip((1..=8).rev()).map(|(digit, weight)| digit * weight).sum();
+        (10 - sum % 10) % 10 == digits[8]
+    }
+
     /// Extract invoice number
     fn extract_invoice_number(&self, text: &str) -> Option<ExtractedField<String>> {
         for pattern in &self.invoice_number_patterns {
@@ -260,7 +906,7 @@ impl InvoiceParser {
                 if let Some(inv_match) = caps.get(1) {
                     let inv_num = inv_match.as_str().trim().to_uppercase();
                     if !inv_num.is_empty() && inv_num.len() < 50 {
-                        return Some(ExtractedField::new(inv_num, 0.85, "invoice_number_regex"));
+                        return Some(ExtractedField::new(inv_num, self.weights.invoice_number_confidence, "invoice_number_regex"));
                     }
                 }
             }
@@ -268,6 +914,18 @@ impl InvoiceParser {
         None
     }
 
+    /// First match of any pattern that looks like a reference number (has a
+    /// digit), so "Quote valid for 30 days" is passed over
+    fn extract_reference(&self, text: &str, patterns: &[Regex], source: &str) -> Option<ExtractedField<String>> {
+        patterns.iter().find_map(|pattern| {
+            pattern
+                .captures_iter(text)
+                .map(|caps| caps[1].trim_end_matches(['-', '/']).to_uppercase())
+                .find(|number| number.len() < 50 && number.chars().any(|c| c.is_ascii_digit()))
+                .map(|number| ExtractedField::new(number, self.weights.invoice_number_confidence, source))
+        })
+    }
+
     /// Extract dates from text
     fn extract_dates(&self, text: &str) -> Vec<ExtractedField<String>> {
         let mut dates = Vec::new();
@@ -279,7 +937,13 @@ impl InvoiceParser {
                     let date = date_match.as_str().trim().to_string();
                     if !seen.contains(&date) && date.len() >= 6 {
                         seen.insert(date.clone());
-                        dates.push(ExtractedField::new(date, 0.80, "date_regex"));
+                        let normalized = dates::normalize(&date, self.date_order);
+                        let value = normalized.iso.unwrap_or_else(|| date.clone());
+                        dates.push(ExtractedField {
+                            raw: Some(date),
+                            ambiguous: normalized.ambiguous,
+                            ..ExtractedField::new(value, self.weights.date_confidence, "date_regex")
+                        });
                     }
                 }
             }
@@ -301,7 +965,8 @@ impl InvoiceParser {
         for line in &lines {
             // Skip lines that are clearly not business names
             let lower = line.to_lowercase();
-            if lower.starts_with("abn") 
+            if lower.starts_with("abn")
+                || lower.starts_with("acn")
                 || lower.starts_with("invoice") 
                 || lower.starts_with("date")
                 || lower.starts_with("tax")
@@ -317,9 +982,9 @@ impl InvoiceParser {
                 // Check for common business suffixes
                 let business_suffixes = ["pty ltd", "ltd", "limited", "inc", "corp", "llc", "trading as", "t/a"];
                 let confidence = if business_suffixes.iter().any(|s| lower.contains(s)) {
-                    0.90
+                    self.weights.vendor_suffix_confidence
                 } else {
-                    0.70
+                    self.weights.vendor_plain_confidence
                 };
 
                 return Some(ExtractedField::new(line.to_string(), confidence, "vendor_heuristic"));
@@ -341,7 +1006,7 @@ impl InvoiceParser {
                     if let Ok(amount) = amount_str.parse::<f64>() {
                         if amount > 0.0 && !seen.contains(&amount_str) && amount < 1000000.0 {
                             seen.insert(amount_str);
-                            amounts.push(ExtractedField::new(amount, 0.75, "amount_regex"));
+                            amounts.push(ExtractedField::new(amount, self.weights.amount_confidence, "amount_regex"));
                         }
                     }
                 }
@@ -353,21 +1018,148 @@ impl InvoiceParser {
         amounts
     }
 
+    /// Amount following an explicit total label such as "Total due"
+    fn extract_labelled_total(&self, text: &str) -> Option<ExtractedField<f64>> {
+        let caps = self.amount_patterns[0].captures(text)?;
+        let amount = caps.get(1)?.as_str().replace(",", "").parse::<f64>().ok()?;
+        Some(ExtractedField::new(amount, self.weights.amount_confidence, "amount_regex"))
+    }
+
+    /// Amount on a "Subtotal" or "Total ex GST" line
+    fn extract_subtotal(&self, text: &str) -> Option<ExtractedField<f64>> {
+        self.subtotal_patterns.iter().find_map(|pattern| {
+            let amount = pattern.captures(text)?.get(1)?.as_str().replace(",", "").parse::<f64>().ok()?;
+            Some(ExtractedField::new(amount, self.weights.amount_confidence, "subtotal_regex"))
+        })
+    }
+
     /// Extract payment terms
     fn extract_payment_terms(&self, text: &str) -> Option<ExtractedField<String>> {
         for pattern in &self.payment_terms_patterns {
             if let Some(caps) = pattern.captures(text) {
                 if let Some(terms_match) = caps.get(0) {
                     let terms = terms_match.as_str().trim().to_string();
-                    return Some(ExtractedField::new(terms, 0.75, "payment_terms_regex"));
+                    return Some(ExtractedField::new(terms, self.weights.payment_terms_confidence, "payment_terms_regex"));
                 }
             }
         }
         None
     }
 
-    /// Extract line items from text
+    /// Extract bank account, PayID and BPAY details
+    fn extract_payment_details(&self, text: &str) -> Option<PaymentDetails> {
+        let patterns = &self.payment_patterns;
+        let confidence = self.weights.payment_terms_confidence;
+        let field = |value: String, source: &str| ExtractedField::new(value, confidence, source);
+
+        let bsb = patterns
+            .bsb
+            .captures(text)
+            .map(|caps| field(format!("{}-{}", &caps[1], &caps[2]), "bsb_regex"));
+        let account_number = patterns.account_number.captures(text).and_then(|caps| {
+            let digits: String = caps[1].chars().filter(|c| c.is_ascii_digit()).collect();
+            (6..=10).contains(&digits.len()).then(|| field(digits, "account_regex"))
+        });
+        let account_name = patterns.account_name.captures(text).and_then(|caps| {
+            // Stop at the next column or label on the same line
+            let name = caps[1].split(['|', '\t']).next().unwrap_or("");
+            let name = name.split("  ").next().unwrap_or("").trim();
+            let name = patterns.bsb.find(name).map_or(name, |bsb| name[..bsb.start()].trim());
+            name.chars().any(|c| c.is_alphabetic()).then(|| field(name.to_string(), "account_regex"))
+        });
+        let payid = patterns.payid.captures(text).map(|caps| {
+            let id = caps[1].trim();
+            let id = if id.contains('@') { id.to_string() } else { id.replace(' ', "") };
+            field(id, "payid_regex")
+        });
+        let bpay_biller_code = patterns.biller_code.captures(text).map(|caps| field(caps[1].to_string(), "bpay_regex"));
+        let bpay_reference = bpay_biller_code
+            .as_ref()
+            .and_then(|_| patterns.bpay_reference.captures(text))
+            .map(|caps| field(caps[1].to_string(), "bpay_regex"));
+
+        let details = PaymentDetails {
+            bsb,
+            account_number,
+            account_name,
+            payid,
+            bpay_biller_code,
+            bpay_reference,
+        };
+        (!details.is_empty()).then_some(details)
+    }
+
+    /// Extract the vendor's email and phone number, skipping the "Bill To"
+    /// block and payment lines. A labelled value beats the first one found.
+    fn extract_vendor_contact(&self, text: &str, address: Option<ExtractedField<Address>>) -> Option<VendorContact> {
+        let patterns = &self.contact_patterns;
+        let mut in_bill_to = false;
+        let lines: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| {
+                let lower = line.to_lowercase();
+                if BILL_TO_LABELS.iter().any(|label| lower.starts_with(label)) {
+                    in_bill_to = true;
+                } else if line.is_empty() {
+                    in_bill_to = false;
+                }
+                !in_bill_to && !patterns.excluded_line.is_match(line)
+            })
+            .collect();
+
+        let confidence = self.weights.vendor_plain_confidence;
+        let find = |pattern: &Regex, label: &Regex| {
+            let labelled = lines.iter().find_map(|line| {
+                let at = label.find(line)?.end();
+                pattern.find(&line[at..])
+            });
+            let first = || {
+                lines.iter().find_map(|line| {
+                    pattern.find_iter(line).find(|m| !patterns.fax_label.is_match(&line[..m.start()]))
+                })
+            };
+            let (found, source) = match labelled {
+                Some(found) => (found, "contact_label"),
+                None => (first()?, "contact_regex"),
+            };
+            Some((found.as_str().to_string(), source))
+        };
+
+        let email = find(&patterns.email, &patterns.email_label)
+            .map(|(email, source)| ExtractedField::new(email.to_lowercase(), confidence, source));
+        let phone = find(&patterns.phone, &patterns.phone_label).map(|(raw, source)| {
+            let digits: String = raw.chars().filter(|c| c.is_ascii_digit()).collect();
+            let national = match digits.strip_prefix("61") {
+                Some(rest) if raw.starts_with('+') => format!("0{}", rest.trim_start_matches('0')),
+                _ => digits,
+            };
+            let mut field = ExtractedField::new(national, confidence, source);
+            field.raw = Some(raw);
+            field
+        });
+
+        let contact = VendorContact { email, phone, address };
+        (!contact.is_empty()).then_some(contact)
+    }
+
+    /// Extract line items, reading the item table column by column when it
+    /// has a header row and falling back to one item per line
     fn extract_line_items(&self, text: &str) -> Vec<LineItem> {
+        let mut items = invoice_table::extract_items(
+            text,
+            |line| self.is_non_item_line(line),
+            self.weights.table_line_item_confidence,
+        );
+        if items.is_empty() {
+            items = self.extract_line_items_by_line(text);
+        }
+        items.truncate(50);
+        items
+    }
+
+    /// Guess a line item from each line holding an amount
+    fn extract_line_items_by_line(&self, text: &str) -> Vec<LineItem> {
         let mut items = Vec::new();
         
         // Look for lines that might be line items
@@ -376,16 +1168,17 @@ impl InvoiceParser {
         
         for line in lines {
             let line = line.trim();
-            if line.is_empty() || line.len() < 10 {
+            if line.is_empty() || line.len() < 10 || self.is_non_item_line(line) {
                 continue;
             }
 
             // Try to find amount patterns in the line
             let amount_pattern = Regex::new(r"([\d,]+\.\d{2})").unwrap();
-            let amounts: Vec<f64> = amount_pattern.captures_iter(line)
+            let amounts: Vec<Money> = amount_pattern.captures_iter(line)
                 .filter_map(|caps| caps.get(1))
                 .filter_map(|m| m.as_str().replace(",", "").parse::<f64>().ok())
-                .filter(|&a| a > 0.0)
+                .map(Money::from_dollars)
+                .filter(|&a| a > Money::ZERO)
                 .collect();
 
             if amounts.is_empty() {
@@ -398,17 +1191,17 @@ impl InvoiceParser {
                 .and_then(|caps| caps.get(1))
                 .and_then(|m| m.as_str().parse::<f64>().ok());
 
-            let total = *amounts.last().unwrap_or(&0.0);
+            let total = *amounts.last().unwrap_or(&Money::ZERO);
             let unit_price = if amounts.len() >= 2 {
                 amounts[amounts.len() - 2]
             } else if let Some(qty) = quantity {
-                if qty > 0.0 { total / qty } else { 0.0 }
+                if qty > 0.0 { Money::from_dollars(total.to_dollars() / qty) } else { Money::ZERO }
             } else {
                 total
             };
 
             // Extract description (text before the amounts)
-            let desc = if let Some(first_amount_pos) = line.find(&format!("{:.2}", amounts[0])) {
+            let desc = if let Some(first_amount_pos) = line.find(&amounts[0].to_string()) {
                 line[..first_amount_pos].trim().to_string()
             } else {
                 line.to_string()
@@ -427,7 +1220,12 @@ impl InvoiceParser {
                     quantity,
                     unit_price: Some(unit_price),
                     total,
-                    confidence: if quantity.is_some() { 0.70 } else { 0.50 },
+                    confidence: if quantity.is_some() {
+                        self.weights.line_item_with_quantity_confidence
+                    } else {
+                        self.weights.line_item_confidence
+                    },
+                    ..Default::default()
                 });
             }
         }
@@ -438,7 +1236,7 @@ impl InvoiceParser {
     }
 
     /// Calculate overall confidence score
-    fn calculate_confidence(&self, invoice: &ExtractedInvoice) -> f64 {
+    pub(crate) fn calculate_confidence(&self, invoice: &ExtractedInvoice) -> f64 {
         let mut total_confidence = 0.0;
         let mut field_count = 0;
 
@@ -471,7 +1269,7 @@ impl InvoiceParser {
         
         // Boost confidence if we have key fields
         let boost = if invoice.abn.is_some() && invoice.invoice_number.is_some() && invoice.total_amount.is_some() {
-            0.1
+            self.weights.key_fields_boost
         } else {
             0.0
         };
@@ -480,26 +1278,158 @@ impl InvoiceParser {
     }
 }
 
-/// Parse PDF file and extract text content
-#[cfg(feature = "pdf-parse")]
+/// Lines found on every page of a multi-page document, such as letterheads,
+/// column headings and footers
+fn repeated_lines(pages: &[String]) -> HashSet<String> {
+    if pages.len() < 2 {
+        return HashSet::new();
+    }
+    let lines_of = |page: &String| -> HashSet<String> {
+        page.lines().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect()
+    };
+    pages[1..].iter().fold(lines_of(&pages[0]), |common, page| {
+        let lines = lines_of(page);
+        common.into_iter().filter(|l| lines.contains(l)).collect()
+    })
+}
+
+/// Record the first page each field's value appears on, for fields that
+/// don't have one yet. Spacing is ignored, as values such as ABNs are
+/// stored without it.
+pub fn locate_field_pages(invoice: &mut ExtractedInvoice, pages: &[String]) {
+    let compact = |text: &str| -> String {
+        text.chars()
+            .filter(|c| !c.is_whitespace())
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let pages: Vec<String> = pages.iter().map(|p| compact(p)).collect();
+    let find = |needles: &[String]| {
+        let needles: Vec<String> = needles.iter().map(|n| compact(n)).filter(|n| !n.is_empty()).collect();
+        (1..)
+            .zip(&pages)
+            .find(|(_, page)| needles.iter().any(|n| page.contains(n.as_str())))
+            .map(|(number, _)| number)
+    };
+    let text_fields = [
+        &mut invoice.abn,
+        &mut invoice.acn,
+        &mut invoice.invoice_number,
+        &mut invoice.purchase_order_number,
+        &mut invoice.quote_number,
+        &mut invoice.invoice_date,
+        &mut invoice.due_date,
+        &mut invoice.vendor_name,
+        &mut invoice.payment_terms,
+        &mut invoice.bill_to_name,
+        &mut invoice.bill_to_abn,
+    ];
+    for field in text_fields.into_iter().flatten().filter(|f| f.page.is_none()) {
+        let printed = field.raw.as_ref().unwrap_or(&field.value);
+        field.page = find(&[printed.to_lowercase()]);
+    }
+    let amount_fields = [&mut invoice.total_amount, &mut invoice.gst_amount, &mut invoice.subtotal_amount];
+    for field in amount_fields.into_iter().flatten() {
+        if field.page.is_none() {
+            let plain = format!("{:.2}", field.value.value.abs());
+            field.page = find(&[plain.clone(), with_thousands(&plain)]);
+        }
+    }
+}
+
+/// `1234.50` as `1,234.50`
+pub(crate) fn with_thousands(amount: &str) -> String {
+    let (whole, cents) = amount.split_once('.').unwrap_or((amount, ""));
+    let digits: Vec<char> = whole.chars().collect();
+    let mut grouped = String::new();
+    for (i, digit) in digits.iter().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(*digit);
+    }
+    if cents.is_empty() {
+        grouped
+    } else {
+        format!("{}.{}", grouped, cents)
+    }
+}
+
+/// Extract the text of each page of a PDF, laid out line by line with
+/// columns kept in place. With the `pdf-parse` feature, PDFs the layout
+/// reader finds no text in are tried again with pdf-extract, which returns
+/// the whole document as one page.
+pub fn extract_pdf_pages(pdf_path: &str) -> Result<Vec<String>, String> {
+    let pages = crate::pdf_text::extract_pages(Path::new(pdf_path))?;
+    if pages.iter().any(|p| !p.trim().is_empty()) {
+        return Ok(pages);
+    }
+
+    #[cfg(feature = "pdf-parse")]
+    {
+        let text = pdf_extract::extract_text(pdf_path).map_err(|e| format!("PDF extraction error: {}", e))?;
+        if !text.trim().is_empty() {
+            return Ok(vec![text]);
+        }
+    }
+
+    Err(format!("{} has no text layer; scan it as an image instead", pdf_path))
+}
+
+/// Extract the text layer of a PDF, with pages separated by a blank line
 pub fn extract_pdf_text(pdf_path: &str) -> Result<String, String> {
-    use pdf_extract::extract_text;
-    
-    extract_text(pdf_path).map_err(|e| format!("PDF extraction error: {}", e))
+    let pages = extract_pdf_pages(pdf_path)?;
+    Ok(pages.into_iter().filter(|p| !p.trim().is_empty()).collect::<Vec<_>>().join("\n\n"))
+}
+
+/// Scoring constants from the active settings profile, or the defaults
+pub fn active_weights() -> HeuristicWeights {
+    crate::settings::load_settings()
+        .ok()
+        .and_then(|settings| settings.active_heuristic_weights())
+        .unwrap_or_default()
 }
 
-/// Mock PDF extraction for development without pdf-parse feature
-#[cfg(not(feature = "pdf-parse"))]
-pub fn extract_pdf_text(_pdf_path: &str) -> Result<String, String> {
-    Err("PDF parsing not enabled. Enable 'pdf-parse' feature or implement custom PDF extraction".to_string())
+/// Parser using the active scoring profile and the user's skip and custom
+/// patterns
+pub fn active_parser() -> Result<InvoiceParser, String> {
+    let settings = crate::settings::load_settings().unwrap_or_default();
+    let patterns = custom_patterns::with_patterns(|store| Ok(store.compiled())).unwrap_or_else(|e| {
+        log::warn!("Custom patterns not loaded: {}", e);
+        Vec::new()
+    });
+    Ok(InvoiceParser::with_weights(settings.active_heuristic_weights().unwrap_or_default())?
+        .with_skip_patterns(&settings.line_item_skip_patterns)?
+        .with_custom_patterns(patterns)
+        .with_date_order(settings.date_order))
 }
 
 /// Parse an invoice from a PDF file
 pub fn parse_invoice_pdf(pdf_path: &str) -> Result<ExtractedInvoice, String> {
-    let text = extract_pdf_text(pdf_path)?;
-    
-    let parser = InvoiceParser::new()?;
-    parser.parse_from_text(&text, DocumentType::Pdf)
+    let pages = extract_pdf_pages(pdf_path)?;
+    let text = pages.join("\n\n");
+
+    let parser = active_parser()?;
+    let invoice = parser.parse_pages(&pages, DocumentType::Pdf)?;
+    let invoice = crate::vendor_templates::apply_default_template(&text, invoice);
+    let mut invoice = crate::ml_extract::refine_with_layout_model(invoice);
+    parser.apply_custom_patterns(&text, &mut invoice);
+    custom_patterns::record_parse(&invoice);
+    // Fields replaced by a template or the layout model
+    locate_field_pages(&mut invoice, &pages);
+    Ok(invoice)
+}
+
+/// Parse an invoice that arrived as text rather than a file, such as the
+/// body of an email
+pub fn parse_invoice_text(text: &str) -> Result<ExtractedInvoice, String> {
+    let parser = active_parser()?;
+    let invoice = parser.parse_from_text(text, DocumentType::Pdf)?;
+    let invoice = crate::vendor_templates::apply_default_template(text, invoice);
+    let mut invoice = crate::ml_extract::refine_with_layout_model(invoice);
+    parser.apply_custom_patterns(text, &mut invoice);
+    custom_patterns::record_parse(&invoice);
+    Ok(invoice)
 }
 
 /// Parse an invoice from an image file using OCR
@@ -512,15 +1442,112 @@ pub fn parse_invoice_image(image_path: &str) -> Result<ExtractedInvoice, String>
     ))
 }
 
+/// A company's ABN is its ACN with two check digits in front
+pub fn acn_matches_abn(acn: &str, abn: &str) -> bool {
+    abn.len() == 11 && abn.ends_with(acn)
+}
+
 /// Validation result for extracted invoice data
 #[derive(Debug, Serialize)]
 pub struct InvoiceValidationResult {
     pub is_valid: bool,
     pub missing_fields: Vec<String>,
     pub warnings: Vec<String>,
+    /// Totals that don't reconcile; each is also listed in `warnings`
+    pub arithmetic_warnings: Vec<ArithmeticWarning>,
+    /// ISO code of an invoice billed in a currency other than AUD, whose
+    /// amounts must be converted before they are claimed
+    pub foreign_currency: Option<String>,
     pub suggested_action: String,
 }
 
+/// GST and line items may be rounded per line, so sums can drift by a cent
+/// or two
+const ARITHMETIC_TOLERANCE: Money = Money::from_cents(2);
+
+/// Which sum failed to reconcile
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArithmeticCheck {
+    /// GST should be one eleventh of a GST-inclusive total
+    GstInclusive,
+    /// GST should be ten percent of the subtotal
+    GstExclusive,
+    /// Subtotal plus GST should equal the total
+    SubtotalPlusGst,
+    /// Line items should add up to the subtotal
+    LineItems,
+}
+
+/// A sum on the invoice that doesn't match the amounts it is made of
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ArithmeticWarning {
+    pub check: ArithmeticCheck,
+    pub expected: Money,
+    pub actual: Money,
+    pub message: String,
+}
+
+/// Check GST against the total or subtotal, and line items against the
+/// subtotal. An invoice printing a subtotal apart from its total is treated
+/// as GST-exclusive; otherwise GST is read as included in the total.
+pub fn check_arithmetic(invoice: &ExtractedInvoice) -> Vec<ArithmeticWarning> {
+    let money = |field: &Option<ExtractedField<CurrencyAmount>>| field.as_ref().map(|f| f.value.value);
+    let total = money(&invoice.total_amount);
+    let gst = money(&invoice.gst_amount).filter(|gst| *gst != Money::ZERO);
+    let subtotal = money(&invoice.subtotal_amount);
+    let exclusive_subtotal = subtotal.filter(|subtotal| Some(*subtotal) != total);
+
+    let mut warnings = Vec::new();
+    let mut reconcile = |check, expected: Money, actual: Money, describe: &dyn Fn(Money, Money) -> String| {
+        if (expected - actual).abs() > ARITHMETIC_TOLERANCE {
+            warnings.push(ArithmeticWarning {
+                check,
+                expected,
+                actual,
+                message: describe(expected, actual),
+            });
+        }
+    };
+
+    match (exclusive_subtotal, gst, total) {
+        (Some(subtotal), Some(gst), total) => {
+            reconcile(ArithmeticCheck::GstExclusive, subtotal.gst_on_exclusive(), gst, &|expected, actual| {
+                format!("GST of ${} should be ${}, 10% of the ${} subtotal", actual, expected, subtotal)
+            });
+            if let Some(total) = total {
+                reconcile(ArithmeticCheck::SubtotalPlusGst, subtotal + gst, total, &|expected, actual| {
+                    format!("Total of ${} should be ${}, the subtotal plus GST", actual, expected)
+                });
+            }
+        }
+        (None, Some(gst), Some(total)) => {
+            reconcile(ArithmeticCheck::GstInclusive, total.gst_in_inclusive(), gst, &|expected, actual| {
+                format!("GST of ${} should be ${}, one eleventh of the ${} total", actual, expected, total)
+            });
+        }
+        _ => {}
+    }
+
+    // Without a printed subtotal, items may be listed with or without GST
+    let items: Money = invoice.line_items.iter().map(|item| item.total).sum();
+    let expected = match (subtotal, total) {
+        (Some(subtotal), _) => Some(subtotal),
+        (None, Some(total)) => {
+            let ex_gst = total - gst.unwrap_or(Money::ZERO);
+            let off = |target: Money| (items - target).cents().abs();
+            Some(if off(ex_gst) < off(total) { ex_gst } else { total })
+        }
+        (None, None) => None,
+    };
+    if let (Some(expected), false) = (expected, invoice.line_items.is_empty()) {
+        reconcile(ArithmeticCheck::LineItems, expected, items, &|expected, actual| {
+            format!("Line items add up to ${}, expected ${}", actual, expected)
+        });
+    }
+    warnings
+}
+
 /// Validate extracted invoice data
 pub fn validate_invoice(invoice: &ExtractedInvoice) -> InvoiceValidationResult {
     let mut missing_fields = Vec::new();
@@ -557,11 +1584,31 @@ pub fn validate_invoice(invoice: &ExtractedInvoice) -> InvoiceValidationResult {
             warnings.push(format!("ABN {} failed checksum validation", abn.value));
         }
     }
+    if let Some(ref acn) = invoice.acn {
+        if !InvoiceParser::validate_acn(&acn.value) {
+            warnings.push(format!("ACN {} failed checksum validation", acn.value));
+        }
+        if let Some(ref abn) = invoice.abn {
+            if !acn_matches_abn(&acn.value, &abn.value) {
+                warnings.push(format!("ACN {} does not match ABN {}", acn.value, abn.value));
+            }
+        }
+    }
+
+    // Check that the amounts add up
+    let arithmetic_warnings = check_arithmetic(invoice);
+    warnings.extend(arithmetic_warnings.iter().map(|w| w.message.clone()));
+
+    let foreign_currency = invoice.is_foreign_currency().then(|| invoice.currency_code().to_string());
+    if let Some(ref code) = foreign_currency {
+        warnings.push(format!("Amounts are in {}; convert them to AUD before claiming", code));
+    }
 
     let is_valid = !missing_fields.contains(&"total_amount".to_string()) 
         && invoice.overall_confidence >= 0.5;
 
-    let suggested_action = if missing_fields.is_empty() && invoice.overall_confidence >= 0.75 {
+    let complete = missing_fields.is_empty() && foreign_currency.is_none();
+    let suggested_action = if complete && invoice.overall_confidence >= 0.75 {
         "accept".to_string()
     } else if invoice.overall_confidence >= 0.5 {
         "review".to_string()
@@ -573,6 +1620,8 @@ pub fn validate_invoice(invoice: &ExtractedInvoice) -> InvoiceValidationResult {
         is_valid,
         missing_fields,
         warnings,
+        arithmetic_warnings,
+        foreign_currency,
         suggested_action,
     }
 }
@@ -607,6 +1656,197 @@ mod tests {
         assert!(abn2.is_some());
     }
 
+    #[test]
+    fn test_acn_is_validated_and_checked_against_abn() {
+        assert!(InvoiceParser::validate_acn("051775556"));
+        assert!(!InvoiceParser::validate_acn("051775557"));
+        assert!(!InvoiceParser::validate_acn("05177555"));
+
+        let parser = InvoiceParser::new().unwrap();
+        let text = "Telstra Corporation Limited\nABN 33 051 775 556\nACN 051 775 556\nInvoice #T-1\nTotal: $99.00";
+        let invoice = parser.parse_from_text(text, DocumentType::Pdf).unwrap();
+        assert_eq!(invoice.acn.as_ref().unwrap().value, "051775556");
+        assert_eq!(invoice.vendor_name.as_ref().unwrap().value, "Telstra Corporation Limited");
+        assert!(!validate_invoice(&invoice).warnings.iter().any(|w| w.contains("ACN")));
+
+        // The ACN printed belongs to a different company
+        let mismatched = ExtractedInvoice {
+            abn: Some(ExtractedField::new("51824753556".to_string(), 0.9, "test")),
+            ..invoice
+        };
+        let warnings = validate_invoice(&mismatched).warnings;
+        assert!(warnings.contains(&"ACN 051775556 does not match ABN 51824753556".to_string()), "{:?}", warnings);
+    }
+
+    #[test]
+    fn test_extract_payment_details() {
+        let parser = InvoiceParser::new().unwrap();
+        let text = "Acme Plumbing Pty Ltd\nInvoice #A-100\nTotal: $220.00\nHow to pay\n\
+            Account Name: Acme Plumbing Pty Ltd    BSB: 062-000\nAccount Number: 1234 5678\n\
+            PayID: accounts@acmeplumbing.com.au\nBPAY Biller Code: 12345  Ref: 0012345678";
+        let details = parser.parse_from_text(text, DocumentType::Pdf).unwrap().payment_details.unwrap();
+
+        assert_eq!(details.bsb.unwrap().value, "062-000");
+        assert_eq!(details.account_number.unwrap().value, "12345678");
+        assert_eq!(details.account_name.unwrap().value, "Acme Plumbing Pty Ltd");
+        assert_eq!(details.payid.unwrap().value, "accounts@acmeplumbing.com.au");
+        assert_eq!(details.bpay_biller_code.unwrap().value, "12345");
+        assert_eq!(details.bpay_reference.unwrap().value, "0012345678");
+
+        let none = parser.parse_from_text("Acme\nInvoice #A-1\nTotal: $10.00", DocumentType::Pdf).unwrap();
+        assert!(none.payment_details.is_none());
+    }
+
+    #[test]
+    fn test_extract_vendor_contact() {
+        let parser = InvoiceParser::new().unwrap();
+        let text = "Acme Plumbing Pty Ltd\nUnit 4, 12 Smith Street\nParramatta NSW 2150\n\
+            Ph: (02) 9876 5432  Fax: 02 9876 5433\nE: Accounts@AcmePlumbing.com.au\n\n\
+            Bill To\nHarbour Cafe\nowner@harbourcafe.com.au  0412 345 678\n\n\
+            Invoice #A-100\nTotal: $220.00\nPayID: pay@acmeplumbing.com.au\nOr call 1300 123 456";
+        let contact = parser.parse_from_text(text, DocumentType::Pdf).unwrap().vendor_contact.unwrap();
+
+        assert_eq!(contact.email.unwrap().value, "accounts@acmeplumbing.com.au");
+        let phone = contact.phone.unwrap();
+        assert_eq!(phone.value, "0298765432");
+        assert_eq!(phone.source, "contact_label");
+        assert_eq!(phone.raw.as_deref(), Some("(02) 9876 5432"));
+        let address = contact.address.unwrap().value;
+        assert_eq!(address.postcode.as_deref(), Some("2150"));
+
+        let mobile = parser.parse_from_text("Acme\nMobile: +61 412 345 678\nTotal: $10.00", DocumentType::Pdf);
+        assert_eq!(mobile.unwrap().vendor_contact.unwrap().phone.unwrap().value, "0412345678");
+        let none = parser.parse_from_text("Acme\nInvoice #A-1\nTotal: $10.00", DocumentType::Pdf).unwrap();
+        assert!(none.vendor_contact.is_none());
+    }
+
+    #[test]
+    fn test_extract_purchase_order_and_quote_numbers() {
+        let parser = InvoiceParser::new().unwrap();
+        let text = "Acme Plumbing Pty Ltd\nPO Box 99\nTax Invoice INV-2041\nYour Ref: po-88213\n\
+            As per Quote No. Q2024/117 (quote valid 30 days)\nTotal: $220.00";
+        let invoice = parser.parse_from_text(text, DocumentType::Pdf).unwrap();
+        assert_eq!(invoice.invoice_number.unwrap().value, "INV-2041");
+        assert_eq!(invoice.purchase_order_number.unwrap().value, "PO-88213");
+        assert_eq!(invoice.quote_number.unwrap().value, "Q2024/117");
+
+        let text = "Purchase Order: 4500012345\nEstimate #E-31\nTotal: $10.00";
+        let invoice = parser.parse_from_text(text, DocumentType::Pdf).unwrap();
+        assert_eq!(invoice.purchase_order_number.unwrap().value, "4500012345");
+        assert_eq!(invoice.quote_number.unwrap().value, "E-31");
+
+        let none = parser.parse_from_text("Acme\nPO Box 12\nQuote valid for 30 days\nTotal: $10.00", DocumentType::Pdf);
+        let none = none.unwrap();
+        assert!(none.purchase_order_number.is_none());
+        assert!(none.quote_number.is_none());
+    }
+
+    #[test]
+    fn test_credit_notes_are_detected_and_negated() {
+        let parser = InvoiceParser::new().unwrap();
+        let text = "Acme Plumbing Pty Ltd\nTAX INVOICE / ADJUSTMENT NOTE\nOriginal Invoice No: INV-2041\n\
+            Returned tap set  1 x 100.00  100.00\nGST: $10.00\nTotal: $110.00";
+        let note = parser.parse_from_text(text, DocumentType::Pdf).unwrap();
+        assert_eq!(note.subtype, DocumentSubtype::AdjustmentNote);
+        assert_eq!(note.total_amount.as_ref().unwrap().value.value.to_dollars(), -110.0);
+        assert_eq!(note.gst_amount.as_ref().unwrap().value.value.to_dollars(), -10.0);
+        assert!(note.line_items.iter().all(|item| item.total < Money::ZERO));
+
+        // Signs are applied once, however often the note is re-read
+        let pages = vec![text.to_string()];
+        let paged = parser.parse_pages(&pages, DocumentType::Pdf).unwrap();
+        assert_eq!(paged.total_amount.as_ref().unwrap().value.value.to_dollars(), -110.0);
+        assert_eq!(paged.total_amount.unwrap().page, Some(1));
+
+        assert_eq!(detect_subtype("Acme\nCredit Note CN-12\nTotal: $5.00"), DocumentSubtype::CreditNote);
+        assert_eq!(detect_subtype("Acme\nInvoice To: Harbour Cafe\nQuotation\n"), DocumentSubtype::Quote);
+        assert_eq!(detect_subtype("Acme\nStatement of Account\nInvoice 101  50.00"), DocumentSubtype::Statement);
+        assert_eq!(detect_subtype("Acme\nReceipt\nPaid: $20.00"), DocumentSubtype::Receipt);
+        assert_eq!(
+            detect_subtype("Acme\nTax Invoice INV-2041\nQuote No. Q-17"),
+            DocumentSubtype::TaxInvoice
+        );
+        let invoice = parser.parse_from_text("Acme\nTax Invoice\nTotal: $10.00", DocumentType::Pdf).unwrap();
+        assert_eq!(invoice.total_amount.unwrap().value.value.to_dollars(), 10.0);
+    }
+
+    #[test]
+    fn test_increasing_adjustment_notes_keep_their_sign() {
+        let parser = InvoiceParser::new().unwrap();
+        let text = "Acme Plumbing Pty Ltd\nADJUSTMENT NOTE\nOriginal Invoice No: INV-2041\n\
+            Increasing adjustment for additional labour\nGST: $5.00\nTotal: $55.00";
+        let note = parser.parse_from_text(text, DocumentType::Pdf).unwrap();
+        assert_eq!(note.subtype, DocumentSubtype::AdjustmentNote);
+        assert!(!note.is_credit());
+        assert_eq!(note.total_amount.unwrap().value.value.to_dollars(), 55.0);
+        assert_eq!(note.gst_amount.unwrap().value.value.to_dollars(), 5.0);
+    }
+
+    #[test]
+    fn test_gst_arithmetic_is_reconciled() {
+        let amount = |value| Some(ExtractedField::new(CurrencyAmount::aud(Money::from_dollars(value)), 0.9, "test"));
+        let item = |total| LineItem {
+            description: "Labour".to_string(),
+            total: Money::from_dollars(total),
+            confidence: 0.5,
+            ..Default::default()
+        };
+        let inclusive = ExtractedInvoice {
+            total_amount: amount(100.0),
+            gst_amount: amount(9.09),
+            line_items: vec![item(60.0), item(40.0)],
+            ..Default::default()
+        };
+        assert!(check_arithmetic(&inclusive).is_empty());
+
+        let exclusive = ExtractedInvoice {
+            subtotal_amount: amount(200.0),
+            gst_amount: amount(25.0),
+            total_amount: amount(225.0),
+            line_items: vec![item(150.0), item(40.0)],
+            ..Default::default()
+        };
+        let result = validate_invoice(&exclusive);
+        let summarize = |w: &ArithmeticWarning| (w.check, w.expected.to_dollars(), w.actual.to_dollars());
+        let checks: Vec<_> = result.arithmetic_warnings.iter().map(summarize).collect();
+        assert_eq!(
+            checks,
+            vec![(ArithmeticCheck::GstExclusive, 20.0, 25.0), (ArithmeticCheck::LineItems, 200.0, 190.0)]
+        );
+        assert!(result.warnings.contains(&"GST of $25.00 should be $20.00, 10% of the $200.00 subtotal".to_string()));
+
+        // Credit notes reconcile with their negated amounts
+        let mut note = ExtractedInvoice {
+            subtype: DocumentSubtype::CreditNote,
+            total_amount: amount(110.0),
+            gst_amount: amount(12.0),
+            line_items: vec![item(98.0)],
+            ..Default::default()
+        };
+        note.apply_subtype_sign();
+        let checks: Vec<_> = check_arithmetic(&note).iter().map(summarize).collect();
+        assert_eq!(checks, vec![(ArithmeticCheck::GstInclusive, -10.0, -12.0)]);
+
+        let parser = InvoiceParser::new().unwrap();
+        let text = "Acme\nSubtotal: $1,000.00\nGST: $100.00\nTotal: $1,100.00";
+        let subtotal = parser.parse_from_text(text, DocumentType::Pdf).unwrap().subtotal_amount.unwrap();
+        assert_eq!(subtotal.value, CurrencyAmount::aud(Money::from_cents(100000)));
+    }
+
+    #[test]
+    fn test_foreign_currency_invoices_are_flagged() {
+        let parser = InvoiceParser::new().unwrap();
+        let text = "Cloudhost Inc.\nInvoice #CH-2291\nDate: 01/03/2024\nHosting plan  USD 90.00\nTotal due: USD 99.00";
+        let invoice = parser.parse_from_text(text, DocumentType::Pdf).unwrap();
+        assert_eq!(invoice.currency_code(), "USD");
+        assert_eq!(invoice.total_amount.as_ref().unwrap().value, CurrencyAmount::new(Money::from_cents(9900), "USD"));
+
+        let result = validate_invoice(&invoice);
+        assert_eq!(result.foreign_currency.as_deref(), Some("USD"));
+        assert!(result.warnings.contains(&"Amounts are in USD; convert them to AUD before claiming".to_string()));
+        assert_ne!(result.suggested_action, "accept");
+    }
+
     #[test]
     fn test_extract_invoice_number() {
         let parser = InvoiceParser::new().unwrap();
@@ -627,6 +1867,48 @@ mod tests {
         assert_eq!(amounts[0].value, 110.00);
     }
 
+    #[test]
+    fn test_total_selection_weights() {
+        let text = "Total due: $110.00\nPrevious balance $250.00";
+
+        let parser = InvoiceParser::new().unwrap();
+        let invoice = parser.parse_from_text(text, DocumentType::Pdf).unwrap();
+        assert_eq!(invoice.total_amount.unwrap().value.value.to_dollars(), 250.00);
+
+        let parser = InvoiceParser::with_weights(HeuristicWeights {
+            total_selection: TotalSelection::LabelledFirst,
+            amount_confidence: 0.6,
+            ..Default::default()
+        })
+        .unwrap();
+        let invoice = parser.parse_from_text(text, DocumentType::Pdf).unwrap();
+        let total = invoice.total_amount.unwrap();
+        assert_eq!(total.value.value.to_dollars(), 110.00);
+        assert_eq!(total.confidence, 0.6);
+    }
+
+    #[test]
+    fn test_extract_bill_to() {
+        let parser = InvoiceParser::new().unwrap();
+        let text = "Acme Plumbing Pty Ltd\nABN: 51 824 753 556\nBill To:\nSmith Consulting\nABN 53 004 085 616\nInvoice #A-100\nTotal: $220.00";
+
+        let invoice = parser.parse_from_text(text, DocumentType::Pdf).unwrap();
+        assert_eq!(invoice.bill_to_name.unwrap().value, "Smith Consulting");
+        assert_eq!(invoice.bill_to_abn.unwrap().value, "53004085616");
+        assert_eq!(invoice.abn.unwrap().value, "51824753556");
+    }
+
+    #[test]
+    fn test_extract_vendor_address_from_letterhead() {
+        let parser = InvoiceParser::new().unwrap();
+        let text = "Acme Plumbing Pty Ltd\n88 Queen St\nBrisbane QLD 4000\nABN: 51 824 753 556\nBill To:\nSmith Consulting\n1 George St, Sydney NSW 2000\nABN 53 004 085 616\nInvoice #A-100\nTotal: $220.00";
+
+        let invoice = parser.parse_from_text(text, DocumentType::Pdf).unwrap();
+        // The letterhead address, not the recipient's
+        let address = invoice.vendor_address.unwrap().value;
+        assert_eq!(address.state, Some(crate::address::AustralianState::Qld));
+    }
+
     #[test]
     fn test_extract_payment_terms() {
         let parser = InvoiceParser::new().unwrap();
@@ -635,4 +1917,78 @@ mod tests {
         let terms = parser.extract_payment_terms(text);
         assert!(terms.is_some());
     }
+
+    #[test]
+    fn test_normalization_improves_recall() {
+        let parser = InvoiceParser::new().unwrap();
+        let text = "Oﬃceworks Business\nInvoice Number            Bill To\nINV-2024-001              Harbour Plumbing\nTotal: $110.00";
+
+        // The raw text layer hides the vendor and invoice number
+        let raw_vendor = parser.extract_vendor_name(text).map(|v| v.value);
+        assert_ne!(raw_vendor.as_deref(), Some("Officeworks Business"));
+        assert_ne!(parser.extract_invoice_number(text).map(|v| v.value).as_deref(), Some("INV-2024-001"));
+
+        let invoice = parser.parse_from_text(text, DocumentType::Pdf).unwrap();
+        assert_eq!(invoice.vendor_name.unwrap().value, "Officeworks Business");
+        assert_eq!(invoice.invoice_number.unwrap().value, "INV-2024-001");
+        assert_eq!(invoice.total_amount.unwrap().value.value.to_dollars(), 110.00);
+    }
+
+    #[test]
+    fn test_multi_page_invoice_is_parsed_page_by_page() {
+        let header = "ACME SUPPLIES PTY LTD\nDescription              Qty        Amount";
+        let footer = "Late fee of $25.00 applies to overdue accounts";
+        let pages = vec![
+            format!(
+                "{}\nABN 51 824 753 556\nTax Invoice INV-1001\nBalance brought forward $2,500.00\n\
+                 Widget assembly kit      2 x 450.00     900.00\n{}\nPage 1 of 2",
+                header, footer
+            ),
+            format!(
+                "{}\nInstallation labour      3 x 120.00     360.00\nGST $126.00\nTotal due $1,386.00\n{}\nPage 2 of 2",
+                header, footer
+            ),
+        ];
+
+        let parser = InvoiceParser::new().unwrap();
+        let invoice = parser.parse_pages(&pages, DocumentType::Pdf).unwrap();
+        assert_eq!(invoice.page_count, 2);
+
+        // The summary page's total wins over a larger amount on page one
+        let total = invoice.total_amount.unwrap();
+        assert_eq!((total.value.value.to_dollars(), total.page), (1386.0, Some(2)));
+        assert_eq!(invoice.gst_amount.unwrap().value.value.to_dollars(), 126.0);
+        assert_eq!(invoice.abn.unwrap().page, Some(1));
+        assert_eq!(invoice.invoice_number.unwrap().page, Some(1));
+
+        // Items on both pages, without the footer repeated on each
+        let items: Vec<(&str, Option<u32>)> =
+            invoice.line_items.iter().map(|i| (i.description.as_str(), i.page)).collect();
+        assert_eq!(items.len(), 2, "{:?}", items);
+        assert!(items[0].0.starts_with("Widget assembly kit") && items[0].1 == Some(1));
+        assert!(items[1].0.starts_with("Installation labour") && items[1].1 == Some(2));
+        assert!(!items.iter().any(|(d, _)| d.contains("Late fee")), "{:?}", items);
+
+        assert_eq!(with_thousands("1234567.50"), "1,234,567.50");
+    }
+
+    #[test]
+    fn test_non_item_lines_are_skipped() {
+        let text = "Harbour Plumbing Pty Ltd\n\
+            Callout fee 2 x 60.00 120.00\n\
+            Replacement tap 85.50\n\
+            Tax agent referral 10.00\n\
+            Subtotal 215.50\n\
+            GST 21.55\n\
+            Total (inc GST) 237.05\n\
+            BSB 062-000 Account No 1234 5678 Amount 237.05\n\
+            Page 1 of 2 - Generated 01.02";
+        let parser = InvoiceParser::new().unwrap();
+        let items: Vec<String> = parser.extract_line_items(text).into_iter().map(|i| i.description).collect();
+        assert_eq!(items, vec!["Callout fee 2 x", "Replacement tap", "Tax agent referral"]);
+
+        let parser = parser.with_skip_patterns(&["(?i)referral".to_string()]).unwrap();
+        assert_eq!(parser.extract_line_items(text).len(), 2);
+        assert!(compile_skip_patterns(&["(unclosed".to_string()]).is_err());
+    }
 }
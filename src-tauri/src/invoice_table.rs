@@ -0,0 +1,299 @@
+//! Invoice Table Module
+//!
+//! Reads line items from an invoice's item table. The PDF text layer keeps
+//! each cell at its horizontal position, so a table is found by its header
+//! row (Description, Qty, Unit Price, GST, Amount) and each row below it is
+//! cut into cells at runs of spaces. A cell is placed in the column whose
+//! header it sits under, so a blank quantity or an extra GST code can't
+//! shift the other values along.
+
+use crate::invoice::LineItem;
+use crate::money::Money;
+
+/// Minimum run of spaces between two cells
+const CELL_GAP: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnKind {
+    Description,
+    Quantity,
+    UnitPrice,
+    /// GST amount or tax code
+    Gst,
+    Amount,
+    /// Item codes, discounts and other columns that aren't read
+    Other,
+}
+
+/// Header cells starting with these words, checked in order
+const HEADER_WORDS: [(ColumnKind, &[&str]); 6] = [
+    (ColumnKind::Other, &["item code", "item no", "item #", "sku", "code", "disc"]),
+    (ColumnKind::Description, &["description", "item", "details", "product", "service", "particulars"]),
+    (ColumnKind::Quantity, &["qty", "quantity", "units", "hours", "hrs"]),
+    (ColumnKind::UnitPrice, &["unit price", "unit cost", "price", "rate", "each", "unit"]),
+    (ColumnKind::Gst, &["gst", "tax"]),
+    (ColumnKind::Amount, &["amount", "total", "line total", "ext", "value"]),
+];
+
+/// Text between runs of spaces, with its character offsets in the line
+#[derive(Debug)]
+struct Cell<'a> {
+    start: usize,
+    end: usize,
+    text: &'a str,
+}
+
+/// Cut a line into cells at runs of spaces; a tab always separates cells
+fn split_cells(line: &str) -> Vec<Cell<'_>> {
+    let mut cells = Vec::new();
+    // Character and byte offsets where the current cell starts
+    let mut start: Option<(usize, usize)> = None;
+    // Character and byte offsets where the current run of spaces starts
+    let mut gap: Option<(usize, usize)> = None;
+    let mut gap_width = 0;
+    let chars = line.char_indices().chain(std::iter::once((line.len(), '\t')));
+    for (column, (offset, c)) in chars.enumerate() {
+        if c == ' ' || c == '\t' {
+            gap.get_or_insert((column, offset));
+            gap_width += if c == '\t' { CELL_GAP } else { 1 };
+            continue;
+        }
+        if let (Some((cell_start, byte_start)), Some((gap_start, gap_offset))) = (start, gap) {
+            if gap_width >= CELL_GAP {
+                cells.push(Cell {
+                    start: cell_start,
+                    end: gap_start,
+                    text: &line[byte_start..gap_offset],
+                });
+                start = None;
+            }
+        }
+        start.get_or_insert((column, offset));
+        gap = None;
+        gap_width = 0;
+    }
+    if let (Some((cell_start, byte_start)), Some((gap_start, gap_offset))) = (start, gap) {
+        cells.push(Cell {
+            start: cell_start,
+            end: gap_start,
+            text: &line[byte_start..gap_offset],
+        });
+    }
+    cells
+}
+
+/// A table column: its kind and the span of characters it covers
+#[derive(Debug)]
+struct Column {
+    kind: ColumnKind,
+    start: usize,
+    end: usize,
+}
+
+fn header_kind(cell: &str) -> ColumnKind {
+    let lower = cell.to_lowercase();
+    HEADER_WORDS
+        .iter()
+        .find(|(_, words)| words.iter().any(|word| lower.starts_with(word)))
+        .map_or(ColumnKind::Other, |(kind, _)| *kind)
+}
+
+/// Columns of a header row, if the line is one. A header has no digits,
+/// names a description and an amount column, and at least one of quantity,
+/// unit price or GST. Each column reaches halfway to its neighbours.
+fn header_columns(line: &str) -> Option<Vec<Column>> {
+    let cells = split_cells(line);
+    if cells.len() < 3 || cells.iter().any(|cell| cell.text.chars().any(|c| c.is_ascii_digit())) {
+        return None;
+    }
+    let mut kinds: Vec<ColumnKind> = cells.iter().map(|cell| header_kind(cell.text)).collect();
+    // With amounts before and after GST, the last one is the line total
+    let last_amount = kinds.iter().rposition(|kind| *kind == ColumnKind::Amount)?;
+    for kind in &mut kinds[..last_amount] {
+        if *kind == ColumnKind::Amount {
+            *kind = ColumnKind::Other;
+        }
+    }
+    let has = |wanted: &[ColumnKind]| kinds.iter().any(|kind| wanted.contains(kind));
+    if !has(&[ColumnKind::Description])
+        || !has(&[ColumnKind::Quantity, ColumnKind::UnitPrice, ColumnKind::Gst])
+    {
+        return None;
+    }
+
+    let columns = cells
+        .iter()
+        .zip(&kinds)
+        .enumerate()
+        .map(|(i, (cell, kind))| Column {
+            kind: *kind,
+            start: if i == 0 { 0 } else { (cells[i - 1].end + cell.start) / 2 },
+            end: cells.get(i + 1).map_or(usize::MAX, |next| (cell.end + next.start) / 2),
+        })
+        .collect();
+    Some(columns)
+}
+
+/// Leading number of a quantity such as `2.5 hrs`
+fn parse_quantity(text: &str) -> Option<f64> {
+    text.split_whitespace().next()?.replace(',', "").parse().ok()
+}
+
+/// A row's cells gathered under their columns
+#[derive(Default)]
+struct Row {
+    description: Vec<String>,
+    quantity: Option<String>,
+    unit_price: Option<String>,
+    gst: Option<String>,
+    amount: Option<String>,
+    /// Text outside the description column
+    other_text: bool,
+    /// A description running past its column
+    overflows: bool,
+}
+
+fn read_row(line: &str, columns: &[Column]) -> Row {
+    let mut row = Row::default();
+    let column_at = |position: usize| columns.iter().find(|column| column.start <= position && position < column.end);
+    for cell in split_cells(line) {
+        // Descriptions run on past their header; other values sit under theirs
+        let column = match column_at(cell.start) {
+            Some(column) if column.kind == ColumnKind::Description => Some(column),
+            _ => column_at((cell.start + cell.end) / 2),
+        };
+        let slot = match column.map_or(ColumnKind::Other, |column| column.kind) {
+            ColumnKind::Description => {
+                row.overflows |= column.is_some_and(|column| cell.end > column.end);
+                row.description.push(cell.text.to_string());
+                continue;
+            }
+            ColumnKind::Quantity => &mut row.quantity,
+            ColumnKind::UnitPrice => &mut row.unit_price,
+            ColumnKind::Gst => &mut row.gst,
+            ColumnKind::Amount => &mut row.amount,
+            ColumnKind::Other => {
+                row.other_text = true;
+                continue;
+            }
+        };
+        row.other_text = true;
+        match slot {
+            Some(text) => {
+                text.push(' ');
+                text.push_str(cell.text);
+            }
+            None => *slot = Some(cell.text.to_string()),
+        }
+    }
+    row
+}
+
+/// Line items read column by column from each table in `text`, or none
+/// when no header row is found. A row `is_non_item` matches, such as the
+/// subtotal, ends a table; a line with only a description continues the
+/// item above it.
+pub fn extract_items(text: &str, is_non_item: impl Fn(&str) -> bool, confidence: f64) -> Vec<LineItem> {
+    let mut items: Vec<LineItem> = Vec::new();
+    let mut columns: Option<Vec<Column>> = None;
+    let mut continues_item = false;
+
+    for line in text.lines() {
+        if let Some(header) = header_columns(line) {
+            columns = Some(header);
+            continues_item = false;
+            continue;
+        }
+        let Some(table) = &columns else {
+            continue;
+        };
+        if line.trim().is_empty() {
+            continues_item = false;
+            continue;
+        }
+        if is_non_item(line.trim()) {
+            columns = None;
+            continue;
+        }
+
+        let row = read_row(line, table);
+        let description = row.description.join(" ");
+        let Some(total) = row.amount.as_deref().and_then(Money::parse) else {
+            match items.last_mut() {
+                Some(item) if continues_item && !row.other_text && !row.overflows && !description.is_empty() => {
+                    item.description = format!("{} {}", item.description, description);
+                }
+                _ => columns = None,
+            }
+            continue;
+        };
+        if description.is_empty() {
+            continues_item = false;
+            continue;
+        }
+        let gst_amount = row.gst.as_deref().and_then(Money::parse);
+        items.push(LineItem {
+            description,
+            quantity: row.quantity.as_deref().and_then(parse_quantity),
+            unit_price: row.unit_price.as_deref().and_then(Money::parse),
+            total,
+            confidence,
+            gst: gst_amount,
+            gst_code: row.gst.filter(|_| gst_amount.is_none()).map(|code| code.to_uppercase()),
+            ..Default::default()
+        });
+        continues_item = true;
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invoice::{DocumentType, InvoiceParser};
+
+    #[test]
+    fn test_items_are_read_column_by_column() {
+        let text = "Acme Plumbing Pty Ltd\n\
+            TAX INVOICE\n\
+            Description                 Qty    Unit Price   GST        Amount\n\
+            Callout fee                   1         90.00   GST         90.00\n\
+            Labour - after hours        2.5        120.00   GST        300.00\n\
+            \x20 incl. travel\n\
+            Disposal levy                           15.00   FRE         15.00\n\
+            Subtotal                                                   405.00\n\
+            GST                                                         39.00\n\
+            Total                                                      444.00";
+        let parser = InvoiceParser::new().unwrap();
+        let invoice = parser.parse_from_text(text, DocumentType::Pdf).unwrap();
+        let items: Vec<_> = invoice
+            .line_items
+            .iter()
+            .map(|item| {
+                let (unit_price, code) = (item.unit_price.map(Money::to_dollars), item.gst_code.as_deref());
+                (item.description.as_str(), item.quantity, unit_price, item.total.to_dollars(), code)
+            })
+            .collect();
+        assert_eq!(
+            items,
+            vec![
+                ("Callout fee", Some(1.0), Some(90.0), 90.0, Some("GST")),
+                ("Labour - after hours incl. travel", Some(2.5), Some(120.0), 300.0, Some("GST")),
+                ("Disposal levy", None, Some(15.0), 15.0, Some("FRE")),
+            ]
+        );
+
+        // GST amounts per line, an item code column and a total after GST
+        let text = "SKU     Item               Qty    Price     Amount ex GST    GST     Total\n\
+            A-100   Widget              2     10.00            20.00    2.00     22.00\n\
+            B-200   Gadget, large       1    100.00           100.00   10.00    110.00\n\
+            Thank you for your business";
+        let items = extract_items(text, |line| parser.is_non_item_line(line), 0.85);
+        assert_eq!(items.len(), 2);
+        assert_eq!((items[1].description.as_str(), items[1].total.to_dollars()), ("Gadget, large", 110.0));
+        assert_eq!((items[0].gst.map(Money::to_dollars), items[0].gst_code.as_deref()), (Some(2.0), None));
+
+        // No header: the per-line heuristic is used instead
+        assert!(extract_items("Widget 2 x 10.00 20.00", |_| false, 0.85).is_empty());
+    }
+}
@@ -0,0 +1,102 @@
+//! Keychain Module
+//!
+//! Secrets such as portal passwords are kept in the platform keychain rather
+//! than in settings.json: the Keychain on macOS, Credential Manager on
+//! Windows and the Secret Service on Linux. Each secret is stored under a
+//! service name and an account, such as an upload target's ID. Platforms
+//! without a keychain refuse to store secrets.
+
+/// A store of secrets by service and account
+pub trait Keychain: Send + Sync {
+    fn get(&self, service: &str, account: &str) -> Result<Option<String>, String>;
+    fn set(&self, service: &str, account: &str, secret: &str) -> Result<(), String>;
+    fn delete(&self, service: &str, account: &str) -> Result<(), String>;
+}
+
+/// Managed-state wrapper for the system keychain
+pub struct KeychainState(pub Box<dyn Keychain>);
+
+impl KeychainState {
+    pub fn system() -> Self {
+        #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+        return Self(Box::new(SystemKeychain));
+        #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+        return Self(Box::new(NoKeychain));
+    }
+}
+
+/// The platform keychain
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+struct SystemKeychain;
+
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+impl SystemKeychain {
+    fn entry(service: &str, account: &str) -> Result<keyring::Entry, String> {
+        keyring::Entry::new(service, account).map_err(|e| format!("Failed to open the keychain: {}", e))
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+impl Keychain for SystemKeychain {
+    fn get(&self, service: &str, account: &str) -> Result<Option<String>, String> {
+        match Self::entry(service, account)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(format!("Failed to read from the keychain: {}", e)),
+        }
+    }
+
+    fn set(&self, service: &str, account: &str, secret: &str) -> Result<(), String> {
+        Self::entry(service, account)?
+            .set_password(secret)
+            .map_err(|e| format!("Failed to save to the keychain: {}", e))
+    }
+
+    fn delete(&self, service: &str, account: &str) -> Result<(), String> {
+        match Self::entry(service, account)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!("Failed to remove from the keychain: {}", e)),
+        }
+    }
+}
+
+/// Stands in on platforms without a keychain
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+struct NoKeychain;
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+impl Keychain for NoKeychain {
+    fn get(&self, _service: &str, _account: &str) -> Result<Option<String>, String> {
+        Ok(None)
+    }
+
+    fn set(&self, _service: &str, _account: &str, _secret: &str) -> Result<(), String> {
+        Err("No system keychain is available to store the secret".to_string())
+    }
+
+    fn delete(&self, _service: &str, _account: &str) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Secrets held in memory, for tests
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryKeychain(std::sync::Mutex<std::collections::HashMap<(String, String), String>>);
+
+#[cfg(test)]
+impl Keychain for MemoryKeychain {
+    fn get(&self, service: &str, account: &str) -> Result<Option<String>, String> {
+        Ok(self.0.lock().unwrap().get(&(service.to_string(), account.to_string())).cloned())
+    }
+
+    fn set(&self, service: &str, account: &str, secret: &str) -> Result<(), String> {
+        self.0.lock().unwrap().insert((service.to_string(), account.to_string()), secret.to_string());
+        Ok(())
+    }
+
+    fn delete(&self, service: &str, account: &str) -> Result<(), String> {
+        self.0.lock().unwrap().remove(&(service.to_string(), account.to_string()));
+        Ok(())
+    }
+}
@@ -1,19 +1,87 @@
-mod ocr;
-mod invoice;
-mod tax_report;
+pub mod ocr;
+pub mod ocr_jobs;
+pub mod invoice;
+pub mod invoice_table;
+pub mod currency;
+pub mod tax_report;
+pub mod storage;
+pub mod documents;
+pub mod settings;
+pub mod network;
+pub mod abr;
+pub mod imaging;
+pub mod drafts;
+pub mod cadence;
+pub mod periods;
+pub mod bas;
+pub mod export;
+pub mod ml_extract;
+pub mod digest;
+pub mod cash;
+pub mod summary;
+pub mod rollover;
+pub mod heuristics;
+pub mod receipt;
+pub mod receipt_parser;
+pub mod package;
+pub mod entities;
+pub mod report_builder;
+pub mod time_tracking;
+pub mod classifier;
+pub mod address;
+pub mod preprocess;
+pub mod bundle;
+pub mod category_packs;
+pub mod money;
+pub mod sandbox;
+pub mod pdf_pages;
+pub mod capture;
+pub mod period_locks;
+pub mod text_normalize;
+pub mod stock;
+pub mod text_diff;
+pub mod quality;
+pub mod saved_reports;
+pub mod gst_treatment;
+pub mod thumbnails;
+pub mod finance_export;
+pub mod ocr_pool;
+pub mod share;
+pub mod segmentation;
+pub mod category_clusters;
+pub mod cloud_ocr;
+pub mod handwriting;
+pub mod vendor_templates;
+pub mod fy_comparison;
+pub mod receipt_archive;
+pub mod entity_groups;
+pub mod resource_limits;
+pub mod pdf_text;
+pub mod text_shaping;
+pub mod report_fonts;
+pub mod dates;
+pub mod pagination;
+pub mod anonymize;
+pub mod reminders;
+pub mod capture_metadata;
+pub mod category_rules;
+pub mod upload;
+pub mod csv_import;
+pub mod timeline;
+pub mod straight_through;
+pub mod email_import;
+pub mod verification;
+pub mod custom_patterns;
+pub mod business_use;
+pub mod keychain;
+mod commands;
 
-use ocr::{scan_receipt_ocr, validate_ocr_confidence};
-use invoice::{
-    parse_invoice_pdf, 
-    parse_invoice_image, 
-    validate_invoice,
-    ExtractedInvoice,
-    InvoiceValidationResult,
-};
-use tax_report::{
-    save_tax_report_pdf,
-    merge_pdfs,
-    TaxReportSaveResult,
+use tauri::Manager;
+
+use ocr::{
+    cancel_ocr_job, check_image_quality, get_ocr_lines, get_ocr_status, get_ocr_thresholds, ocr_engine_status,
+    scan_multi_receipt, scan_receipt_ocr, scan_receipt_region, scan_receipts_batch, set_ocr_config, set_ocr_thresholds,
+    validate_ocr_confidence,
 };
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -22,14 +90,141 @@ pub fn run() {
     .plugin(tauri_plugin_fs::init())
     .plugin(tauri_plugin_sql::Builder::default().build())
     .plugin(tauri_plugin_http::init())
+    .manage(ocr_pool::OcrEnginePool::default())
+    .manage(keychain::KeychainState::system())
     .invoke_handler(tauri::generate_handler![
       scan_receipt_ocr,
       validate_ocr_confidence,
-      parse_invoice_pdf_command,
-      parse_invoice_image_command,
-      validate_invoice_command,
-      save_tax_report_pdf_command,
-      merge_pdfs_command,
+      get_ocr_status,
+      set_ocr_config,
+      get_ocr_thresholds,
+      set_ocr_thresholds,
+      scan_receipts_batch,
+      scan_receipt_region,
+      scan_multi_receipt,
+      get_ocr_lines,
+      check_image_quality,
+      cancel_ocr_job,
+      ocr_engine_status,
+      commands::parse_invoice_pdf_command,
+      commands::parse_invoice_image_command,
+      commands::validate_invoice_command,
+      commands::save_tax_report_pdf_command,
+      commands::merge_pdfs_command,
+      commands::share_report_command,
+      commands::delete_report_command,
+      commands::list_saved_reports_command,
+      commands::list_saved_reports_page_command,
+      commands::regenerate_report_command,
+      commands::list_documents_command,
+      commands::list_documents_page_command,
+      commands::get_document_command,
+      commands::queue_thumbnails_command,
+      commands::request_thumbnails_command,
+      commands::save_document_command,
+      commands::save_manual_entry_command,
+      commands::preview_csv_import_command,
+      commands::import_csv_expenses_command,
+      commands::import_email_command,
+      commands::bulk_update_documents_command,
+      commands::get_calendar_heat_command,
+      commands::get_timeline_command,
+      commands::run_straight_through_command,
+      commands::get_auto_processed_digest_command,
+      commands::undo_auto_processed_command,
+      commands::get_settings_command,
+      commands::update_settings_command,
+      commands::get_line_item_skip_patterns_command,
+      commands::set_line_item_skip_patterns_command,
+      commands::get_network_status_command,
+      commands::report_power_state_command,
+      commands::lookup_abn_command,
+      commands::save_draft_command,
+      commands::get_draft_command,
+      commands::list_drafts_command,
+      commands::discard_draft_command,
+      commands::get_vendor_cadences_command,
+      commands::get_missing_invoice_alerts_command,
+      commands::get_invoices_needing_attention_command,
+      commands::set_reminder_schedule_command,
+      commands::suggest_vendor_from_location_command,
+      commands::get_travel_diary_entries_command,
+      commands::set_field_locks_command,
+      commands::reextract_document_command,
+      commands::diff_document_text_command,
+      commands::get_gst_credit_timing_report_command,
+      commands::get_bas_purchase_labels_command,
+      commands::set_gst_treatment_command,
+      commands::export_originals_command,
+      commands::export_ocr_text_command,
+      commands::export_anonymized_dataset_command,
+      commands::generate_weekly_digest_command,
+      commands::add_cash_expense_command,
+      commands::list_cash_expenses_command,
+      commands::list_cash_expenses_page_command,
+      commands::delete_cash_expense_command,
+      commands::get_cash_totals_command,
+      commands::get_financial_year_summary_command,
+      commands::get_fy_comparison_command,
+      commands::export_fy_comparison_pdf_command,
+      commands::get_documents_to_check_command,
+      commands::set_stock_value_command,
+      commands::list_stock_values_command,
+      commands::add_stock_adjustment_command,
+      commands::list_stock_adjustments_command,
+      commands::delete_stock_adjustment_command,
+      commands::rollover_financial_year_command,
+      commands::compare_heuristic_profiles_command,
+      commands::get_bank_match_amounts_command,
+      commands::export_handover_package_command,
+      commands::verify_package_command,
+      commands::list_upload_targets_command,
+      commands::save_upload_target_command,
+      commands::delete_upload_target_command,
+      commands::get_cloud_ocr_keys_command,
+      commands::set_cloud_ocr_key_command,
+      commands::upload_package_command,
+      commands::list_entities_command,
+      commands::save_entity_command,
+      commands::delete_entity_command,
+      commands::list_entity_groups_command,
+      commands::save_entity_group_command,
+      commands::delete_entity_group_command,
+      commands::get_entity_group_report_command,
+      commands::check_invoice_recipient_command,
+      commands::add_time_entry_command,
+      commands::list_time_entries_command,
+      commands::delete_time_entry_command,
+      commands::create_draft_invoice_from_time_command,
+      commands::list_issued_invoices_command,
+      commands::generate_issued_invoice_pdf_command,
+      commands::preclassify_import_command,
+      commands::list_skipped_imports_command,
+      commands::restore_skipped_import_command,
+      commands::parse_address_command,
+      commands::get_state_totals_command,
+      commands::export_document_bundle_command,
+      commands::export_finance_file_command,
+      commands::list_category_packs_command,
+      commands::set_category_pack_installed_command,
+      commands::list_entity_categories_command,
+      commands::suggest_document_category_command,
+      commands::suggest_category_clusters_command,
+      commands::list_category_rules_command,
+      commands::save_category_rule_command,
+      commands::delete_category_rule_command,
+      commands::dry_run_category_rules_command,
+      commands::register_custom_pattern_command,
+      commands::list_custom_patterns_command,
+      commands::delete_custom_pattern_command,
+      commands::list_vendor_templates_command,
+      commands::set_vendor_template_profile_command,
+      commands::delete_vendor_template_command,
+      commands::quick_capture_command,
+      commands::list_lodged_periods_command,
+      commands::lock_bas_period_command,
+      commands::unlock_bas_period_command,
+      commands::record_period_adjustment_command,
     ])
     .setup(|app| {
       if cfg!(debug_assertions) {
@@ -39,45 +234,30 @@ pub fn run() {
             .build(),
         )?;
       }
+      #[cfg(mobile)]
+      {
+        use tauri_plugin_share_sheet::ShareSheetExt;
+        app.handle().plugin(tauri_plugin_share_sheet::init())?;
+        tax_report::set_reports_directory(app.share_sheet().reports_directory()?);
+      }
+      app.manage(share::ShareSheetState::platform(app.handle()));
+      match digest::run_scheduled(chrono::Local::now().date_naive()) {
+        Ok(Some(result)) => log::info!("Weekly digest written to {}", result.file_path),
+        Ok(None) => {}
+        Err(e) => log::warn!("Scheduled digest failed: {}", e),
+      }
+      match reminders::notify_due(app.handle(), chrono::Local::now().date_naive()) {
+        Ok(0) => {}
+        Ok(count) => log::info!("Sent {} invoice reminders", count),
+        Err(e) => log::warn!("Invoice reminders failed: {}", e),
+      }
+      match capture::resume_pending(app.handle()) {
+        Ok(0) => {}
+        Ok(count) => log::info!("Resumed {} unfinished captures", count),
+        Err(e) => log::warn!("Failed to resume captures: {}", e),
+      }
       Ok(())
     })
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
 }
-
-/// Tauri command to parse a PDF invoice
-#[tauri::command]
-async fn parse_invoice_pdf_command(pdf_path: String) -> Result<ExtractedInvoice, String> {
-    invoice::parse_invoice_pdf(&pdf_path)
-}
-
-/// Tauri command to parse an image invoice (via OCR text extraction)
-/// In production, this would first run OCR then parse the extracted text
-#[tauri::command]
-async fn parse_invoice_image_command(image_path: String) -> Result<ExtractedInvoice, String> {
-    invoice::parse_invoice_image(&image_path)
-}
-
-/// Tauri command to validate extracted invoice data
-#[tauri::command]
-async fn validate_invoice_command(invoice: ExtractedInvoice) -> InvoiceValidationResult {
-    invoice::validate_invoice(&invoice)
-}
-
-/// Tauri command to save a tax report PDF
-#[tauri::command]
-async fn save_tax_report_pdf_command(
-    filename: String,
-    pdf_data: Vec<u8>,
-) -> Result<TaxReportSaveResult, String> {
-    tax_report::save_tax_report_pdf(filename, pdf_data).await
-}
-
-/// Tauri command to merge multiple PDFs into one
-#[tauri::command]
-async fn merge_pdfs_command(
-    pdf_paths: Vec<String>,
-    output_filename: String,
-) -> Result<TaxReportSaveResult, String> {
-    tax_report::merge_pdfs(pdf_paths, output_filename).await
-}
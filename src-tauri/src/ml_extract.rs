@@ -0,0 +1,454 @@
+//! Layout Model Extraction Module
+//!
+//! Optional on-device key-value extraction using an ONNX token-classification
+//! model (LayoutLM-style), ensembled with the regex parser to improve recall
+//! on unusual invoice layouts.
+//!
+//! The model runtime is only compiled with the `ml-extract` feature. A model
+//! directory contains:
+//! - `model.onnx` taking `input_ids`, `attention_mask` and `bbox` and
+//!   producing `logits`
+//! - `vocab.txt` WordPiece vocabulary
+//! - `labels.json` BIO labels such as `B-total_amount`, `I-vendor_name`, `O`
+
+// Decoding and ensembling are only reachable from the model runtime
+#![cfg_attr(not(feature = "ml-extract"), allow(dead_code))]
+
+use serde::{Deserialize, Serialize};
+
+use crate::currency::CurrencyAmount;
+use crate::invoice::{ExtractedField, ExtractedInvoice, InvoiceParser};
+use crate::money::Money;
+
+/// Confidence bonus when the model and regexes agree on a value
+const AGREEMENT_BOOST: f64 = 0.05;
+
+/// Model confidences are discounted slightly relative to regex matches
+const MODEL_WEIGHT: f64 = 0.9;
+
+/// Source tag for values found by the model
+const MODEL_SOURCE: &str = "layout_model";
+
+/// A word with a bounding box normalised to 0-1000, as LayoutLM expects
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutToken {
+    pub text: String,
+    pub bbox: [i64; 4],
+}
+
+/// A field value predicted by the layout model
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FieldPrediction {
+    /// ExtractedInvoice field name, e.g. `total_amount`
+    pub field: String,
+    pub value: String,
+    pub confidence: f64,
+}
+
+/// Split text into words with approximate boxes from line and column positions.
+///
+/// Used when no OCR word geometry is available (e.g. PDF text layers).
+pub fn layout_tokens(text: &str) -> Vec<LayoutToken> {
+    let lines: Vec<&str> = text.lines().collect();
+    let line_count = lines.len().max(1) as i64;
+    let max_cols = lines.iter().map(|l| l.chars().count()).max().unwrap_or(1).max(1) as i64;
+
+    let mut tokens = Vec::new();
+    for (row, line) in lines.iter().enumerate() {
+        let y0 = row as i64 * 1000 / line_count;
+        let y1 = (row as i64 + 1) * 1000 / line_count;
+
+        let mut col = 0i64;
+        for word in line.split(' ') {
+            let len = word.chars().count() as i64;
+            if len > 0 {
+                tokens.push(LayoutToken {
+                    text: word.to_string(),
+                    bbox: [col * 1000 / max_cols, y0, (col + len) * 1000 / max_cols, y1],
+                });
+            }
+            col += len + 1;
+        }
+    }
+    tokens
+}
+
+/// Combine BIO-labelled words into field predictions, keeping the most
+/// confident span per field
+pub fn decode_bio(words: &[String], labels: &[(String, f64)]) -> Vec<FieldPrediction> {
+    type Span<'a> = (String, Vec<&'a str>, Vec<f64>);
+
+    fn finish(span: Option<Span>, spans: &mut Vec<FieldPrediction>) {
+        if let Some((field, parts, scores)) = span {
+            spans.push(FieldPrediction {
+                field,
+                value: parts.join(" "),
+                confidence: scores.iter().sum::<f64>() / scores.len() as f64,
+            });
+        }
+    }
+
+    let mut spans: Vec<FieldPrediction> = Vec::new();
+    let mut current: Option<Span> = None;
+
+    for (word, (label, score)) in words.iter().zip(labels) {
+        if let Some(field) = label.strip_prefix("B-") {
+            finish(current.take(), &mut spans);
+            current = Some((field.to_string(), vec![word.as_str()], vec![*score]));
+        } else if let Some(field) = label.strip_prefix("I-") {
+            match current.as_mut() {
+                Some((current_field, parts, scores)) if current_field == field => {
+                    parts.push(word);
+                    scores.push(*score);
+                }
+                _ => {
+                    finish(current.take(), &mut spans);
+                    current = Some((field.to_string(), vec![word.as_str()], vec![*score]));
+                }
+            }
+        } else {
+            finish(current.take(), &mut spans);
+        }
+    }
+    finish(current.take(), &mut spans);
+
+    let mut best: Vec<FieldPrediction> = Vec::new();
+    for span in spans {
+        match best.iter_mut().find(|b| b.field == span.field) {
+            Some(existing) if existing.confidence >= span.confidence => {}
+            Some(existing) => *existing = span,
+            None => best.push(span),
+        }
+    }
+    best
+}
+
+/// Merge model predictions into a regex-parsed invoice.
+///
+/// Missing fields are filled from the model, agreeing values get a
+/// confidence boost, and disagreements keep the more confident value.
+pub fn ensemble(mut invoice: ExtractedInvoice, predictions: &[FieldPrediction]) -> ExtractedInvoice {
+    let currency = invoice.currency_code().to_string();
+    for prediction in predictions {
+        let confidence = prediction.confidence * MODEL_WEIGHT;
+        match prediction.field.as_str() {
+            "vendor_name" => merge_text(&mut invoice.vendor_name, &prediction.value, confidence),
+            "invoice_number" => merge_text(&mut invoice.invoice_number, &prediction.value, confidence),
+            "invoice_date" => merge_text(&mut invoice.invoice_date, &prediction.value, confidence),
+            "due_date" => merge_text(&mut invoice.due_date, &prediction.value, confidence),
+            "payment_terms" => merge_text(&mut invoice.payment_terms, &prediction.value, confidence),
+            "abn" => {
+                let abn: String = prediction.value.chars().filter(|c| c.is_ascii_digit()).collect();
+                if InvoiceParser::validate_abn(&abn) {
+                    merge_text(&mut invoice.abn, &abn, confidence);
+                }
+            }
+            "total_amount" => merge_amount(&mut invoice.total_amount, &prediction.value, &currency, confidence),
+            "gst_amount" => merge_amount(&mut invoice.gst_amount, &prediction.value, &currency, confidence),
+            _ => {}
+        }
+    }
+
+    // The model reads amounts unsigned
+    invoice.apply_subtype_sign();
+    if let Ok(parser) = InvoiceParser::new() {
+        invoice.overall_confidence = parser.calculate_confidence(&invoice);
+    }
+    invoice
+}
+
+fn merge_text(field: &mut Option<ExtractedField<String>>, value: &str, confidence: f64) {
+    let value = value.trim();
+    if value.is_empty() {
+        return;
+    }
+    match field {
+        Some(existing) if existing.value.eq_ignore_ascii_case(value) => agree(existing),
+        Some(existing) if existing.confidence >= confidence => {}
+        _ => *field = Some(ExtractedField::new(value.to_string(), confidence, MODEL_SOURCE)),
+    }
+}
+
+/// Merge an amount the model read, taking it to be in the invoice's currency
+fn merge_amount(field: &mut Option<ExtractedField<CurrencyAmount>>, value: &str, currency: &str, confidence: f64) {
+    let cleaned: String = value.chars().filter(|c| c.is_ascii_digit() || *c == '.').collect();
+    let Ok(amount) = cleaned.parse::<f64>().map(Money::from_dollars) else {
+        return;
+    };
+    match field {
+        Some(existing) if existing.value.value.abs() == amount => agree(existing),
+        Some(existing) if existing.confidence >= confidence => {}
+        _ => *field = Some(ExtractedField::new(CurrencyAmount::new(amount, currency), confidence, MODEL_SOURCE)),
+    }
+}
+
+fn agree<T>(field: &mut ExtractedField<T>) {
+    field.confidence = (field.confidence + AGREEMENT_BOOST).min(1.0);
+    field.source = format!("{}+{}", field.source, MODEL_SOURCE);
+}
+
+/// Run the layout model over a parsed invoice when enabled in settings.
+///
+/// Model failures are logged and the regex result is returned unchanged.
+#[cfg(feature = "ml-extract")]
+pub fn refine_with_layout_model(invoice: ExtractedInvoice) -> ExtractedInvoice {
+    use std::sync::{Mutex, OnceLock};
+
+    static MODEL: OnceLock<Mutex<Option<model::LayoutModel>>> = OnceLock::new();
+
+    let settings = crate::settings::load_settings().unwrap_or_default();
+    if !settings.layout_model_enabled {
+        return invoice;
+    }
+
+    let predictions = {
+        let cell = MODEL.get_or_init(|| Mutex::new(None));
+        let Ok(mut guard) = cell.lock() else {
+            return invoice;
+        };
+        if guard.is_none() {
+            match model::default_model_dir().and_then(|dir| model::LayoutModel::load(&dir)) {
+                Ok(loaded) => *guard = Some(loaded),
+                Err(e) => {
+                    log::warn!("Layout model unavailable: {}", e);
+                    return invoice;
+                }
+            }
+        }
+        match guard.as_mut().map(|m| m.predict(&invoice.raw_text)) {
+            Some(Ok(predictions)) => predictions,
+            Some(Err(e)) => {
+                log::warn!("Layout model inference failed: {}", e);
+                return invoice;
+            }
+            None => return invoice,
+        }
+    };
+
+    ensemble(invoice, &predictions)
+}
+
+/// Without the `ml-extract` feature the regex result is used as-is
+#[cfg(not(feature = "ml-extract"))]
+pub fn refine_with_layout_model(invoice: ExtractedInvoice) -> ExtractedInvoice {
+    invoice
+}
+
+#[cfg(feature = "ml-extract")]
+mod model {
+    use ort::session::Session;
+    use ort::value::Tensor;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use super::{decode_bio, layout_tokens, FieldPrediction};
+    use crate::storage;
+
+    /// LayoutLM-family models accept at most 512 positions
+    const MAX_SEQUENCE: usize = 512;
+
+    pub fn default_model_dir() -> Result<PathBuf, String> {
+        let settings = crate::settings::load_settings()?;
+        match settings.layout_model_path {
+            Some(path) => Ok(PathBuf::from(path)),
+            None => Ok(storage::get_data_directory()?.join("models").join("layout")),
+        }
+    }
+
+    pub struct LayoutModel {
+        session: Session,
+        vocab: HashMap<String, i64>,
+        labels: Vec<String>,
+    }
+
+    impl LayoutModel {
+        pub fn load(dir: &Path) -> Result<Self, String> {
+            let session = Session::builder()
+                .and_then(|b| b.commit_from_file(dir.join("model.onnx")))
+                .map_err(|e| format!("Failed to load layout model: {}", e))?;
+
+            let vocab = fs::read_to_string(dir.join("vocab.txt"))
+                .map_err(|e| format!("Failed to read vocabulary: {}", e))?
+                .lines()
+                .enumerate()
+                .map(|(i, token)| (token.to_string(), i as i64))
+                .collect();
+
+            let labels_json = fs::read_to_string(dir.join("labels.json"))
+                .map_err(|e| format!("Failed to read labels: {}", e))?;
+            let labels = serde_json::from_str(&labels_json)
+                .map_err(|e| format!("Failed to parse labels: {}", e))?;
+
+            Ok(Self { session, vocab, labels })
+        }
+
+        pub fn predict(&mut self, text: &str) -> Result<Vec<FieldPrediction>, String> {
+            let tokens = layout_tokens(text);
+            let cls = self.token_id("[CLS]");
+            let sep = self.token_id("[SEP]");
+
+            // Sub-token inputs, remembering the first sub-token of each word
+            let mut ids = vec![cls];
+            let mut boxes = vec![[0i64; 4]];
+            let mut word_starts = Vec::new();
+            let mut words = Vec::new();
+            for token in &tokens {
+                let pieces = self.wordpiece(&token.text.to_lowercase());
+                if ids.len() + pieces.len() >= MAX_SEQUENCE {
+                    break;
+                }
+                word_starts.push(ids.len());
+                words.push(token.text.clone());
+                for piece in pieces {
+                    ids.push(piece);
+                    boxes.push(token.bbox);
+                }
+            }
+            ids.push(sep);
+            boxes.push([1000; 4]);
+
+            let len = ids.len();
+            let input_ids = Tensor::from_array(([1usize, len], ids))
+                .map_err(|e| format!("Failed to build input tensor: {}", e))?;
+            let attention_mask = Tensor::from_array(([1usize, len], vec![1i64; len]))
+                .map_err(|e| format!("Failed to build input tensor: {}", e))?;
+            let bbox = Tensor::from_array(([1usize, len, 4], boxes.concat()))
+                .map_err(|e| format!("Failed to build input tensor: {}", e))?;
+
+            let outputs = self
+                .session
+                .run(ort::inputs![
+                    "input_ids" => input_ids,
+                    "attention_mask" => attention_mask,
+                    "bbox" => bbox,
+                ])
+                .map_err(|e| format!("Layout model inference failed: {}", e))?;
+            let (_, logits) = outputs["logits"]
+                .try_extract_tensor::<f32>()
+                .map_err(|e| format!("Unexpected layout model output: {}", e))?;
+
+            let label_count = self.labels.len();
+            let word_labels: Vec<(String, f64)> = word_starts
+                .iter()
+                .map(|&position| {
+                    let row = &logits[position * label_count..(position + 1) * label_count];
+                    let (best, probability) = softmax_argmax(row);
+                    (self.labels[best].clone(), probability)
+                })
+                .collect();
+
+            Ok(decode_bio(&words, &word_labels))
+        }
+
+        fn token_id(&self, token: &str) -> i64 {
+            self.vocab.get(token).copied().unwrap_or(0)
+        }
+
+        /// Greedy longest-match WordPiece tokenisation
+        fn wordpiece(&self, word: &str) -> Vec<i64> {
+            let chars: Vec<char> = word.chars().collect();
+            let mut pieces = Vec::new();
+            let mut start = 0;
+
+            while start < chars.len() {
+                let mut end = chars.len();
+                let mut found = None;
+                while start < end {
+                    let mut candidate: String = chars[start..end].iter().collect();
+                    if start > 0 {
+                        candidate = format!("##{}", candidate);
+                    }
+                    if let Some(&id) = self.vocab.get(&candidate) {
+                        found = Some(id);
+                        break;
+                    }
+                    end -= 1;
+                }
+                match found {
+                    Some(id) => {
+                        pieces.push(id);
+                        start = end;
+                    }
+                    None => return vec![self.token_id("[UNK]")],
+                }
+            }
+            pieces
+        }
+    }
+
+    fn softmax_argmax(logits: &[f32]) -> (usize, f64) {
+        let max = logits.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let exp: Vec<f64> = logits.iter().map(|&l| ((l - max) as f64).exp()).collect();
+        let sum: f64 = exp.iter().sum();
+        let (best, value) = exp
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+            .unwrap_or((0, &0.0));
+        (best, value / sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invoice::DocumentType;
+
+    #[test]
+    fn test_layout_tokens_positions() {
+        let tokens = layout_tokens("ACME PTY LTD\nTotal 110.00");
+        assert_eq!(tokens.len(), 5);
+        assert_eq!(tokens[0].text, "ACME");
+        assert_eq!(tokens[0].bbox[1], 0);
+        assert_eq!(tokens[3].bbox[1], 500);
+    }
+
+    #[test]
+    fn test_decode_bio_spans() {
+        let words: Vec<String> = ["Acme", "Widgets", "Total", "$110.00"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let labels = vec![
+            ("B-vendor_name".to_string(), 0.9),
+            ("I-vendor_name".to_string(), 0.7),
+            ("O".to_string(), 0.99),
+            ("B-total_amount".to_string(), 0.95),
+        ];
+
+        let predictions = decode_bio(&words, &labels);
+        assert_eq!(predictions.len(), 2);
+        assert_eq!(predictions[0].value, "Acme Widgets");
+        assert!((predictions[0].confidence - 0.8).abs() < 1e-9);
+        assert_eq!(predictions[1].field, "total_amount");
+    }
+
+    #[test]
+    fn test_ensemble_fills_and_boosts() {
+        let parser = InvoiceParser::new().unwrap();
+        let invoice = parser
+            .parse_from_text("Total: $110.00", DocumentType::Pdf)
+            .unwrap();
+        let regex_confidence = invoice.total_amount.as_ref().unwrap().confidence;
+
+        let predictions = vec![
+            FieldPrediction {
+                field: "total_amount".to_string(),
+                value: "$110.00".to_string(),
+                confidence: 0.8,
+            },
+            FieldPrediction {
+                field: "invoice_number".to_string(),
+                value: "A-1001".to_string(),
+                confidence: 0.9,
+            },
+        ];
+        let merged = ensemble(invoice, &predictions);
+
+        let total = merged.total_amount.unwrap();
+        assert!(total.confidence > regex_confidence);
+        assert_eq!(total.source, "amount_regex+layout_model");
+        assert_eq!(merged.invoice_number.unwrap().source, MODEL_SOURCE);
+    }
+}
@@ -0,0 +1,226 @@
+//! Money Module
+//!
+//! Exact cent arithmetic for totals and GST. Extracted invoice and receipt
+//! amounts are held as `Money`, and every sum of the `f64` dollars stored on
+//! documents goes through whole cents here, so GST checks and reports don't
+//! drift from floating point error. `Money` is serialized as a number of
+//! dollars, such as `12.34`, so the frontend and saved files read it as
+//! before.
+//!
+//! Rounding follows ATO guidance:
+//! - GST on a taxable sale is rounded to the nearest cent, with half a cent
+//!   rounded up
+//! - Amounts reported at BAS labels are whole dollars, with cents dropped
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Neg, Sub};
+
+/// An amount of money in whole cents
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money(i64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    /// Convert a dollar amount, rounding to the nearest cent with half a
+    /// cent rounded away from zero. Values like 1.005 that can't be held
+    /// exactly in binary are first settled to six decimal places so they
+    /// round the way they were written.
+    pub fn from_dollars(dollars: f64) -> Self {
+        if !dollars.is_finite() {
+            return Self::ZERO;
+        }
+        let micros = (dollars.abs() * 1_000_000.0).round() as i64;
+        let cents = (micros + 5_000) / 10_000;
+        Self(if dollars < 0.0 { -cents } else { cents })
+    }
+
+    /// Read an amount as printed, such as `$1,234.50`; the dollar sign,
+    /// thousands separators and spaces are ignored
+    pub fn parse(text: &str) -> Option<Self> {
+        let cleaned: String = text.chars().filter(|c| !matches!(c, '$' | ',') && !c.is_whitespace()).collect();
+        cleaned.parse::<f64>().ok().filter(|dollars| dollars.is_finite()).map(Self::from_dollars)
+    }
+
+    pub const fn from_cents(cents: i64) -> Self {
+        Self(cents)
+    }
+
+    pub fn to_dollars(self) -> f64 {
+        self.0 as f64 / 100.0
+    }
+
+    pub fn cents(self) -> i64 {
+        self.0
+    }
+
+    pub fn abs(self) -> Money {
+        Self(self.0.abs())
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Price of `quantity` units at this unit price, rounded to the nearest
+    /// cent
+    pub fn times(self, quantity: f64) -> Money {
+        Self::from_dollars(self.to_dollars() * quantity)
+    }
+
+    /// GST payable on a GST-exclusive price (ten percent), rounded to the
+    /// nearest cent
+    pub fn gst_on_exclusive(self) -> Money {
+        Self(div_round_half_up(self.0, 10))
+    }
+
+    /// GST included in a GST-inclusive price (one eleventh), rounded to the
+    /// nearest cent
+    pub fn gst_in_inclusive(self) -> Money {
+        Self(div_round_half_up(self.0, 11))
+    }
+
+    /// Whole dollars as reported at a BAS label. Cents are dropped rather
+    /// than rounded.
+    pub fn bas_whole_dollars(self) -> i64 {
+        self.0 / 100
+    }
+}
+
+/// Divide cents, rounding half away from zero
+fn div_round_half_up(cents: i64, divisor: i64) -> i64 {
+    let quotient = (cents.abs() * 2 + divisor) / (divisor * 2);
+    if cents < 0 {
+        -quotient
+    } else {
+        quotient
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        Money(self.0 + other.0)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Money) {
+        self.0 += other.0;
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Money) -> Money {
+        Money(self.0 - other.0)
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        Money(iter.map(|m| m.0).sum())
+    }
+}
+
+impl<'a> Sum<&'a Money> for Money {
+    fn sum<I: Iterator<Item = &'a Money>>(iter: I) -> Money {
+        iter.copied().sum()
+    }
+}
+
+/// Two decimal places without a currency sign, such as `-12.30`
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        write!(f, "{}{}.{:02}", sign, self.0.abs() / 100, self.0.abs() % 100)
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.to_dollars())
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        f64::deserialize(deserializer).map(Money::from_dollars)
+    }
+}
+
+/// Round a dollar amount to the nearest cent
+pub fn round_cents(dollars: f64) -> f64 {
+    Money::from_dollars(dollars).to_dollars()
+}
+
+/// Add dollar amounts cent by cent, so the result is exact to the cent
+pub fn sum_dollars(amounts: impl IntoIterator<Item = f64>) -> f64 {
+    amounts.into_iter().map(Money::from_dollars).sum::<Money>().to_dollars()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cent_rounding_and_sums() {
+        assert_eq!(Money::from_dollars(1.005).to_dollars(), 1.01);
+        assert_eq!(Money::from_dollars(1.0049).to_dollars(), 1.0);
+        assert_eq!(Money::from_dollars(-2.675).to_dollars(), -2.68);
+        assert_eq!(round_cents(0.1 + 0.2), 0.3);
+
+        // Naive f64 addition drifts; cent sums don't
+        let amounts = vec![0.1; 10];
+        assert_ne!(amounts.iter().sum::<f64>(), 1.0);
+        assert_eq!(sum_dollars(amounts), 1.0);
+    }
+
+    #[test]
+    fn test_gst_rounding_and_bas_truncation() {
+        // $1.05 exclusive: 10.5 cents rounds up to 11
+        assert_eq!(Money::from_dollars(1.05).gst_on_exclusive().to_dollars(), 0.11);
+        assert_eq!(Money::from_dollars(-1.05).gst_on_exclusive().to_dollars(), -0.11);
+        assert_eq!(Money::from_dollars(1.04).gst_on_exclusive().to_dollars(), 0.10);
+
+        // GST is rounded per sale, not on the combined total
+        let sales = [Money::from_dollars(1.05), Money::from_dollars(1.05)];
+        let per_sale: Money = sales.iter().map(|s| s.gst_on_exclusive()).sum();
+        assert_eq!(per_sale.to_dollars(), 0.22);
+        assert_eq!(sales.iter().sum::<Money>().gst_on_exclusive().to_dollars(), 0.21);
+
+        assert_eq!(Money::from_dollars(1234.99).bas_whole_dollars(), 1234);
+        assert_eq!(Money::from_dollars(-10.75).bas_whole_dollars(), -10);
+    }
+
+    #[test]
+    fn test_serialized_as_dollars() {
+        let amount = Money::from_cents(-1230);
+        assert_eq!(amount.to_string(), "-12.30");
+        assert_eq!(serde_json::to_string(&amount).unwrap(), "-12.3");
+        assert_eq!(serde_json::from_str::<Money>("1.005").unwrap(), Money::from_cents(101));
+        assert_eq!(serde_json::from_str::<Money>("42").unwrap(), Money::from_cents(4200));
+        assert_eq!(Money::from_dollars(19.99).times(3.0), Money::from_cents(5997));
+    }
+
+    #[test]
+    fn test_printed_amounts_parse() {
+        assert_eq!(Money::parse("$1,234.50"), Some(Money::from_cents(123450)));
+        assert_eq!(Money::parse(" 1 234.5 "), Some(Money::from_cents(123450)));
+        assert_eq!(Money::parse("-12.30"), Some(Money::from_cents(-1230)));
+        assert_eq!(Money::parse("inf"), None);
+        assert_eq!(Money::parse("N/A"), None);
+    }
+}
@@ -0,0 +1,135 @@
+//! Network Policy Module
+//!
+//! Central gate for outbound network calls. When privacy mode is enabled
+//! every networked feature is blocked and must fall back to offline data,
+//! marking its results with `Provenance::Offline` instead of failing.
+
+use serde::{Deserialize, Serialize};
+
+use crate::settings::AppSettings;
+
+/// Features that may contact an external service
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkFeature {
+    /// Australian Business Register lookups
+    AbnLookup,
+    /// Currency exchange rates
+    ExchangeRates,
+    /// ATO industry benchmark data
+    Benchmarks,
+    /// Cloud OCR fallback for receipts read poorly on the device
+    CloudOcr,
+    /// Uploading handover packages to an accountant's portal
+    PortalUpload,
+}
+
+impl NetworkFeature {
+    pub const ALL: [NetworkFeature; 5] = [
+        NetworkFeature::AbnLookup,
+        NetworkFeature::ExchangeRates,
+        NetworkFeature::Benchmarks,
+        NetworkFeature::CloudOcr,
+        NetworkFeature::PortalUpload,
+    ];
+}
+
+/// Where a piece of data came from
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Provenance {
+    /// Fetched from the external service
+    Network,
+    /// Served from previously fetched data
+    Cache,
+    /// Produced without network access
+    Offline,
+}
+
+/// A value annotated with its provenance
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Sourced<T> {
+    pub value: Option<T>,
+    pub provenance: Provenance,
+    /// Why the value is offline or missing
+    pub note: Option<String>,
+}
+
+impl<T> Sourced<T> {
+    pub fn network(value: T) -> Self {
+        Self {
+            value: Some(value),
+            provenance: Provenance::Network,
+            note: None,
+        }
+    }
+
+    /// Offline fallback, optionally carrying locally derived data
+    pub fn offline(value: Option<T>, note: &str) -> Self {
+        Self {
+            value,
+            provenance: Provenance::Offline,
+            note: Some(note.to_string()),
+        }
+    }
+}
+
+/// Whether a feature may use the network
+#[derive(Debug, Serialize, Clone)]
+pub struct NetworkAccess {
+    pub feature: NetworkFeature,
+    pub allowed: bool,
+    pub reason: Option<String>,
+}
+
+/// Current network policy for every feature
+#[derive(Debug, Serialize, Clone)]
+pub struct NetworkStatus {
+    pub privacy_mode: bool,
+    pub features: Vec<NetworkAccess>,
+}
+
+/// Check whether a feature may make outbound calls under the given settings
+pub fn check_access(feature: NetworkFeature, settings: &AppSettings) -> NetworkAccess {
+    if settings.privacy_mode {
+        NetworkAccess {
+            feature,
+            allowed: false,
+            reason: Some("Privacy mode is enabled".to_string()),
+        }
+    } else {
+        NetworkAccess {
+            feature,
+            allowed: true,
+            reason: None,
+        }
+    }
+}
+
+/// Report the network policy for all features
+pub fn network_status(settings: &AppSettings) -> NetworkStatus {
+    NetworkStatus {
+        privacy_mode: settings.privacy_mode,
+        features: NetworkFeature::ALL
+            .iter()
+            .map(|&feature| check_access(feature, settings))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_privacy_mode_blocks_all_features() {
+        let settings = AppSettings {
+            privacy_mode: true,
+            ..Default::default()
+        };
+        let status = network_status(&settings);
+        assert_eq!(status.features.len(), NetworkFeature::ALL.len());
+        assert!(status.features.iter().all(|f| !f.allowed));
+
+        let status = network_status(&AppSettings::default());
+        assert!(status.features.iter().all(|f| f.allowed));
+    }
+}
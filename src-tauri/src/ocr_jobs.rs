@@ -0,0 +1,164 @@
+//! OCR Jobs Module
+//!
+//! Runs OCR scans in the background so the invoke returns a job id
+//! straight away. Each job has a cancel token that the engine checks
+//! between stages (decoding, each page, each file of a batch); a running
+//! Tesseract pass can't be interrupted, so cancellation takes effect at
+//! the next stage. The result is delivered through `OCR_JOB_EVENT`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::ocr::{BatchOcrResult, ExtractedReceipt};
+use crate::storage;
+
+/// Event emitted when a job finishes, fails or is cancelled
+pub const OCR_JOB_EVENT: &str = "ocr://job";
+
+/// Error returned by work stopped through its cancel token
+pub const CANCELLED: &str = "OCR job cancelled";
+
+/// Jobs still running, by id
+static JOBS: Mutex<Option<HashMap<String, CancelToken>>> = Mutex::new(None);
+
+/// Shared flag telling a job to stop
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// `Err(CANCELLED)` once the job has been cancelled
+    pub fn check(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            Err(CANCELLED.to_string())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OcrJobStatus {
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// What a job produced
+#[derive(Debug, Clone)]
+pub enum OcrJobOutput {
+    Receipt(Box<ExtractedReceipt>),
+    Batch(Vec<BatchOcrResult>),
+}
+
+/// Payload of `OCR_JOB_EVENT`
+#[derive(Debug, Serialize, Clone)]
+pub struct OcrJobEvent {
+    pub job_id: String,
+    pub status: OcrJobStatus,
+    pub error: Option<String>,
+    /// Set for single-receipt scans
+    pub receipt: Option<ExtractedReceipt>,
+    /// Set for batch scans
+    pub results: Option<Vec<BatchOcrResult>>,
+}
+
+impl OcrJobEvent {
+    fn new(job_id: String, token: &CancelToken, result: Result<OcrJobOutput, String>) -> Self {
+        let mut event = Self {
+            job_id,
+            status: OcrJobStatus::Completed,
+            error: None,
+            receipt: None,
+            results: None,
+        };
+        match result {
+            Ok(OcrJobOutput::Receipt(receipt)) => event.receipt = Some(*receipt),
+            // A cancelled batch still reports the files finished before it stopped
+            Ok(OcrJobOutput::Batch(results)) => {
+                if token.is_cancelled() {
+                    event.status = OcrJobStatus::Cancelled;
+                }
+                event.results = Some(results);
+            }
+            Err(e) => {
+                event.status = if token.is_cancelled() { OcrJobStatus::Cancelled } else { OcrJobStatus::Failed };
+                event.error = Some(e);
+            }
+        }
+        event
+    }
+}
+
+fn with_jobs<R>(f: impl FnOnce(&mut HashMap<String, CancelToken>) -> R) -> R {
+    let mut jobs = JOBS.lock().unwrap_or_else(|e| e.into_inner());
+    f(jobs.get_or_insert_with(HashMap::new))
+}
+
+/// Run `work` on a background thread and pass its outcome to `notify`,
+/// returning the job id
+pub fn start(
+    work: impl FnOnce(&CancelToken) -> Result<OcrJobOutput, String> + Send + 'static,
+    notify: impl FnOnce(OcrJobEvent) + Send + 'static,
+) -> String {
+    let job_id = storage::generate_id("ocr");
+    let token = CancelToken::default();
+    with_jobs(|jobs| jobs.insert(job_id.clone(), token.clone()));
+
+    let id = job_id.clone();
+    thread::spawn(move || {
+        let result = work(&token);
+        with_jobs(|jobs| jobs.remove(&id));
+        notify(OcrJobEvent::new(id, &token, result));
+    });
+    job_id
+}
+
+/// Ask a running job to stop; `false` if it already finished or never existed
+pub fn cancel(job_id: &str) -> bool {
+    with_jobs(|jobs| jobs.get(job_id).map(CancelToken::cancel).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_cancelled_job_reports_cancelled() {
+        let (release, wait) = mpsc::channel::<()>();
+        let (done, events) = mpsc::channel();
+        let job_id = start(
+            move |token| {
+                wait.recv().unwrap();
+                token.check()?;
+                Err("unreachable".to_string())
+            },
+            move |event| done.send(event).unwrap(),
+        );
+
+        assert!(cancel(&job_id));
+        release.send(()).unwrap();
+        let event = events.recv().unwrap();
+        assert_eq!(event.job_id, job_id);
+        assert_eq!(event.status, OcrJobStatus::Cancelled);
+        assert_eq!(event.error.as_deref(), Some(CANCELLED));
+        // Finished jobs leave the registry
+        assert!(!cancel(&job_id));
+
+        let (done, events) = mpsc::channel();
+        start(|_| Err("unreadable".to_string()), move |event| done.send(event).unwrap());
+        assert_eq!(events.recv().unwrap().status, OcrJobStatus::Failed);
+    }
+}
@@ -0,0 +1,226 @@
+//! OCR Engine Pool Module
+//!
+//! Starting Tesseract loads its language data, which can take longer than
+//! recognising a receipt. Engines are kept in a small pool in Tauri managed
+//! state and lent to scans, so only the first scan pays for initialisation.
+//! The pool is emptied when the languages or language data path change.
+//! No more engines are lent at once than the resource limits allow; further
+//! scans wait for one to be returned.
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::ocr::{OcrConfig, OcrEngine};
+use crate::ocr_jobs::CancelToken;
+use crate::settings::AppSettings;
+
+/// Idle engines kept loaded; batches borrow more and drop the extras
+const MAX_IDLE_ENGINES: usize = 2;
+
+/// How often a scan waiting for an engine checks whether it was cancelled
+const WAIT_POLL: Duration = Duration::from_millis(200);
+
+/// Settings an engine is initialised with; engines are only reused while
+/// these stay the same
+#[derive(Debug, Clone, PartialEq)]
+struct EngineKey {
+    config: OcrConfig,
+    tessdata_path: Option<String>,
+}
+
+impl EngineKey {
+    fn from_settings(settings: &AppSettings) -> Self {
+        Self {
+            config: settings.ocr.clone(),
+            tessdata_path: settings.tessdata_path.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct PoolState {
+    key: Option<EngineKey>,
+    idle: Vec<OcrEngine>,
+    in_use: usize,
+    created: usize,
+    last_error: Option<String>,
+}
+
+/// Readiness of the pooled OCR engines
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OcrEngineStatus {
+    /// `tesseract`, or `mock` in builds without OCR
+    pub engine: String,
+    /// An engine is loaded for the current settings, so the next scan
+    /// starts without initialisation
+    pub ready: bool,
+    /// Languages the loaded engines recognise
+    pub languages: Vec<String>,
+    pub idle_engines: usize,
+    pub engines_in_use: usize,
+    /// Engines initialised since the app started
+    pub engines_created: usize,
+    /// Why the last engine failed to initialise
+    pub last_error: Option<String>,
+}
+
+/// Initialised OCR engines shared by scans
+#[derive(Default)]
+pub struct OcrEnginePool {
+    state: Mutex<PoolState>,
+    /// Signalled whenever an engine is returned
+    returned: Condvar,
+}
+
+impl OcrEnginePool {
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Borrow an engine for the given settings, initialising one when none
+    /// is idle. Waits while the concurrency limit's worth of engines are
+    /// lent out. Return it with `checkin` when the scan is done.
+    pub fn checkout(&self, settings: &AppSettings, cancel: CancelToken) -> Result<OcrEngine, String> {
+        let key = EngineKey::from_settings(settings);
+        let limit = settings.resource_limits.max_concurrent_ocr.max(1);
+        let idle = {
+            let mut state = self.lock();
+            while state.in_use >= limit {
+                cancel.check()?;
+                state = self
+                    .returned
+                    .wait_timeout(state, WAIT_POLL)
+                    .map_or_else(|e| e.into_inner().0, |(state, _)| state);
+            }
+            if state.key.as_ref() != Some(&key) {
+                state.idle.clear();
+                state.key = Some(key.clone());
+            }
+            state.in_use += 1;
+            state.idle.pop()
+        };
+
+        let engine = match idle {
+            Some(engine) => Ok(engine),
+            // Initialised without the lock so other scans aren't held up
+            None => {
+                let created = OcrEngine::with_tessdata(key.config, key.tessdata_path.as_deref());
+                let mut state = self.lock();
+                match &created {
+                    Ok(_) => {
+                        state.created += 1;
+                        state.last_error = None;
+                    }
+                    Err(e) => {
+                        state.in_use -= 1;
+                        state.last_error = Some(e.clone());
+                        self.returned.notify_one();
+                    }
+                }
+                created
+            }
+        }?;
+
+        Ok(engine
+            .with_decode_limits(settings.decode_limits.clone())
+            .with_upscale(settings.ocr_upscale.clone())
+            .with_preprocess(settings.ocr_preprocess.clone())
+            .with_resource_limits(&settings.resource_limits)
+            .with_cancel_token(cancel))
+    }
+
+    /// Return a borrowed engine. It is kept if the settings it was made for
+    /// are still current and the pool has room.
+    pub fn checkin(&self, engine: OcrEngine, settings: &AppSettings) {
+        let mut state = self.lock();
+        state.in_use = state.in_use.saturating_sub(1);
+        if state.key.as_ref() == Some(&EngineKey::from_settings(settings)) && state.idle.len() < MAX_IDLE_ENGINES {
+            state.idle.push(engine.with_cancel_token(CancelToken::default()).with_fallback(None, 0.0));
+        }
+        self.returned.notify_one();
+    }
+
+    /// Run `f` with a borrowed engine, returning it afterwards
+    pub fn with_engine<R>(
+        &self,
+        settings: &AppSettings,
+        cancel: CancelToken,
+        f: impl FnOnce(&mut OcrEngine) -> Result<R, String>,
+    ) -> Result<R, String> {
+        let mut engine = self.checkout(settings, cancel)?;
+        let result = f(&mut engine);
+        self.checkin(engine, settings);
+        result
+    }
+
+    /// Whether scans with `settings` will find an engine ready
+    pub fn status(&self, settings: &AppSettings) -> OcrEngineStatus {
+        let state = self.lock();
+        let current = state.key.as_ref() == Some(&EngineKey::from_settings(settings));
+        OcrEngineStatus {
+            engine: if cfg!(feature = "ocr-tesseract") { "tesseract" } else { "mock" }.to_string(),
+            ready: current && !state.idle.is_empty(),
+            languages: state.key.as_ref().map_or_else(|| settings.ocr.languages.clone(), |k| k.config.languages.clone()),
+            idle_engines: if current { state.idle.len() } else { 0 },
+            engines_in_use: state.in_use,
+            engines_created: state.created,
+            last_error: state.last_error.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource_limits::ResourceLimits;
+
+    #[test]
+    fn test_engines_are_reused_until_settings_change() {
+        let pool = OcrEnginePool::default();
+        let mut settings = AppSettings::default();
+        assert!(!pool.status(&settings).ready);
+
+        let first = pool.checkout(&settings, CancelToken::default()).unwrap();
+        let second = pool.checkout(&settings, CancelToken::default()).unwrap();
+        let third = pool.checkout(&settings, CancelToken::default()).unwrap();
+        assert_eq!(pool.status(&settings).engines_in_use, 3);
+        for engine in [first, second, third] {
+            pool.checkin(engine, &settings);
+        }
+        let status = pool.status(&settings);
+        assert!(status.ready);
+        assert_eq!((status.idle_engines, status.engines_in_use, status.engines_created), (MAX_IDLE_ENGINES, 0, 3));
+
+        pool.with_engine(&settings, CancelToken::default(), |_| Ok(())).unwrap();
+        assert_eq!(pool.status(&settings).engines_created, 3);
+
+        // A language change needs freshly initialised engines
+        settings.ocr.languages.push("chi_sim".to_string());
+        assert!(!pool.status(&settings).ready);
+        pool.with_engine(&settings, CancelToken::default(), |_| Ok(())).unwrap();
+        let status = pool.status(&settings);
+        assert_eq!((status.idle_engines, status.engines_created), (1, 4));
+        assert_eq!(status.languages, vec!["eng".to_string(), "chi_sim".to_string()]);
+
+        settings.ocr.page_segmentation_mode = 99;
+        assert!(pool.checkout(&settings, CancelToken::default()).is_err());
+        assert!(pool.status(&settings).last_error.is_some());
+        assert_eq!(pool.status(&settings).engines_in_use, 0);
+
+        // Scans beyond the concurrency limit wait for an engine; cancelled ones give up
+        let limited = AppSettings {
+            resource_limits: ResourceLimits {
+                max_concurrent_ocr: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let engine = pool.checkout(&limited, CancelToken::default()).unwrap();
+        let cancelled = CancelToken::default();
+        cancelled.cancel();
+        assert!(pool.checkout(&limited, cancelled).is_err());
+        pool.checkin(engine, &limited);
+        assert!(pool.checkout(&limited, CancelToken::default()).is_ok());
+    }
+}
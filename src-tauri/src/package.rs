@@ -0,0 +1,328 @@
+//! Handover Package Module
+//!
+//! Builds a ZIP for handing documents to an accountant. Alongside the
+//! originals (under canonical filenames) the package carries an index page,
+//! a README and a SHA-256 checksum manifest so the recipient can confirm
+//! nothing is missing or altered.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::documents::StoredDocument;
+use crate::export::{self, SkippedExport};
+use crate::storage;
+
+/// Manifest file name inside the package
+pub const MANIFEST_NAME: &str = "manifest.json";
+
+/// A file recorded in the manifest
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub sha256: String,
+    pub size: u64,
+    pub document_id: Option<String>,
+}
+
+/// Checksum manifest stored in the package
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PackageManifest {
+    pub created_at: String,
+    pub document_count: usize,
+    pub files: Vec<ManifestEntry>,
+}
+
+/// Result of writing a package
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PackageResult {
+    pub package_path: String,
+    pub manifest: PackageManifest,
+    pub skipped: Vec<SkippedExport>,
+}
+
+/// Result of checking a package against its manifest
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PackageVerification {
+    pub valid: bool,
+    pub checked: usize,
+    /// Listed in the manifest but absent from the package
+    pub missing: Vec<String>,
+    /// Present but with a different checksum
+    pub mismatched: Vec<String>,
+    /// Present but not listed in the manifest
+    pub unexpected: Vec<String>,
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn zip_error(e: impl std::fmt::Display) -> String {
+    format!("Failed to write package: {}", e)
+}
+
+/// Write documents and their supporting files into a ZIP at `package_path`
+pub fn create_package(documents: &[StoredDocument], package_path: &Path) -> Result<PackageResult, String> {
+    if let Some(parent) = package_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    let file = File::create(package_path).map_err(|e| format!("Failed to create package: {}", e))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default();
+
+    let mut manifest = PackageManifest {
+        created_at: storage::now_timestamp(),
+        ..Default::default()
+    };
+    let mut skipped = Vec::new();
+    let mut included: Vec<(&StoredDocument, String)> = Vec::new();
+    let mut used_names = HashSet::new();
+
+    for doc in documents {
+        let data = match doc.source_path.as_deref().map(fs::read) {
+            Some(Ok(data)) => data,
+            Some(Err(e)) => {
+                skipped.push(SkippedExport {
+                    document_id: doc.id.clone(),
+                    reason: format!("Failed to read original: {}", e),
+                });
+                continue;
+            }
+            None => {
+                skipped.push(SkippedExport {
+                    document_id: doc.id.clone(),
+                    reason: "No original file".to_string(),
+                });
+                continue;
+            }
+        };
+
+        let path = format!("documents/{}", unique_name(&export::canonical_filename(doc), &mut used_names));
+        zip.start_file(path.as_str(), options).map_err(zip_error)?;
+        zip.write_all(&data).map_err(zip_error)?;
+
+        manifest.files.push(ManifestEntry {
+            path: path.clone(),
+            sha256: sha256_hex(&data),
+            size: data.len() as u64,
+            document_id: Some(doc.id.clone()),
+        });
+        included.push((doc, path));
+    }
+    manifest.document_count = included.len();
+
+    let supporting = [
+        ("index.html".to_string(), render_index(&included, &manifest.created_at)),
+        ("README.txt".to_string(), render_readme(included.len())),
+    ];
+    for (path, contents) in supporting {
+        zip.start_file(path.as_str(), options).map_err(zip_error)?;
+        zip.write_all(contents.as_bytes()).map_err(zip_error)?;
+        manifest.files.push(ManifestEntry {
+            sha256: sha256_hex(contents.as_bytes()),
+            size: contents.len() as u64,
+            path,
+            document_id: None,
+        });
+    }
+
+    // Plain checksum list for `sha256sum -c SHA256SUMS`
+    let sums: String = manifest
+        .files
+        .iter()
+        .map(|f| format!("{}  {}\n", f.sha256, f.path))
+        .collect();
+    zip.start_file("SHA256SUMS", options).map_err(zip_error)?;
+    zip.write_all(sums.as_bytes()).map_err(zip_error)?;
+
+    let manifest_json = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+    zip.start_file(MANIFEST_NAME, options).map_err(zip_error)?;
+    zip.write_all(manifest_json.as_bytes()).map_err(zip_error)?;
+    zip.finish().map_err(zip_error)?;
+
+    Ok(PackageResult {
+        package_path: package_path.to_string_lossy().to_string(),
+        manifest,
+        skipped,
+    })
+}
+
+/// Re-hash every file in a package and compare against its manifest
+pub fn verify_package(package_path: &Path) -> Result<PackageVerification, String> {
+    let file = File::open(package_path).map_err(|e| format!("Failed to open package: {}", e))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| format!("Not a valid package: {}", e))?;
+
+    let mut hashes = BTreeMap::new();
+    let mut manifest = None;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(|e| format!("Failed to read package: {}", e))?;
+        if entry.is_dir() {
+            continue;
+        }
+        let mut data = Vec::new();
+        entry
+            .read_to_end(&mut data)
+            .map_err(|e| format!("Failed to read {}: {}", entry.name(), e))?;
+
+        if entry.name() == MANIFEST_NAME {
+            manifest = Some(
+                serde_json::from_slice::<PackageManifest>(&data)
+                    .map_err(|e| format!("Invalid manifest: {}", e))?,
+            );
+        } else {
+            hashes.insert(entry.name().to_string(), sha256_hex(&data));
+        }
+    }
+    let manifest = manifest.ok_or_else(|| "Package has no manifest".to_string())?;
+
+    let mut result = PackageVerification::default();
+    let mut listed = HashSet::new();
+    for file in &manifest.files {
+        listed.insert(file.path.as_str());
+        match hashes.get(&file.path) {
+            None => result.missing.push(file.path.clone()),
+            Some(hash) if *hash != file.sha256 => result.mismatched.push(file.path.clone()),
+            Some(_) => {}
+        }
+        result.checked += 1;
+    }
+    result.unexpected = hashes
+        .keys()
+        .filter(|path| path.as_str() != "SHA256SUMS" && !listed.contains(path.as_str()))
+        .cloned()
+        .collect();
+
+    result.valid = result.missing.is_empty() && result.mismatched.is_empty() && result.unexpected.is_empty();
+    Ok(result)
+}
+
+fn unique_name(filename: &str, used: &mut HashSet<String>) -> String {
+    let path = Path::new(filename);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(filename);
+    let extension = path.extension().and_then(|e| e.to_str());
+
+    let mut candidate = filename.to_string();
+    let mut counter = 2;
+    while used.contains(&candidate) {
+        candidate = match extension {
+            Some(ext) => format!("{}_{}.{}", stem, counter, ext),
+            None => format!("{}_{}", stem, counter),
+        };
+        counter += 1;
+    }
+    used.insert(candidate.clone());
+    candidate
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render_index(included: &[(&StoredDocument, String)], created_at: &str) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Document package</title>\
+         <style>body{font-family:sans-serif}table{border-collapse:collapse}\
+         td,th{padding:4px 8px;border-bottom:1px solid #ddd;text-align:left}</style></head><body>",
+    );
+    html.push_str(&format!(
+        "<h1>Document package</h1><p>{} documents, prepared {}.</p>",
+        included.len(),
+        escape(created_at)
+    ));
+    html.push_str("<table><tr><th>Date</th><th>Vendor</th><th>Category</th><th>Total</th><th>GST</th><th>File</th></tr>");
+    for (doc, path) in included {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td><a href=\"{}\">{}</a></td></tr>",
+            escape(doc.date.as_deref().unwrap_or("")),
+            escape(doc.vendor.as_deref().unwrap_or("")),
+            escape(doc.category.as_deref().unwrap_or("")),
+            doc.total.map(|t| format!("${}", t)).unwrap_or_default(),
+            doc.gst.map(|g| format!("${}", g)).unwrap_or_default(),
+            escape(path),
+            escape(path.trim_start_matches("documents/")),
+        ));
+    }
+    html.push_str("</table></body></html>\n");
+    html
+}
+
+fn render_readme(document_count: usize) -> String {
+    format!(
+        "Document package\n\
+         ================\n\n\
+         This package contains {} documents in the documents/ folder.\n\
+         Open index.html for a list with dates, vendors and amounts.\n\n\
+         Checking the package\n\
+         --------------------\n\
+         manifest.json lists every file with its SHA-256 checksum.\n\
+         To check the files from a terminal, extract the package and run:\n\n\
+         \x20   sha256sum -c SHA256SUMS\n",
+        document_count
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_round_trip_and_tamper_detection() {
+        let dir = storage::test_dir();
+        let source = dir.join("IMG_0001.jpg");
+        fs::write(&source, b"receipt bytes").unwrap();
+
+        let docs = vec![
+            StoredDocument {
+                id: "a".to_string(),
+                vendor: Some("Officeworks".to_string()),
+                date: Some("2024-02-01".to_string()),
+                source_path: Some(source.to_string_lossy().to_string()),
+                ..Default::default()
+            },
+            StoredDocument {
+                id: "b".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        let package = dir.join("handover.zip");
+        let result = create_package(&docs, &package).unwrap();
+        assert_eq!(result.manifest.document_count, 1);
+        assert_eq!(result.skipped.len(), 1);
+
+        let verification = verify_package(&package).unwrap();
+        assert!(verification.valid, "{:?}", verification);
+        assert_eq!(verification.checked, 3);
+
+        // Rewrite the package with one document altered
+        let tampered = dir.join("tampered.zip");
+        let mut archive = ZipArchive::new(File::open(&package).unwrap()).unwrap();
+        let mut writer = ZipWriter::new(File::create(&tampered).unwrap());
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).unwrap();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            if entry.name().starts_with("documents/") {
+                data = b"altered".to_vec();
+            }
+            writer.start_file(entry.name(), SimpleFileOptions::default()).unwrap();
+            writer.write_all(&data).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let verification = verify_package(&tampered).unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.mismatched, vec!["documents/2024-02-01_officeworks.jpg".to_string()]);
+    }
+}
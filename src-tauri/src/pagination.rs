@@ -0,0 +1,194 @@
+//! Pagination Module
+//!
+//! Cursor-based paging for long lists. Items are sorted on a chosen field
+//! with their ID as a tie-breaker, so the order is stable. The cursor holds
+//! the sort value and ID of the last item returned rather than an offset, so
+//! adding or removing items between requests never repeats or skips any.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Items per page when the request doesn't say
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+
+/// Largest page a request can ask for
+pub const MAX_PAGE_SIZE: usize = 500;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+/// Sorting and position for one page of a list
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct PageRequest<S> {
+    pub sort_by: S,
+    pub direction: SortDirection,
+    /// `next_cursor` of the previous page; the first page when unset
+    pub cursor: Option<String>,
+    /// Items per page, up to `MAX_PAGE_SIZE`
+    pub limit: Option<usize>,
+}
+
+/// One page of a sorted list
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Items matching the filter across all pages
+    pub total_count: usize,
+    /// Pass back to fetch the following page; `None` on the last page
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            total_count: self.total_count,
+            next_cursor: self.next_cursor,
+        }
+    }
+}
+
+/// Value an item is sorted on. Missing values sort first.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    Missing,
+    Number(f64),
+    Text(String),
+}
+
+impl SortKey {
+    /// Text compared case-insensitively
+    pub fn text(value: &str) -> Self {
+        SortKey::Text(value.to_lowercase())
+    }
+
+    pub fn optional_text(value: Option<&str>) -> Self {
+        value.map_or(SortKey::Missing, SortKey::text)
+    }
+
+    pub fn optional_number(value: Option<f64>) -> Self {
+        value.map_or(SortKey::Missing, SortKey::Number)
+    }
+
+    fn compare(&self, other: &SortKey) -> Ordering {
+        match (self, other) {
+            (SortKey::Number(a), SortKey::Number(b)) => a.total_cmp(b),
+            (SortKey::Text(a), SortKey::Text(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            SortKey::Missing => 0,
+            SortKey::Number(_) => 1,
+            SortKey::Text(_) => 2,
+        }
+    }
+}
+
+/// Sort `items` and return the page after the request's cursor
+pub fn paginate<T, S: Copy>(
+    items: Vec<T>,
+    request: &PageRequest<S>,
+    key: impl Fn(&T, S) -> SortKey,
+    id: impl Fn(&T) -> &str,
+) -> Result<Page<T>, String> {
+    let limit = request.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let order = |a: (&SortKey, &str), b: (&SortKey, &str)| {
+        let ordering = a.0.compare(b.0).then_with(|| a.1.cmp(b.1));
+        match request.direction {
+            SortDirection::Ascending => ordering,
+            SortDirection::Descending => ordering.reverse(),
+        }
+    };
+
+    let mut keyed: Vec<(SortKey, T)> = items.into_iter().map(|item| (key(&item, request.sort_by), item)).collect();
+    keyed.sort_by(|a, b| order((&a.0, id(&a.1)), (&b.0, id(&b.1))));
+    let total_count = keyed.len();
+
+    let start = match request.cursor.as_deref() {
+        Some(cursor) => {
+            let (after_key, after_id) = decode_cursor(cursor)?;
+            keyed
+                .iter()
+                .position(|(key, item)| order((key, id(item)), (&after_key, &after_id)) == Ordering::Greater)
+                .unwrap_or(total_count)
+        }
+        None => 0,
+    };
+    let mut page: Vec<(SortKey, T)> = keyed.into_iter().skip(start).take(limit + 1).collect();
+    let next_cursor = if page.len() > limit {
+        page.truncate(limit);
+        page.last().map(|(key, item)| encode_cursor(key, id(item)))
+    } else {
+        None
+    };
+
+    Ok(Page {
+        items: page.into_iter().map(|(_, item)| item).collect(),
+        total_count,
+        next_cursor,
+    })
+}
+
+fn encode_cursor(key: &SortKey, id: &str) -> String {
+    let json = serde_json::to_string(&(key, id)).unwrap_or_default();
+    json.bytes().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_cursor(cursor: &str) -> Result<(SortKey, String), String> {
+    let invalid = || "Invalid page cursor".to_string();
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| cursor.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(invalid)?;
+    serde_json::from_slice(&bytes).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, Default)]
+    struct ByAmount;
+
+    fn fetch(items: &[(&'static str, Option<f64>)], request: &PageRequest<ByAmount>) -> Page<&'static str> {
+        let page = paginate(items.to_vec(), request, |item, _| SortKey::optional_number(item.1), |item| item.0);
+        page.unwrap().map(|item| item.0)
+    }
+
+    #[test]
+    fn test_cursor_pages_are_stable_when_items_change() {
+        let mut items = vec![("a", Some(5.0)), ("b", Some(1.0)), ("c", None), ("d", Some(5.0)), ("e", Some(3.0))];
+        let mut request = PageRequest {
+            limit: Some(2),
+            direction: SortDirection::Descending,
+            ..PageRequest::default()
+        };
+
+        let first = fetch(&items, &request);
+        assert_eq!(first.items, vec!["d", "a"]);
+        assert_eq!(first.total_count, 5);
+
+        // An item added ahead of the cursor and one removed after it
+        items.push(("f", Some(9.0)));
+        items.retain(|item| item.0 != "b");
+        request.cursor = first.next_cursor;
+        let second = fetch(&items, &request);
+        assert_eq!(second.items, vec!["e", "c"]);
+        assert_eq!(second.total_count, 5);
+        assert!(second.next_cursor.is_none());
+
+        request.cursor = Some("not a cursor".to_string());
+        assert!(paginate(items, &request, |_, _| SortKey::Missing, |item| item.0).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+//! PDF Pages Module
+//!
+//! Page images for receipts that arrive as scanned PDFs with no text layer.
+//! With the `pdf-render` feature pages are rendered by PDFium. Without it,
+//! the scanned image embedded in each page is extracted directly, which
+//! covers PDFs written by scanners and phone scanning apps.
+
+use image::DynamicImage;
+use lopdf::Document;
+use std::path::Path;
+
+use crate::imaging::DecodeLimits;
+use crate::pdf_text;
+
+/// Letters and digits a PDF needs before it counts as having a text layer
+const MIN_TEXT_CHARS: usize = 20;
+
+/// Width pages are rendered at, roughly 240 dpi for A4
+#[cfg(feature = "pdf-render")]
+const RENDER_WIDTH: i32 = 2000;
+
+/// Text layer of a PDF, or `None` for image-only (scanned) PDFs
+pub fn text_layer(path: &Path) -> Result<Option<String>, String> {
+    let doc = Document::load(path).map_err(|e| format!("Failed to read PDF {}: {}", path.display(), e))?;
+    let text = pdf_text::document_text(&doc).unwrap_or_default();
+    let chars = text.chars().filter(|c| c.is_alphanumeric()).count();
+    Ok((chars >= MIN_TEXT_CHARS).then_some(text))
+}
+
+/// Render every page of a PDF to an image for OCR
+#[cfg(feature = "pdf-render")]
+pub fn render_pages(path: &Path, limits: &DecodeLimits) -> Result<Vec<DynamicImage>, String> {
+    use pdfium_render::prelude::*;
+
+    let bindings = Pdfium::bind_to_system_library().map_err(|e| format!("PDFium not available: {}", e))?;
+    let pdfium = Pdfium::new(bindings);
+    let doc = pdfium
+        .load_pdf_from_file(path, None)
+        .map_err(|e| format!("Failed to read PDF {}: {}", path.display(), e))?;
+    let config = PdfRenderConfig::new()
+        .set_target_width(RENDER_WIDTH)
+        .set_maximum_height(limits.max_height.min(i32::MAX as u32) as i32);
+
+    doc.pages()
+        .iter()
+        .map(|page| {
+            page.render_with_config(&config)
+                .map(|bitmap| bitmap.as_image())
+                .map_err(|e| format!("Failed to render PDF page: {}", e))
+        })
+        .collect()
+}
+
+/// Extract the scanned image from every page of a PDF for OCR
+#[cfg(not(feature = "pdf-render"))]
+pub fn render_pages(path: &Path, limits: &DecodeLimits) -> Result<Vec<DynamicImage>, String> {
+    let doc = Document::load(path).map_err(|e| format!("Failed to read PDF {}: {}", path.display(), e))?;
+    let mut pages = Vec::new();
+    for (number, page_id) in doc.get_pages() {
+        let Some(image) = largest_page_image(&doc, page_id, limits)? else {
+            log::warn!("No scanned image found on page {} of {}", number, path.display());
+            continue;
+        };
+        pages.push(image);
+    }
+    if pages.is_empty() {
+        return Err(format!("No page images found in {}", path.display()));
+    }
+    Ok(pages)
+}
+
+/// Decode the largest image drawn on a page; scans are one image per page
+#[cfg(not(feature = "pdf-render"))]
+fn largest_page_image(
+    doc: &Document,
+    page_id: lopdf::ObjectId,
+    limits: &DecodeLimits,
+) -> Result<Option<DynamicImage>, String> {
+    let Ok(images) = doc.get_page_images(page_id) else {
+        return Ok(None);
+    };
+    let Some(largest) = images.iter().max_by_key(|i| i.width * i.height) else {
+        return Ok(None);
+    };
+
+    let (width, height) = (largest.width.max(0) as u32, largest.height.max(0) as u32);
+    if width > limits.max_width || height > limits.max_height {
+        return Err(crate::imaging::ImageDecodeError::DimensionsTooLarge {
+            width,
+            height,
+            max_width: limits.max_width,
+            max_height: limits.max_height,
+        }
+        .to_string());
+    }
+
+    let filters = largest.filters.clone().unwrap_or_default();
+    let image = if filters.iter().any(|f| f == "DCTDecode") {
+        image::load_from_memory_with_format(largest.content, image::ImageFormat::Jpeg)
+            .map_err(|e| format!("Failed to decode page image: {}", e))?
+    } else {
+        let Some(pixels) = raw_pixels(doc, largest.id)? else {
+            return Ok(None);
+        };
+        match (largest.color_space.as_deref(), largest.bits_per_component) {
+            (Some("DeviceGray"), Some(8)) => image::GrayImage::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8),
+            (Some("DeviceRGB"), Some(8)) => image::RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8),
+            _ => None,
+        }
+        .ok_or_else(|| "Unsupported page image format".to_string())?
+    };
+    Ok(Some(crate::imaging::downscale_to_fit(image, limits.downscale_above)))
+}
+
+/// Decompress an image stream's pixel data
+#[cfg(not(feature = "pdf-render"))]
+fn raw_pixels(doc: &Document, id: lopdf::ObjectId) -> Result<Option<Vec<u8>>, String> {
+    let stream = doc
+        .get_object(id)
+        .and_then(|o| o.as_stream())
+        .map_err(|e| format!("Failed to read page image: {}", e))?;
+    // lopdf won't decompress image streams, so decode a copy without the subtype
+    let mut stream = stream.clone();
+    stream.dict.remove(b"Subtype");
+    Ok(stream.get_plain_content().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Object, Stream};
+
+    /// A one-page PDF with an embedded grayscale scan and no text
+    fn scanned_pdf(path: &Path) {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let pixels: Vec<u8> = (0..40 * 20).map(|i| if i % 7 == 0 { 0 } else { 255 }).collect();
+        let mut scan = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => 40,
+                "Height" => 20,
+                "ColorSpace" => "DeviceGray",
+                "BitsPerComponent" => 8,
+            },
+            pixels,
+        );
+        scan.compress().unwrap();
+        let scan_id = doc.add_object(scan);
+        let content_id = doc.add_object(Stream::new(dictionary! {}, b"q 400 0 0 200 0 0 cm /Im0 Do Q".to_vec()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 400.into(), 200.into()],
+            "Contents" => content_id,
+            "Resources" => dictionary! { "XObject" => dictionary! { "Im0" => scan_id } },
+        });
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        doc.save(path).unwrap();
+    }
+
+    #[test]
+    fn test_scanned_pdf_has_no_text_layer_and_yields_page_images() {
+        let dir = crate::storage::test_dir();
+        let path = dir.join("scan.pdf");
+        scanned_pdf(&path);
+
+        assert!(text_layer(&path).unwrap().is_none());
+        #[cfg(not(feature = "pdf-render"))]
+        {
+            let pages = render_pages(&path, &DecodeLimits::default()).unwrap();
+            assert_eq!(pages.len(), 1);
+            assert_eq!((pages[0].width(), pages[0].height()), (40, 20));
+        }
+    }
+}
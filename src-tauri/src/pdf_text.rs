@@ -0,0 +1,402 @@
+//! PDF Text Module
+//!
+//! Reads the text layer of a PDF with its layout intact. Invoice generators
+//! usually place every cell of a table with its own positioning operators,
+//! and a plain dump of the text operators puts each cell on a line of its
+//! own, pulling amounts away from their labels. Here each piece of text is
+//! positioned on the page, pieces sharing a baseline are joined into one
+//! line, and the gaps between them are kept as spaces so columns line up.
+
+use lopdf::content::Operation;
+use lopdf::{Dictionary, Document, Encoding, Object, ObjectId};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Pieces of text whose baselines are closer than this, as a fraction of
+/// the font size, are on the same line
+const SAME_LINE: f32 = 0.4;
+
+/// Gaps wider than this, as a fraction of the font size, separate words
+const WORD_GAP: f32 = 0.2;
+
+/// Width of a character cell, as a fraction of the font size, when
+/// converting horizontal positions to columns
+const CELL_WIDTH: f32 = 0.5;
+
+/// Glyph width, in thousandths of the font size, for fonts without widths
+const DEFAULT_GLYPH_WIDTH: f32 = 500.0;
+
+/// 2D transform `[a b c d e f]`, as used by the `cm` and `Tm` operators
+type Matrix = [f32; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// `a` applied first, then `b`
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    [
+        a[0] * b[0] + a[1] * b[2],
+        a[0] * b[1] + a[1] * b[3],
+        a[2] * b[0] + a[3] * b[2],
+        a[2] * b[1] + a[3] * b[3],
+        a[4] * b[0] + a[5] * b[2] + b[4],
+        a[4] * b[1] + a[5] * b[3] + b[5],
+    ]
+}
+
+fn translate(tx: f32, ty: f32) -> Matrix {
+    [1.0, 0.0, 0.0, 1.0, tx, ty]
+}
+
+/// A piece of text placed on the page
+#[derive(Debug, Clone)]
+struct TextRun {
+    x: f32,
+    y: f32,
+    end_x: f32,
+    size: f32,
+    text: String,
+}
+
+/// What the page's fonts need for decoding and measuring text
+struct PageFont<'a> {
+    encoding: Option<Encoding<'a>>,
+    /// Character codes are two bytes (composite fonts)
+    two_byte: bool,
+    first_char: i64,
+    widths: Vec<f32>,
+}
+
+impl<'a> PageFont<'a> {
+    fn load(doc: &'a Document, font: &'a Dictionary) -> Self {
+        let two_byte = font.get(b"Subtype").and_then(Object::as_name).is_ok_and(|s| s == b"Type0");
+        let widths = font
+            .get(b"Widths")
+            .and_then(|w| doc.dereference(w))
+            .and_then(|(_, w)| w.as_array())
+            .map(|w| w.iter().map(|v| number(v).unwrap_or(DEFAULT_GLYPH_WIDTH)).collect())
+            .unwrap_or_default();
+        Self {
+            encoding: font.get_font_encoding(doc).ok(),
+            two_byte,
+            first_char: font.get(b"FirstChar").and_then(Object::as_i64).unwrap_or(0),
+            widths,
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> String {
+        self.encoding
+            .as_ref()
+            .and_then(|encoding| Document::decode_text(encoding, bytes).ok())
+            .unwrap_or_else(|| bytes.iter().map(|&b| b as char).collect())
+    }
+
+    /// Width of each glyph as a fraction of the font size, and whether it
+    /// is a word space
+    fn glyphs(&self, bytes: &[u8]) -> Vec<(f32, bool)> {
+        if self.two_byte {
+            return bytes.chunks(2).map(|_| (DEFAULT_GLYPH_WIDTH / 1000.0, false)).collect();
+        }
+        bytes
+            .iter()
+            .map(|&code| {
+                let width = usize::try_from(code as i64 - self.first_char)
+                    .ok()
+                    .and_then(|i| self.widths.get(i).copied())
+                    .unwrap_or(DEFAULT_GLYPH_WIDTH);
+                (width / 1000.0, code == b' ')
+            })
+            .collect()
+    }
+}
+
+fn number(object: &Object) -> Option<f32> {
+    object.as_float().ok()
+}
+
+fn numbers<const N: usize>(operands: &[Object]) -> Option<[f32; N]> {
+    let mut values = [0.0; N];
+    for (value, operand) in values.iter_mut().zip(operands) {
+        *value = number(operand)?;
+    }
+    (operands.len() >= N).then_some(values)
+}
+
+/// Graphics and text state while reading a page's content stream
+struct TextState {
+    ctm: Matrix,
+    saved: Vec<Matrix>,
+    matrix: Matrix,
+    line_matrix: Matrix,
+    font: Vec<u8>,
+    size: f32,
+    leading: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    horizontal_scale: f32,
+}
+
+impl Default for TextState {
+    fn default() -> Self {
+        Self {
+            ctm: IDENTITY,
+            saved: Vec::new(),
+            matrix: IDENTITY,
+            line_matrix: IDENTITY,
+            font: Vec::new(),
+            size: 0.0,
+            leading: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scale: 1.0,
+        }
+    }
+}
+
+impl TextState {
+    fn next_line(&mut self, tx: f32, ty: f32) {
+        self.line_matrix = multiply(&translate(tx, ty), &self.line_matrix);
+        self.matrix = self.line_matrix;
+    }
+
+    /// Place a string at the current position and advance past it
+    fn show(&mut self, fonts: &BTreeMap<Vec<u8>, PageFont>, bytes: &[u8], runs: &mut Vec<TextRun>) {
+        let Some(font) = fonts.get(&self.font) else {
+            return;
+        };
+        let start = multiply(&self.matrix, &self.ctm);
+        let advance: f32 = font
+            .glyphs(bytes)
+            .iter()
+            .map(|&(width, is_space)| {
+                let spacing = self.char_spacing + if is_space { self.word_spacing } else { 0.0 };
+                (width * self.size + spacing) * self.horizontal_scale
+            })
+            .sum();
+        self.matrix = multiply(&translate(advance, 0.0), &self.matrix);
+        let end = multiply(&self.matrix, &self.ctm);
+
+        let text = font.decode(bytes);
+        if text.trim().is_empty() {
+            return;
+        }
+        runs.push(TextRun {
+            x: start[4],
+            y: start[5],
+            end_x: end[4],
+            size: (self.size * start[2].hypot(start[3])).abs().max(1.0),
+            text,
+        });
+    }
+
+    fn show_operand(&mut self, operand: Option<&Object>, fonts: &BTreeMap<Vec<u8>, PageFont>, runs: &mut Vec<TextRun>) {
+        if let Some(Object::String(bytes, _)) = operand {
+            self.show(fonts, bytes, runs);
+        }
+    }
+
+    fn apply(&mut self, operation: &Operation, fonts: &BTreeMap<Vec<u8>, PageFont>, runs: &mut Vec<TextRun>) {
+        let operands = &operation.operands;
+        match operation.operator.as_str() {
+            "q" => self.saved.push(self.ctm),
+            "Q" => self.ctm = self.saved.pop().unwrap_or(IDENTITY),
+            "cm" => {
+                if let Some(m) = numbers::<6>(operands) {
+                    self.ctm = multiply(&m, &self.ctm);
+                }
+            }
+            "BT" => {
+                self.matrix = IDENTITY;
+                self.line_matrix = IDENTITY;
+            }
+            "Tf" => {
+                if let Some(name) = operands.first().and_then(|o| o.as_name().ok()) {
+                    self.font = name.to_vec();
+                }
+                self.size = operands.get(1).and_then(number).unwrap_or(self.size);
+            }
+            "TL" => self.leading = operands.first().and_then(number).unwrap_or(self.leading),
+            "Tc" => self.char_spacing = operands.first().and_then(number).unwrap_or(0.0),
+            "Tw" => self.word_spacing = operands.first().and_then(number).unwrap_or(0.0),
+            "Tz" => self.horizontal_scale = operands.first().and_then(number).unwrap_or(100.0) / 100.0,
+            "Td" => {
+                if let Some([tx, ty]) = numbers(operands) {
+                    self.next_line(tx, ty);
+                }
+            }
+            "TD" => {
+                if let Some([tx, ty]) = numbers(operands) {
+                    self.leading = -ty;
+                    self.next_line(tx, ty);
+                }
+            }
+            "Tm" => {
+                if let Some(m) = numbers::<6>(operands) {
+                    self.line_matrix = m;
+                    self.matrix = m;
+                }
+            }
+            "T*" => self.next_line(0.0, -self.leading),
+            "Tj" => self.show_operand(operands.last(), fonts, runs),
+            "'" => {
+                self.next_line(0.0, -self.leading);
+                self.show_operand(operands.last(), fonts, runs);
+            }
+            "\"" => {
+                if let Some([word, char]) = numbers(operands) {
+                    self.word_spacing = word;
+                    self.char_spacing = char;
+                }
+                self.next_line(0.0, -self.leading);
+                self.show_operand(operands.last(), fonts, runs);
+            }
+            "TJ" => {
+                for element in operands.first().and_then(|o| o.as_array().ok()).into_iter().flatten() {
+                    match element {
+                        Object::String(bytes, _) => self.show(fonts, bytes, runs),
+                        other => {
+                            let adjust = number(other).unwrap_or(0.0);
+                            let shift = -adjust / 1000.0 * self.size * self.horizontal_scale;
+                            self.matrix = multiply(&translate(shift, 0.0), &self.matrix);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Text pieces on one page, in content stream order
+fn page_runs(doc: &Document, page_id: ObjectId) -> Result<Vec<TextRun>, String> {
+    let fonts: BTreeMap<Vec<u8>, PageFont> = doc
+        .get_page_fonts(page_id)
+        .map_err(|e| format!("Failed to read PDF fonts: {}", e))?
+        .into_iter()
+        .map(|(name, font)| (name, PageFont::load(doc, font)))
+        .collect();
+    let content = doc
+        .get_and_decode_page_content(page_id)
+        .map_err(|e| format!("Failed to read PDF page: {}", e))?;
+
+    let mut state = TextState::default();
+    let mut runs = Vec::new();
+    for operation in &content.operations {
+        state.apply(operation, &fonts, &mut runs);
+    }
+    Ok(runs)
+}
+
+/// Lay out a page's text top to bottom, one line per baseline, with the
+/// horizontal gaps kept as spaces
+fn layout_page(mut runs: Vec<TextRun>) -> String {
+    if runs.is_empty() {
+        return String::new();
+    }
+    runs.sort_by(|a, b| b.y.total_cmp(&a.y).then(a.x.total_cmp(&b.x)));
+
+    let mut lines: Vec<Vec<TextRun>> = Vec::new();
+    for run in runs {
+        match lines.last_mut() {
+            Some(line) if (line[0].y - run.y).abs() <= SAME_LINE * line[0].size.max(run.size) => line.push(run),
+            _ => lines.push(vec![run]),
+        }
+    }
+
+    let mut sizes: Vec<f32> = lines.iter().flatten().map(|r| r.size).collect();
+    sizes.sort_by(f32::total_cmp);
+    let cell = sizes[sizes.len() / 2] * CELL_WIDTH;
+    let left = lines.iter().flatten().map(|r| r.x).fold(f32::INFINITY, f32::min);
+
+    lines
+        .into_iter()
+        .map(|mut line| {
+            line.sort_by(|a, b| a.x.total_cmp(&b.x));
+            let mut text = String::new();
+            let mut previous_end: Option<f32> = None;
+            for run in line {
+                let column = ((run.x - left) / cell).round().max(0.0) as usize;
+                let length = text.chars().count();
+                if length < column {
+                    text.extend(std::iter::repeat(' ').take(column - length));
+                } else if previous_end.is_some_and(|end| run.x - end > WORD_GAP * run.size) && !text.ends_with(' ') {
+                    text.push(' ');
+                }
+                text.push_str(&run.text);
+                previous_end = Some(run.end_x);
+            }
+            text.trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Text of each page of a loaded PDF with its layout kept, empty for
+/// image-only pages
+pub fn page_texts(doc: &Document) -> Result<Vec<String>, String> {
+    doc.get_pages()
+        .into_values()
+        .map(|page_id| Ok(layout_page(page_runs(doc, page_id)?)))
+        .collect()
+}
+
+/// Text of every page of a loaded PDF with its layout kept. Pages are
+/// separated by a blank line; image-only pages contribute nothing.
+pub fn document_text(doc: &Document) -> Result<String, String> {
+    let pages: Vec<String> = page_texts(doc)?.into_iter().filter(|p| !p.is_empty()).collect();
+    Ok(pages.join("\n\n"))
+}
+
+fn load(path: &Path) -> Result<Document, String> {
+    Document::load(path).map_err(|e| format!("Failed to read PDF {}: {}", path.display(), e))
+}
+
+/// Text of a PDF file with its layout kept
+pub fn extract_text(path: &Path) -> Result<String, String> {
+    document_text(&load(path)?)
+}
+
+/// Text of each page of a PDF file with its layout kept
+pub fn extract_pages(path: &Path) -> Result<Vec<String>, String> {
+    page_texts(&load(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report_builder::{Align, Column, ReportBuilder};
+
+    #[test]
+    fn test_table_rows_stay_on_one_line_with_columns_aligned() {
+        let dir = crate::storage::test_dir();
+        let path = dir.join("invoice.pdf");
+
+        let mut report = ReportBuilder::new("Tax Invoice");
+        report.text("ABN 51 824 753 556").table(
+            &[
+                Column::new("Description", 250.0, Align::Left),
+                Column::new("Qty", 60.0, Align::Right),
+                Column::new("Amount", 100.0, Align::Right),
+            ],
+            &[
+                vec!["Printer paper".to_string(), "2".to_string(), "$42.50".to_string()],
+                vec!["Toner".to_string(), "1".to_string(), "$118.00".to_string()],
+            ],
+        );
+        report.text_right("Total $160.50", true);
+        std::fs::write(&path, report.build()).unwrap();
+
+        let text = extract_text(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        let row = |label: &str| *lines.iter().find(|l| l.trim_start().starts_with(label)).unwrap();
+
+        let paper = row("Printer paper");
+        let toner = row("Toner");
+        assert!(paper.ends_with("$42.50"), "{:?}", paper);
+        assert!(toner.ends_with("$118.00"), "{:?}", toner);
+        assert!(paper.contains("  2  "), "{:?}", paper);
+        // Right-aligned amounts end in the same column
+        assert!(paper.len().abs_diff(toner.len()) <= 1, "{}\n{}", paper, toner);
+        assert!(lines.iter().any(|l| l.trim() == "Total $160.50"));
+        assert!(lines.iter().any(|l| l.trim() == "ABN 51 824 753 556"));
+    }
+}
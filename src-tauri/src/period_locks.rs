@@ -0,0 +1,343 @@
+//! Period Locks Module
+//!
+//! BAS quarters that have been lodged. Once a quarter is lodged, documents
+//! dated in it can't have their BAS figures changed directly; corrections
+//! are recorded as adjustment entries instead and reported in the next
+//! quarter that is still open, while the lodged quarter keeps the figures
+//! it was lodged with.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::documents::StoredDocument;
+use crate::money::Money;
+use crate::periods::{self, BasPeriod};
+use crate::storage;
+
+static LOCKS_LOCK: Mutex<()> = Mutex::new(());
+
+/// A lodged BAS quarter for one entity
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LodgedPeriod {
+    pub period: BasPeriod,
+    /// Entity the statement was lodged for; `None` for unassigned documents
+    pub entity_id: Option<String>,
+    pub lodged_at: String,
+    /// Lodgement receipt number or other reference
+    pub reference: Option<String>,
+}
+
+impl LodgedPeriod {
+    /// Whether a document falls in this lodged quarter
+    pub fn covers(&self, doc: &StoredDocument) -> bool {
+        doc.entity_id == self.entity_id && document_period(doc) == Some(self.period)
+    }
+}
+
+/// A correction to a document in a lodged quarter
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AdjustmentEntry {
+    pub id: String,
+    pub document_id: String,
+    pub entity_id: Option<String>,
+    pub vendor: Option<String>,
+    /// Lodged quarter the document is dated in
+    pub original_period: BasPeriod,
+    /// Open quarter the correction is reported in
+    pub adjustment_period: BasPeriod,
+    pub previous_total: Money,
+    pub previous_gst: Money,
+    pub total: Money,
+    pub gst: Money,
+    pub reason: String,
+    pub created_at: String,
+}
+
+impl AdjustmentEntry {
+    /// Change in purchases, reported at G11
+    pub fn purchases_delta(&self) -> Money {
+        self.total - self.previous_total
+    }
+
+    /// Change in GST credits, reported at 1B
+    pub fn gst_delta(&self) -> Money {
+        self.gst - self.previous_gst
+    }
+}
+
+/// BAS quarter a document is dated in
+fn document_period(doc: &StoredDocument) -> Option<BasPeriod> {
+    doc.date.as_deref().and_then(periods::parse_date).map(BasPeriod::of)
+}
+
+/// Whether an edit changes anything reported on the BAS
+fn changes_bas_figures(before: &StoredDocument, after: Option<&StoredDocument>) -> bool {
+    after.map_or(true, |after| {
+        before.date != after.date
+            || before.total != after.total
+            || before.gst != after.gst
+            || before.kind != after.kind
+            || before.entity_id != after.entity_id
+    })
+}
+
+/// Reject an edit (or a deletion, when `after` is `None`) that would change
+/// the BAS figures of a lodged quarter. Documents without a date yet may be
+/// dated into a lodged quarter; they are reported as late entries.
+pub fn check_edit(
+    lodged: &[LodgedPeriod],
+    before: &StoredDocument,
+    after: Option<&StoredDocument>,
+) -> Result<(), String> {
+    if !changes_bas_figures(before, after) {
+        return Ok(());
+    }
+    let moved_into = after.filter(|_| before.date.is_some());
+    let locked = lodged
+        .iter()
+        .find(|l| l.covers(before) || moved_into.is_some_and(|after| l.covers(after)));
+    match locked {
+        Some(lock) => Err(format!(
+            "{} has been lodged; record an adjustment instead of editing this document",
+            lock.period.label()
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Persisted locks and adjustments
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+struct PeriodLockData {
+    lodged: Vec<LodgedPeriod>,
+    adjustments: Vec<AdjustmentEntry>,
+}
+
+/// JSON-backed record of lodged quarters and adjustment entries
+pub struct PeriodLockStore {
+    path: PathBuf,
+    data: PeriodLockData,
+}
+
+impl PeriodLockStore {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            data: storage::load_json(path)?,
+        })
+    }
+
+    pub fn open_default() -> Result<Self, String> {
+        Self::open(&storage::get_data_directory()?.join("period_locks.json"))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        storage::save_json(&self.path, &self.data)
+    }
+
+    pub fn lodged(&self) -> &[LodgedPeriod] {
+        &self.data.lodged
+    }
+
+    pub fn adjustments(&self) -> &[AdjustmentEntry] {
+        &self.data.adjustments
+    }
+
+    fn is_locked(&self, period: BasPeriod, entity_id: &Option<String>) -> bool {
+        self.data
+            .lodged
+            .iter()
+            .any(|l| l.period == period && &l.entity_id == entity_id)
+    }
+
+    /// Mark a quarter lodged
+    pub fn lock(
+        &mut self,
+        period: BasPeriod,
+        entity_id: Option<String>,
+        reference: Option<String>,
+    ) -> Result<LodgedPeriod, String> {
+        if !(1..=4).contains(&period.quarter) {
+            return Err(format!("Invalid quarter: {}", period.quarter));
+        }
+        if self.is_locked(period, &entity_id) {
+            return Err(format!("{} is already lodged", period.label()));
+        }
+        let lodged = LodgedPeriod {
+            period,
+            entity_id,
+            lodged_at: storage::now_timestamp(),
+            reference: reference.filter(|r| !r.trim().is_empty()),
+        };
+        self.data.lodged.push(lodged.clone());
+        self.data.lodged.sort_by_key(|l| l.period);
+        Ok(lodged)
+    }
+
+    /// Reopen a quarter marked lodged by mistake
+    pub fn unlock(&mut self, period: BasPeriod, entity_id: &Option<String>) -> bool {
+        let before = self.data.lodged.len();
+        self.data
+            .lodged
+            .retain(|l| !(l.period == period && &l.entity_id == entity_id));
+        self.data.lodged.len() != before
+    }
+
+    /// Correct the total and GST of a document in a lodged quarter. The
+    /// correction is reported in the first open quarter on or after the
+    /// one containing `today`.
+    pub fn record_adjustment(
+        &mut self,
+        document: &mut StoredDocument,
+        total: Money,
+        gst: Money,
+        reason: &str,
+        today: NaiveDate,
+    ) -> Result<AdjustmentEntry, String> {
+        let original_period = document_period(document)
+            .filter(|p| self.is_locked(*p, &document.entity_id))
+            .ok_or_else(|| "Document is not in a lodged quarter; edit it directly".to_string())?;
+        if reason.trim().is_empty() {
+            return Err("A reason is required for an adjustment".to_string());
+        }
+        if total <= Money::ZERO {
+            return Err("Total must be greater than zero".to_string());
+        }
+        if gst < Money::ZERO || gst > total {
+            return Err("GST must be between zero and the total".to_string());
+        }
+
+        let mut adjustment_period = BasPeriod::of(today).max(original_period.next());
+        while self.is_locked(adjustment_period, &document.entity_id) {
+            adjustment_period = adjustment_period.next();
+        }
+
+        let entry = AdjustmentEntry {
+            id: storage::generate_id("adj"),
+            document_id: document.id.clone(),
+            entity_id: document.entity_id.clone(),
+            vendor: document.vendor.clone(),
+            original_period,
+            adjustment_period,
+            previous_total: document.total.unwrap_or_default(),
+            previous_gst: document.gst.unwrap_or_default(),
+            total,
+            gst,
+            reason: reason.trim().to_string(),
+            created_at: storage::now_timestamp(),
+        };
+        document.total = Some(total);
+        document.gst = Some(gst);
+        self.data.adjustments.push(entry.clone());
+        Ok(entry)
+    }
+
+    /// Take back an adjustment whose corrected document couldn't be saved
+    pub fn discard_adjustment(&mut self, id: &str) -> bool {
+        let before = self.data.adjustments.len();
+        self.data.adjustments.retain(|a| a.id != id);
+        self.data.adjustments.len() != before
+    }
+}
+
+/// Quarters lodged so far, read from the default store
+pub fn lodged_periods() -> Result<Vec<LodgedPeriod>, String> {
+    Ok(PeriodLockStore::open_default()?.data.lodged)
+}
+
+/// Run a closure against the default store while holding its lock
+pub fn with_period_locks<R>(f: impl FnOnce(&mut PeriodLockStore) -> Result<R, String>) -> Result<R, String> {
+    let _guard = LOCKS_LOCK.lock().map_err(|_| "Period lock store lock poisoned".to_string())?;
+    let mut store = PeriodLockStore::open_default()?;
+    f(&mut store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(date: &str, total: f64, gst: f64) -> StoredDocument {
+        StoredDocument {
+            id: "doc-1".to_string(),
+            date: Some(date.to_string()),
+            total: Some(Money::from_dollars(total)),
+            gst: Some(Money::from_dollars(gst)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_lodged_quarter_blocks_edits_and_takes_adjustments() {
+        let dir = storage::test_dir();
+        let path = dir.join("period_locks.json");
+        let mut store = PeriodLockStore::open(&path).unwrap();
+        let q1 = BasPeriod { financial_year: 2024, quarter: 1 };
+        store.lock(q1, None, Some("LR-123".to_string())).unwrap();
+        assert!(store.lock(q1, None, None).is_err());
+
+        let original = doc("2023-08-10", 110.0, 10.0);
+        let mut edited = original.clone();
+        edited.category = Some("Office".to_string());
+        assert!(check_edit(store.lodged(), &original, Some(&edited)).is_ok());
+        edited.total = Some(Money::from_cents(22000));
+        assert!(check_edit(store.lodged(), &original, Some(&edited)).is_err());
+        assert!(check_edit(store.lodged(), &original, None).is_err());
+
+        // Moving a dated document into the lodged quarter is blocked too
+        let later = doc("2023-11-01", 55.0, 5.0);
+        let mut moved = later.clone();
+        moved.date = Some("2023-09-30".to_string());
+        assert!(check_edit(store.lodged(), &later, Some(&moved)).is_err());
+
+        // Another entity's quarter is still open
+        let mut other = original.clone();
+        other.entity_id = Some("ent-2".to_string());
+        let mut other_edit = other.clone();
+        other_edit.total = Some(Money::from_cents(100));
+        assert!(check_edit(store.lodged(), &other, Some(&other_edit)).is_ok());
+
+        // Q2 is also lodged, so a correction made in November lands in Q3
+        store.lock(q1.next(), None, None).unwrap();
+        let mut document = original.clone();
+        let today = NaiveDate::from_ymd_opt(2023, 11, 20).unwrap();
+        let (total, gst) = (Money::from_cents(16500), Money::from_cents(1500));
+        let entry = store
+            .record_adjustment(&mut document, total, gst, "Missed second page", today)
+            .unwrap();
+        assert_eq!(entry.adjustment_period, BasPeriod { financial_year: 2024, quarter: 3 });
+        assert_eq!(entry.gst_delta(), Money::from_cents(500));
+        assert_eq!(entry.purchases_delta(), Money::from_cents(5500));
+        assert_eq!(document.total, Some(Money::from_cents(16500)));
+        let mut open = doc("2024-02-01", 22.0, 2.0);
+        assert!(store.record_adjustment(&mut open, Money::from_cents(100), Money::ZERO, "Typo", today).is_err());
+
+        store.save().unwrap();
+        let reopened = PeriodLockStore::open(&path).unwrap();
+        assert_eq!(reopened.lodged().len(), 2);
+        assert_eq!(reopened.adjustments().len(), 1);
+    }
+
+    #[test]
+    fn test_adjustments_reject_invalid_amounts() {
+        let dir = storage::test_dir();
+        let path = dir.join("period_locks.json");
+        let mut store = PeriodLockStore::open(&path).unwrap();
+        store.lock(BasPeriod { financial_year: 2024, quarter: 1 }, None, None).unwrap();
+        let today = NaiveDate::from_ymd_opt(2023, 11, 20).unwrap();
+
+        let mut document = doc("2023-08-10", 110.0, 10.0);
+        for (total, gst) in [(0, 0), (-500, 0), (5000, 6000), (5000, -100)] {
+            let (total, gst) = (Money::from_cents(total), Money::from_cents(gst));
+            assert!(store.record_adjustment(&mut document, total, gst, "Typo", today).is_err());
+        }
+        assert_eq!(document.total, Some(Money::from_cents(11000)));
+        assert!(store.adjustments().is_empty());
+
+        let (total, gst) = (Money::from_cents(5500), Money::from_cents(500));
+        let entry = store.record_adjustment(&mut document, total, gst, "Credit note", today).unwrap();
+        assert!(store.discard_adjustment(&entry.id));
+        assert!(store.adjustments().is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+//! Reporting Periods Module
+//!
+//! Australian financial years (1 July - 30 June) and quarterly BAS periods.
+//! Financial years are identified by the calendar year they end in, so
+//! FY2024 runs from 1 July 2023 to 30 June 2024.
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+/// Financial year (identified by its ending year) containing a date
+pub fn financial_year_of(date: NaiveDate) -> i32 {
+    if date.month() >= 7 {
+        date.year() + 1
+    } else {
+        date.year()
+    }
+}
+
+/// Display label such as "FY2023-24"
+pub fn financial_year_label(fy: i32) -> String {
+    format!("FY{}-{:02}", fy - 1, fy % 100)
+}
+
+/// Parse a stored YYYY-MM-DD date or RFC 3339 timestamp
+pub fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .or_else(|| chrono::DateTime::parse_from_rfc3339(value).ok().map(|dt| dt.date_naive()))
+}
+
+/// A quarterly BAS period. Quarter 1 is July - September.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BasPeriod {
+    /// Financial year the quarter belongs to
+    pub financial_year: i32,
+    /// Quarter number within the financial year (1 - 4)
+    pub quarter: u8,
+}
+
+impl BasPeriod {
+    /// BAS period containing a date
+    pub fn of(date: NaiveDate) -> Self {
+        let quarter = match date.month() {
+            7..=9 => 1,
+            10..=12 => 2,
+            1..=3 => 3,
+            _ => 4,
+        };
+        Self {
+            financial_year: financial_year_of(date),
+            quarter,
+        }
+    }
+
+    /// First day of the quarter
+    pub fn start(&self) -> NaiveDate {
+        let (year, month) = self.start_month();
+        NaiveDate::from_ymd_opt(year, month, 1).unwrap()
+    }
+
+    /// Last day of the quarter
+    pub fn end(&self) -> NaiveDate {
+        self.next().start().pred_opt().unwrap()
+    }
+
+    /// The following quarter
+    pub fn next(&self) -> Self {
+        if self.quarter == 4 {
+            Self { financial_year: self.financial_year + 1, quarter: 1 }
+        } else {
+            Self { financial_year: self.financial_year, quarter: self.quarter + 1 }
+        }
+    }
+
+    /// Standard lodgement due date: the 28th of the month after the quarter,
+    /// except the December quarter which is due on 28 February
+    pub fn lodgement_due(&self) -> NaiveDate {
+        let end = self.end();
+        let (year, month) = match self.quarter {
+            2 => (end.year() + 1, 2),
+            4 => (end.year(), 7),
+            _ => (end.year(), end.month() + 1),
+        };
+        NaiveDate::from_ymd_opt(year, month, 28).unwrap()
+    }
+
+    /// Display label such as "Q1 FY2023-24"
+    pub fn label(&self) -> String {
+        format!("Q{} {}", self.quarter, financial_year_label(self.financial_year))
+    }
+
+    fn start_month(&self) -> (i32, u32) {
+        match self.quarter {
+            1 => (self.financial_year - 1, 7),
+            2 => (self.financial_year - 1, 10),
+            3 => (self.financial_year, 1),
+            _ => (self.financial_year, 4),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_financial_year_of() {
+        assert_eq!(financial_year_of(date(2023, 7, 1)), 2024);
+        assert_eq!(financial_year_of(date(2024, 6, 30)), 2024);
+        assert_eq!(financial_year_label(2024), "FY2023-24");
+    }
+
+    #[test]
+    fn test_bas_period_boundaries() {
+        let q2 = BasPeriod::of(date(2023, 11, 15));
+        assert_eq!(q2, BasPeriod { financial_year: 2024, quarter: 2 });
+        assert_eq!(q2.start(), date(2023, 10, 1));
+        assert_eq!(q2.end(), date(2023, 12, 31));
+        assert_eq!(q2.lodgement_due(), date(2024, 2, 28));
+
+        let q4 = BasPeriod { financial_year: 2024, quarter: 4 };
+        assert_eq!(q4.end(), date(2024, 6, 30));
+        assert_eq!(q4.lodgement_due(), date(2024, 7, 28));
+        assert_eq!(q4.next(), BasPeriod { financial_year: 2025, quarter: 1 });
+    }
+}
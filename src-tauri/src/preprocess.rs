@@ -0,0 +1,562 @@
+//! Image Preprocessing Module
+//!
+//! Cleans up receipt photos before OCR: grayscale conversion, median
+//! denoising, contrast stretching, deskewing and adaptive thresholding.
+//! Phone photos of crumpled or unevenly lit receipts are far more legible to
+//! Tesseract after these steps. Faded thermal paper can additionally be
+//! darkened and given local contrast with the `thermal` enhancement.
+
+use image::{DynamicImage, GrayImage, Luma};
+use serde::{Deserialize, Serialize};
+
+/// Extra enhancement for receipts that are hard to read
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Enhancement {
+    #[default]
+    None,
+    /// Gamma correction and local contrast for faded thermal print
+    Thermal,
+}
+
+/// Preprocessing steps applied before OCR, in pipeline order. Every step
+/// after grayscale works on a grayscale image and implies it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PreprocessOptions {
+    pub enabled: bool,
+    /// Turn sideways and upside-down photos upright before the other steps
+    pub auto_rotate: bool,
+    pub grayscale: bool,
+    /// 3x3 median filter to remove speckle noise
+    pub denoise: bool,
+    /// Stretch intensities so the darkest and lightest 1% span the full range
+    pub contrast_stretch: bool,
+    pub deskew: bool,
+    /// Largest rotation searched for when deskewing, in degrees
+    pub max_skew_degrees: f32,
+    /// Binarize against the local mean to cope with uneven lighting
+    pub adaptive_threshold: bool,
+    /// Side of the square neighbourhood used for the local mean, in pixels
+    pub threshold_window: u32,
+    /// Pixels this fraction darker than their neighbourhood become black
+    pub threshold_bias: f32,
+    pub enhancement: Enhancement,
+    /// Photos read with fewer characters than this are read again with the
+    /// thermal enhancement; 0 never retries
+    pub thermal_retry_min_chars: usize,
+}
+
+impl Default for PreprocessOptions {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            auto_rotate: true,
+            grayscale: true,
+            denoise: true,
+            contrast_stretch: true,
+            deskew: true,
+            max_skew_degrees: 10.0,
+            adaptive_threshold: true,
+            threshold_window: 31,
+            threshold_bias: 0.15,
+            enhancement: Enhancement::None,
+            thermal_retry_min_chars: 40,
+        }
+    }
+}
+
+impl PreprocessOptions {
+    fn needs_grayscale(&self) -> bool {
+        self.grayscale
+            || self.denoise
+            || self.enhancement != Enhancement::None
+            || self.contrast_stretch
+            || self.deskew
+            || self.adaptive_threshold
+    }
+
+    /// Whether a photo that produced `chars` characters of text should be
+    /// read again with the thermal enhancement
+    pub fn should_retry_thermal(&self, chars: usize) -> bool {
+        self.enabled && self.enhancement == Enhancement::None && chars < self.thermal_retry_min_chars
+    }
+}
+
+/// Run the enabled preprocessing steps over an image
+pub fn preprocess(image: DynamicImage, options: &PreprocessOptions) -> DynamicImage {
+    if !options.enabled || !options.needs_grayscale() {
+        return image;
+    }
+
+    let mut gray = image.to_luma8();
+    if options.denoise {
+        gray = median_filter(&gray);
+    }
+    if options.enhancement == Enhancement::Thermal {
+        enhance_thermal(&mut gray);
+    }
+    if options.contrast_stretch {
+        contrast_stretch(&mut gray);
+    }
+    if options.deskew {
+        let angle = estimate_skew(&gray, options.max_skew_degrees);
+        if angle.abs() >= 0.25 {
+            gray = rotate(&gray, -angle);
+        }
+    }
+    if options.adaptive_threshold {
+        gray = adaptive_threshold(&gray, options.threshold_window, options.threshold_bias);
+    }
+    DynamicImage::ImageLuma8(gray)
+}
+
+/// Detect a sideways or upside-down image and turn it upright, returning
+/// the clockwise rotation applied (0, 90, 180 or 270 degrees)
+pub fn auto_rotate(image: DynamicImage) -> (DynamicImage, u32) {
+    let degrees = detect_orientation(&image.to_luma8());
+    (rotate_clockwise(image, degrees), degrees)
+}
+
+/// Rotate clockwise by a multiple of 90 degrees
+pub fn rotate_clockwise(image: DynamicImage, degrees: u32) -> DynamicImage {
+    match degrees % 360 {
+        90 => image.rotate90(),
+        180 => image.rotate180(),
+        270 => image.rotate270(),
+        _ => image,
+    }
+}
+
+/// Clockwise rotation that makes the text in an image upright.
+///
+/// Text lines give a sharp projection profile along the reading direction,
+/// which tells sideways from upright. Latin text has more ascenders than
+/// descenders, so ink sitting below each line's core band means the text is
+/// upside down.
+pub fn detect_orientation(image: &GrayImage) -> u32 {
+    let longest = image.width().max(image.height());
+    let sample = if longest > 800 {
+        let scale = 800.0 / longest as f32;
+        image::imageops::resize(
+            image,
+            ((image.width() as f32 * scale).round() as u32).max(1),
+            ((image.height() as f32 * scale).round() as u32).max(1),
+            image::imageops::FilterType::Triangle,
+        )
+    } else {
+        image.clone()
+    };
+    let binary = adaptive_threshold(&sample, 25, 0.15);
+
+    let (rows, columns) = ink_profiles(&binary);
+    let sideways = profile_sharpness(&columns) > profile_sharpness(&rows) * 1.2;
+    let upright = if sideways { image::imageops::rotate90(&binary) } else { binary };
+    let (rows, _) = ink_profiles(&upright);
+    let flipped = is_upside_down(&rows);
+
+    match (sideways, flipped) {
+        (false, false) => 0,
+        (false, true) => 180,
+        (true, false) => 90,
+        (true, true) => 270,
+    }
+}
+
+/// Count of black pixels in each row and each column
+fn ink_profiles(binary: &GrayImage) -> (Vec<u32>, Vec<u32>) {
+    let mut rows = vec![0u32; binary.height() as usize];
+    let mut columns = vec![0u32; binary.width() as usize];
+    for (x, y, pixel) in binary.enumerate_pixels() {
+        if pixel[0] == 0 {
+            rows[y as usize] += 1;
+            columns[x as usize] += 1;
+        }
+    }
+    (rows, columns)
+}
+
+/// Squared coefficient of variation; high when ink alternates with gaps
+fn profile_sharpness(profile: &[u32]) -> f64 {
+    if profile.is_empty() {
+        return 0.0;
+    }
+    let n = profile.len() as f64;
+    let mean = profile.iter().map(|&v| v as f64).sum::<f64>() / n;
+    if mean == 0.0 {
+        return 0.0;
+    }
+    let variance = profile.iter().map(|&v| (v as f64 - mean).powi(2)).sum::<f64>() / n;
+    variance / (mean * mean)
+}
+
+/// Compare ink above and below the core band of each text line
+fn is_upside_down(rows: &[u32]) -> bool {
+    let peak = rows.iter().copied().max().unwrap_or(0);
+    let gap = (peak / 20).max(1);
+    let (mut above, mut below) = (0u64, 0u64);
+
+    let mut start = None;
+    for (y, &ink) in rows.iter().chain(std::iter::once(&0)).enumerate() {
+        match (start, ink >= gap) {
+            (None, true) => start = Some(y),
+            (Some(top), false) => {
+                let band = &rows[top..y];
+                start = None;
+                if band.len() < 5 {
+                    continue;
+                }
+                let band_peak = band.iter().copied().max().unwrap_or(0);
+                let core: Vec<usize> = (0..band.len()).filter(|&i| band[i] * 2 >= band_peak).collect();
+                let (first, last) = (core[0], core[core.len() - 1]);
+                above += band[..first].iter().map(|&v| v as u64).sum::<u64>();
+                below += band[last + 1..].iter().map(|&v| v as u64).sum::<u64>();
+            }
+            _ => {}
+        }
+    }
+    below as f64 > above as f64 * 1.1
+}
+
+/// 3x3 median filter, leaving the outermost pixels untouched
+pub fn median_filter(image: &GrayImage) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let mut out = image.clone();
+    if width < 3 || height < 3 {
+        return out;
+    }
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let mut window = [0u8; 9];
+            for (i, (dx, dy)) in (0..3).flat_map(|dy| (0..3).map(move |dx| (dx, dy))).enumerate() {
+                window[i] = image.get_pixel(x + dx - 1, y + dy - 1)[0];
+            }
+            window.sort_unstable();
+            out.put_pixel(x, y, Luma([window[4]]));
+        }
+    }
+    out
+}
+
+/// Linearly map the 1st-99th percentile intensities onto 0-255
+pub fn contrast_stretch(image: &mut GrayImage) {
+    let mut histogram = [0u64; 256];
+    for pixel in image.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+    let total: u64 = histogram.iter().sum();
+    if total == 0 {
+        return;
+    }
+
+    let percentile = |fraction: f64| -> u8 {
+        let target = (total as f64 * fraction) as u64;
+        let mut seen = 0;
+        for (value, count) in histogram.iter().enumerate() {
+            seen += count;
+            if seen > target {
+                return value as u8;
+            }
+        }
+        255
+    };
+    let (low, high) = (percentile(0.01), percentile(0.99));
+    if high <= low {
+        return;
+    }
+
+    let scale = 255.0 / (high - low) as f32;
+    for pixel in image.pixels_mut() {
+        let value = (pixel[0].clamp(low, high) - low) as f32 * scale;
+        pixel[0] = value.round() as u8;
+    }
+}
+
+/// Gamma applied to faded thermal print; above 1 darkens the pale greys
+/// the print has faded to while leaving the white paper white
+const THERMAL_GAMMA: f32 = 2.2;
+
+/// Tiles across each side of the image for local contrast enhancement
+const THERMAL_TILES: u32 = 8;
+
+/// Histogram bins are clipped at this multiple of the average count, so
+/// blank paper isn't stretched into noise
+const THERMAL_CLIP_LIMIT: f32 = 3.0;
+
+/// Darken faded thermal print with a gamma curve, then equalize contrast
+/// tile by tile (contrast-limited adaptive histogram equalization) so
+/// print that faded unevenly across the receipt comes up everywhere
+pub fn enhance_thermal(image: &mut GrayImage) {
+    let lut: Vec<u8> = (0..=255u32)
+        .map(|v| (255.0 * (v as f32 / 255.0).powf(THERMAL_GAMMA)).round() as u8)
+        .collect();
+    for pixel in image.pixels_mut() {
+        pixel[0] = lut[pixel[0] as usize];
+    }
+    local_contrast(image, THERMAL_TILES, THERMAL_CLIP_LIMIT);
+}
+
+/// Contrast-limited adaptive histogram equalization over a `tiles` x
+/// `tiles` grid, blending neighbouring tiles' mappings bilinearly
+pub fn local_contrast(image: &mut GrayImage, tiles: u32, clip_limit: f32) {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return;
+    }
+    let tiles = tiles.clamp(1, width.min(height));
+    let (tile_w, tile_h) = (width.div_ceil(tiles), height.div_ceil(tiles));
+
+    let mut maps = vec![[0u8; 256]; (tiles * tiles) as usize];
+    for ty in 0..tiles {
+        for tx in 0..tiles {
+            let mut histogram = [0u32; 256];
+            let (x0, y0) = (tx * tile_w, ty * tile_h);
+            for y in y0..(y0 + tile_h).min(height) {
+                for x in x0..(x0 + tile_w).min(width) {
+                    histogram[image.get_pixel(x, y)[0] as usize] += 1;
+                }
+            }
+            let count: u32 = histogram.iter().sum();
+            if count == 0 {
+                continue;
+            }
+
+            // Clip tall bins and share the excess across all of them
+            let limit = ((clip_limit * count as f32 / 256.0) as u32).max(1);
+            let excess: u32 = histogram.iter().map(|&h| h.saturating_sub(limit)).sum();
+            let share = excess as f32 / 256.0;
+            let mut cumulative = 0.0;
+            let map = &mut maps[(ty * tiles + tx) as usize];
+            for (value, &h) in histogram.iter().enumerate() {
+                cumulative += h.min(limit) as f32 + share;
+                map[value] = (cumulative * 255.0 / count as f32).round().min(255.0) as u8;
+            }
+        }
+    }
+
+    // Each pixel blends the mappings of the four tiles whose centres surround it
+    let neighbours = |position: u32, size: u32| {
+        let t = (position as f32 + 0.5) / size as f32 - 0.5;
+        let first = t.floor().clamp(0.0, (tiles - 1) as f32);
+        let second = (first + 1.0).min((tiles - 1) as f32);
+        (first as u32, second as u32, (t - first).clamp(0.0, 1.0))
+    };
+    for y in 0..height {
+        let (ty0, ty1, fy) = neighbours(y, tile_h);
+        for x in 0..width {
+            let (tx0, tx1, fx) = neighbours(x, tile_w);
+            let value = image.get_pixel(x, y)[0] as usize;
+            let map = |tx: u32, ty: u32| maps[(ty * tiles + tx) as usize][value] as f32;
+            let top = map(tx0, ty0) * (1.0 - fx) + map(tx1, ty0) * fx;
+            let bottom = map(tx0, ty1) * (1.0 - fx) + map(tx1, ty1) * fx;
+            image.put_pixel(x, y, Luma([(top * (1.0 - fy) + bottom * fy).round() as u8]));
+        }
+    }
+}
+
+/// Estimate text skew in degrees by finding the rotation whose horizontal
+/// projection profile is sharpest (text lines line up with pixel rows)
+pub fn estimate_skew(image: &GrayImage, max_degrees: f32) -> f32 {
+    // Work on a small copy; the profile only needs the line structure
+    let sample = if image.width() > 600 {
+        let height = (image.height() as f32 * 600.0 / image.width() as f32).round().max(1.0) as u32;
+        image::imageops::resize(image, 600, height, image::imageops::FilterType::Triangle)
+    } else {
+        image.clone()
+    };
+
+    let ink: Vec<(f32, f32)> = sample
+        .enumerate_pixels()
+        .filter(|(_, _, p)| p[0] < 128)
+        .map(|(x, y, _)| (x as f32, y as f32))
+        .collect();
+    if ink.is_empty() {
+        return 0.0;
+    }
+
+    let height = sample.height() as f32;
+    let width = sample.width() as f32;
+    let score = |degrees: f32| -> f64 {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let rows = (height + width) as usize + 2;
+        let mut profile = vec![0u32; rows * 2];
+        for &(x, y) in &ink {
+            // Row the pixel would land on once the skew is undone
+            let row = (y * cos + x * sin + width) as usize;
+            if row < profile.len() {
+                profile[row] += 1;
+            }
+        }
+        profile.iter().map(|&c| (c as f64) * (c as f64)).sum()
+    };
+
+    let mut best = (0.0, score(0.0));
+    let mut degrees = -max_degrees;
+    while degrees <= max_degrees {
+        let value = score(degrees);
+        if value > best.1 {
+            best = (degrees, value);
+        }
+        degrees += 0.5;
+    }
+    best.0
+}
+
+/// Rotate about the centre by `degrees` (counter-clockwise), filling
+/// uncovered corners with white
+pub fn rotate(image: &GrayImage, degrees: f32) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+
+    GrayImage::from_fn(width, height, |x, y| {
+        let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+        let sx = cos * dx - sin * dy + cx;
+        let sy = sin * dx + cos * dy + cy;
+        if sx < 0.0 || sy < 0.0 || sx >= (width - 1) as f32 || sy >= (height - 1) as f32 {
+            return Luma([255]);
+        }
+
+        // Bilinear interpolation
+        let (x0, y0) = (sx.floor() as u32, sy.floor() as u32);
+        let (fx, fy) = (sx - x0 as f32, sy - y0 as f32);
+        let p = |x, y| image.get_pixel(x, y)[0] as f32;
+        let top = p(x0, y0) * (1.0 - fx) + p(x0 + 1, y0) * fx;
+        let bottom = p(x0, y0 + 1) * (1.0 - fx) + p(x0 + 1, y0 + 1) * fx;
+        Luma([(top * (1.0 - fy) + bottom * fy).round() as u8])
+    })
+}
+
+/// Bradley-style adaptive threshold using an integral image
+pub fn adaptive_threshold(image: &GrayImage, window: u32, bias: f32) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let stride = width as usize + 1;
+    let mut integral = vec![0u64; stride * (height as usize + 1)];
+    for y in 0..height as usize {
+        let mut row_sum = 0u64;
+        for x in 0..width as usize {
+            row_sum += image.get_pixel(x as u32, y as u32)[0] as u64;
+            integral[(y + 1) * stride + x + 1] = integral[y * stride + x + 1] + row_sum;
+        }
+    }
+
+    let half = (window.max(3) / 2) as i64;
+    GrayImage::from_fn(width, height, |x, y| {
+        let x0 = (x as i64 - half).max(0) as usize;
+        let y0 = (y as i64 - half).max(0) as usize;
+        let x1 = (x as i64 + half + 1).min(width as i64) as usize;
+        let y1 = (y as i64 + half + 1).min(height as i64) as usize;
+        let sum = integral[y1 * stride + x1] + integral[y0 * stride + x0]
+            - integral[y0 * stride + x1]
+            - integral[y1 * stride + x0];
+        let mean = sum as f32 / ((x1 - x0) * (y1 - y0)) as f32;
+
+        let value = image.get_pixel(x, y)[0] as f32;
+        Luma([if value < mean * (1.0 - bias) { 0 } else { 255 }])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lines of blocky "text": an x-height band with frequent ascenders
+    /// and occasional descenders
+    fn text_page() -> GrayImage {
+        GrayImage::from_fn(240, 320, |x, y| {
+            let (line, row) = (y / 32, y % 32);
+            if line == 0 || line > 8 || !(20..220).contains(&x) {
+                return Luma([255]);
+            }
+            let glyph = x % 8 < 5;
+            let ascender = x % 16 < 2 && (4..12).contains(&row);
+            let descender = x % 48 < 2 && (22..26).contains(&row);
+            let core = glyph && (12..22).contains(&row);
+            Luma([if core || ascender || descender { 0 } else { 255 }])
+        })
+    }
+
+    fn lined_page() -> GrayImage {
+        GrayImage::from_fn(300, 300, |x, y| {
+            let on_line = y % 20 < 3 && (40..260).contains(&x) && (40..260).contains(&y);
+            Luma([if on_line { 0 } else { 255 }])
+        })
+    }
+
+    #[test]
+    fn test_detects_orientation() {
+        let page = text_page();
+        assert_eq!(detect_orientation(&page), 0);
+        // Photographed rotated clockwise by n degrees needs 360 - n to fix
+        assert_eq!(detect_orientation(&image::imageops::rotate90(&page)), 270);
+        assert_eq!(detect_orientation(&image::imageops::rotate180(&page)), 180);
+        assert_eq!(detect_orientation(&image::imageops::rotate270(&page)), 90);
+    }
+
+    #[test]
+    fn test_deskew_recovers_rotation() {
+        let skewed = rotate(&lined_page(), 4.0);
+        let angle = estimate_skew(&skewed, 10.0);
+        assert!((angle.abs() - 4.0).abs() <= 0.5, "estimated {}", angle);
+
+        let straightened = rotate(&skewed, -angle);
+        assert!(estimate_skew(&straightened, 10.0).abs() <= 0.5);
+    }
+
+    #[test]
+    fn test_threshold_handles_uneven_lighting() {
+        // Dark text on a background that fades from bright to dim
+        let page = GrayImage::from_fn(200, 60, |x, y| {
+            let background = 240 - (x * 120 / 200) as u8;
+            Luma([if (20..30).contains(&y) && x % 10 < 5 { background / 3 } else { background }])
+        });
+        let mut stretched = page.clone();
+        contrast_stretch(&mut stretched);
+        assert_eq!(stretched.pixels().map(|p| p[0]).max(), Some(255));
+
+        let binary = adaptive_threshold(&page, 31, 0.15);
+        // Text is black and background white at both ends of the gradient
+        assert_eq!(binary.get_pixel(12, 25)[0], 0);
+        assert_eq!(binary.get_pixel(192, 25)[0], 0);
+        assert_eq!(binary.get_pixel(12, 50)[0], 255);
+        assert_eq!(binary.get_pixel(196, 50)[0], 255);
+    }
+
+    #[test]
+    fn test_thermal_enhancement_recovers_faded_print() {
+        // Pale grey print on grainy white paper, fading further to the right
+        let faded = GrayImage::from_fn(320, 160, |x, y| {
+            let grain = ((x * 7 + y * 13) % 16) as u8;
+            let ink = 190 + (x * 40 / 320) as u8;
+            Luma([if (60..80).contains(&y) && x % 8 < 4 { ink + grain } else { 236 + grain }])
+        });
+        let mut enhanced = faded.clone();
+        enhance_thermal(&mut enhanced);
+
+        // Contrast between print and paper at both ends of the fade
+        let contrast = |image: &GrayImage, left: u32| {
+            let mean = |ink: bool| {
+                let values: Vec<f64> = (left..left + 32)
+                    .flat_map(|x| (60..80).map(move |y| (x, y)))
+                    .filter(|&(x, _)| (x % 8 < 4) == ink)
+                    .map(|(x, y)| image.get_pixel(x, y)[0] as f64)
+                    .collect();
+                values.iter().sum::<f64>() / values.len() as f64
+            };
+            mean(false) - mean(true)
+        };
+        for left in [0, 288] {
+            let (before, after) = (contrast(&faded, left), contrast(&enhanced, left));
+            assert!(after >= before * 2.0, "at {}: {} -> {}", left, before, after);
+        }
+
+        let options = PreprocessOptions::default();
+        assert!(options.should_retry_thermal(12));
+        assert!(!options.should_retry_thermal(120));
+        let thermal = PreprocessOptions {
+            enhancement: Enhancement::Thermal,
+            ..options
+        };
+        assert!(!thermal.should_retry_thermal(0));
+    }
+}
@@ -0,0 +1,170 @@
+//! Image Quality Module
+//!
+//! Quick checks on a receipt photo before it is sent to OCR, so the UI can
+//! ask for a retake instead of returning an unreadable scan. Three metrics
+//! are measured:
+//! - Sharpness, as the variance of the Laplacian (blurred photos have few
+//!   strong edges, so the variance is low)
+//! - Brightness, as the mean grayscale intensity
+//! - Resolution, as the pixels per inch across a receipt of typical width
+
+use image::{DynamicImage, GrayImage};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::imaging::{self, DecodeLimits, UpscaleSettings};
+
+/// Photos are measured at this size so the blur score doesn't depend on
+/// the camera's resolution
+const SAMPLE_EDGE: u32 = 1000;
+
+/// Laplacian variance below which a photo is too blurry to read
+const MIN_SHARPNESS: f64 = 100.0;
+
+/// Mean intensity range (0-255) outside which a photo is too dark or washed out
+const MIN_BRIGHTNESS: f64 = 50.0;
+const MAX_BRIGHTNESS: f64 = 225.0;
+
+/// Estimated resolution below which small print is lost
+const MIN_DPI: f32 = 100.0;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityIssue {
+    Blurry,
+    TooDark,
+    Overexposed,
+    LowResolution,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityRecommendation {
+    Ok,
+    Retake,
+}
+
+/// Measured quality of a photo and whether it should be retaken
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QualityReport {
+    pub width: u32,
+    pub height: u32,
+    pub estimated_dpi: f32,
+    /// Variance of the Laplacian; higher is sharper
+    pub sharpness: f64,
+    /// Mean grayscale intensity, 0-255
+    pub brightness: f64,
+    pub issues: Vec<QualityIssue>,
+    pub recommendation: QualityRecommendation,
+}
+
+/// Variance of the 4-neighbour Laplacian over the image interior
+pub fn laplacian_variance(image: &GrayImage) -> f64 {
+    let (width, height) = image.dimensions();
+    if width < 3 || height < 3 {
+        return 0.0;
+    }
+
+    let (mut sum, mut sum_sq, mut count) = (0.0, 0.0, 0.0);
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let pixel = |x: u32, y: u32| image.get_pixel(x, y)[0] as f64;
+            let laplacian = pixel(x - 1, y) + pixel(x + 1, y) + pixel(x, y - 1) + pixel(x, y + 1) - 4.0 * pixel(x, y);
+            sum += laplacian;
+            sum_sq += laplacian * laplacian;
+            count += 1.0;
+        }
+    }
+    let mean = sum / count;
+    sum_sq / count - mean * mean
+}
+
+/// Mean grayscale intensity
+pub fn mean_brightness(image: &GrayImage) -> f64 {
+    let pixels = image.width() as u64 * image.height() as u64;
+    if pixels == 0 {
+        return 0.0;
+    }
+    image.pixels().map(|p| p[0] as u64).sum::<u64>() as f64 / pixels as f64
+}
+
+/// Measure a decoded photo. `width` and `height` are the original
+/// dimensions, in case the image was downscaled while loading.
+pub fn assess(image: &DynamicImage, width: u32, height: u32, assumed_width_mm: f32) -> QualityReport {
+    let sample = imaging::downscale_to_fit(image.clone(), SAMPLE_EDGE).to_luma8();
+    let sharpness = laplacian_variance(&sample);
+    let brightness = mean_brightness(&sample);
+    let estimated_dpi = if assumed_width_mm > 0.0 {
+        width.min(height) as f32 / (assumed_width_mm / 25.4)
+    } else {
+        0.0
+    };
+
+    let mut issues = Vec::new();
+    if sharpness < MIN_SHARPNESS {
+        issues.push(QualityIssue::Blurry);
+    }
+    if brightness < MIN_BRIGHTNESS {
+        issues.push(QualityIssue::TooDark);
+    } else if brightness > MAX_BRIGHTNESS {
+        issues.push(QualityIssue::Overexposed);
+    }
+    if estimated_dpi < MIN_DPI {
+        issues.push(QualityIssue::LowResolution);
+    }
+
+    QualityReport {
+        width,
+        height,
+        estimated_dpi,
+        sharpness,
+        brightness,
+        recommendation: if issues.is_empty() { QualityRecommendation::Ok } else { QualityRecommendation::Retake },
+        issues,
+    }
+}
+
+/// Load a photo and measure it
+pub fn check_image(path: &Path, limits: &DecodeLimits, upscale: &UpscaleSettings) -> Result<QualityReport, String> {
+    let (width, height) = imaging::image_dimensions(path)?;
+    let image = imaging::load_image(path, limits)?;
+    Ok(assess(&image, width, height, upscale.assumed_width_mm))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    /// Black text-like stripes on white paper
+    fn striped(width: u32, height: u32) -> DynamicImage {
+        let image = GrayImage::from_fn(width, height, |x, y| {
+            if (y / 6) % 3 == 0 && (x / 4) % 2 == 0 {
+                Luma([20])
+            } else {
+                Luma([235])
+            }
+        });
+        DynamicImage::ImageLuma8(image)
+    }
+
+    #[test]
+    fn test_blurry_and_dark_photos_need_a_retake() {
+        let sharp = assess(&striped(800, 1200), 800, 1200, 80.0);
+        assert_eq!(sharp.recommendation, QualityRecommendation::Ok, "{:?}", sharp);
+
+        let blurred = striped(800, 1200).blur(6.0);
+        let report = assess(&blurred, 800, 1200, 80.0);
+        assert!(report.sharpness < sharp.sharpness);
+        assert_eq!(report.issues, vec![QualityIssue::Blurry]);
+        assert_eq!(report.recommendation, QualityRecommendation::Retake);
+
+        let dark = DynamicImage::ImageLuma8(GrayImage::from_pixel(800, 1200, Luma([10])));
+        assert!(assess(&dark, 800, 1200, 80.0).issues.contains(&QualityIssue::TooDark));
+
+        // 200px across 80mm of paper is about 63 dpi
+        assert!(assess(&striped(200, 300), 200, 300, 80.0)
+            .issues
+            .contains(&QualityIssue::LowResolution));
+    }
+}
@@ -0,0 +1,526 @@
+//! Receipt Totals Module
+//!
+//! Breaks a receipt's total into its parts. Payment tender lines (cash, card,
+//! Buy Now Pay Later) are pulled out of the text, since split-tender and BNPL
+//! receipts list several amounts near the total; this keeps the expense total
+//! correct and gives bank matching the individual amounts that actually hit
+//! an account. Tips and foreign transaction fees are captured the same way,
+//! as separate components of the total, along with card surcharges, rounding
+//! and discounts, which are taxed differently from the items. Finally the
+//! items are cross-checked against the total, so a misread total or a missed
+//! item is flagged for review.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+use crate::money::Money;
+use crate::ocr::{ExtractedField, ExtractedReceipt};
+
+/// Largest gap between the items and the total put down to rounding
+const ITEM_TOTAL_TOLERANCE: Money = Money::from_cents(5);
+
+/// Factor applied to the total's confidence when the items don't add up
+const DISCREPANCY_PENALTY: f64 = 0.7;
+
+/// How part of a receipt was paid
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum TenderMethod {
+    Cash,
+    Card,
+    Afterpay,
+    Zip,
+    Humm,
+    Klarna,
+    PayPal,
+    GiftCard,
+}
+
+impl TenderMethod {
+    pub(crate) fn from_label(label: &str) -> Option<Self> {
+        let label = label.to_lowercase();
+        let method = match label.split_whitespace().next()? {
+            "cash" => TenderMethod::Cash,
+            "eftpos" | "visa" | "mastercard" | "amex" | "debit" | "credit" | "card" => TenderMethod::Card,
+            "afterpay" => TenderMethod::Afterpay,
+            "zip" | "zippay" => TenderMethod::Zip,
+            "humm" => TenderMethod::Humm,
+            "klarna" => TenderMethod::Klarna,
+            "paypal" => TenderMethod::PayPal,
+            "gift" | "giftcard" => TenderMethod::GiftCard,
+            _ => return None,
+        };
+        Some(method)
+    }
+
+    /// Buy Now Pay Later providers settle the merchant and bill the
+    /// customer in instalments
+    pub fn is_bnpl(&self) -> bool {
+        matches!(
+            self,
+            TenderMethod::Afterpay | TenderMethod::Zip | TenderMethod::Humm | TenderMethod::Klarna
+        )
+    }
+}
+
+/// A line adjusting the receipt total rather than a purchased item
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AdjustmentKind {
+    Tip,
+    CardSurcharge,
+    Rounding,
+    Discount,
+}
+
+impl AdjustmentKind {
+    /// Surcharges are part of the price of a taxable supply and discounts
+    /// reduce it, so both change the GST. A voluntary tip isn't payment for
+    /// the supply and cash rounding doesn't change the GST payable.
+    pub fn affects_gst(&self) -> bool {
+        matches!(self, AdjustmentKind::CardSurcharge | AdjustmentKind::Discount)
+    }
+}
+
+/// One adjustment line on a receipt
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReceiptAdjustment {
+    pub kind: AdjustmentKind,
+    /// Change to the total; negative for discounts and rounding down
+    pub amount: Money,
+    pub confidence: f64,
+    /// Receipt line the adjustment was read from
+    pub line: String,
+}
+
+/// One payment line on a receipt
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TenderLine {
+    pub method: TenderMethod,
+    pub amount: Money,
+    /// Number of BNPL instalments, when printed on the receipt
+    pub instalments: Option<u32>,
+    pub confidence: f64,
+    /// Receipt line the tender was read from
+    pub line: String,
+}
+
+fn tender_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(
+            r"(?i)^\s*(cash|eftpos|visa|mastercard|amex|debit|credit|card|afterpay|zip\s*pay|zip|humm|klarna|paypal|gift\s*card)\b[^\d$\n]*\$?\s*([\d,]+\.\d{2})\s*$",
+        )
+        .unwrap()
+    })
+}
+
+fn change_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(?i)^\s*change\b[^\d$\n]*\$?\s*([\d,]+\.\d{2})").unwrap())
+}
+
+fn instalment_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"(?i)(\d+)\s*(?:payments|instal+ments)\s*of\s*\$?\s*[\d,]+\.\d{2}").unwrap()
+    })
+}
+
+/// Label at the start of an adjustment line, optionally after a percentage
+fn adjustment_label_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(
+            r"(?i)^\s*(?:\d+(?:\.\d+)?\s*%\s*)?(?:(?P<tip>tips?|gratuity)|(?P<surcharge>(?:(?:credit\s*)?card|eftpos|visa|mastercard|amex|merchant|payment)\s+surcharge|surcharge)|(?P<rounding>(?:cash\s+)?rounding|round(?:ing)?\s+adj(?:ustment)?)|(?P<discount>(?:(?:member|staff|loyalty|promo)\s+)?(?:discount|disc)|promo(?:tion)?|coupon|voucher))\b",
+        )
+        .unwrap()
+    })
+}
+
+/// Signed amount ending a line: `-0.02`, `$-0.02`, `0.02-` or `(0.02)`
+fn signed_amount_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"(\()?(-)?\s*\$?\s*(-)?([\d,]+\.\d{2})(-)?\)?\s*$").unwrap())
+}
+
+fn foreign_fee_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(
+            r"(?i)^\s*(?:(?:foreign|international|intl|overseas)\s+(?:transaction|currency|exchange|purchase)?\s*fee|fx\s+fee|currency\s+conversion\s+fee)\b[^\d$\n]*\$?\s*([\d,]+\.\d{2})\s*$",
+        )
+        .unwrap()
+    })
+}
+
+/// Find tender lines and any cash change given
+pub fn extract_tenders(text: &str) -> (Vec<TenderLine>, Money) {
+    let instalments = instalment_pattern()
+        .captures(text)
+        .and_then(|caps| caps[1].parse::<u32>().ok());
+
+    let mut tenders = Vec::new();
+    let mut change = Money::ZERO;
+    for line in text.lines() {
+        if let Some(caps) = change_pattern().captures(line) {
+            change += Money::parse(&caps[1]).unwrap_or(Money::ZERO);
+            continue;
+        }
+        // "Card surcharge 0.45" is not a card payment
+        if adjustment_kind(line).is_some() {
+            continue;
+        }
+        let Some(caps) = tender_pattern().captures(line) else {
+            continue;
+        };
+        let (Some(method), Some(amount)) = (TenderMethod::from_label(&caps[1]), Money::parse(&caps[2])) else {
+            continue;
+        };
+        if amount <= Money::ZERO {
+            continue;
+        }
+        tenders.push(TenderLine {
+            method,
+            amount,
+            instalments: if method.is_bnpl() { instalments } else { None },
+            confidence: 0.80,
+            line: line.trim().to_string(),
+        });
+    }
+
+    (tenders, change)
+}
+
+/// Amount actually spent: tendered amounts less any change
+pub fn tendered_total(tenders: &[TenderLine], change: Money) -> Option<Money> {
+    if tenders.is_empty() {
+        return None;
+    }
+    Some(tenders.iter().map(|t| t.amount).sum::<Money>() - change)
+}
+
+/// Record tenders on a receipt and correct its total when the tenders
+/// disagree with the detected total
+pub fn apply_tenders(receipt: &mut ExtractedReceipt) {
+    let (tenders, change) = extract_tenders(&receipt.raw_text);
+
+    if let Some(total) = tendered_total(&tenders, change) {
+        let detected = receipt.total_amount.value;
+        if detected == total {
+            receipt.total_amount.confidence = (receipt.total_amount.confidence + 0.05).min(1.0);
+        } else if total > Money::ZERO {
+            receipt.total_amount = ExtractedField::new(total, 0.80, "tender_sum");
+        }
+    }
+
+    receipt.tenders = tenders;
+}
+
+/// Find a labelled component amount, such as a foreign transaction fee, on
+/// its own line
+fn component_field(text: &str, pattern: &Regex, source: &str) -> Option<ExtractedField<Money>> {
+    text.lines()
+        .filter_map(|line| pattern.captures(line))
+        .filter_map(|caps| Money::parse(&caps[1]))
+        .find(|amount| *amount > Money::ZERO)
+        .map(|amount| ExtractedField::new(amount, 0.80, source))
+}
+
+/// Kind of adjustment a line or item name is labelled as
+pub fn adjustment_kind(label: &str) -> Option<AdjustmentKind> {
+    let caps = adjustment_label_pattern().captures(label)?;
+    let kind = if caps.name("tip").is_some() {
+        AdjustmentKind::Tip
+    } else if caps.name("surcharge").is_some() {
+        AdjustmentKind::CardSurcharge
+    } else if caps.name("rounding").is_some() {
+        AdjustmentKind::Rounding
+    } else {
+        AdjustmentKind::Discount
+    };
+    Some(kind)
+}
+
+/// Find the adjustment lines on a receipt. Tips and surcharges always add
+/// to the total and discounts always reduce it, however the sign is printed;
+/// rounding keeps its printed sign.
+pub fn extract_adjustments(text: &str) -> Vec<ReceiptAdjustment> {
+    text.lines()
+        .filter_map(|line| {
+            let label_end = adjustment_label_pattern().find(line)?.end();
+            let kind = adjustment_kind(line)?;
+            let caps = signed_amount_pattern().captures(&line[label_end..])?;
+            let amount = Money::parse(&caps[4]).filter(|a| *a > Money::ZERO)?;
+            let negative = [1, 2, 3, 5].iter().any(|&i| caps.get(i).is_some());
+            let amount = match kind {
+                AdjustmentKind::Tip | AdjustmentKind::CardSurcharge => amount,
+                AdjustmentKind::Discount => -amount,
+                AdjustmentKind::Rounding if negative => -amount,
+                AdjustmentKind::Rounding => amount,
+            };
+            Some(ReceiptAdjustment {
+                kind,
+                amount,
+                confidence: 0.80,
+                line: line.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Record adjustment and foreign transaction fee lines on a receipt. They
+/// are part of the total, so they are removed from the purchased items.
+pub fn apply_tip_and_fees(receipt: &mut ExtractedReceipt) {
+    receipt.adjustments = extract_adjustments(&receipt.raw_text);
+    receipt.tip = receipt
+        .adjustments
+        .iter()
+        .find(|a| a.kind == AdjustmentKind::Tip)
+        .map(|tip| ExtractedField::new(tip.amount, tip.confidence, "keyword_tip"));
+    receipt.foreign_fee = component_field(&receipt.raw_text, foreign_fee_pattern(), "keyword_foreign_fee");
+
+    receipt.items.retain(|item| {
+        let line = format!("{} {:.2}", item.name, item.amount);
+        adjustment_kind(&item.name).is_none() && !foreign_fee_pattern().is_match(&line)
+    });
+}
+
+/// A receipt total that doesn't match the sum of its items
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TotalDiscrepancy {
+    pub field: String,
+    /// Sum of the line items plus any adjustments and foreign transaction fee
+    pub items_total: Money,
+    pub total_amount: Money,
+    /// Total less the items; positive when items appear to be missing
+    pub difference: Money,
+}
+
+/// Compare the items with the total. Receipts without items are not
+/// checked, and items matching the subtotal are accepted since surcharges
+/// and rounding are often printed after it.
+pub fn item_total_discrepancy(receipt: &ExtractedReceipt) -> Option<TotalDiscrepancy> {
+    if receipt.items.is_empty() || receipt.total_amount.value <= Money::ZERO {
+        return None;
+    }
+    let within_tolerance = |difference: Money| difference.abs() <= ITEM_TOTAL_TOLERANCE;
+
+    let items: Money = receipt.items.iter().map(|item| item.amount).sum();
+    if receipt
+        .subtotal
+        .as_ref()
+        .is_some_and(|subtotal| within_tolerance(subtotal.value - items))
+    {
+        return None;
+    }
+
+    // The tip is kept in its own field as well as the adjustments
+    let adjustments = receipt
+        .adjustments
+        .iter()
+        .filter(|a| a.kind != AdjustmentKind::Tip)
+        .map(|a| a.amount);
+    let components = [&receipt.tip, &receipt.foreign_fee]
+        .into_iter()
+        .flatten()
+        .map(|field| field.value)
+        .chain(adjustments)
+        .sum::<Money>();
+    let items_total = items + components;
+    let difference = receipt.total_amount.value - items_total;
+    (!within_tolerance(difference)).then(|| TotalDiscrepancy {
+        field: "total_amount".to_string(),
+        items_total,
+        total_amount: receipt.total_amount.value,
+        difference,
+    })
+}
+
+/// Lower the confidence of a total its items don't add up to
+pub fn cross_check_items(receipt: &mut ExtractedReceipt) {
+    if item_total_discrepancy(receipt).is_none() {
+        return;
+    }
+    receipt.total_amount.confidence *= DISCREPANCY_PENALTY;
+    receipt.overall_confidence =
+        (receipt.vendor.confidence + receipt.date.confidence + receipt.total_amount.confidence) / 3.0;
+}
+
+/// Amounts to look for on bank statements. Cash never reaches the bank and
+/// BNPL purchases appear as instalments, so each tender is matched on its own.
+/// Banks post foreign transaction fees as a separate charge, so the fee is
+/// matched on its own and taken off the purchase amount.
+pub fn bank_match_amounts(receipt: &ExtractedReceipt) -> Vec<Money> {
+    let mut amounts: Vec<Money> = if receipt.tenders.is_empty() {
+        vec![receipt.total_amount.value]
+    } else {
+        receipt
+            .tenders
+            .iter()
+            .filter(|t| t.method != TenderMethod::Cash)
+            .map(|t| match t.instalments.filter(|n| *n > 1) {
+                Some(n) => Money::from_dollars(t.amount.to_dollars() / n as f64),
+                None => t.amount,
+            })
+            .collect()
+    };
+
+    let Some(fee) = receipt.foreign_fee.as_ref().map(|f| f.value) else {
+        return amounts;
+    };
+    if let Some(purchase) = amounts.iter_mut().filter(|a| **a > fee).max() {
+        *purchase = *purchase - fee;
+        amounts.push(fee);
+    }
+    amounts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ocr::ExtractedItem;
+
+    fn dollars(amounts: Vec<Money>) -> Vec<f64> {
+        amounts.into_iter().map(Money::to_dollars).collect()
+    }
+
+    fn test_field<T>(value: T, confidence: f64) -> ExtractedField<T> {
+        ExtractedField::new(value, confidence, "test")
+    }
+
+    /// Receipt read from `text`, with the first line as the vendor
+    fn receipt(text: &str, total: f64) -> ExtractedReceipt {
+        ExtractedReceipt {
+            vendor: test_field(text.lines().next().unwrap_or_default().to_string(), 0.9),
+            date: test_field("2024-01-01".to_string(), 0.9),
+            total_amount: test_field(Money::from_dollars(total), 0.8),
+            raw_text: text.to_string(),
+            overall_confidence: 0.8,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_split_tender_with_change() {
+        let text = "JB HI-FI\nHeadphones 149.00\nTOTAL 149.00\nVISA 100.00\nCASH $50.00\nCHANGE $1.00";
+        let (tenders, change) = extract_tenders(text);
+
+        assert_eq!(tenders.len(), 2);
+        assert_eq!(tenders[0].method, TenderMethod::Card);
+        assert_eq!(tenders[1].method, TenderMethod::Cash);
+        assert_eq!(change.to_dollars(), 1.0);
+        assert_eq!(tendered_total(&tenders, change).map(Money::to_dollars), Some(149.0));
+    }
+
+    #[test]
+    fn test_bnpl_instalment_matching() {
+        let text = "Myer\nTOTAL 200.00\nAFTERPAY $200.00\n4 payments of $50.00";
+        let (tenders, _) = extract_tenders(text);
+        assert_eq!(tenders.len(), 1);
+        assert_eq!(tenders[0].instalments, Some(4));
+
+        let receipt = ExtractedReceipt {
+            tenders,
+            ..receipt(text, 200.0)
+        };
+        assert_eq!(dollars(bank_match_amounts(&receipt)), vec![50.0]);
+    }
+
+    #[test]
+    fn test_tip_and_foreign_fee_components() {
+        let text = "Figma Inc\nProfessional plan 45.00\nForeign transaction fee $1.35\nTOTAL 46.35\nVISA 46.35";
+        let (tenders, _) = extract_tenders(text);
+        let mut receipt = ExtractedReceipt {
+            items: vec![ExtractedItem {
+                name: "Foreign transaction fee".to_string(),
+                amount: Money::from_dollars(1.35),
+                confidence: 0.8,
+                quantity: None,
+            }],
+            tenders,
+            ..receipt(text, 46.35)
+        };
+        apply_tip_and_fees(&mut receipt);
+
+        assert!(receipt.tip.is_none());
+        assert_eq!(receipt.foreign_fee.as_ref().map(|f| f.value.to_dollars()), Some(1.35));
+        assert!(receipt.items.is_empty());
+        assert_eq!(dollars(bank_match_amounts(&receipt)), vec![45.0, 1.35]);
+
+        receipt.raw_text = "Cafe Sydney\nSubtotal 80.00\nGratuity 8.00\nTOTAL 88.00".to_string();
+        apply_tip_and_fees(&mut receipt);
+        assert_eq!(receipt.tip.as_ref().map(|f| f.value.to_dollars()), Some(8.0));
+        assert!(receipt.foreign_fee.is_none());
+    }
+
+    #[test]
+    fn test_adjustment_lines_are_typed() {
+        let text = "Cafe Sydney\nBarramundi 46.00\nRisotto 34.00\n10% Member Discount -8.00\nGratuity 7.20\n\
+                    Card surcharge 1.5% $1.19\nRounding 0.01-\nTOTAL 80.38\nVISA 80.38";
+        let adjustments = extract_adjustments(text);
+        let typed: Vec<(AdjustmentKind, f64)> = adjustments.iter().map(|a| (a.kind, a.amount.to_dollars())).collect();
+        assert_eq!(
+            typed,
+            vec![
+                (AdjustmentKind::Discount, -8.0),
+                (AdjustmentKind::Tip, 7.2),
+                (AdjustmentKind::CardSurcharge, 1.19),
+                (AdjustmentKind::Rounding, -0.01),
+            ]
+        );
+        assert!(AdjustmentKind::Discount.affects_gst() && !AdjustmentKind::Tip.affects_gst());
+
+        // The surcharge is not a card payment
+        let (tenders, _) = extract_tenders(text);
+        assert_eq!(dollars(tenders.iter().map(|t| t.amount).collect()), vec![80.38]);
+
+        let item = |name: &str, amount: f64| ExtractedItem {
+            name: name.to_string(),
+            amount: Money::from_dollars(amount),
+            confidence: 0.8,
+            quantity: None,
+        };
+        let mut receipt = ExtractedReceipt {
+            items: vec![item("Barramundi", 46.0), item("Risotto", 34.0), item("10% Member Discount", 8.0)],
+            tenders,
+            ..receipt(text, 80.38)
+        };
+        apply_tip_and_fees(&mut receipt);
+        assert_eq!(receipt.items.len(), 2);
+        assert_eq!(receipt.tip.as_ref().map(|f| f.value.to_dollars()), Some(7.2));
+        assert_eq!(receipt.adjustments.len(), 4);
+        assert_eq!(item_total_discrepancy(&receipt), None);
+    }
+
+    #[test]
+    fn test_items_cross_checked_against_total() {
+        let field = |value: f64| test_field(Money::from_dollars(value), 0.9);
+        let item = |name: &str, amount: f64| ExtractedItem {
+            name: name.to_string(),
+            amount: Money::from_dollars(amount),
+            confidence: 0.8,
+            quantity: None,
+        };
+        let mut receipt = ExtractedReceipt {
+            total_amount: field(88.0),
+            items: vec![item("Barramundi", 46.0), item("Risotto", 34.0)],
+            overall_confidence: 0.9,
+            tip: Some(field(8.0)),
+            ..receipt("Cafe Sydney", 88.0)
+        };
+        assert_eq!(item_total_discrepancy(&receipt), None);
+
+        // A total misread as 98.00
+        receipt.total_amount = field(98.0);
+        let discrepancy = item_total_discrepancy(&receipt).unwrap();
+        assert_eq!(dollars(vec![discrepancy.items_total, discrepancy.difference]), vec![88.0, 10.0]);
+        cross_check_items(&mut receipt);
+        assert!((receipt.total_amount.confidence - 0.63).abs() < 1e-9);
+        assert!(receipt.overall_confidence < 0.9);
+
+        // Items matching the subtotal are accepted
+        receipt.subtotal = Some(field(80.0));
+        assert_eq!(item_total_discrepancy(&receipt), None);
+    }
+}
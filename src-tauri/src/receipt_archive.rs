@@ -0,0 +1,128 @@
+//! Receipt Archive Module
+//!
+//! Keeps a copy of each scanned photo in the data directory so the original
+//! survives being deleted from the phone. Copies are filed by financial year
+//! and month (`Receipts/FY2023-24/07-July/`) under a name built from the
+//! extracted date, vendor and amount (`2023-07-14_bunnings_42.50.jpg`).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::export::slugify;
+use crate::ocr::{self, ExtractedReceipt};
+use crate::periods;
+use crate::settings::AppSettings;
+use crate::storage;
+
+/// Receipts whose date couldn't be read are filed here instead of by month
+const UNDATED_DIR: &str = "Undated";
+
+/// The app-managed archive under the data directory
+pub fn archive_directory() -> Result<PathBuf, String> {
+    Ok(storage::get_data_directory()?.join("Receipts"))
+}
+
+/// Archive path for a receipt relative to the archive root, without any
+/// suffix added to keep it unique
+pub fn archive_relative_path(receipt: &ExtractedReceipt, source: &Path) -> PathBuf {
+    let date = periods::parse_date(&receipt.date.value);
+    let dir = match date {
+        Some(date) => PathBuf::from(periods::financial_year_label(periods::financial_year_of(date)))
+            .join(date.format("%m-%B").to_string()),
+        None => PathBuf::from(UNDATED_DIR),
+    };
+
+    let vendor = slugify(&receipt.vendor.value, 40);
+    let stem = [
+        date.map_or_else(|| "undated".to_string(), |d| d.format("%Y-%m-%d").to_string()),
+        if vendor.is_empty() { "unknown-vendor".to_string() } else { vendor },
+        format!("{:.2}", receipt.total_amount.value),
+    ]
+    .join("_");
+    let extension = source
+        .extension()
+        .and_then(|e| e.to_str())
+        .map_or_else(|| "jpg".to_string(), |e| e.to_lowercase().replace("jpeg", "jpg"));
+    dir.join(format!("{}.{}", stem, extension))
+}
+
+fn same_contents(a: &Path, b: &Path) -> bool {
+    match (fs::read(a), fs::read(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Copy a scanned file into the archive under `root` and return where it
+/// went. Scanning the same file again reuses its existing copy; a different
+/// receipt with the same name gets `_2`, `_3`, ... appended.
+pub fn archive_receipt(receipt: &ExtractedReceipt, source: &Path, root: &Path) -> Result<PathBuf, String> {
+    let relative = archive_relative_path(receipt, source);
+    let target = root.join(&relative);
+    let dir = target.parent().unwrap_or(root);
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create directory: {}", e))?;
+
+    let stem = relative.file_stem().and_then(|s| s.to_str()).unwrap_or("receipt");
+    let extension = relative.extension().and_then(|e| e.to_str()).unwrap_or("jpg");
+    let mut candidate = target.clone();
+    let mut counter = 2;
+    while candidate.exists() {
+        if same_contents(&candidate, source) {
+            return Ok(candidate);
+        }
+        candidate = dir.join(format!("{}_{}.{}", stem, counter, extension));
+        counter += 1;
+    }
+    fs::copy(source, &candidate).map_err(|e| format!("Failed to archive {}: {}", source.display(), e))?;
+    Ok(candidate)
+}
+
+/// Archive a freshly scanned receipt when the setting is on, recording the
+/// copy's path on the receipt. A photo scanned sideways is turned upright in
+/// the copy, with the receipt's field boxes moved to match; the original is
+/// left as it is. Failures are logged rather than failing the scan.
+pub fn archive_if_enabled(receipt: &mut ExtractedReceipt, source: &str, settings: &AppSettings) {
+    if !settings.archive_receipts {
+        return;
+    }
+    let path = match archive_directory().and_then(|root| archive_receipt(receipt, Path::new(source), &root)) {
+        Ok(path) => path,
+        Err(e) => {
+            log::warn!("Failed to archive receipt {}: {}", source, e);
+            return;
+        }
+    };
+    if let Err(e) = ocr::persist_orientation(&path, receipt) {
+        log::warn!("Failed to store upright copy of {}: {}", source, e);
+    }
+    receipt.archived_path = Some(path.to_string_lossy().to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::receipt_parser::ReceiptParser;
+
+    #[test]
+    fn test_receipts_are_filed_by_year_and_month() {
+        let root = storage::test_dir();
+        let photo = root.join("IMG_0042.JPEG");
+        fs::write(&photo, b"first photo").unwrap();
+
+        let mut receipt = ReceiptParser::shared().parse_from_text("TOTAL $42.50", 0.9);
+        receipt.vendor.value = "Bunnings Warehouse".to_string();
+        receipt.date.value = "2023-07-14".to_string();
+        let archived = archive_receipt(&receipt, &photo, &root).unwrap();
+        assert_eq!(archived, root.join("FY2023-24/07-July/2023-07-14_bunnings-warehouse_42.50.jpg"));
+        assert_eq!(fs::read(&archived).unwrap(), b"first photo");
+
+        // Rescanning reuses the copy; a different photo gets its own name
+        assert_eq!(archive_receipt(&receipt, &photo, &root).unwrap(), archived);
+        fs::write(&photo, b"second photo").unwrap();
+        let second = archive_receipt(&receipt, &photo, &root).unwrap();
+        assert_eq!(second.file_name().unwrap(), "2023-07-14_bunnings-warehouse_42.50_2.jpg");
+
+        receipt.date.value = String::new();
+        assert!(archive_relative_path(&receipt, &photo).starts_with(UNDATED_DIR));
+    }
+}
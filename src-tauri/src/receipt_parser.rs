@@ -0,0 +1,463 @@
+//! Receipt Parser Module
+//!
+//! Extracts receipt fields from OCR lines or raw receipt text, using
+//! regexes for labelled values and the layout of a typical till receipt:
+//! - Vendor name in the first few lines
+//! - Purchased items between the header and the subtotal or total, with
+//!   repeated lines for the same product collapsed into one item
+//! - Subtotal, GST, total and payment lines at the bottom
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+use crate::dates::{self, DateOrder};
+use crate::money::Money;
+use crate::ocr::{ExtractedField, ExtractedItem, ExtractedReceipt, OcrLine};
+use crate::receipt::{self, TenderMethod};
+
+/// How many lines from the top the vendor name is looked for in
+const VENDOR_SEARCH_LINES: usize = 5;
+
+/// Receipt parser for extracting structured data from OCR output
+pub struct ReceiptParser {
+    amount_pattern: Regex,
+    date_pattern: Regex,
+    total_pattern: Regex,
+    subtotal_pattern: Regex,
+    gst_pattern: Regex,
+    payment_method_pattern: Regex,
+    card_network_pattern: Regex,
+    /// Masked card number, keeping the last four digits
+    card_number_pattern: Regex,
+    /// Header lines that are never the vendor name
+    vendor_skip_pattern: Regex,
+    /// Lines that carry an amount but are not purchased items
+    non_item_pattern: Regex,
+}
+
+fn not_found<T: Default>() -> ExtractedField<T> {
+    ExtractedField::new(T::default(), 0.0, "not_found")
+}
+
+impl ReceiptParser {
+    pub fn new() -> Result<Self, String> {
+        let regex = |pattern: &str| Regex::new(pattern).map_err(|e| e.to_string());
+        Ok(Self {
+            amount_pattern: regex(r"\$?\s?(\d{1,3}(?:,\d{3})*\.\d{2})\b")?,
+            date_pattern: regex(
+                r"(?i)\b(\d{4}-\d{2}-\d{2}|\d{1,2}[/-]\d{1,2}[/-]\d{2,4}|\d{1,2} (?:jan|feb|mar|apr|may|jun|jul|aug|sep|oct|nov|dec)[a-z]* \d{4})\b",
+            )?,
+            total_pattern: regex(r"(?i)\b(?:total|amount due|balance due)\b|合计|總計|总计|应付|應付|实付|實付")?,
+            subtotal_pattern: regex(r"(?i)\bsub\s*-?\s*total\b")?,
+            gst_pattern: regex(r"(?i)\b(?:gst|tax)\b[^\d\n]*?\$?\s*(\d{1,3}(?:,\d{3})*\.\d{2})\b")?,
+            payment_method_pattern: regex(
+                r"(?i)\b(cash|eftpos|visa|mastercard|amex|debit|afterpay|zip\s*pay|humm|klarna|paypal|gift\s*card)\b",
+            )?,
+            card_network_pattern: regex(
+                r"(?i)\b(visa|master\s?card|amex|american\s+express|eftpos|debit|diners(?:\s+club)?|jcb|union\s?pay)\b",
+            )?,
+            card_number_pattern: regex(r"(?i)(?:[*x•#]{2,}[\s*x•#-]*|\bending(?:\s+in)?\s+)(\d{4})\b")?,
+            vendor_skip_pattern: regex(
+                r"(?i)^(?:tax\s+invoice|invoice|receipt|customer\s+copy|merchant\s+copy|welcome\b.*|thank\s+you\b.*)$",
+            )?,
+            non_item_pattern: regex(
+                r"(?i)\b(?:total|sub\s*-?\s*total|gst|tax|change|cash|eftpos|card|visa|mastercard|balance|rounding|tendered)\b",
+            )?,
+        })
+    }
+
+    /// Parser shared by every extraction, so patterns compile once
+    pub fn shared() -> &'static ReceiptParser {
+        static PARSER: OnceLock<ReceiptParser> = OnceLock::new();
+        PARSER.get_or_init(|| ReceiptParser::new().expect("receipt patterns are valid"))
+    }
+
+    /// Parse receipt text that has no word positions, such as a PDF text
+    /// layer, giving every line the same confidence
+    pub fn parse_from_text(&self, text: &str, line_confidence: f64) -> ExtractedReceipt {
+        let lines: Vec<OcrLine> = text
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|line| !line.is_empty())
+            .map(|text| OcrLine {
+                text,
+                confidence: line_confidence,
+                words: Vec::new(),
+            })
+            .collect();
+        self.parse_lines(&lines)
+    }
+
+    /// Build receipt fields from recognised lines, scaling each field's
+    /// confidence by the OCR confidence of the line it was read from
+    pub fn parse_lines(&self, lines: &[OcrLine]) -> ExtractedReceipt {
+        let vendor_index = self.find_vendor(lines);
+        let vendor = vendor_index
+            .map(|i| ExtractedField {
+                bbox: lines[i].bbox(),
+                ..ExtractedField::new(lines[i].text.clone(), lines[i].confidence * 0.85, &format!("ocr_line_{}", i))
+            })
+            .unwrap_or_else(not_found);
+
+        let (total_index, total_amount) = self.extract_total(lines);
+        let subtotal = self.extract_labelled(lines, |text| self.subtotal_pattern.is_match(text), "keyword_subtotal");
+        let gst = self
+            .extract_gst(lines)
+            .filter(|gst| total_index.is_none() || gst.value < total_amount.value);
+
+        // Items sit between the header and the first summary line
+        let summary_index = lines
+            .iter()
+            .position(|line| self.subtotal_pattern.is_match(&line.text) || self.total_pattern.is_match(&line.text))
+            .or(total_index);
+        let first_item = vendor_index.map_or(0, |i| i + 1);
+        let last_item = summary_index.unwrap_or(lines.len());
+        let items = lines
+            .get(first_item..last_item.max(first_item))
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|line| self.extract_item(line))
+            .collect();
+        let items = collapse_repeated_items(items);
+
+        let date = self.extract_date(lines);
+        let overall_confidence = (vendor.confidence + date.confidence + total_amount.confidence) / 3.0;
+        let mut receipt = ExtractedReceipt {
+            vendor,
+            date,
+            total_amount,
+            items,
+            raw_text: lines.iter().map(|l| l.text.as_str()).collect::<Vec<_>>().join("\n"),
+            overall_confidence,
+            tenders: Vec::new(),
+            rotation_degrees: 0,
+            tip: None,
+            foreign_fee: None,
+            adjustments: Vec::new(),
+            subtotal,
+            gst,
+            payment_method: None,
+            payment_details: None,
+            abn: None,
+            reference: None,
+            codes: Vec::new(),
+            archived_path: None,
+        };
+
+        // Split-tender and BNPL lines can otherwise be mistaken for the total
+        receipt::apply_tenders(&mut receipt);
+        receipt::apply_tip_and_fees(&mut receipt);
+        receipt::cross_check_items(&mut receipt);
+        receipt.payment_method = self.extract_payment_method(lines, &receipt, total_index);
+        receipt.payment_details = self.extract_payment_details(lines, &receipt, total_index);
+        receipt
+    }
+
+    fn last_amount(&self, text: &str) -> Option<Money> {
+        self.amount_pattern.captures_iter(text).last().and_then(|caps| Money::parse(&caps[1]))
+    }
+
+    /// A note such as "Total includes GST $1.00" rather than a total
+    fn is_gst_note(&self, text: &str) -> bool {
+        text.to_lowercase().contains("includ") && self.gst_pattern.is_match(text)
+    }
+
+    fn find_vendor(&self, lines: &[OcrLine]) -> Option<usize> {
+        lines.iter().take(VENDOR_SEARCH_LINES).position(|line| {
+            let text = line.text.trim();
+            text.chars().filter(|c| c.is_alphabetic()).count() >= 3
+                && !self.date_pattern.is_match(text)
+                && !self.amount_pattern.is_match(text)
+                && !self.vendor_skip_pattern.is_match(text)
+                && !text.to_lowercase().contains("invoice")
+                && !text.to_uppercase().contains("ABN")
+        })
+    }
+
+    fn extract_date(&self, lines: &[OcrLine]) -> ExtractedField<String> {
+        lines
+            .iter()
+            .enumerate()
+            .find_map(|(i, line)| {
+                let raw = self.date_pattern.captures(&line.text)?;
+                // Read day-first here; the engine re-reads ambiguous dates in the configured order
+                let date = dates::normalize(&raw[1], DateOrder::DayFirst);
+                let span = raw.get(1)?;
+                Some(ExtractedField {
+                    bbox: line.span_bbox(span.start(), span.end()),
+                    raw: Some(date.raw.clone()),
+                    ambiguous: date.ambiguous,
+                    ..ExtractedField::new(
+                        date.iso.unwrap_or(date.raw),
+                        line.confidence * 0.95,
+                        &format!("ocr_line_{}", i),
+                    )
+                })
+            })
+            .unwrap_or_else(not_found)
+    }
+
+    /// Prefer the last labelled total that is not a subtotal, falling back
+    /// to the largest amount on the receipt
+    fn extract_total(&self, lines: &[OcrLine]) -> (Option<usize>, ExtractedField<Money>) {
+        let labelled = lines.iter().enumerate().rev().find_map(|(i, line)| {
+            if !self.total_pattern.is_match(&line.text)
+                || self.subtotal_pattern.is_match(&line.text)
+                || self.is_gst_note(&line.text)
+            {
+                return None;
+            }
+            self.last_amount(&line.text).map(|amount| (i, amount, line))
+        });
+        if let Some((i, amount, line)) = labelled {
+            return (
+                Some(i),
+                ExtractedField {
+                    bbox: line.amount_bbox(),
+                    ..ExtractedField::new(amount, line.confidence * 0.95, "keyword_total")
+                },
+            );
+        }
+
+        let largest = lines
+            .iter()
+            .filter_map(|line| self.last_amount(&line.text).map(|amount| (amount, line)))
+            .fold(None, |best: Option<(Money, &OcrLine)>, (amount, line)| match best {
+                Some((b, _)) if b >= amount => best,
+                _ => Some((amount, line)),
+            });
+        (
+            None,
+            ExtractedField {
+                bbox: largest.and_then(|(_, line)| line.amount_bbox()),
+                ..ExtractedField::new(
+                    largest.map_or(Money::ZERO, |(amount, _)| amount),
+                    largest.map_or(0.0, |(_, line)| line.confidence * 0.6),
+                    "largest_amount",
+                )
+            },
+        )
+    }
+
+    /// Last amount on the first line matching a label
+    fn extract_labelled(
+        &self,
+        lines: &[OcrLine],
+        is_label: impl Fn(&str) -> bool,
+        source: &str,
+    ) -> Option<ExtractedField<Money>> {
+        lines.iter().find(|line| is_label(&line.text)).and_then(|line| {
+            Some(ExtractedField {
+                bbox: line.amount_bbox(),
+                ..ExtractedField::new(self.last_amount(&line.text)?, line.confidence * 0.9, source)
+            })
+        })
+    }
+
+    /// GST is printed on its own line ("GST 1.00") or as part of a total
+    /// ("Total includes GST $1.00"). Totals labelled "inc GST" carry the
+    /// total, not the GST, so they're skipped.
+    fn extract_gst(&self, lines: &[OcrLine]) -> Option<ExtractedField<Money>> {
+        lines.iter().find_map(|line| {
+            let is_total = self.total_pattern.is_match(&line.text) || self.subtotal_pattern.is_match(&line.text);
+            if is_total && !self.is_gst_note(&line.text) {
+                return None;
+            }
+            let caps = self.gst_pattern.captures(&line.text)?;
+            let span = caps.get(1)?;
+            Some(ExtractedField {
+                bbox: line.span_bbox(span.start(), span.end()),
+                ..ExtractedField::new(Money::parse(&caps[1])?, line.confidence * 0.9, "keyword_gst")
+            })
+        })
+    }
+
+    fn extract_item(&self, line: &OcrLine) -> Option<ExtractedItem> {
+        if self.non_item_pattern.is_match(&line.text) {
+            return None;
+        }
+        let caps = self.amount_pattern.captures_iter(&line.text).last()?;
+        let name = line.text[..caps.get(0)?.start()].trim().trim_end_matches(['$', ':']).trim();
+        if name.chars().filter(|c| c.is_alphabetic()).count() < 2 {
+            return None;
+        }
+        Some(ExtractedItem {
+            name: name.to_string(),
+            amount: Money::parse(&caps[1])?,
+            confidence: line.confidence * 0.8,
+            quantity: None,
+        })
+    }
+
+    /// Payment method from the tender lines, or from a card or cash label
+    /// below the total when no tender amounts were printed
+    fn extract_payment_method(
+        &self,
+        lines: &[OcrLine],
+        receipt: &ExtractedReceipt,
+        total_index: Option<usize>,
+    ) -> Option<ExtractedField<TenderMethod>> {
+        if let Some(tender) = receipt.tenders.iter().max_by_key(|tender| tender.amount) {
+            return Some(ExtractedField::new(tender.method, tender.confidence, "tender_line"));
+        }
+        lines
+            .iter()
+            .skip(total_index.unwrap_or(0))
+            .find_map(|line| {
+                let found = self.payment_method_pattern.find(&line.text)?;
+                Some(ExtractedField {
+                    bbox: line.span_bbox(found.start(), found.end()),
+                    ..ExtractedField::new(
+                        TenderMethod::from_label(found.as_str())?,
+                        line.confidence * 0.8,
+                        "keyword_payment",
+                    )
+                })
+            })
+    }
+
+    /// Card network and last four digits from the payment lines below the
+    /// total, or cash when that is how the receipt was paid
+    fn extract_payment_details(
+        &self,
+        lines: &[OcrLine],
+        receipt: &ExtractedReceipt,
+        total_index: Option<usize>,
+    ) -> Option<ExtractedField<String>> {
+        let payment_lines = || lines.iter().skip(total_index.unwrap_or(0));
+        let network = payment_lines().find_map(|line| {
+            let found = self.card_network_pattern.find(&line.text)?;
+            Some((card_network_name(found.as_str()), line))
+        });
+        let number = payment_lines().find_map(|line| {
+            let caps = self.card_number_pattern.captures(&line.text)?;
+            Some((caps.get(1)?.as_str(), line))
+        });
+
+        let (value, confidence, line) = match (network, number) {
+            (Some((network, line)), Some((digits, number_line))) => {
+                let confidence = if std::ptr::eq(line, number_line) { 0.9 } else { 0.8 };
+                (format!("{} ****{}", network, digits), confidence, line)
+            }
+            (Some((network, line)), None) => (network.to_string(), 0.7, line),
+            (None, Some((digits, line))) => (format!("****{}", digits), 0.75, line),
+            (None, None) => {
+                let cash = receipt.payment_method.as_ref().filter(|m| m.value == TenderMethod::Cash)?;
+                return Some(ExtractedField {
+                    bbox: cash.bbox,
+                    ..ExtractedField::new("Cash".to_string(), cash.confidence, &cash.source)
+                });
+            }
+        };
+        Some(ExtractedField::new(value, line.confidence * confidence, "keyword_card"))
+    }
+}
+
+/// Merge runs of identical item lines, as supermarkets print one line per
+/// unit scanned, into a single item with a quantity
+fn collapse_repeated_items(items: Vec<ExtractedItem>) -> Vec<ExtractedItem> {
+    let mut collapsed: Vec<ExtractedItem> = Vec::with_capacity(items.len());
+    let mut unit_amount = Money::ZERO;
+    for item in items {
+        if let Some(last) = collapsed.last_mut() {
+            if last.name.eq_ignore_ascii_case(&item.name) && unit_amount == item.amount {
+                let quantity = last.quantity.unwrap_or(1) + 1;
+                last.quantity = Some(quantity);
+                last.amount = unit_amount.times(quantity as f64);
+                last.confidence = last.confidence.min(item.confidence);
+                continue;
+            }
+        }
+        unit_amount = item.amount;
+        collapsed.push(item);
+    }
+    collapsed
+}
+
+/// Display name of a card network as printed on a receipt
+fn card_network_name(label: &str) -> &'static str {
+    let label = label.to_lowercase();
+    match label.split_whitespace().next().unwrap_or("") {
+        "visa" => "Visa",
+        "american" | "amex" => "Amex",
+        "eftpos" => "EFTPOS",
+        "debit" => "Debit",
+        "diners" => "Diners Club",
+        "jcb" => "JCB",
+        "union" | "unionpay" => "UnionPay",
+        _ => "Mastercard",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_supermarket_receipt_text() {
+        let text = "TAX INVOICE\n\
+            Coles Supermarkets\n\
+            ABN 45 004 189 708\n\
+            14/03/2024 10:42\n\
+            Milk 2L 3.10\n\
+            Sourdough Loaf 6.50\n\
+            2 x Bananas 1.80\n\
+            SUBTOTAL 11.40\n\
+            TOTAL $11.40\n\
+            Total includes GST $0.59\n\
+            VISA **** 1234 APPROVED";
+        let receipt = ReceiptParser::new().unwrap().parse_from_text(text, 1.0);
+
+        assert_eq!(receipt.vendor.value, "Coles Supermarkets");
+        assert_eq!(receipt.date.value, "2024-03-14");
+        assert_eq!(receipt.total_amount.value.to_dollars(), 11.40);
+        assert_eq!(receipt.subtotal.as_ref().unwrap().value.to_dollars(), 11.40);
+        assert_eq!(receipt.gst.as_ref().unwrap().value.to_dollars(), 0.59);
+        assert_eq!(receipt.payment_method.as_ref().unwrap().value, TenderMethod::Card);
+        assert_eq!(receipt.payment_details.as_ref().unwrap().value, "Visa ****1234");
+        let items: Vec<(&str, f64)> = receipt.items.iter().map(|i| (i.name.as_str(), i.amount.to_dollars())).collect();
+        assert_eq!(items, vec![("Milk 2L", 3.10), ("Sourdough Loaf", 6.50), ("2 x Bananas", 1.80)]);
+    }
+
+    #[test]
+    fn test_repeated_item_lines_are_collapsed() {
+        let text = "Woolworths\n\
+            Bananas 0.85\n\
+            Bananas 0.85\n\
+            BANANAS 0.85\n\
+            Milk 2L 3.10\n\
+            Bananas 0.85\n\
+            TOTAL $6.50";
+        let receipt = ReceiptParser::shared().parse_from_text(text, 1.0);
+
+        let items: Vec<(&str, f64, Option<u32>)> =
+            receipt.items.iter().map(|i| (i.name.as_str(), i.amount.to_dollars(), i.quantity)).collect();
+        assert_eq!(
+            items,
+            vec![("Bananas", 2.55, Some(3)), ("Milk 2L", 3.10, None), ("Bananas", 0.85, None)]
+        );
+        assert!(receipt::item_total_discrepancy(&receipt).is_none());
+    }
+
+    #[test]
+    fn test_items_containing_summary_words_are_kept() {
+        let text = "Harris Farm\nCashews 5.00\nCardigan 39.99\nTaxi fare 22.00\nTotally Nuts 4.00\nTOTAL $70.99";
+        let receipt = ReceiptParser::shared().parse_from_text(text, 1.0);
+
+        let items: Vec<&str> = receipt.items.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(items, vec!["Cashews", "Cardigan", "Taxi fare", "Totally Nuts"]);
+        assert!(receipt::item_total_discrepancy(&receipt).is_none());
+    }
+
+    #[test]
+    fn test_inclusive_total_is_not_read_as_gst() {
+        let text = "Bunnings\nHammer 22.00\nTOTAL INC GST 22.00\nGST 2.00\nCASH 50.00\nCHANGE 28.00";
+        let receipt = ReceiptParser::shared().parse_from_text(text, 1.0);
+
+        assert_eq!(receipt.total_amount.value.to_dollars(), 22.00);
+        assert_eq!(receipt.gst.as_ref().unwrap().value.to_dollars(), 2.00);
+        assert_eq!(receipt.payment_method.as_ref().unwrap().value, TenderMethod::Cash);
+        assert_eq!(receipt.payment_details.as_ref().unwrap().value, "Cash");
+        assert!(receipt.subtotal.is_none());
+    }
+}
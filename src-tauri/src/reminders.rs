@@ -0,0 +1,281 @@
+//! Invoice Reminder Module
+//!
+//! Escalating reminders for unpaid invoices. A schedule lists steps relative
+//! to the due date (by default a week before, on the day, then weekly once
+//! overdue); a vendor can have its own schedule in place of the global one.
+//! Each step that falls due raises one `REMINDER_EVENT` notification, and
+//! every unpaid invoice with a step behind it is listed as needing attention,
+//! most overdue first.
+
+use chrono::{Days, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+use crate::documents::{self, DocumentFilter, DocumentKind, StoredDocument};
+use crate::money::Money;
+use crate::periods;
+use crate::settings::{self, AppSettings};
+use crate::storage;
+
+/// Event emitted for each reminder step that falls due
+pub const REMINDER_EVENT: &str = "reminder://invoice";
+
+static LOG_LOCK: Mutex<()> = Mutex::new(());
+
+/// One point in an escalation schedule
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EscalationStep {
+    /// Days after the due date; negative for reminders before it
+    pub days_from_due: i64,
+    /// Repeat this often afterwards until the invoice is paid
+    #[serde(default)]
+    pub repeat_every_days: Option<u64>,
+}
+
+/// When to remind about an unpaid invoice
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EscalationSchedule {
+    pub steps: Vec<EscalationStep>,
+}
+
+impl Default for EscalationSchedule {
+    fn default() -> Self {
+        let step = |days_from_due, repeat_every_days| EscalationStep {
+            days_from_due,
+            repeat_every_days,
+        };
+        Self {
+            steps: vec![step(-7, None), step(0, None), step(7, Some(7))],
+        }
+    }
+}
+
+impl EscalationSchedule {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.steps.is_empty() {
+            return Err("A reminder schedule needs at least one step".to_string());
+        }
+        if self.steps.iter().any(|s| s.repeat_every_days == Some(0)) {
+            return Err("Reminders cannot repeat every 0 days".to_string());
+        }
+        Ok(())
+    }
+
+    /// Date of the most recent step on or before `today`
+    fn last_step(&self, due: NaiveDate, today: NaiveDate) -> Option<NaiveDate> {
+        self.steps
+            .iter()
+            .filter_map(|step| {
+                let first = offset(due, step.days_from_due)?;
+                if first > today {
+                    return None;
+                }
+                let elapsed = (today - first).num_days() as u64;
+                match step.repeat_every_days {
+                    Some(every) => first.checked_add_days(Days::new(elapsed / every * every)),
+                    None => Some(first),
+                }
+            })
+            .max()
+    }
+}
+
+fn offset(date: NaiveDate, days: i64) -> Option<NaiveDate> {
+    if days < 0 {
+        date.checked_sub_days(Days::new(days.unsigned_abs()))
+    } else {
+        date.checked_add_days(Days::new(days as u64))
+    }
+}
+
+/// Invoice reminder settings
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ReminderSettings {
+    pub enabled: bool,
+    pub schedule: EscalationSchedule,
+    /// Schedules used in place of `schedule` for particular vendors, keyed
+    /// by vendor name (matched case-insensitively)
+    pub vendor_schedules: BTreeMap<String, EscalationSchedule>,
+}
+
+impl Default for ReminderSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            schedule: EscalationSchedule::default(),
+            vendor_schedules: BTreeMap::new(),
+        }
+    }
+}
+
+impl ReminderSettings {
+    fn schedule_for(&self, vendor: Option<&str>) -> &EscalationSchedule {
+        vendor
+            .and_then(|vendor| {
+                self.vendor_schedules
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(vendor.trim()))
+            })
+            .map_or(&self.schedule, |(_, schedule)| schedule)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReminderLevel {
+    Upcoming,
+    DueToday,
+    Overdue,
+}
+
+/// An unpaid invoice with a reminder step behind it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct InvoiceReminder {
+    pub document_id: String,
+    pub vendor: Option<String>,
+    pub amount: Option<Money>,
+    /// YYYY-MM-DD
+    pub due_date: String,
+    /// Negative once overdue
+    pub days_until_due: i64,
+    pub level: ReminderLevel,
+    /// Date of the step that raised the reminder (YYYY-MM-DD)
+    pub step_date: String,
+}
+
+fn reminder(doc: &StoredDocument, settings: &ReminderSettings, today: NaiveDate) -> Option<InvoiceReminder> {
+    if doc.kind != DocumentKind::Invoice || doc.paid {
+        return None;
+    }
+    let raw = &doc.invoice.as_ref()?.due_date.as_ref()?.value;
+    let due = periods::parse_date(&documents::normalize_date(raw)?)?;
+    let step = settings.schedule_for(doc.vendor.as_deref()).last_step(due, today)?;
+    let days_until_due = (due - today).num_days();
+    Some(InvoiceReminder {
+        document_id: doc.id.clone(),
+        vendor: doc.vendor.clone(),
+        amount: doc.total,
+        due_date: due.format("%Y-%m-%d").to_string(),
+        days_until_due,
+        level: match days_until_due {
+            d if d > 0 => ReminderLevel::Upcoming,
+            0 => ReminderLevel::DueToday,
+            _ => ReminderLevel::Overdue,
+        },
+        step_date: step.format("%Y-%m-%d").to_string(),
+    })
+}
+
+/// Unpaid invoices with a reminder step on or before `today`, most overdue first
+pub fn needing_attention(
+    documents: &[StoredDocument],
+    settings: &ReminderSettings,
+    today: NaiveDate,
+) -> Vec<InvoiceReminder> {
+    let mut reminders: Vec<InvoiceReminder> =
+        documents.iter().filter_map(|doc| reminder(doc, settings, today)).collect();
+    reminders.sort_by(|a, b| a.days_until_due.cmp(&b.days_until_due).then_with(|| a.document_id.cmp(&b.document_id)));
+    reminders
+}
+
+/// Reminders whose step is newer than the last one notified for the
+/// invoice, recording them as notified
+fn take_unnotified(reminders: Vec<InvoiceReminder>, notified: &mut BTreeMap<String, String>) -> Vec<InvoiceReminder> {
+    reminders
+        .into_iter()
+        .filter(|r| {
+            let is_new = notified.get(&r.document_id).map_or(true, |last| *last < r.step_date);
+            if is_new {
+                notified.insert(r.document_id.clone(), r.step_date.clone());
+            }
+            is_new
+        })
+        .collect()
+}
+
+/// Notify each reminder step that has fallen due since the last run,
+/// returning how many were sent
+pub fn notify_due(app: &AppHandle, today: NaiveDate) -> Result<usize, String> {
+    let settings: AppSettings = settings::load_settings()?;
+    if !settings.invoice_reminders.enabled {
+        return Ok(0);
+    }
+    let documents = documents::with_store(|store| Ok(store.list(&DocumentFilter::default())))?;
+    let reminders = needing_attention(&documents, &settings.invoice_reminders, today);
+
+    let _guard = LOG_LOCK.lock().map_err(|_| "Reminder log lock poisoned".to_string())?;
+    let path = storage::get_data_directory()?.join("reminder_log.json");
+    let mut notified: BTreeMap<String, String> = storage::load_json(&path)?;
+    let due = take_unnotified(reminders, &mut notified);
+    for reminder in &due {
+        if let Err(e) = app.emit(REMINDER_EVENT, reminder) {
+            log::warn!("Failed to emit reminder for {}: {}", reminder.document_id, e);
+        }
+    }
+    storage::save_json(&path, &notified)?;
+    Ok(due.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invoice::{ExtractedField, ExtractedInvoice};
+
+    fn invoice(id: &str, vendor: &str, due: &str, paid: bool) -> StoredDocument {
+        StoredDocument {
+            id: id.to_string(),
+            kind: DocumentKind::Invoice,
+            vendor: Some(vendor.to_string()),
+            paid,
+            invoice: Some(ExtractedInvoice {
+                due_date: Some(ExtractedField::new(due.to_string(), 0.8, "test")),
+                ..ExtractedInvoice::default()
+            }),
+            ..StoredDocument::default()
+        }
+    }
+
+    #[test]
+    fn test_reminders_escalate_and_notify_once_per_step() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 20).unwrap();
+        let docs = vec![
+            invoice("soon", "Telstra", "2024-03-25", false),
+            invoice("far", "Telstra", "2024-04-30", false),
+            invoice("today", "Origin", "2024-03-20", false),
+            invoice("late", "AGL", "2024-03-01", false),
+            invoice("paid", "AGL", "2024-03-01", true),
+            invoice("strict", "Landlord Pty Ltd", "2024-03-22", false),
+        ];
+        let mut settings = ReminderSettings::default();
+        let strict = EscalationSchedule {
+            steps: vec![EscalationStep {
+                days_from_due: 1,
+                repeat_every_days: Some(1),
+            }],
+        };
+        settings.vendor_schedules.insert("landlord pty ltd".to_string(), strict);
+
+        let reminders = needing_attention(&docs, &settings, today);
+        let summary: Vec<(&str, ReminderLevel, &str)> =
+            reminders.iter().map(|r| (r.document_id.as_str(), r.level, r.step_date.as_str())).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("late", ReminderLevel::Overdue, "2024-03-15"),
+                ("today", ReminderLevel::DueToday, "2024-03-20"),
+                ("soon", ReminderLevel::Upcoming, "2024-03-18"),
+            ]
+        );
+
+        let mut notified = BTreeMap::new();
+        assert_eq!(take_unnotified(reminders.clone(), &mut notified).len(), 3);
+        assert!(take_unnotified(reminders, &mut notified).is_empty());
+        // The weekly overdue reminder fires again a week later
+        let next_week = needing_attention(&docs, &settings, today + Days::new(7));
+        let sent: Vec<String> = take_unnotified(next_week, &mut notified).into_iter().map(|r| r.document_id).collect();
+        assert_eq!(sent, vec!["late", "today", "strict", "soon"]);
+    }
+}
@@ -0,0 +1,383 @@
+//! Report Builder Module
+//!
+//! Minimal PDF writer for backend-generated documents such as issued
+//! invoices. Lays out headings, paragraphs and simple tables on A4 pages in
+//! the standard Helvetica fonts. Text Helvetica can't show, such as Chinese
+//! or Arabic vendor names, is drawn in a fallback font whose used glyphs are
+//! embedded, and right-to-left text is shaped and reordered for display.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::report_fonts::{self, TrueTypeFont};
+use crate::text_shaping;
+
+/// A4 page size in points
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 50.0;
+
+/// Column alignment within a table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// A table column: header, width in points and alignment
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub header: String,
+    pub width: f32,
+    pub align: Align,
+}
+
+impl Column {
+    pub fn new(header: &str, width: f32, align: Align) -> Self {
+        Self {
+            header: header.to_string(),
+            width,
+            align,
+        }
+    }
+}
+
+/// A fallback font and the glyphs drawn with it, mapped to the text each
+/// stands for
+struct EmbeddedFont {
+    font: Arc<TrueTypeFont>,
+    glyphs: BTreeMap<u16, String>,
+}
+
+/// Part of a line drawn in one font
+enum FontRun {
+    Standard(String),
+    Embedded(usize, Vec<u16>),
+}
+
+/// Builds a PDF document top to bottom, starting new pages as needed
+pub struct ReportBuilder {
+    title: String,
+    pages: Vec<String>,
+    current: String,
+    y: f32,
+    embedded: Vec<EmbeddedFont>,
+}
+
+impl ReportBuilder {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            pages: Vec::new(),
+            current: String::new(),
+            y: PAGE_HEIGHT - MARGIN,
+            embedded: Vec::new(),
+        }
+    }
+
+    pub fn heading(&mut self, text: &str) -> &mut Self {
+        self.space(6.0);
+        self.line(text, 16.0, true, MARGIN, Align::Left)
+    }
+
+    pub fn subheading(&mut self, text: &str) -> &mut Self {
+        self.space(4.0);
+        self.line(text, 12.0, true, MARGIN, Align::Left)
+    }
+
+    pub fn text(&mut self, text: &str) -> &mut Self {
+        self.line(text, 10.0, false, MARGIN, Align::Left)
+    }
+
+    /// Text aligned to the right margin
+    pub fn text_right(&mut self, text: &str, bold: bool) -> &mut Self {
+        self.line(text, 10.0, bold, PAGE_WIDTH - MARGIN, Align::Right)
+    }
+
+    /// Vertical gap in points
+    pub fn space(&mut self, points: f32) -> &mut Self {
+        self.y -= points;
+        self
+    }
+
+    pub fn table(&mut self, columns: &[Column], rows: &[Vec<String>]) -> &mut Self {
+        let header: Vec<String> = columns.iter().map(|c| c.header.clone()).collect();
+        self.table_row(columns, &header, true);
+        for row in rows {
+            self.table_row(columns, row, false);
+        }
+        self
+    }
+
+    fn table_row(&mut self, columns: &[Column], cells: &[String], bold: bool) {
+        self.ensure_room(14.0);
+        let mut x = MARGIN;
+        for (column, cell) in columns.iter().zip(cells) {
+            let anchor = match column.align {
+                Align::Left => x,
+                Align::Right => x + column.width - 4.0,
+            };
+            self.draw(cell, 10.0, bold, anchor, column.align);
+            x += column.width;
+        }
+        self.y -= 14.0;
+    }
+
+    fn line(&mut self, text: &str, size: f32, bold: bool, x: f32, align: Align) -> &mut Self {
+        let height = size * 1.4;
+        self.ensure_room(height);
+        self.draw(text, size, bold, x, align);
+        self.y -= height;
+        self
+    }
+
+    fn draw(&mut self, text: &str, size: f32, bold: bool, x: f32, align: Align) {
+        let runs = self.font_runs(&text_shaping::visual_order(text));
+        let width: f32 = runs
+            .iter()
+            .map(|run| match run {
+                FontRun::Standard(text) => text_width(text, size),
+                FontRun::Embedded(font, glyphs) => {
+                    let font = &self.embedded[*font].font;
+                    glyphs.iter().map(|&g| font.advance(g)).sum::<f32>() * size / 1000.0
+                }
+            })
+            .sum();
+        let x = match align {
+            Align::Left => x,
+            Align::Right => x - width,
+        };
+
+        let standard = if bold { "F2" } else { "F1" };
+        let mut operators = format!("BT {:.2} {:.2} Td", x, self.y - size);
+        for run in runs {
+            let shown = match run {
+                FontRun::Standard(text) => format!(" /{} {} Tf ({}) Tj", standard, size, escape(&text)),
+                FontRun::Embedded(font, glyphs) => {
+                    let hex: String = glyphs.iter().map(|g| format!("{:04X}", g)).collect();
+                    format!(" /F{} {} Tf <{}> Tj", font + 3, size, hex)
+                }
+            };
+            operators.push_str(&shown);
+        }
+        operators.push_str(" ET\n");
+        self.current.push_str(&operators);
+    }
+
+    /// Split text in display order into runs of Helvetica and fallback
+    /// fonts. Spaces and punctuation stay in the fallback font they follow.
+    fn font_runs(&mut self, text: &str) -> Vec<FontRun> {
+        let mut runs: Vec<FontRun> = Vec::new();
+        for c in text.chars() {
+            if let Some(FontRun::Embedded(index, glyphs)) = runs.last_mut() {
+                let embedded = &mut self.embedded[*index];
+                if let Some(glyph) = embedded.font.glyph(c).filter(|_| !c.is_alphanumeric()) {
+                    glyphs.push(glyph);
+                    embedded.glyphs.entry(glyph).or_insert_with(|| c.to_string());
+                    continue;
+                }
+            }
+
+            let fallback = (!is_standard(c)).then(|| report_fonts::fallback_for(c)).flatten();
+            match fallback {
+                Some(font) => {
+                    let index = match self.embedded.iter().position(|e| Arc::ptr_eq(&e.font, &font)) {
+                        Some(index) => index,
+                        None => {
+                            self.embedded.push(EmbeddedFont {
+                                font: font.clone(),
+                                glyphs: BTreeMap::new(),
+                            });
+                            self.embedded.len() - 1
+                        }
+                    };
+                    let glyph = font.glyph(c).unwrap_or_default();
+                    self.embedded[index].glyphs.entry(glyph).or_insert_with(|| text_shaping::unshape(c));
+                    match runs.last_mut() {
+                        Some(FontRun::Embedded(last, glyphs)) if *last == index => glyphs.push(glyph),
+                        _ => runs.push(FontRun::Embedded(index, vec![glyph])),
+                    }
+                }
+                None => {
+                    let c = if is_standard(c) { c } else { '?' };
+                    match runs.last_mut() {
+                        Some(FontRun::Standard(text)) => text.push(c),
+                        _ => runs.push(FontRun::Standard(c.to_string())),
+                    }
+                }
+            }
+        }
+        runs
+    }
+
+    fn ensure_room(&mut self, height: f32) {
+        if self.y - height < MARGIN {
+            self.pages.push(std::mem::take(&mut self.current));
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+    }
+
+    /// Serialize the document to PDF bytes
+    pub fn build(mut self) -> Vec<u8> {
+        self.pages.push(std::mem::take(&mut self.current));
+        let page_count = self.pages.len();
+
+        // Objects: 1 catalog, 2 page tree, 3-4 fonts, 5 info, then a page
+        // and content stream per page, then five per embedded font
+        let first_embedded = 6 + page_count * 2;
+        let font_resources: String = (0..self.embedded.len())
+            .map(|i| format!(" /F{} {} 0 R", i + 3, first_embedded + i * 5))
+            .collect();
+        let mut objects: Vec<String> = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                (0..page_count)
+                    .map(|i| format!("{} 0 R", 6 + i * 2))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                page_count
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_string(),
+            format!("<< /Title {} /Producer (Tally) >>", text_string(&self.title)),
+        ];
+        for (i, content) in self.pages.iter().enumerate() {
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /Font << /F1 3 0 R /F2 4 0 R{} >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH,
+                PAGE_HEIGHT,
+                font_resources,
+                7 + i * 2
+            ));
+            objects.push(format!(
+                "<< /Length {} >>\nstream\n{}endstream",
+                content.len(),
+                content
+            ));
+        }
+
+        let mut objects: Vec<Vec<u8>> = objects.into_iter().map(String::into_bytes).collect();
+        for (i, embedded) in self.embedded.iter().enumerate() {
+            objects.extend(report_fonts::embed_objects(&embedded.font, &embedded.glyphs, first_embedded + i * 5));
+        }
+
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+            pdf.extend_from_slice(object);
+            pdf.extend_from_slice(b"\nendobj\n");
+        }
+
+        let xref_offset = pdf.len();
+        let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            trailer.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        trailer.push_str(&format!(
+            "trailer\n<< /Size {} /Root 1 0 R /Info 5 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref_offset
+        ));
+        pdf.extend_from_slice(trailer.as_bytes());
+        pdf
+    }
+}
+
+/// Approximate Helvetica text width, accurate for the figures used in
+/// right-aligned amount columns
+fn text_width(text: &str, size: f32) -> f32 {
+    text.chars()
+        .map(|c| match c {
+            '0'..='9' | '$' => 0.556,
+            '.' | ',' | ' ' => 0.278,
+            '-' => 0.333,
+            c if c.is_uppercase() => 0.667,
+            _ => 0.5,
+        })
+        .sum::<f32>()
+        * size
+}
+
+/// Whether Helvetica's WinAnsi encoding has the character. Latin-1
+/// letters such as `é` share their codes with WinAnsi.
+fn is_standard(c: char) -> bool {
+    matches!(c, ' '..='~' | '\u{00A0}'..='\u{00FF}')
+}
+
+/// Escape text for a PDF string literal in the standard fonts' encoding,
+/// replacing characters outside it
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_ascii() && !c.is_ascii_control() => escaped.push(c),
+            c if is_standard(c) => escaped.push_str(&format!("\\{:03o}", c as u32)),
+            _ => escaped.push('?'),
+        }
+    }
+    escaped
+}
+
+/// A PDF text string for document metadata, in UTF-16 when it isn't ASCII
+fn text_string(text: &str) -> String {
+    if text.is_ascii() {
+        return format!("({})", escape(text));
+    }
+    let units: String = text.encode_utf16().map(|u| format!("{:04X}", u)).collect();
+    format!("<FEFF{}>", units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builds_valid_pdf_structure() {
+        let mut builder = ReportBuilder::new("Invoice (draft)");
+        builder.heading("Tax Invoice").text("Line one");
+        let rows: Vec<Vec<String>> = (0..80)
+            .map(|i| vec![format!("Item {}", i), format!("${:.2}", i as f64)])
+            .collect();
+        builder.table(
+            &[Column::new("Item", 300.0, Align::Left), Column::new("Amount", 100.0, Align::Right)],
+            &rows,
+        );
+        let pdf = String::from_utf8(builder.build()).unwrap();
+
+        assert!(pdf.starts_with("%PDF-1.4"));
+        assert!(pdf.ends_with("%%EOF\n"));
+        assert!(pdf.contains("/Count 2"));
+        assert!(pdf.contains("(Invoice \\(draft\\))"));
+
+        // The xref offset points at the xref table
+        let startxref: usize = pdf.lines().rev().nth(1).unwrap().parse().unwrap();
+        assert!(pdf[startxref..].starts_with("xref"));
+    }
+
+    #[test]
+    fn test_unicode_vendor_names_are_embedded_and_extractable() {
+        // Needs a system font with Arabic, such as DejaVu Sans or Noto
+        if report_fonts::fallback_for('م').is_none() {
+            return;
+        }
+        let mut builder = ReportBuilder::new("Café receipts");
+        builder.text("Café de Paris").text("مطعم 42.50");
+        let pdf = builder.build();
+
+        let doc = lopdf::Document::load_mem(&pdf).unwrap();
+        let text = crate::pdf_text::document_text(&doc).unwrap();
+        assert!(text.contains("Café de Paris"), "{}", text);
+        // Drawn right to left, so the letters come out in display order
+        let display: String = "مطعم".chars().rev().collect();
+        assert!(text.contains(&format!("42.50 {}", display)), "{}", text);
+        assert!(String::from_utf8_lossy(&pdf).contains("/FontFile2"));
+    }
+}
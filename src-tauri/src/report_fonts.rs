@@ -0,0 +1,594 @@
+//! Report Fonts Module
+//!
+//! Fonts for text the standard PDF fonts can't show, such as Chinese or
+//! Arabic vendor names. A TrueType font covering the character is found
+//! among the fonts in the app's `Fonts` folder and the Noto and other
+//! Unicode fonts the operating system ships, and the glyphs a report uses
+//! are embedded in it. Unused glyphs are emptied so a report using a few
+//! characters of a large CJK font stays small.
+//!
+//! Only fonts with TrueType outlines can be embedded; CFF-based OpenType
+//! fonts are skipped.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use crate::storage;
+
+/// System fonts tried in order, after any in the app's `Fonts` folder.
+/// Noto fonts come first, then the Unicode fonts each platform ships.
+const FALLBACK_FONTS: &[&str] = &[
+    "NotoSans-Regular.ttf",
+    "NotoSansArabic-Regular.ttf",
+    "NotoNaskhArabic-Regular.ttf",
+    "NotoSansHebrew-Regular.ttf",
+    "NotoSansThai-Regular.ttf",
+    "NotoSansDevanagari-Regular.ttf",
+    "NotoSansSC-Regular.ttf",
+    "NotoSansTC-Regular.ttf",
+    "NotoSansJP-Regular.ttf",
+    "NotoSansKR-Regular.ttf",
+    "DroidSansFallbackFull.ttf",
+    "DroidSansFallback.ttf",
+    "Arial Unicode.ttf",
+    "msyh.ttc",
+    "simsun.ttc",
+    "malgun.ttf",
+    "meiryo.ttc",
+    "arial.ttf",
+    "tahoma.ttf",
+    "wqy-zenhei.ttc",
+    "wqy-microhei.ttc",
+    "DejaVuSans.ttf",
+];
+
+/// Folders are searched this deep for fonts
+const MAX_SEARCH_DEPTH: usize = 4;
+
+/// Tables kept in an embedded subset; the rest aren't needed to draw glyphs
+const SUBSET_TABLES: &[&[u8; 4]] = &[b"cvt ", b"fpgm", b"glyf", b"head", b"hhea", b"hmtx", b"loca", b"maxp", b"prep"];
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    data.get(pos..pos + 2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn read_i16(data: &[u8], pos: usize) -> Option<i16> {
+    read_u16(data, pos).map(|v| v as i16)
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    data.get(pos..pos + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// A TrueType font, or one face of a TrueType collection
+pub struct TrueTypeFont {
+    data: Vec<u8>,
+    /// Offset and length of each table in `data`
+    tables: BTreeMap<[u8; 4], (usize, usize)>,
+    /// PostScript name
+    pub name: String,
+    units_per_em: f32,
+    cmap: HashMap<char, u16>,
+    advances: Vec<u16>,
+    ascent: i16,
+    descent: i16,
+    bbox: [i16; 4],
+    long_loca: bool,
+}
+
+impl TrueTypeFont {
+    /// Load the first face of a font file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = fs::read(path).map_err(|e| format!("Failed to read font {}: {}", path.display(), e))?;
+        let fallback_name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("Font").to_string();
+        Self::parse(data, &fallback_name).ok_or_else(|| format!("Unsupported font: {}", path.display()))
+    }
+
+    fn parse(data: Vec<u8>, fallback_name: &str) -> Option<Self> {
+        let offset = if data.get(0..4)? == b"ttcf" { read_u32(&data, 12)? as usize } else { 0 };
+        // TrueType outlines only; "OTTO" fonts use CFF
+        if !matches!(read_u32(&data, offset)?, 0x0001_0000 | 0x7472_7565) {
+            return None;
+        }
+        let mut tables = BTreeMap::new();
+        for i in 0..read_u16(&data, offset + 4)? as usize {
+            let record = offset + 12 + i * 16;
+            let tag: [u8; 4] = data.get(record..record + 4)?.try_into().ok()?;
+            let start = read_u32(&data, record + 8)? as usize;
+            let length = read_u32(&data, record + 12)? as usize;
+            data.get(start..start.checked_add(length)?)?;
+            tables.insert(tag, (start, length));
+        }
+        let table = |tag: &[u8; 4]| tables.get(tag).map(|&(start, _)| start);
+        let (head, hhea, hmtx, maxp) = (table(b"head")?, table(b"hhea")?, table(b"hmtx")?, table(b"maxp")?);
+        table(b"glyf")?;
+        table(b"loca")?;
+
+        let glyph_count = read_u16(&data, maxp + 4)? as usize;
+        let metric_count = (read_u16(&data, hhea + 34)? as usize).clamp(1, glyph_count.max(1));
+        let mut advances: Vec<u16> = (0..metric_count)
+            .map(|i| read_u16(&data, hmtx + i * 4))
+            .collect::<Option<_>>()?;
+        let last = *advances.last()?;
+        advances.resize(glyph_count.max(metric_count), last);
+
+        let cmap = table(b"cmap").and_then(|cmap| parse_cmap(&data, cmap)).unwrap_or_default();
+        let name = table(b"name")
+            .and_then(|name| postscript_name(&data, name))
+            .unwrap_or_else(|| fallback_name.to_string())
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .collect();
+
+        Some(Self {
+            name,
+            units_per_em: f32::from(read_u16(&data, head + 18)?.max(16)),
+            ascent: read_i16(&data, hhea + 4)?,
+            descent: read_i16(&data, hhea + 6)?,
+            bbox: [
+                read_i16(&data, head + 36)?,
+                read_i16(&data, head + 38)?,
+                read_i16(&data, head + 40)?,
+                read_i16(&data, head + 42)?,
+            ],
+            long_loca: read_i16(&data, head + 50)? == 1,
+            cmap,
+            advances,
+            tables,
+            data,
+        })
+    }
+
+    /// Glyph for a character, if the font has one
+    pub fn glyph(&self, c: char) -> Option<u16> {
+        self.cmap.get(&c).copied().filter(|&g| g != 0)
+    }
+
+    /// Advance width of a glyph in thousandths of the font size
+    pub fn advance(&self, glyph: u16) -> f32 {
+        let units = self.advances.get(glyph as usize).or(self.advances.last()).copied().unwrap_or(0);
+        f32::from(units) * 1000.0 / self.units_per_em
+    }
+
+    fn scale(&self, units: i16) -> i32 {
+        (f32::from(units) * 1000.0 / self.units_per_em).round() as i32
+    }
+
+    fn table(&self, tag: &[u8; 4]) -> &[u8] {
+        self.tables
+            .get(tag)
+            .map_or(&[][..], |&(start, length)| &self.data[start..start + length])
+    }
+
+    fn glyph_data(&self, glyph: u16) -> &[u8] {
+        let loca = self.table(b"loca");
+        let glyf = self.table(b"glyf");
+        let entry = |i: usize| {
+            if self.long_loca {
+                read_u32(loca, i * 4).map(|v| v as usize)
+            } else {
+                read_u16(loca, i * 2).map(|v| v as usize * 2)
+            }
+        };
+        match (entry(glyph as usize), entry(glyph as usize + 1)) {
+            (Some(start), Some(end)) if start < end => glyf.get(start..end).unwrap_or_default(),
+            _ => &[],
+        }
+    }
+
+    /// Glyphs a composite glyph is built from
+    fn components(&self, glyph: u16) -> Vec<u16> {
+        const ARGS_ARE_WORDS: u16 = 0x0001;
+        const HAS_SCALE: u16 = 0x0008;
+        const MORE_COMPONENTS: u16 = 0x0020;
+        const HAS_XY_SCALE: u16 = 0x0040;
+        const HAS_TWO_BY_TWO: u16 = 0x0080;
+
+        let data = self.glyph_data(glyph);
+        let mut components = Vec::new();
+        if read_i16(data, 0).map_or(true, |contours| contours >= 0) {
+            return components;
+        }
+        let mut pos = 10;
+        while let (Some(flags), Some(component)) = (read_u16(data, pos), read_u16(data, pos + 2)) {
+            components.push(component);
+            pos += 4 + if flags & ARGS_ARE_WORDS != 0 { 4 } else { 2 };
+            pos += if flags & HAS_SCALE != 0 {
+                2
+            } else if flags & HAS_XY_SCALE != 0 {
+                4
+            } else if flags & HAS_TWO_BY_TWO != 0 {
+                8
+            } else {
+                0
+            };
+            if flags & MORE_COMPONENTS == 0 {
+                break;
+            }
+        }
+        components
+    }
+
+    /// A copy of the font with every glyph but `used` (and the pieces they
+    /// are built from) emptied. Glyph IDs are unchanged.
+    pub fn subset(&self, used: &BTreeSet<u16>) -> Vec<u8> {
+        let mut keep = BTreeSet::new();
+        let mut pending: Vec<u16> = std::iter::once(0).chain(used.iter().copied()).collect();
+        while let Some(glyph) = pending.pop() {
+            if keep.insert(glyph) {
+                pending.extend(self.components(glyph));
+            }
+        }
+
+        let mut glyf = Vec::new();
+        let mut loca = Vec::with_capacity((self.advances.len() + 1) * 4);
+        for glyph in 0..self.advances.len() {
+            loca.extend_from_slice(&(glyf.len() as u32).to_be_bytes());
+            if keep.contains(&(glyph as u16)) {
+                glyf.extend_from_slice(self.glyph_data(glyph as u16));
+                glyf.resize(glyf.len().next_multiple_of(4), 0);
+            }
+        }
+        loca.extend_from_slice(&(glyf.len() as u32).to_be_bytes());
+
+        let mut head = self.table(b"head").to_vec();
+        if head.len() >= 52 {
+            // Clear the whole-font checksum and switch to 32-bit loca offsets
+            head[8..12].fill(0);
+            head[50..52].copy_from_slice(&1i16.to_be_bytes());
+        }
+
+        let tables: Vec<(&[u8; 4], Vec<u8>)> = SUBSET_TABLES
+            .iter()
+            .filter_map(|&tag| match tag {
+                b"glyf" => Some((tag, std::mem::take(&mut glyf))),
+                b"loca" => Some((tag, std::mem::take(&mut loca))),
+                b"head" => Some((tag, std::mem::take(&mut head))),
+                tag if self.tables.contains_key(tag) => Some((tag, self.table(tag).to_vec())),
+                _ => None,
+            })
+            .collect();
+        write_font(&tables)
+    }
+}
+
+fn checksum(table: &[u8]) -> u32 {
+    table.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+/// Assemble a TrueType file from tables sorted by tag
+fn write_font(tables: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let count = tables.len() as u16;
+    let power = if count == 0 { 0 } else { 15 - count.leading_zeros() as u16 };
+    let search_range = (1u16 << power) * 16;
+
+    let mut font = Vec::new();
+    font.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+    for value in [count, search_range, power, count * 16 - search_range] {
+        font.extend_from_slice(&value.to_be_bytes());
+    }
+
+    let mut offset = 12 + tables.len() * 16;
+    for (tag, table) in tables {
+        font.extend_from_slice(*tag);
+        font.extend_from_slice(&checksum(table).to_be_bytes());
+        font.extend_from_slice(&(offset as u32).to_be_bytes());
+        font.extend_from_slice(&(table.len() as u32).to_be_bytes());
+        offset += table.len().next_multiple_of(4);
+    }
+    for (_, table) in tables {
+        font.extend_from_slice(table);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+    font
+}
+
+/// Character to glyph mapping from the best Unicode subtable
+fn parse_cmap(data: &[u8], cmap: usize) -> Option<HashMap<char, u16>> {
+    let mut subtables = Vec::new();
+    for i in 0..read_u16(data, cmap + 2)? as usize {
+        let record = cmap + 4 + i * 8;
+        let platform = read_u16(data, record)?;
+        let encoding = read_u16(data, record + 2)?;
+        let start = cmap + read_u32(data, record + 4)? as usize;
+        let format = read_u16(data, start)?;
+        let unicode = platform == 0 || (platform == 3 && matches!(encoding, 1 | 10));
+        if unicode && matches!(format, 4 | 12) {
+            subtables.push((format, start));
+        }
+    }
+    // Format 12 covers characters beyond the Basic Multilingual Plane
+    subtables.sort_by_key(|&(format, _)| std::cmp::Reverse(format));
+    let &(format, start) = subtables.first()?;
+
+    let mut map = HashMap::new();
+    if format == 12 {
+        for group in 0..read_u32(data, start + 12)? as usize {
+            let record = start + 16 + group * 12;
+            let first = read_u32(data, record)?;
+            let last = read_u32(data, record + 4)?;
+            let glyph = read_u32(data, record + 8)?;
+            for code in first..=last.min(first.saturating_add(0xFFFF)) {
+                if let Some(c) = char::from_u32(code) {
+                    map.insert(c, (glyph + code - first) as u16);
+                }
+            }
+        }
+        return Some(map);
+    }
+
+    let segments = read_u16(data, start + 6)? as usize / 2;
+    let ends = start + 14;
+    let starts = ends + segments * 2 + 2;
+    let deltas = starts + segments * 2;
+    let range_offsets = deltas + segments * 2;
+    for segment in 0..segments {
+        let end = read_u16(data, ends + segment * 2)?;
+        let first = read_u16(data, starts + segment * 2)?;
+        let delta = read_u16(data, deltas + segment * 2)?;
+        let range_offset_pos = range_offsets + segment * 2;
+        let range_offset = read_u16(data, range_offset_pos)? as usize;
+        for code in first..=end {
+            if code == 0xFFFF {
+                break;
+            }
+            let glyph = if range_offset == 0 {
+                code.wrapping_add(delta)
+            } else {
+                match read_u16(data, range_offset_pos + range_offset + (code - first) as usize * 2) {
+                    Some(0) | None => continue,
+                    Some(glyph) => glyph.wrapping_add(delta),
+                }
+            };
+            if let Some(c) = char::from_u32(u32::from(code)) {
+                map.insert(c, glyph);
+            }
+        }
+    }
+    Some(map)
+}
+
+fn postscript_name(data: &[u8], name: usize) -> Option<String> {
+    let strings = name + read_u16(data, name + 4)? as usize;
+    for i in 0..read_u16(data, name + 2)? as usize {
+        let record = name + 6 + i * 12;
+        if read_u16(data, record + 6)? != 6 {
+            continue;
+        }
+        let platform = read_u16(data, record)?;
+        let length = read_u16(data, record + 8)? as usize;
+        let start = strings + read_u16(data, record + 10)? as usize;
+        let bytes = data.get(start..start + length)?;
+        let text = if platform == 1 {
+            bytes.iter().map(|&b| b as char).collect()
+        } else {
+            let units: Vec<u16> = bytes.chunks_exact(2).map(|b| u16::from_be_bytes([b[0], b[1]])).collect();
+            String::from_utf16_lossy(&units)
+        };
+        if !text.is_empty() {
+            return Some(text);
+        }
+    }
+    None
+}
+
+fn font_directories() -> Vec<PathBuf> {
+    let mut directories = Vec::new();
+    if let Some(windows) = std::env::var_os("WINDIR") {
+        directories.push(PathBuf::from(windows).join("Fonts"));
+    }
+    directories.extend(
+        [
+            "/System/Library/Fonts",
+            "/Library/Fonts",
+            "/system/fonts",
+            "/usr/share/fonts",
+            "/usr/local/share/fonts",
+        ]
+        .iter()
+        .map(PathBuf::from),
+    );
+    if let Some(home) = dirs::home_dir() {
+        directories.extend([home.join("Library/Fonts"), home.join(".local/share/fonts"), home.join(".fonts")]);
+    }
+    directories
+}
+
+fn collect_fonts(dir: &Path, depth: usize, found: &mut HashMap<String, PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if depth < MAX_SEARCH_DEPTH {
+                collect_fonts(&path, depth + 1, found);
+            }
+        } else if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+            found.entry(name.to_lowercase()).or_insert(path);
+        }
+    }
+}
+
+/// Fonts in the app's `Fonts` folder, sorted by name
+fn app_fonts() -> Vec<PathBuf> {
+    let Ok(dir) = storage::get_data_directory().map(|d| d.join("Fonts")) else {
+        return Vec::new();
+    };
+    let mut fonts: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| entries.flatten().map(|e| e.path()).collect())
+        .unwrap_or_default();
+    fonts.retain(|p| {
+        p.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| matches!(e.to_lowercase().as_str(), "ttf" | "ttc"))
+    });
+    fonts.sort();
+    fonts
+}
+
+/// A fallback font, loaded the first time a character needs it
+struct FallbackFont {
+    path: PathBuf,
+    font: OnceLock<Option<Arc<TrueTypeFont>>>,
+}
+
+fn fallback_fonts() -> &'static [FallbackFont] {
+    static FONTS: OnceLock<Vec<FallbackFont>> = OnceLock::new();
+    FONTS.get_or_init(|| {
+        let mut system = HashMap::new();
+        for dir in font_directories() {
+            collect_fonts(&dir, 0, &mut system);
+        }
+        app_fonts()
+            .into_iter()
+            .chain(FALLBACK_FONTS.iter().filter_map(|name| system.remove(&name.to_lowercase())))
+            .map(|path| FallbackFont {
+                path,
+                font: OnceLock::new(),
+            })
+            .collect()
+    })
+}
+
+/// The first fallback font with a glyph for the character
+pub fn fallback_for(c: char) -> Option<Arc<TrueTypeFont>> {
+    fallback_fonts().iter().find_map(|fallback| {
+        let font = fallback.font.get_or_init(|| match TrueTypeFont::load(&fallback.path) {
+            Ok(font) => Some(Arc::new(font)),
+            Err(e) => {
+                log::debug!("Skipping fallback font: {}", e);
+                None
+            }
+        });
+        font.as_ref().filter(|f| f.glyph(c).is_some()).cloned()
+    })
+}
+
+/// PDF objects embedding the glyphs a document used from a font, numbered
+/// from `first_id`: the Type 0 font to reference from page resources, then
+/// its CID font, descriptor, font file and ToUnicode map. `glyphs` maps
+/// each glyph to the text it stands for.
+pub fn embed_objects(font: &TrueTypeFont, glyphs: &BTreeMap<u16, String>, first_id: usize) -> Vec<Vec<u8>> {
+    let [cid_id, descriptor_id, file_id, to_unicode_id] = [first_id + 1, first_id + 2, first_id + 3, first_id + 4];
+    // Subset fonts are named with a six-letter tag unique to the glyph set
+    let hash = glyphs
+        .keys()
+        .fold(5381u32, |hash, &glyph| hash.wrapping_mul(33) ^ u32::from(glyph));
+    let tag: String = (0..6).map(|i| (b'A' + (hash >> (i * 5)) as u8 % 26) as char).collect();
+    let base_font = format!("{}+{}", tag, font.name);
+
+    let widths: Vec<String> = glyphs
+        .keys()
+        .map(|&glyph| format!("{} [{}]", glyph, font.advance(glyph).round()))
+        .collect();
+    let type0 = format!(
+        "<< /Type /Font /Subtype /Type0 /BaseFont /{} /Encoding /Identity-H \
+         /DescendantFonts [{} 0 R] /ToUnicode {} 0 R >>",
+        base_font, cid_id, to_unicode_id
+    );
+    let cid_font = format!(
+        "<< /Type /Font /Subtype /CIDFontType2 /BaseFont /{} \
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> \
+         /FontDescriptor {} 0 R /W [{}] /CIDToGIDMap /Identity >>",
+        base_font,
+        descriptor_id,
+        widths.join(" ")
+    );
+    let [x_min, y_min, x_max, y_max] = font.bbox.map(|v| font.scale(v));
+    let descriptor = format!(
+        "<< /Type /FontDescriptor /FontName /{} /Flags 4 /FontBBox [{} {} {} {}] /ItalicAngle 0 \
+         /Ascent {} /Descent {} /CapHeight {} /StemV 80 /FontFile2 {} 0 R >>",
+        base_font,
+        x_min,
+        y_min,
+        x_max,
+        y_max,
+        font.scale(font.ascent),
+        font.scale(font.descent),
+        font.scale(font.ascent),
+        file_id
+    );
+
+    let subset = font.subset(&glyphs.keys().copied().collect());
+    let length1 = subset.len();
+    let mut stream = lopdf::Stream::new(lopdf::Dictionary::new(), subset);
+    let filter = match stream.compress() {
+        Ok(()) if stream.dict.has(b"Filter") => " /Filter /FlateDecode",
+        _ => "",
+    };
+    let mut file =
+        format!("<< /Length {} /Length1 {}{} >>\nstream\n", stream.content.len(), length1, filter).into_bytes();
+    file.extend_from_slice(&stream.content);
+    file.extend_from_slice(b"\nendstream");
+
+    let to_unicode = to_unicode_cmap(glyphs);
+    let to_unicode = format!("<< /Length {} >>\nstream\n{}endstream", to_unicode.len(), to_unicode);
+
+    vec![
+        type0.into_bytes(),
+        cid_font.into_bytes(),
+        descriptor.into_bytes(),
+        file,
+        to_unicode.into_bytes(),
+    ]
+}
+
+/// CMap mapping glyph IDs back to text, so the PDF's text can be copied
+/// and searched
+fn to_unicode_cmap(glyphs: &BTreeMap<u16, String>) -> String {
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+         1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+    );
+    let entries: Vec<(&u16, &String)> = glyphs.iter().collect();
+    // A bfchar block holds at most 100 entries
+    for block in entries.chunks(100) {
+        cmap.push_str(&format!("{} beginbfchar\n", block.len()));
+        for (glyph, text) in block {
+            let units: String = text.encode_utf16().map(|u| format!("{:04X}", u)).collect();
+            cmap.push_str(&format!("<{:04X}> <{}>\n", glyph, units));
+        }
+        cmap.push_str("endbfchar\n");
+    }
+    cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend\n");
+    cmap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subset_keeps_only_used_glyphs() {
+        // Needs a system font with Arabic, such as DejaVu Sans or Noto
+        let Some(font) = fallback_for('ب') else {
+            return;
+        };
+        let used: BTreeSet<u16> = ['ب', 'ت'].iter().filter_map(|&c| font.glyph(c)).collect();
+        let subset = TrueTypeFont::parse(font.subset(&used), "Subset").unwrap();
+
+        for glyph in &used {
+            assert_eq!(subset.glyph_data(*glyph), font.glyph_data(*glyph));
+            assert_eq!(subset.advance(*glyph), font.advance(*glyph));
+        }
+        let unused = font.glyph('z').unwrap();
+        assert!(!font.glyph_data(unused).is_empty());
+        assert!(subset.glyph_data(unused).is_empty());
+        assert!(subset.data.len() < font.data.len());
+
+        let glyphs: BTreeMap<u16, String> = used.iter().map(|&g| (g, "ب".to_string())).collect();
+        let objects = embed_objects(&font, &glyphs, 10);
+        assert_eq!(objects.len(), 5);
+        assert!(String::from_utf8_lossy(&objects[0]).contains("/DescendantFonts [11 0 R]"));
+        assert!(String::from_utf8_lossy(&objects[4]).contains("<0628>"));
+    }
+}
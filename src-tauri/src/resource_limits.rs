@@ -0,0 +1,160 @@
+//! Resource Limits Module
+//!
+//! Limits on how hard OCR may work the device. Phones have fewer cores and
+//! less memory than desktops and run on battery, so the defaults depend on
+//! the platform the app was built for: mobile builds scan one receipt at a
+//! time, preprocess smaller images and hold batch scans while the battery
+//! is low. The frontend reports the battery state, as the webview can read
+//! it on every platform the backend builds for.
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use crate::ocr_jobs::CancelToken;
+
+/// Whether the app was built for a phone or tablet
+pub const MOBILE: bool = cfg!(any(target_os = "android", target_os = "ios"));
+
+/// How often waiting work checks whether it was cancelled
+const WAIT_POLL: Duration = Duration::from_millis(500);
+
+static POWER: Mutex<PowerState> = Mutex::new(PowerState {
+    on_battery: false,
+    battery_percent: None,
+});
+static POWER_CHANGED: Condvar = Condvar::new();
+
+/// Hold batch work while running on a low battery
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct BatterySaver {
+    pub enabled: bool,
+    /// Batches wait while on battery below this charge
+    pub min_battery_percent: u8,
+}
+
+impl Default for BatterySaver {
+    fn default() -> Self {
+        Self {
+            enabled: MOBILE,
+            min_battery_percent: 30,
+        }
+    }
+}
+
+impl BatterySaver {
+    /// Whether batch work should wait in this power state
+    pub fn defers(&self, power: &PowerState) -> bool {
+        self.enabled && power.on_battery && power.battery_percent.is_some_and(|p| p < self.min_battery_percent)
+    }
+}
+
+/// Limits on OCR work
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ResourceLimits {
+    /// Most OCR engines working at once, across scans and batch workers
+    pub max_concurrent_ocr: usize,
+    /// Larger images are downscaled to this many megapixels before
+    /// preprocessing, which holds several copies of the image in memory
+    pub max_preprocess_megapixels: f64,
+    pub battery_saver: BatterySaver,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            max_concurrent_ocr: if MOBILE { 1 } else { 4 },
+            max_preprocess_megapixels: if MOBILE { 12.0 } else { 40.0 },
+            battery_saver: BatterySaver::default(),
+        }
+    }
+}
+
+impl ResourceLimits {
+    pub fn max_preprocess_pixels(&self) -> u64 {
+        (self.max_preprocess_megapixels.max(1.0) * 1_000_000.0) as u64
+    }
+}
+
+/// Battery state reported by the frontend
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct PowerState {
+    pub on_battery: bool,
+    /// Charge left, when the platform reports it
+    pub battery_percent: Option<u8>,
+}
+
+fn lock_power() -> std::sync::MutexGuard<'static, PowerState> {
+    POWER.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Record the latest battery state, waking any deferred batches
+pub fn set_power_state(state: PowerState) {
+    *lock_power() = state;
+    POWER_CHANGED.notify_all();
+}
+
+pub fn power_state() -> PowerState {
+    lock_power().clone()
+}
+
+/// Block until the battery saver lets batch work run. `on_deferred` is
+/// called once if the work has to wait.
+pub fn wait_for_power(saver: &BatterySaver, cancel: &CancelToken, on_deferred: impl FnOnce()) -> Result<(), String> {
+    let mut power = lock_power();
+    if !saver.defers(&power) {
+        return Ok(());
+    }
+    on_deferred();
+    while saver.defers(&power) {
+        cancel.check()?;
+        power = POWER_CHANGED
+            .wait_timeout(power, WAIT_POLL)
+            .map_or_else(|e| e.into_inner().0, |(power, _)| power);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_low_battery_defers_batches_until_charging() {
+        let saver = BatterySaver {
+            enabled: true,
+            min_battery_percent: 30,
+        };
+        let low = PowerState {
+            on_battery: true,
+            battery_percent: Some(20),
+        };
+        assert!(saver.defers(&low));
+        assert!(!saver.defers(&PowerState { battery_percent: Some(80), ..low.clone() }));
+        assert!(!BatterySaver { enabled: false, ..saver.clone() }.defers(&low));
+
+        set_power_state(low);
+        let (deferred, was_deferred) = mpsc::channel();
+        let waiting = {
+            let saver = saver.clone();
+            std::thread::spawn(move || wait_for_power(&saver, &CancelToken::default(), || deferred.send(()).unwrap()))
+        };
+        was_deferred.recv().unwrap();
+        set_power_state(PowerState {
+            on_battery: false,
+            battery_percent: Some(20),
+        });
+        assert!(waiting.join().unwrap().is_ok());
+
+        assert_eq!(ResourceLimits::default().max_concurrent_ocr, if MOBILE { 1 } else { 4 });
+        let limits = ResourceLimits {
+            max_preprocess_megapixels: 12.0,
+            ..Default::default()
+        };
+        assert_eq!(limits.max_preprocess_pixels(), 12_000_000);
+    }
+}
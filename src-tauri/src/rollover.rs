@@ -0,0 +1,181 @@
+//! Financial Year Rollover Module
+//!
+//! Carries unfinished work (unpaid invoices and documents still awaiting
+//! review) from a closing financial year into the next one. Carried documents
+//! are tagged so the new year's views can include them and its summary can
+//! report them as the opening position.
+
+use serde::{Deserialize, Serialize};
+
+use crate::documents::{DocumentKind, DocumentStatus, StoredDocument};
+use crate::money::Money;
+use crate::periods;
+
+/// Why a document was carried into the new year
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum CarryReason {
+    Unpaid,
+    Unreviewed,
+    UnpaidAndUnreviewed,
+}
+
+/// A document carried into the new financial year
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CarriedItem {
+    pub document_id: String,
+    pub vendor: Option<String>,
+    pub date: Option<String>,
+    pub total: Option<Money>,
+    pub reason: CarryReason,
+}
+
+/// Outcome of rolling a financial year forward
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RolloverResult {
+    pub closing_year: i32,
+    pub opening_year: i32,
+    /// Tag applied to carried documents
+    pub tag: String,
+    pub carried: Vec<CarriedItem>,
+}
+
+/// Opening position of a financial year: items carried in from the previous one
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct OpeningPosition {
+    pub carried_from: String,
+    pub items: Vec<CarriedItem>,
+    pub unpaid_count: usize,
+    pub unpaid_total: Money,
+    pub unreviewed_count: usize,
+}
+
+/// Tag marking documents carried out of a closing financial year
+pub fn carried_tag(closing_year: i32) -> String {
+    format!("carried-from-{}", periods::financial_year_label(closing_year))
+}
+
+/// Why a document is unfinished, if it is
+fn carry_reason(doc: &StoredDocument) -> Option<CarryReason> {
+    let unpaid = doc.kind == DocumentKind::Invoice && !doc.paid;
+    let unreviewed = doc.status == DocumentStatus::PendingReview;
+    match (unpaid, unreviewed) {
+        (true, true) => Some(CarryReason::UnpaidAndUnreviewed),
+        (true, false) => Some(CarryReason::Unpaid),
+        (false, true) => Some(CarryReason::Unreviewed),
+        (false, false) => None,
+    }
+}
+
+fn carried_item(doc: &StoredDocument, reason: CarryReason) -> CarriedItem {
+    CarriedItem {
+        document_id: doc.id.clone(),
+        vendor: doc.vendor.clone(),
+        date: doc.date.clone(),
+        total: doc.total,
+        reason,
+    }
+}
+
+/// Tag every unfinished document dated in `closing_year` as carried forward.
+///
+/// Safe to run more than once; already-tagged documents are reported again
+/// but not tagged twice.
+pub fn roll_forward(documents: &mut [StoredDocument], closing_year: i32) -> RolloverResult {
+    let tag = carried_tag(closing_year);
+    let mut carried = Vec::new();
+
+    for doc in documents.iter_mut() {
+        let in_year = doc
+            .date
+            .as_deref()
+            .and_then(periods::parse_date)
+            .is_some_and(|d| periods::financial_year_of(d) == closing_year);
+        if !in_year {
+            continue;
+        }
+        let Some(reason) = carry_reason(doc) else {
+            continue;
+        };
+
+        if !doc.tags.contains(&tag) {
+            doc.tags.push(tag.clone());
+            doc.updated_at = crate::storage::now_timestamp();
+            doc.revision += 1;
+        }
+        carried.push(carried_item(doc, reason));
+    }
+
+    RolloverResult {
+        closing_year,
+        opening_year: closing_year + 1,
+        tag,
+        carried,
+    }
+}
+
+/// Items carried into `financial_year` that are still unfinished
+pub fn opening_position(documents: &[StoredDocument], financial_year: i32) -> OpeningPosition {
+    let tag = carried_tag(financial_year - 1);
+    let items: Vec<CarriedItem> = documents
+        .iter()
+        .filter(|d| d.tags.contains(&tag))
+        .filter_map(|d| carry_reason(d).map(|reason| carried_item(d, reason)))
+        .collect();
+
+    let unpaid: Vec<&CarriedItem> = items
+        .iter()
+        .filter(|i| i.reason != CarryReason::Unreviewed)
+        .collect();
+    let unpaid_total = unpaid.iter().filter_map(|i| i.total).sum();
+
+    OpeningPosition {
+        carried_from: periods::financial_year_label(financial_year - 1),
+        unpaid_count: unpaid.len(),
+        unpaid_total,
+        unreviewed_count: items.iter().filter(|i| i.reason != CarryReason::Unpaid).count(),
+        items,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(id: &str, kind: DocumentKind, date: &str, paid: bool, status: DocumentStatus) -> StoredDocument {
+        StoredDocument {
+            id: id.to_string(),
+            kind,
+            date: Some(date.to_string()),
+            total: Some(Money::from_cents(10000)),
+            paid,
+            status,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_roll_forward_tags_unfinished_items() {
+        let mut docs = vec![
+            doc("paid", DocumentKind::Invoice, "2024-05-01", true, DocumentStatus::Accepted),
+            doc("unpaid", DocumentKind::Invoice, "2024-06-15", false, DocumentStatus::Accepted),
+            doc("unreviewed", DocumentKind::Receipt, "2024-06-20", false, DocumentStatus::PendingReview),
+            doc("next-year", DocumentKind::Invoice, "2024-07-02", false, DocumentStatus::PendingReview),
+        ];
+
+        let result = roll_forward(&mut docs, 2024);
+        assert_eq!(result.carried.len(), 2);
+        assert_eq!(result.tag, "carried-from-FY2023-24");
+        assert_eq!(docs[1].tags, vec!["carried-from-FY2023-24".to_string()]);
+
+        // Running again does not duplicate tags
+        roll_forward(&mut docs, 2024);
+        assert_eq!(docs[1].tags.len(), 1);
+
+        // Once paid, an item drops out of the opening position
+        docs[1].paid = true;
+        let opening = opening_position(&docs, 2025);
+        assert_eq!(opening.items.len(), 1);
+        assert_eq!(opening.unpaid_count, 0);
+        assert_eq!(opening.unreviewed_count, 1);
+    }
+}
@@ -0,0 +1,132 @@
+//! Path Sandbox Module
+//!
+//! Validation for file paths and file names passed in from the frontend.
+//! Commands run these checks before reading, writing or deleting anything,
+//! so a buggy or compromised frontend can only touch:
+//! - Tally's own data and reports directories
+//! - Paths the user has granted through the fs plugin scope (file dialogs
+//!   and drag and drop)
+
+use std::path::{Component, Path, PathBuf};
+use tauri::AppHandle;
+use tauri_plugin_fs::FsExt;
+
+use crate::storage;
+use crate::tax_report;
+
+/// Directories Tally manages and may always access
+pub fn app_directories() -> Result<Vec<PathBuf>, String> {
+    Ok(vec![storage::get_data_directory()?, tax_report::get_reports_directory()?])
+}
+
+/// Resolve `..` and symlinks so a path can be compared against its roots.
+/// Files that don't exist yet are resolved through their parent directory.
+fn resolve(path: &Path) -> Result<PathBuf, String> {
+    if let Ok(resolved) = path.canonicalize() {
+        return Ok(resolved);
+    }
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(format!("Invalid path: {}", path.display()));
+    };
+    parent
+        .canonicalize()
+        .map(|p| p.join(name))
+        .map_err(|e| format!("Invalid path {}: {}", path.display(), e))
+}
+
+/// Check a path against the app directories and the user-granted scope,
+/// returning the resolved path
+pub fn check_path(path: &str, roots: &[PathBuf], granted: impl Fn(&Path) -> bool) -> Result<PathBuf, String> {
+    let raw = Path::new(path);
+    if path.trim().is_empty() || !raw.is_absolute() {
+        return Err(format!("Path must be absolute: {}", path));
+    }
+    let resolved = resolve(raw)?;
+
+    let in_app_directory = roots
+        .iter()
+        .filter_map(|root| resolve(root).ok())
+        .any(|root| resolved.starts_with(root));
+    if in_app_directory || granted(&resolved) {
+        Ok(resolved)
+    } else {
+        Err(format!("Access denied: {} is outside the allowed directories", path))
+    }
+}
+
+/// Validate a path passed to a command
+pub fn validate_path(app: &AppHandle, path: &str) -> Result<PathBuf, String> {
+    let scope = app.fs_scope();
+    check_path(path, &app_directories()?, |p| scope.is_allowed(p))
+}
+
+/// Validate every path passed to a command
+pub fn validate_paths(app: &AppHandle, paths: &[String]) -> Result<(), String> {
+    let scope = app.fs_scope();
+    let roots = app_directories()?;
+    for path in paths {
+        check_path(path, &roots, |p| scope.is_allowed(p))?;
+    }
+    Ok(())
+}
+
+/// Validate the original file path a frontend save puts on a record, unless
+/// it's the path already stored, which was checked when first saved. Later
+/// exports, bundles and re-extraction read the stored path without checking
+/// it again.
+pub fn validate_source_path(
+    app: &AppHandle,
+    path: Option<&str>,
+    stored: Option<&str>,
+) -> Result<Option<String>, String> {
+    match path {
+        Some(path) if Some(path) != stored => Ok(Some(validate_path(app, path)?.to_string_lossy().to_string())),
+        path => Ok(path.map(str::to_string)),
+    }
+}
+
+/// Validate a file name to be created inside an app directory. Only a bare
+/// name is accepted, so the file can't escape the directory it's joined to.
+pub fn validate_file_name(name: &str) -> Result<&str, String> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) if !name.contains(['/', '\\']) => Ok(name),
+        _ => Err(format!("Invalid file name: {}", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_check_path_limits_to_roots_and_grants() {
+        let dir = storage::test_dir();
+        let root = dir.join("data");
+        let granted = dir.join("granted.pdf");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(&granted, b"pdf").unwrap();
+        let roots = vec![root.clone()];
+        let allow = |p: &Path| p.ends_with("granted.pdf");
+
+        assert!(check_path(&root.join("a.json").to_string_lossy(), &roots, allow).is_ok());
+        assert!(check_path(&granted.to_string_lossy(), &roots, allow).is_ok());
+        assert!(check_path(&dir.join("other.pdf").to_string_lossy(), &roots, allow).is_err());
+
+        // Parent components can't climb out of a root
+        let escape = root.join("..").join("secret.txt");
+        assert!(check_path(&escape.to_string_lossy(), &roots, allow).is_err());
+        assert!(check_path("relative.pdf", &roots, allow).is_err());
+    }
+
+    #[test]
+    fn test_validate_file_name() {
+        assert_eq!(validate_file_name("report-2024.pdf"), Ok("report-2024.pdf"));
+        assert!(validate_file_name("../report.pdf").is_err());
+        assert!(validate_file_name("/etc/passwd").is_err());
+        assert!(validate_file_name("sub/report.pdf").is_err());
+        assert!(validate_file_name("..").is_err());
+        assert!(validate_file_name("").is_err());
+    }
+}
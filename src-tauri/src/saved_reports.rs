@@ -0,0 +1,408 @@
+//! Saved Reports Module
+//!
+//! Register of the reports the backend has written to disk, with the
+//! parameters each was generated from. A saved report can be regenerated
+//! against current data: each run is written next to the original as a new
+//! version (`-v2`, `-v3`) and the report's headline totals are kept per
+//! version, so a regeneration can list what changed since the last run.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::bundle::{self, BundleResult};
+use crate::digest;
+use crate::documents::{self, DocumentFilter};
+use crate::fy_comparison;
+use crate::money::Money;
+use crate::pagination::{self, Page, PageRequest, SortKey};
+use crate::periods;
+use crate::storage;
+
+static REGISTER_LOCK: Mutex<()> = Mutex::new(());
+
+/// What was generated and the inputs needed to generate it again
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ReportParameters {
+    WeeklyDigest {
+        /// Last day of the week (YYYY-MM-DD)
+        week_ending: String,
+    },
+    DocumentBundle {
+        filter: DocumentFilter,
+    },
+    FyComparison {
+        financial_year: i32,
+    },
+}
+
+impl ReportParameters {
+    fn title(&self) -> String {
+        match self {
+            ReportParameters::WeeklyDigest { week_ending } => format!("Weekly digest {}", week_ending),
+            ReportParameters::DocumentBundle { .. } => "Document bundle".to_string(),
+            ReportParameters::FyComparison { financial_year } => {
+                format!("Financial year comparison {}", periods::financial_year_label(*financial_year))
+            }
+        }
+    }
+}
+
+/// A labelled figure compared between versions
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReportTotal {
+    pub label: String,
+    pub value: f64,
+}
+
+impl ReportTotal {
+    pub fn new(label: &str, value: f64) -> Self {
+        Self {
+            label: label.to_string(),
+            value,
+        }
+    }
+}
+
+/// One generated copy of a report
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReportVersion {
+    /// Starts at 1 for the original
+    pub version: u32,
+    pub file_path: String,
+    pub generated_at: String,
+    pub totals: Vec<ReportTotal>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SavedReport {
+    pub id: String,
+    pub title: String,
+    pub parameters: ReportParameters,
+    /// Oldest first
+    pub versions: Vec<ReportVersion>,
+    pub created_at: String,
+}
+
+/// A total that differs from the previous version
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TotalChange {
+    pub label: String,
+    /// `None` when the total is new in this version
+    pub previous: Option<f64>,
+    /// `None` when the total no longer appears
+    pub current: Option<f64>,
+    pub difference: f64,
+}
+
+/// Outcome of regenerating a saved report
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RegenerationResult {
+    pub report_id: String,
+    pub version: ReportVersion,
+    pub changes: Vec<TotalChange>,
+}
+
+/// Field a page of saved reports is sorted on
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportSortField {
+    /// When the latest version was generated
+    #[default]
+    GeneratedAt,
+    Title,
+    CreatedAt,
+}
+
+impl ReportSortField {
+    fn key(self, report: &SavedReport) -> SortKey {
+        match self {
+            ReportSortField::GeneratedAt => {
+                SortKey::optional_text(report.versions.last().map(|v| v.generated_at.as_str()))
+            }
+            ReportSortField::Title => SortKey::text(&report.title),
+            ReportSortField::CreatedAt => SortKey::text(&report.created_at),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+struct RegisterData {
+    reports: Vec<SavedReport>,
+}
+
+/// JSON-backed register of saved reports
+pub struct ReportRegister {
+    path: PathBuf,
+    data: RegisterData,
+}
+
+impl ReportRegister {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            data: storage::load_json(path)?,
+        })
+    }
+
+    pub fn open_default() -> Result<Self, String> {
+        Self::open(&storage::get_data_directory()?.join("saved_reports.json"))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        storage::save_json(&self.path, &self.data)
+    }
+
+    /// Saved reports, most recently generated first
+    pub fn list(&self) -> Vec<SavedReport> {
+        let mut reports = self.data.reports.clone();
+        let latest = |r: &SavedReport| r.versions.last().map(|v| v.generated_at.clone());
+        reports.sort_by_key(|r| std::cmp::Reverse(latest(r)));
+        reports
+    }
+
+    /// One page of saved reports, optionally only those whose title contains
+    /// `title_contains` (case-insensitive)
+    pub fn page(
+        &self,
+        title_contains: Option<&str>,
+        request: &PageRequest<ReportSortField>,
+    ) -> Result<Page<SavedReport>, String> {
+        let needle = title_contains.map(str::to_lowercase);
+        let matching: Vec<SavedReport> = self
+            .data
+            .reports
+            .iter()
+            .filter(|r| needle.as_ref().map_or(true, |n| r.title.to_lowercase().contains(n)))
+            .cloned()
+            .collect();
+        pagination::paginate(matching, request, |r, field| field.key(r), |r| &r.id)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&SavedReport> {
+        self.data.reports.iter().find(|r| r.id == id)
+    }
+
+    /// Register a newly generated report. Generating the same report again
+    /// overwrites its file, so it replaces the existing first version and
+    /// drops any later ones.
+    pub fn record(&mut self, parameters: ReportParameters, file_path: &str, totals: Vec<ReportTotal>) -> SavedReport {
+        let version = ReportVersion {
+            version: 1,
+            file_path: file_path.to_string(),
+            generated_at: storage::now_timestamp(),
+            totals,
+        };
+        if let Some(existing) = self
+            .data
+            .reports
+            .iter_mut()
+            .find(|r| r.parameters == parameters && r.versions.first().is_some_and(|v| v.file_path == file_path))
+        {
+            existing.versions = vec![version];
+            return existing.clone();
+        }
+
+        let report = SavedReport {
+            id: storage::generate_id("report"),
+            title: parameters.title(),
+            parameters,
+            versions: vec![version],
+            created_at: storage::now_timestamp(),
+        };
+        self.data.reports.push(report.clone());
+        report
+    }
+
+    /// Add a regenerated version and compare its totals with the latest one
+    pub fn add_version(
+        &mut self,
+        id: &str,
+        file_path: &str,
+        totals: Vec<ReportTotal>,
+    ) -> Result<RegenerationResult, String> {
+        let report = self
+            .data
+            .reports
+            .iter_mut()
+            .find(|r| r.id == id)
+            .ok_or_else(|| format!("Saved report not found: {}", id))?;
+        let previous = report.versions.last();
+        let version = ReportVersion {
+            version: previous.map_or(1, |v| v.version + 1),
+            file_path: file_path.to_string(),
+            generated_at: storage::now_timestamp(),
+            totals,
+        };
+        let changes = total_changes(previous.map_or(&[], |v| v.totals.as_slice()), &version.totals);
+        report.versions.push(version.clone());
+
+        Ok(RegenerationResult {
+            report_id: id.to_string(),
+            version,
+            changes,
+        })
+    }
+}
+
+/// Totals that were added, removed or changed between two versions
+pub fn total_changes(previous: &[ReportTotal], current: &[ReportTotal]) -> Vec<TotalChange> {
+    let find = |totals: &[ReportTotal], label: &str| totals.iter().find(|t| t.label == label).map(|t| t.value);
+    let removed = previous.iter().filter(|p| find(current, &p.label).is_none());
+
+    current
+        .iter()
+        .chain(removed)
+        .filter_map(|total| {
+            let before = find(previous, &total.label);
+            let after = find(current, &total.label);
+            let difference = Money::from_dollars(after.unwrap_or(0.0)) - Money::from_dollars(before.unwrap_or(0.0));
+            (before.is_none() || after.is_none() || difference != Money::ZERO).then(|| TotalChange {
+                label: total.label.clone(),
+                previous: before,
+                current: after,
+                difference: difference.to_dollars(),
+            })
+        })
+        .collect()
+}
+
+/// Path of a later version of a report: `digest.html` becomes `digest-v2.html`
+pub fn versioned_path(original: &Path, version: u32) -> PathBuf {
+    let stem = original.file_stem().and_then(|s| s.to_str()).unwrap_or("report");
+    let name = match original.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}-v{}.{}", stem, version, ext),
+        None => format!("{}-v{}", stem, version),
+    };
+    original.with_file_name(name)
+}
+
+/// Headline figures compared between versions of a saved bundle
+pub fn bundle_totals(result: &BundleResult) -> Vec<ReportTotal> {
+    vec![
+        ReportTotal::new("Documents", result.document_count as f64),
+        ReportTotal::new("Pages", result.page_count as f64),
+        ReportTotal::new("Skipped", result.skipped.len() as f64),
+    ]
+}
+
+/// Generate a report from its parameters, writing it to `file_path`
+fn run(parameters: &ReportParameters, file_path: &Path) -> Result<Vec<ReportTotal>, String> {
+    match parameters {
+        ReportParameters::WeeklyDigest { week_ending } => {
+            let week_ending =
+                periods::parse_date(week_ending).ok_or_else(|| format!("Invalid week ending date: {}", week_ending))?;
+            Ok(digest::digest_totals(&digest::save_digest(week_ending, file_path)?.digest))
+        }
+        ReportParameters::DocumentBundle { filter } => {
+            let documents = documents::with_store(|store| Ok(store.list(filter)))?;
+            Ok(bundle_totals(&bundle::create_bundle(&documents, file_path)?))
+        }
+        ReportParameters::FyComparison { financial_year } => Ok(fy_comparison::comparison_totals(
+            &fy_comparison::save_comparison(*financial_year, file_path)?.comparison,
+        )),
+    }
+}
+
+/// Run a closure against the default register while holding its lock
+pub fn with_register<R>(f: impl FnOnce(&mut ReportRegister) -> Result<R, String>) -> Result<R, String> {
+    let _guard = REGISTER_LOCK.lock().map_err(|_| "Report register lock poisoned".to_string())?;
+    let mut register = ReportRegister::open_default()?;
+    f(&mut register)
+}
+
+/// Record a newly generated report in the default register
+pub fn record(parameters: ReportParameters, file_path: &str, totals: Vec<ReportTotal>) -> Result<SavedReport, String> {
+    with_register(|register| {
+        let report = register.record(parameters, file_path, totals);
+        register.save()?;
+        Ok(report)
+    })
+}
+
+/// Re-run a saved report with its original parameters against current data
+/// and save the output as its next version
+pub fn regenerate(report_id: &str) -> Result<RegenerationResult, String> {
+    let report = with_register(|register| {
+        register
+            .get(report_id)
+            .cloned()
+            .ok_or_else(|| format!("Saved report not found: {}", report_id))
+    })?;
+    let (Some(first), Some(latest)) = (report.versions.first(), report.versions.last()) else {
+        return Err(format!("Saved report {} has no versions", report_id));
+    };
+    let file_path = versioned_path(Path::new(&first.file_path), latest.version + 1);
+
+    // The report reads other stores, so it runs without the register lock
+    let totals = run(&report.parameters, &file_path)?;
+    with_register(|register| {
+        let result = register.add_version(report_id, &file_path.to_string_lossy(), totals)?;
+        register.save()?;
+        Ok(result)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(label: &str, previous: Option<f64>, current: Option<f64>, difference: f64) -> TotalChange {
+        TotalChange {
+            label: label.to_string(),
+            previous,
+            current,
+            difference,
+        }
+    }
+
+    #[test]
+    fn test_versions_record_changed_totals() {
+        let dir = storage::test_dir();
+        let path = dir.join("saved_reports.json");
+        let mut register = ReportRegister::open(&path).unwrap();
+        let parameters = ReportParameters::WeeklyDigest {
+            week_ending: "2024-06-30".to_string(),
+        };
+        let totals = vec![
+            ReportTotal::new("Imported total", 120.0),
+            ReportTotal::new("Category: Travel", 20.0),
+        ];
+        let report = register.record(parameters.clone(), "/digests/weekly-digest-2024-06-30.html", totals);
+        assert_eq!(report.title, "Weekly digest 2024-06-30");
+
+        let regenerated = register
+            .add_version(
+                &report.id,
+                "/digests/weekly-digest-2024-06-30-v2.html",
+                vec![ReportTotal::new("Imported total", 150.5), ReportTotal::new("Category: Meals", 30.5)],
+            )
+            .unwrap();
+        assert_eq!(regenerated.version.version, 2);
+        assert_eq!(
+            regenerated.changes,
+            vec![
+                change("Imported total", Some(120.0), Some(150.5), 30.5),
+                change("Category: Meals", None, Some(30.5), 30.5),
+                change("Category: Travel", Some(20.0), None, -20.0),
+            ]
+        );
+        assert!(register.add_version("missing", "x.html", Vec::new()).is_err());
+        assert!(total_changes(&regenerated.version.totals, &regenerated.version.totals).is_empty());
+
+        // Generating the same digest again starts its history over
+        register.save().unwrap();
+        let mut reopened = ReportRegister::open(&path).unwrap();
+        assert_eq!(reopened.get(&report.id).unwrap().versions.len(), 2);
+        let again = reopened.record(parameters, "/digests/weekly-digest-2024-06-30.html", Vec::new());
+        assert_eq!((again.id.as_str(), again.versions.len()), (report.id.as_str(), 1));
+        assert_eq!(reopened.list().len(), 1);
+
+        assert_eq!(
+            versioned_path(Path::new("/digests/weekly-digest-2024-06-30.html"), 3),
+            Path::new("/digests/weekly-digest-2024-06-30-v3.html")
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Receipt Segmentation Module
+//!
+//! Finds the separate receipts in a photo of several laid out together.
+//! Receipt paper is brighter than the table it sits on, so a downscaled
+//! copy of the photo is split into paper and background with Otsu's
+//! threshold, and each large connected area of paper is taken to be one
+//! receipt. Photos where the paper can't be told apart from the background
+//! are treated as a single receipt.
+
+use image::{DynamicImage, GrayImage};
+
+use crate::imaging;
+use crate::ocr::BoundingBox;
+
+/// Photos are segmented at this size; receipts are large enough to survive it
+const SAMPLE_EDGE: u32 = 400;
+
+/// Paper areas smaller than this share of the photo are glare or scraps
+const MIN_REGION_FRACTION: f64 = 0.03;
+
+/// Paper and background means must differ by this much for the split to be
+/// trusted
+const MIN_CONTRAST: f64 = 40.0;
+
+/// Sample pixels added around each region so edges aren't clipped
+const REGION_MARGIN: u32 = 2;
+
+/// Threshold separating dark and bright pixels, chosen to maximise the
+/// variance between the two classes. Returns the threshold and the
+/// difference between the class means.
+pub fn otsu_threshold(image: &GrayImage) -> (u8, f64) {
+    let mut histogram = [0u64; 256];
+    for pixel in image.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+    let total = image.pixels().len() as f64;
+    let sum: f64 = histogram.iter().enumerate().map(|(v, &n)| v as f64 * n as f64).sum();
+
+    let (mut best, mut best_variance, mut best_contrast) = (0u8, 0.0, 0.0);
+    let (mut dark_count, mut dark_sum) = (0.0, 0.0);
+    for (value, &count) in histogram.iter().enumerate() {
+        dark_count += count as f64;
+        dark_sum += value as f64 * count as f64;
+        let bright_count = total - dark_count;
+        if dark_count == 0.0 || bright_count == 0.0 {
+            continue;
+        }
+        let contrast = (sum - dark_sum) / bright_count - dark_sum / dark_count;
+        let variance = dark_count * bright_count * contrast * contrast;
+        if variance > best_variance {
+            (best, best_variance, best_contrast) = (value as u8, variance, contrast);
+        }
+    }
+    (best, best_contrast)
+}
+
+/// Bounding boxes of the connected areas above `threshold`, in sample pixels
+fn bright_components(image: &GrayImage, threshold: u8, min_area: usize) -> Vec<BoundingBox> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let mut visited: Vec<bool> = image.pixels().map(|p| p[0] <= threshold).collect();
+    let mut components = Vec::new();
+    let mut stack = Vec::new();
+
+    for start in 0..visited.len() {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        stack.push(start);
+        let (mut area, mut min_x, mut min_y, mut max_x, mut max_y) = (0, width, height, 0, 0);
+        while let Some(index) = stack.pop() {
+            let (x, y) = (index % width, index / width);
+            area += 1;
+            (min_x, min_y, max_x, max_y) = (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y));
+            let neighbours = [
+                (x > 0).then(|| index - 1),
+                (x + 1 < width).then(|| index + 1),
+                (y > 0).then(|| index - width),
+                (y + 1 < height).then(|| index + width),
+            ];
+            for next in neighbours.into_iter().flatten() {
+                if !visited[next] {
+                    visited[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        if area >= min_area {
+            components.push(BoundingBox {
+                x: min_x as u32,
+                y: min_y as u32,
+                width: (max_x - min_x + 1) as u32,
+                height: (max_y - min_y + 1) as u32,
+            });
+        }
+    }
+    components
+}
+
+/// Regions of `image` holding separate receipts, left to right and then top
+/// to bottom. The whole image is returned when fewer than two are found.
+pub fn find_receipt_regions(image: &DynamicImage) -> Vec<BoundingBox> {
+    let whole = vec![BoundingBox {
+        x: 0,
+        y: 0,
+        width: image.width(),
+        height: image.height(),
+    }];
+    let sample = imaging::downscale_to_fit(image.clone(), SAMPLE_EDGE).to_luma8();
+    if sample.width() == 0 || sample.height() == 0 {
+        return whole;
+    }
+    let (threshold, contrast) = otsu_threshold(&sample);
+    if contrast < MIN_CONTRAST {
+        return whole;
+    }
+
+    let min_area = (sample.width() as f64 * sample.height() as f64 * MIN_REGION_FRACTION) as usize;
+    let mut regions = bright_components(&sample, threshold, min_area.max(1));
+    if regions.len() < 2 {
+        return whole;
+    }
+    regions.sort_by_key(|r| (r.x, r.y));
+
+    let scale_x = image.width() as f64 / sample.width() as f64;
+    let scale_y = image.height() as f64 / sample.height() as f64;
+    regions
+        .into_iter()
+        .map(|r| {
+            let x = r.x.saturating_sub(REGION_MARGIN);
+            let y = r.y.saturating_sub(REGION_MARGIN);
+            let right = (r.x + r.width + REGION_MARGIN).min(sample.width());
+            let bottom = (r.y + r.height + REGION_MARGIN).min(sample.height());
+            let left_px = (x as f64 * scale_x).floor() as u32;
+            let top_px = (y as f64 * scale_y).floor() as u32;
+            let right_px = ((right as f64 * scale_x).ceil() as u32).min(image.width());
+            let bottom_px = ((bottom as f64 * scale_y).ceil() as u32).min(image.height());
+            BoundingBox {
+                x: left_px,
+                y: top_px,
+                width: right_px - left_px,
+                height: bottom_px - top_px,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    #[test]
+    fn test_receipts_on_a_dark_table_are_separated() {
+        // Two receipts with lines of text, and a speck of glare
+        let receipt = |x: u32, y: u32, left: u32, right: u32| {
+            (left..right).contains(&x) && (40..760).contains(&y) && !(y % 40 < 6 && x > left + 20 && x < right - 20)
+        };
+        let photo = GrayImage::from_fn(1000, 800, |x, y| {
+            if receipt(x, y, 560, 900) || receipt(x, y, 80, 420) || (x < 10 && y < 10) {
+                Luma([235])
+            } else {
+                Luma([40])
+            }
+        });
+
+        let regions = find_receipt_regions(&DynamicImage::ImageLuma8(photo));
+        assert_eq!(regions.len(), 2);
+        let first = regions[0];
+        assert!(first.x <= 80 && first.x >= 70, "{:?}", first);
+        assert!(first.x + first.width >= 420 && first.x + first.width <= 430, "{:?}", first);
+        assert!(first.y <= 40 && first.y + first.height >= 760, "{:?}", first);
+        assert!(regions[1].x <= 560 && regions[1].x >= 550, "{:?}", regions[1]);
+
+        let single = GrayImage::from_fn(600, 900, |x, y| {
+            if y % 40 < 6 && (40..560).contains(&x) {
+                Luma([30])
+            } else {
+                Luma([240])
+            }
+        });
+        let regions = find_receipt_regions(&DynamicImage::ImageLuma8(single));
+        assert_eq!((regions.len(), regions[0].width, regions[0].height), (1, 600, 900));
+    }
+}
@@ -0,0 +1,113 @@
+//! App Settings Module
+//!
+//! Backend settings persisted alongside the document store.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::capture_metadata::TravelSettings;
+use crate::cloud_ocr::CloudOcrSettings;
+use crate::dates::DateOrder;
+use crate::digest::DigestSettings;
+use crate::imaging::{DecodeLimits, UpscaleSettings};
+use crate::invoice::HeuristicWeights;
+use crate::ocr::{OcrConfig, ThresholdConfig};
+use crate::preprocess::PreprocessOptions;
+use crate::reminders::ReminderSettings;
+use crate::resource_limits::ResourceLimits;
+use crate::storage;
+use crate::straight_through::StraightThroughSettings;
+use crate::upload::UploadTarget;
+
+/// Persisted application settings
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct AppSettings {
+    /// Disable every outbound network call
+    pub privacy_mode: bool,
+    /// GUID issued by the ABR for web service access
+    pub abr_guid: Option<String>,
+    /// Limits applied when decoding receipt images
+    pub decode_limits: DecodeLimits,
+    /// Upscaling applied to low-resolution receipts before OCR
+    pub ocr_upscale: UpscaleSettings,
+    /// Cleanup applied to receipt photos before OCR
+    pub ocr_preprocess: PreprocessOptions,
+    /// Languages and layout hints for receipt OCR
+    pub ocr: OcrConfig,
+    /// Confidence cutoffs for accepting or reviewing scanned receipts
+    pub ocr_thresholds: ThresholdConfig,
+    /// Directory holding Tesseract `.traineddata` files; searched for in
+    /// standard install locations when unset
+    pub tessdata_path: Option<String>,
+    /// Cloud service that re-reads receipts the device reads poorly
+    pub cloud_ocr: CloudOcrSettings,
+    /// Copy scanned photos into the app's `Receipts` archive so originals
+    /// survive being deleted from the phone or camera roll
+    pub archive_receipts: bool,
+    /// OCR concurrency, memory and battery limits; defaults suit the platform
+    pub resource_limits: ResourceLimits,
+    /// Refine invoice extraction with the on-device layout model
+    /// (requires the `ml-extract` build feature)
+    pub layout_model_enabled: bool,
+    /// Directory holding the layout model; defaults to `models/layout`
+    /// in the data directory
+    pub layout_model_path: Option<String>,
+    /// Automatic weekly digest schedule
+    pub digest: DigestSettings,
+    /// Named invoice parser scoring profiles
+    pub heuristic_profiles: BTreeMap<String, HeuristicWeights>,
+    /// Profile used for parsing; the built-in defaults when unset
+    pub active_heuristic_profile: Option<String>,
+    /// Extra regexes for invoice lines that are never line items
+    pub line_item_skip_patterns: Vec<String>,
+    /// Entity new documents are claimed under
+    pub active_entity_id: Option<String>,
+    /// Reading of numeric dates such as 03/04/2024 when either field could
+    /// be the month
+    pub date_order: DateOrder,
+    /// Escalating reminders for unpaid invoices
+    pub invoice_reminders: ReminderSettings,
+    /// Home location for prefilling the travel diary
+    pub travel: TravelSettings,
+    /// Accountant portals handover packages can be uploaded to
+    pub upload_targets: Vec<UploadTarget>,
+    /// Accepting trusted, validated documents without review
+    pub straight_through: StraightThroughSettings,
+}
+
+/// Name of the built-in scoring profile
+pub const DEFAULT_PROFILE: &str = "default";
+
+impl AppSettings {
+    /// Weights for a named profile, including the built-in `default`
+    pub fn heuristic_weights(&self, name: &str) -> Option<HeuristicWeights> {
+        match self.heuristic_profiles.get(name) {
+            Some(weights) => Some(weights.clone()),
+            None if name == DEFAULT_PROFILE => Some(HeuristicWeights::default()),
+            None => None,
+        }
+    }
+
+    /// Weights for the active profile, if one is selected
+    pub fn active_heuristic_weights(&self) -> Option<HeuristicWeights> {
+        self.active_heuristic_profile
+            .as_deref()
+            .and_then(|name| self.heuristic_weights(name))
+    }
+}
+
+fn settings_path() -> Result<PathBuf, String> {
+    Ok(storage::get_data_directory()?.join("settings.json"))
+}
+
+/// Load settings, falling back to defaults when none have been saved
+pub fn load_settings() -> Result<AppSettings, String> {
+    storage::load_json(&settings_path()?)
+}
+
+/// Persist settings
+pub fn save_settings(settings: &AppSettings) -> Result<(), String> {
+    storage::save_json(&settings_path()?, settings)
+}
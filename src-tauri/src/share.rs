@@ -0,0 +1,157 @@
+//! Report Sharing Module
+//!
+//! Hands a generated report to the platform share sheet so mobile users can
+//! get it off the device. Only files in the reports directory can be
+//! shared; on mobile that directory is in app storage, as given by the
+//! plugin, so the native share sheet can read it. The share sheet is held
+//! in managed state as a `ShareSheet`; on iOS and Android it is presented
+//! by the bundled `share-sheet` plugin. Desktop platforms report that
+//! sharing is unavailable and leave the report where it was saved.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tauri::{AppHandle, Manager};
+
+use crate::sandbox;
+use crate::tax_report;
+
+/// What the share sheet is asked to share
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ShareRequest {
+    pub file_path: String,
+    pub mime_type: String,
+    /// Shown as the share sheet title and used as the email subject
+    pub title: String,
+}
+
+/// Outcome of sharing a report
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShareResult {
+    pub file_path: String,
+    /// The user picked a destination; `false` when they dismissed the sheet
+    /// or sharing isn't available
+    pub completed: bool,
+    pub error: Option<String>,
+}
+
+/// Platform share sheet
+pub trait ShareSheet: Send + Sync {
+    /// Present the share sheet and wait until it is dismissed, returning
+    /// whether the file was shared
+    fn share(&self, request: &ShareRequest) -> Result<bool, String>;
+}
+
+/// Managed-state wrapper for the platform share sheet
+pub struct ShareSheetState(pub Box<dyn ShareSheet>);
+
+impl ShareSheetState {
+    /// The share sheet for this platform; on mobile the `share-sheet`
+    /// plugin must already be registered
+    #[cfg_attr(not(mobile), allow(unused_variables))]
+    pub fn platform(app: &AppHandle) -> Self {
+        #[cfg(mobile)]
+        return Self(Box::new(PluginShareSheet(app.clone())));
+        #[cfg(not(mobile))]
+        return Self(Box::new(NoShareSheet));
+    }
+}
+
+/// The native share sheet, presented through the `share-sheet` plugin
+#[cfg(mobile)]
+struct PluginShareSheet(AppHandle);
+
+#[cfg(mobile)]
+impl ShareSheet for PluginShareSheet {
+    fn share(&self, request: &ShareRequest) -> Result<bool, String> {
+        use tauri_plugin_share_sheet::{ShareFile, ShareSheetExt};
+
+        self.0.share_sheet().share_file(&ShareFile {
+            path: request.file_path.clone(),
+            mime_type: request.mime_type.clone(),
+            title: request.title.clone(),
+        })
+    }
+}
+
+/// Desktop platforms have no share sheet
+#[cfg(not(mobile))]
+struct NoShareSheet;
+
+#[cfg(not(mobile))]
+impl ShareSheet for NoShareSheet {
+    fn share(&self, _request: &ShareRequest) -> Result<bool, String> {
+        Err("Sharing isn't available on this device; the report is saved in the reports folder".to_string())
+    }
+}
+
+fn mime_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()).map(str::to_lowercase).as_deref() {
+        Some("pdf") => "application/pdf",
+        Some("html") | Some("htm") => "text/html",
+        Some("csv") => "text/csv",
+        Some("zip") => "application/zip",
+        Some("ofx") => "application/x-ofx",
+        Some("qif") => "application/qif",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Build a share request for a report, refusing files outside `reports_dir`
+pub fn share_request(reports_dir: &Path, file_path: &str) -> Result<ShareRequest, String> {
+    let canonical = sandbox::check_path(file_path, &[reports_dir.to_path_buf()], |_| false)
+        .map_err(|_| format!("Only saved reports can be shared: {}", file_path))?;
+    if !canonical.is_file() {
+        return Err(format!("Report not found: {}", file_path));
+    }
+
+    let title = canonical
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Report".to_string());
+    Ok(ShareRequest {
+        file_path: canonical.to_string_lossy().to_string(),
+        mime_type: mime_type(&canonical).to_string(),
+        title,
+    })
+}
+
+/// Share a saved report through the platform share sheet. Blocks until the
+/// sheet is dismissed.
+pub fn share_report(app: &AppHandle, file_path: &str) -> Result<ShareResult, String> {
+    let request = share_request(&tax_report::get_reports_directory()?, file_path)?;
+    let sheet = app.state::<ShareSheetState>();
+    let (completed, error) = match sheet.inner().0.share(&request) {
+        Ok(completed) => (completed, None),
+        Err(e) => (false, Some(e)),
+    };
+    Ok(ShareResult {
+        file_path: request.file_path,
+        completed,
+        error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage;
+
+    #[test]
+    fn test_only_saved_reports_can_be_shared() {
+        let root = storage::test_dir();
+        let reports = root.join("TallyTaxReports");
+        std::fs::create_dir_all(&reports).unwrap();
+        let report = reports.join("Tax Report 2024.pdf");
+        std::fs::write(&report, b"%PDF-1.7").unwrap();
+        let outside = root.join("secret.pdf");
+        std::fs::write(&outside, b"%PDF-1.7").unwrap();
+
+        let request = share_request(&reports, &report.to_string_lossy()).unwrap();
+        assert_eq!((request.mime_type.as_str(), request.title.as_str()), ("application/pdf", "Tax Report 2024"));
+
+        let escaped = reports.join("..").join("secret.pdf");
+        assert!(share_request(&reports, &escaped.to_string_lossy()).is_err());
+        assert!(share_request(&reports, &outside.to_string_lossy()).is_err());
+        assert!(share_request(&reports, &reports.join("missing.pdf").to_string_lossy()).is_err());
+    }
+}
@@ -0,0 +1,312 @@
+//! Trading Stock Module
+//!
+//! End-of-year stocktake for businesses that hold trading stock. Each
+//! financial year has an opening and a closing stock value; a year without
+//! its own opening value starts from the previous year's closing value.
+//! Stock taken out of the business other than by sale (goods used
+//! privately, given away) is recorded as an adjustment so it isn't claimed
+//! as a cost of sales.
+//!
+//! The cost-of-goods-sold adjustment added to the year's expenses is
+//! opening stock less closing stock less adjustments: purchases are
+//! already counted as documents, so only the change in stock on hand is
+//! added here.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::money::{round_cents, sum_dollars, Money};
+use crate::periods;
+use crate::storage;
+
+static STOCK_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StockValueKind {
+    Opening,
+    Closing,
+}
+
+/// How stock on hand was valued; each item may use any of the three
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ValuationMethod {
+    #[default]
+    Cost,
+    MarketSelling,
+    Replacement,
+}
+
+/// Value of stock on hand at the start or end of a financial year
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StockValue {
+    pub id: String,
+    pub financial_year: i32,
+    pub kind: StockValueKind,
+    pub value: f64,
+    #[serde(default)]
+    pub valuation_method: ValuationMethod,
+    /// Date the stocktake was done (YYYY-MM-DD)
+    pub counted_on: Option<String>,
+    pub notes: Option<String>,
+    pub created_at: String,
+}
+
+/// New opening or closing value from the stocktake form
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NewStockValue {
+    pub financial_year: i32,
+    pub kind: StockValueKind,
+    pub value: f64,
+    #[serde(default)]
+    pub valuation_method: ValuationMethod,
+    pub counted_on: Option<String>,
+    pub notes: Option<String>,
+}
+
+/// Stock removed other than by sale
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StockAdjustment {
+    pub id: String,
+    /// Date the stock was removed (YYYY-MM-DD)
+    pub date: String,
+    /// Cost of the stock removed
+    pub amount: f64,
+    pub reason: String,
+    pub created_at: String,
+}
+
+/// New adjustment from the quick-entry form
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NewStockAdjustment {
+    pub date: String,
+    pub amount: f64,
+    pub reason: String,
+}
+
+/// A financial year's stock movement, as reported in the annual summary
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TradingStockSummary {
+    pub opening_value: f64,
+    /// The opening value is the previous year's closing stocktake
+    pub opening_from_previous_year: bool,
+    /// `None` until the closing stocktake is entered
+    pub closing_value: Option<f64>,
+    pub adjustments_total: f64,
+    /// Added to the year's expenses; negative when stock on hand grew
+    pub cogs_adjustment: f64,
+}
+
+/// Persisted values and adjustments
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+struct StockData {
+    values: Vec<StockValue>,
+    adjustments: Vec<StockAdjustment>,
+}
+
+/// JSON-backed stocktake ledger
+pub struct StockLedger {
+    path: PathBuf,
+    data: StockData,
+}
+
+impl StockLedger {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            data: storage::load_json(path)?,
+        })
+    }
+
+    pub fn open_default() -> Result<Self, String> {
+        Self::open(&storage::get_data_directory()?.join("stock.json"))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        storage::save_json(&self.path, &self.data)
+    }
+
+    /// Opening and closing values, optionally for one financial year
+    pub fn values(&self, financial_year: Option<i32>) -> Vec<StockValue> {
+        let mut values: Vec<StockValue> = self
+            .data
+            .values
+            .iter()
+            .filter(|v| financial_year.map_or(true, |fy| v.financial_year == fy))
+            .cloned()
+            .collect();
+        values.sort_by_key(|v| (v.financial_year, v.kind == StockValueKind::Closing));
+        values
+    }
+
+    fn value(&self, financial_year: i32, kind: StockValueKind) -> Option<&StockValue> {
+        self.data
+            .values
+            .iter()
+            .find(|v| v.financial_year == financial_year && v.kind == kind)
+    }
+
+    /// Record a year's opening or closing value, replacing any earlier entry
+    pub fn set_value(&mut self, entry: NewStockValue) -> Result<StockValue, String> {
+        if !entry.value.is_finite() || entry.value < 0.0 {
+            return Err("Stock value can't be negative".to_string());
+        }
+        let counted_on = match entry.counted_on.as_deref().filter(|d| !d.trim().is_empty()) {
+            Some(date) => Some(
+                periods::parse_date(date)
+                    .ok_or_else(|| format!("Invalid date: {}", date))?
+                    .format("%Y-%m-%d")
+                    .to_string(),
+            ),
+            None => None,
+        };
+
+        let value = StockValue {
+            id: storage::generate_id("stock"),
+            financial_year: entry.financial_year,
+            kind: entry.kind,
+            value: round_cents(entry.value),
+            valuation_method: entry.valuation_method,
+            counted_on,
+            notes: entry.notes.filter(|n| !n.trim().is_empty()),
+            created_at: storage::now_timestamp(),
+        };
+        self.data
+            .values
+            .retain(|v| !(v.financial_year == value.financial_year && v.kind == value.kind));
+        self.data.values.push(value.clone());
+        Ok(value)
+    }
+
+    /// Adjustments dated in a financial year (or all), oldest first
+    pub fn adjustments(&self, financial_year: Option<i32>) -> Vec<StockAdjustment> {
+        let mut adjustments: Vec<StockAdjustment> = self
+            .data
+            .adjustments
+            .iter()
+            .filter(|a| financial_year.is_none() || adjustment_year(a) == financial_year)
+            .cloned()
+            .collect();
+        adjustments.sort_by(|a, b| a.date.cmp(&b.date));
+        adjustments
+    }
+
+    pub fn add_adjustment(&mut self, entry: NewStockAdjustment) -> Result<StockAdjustment, String> {
+        let date = periods::parse_date(&entry.date).ok_or_else(|| format!("Invalid date: {}", entry.date))?;
+        if entry.reason.trim().is_empty() {
+            return Err("A reason is required for stock adjustments".to_string());
+        }
+        if entry.amount <= 0.0 {
+            return Err("Amount must be greater than zero".to_string());
+        }
+
+        let adjustment = StockAdjustment {
+            id: storage::generate_id("stockadj"),
+            date: date.format("%Y-%m-%d").to_string(),
+            amount: round_cents(entry.amount),
+            reason: entry.reason.trim().to_string(),
+            created_at: storage::now_timestamp(),
+        };
+        self.data.adjustments.push(adjustment.clone());
+        Ok(adjustment)
+    }
+
+    pub fn delete_adjustment(&mut self, id: &str) -> bool {
+        let before = self.data.adjustments.len();
+        self.data.adjustments.retain(|a| a.id != id);
+        self.data.adjustments.len() != before
+    }
+
+    /// Stock movement for a financial year; `None` when the year has no
+    /// stock records at all
+    pub fn year_summary(&self, financial_year: i32) -> Option<TradingStockSummary> {
+        let opening = self.value(financial_year, StockValueKind::Opening);
+        let previous_closing = self.value(financial_year - 1, StockValueKind::Closing);
+        let closing = self.value(financial_year, StockValueKind::Closing);
+        let adjustments = self.adjustments(Some(financial_year));
+        if opening.is_none() && previous_closing.is_none() && closing.is_none() && adjustments.is_empty() {
+            return None;
+        }
+
+        let opening_value = opening.or(previous_closing).map_or(0.0, |v| v.value);
+        let adjustments_total = sum_dollars(adjustments.iter().map(|a| a.amount));
+        // Until the closing count is in, stock is treated as unchanged
+        let closing_money = Money::from_dollars(closing.map_or(opening_value, |v| v.value));
+        let cogs_adjustment =
+            Money::from_dollars(opening_value) - closing_money - Money::from_dollars(adjustments_total);
+
+        Some(TradingStockSummary {
+            opening_value,
+            opening_from_previous_year: opening.is_none() && previous_closing.is_some(),
+            closing_value: closing.map(|v| v.value),
+            adjustments_total,
+            cogs_adjustment: cogs_adjustment.to_dollars(),
+        })
+    }
+}
+
+fn adjustment_year(adjustment: &StockAdjustment) -> Option<i32> {
+    periods::parse_date(&adjustment.date).map(periods::financial_year_of)
+}
+
+/// Run a closure against the default ledger while holding its lock
+pub fn with_stock_ledger<R>(f: impl FnOnce(&mut StockLedger) -> Result<R, String>) -> Result<R, String> {
+    let _guard = STOCK_LOCK.lock().map_err(|_| "Stock ledger lock poisoned".to_string())?;
+    let mut ledger = StockLedger::open_default()?;
+    f(&mut ledger)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(financial_year: i32, kind: StockValueKind, value: f64) -> NewStockValue {
+        NewStockValue {
+            financial_year,
+            kind,
+            value,
+            valuation_method: ValuationMethod::Cost,
+            counted_on: None,
+            notes: None,
+        }
+    }
+
+    #[test]
+    fn test_year_summary_carries_closing_stock_forward() {
+        let dir = storage::test_dir();
+        let path = dir.join("stock.json");
+        let mut ledger = StockLedger::open(&path).unwrap();
+        assert!(ledger.year_summary(2024).is_none());
+        assert!(ledger.set_value(value(2024, StockValueKind::Closing, -1.0)).is_err());
+
+        ledger.set_value(value(2024, StockValueKind::Opening, 5_000.0)).unwrap();
+        ledger.set_value(value(2024, StockValueKind::Closing, 3_000.0)).unwrap();
+        ledger.set_value(value(2024, StockValueKind::Closing, 3_200.0)).unwrap();
+        ledger
+            .add_adjustment(NewStockAdjustment {
+                date: "2024-02-10".to_string(),
+                amount: 150.0,
+                reason: "Goods taken for private use".to_string(),
+            })
+            .unwrap();
+
+        let fy2024 = ledger.year_summary(2024).unwrap();
+        assert_eq!(fy2024.closing_value, Some(3_200.0));
+        assert_eq!(fy2024.cogs_adjustment, 1_650.0);
+        assert_eq!(ledger.values(Some(2024)).len(), 2);
+
+        // FY2025 opens with FY2024's closing count; stock grew, so the adjustment is negative
+        ledger.set_value(value(2025, StockValueKind::Closing, 4_000.0)).unwrap();
+        let fy2025 = ledger.year_summary(2025).unwrap();
+        assert!(fy2025.opening_from_previous_year);
+        assert_eq!(fy2025.opening_value, 3_200.0);
+        assert_eq!(fy2025.cogs_adjustment, -800.0);
+
+        ledger.save().unwrap();
+        let reopened = StockLedger::open(&path).unwrap();
+        assert_eq!(reopened.year_summary(2025), Some(fy2025));
+    }
+}
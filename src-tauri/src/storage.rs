@@ -0,0 +1,139 @@
+//! Local storage helpers
+//!
+//! Backend-managed records are persisted as JSON files in the app data
+//! directory. Writes go to a temporary file which is then renamed over the
+//! target, so a crash mid-write never leaves a truncated store behind.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static ID_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Get the directory where backend data files are stored
+pub fn get_data_directory() -> Result<PathBuf, String> {
+    let data_dir = dirs::data_dir()
+        .ok_or_else(|| "Could not determine data directory".to_string())?;
+
+    Ok(data_dir.join("Tally"))
+}
+
+/// Get the directory for disposable cached data
+pub fn get_cache_directory() -> Result<PathBuf, String> {
+    let cache_dir = dirs::cache_dir()
+        .ok_or_else(|| "Could not determine cache directory".to_string())?;
+
+    Ok(cache_dir.join("Tally"))
+}
+
+/// Load a JSON file, returning the default value if it does not exist yet
+pub fn load_json<T: DeserializeOwned + Default>(path: &Path) -> Result<T, String> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+/// Atomically write a value as pretty-printed JSON
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        if !parent.exists() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create directory: {}", e))?;
+        }
+    }
+
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| format!("Failed to serialize data: {}", e))?;
+
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, json)
+        .map_err(|e| format!("Failed to write {}: {}", tmp_path.display(), e))?;
+    fs::rename(&tmp_path, path)
+        .map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+}
+
+/// Generate a unique record identifier with the given prefix
+pub fn generate_id(prefix: &str) -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let counter = ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    format!("{}-{:x}-{:x}", prefix, nanos, counter)
+}
+
+/// Current local time as an RFC 3339 timestamp
+pub fn now_timestamp() -> String {
+    chrono::Local::now().to_rfc3339()
+}
+
+/// A uniquely named temporary directory for tests, removed with its
+/// contents when dropped
+#[cfg(test)]
+pub struct TestDir(PathBuf);
+
+#[cfg(test)]
+impl std::ops::Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<Path> for TestDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Create an empty temporary directory for a test
+#[cfg(test)]
+pub fn test_dir() -> TestDir {
+    let dir = std::env::temp_dir().join(generate_id("tally-test"));
+    fs::create_dir_all(&dir).expect("failed to create test directory");
+    TestDir(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_id_is_unique() {
+        let a = generate_id("doc");
+        let b = generate_id("doc");
+        assert!(a.starts_with("doc-"));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_save_and_load_json() {
+        let dir = test_dir();
+        let path = dir.join("values.json");
+
+        let missing: Vec<u32> = load_json(&path).unwrap();
+        assert!(missing.is_empty());
+
+        save_json(&path, &vec![1u32, 2, 3]).unwrap();
+        let loaded: Vec<u32> = load_json(&path).unwrap();
+        assert_eq!(loaded, vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,330 @@
+//! Straight-Through Processing Module
+//!
+//! Opt-in hands-free handling for high-volume users. A freshly extracted
+//! document that passes every validation rule, comes from a trusted vendor,
+//! clears the confidence bar and has a category (its own or from a category
+//! rule) is categorised and accepted without anyone looking at it. Each one
+//! is logged, so the day's auto-processed documents can be shown as a digest
+//! and any of them sent back for review with one click.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::category_rules::{self, CategoryRule};
+use crate::documents::{self, DocumentStatus, DocumentStore, StoredDocument};
+use crate::invoice::{self, InvoiceParser};
+use crate::money::Money;
+use crate::ocr;
+use crate::periods;
+use crate::settings::{self, AppSettings};
+use crate::storage;
+
+static LOG_LOCK: Mutex<()> = Mutex::new(());
+
+/// When documents may skip review
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct StraightThroughSettings {
+    pub enabled: bool,
+    /// Vendors whose documents may be accepted unseen, matched ignoring case
+    pub trusted_vendors: Vec<String>,
+    /// Overall extraction confidence a document must reach, from 0 to 1
+    pub min_confidence: f64,
+}
+
+impl Default for StraightThroughSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            trusted_vendors: Vec::new(),
+            min_confidence: 0.9,
+        }
+    }
+}
+
+impl StraightThroughSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.min_confidence) {
+            return Err("Minimum confidence must be between 0 and 1".to_string());
+        }
+        Ok(())
+    }
+
+    fn trusts(&self, vendor: &str) -> bool {
+        self.trusted_vendors.iter().any(|trusted| trusted.trim().eq_ignore_ascii_case(vendor.trim()))
+    }
+}
+
+/// A document accepted without review
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AutoProcessedItem {
+    pub id: String,
+    pub document_id: String,
+    pub vendor: Option<String>,
+    pub date: Option<String>,
+    pub total: Option<Money>,
+    pub category: String,
+    /// Category before processing, put back on undo
+    pub previous_category: Option<String>,
+    pub processed_at: String,
+    /// Set once the document has been sent back for review
+    pub undone_at: Option<String>,
+}
+
+/// What was accepted without review on one day
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AutoProcessedDigest {
+    /// YYYY-MM-DD
+    pub date: String,
+    pub items: Vec<AutoProcessedItem>,
+    pub total: Money,
+}
+
+/// Category for a document that may skip review, or every reason it can't
+pub fn check(doc: &StoredDocument, settings: &AppSettings, rules: &[CategoryRule]) -> Result<String, Vec<String>> {
+    let guardrails = &settings.straight_through;
+    let mut blockers = Vec::new();
+
+    if !guardrails.enabled {
+        blockers.push("Straight-through processing is turned off".to_string());
+    }
+    if doc.status != DocumentStatus::PendingReview {
+        blockers.push("Document has already been reviewed".to_string());
+    }
+    if doc.provisional || doc.manual_entry {
+        blockers.push("Document has not been extracted".to_string());
+    }
+    match doc.vendor.as_deref() {
+        Some(vendor) if guardrails.trusts(vendor) => {}
+        Some(vendor) => blockers.push(format!("{} is not a trusted vendor", vendor)),
+        None => blockers.push("No vendor detected".to_string()),
+    }
+    if doc.confidence < guardrails.min_confidence {
+        blockers.push(format!(
+            "Confidence {:.0}% is below {:.0}%",
+            doc.confidence * 100.0,
+            guardrails.min_confidence * 100.0
+        ));
+    }
+    if doc.date.is_none() || !doc.total.is_some_and(|total| total > Money::ZERO) {
+        blockers.push("Date or total is missing".to_string());
+    }
+
+    if let Some(inv) = &doc.invoice {
+        let validation = invoice::validate_invoice(inv);
+        if validation.suggested_action != "accept" {
+            blockers.push(format!("Invoice validation suggests {}", validation.suggested_action.replace('_', " ")));
+        }
+        blockers.extend(validation.arithmetic_warnings.into_iter().map(|w| w.message));
+        if inv.abn.as_ref().is_some_and(|abn| !InvoiceParser::validate_abn(&abn.value)) {
+            blockers.push("ABN failed checksum validation".to_string());
+        }
+    } else if let Some(receipt) = &doc.receipt {
+        let validation = ocr::validate_receipt(receipt, &settings.ocr_thresholds);
+        if validation.suggested_action != "accept" {
+            blockers.push(format!("Receipt validation suggests {}", validation.suggested_action.replace('_', " ")));
+        }
+        if !validation.low_confidence_fields.is_empty() || !validation.handwritten_fields.is_empty() {
+            blockers.push("Some fields need checking".to_string());
+        }
+    } else {
+        blockers.push("No extracted data".to_string());
+    }
+
+    let entity_id = doc.entity_id.as_deref().or(settings.active_entity_id.as_deref());
+    let category = doc
+        .category
+        .clone()
+        .or_else(|| category_rules::evaluate(rules, doc, entity_id).map(|m| m.category));
+    match category {
+        Some(category) if blockers.is_empty() => Ok(category),
+        Some(_) => Err(blockers),
+        None => {
+            blockers.push("No category rule matches".to_string());
+            Err(blockers)
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+struct LogData {
+    items: Vec<AutoProcessedItem>,
+}
+
+/// JSON-backed log of auto-processed documents
+pub struct AutoProcessLog {
+    path: PathBuf,
+    data: LogData,
+}
+
+impl AutoProcessLog {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            data: storage::load_json(path)?,
+        })
+    }
+
+    pub fn open_default() -> Result<Self, String> {
+        Self::open(&storage::get_data_directory()?.join("auto_processed.json"))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        storage::save_json(&self.path, &self.data)
+    }
+
+    /// Items processed on `date` and not undone, oldest first
+    pub fn digest(&self, date: NaiveDate) -> AutoProcessedDigest {
+        let items: Vec<AutoProcessedItem> = self
+            .data
+            .items
+            .iter()
+            .filter(|item| item.undone_at.is_none() && periods::parse_date(&item.processed_at) == Some(date))
+            .cloned()
+            .collect();
+        AutoProcessedDigest {
+            date: date.format("%Y-%m-%d").to_string(),
+            total: items.iter().filter_map(|item| item.total).sum(),
+            items,
+        }
+    }
+}
+
+/// Accept a document without review if it clears every guardrail. A
+/// document in a lodged period is left alone. The caller saves both stores.
+pub fn process(
+    store: &mut DocumentStore,
+    log: &mut AutoProcessLog,
+    document_id: &str,
+    settings: &AppSettings,
+    rules: &[CategoryRule],
+) -> Option<AutoProcessedItem> {
+    let mut doc = store.get(document_id)?.clone();
+    let category = check(&doc, settings, rules).ok()?;
+    let previous_category = doc.category.replace(category.clone());
+    doc.status = DocumentStatus::Accepted;
+    let doc = match store.update(doc) {
+        Ok(doc) => doc,
+        Err(e) => {
+            log::warn!("Left {} for review: {}", document_id, e);
+            return None;
+        }
+    };
+
+    let item = AutoProcessedItem {
+        id: storage::generate_id("auto"),
+        document_id: doc.id,
+        vendor: doc.vendor,
+        date: doc.date,
+        total: doc.total,
+        category,
+        previous_category,
+        processed_at: storage::now_timestamp(),
+        undone_at: None,
+    };
+    log.data.items.push(item.clone());
+    Some(item)
+}
+
+/// Send an auto-processed document back for review with its old category.
+/// The caller saves both stores.
+pub fn undo(store: &mut DocumentStore, log: &mut AutoProcessLog, item_id: &str) -> Result<StoredDocument, String> {
+    let item = log
+        .data
+        .items
+        .iter_mut()
+        .find(|item| item.id == item_id)
+        .ok_or_else(|| format!("Auto-processed item {} not found", item_id))?;
+    if item.undone_at.is_some() {
+        return Err("This item has already been undone".to_string());
+    }
+    let mut doc = store
+        .get(&item.document_id)
+        .cloned()
+        .ok_or_else(|| "The document no longer exists".to_string())?;
+    if doc.status != DocumentStatus::Accepted || doc.category.as_deref() != Some(item.category.as_str()) {
+        return Err("The document has been changed since it was auto-processed".to_string());
+    }
+    doc.category = item.previous_category.clone();
+    doc.status = DocumentStatus::PendingReview;
+    let doc = store.update(doc)?;
+    item.undone_at = Some(storage::now_timestamp());
+    Ok(doc)
+}
+
+/// Run the default log under its lock
+pub fn with_log<R>(f: impl FnOnce(&mut AutoProcessLog) -> Result<R, String>) -> Result<R, String> {
+    let _guard = LOG_LOCK.lock().map_err(|_| "Auto-process log lock poisoned".to_string())?;
+    let mut log = AutoProcessLog::open_default()?;
+    f(&mut log)
+}
+
+/// Auto-process documents in the default store, when turned on
+pub fn run(document_ids: &[String]) -> Result<Vec<AutoProcessedItem>, String> {
+    let settings = settings::load_settings()?;
+    if !settings.straight_through.enabled {
+        return Ok(Vec::new());
+    }
+    let rules = category_rules::with_rules(|rules| Ok(rules.list()))?;
+    documents::with_store(|store| {
+        with_log(|log| {
+            let items: Vec<AutoProcessedItem> = document_ids
+                .iter()
+                .filter_map(|id| process(store, log, id, &settings, &rules))
+                .collect();
+            if !items.is_empty() {
+                store.save()?;
+                log.save()?;
+            }
+            Ok(items)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents::Extraction;
+    use crate::invoice::DocumentType;
+
+    #[test]
+    fn test_trusted_documents_are_accepted_and_can_be_undone() {
+        let dir = storage::test_dir();
+        let mut store = DocumentStore::open(&dir.join("documents.json")).unwrap();
+        let mut log = AutoProcessLog::open(&dir.join("auto_processed.json")).unwrap();
+        let text = "Acme Plumbing Pty Ltd\nABN: 51 824 753 556\nTAX INVOICE\nInvoice Number: INV-1001\n\
+            Invoice Date: 12/03/2024\nPayment terms: 14 days\n\
+            Subtotal 100.00\nGST 10.00\nTotal 110.00";
+        let invoice = InvoiceParser::new().unwrap().parse_from_text(text, DocumentType::Pdf).unwrap();
+        let mut doc = StoredDocument {
+            category: Some("Repairs".to_string()),
+            ..Default::default()
+        };
+        doc.apply_extraction(Extraction::Invoice(Box::new(invoice)));
+        let doc = store.update(doc).unwrap();
+
+        let mut settings = AppSettings::default();
+        settings.straight_through.min_confidence = 0.5;
+        assert_eq!(
+            check(&doc, &settings, &[]).unwrap_err(),
+            vec!["Straight-through processing is turned off", "Acme Plumbing Pty Ltd is not a trusted vendor"]
+        );
+        settings.straight_through.enabled = true;
+        settings.straight_through.trusted_vendors = vec!["acme plumbing pty ltd".to_string()];
+
+        let item = process(&mut store, &mut log, &doc.id, &settings, &[]).unwrap();
+        assert_eq!((item.category.as_str(), item.total), ("Repairs", Some(Money::from_cents(11000))));
+        assert_eq!(store.get(&doc.id).unwrap().status, DocumentStatus::Accepted);
+        assert!(process(&mut store, &mut log, &doc.id, &settings, &[]).is_none());
+
+        let today = chrono::Local::now().date_naive();
+        assert_eq!((log.digest(today).items.len(), log.digest(today).total), (1, Money::from_cents(11000)));
+        let restored = undo(&mut store, &mut log, &item.id).unwrap();
+        assert_eq!(restored.status, DocumentStatus::PendingReview);
+        assert!(log.digest(today).items.is_empty());
+        assert!(undo(&mut store, &mut log, &item.id).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+//! Financial Year Summary Module
+//!
+//! Expense totals for a financial year by category. Receipted documents and
+//! unsubstantiated cash journal entries are totalled separately so claims
+//! without a receipt are always visible as such. Businesses with trading
+//! stock also get the year's cost-of-goods-sold adjustment.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::cash::{CashExpense, MAX_UNSUBSTANTIATED_TOTAL};
+use crate::documents::StoredDocument;
+use crate::money::Money;
+use crate::periods;
+use crate::rollover::{self, OpeningPosition};
+use crate::stock::TradingStockSummary;
+
+const UNCATEGORISED: &str = "Uncategorised";
+
+/// Totals for one expense category
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CategorySummary {
+    pub category: String,
+    /// Backed by a receipt or invoice
+    pub substantiated_total: Money,
+    /// Cash journal entries without a receipt
+    pub unsubstantiated_total: Money,
+    pub total: Money,
+}
+
+/// Running category totals, kept in cents while summing
+#[derive(Default)]
+struct CategoryTotals {
+    substantiated: Money,
+    unsubstantiated: Money,
+}
+
+/// Financial year expense summary
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FinancialYearSummary {
+    pub financial_year: i32,
+    pub label: String,
+    pub document_count: usize,
+    pub substantiated_total: Money,
+    pub gst_total: Money,
+    /// Documents in another currency, left out of the totals until their
+    /// amounts are converted
+    #[serde(default)]
+    pub foreign_currency_count: usize,
+    pub cash_expense_count: usize,
+    pub unsubstantiated_total: Money,
+    /// Unsubstantiated allowance left for the year
+    pub unsubstantiated_remaining: Money,
+    /// Receipted and unsubstantiated expenses plus the stock adjustment
+    pub total_expenses: Money,
+    pub categories: Vec<CategorySummary>,
+    /// Unfinished items carried in from the previous year
+    pub opening_position: OpeningPosition,
+    /// Change in trading stock; `None` for businesses without stock
+    #[serde(default)]
+    pub trading_stock: Option<TradingStockSummary>,
+}
+
+/// Summarise documents and cash expenses dated within a financial year,
+/// opening with any items carried in from the previous year
+pub fn financial_year_summary(
+    documents: &[StoredDocument],
+    cash_expenses: &[CashExpense],
+    trading_stock: Option<TradingStockSummary>,
+    financial_year: i32,
+) -> FinancialYearSummary {
+    let in_year = |date: Option<&str>| {
+        date.and_then(periods::parse_date)
+            .is_some_and(|d| periods::financial_year_of(d) == financial_year)
+    };
+
+    let mut categories: BTreeMap<String, CategoryTotals> = BTreeMap::new();
+
+    let opening_position = rollover::opening_position(documents, financial_year);
+
+    let documents: Vec<&StoredDocument> = documents.iter().filter(|d| in_year(d.date.as_deref())).collect();
+    for doc in &documents {
+        category_entry(&mut categories, doc.category.as_ref()).substantiated += doc.aud_total().unwrap_or_default();
+    }
+
+    let cash: Vec<&CashExpense> = cash_expenses.iter().filter(|e| in_year(Some(&e.date))).collect();
+    for expense in &cash {
+        category_entry(&mut categories, expense.category.as_ref()).unsubstantiated += Money::from_dollars(expense.amount);
+    }
+
+    let categories: Vec<CategorySummary> = categories
+        .into_iter()
+        .map(|(category, c)| CategorySummary {
+            category,
+            substantiated_total: c.substantiated,
+            unsubstantiated_total: c.unsubstantiated,
+            total: c.substantiated + c.unsubstantiated,
+        })
+        .collect();
+
+    let substantiated_total: Money = documents.iter().filter_map(|d| d.aud_total()).sum();
+    let unsubstantiated_total: Money = cash.iter().map(|e| Money::from_dollars(e.amount)).sum();
+    let cogs_adjustment = Money::from_dollars(trading_stock.as_ref().map_or(0.0, |s| s.cogs_adjustment));
+    let allowance = Money::from_dollars(MAX_UNSUBSTANTIATED_TOTAL);
+
+    FinancialYearSummary {
+        financial_year,
+        label: periods::financial_year_label(financial_year),
+        document_count: documents.len(),
+        substantiated_total,
+        gst_total: documents.iter().filter_map(|d| d.aud_gst()).sum(),
+        foreign_currency_count: documents.iter().filter(|d| d.is_foreign_currency()).count(),
+        cash_expense_count: cash.len(),
+        unsubstantiated_total,
+        unsubstantiated_remaining: (allowance - unsubstantiated_total).max(Money::ZERO),
+        total_expenses: substantiated_total + unsubstantiated_total + cogs_adjustment,
+        categories,
+        opening_position,
+        trading_stock,
+    }
+}
+
+fn category_entry<'a>(
+    categories: &'a mut BTreeMap<String, CategoryTotals>,
+    name: Option<&String>,
+) -> &'a mut CategoryTotals {
+    let name = name.cloned().unwrap_or_else(|| UNCATEGORISED.to_string());
+    categories.entry(name).or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_separates_unsubstantiated_cash() {
+        let docs = vec![StoredDocument {
+            date: Some("2023-09-01".to_string()),
+            category: Some("Travel".to_string()),
+            total: Some(Money::from_cents(11000)),
+            gst: Some(Money::from_cents(1000)),
+            ..Default::default()
+        }];
+        let cash = vec![
+            CashExpense {
+                date: "2023-09-02".to_string(),
+                amount: 8.5,
+                category: Some("Travel".to_string()),
+                ..Default::default()
+            },
+            CashExpense {
+                date: "2024-07-02".to_string(),
+                amount: 5.0,
+                ..Default::default()
+            },
+        ];
+
+        let summary = financial_year_summary(&docs, &cash, None, 2024);
+        assert_eq!(summary.cash_expense_count, 1);
+        assert_eq!(summary.unsubstantiated_total, Money::from_cents(850));
+        assert_eq!(summary.total_expenses, Money::from_cents(11850));
+        assert!(summary.trading_stock.is_none());
+        assert_eq!(summary.categories.len(), 1);
+        assert_eq!(summary.categories[0].substantiated_total, Money::from_cents(11000));
+        assert_eq!(summary.categories[0].unsubstantiated_total, Money::from_cents(850));
+    }
+}
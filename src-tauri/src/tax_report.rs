@@ -5,9 +5,13 @@
 
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use tauri_plugin_fs::FsExt;
 
+use crate::bundle::{self, BundlePart};
+use crate::sandbox;
+
 /// Result of saving a tax report PDF
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TaxReportSaveResult {
@@ -35,12 +39,9 @@ pub async fn save_tax_report_pdf(
 ) -> Result<TaxReportSaveResult, String> {
     // In a desktop app, we would typically use a save dialog
     // For now, we'll save to a default location (Downloads or Documents)
+    sandbox::validate_file_name(&filename)?;
     
-    let home_dir = dirs::home_dir()
-        .ok_or_else(|| "Could not determine home directory".to_string())?;
-    
-    // Default to Documents/TallyTaxReports
-    let default_dir = home_dir.join("Documents").join("TallyTaxReports");
+    let default_dir = get_reports_directory()?;
     
     // Create directory if it doesn't exist
     if !default_dir.exists() {
@@ -79,18 +80,12 @@ pub async fn merge_pdfs(
     pdf_paths: Vec<String>,
     output_filename: String,
 ) -> Result<TaxReportSaveResult, String> {
-    // Note: Full PDF merging requires a PDF library like lopdf or printpdf
-    // This is a placeholder implementation that copies the first PDF
-    // In production, you would use a proper PDF merging library
-    
     if pdf_paths.is_empty() {
         return Err("No PDFs provided to merge".to_string());
     }
+    sandbox::validate_file_name(&output_filename)?;
     
-    let home_dir = dirs::home_dir()
-        .ok_or_else(|| "Could not determine home directory".to_string())?;
-    
-    let default_dir = home_dir.join("Documents").join("TallyTaxReports");
+    let default_dir = get_reports_directory()?;
     
     if !default_dir.exists() {
         fs::create_dir_all(&default_dir)
@@ -99,12 +94,23 @@ pub async fn merge_pdfs(
     
     let output_path = default_dir.join(&output_filename);
     
-    // For now, just copy the first PDF
-    // TODO: Implement proper PDF merging with lopdf
-    if let Some(first_path) = pdf_paths.first() {
-        fs::copy(first_path, &output_path)
-            .map_err(|e| format!("Failed to merge PDFs: {}", e))?;
+    // Bookmark each merged file by name
+    let mut parts = Vec::with_capacity(pdf_paths.len());
+    for pdf_path in &pdf_paths {
+        let path = Path::new(pdf_path);
+        let title = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("Document")
+            .to_string();
+        parts.push(BundlePart {
+            pdf: bundle::load_part(path)?,
+            outline: vec![title],
+        });
     }
+    bundle::merge_parts(parts)?
+        .save(&output_path)
+        .map_err(|e| format!("Failed to merge PDFs: {}", e))?;
     
     let file_size = fs::metadata(&output_path)
         .map(|m| m.len())
@@ -118,8 +124,20 @@ pub async fn merge_pdfs(
     })
 }
 
+/// Reports directory chosen by the platform, set once at startup
+static REPORTS_DIRECTORY: OnceLock<PathBuf> = OnceLock::new();
+
+/// Save reports in `dir` instead of Documents/TallyTaxReports. Used on
+/// mobile, where the share sheet can only reach files in app storage.
+pub fn set_reports_directory(dir: PathBuf) {
+    let _ = REPORTS_DIRECTORY.set(dir);
+}
+
 /// Get the default reports directory
-pub fn get_reports_directory() -> Result<std::path::PathBuf, String> {
+pub fn get_reports_directory() -> Result<PathBuf, String> {
+    if let Some(dir) = REPORTS_DIRECTORY.get() {
+        return Ok(dir.clone());
+    }
     let home_dir = dirs::home_dir()
         .ok_or_else(|| "Could not determine home directory".to_string())?;
     
@@ -176,8 +194,9 @@ pub struct ReportFileInfo {
     pub modified_time: Option<u64>,
 }
 
-/// Delete a saved report
+/// Delete a saved report. Only files in the reports directory can be deleted.
 pub fn delete_report(file_path: String) -> Result<(), String> {
+    let file_path = sandbox::check_path(&file_path, &[get_reports_directory()?], |_| false)?;
     fs::remove_file(&file_path)
         .map_err(|e| format!("Failed to delete report: {}", e))
 }
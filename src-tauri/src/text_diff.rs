@@ -0,0 +1,156 @@
+//! Text Diff Module
+//!
+//! Line-by-line diff of OCR text, so the UI can show what changed between
+//! two documents or between a document's extraction versions (after an
+//! enhancement retry or a re-scan).
+
+use serde::{Deserialize, Serialize};
+
+use crate::documents::StoredDocument;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffKind {
+    Unchanged,
+    Added,
+    Removed,
+}
+
+/// One line of the diff
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DiffLine {
+    pub kind: DiffKind,
+    pub text: String,
+    /// 1-based line number in the left text; `None` for added lines
+    pub left_line: Option<usize>,
+    /// 1-based line number in the right text; `None` for removed lines
+    pub right_line: Option<usize>,
+}
+
+/// Diff between two texts, in display order
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TextDiff {
+    pub lines: Vec<DiffLine>,
+    pub added: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+/// One side of a diff: a document's current OCR text, or an earlier version
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TextSource {
+    pub document_id: String,
+    /// Index into the document's text versions; `None` for the current text
+    pub version: Option<usize>,
+}
+
+/// The text a source refers to
+pub fn source_text(doc: &StoredDocument, version: Option<usize>) -> Result<String, String> {
+    let text = match version {
+        Some(index) => doc.text_versions.get(index).map(|v| v.text.as_str()).ok_or_else(|| {
+            format!("Document {} has {} earlier text versions", doc.id, doc.text_versions.len())
+        })?,
+        None => doc
+            .ocr_text()
+            .ok_or_else(|| format!("Document {} has no extracted text", doc.id))?,
+    };
+    Ok(text.to_string())
+}
+
+/// Diff two texts by line. Trailing whitespace is ignored when comparing,
+/// since OCR output varies in it between runs.
+pub fn diff_lines(left: &str, right: &str) -> TextDiff {
+    let left: Vec<&str> = left.lines().map(str::trim_end).collect();
+    let right: Vec<&str> = right.lines().map(str::trim_end).collect();
+
+    // Longest common subsequence table over the suffixes of both texts
+    let (n, m) = (left.len(), right.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if left[i] == right[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let line = |kind, text: &str, left_line, right_line| DiffLine {
+        kind,
+        text: text.to_string(),
+        left_line,
+        right_line,
+    };
+    let mut lines = Vec::with_capacity(n.max(m));
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && left[i] == right[j] {
+            lines.push(line(DiffKind::Unchanged, left[i], Some(i + 1), Some(j + 1)));
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            lines.push(line(DiffKind::Added, right[j], None, Some(j + 1)));
+            j += 1;
+        } else {
+            lines.push(line(DiffKind::Removed, left[i], Some(i + 1), None));
+            i += 1;
+        }
+    }
+
+    let count = |kind| lines.iter().filter(|l| l.kind == kind).count();
+    TextDiff {
+        added: count(DiffKind::Added),
+        removed: count(DiffKind::Removed),
+        unchanged: count(DiffKind::Unchanged),
+        lines,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_marks_changed_lines() {
+        let before = "WOOLWORTHS METRO\nMilk 2L 3.6O\nTOTAL 7.6O  \nEFTPOS";
+        let after = "WOOLWORTHS METRO\nMilk 2L 3.60\nBread 4.00\nTOTAL 7.60\nEFTPOS";
+        let diff = diff_lines(before, after);
+
+        assert_eq!((diff.added, diff.removed, diff.unchanged), (3, 2, 2));
+        let kinds: Vec<DiffKind> = diff.lines.iter().map(|l| l.kind).collect();
+        assert_eq!(kinds.first(), Some(&DiffKind::Unchanged));
+        assert_eq!(kinds.last(), Some(&DiffKind::Unchanged));
+        let removed = diff.lines.iter().find(|l| l.kind == DiffKind::Removed).unwrap();
+        assert_eq!((removed.text.as_str(), removed.left_line), ("Milk 2L 3.6O", Some(2)));
+        let last = diff.lines.last().unwrap();
+        assert_eq!((last.left_line, last.right_line), (Some(4), Some(5)));
+
+        assert_eq!(diff_lines("same\n", "same").unchanged, 1);
+    }
+
+    #[test]
+    fn test_extraction_versions_are_diffable() {
+        use crate::documents::{Extraction, MAX_TEXT_VERSIONS};
+        use crate::receipt_parser::ReceiptParser;
+
+        let extraction = |text: &str| Extraction::Receipt(Box::new(ReceiptParser::shared().parse_from_text(text, 0.9)));
+        let mut doc = StoredDocument::default();
+        assert!(source_text(&doc, None).is_err());
+
+        doc.apply_extraction(extraction("Coles\nTOTAL 1O.00"));
+        doc.apply_extraction(extraction("Coles\nTOTAL 1O.00"));
+        assert!(doc.text_versions.is_empty());
+        doc.apply_extraction(extraction("Coles\nTOTAL 10.00"));
+        assert_eq!(doc.text_versions.len(), 1);
+
+        let diff = diff_lines(&source_text(&doc, Some(0)).unwrap(), &source_text(&doc, None).unwrap());
+        assert_eq!((diff.added, diff.removed), (1, 1));
+        assert!(source_text(&doc, Some(1)).is_err());
+
+        for total in 0..10 {
+            doc.apply_extraction(extraction(&format!("Coles\nTOTAL {}.00", total)));
+        }
+        assert_eq!(doc.text_versions.len(), MAX_TEXT_VERSIONS);
+    }
+}
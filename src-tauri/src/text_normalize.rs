@@ -0,0 +1,159 @@
+//! Text Normalization Module
+//!
+//! Cleans up text extracted from PDFs before it is parsed. PDF text layers
+//! often contain:
+//! - Typographic ligatures (ﬁ, ﬂ, ﬃ) and invisible characters that break
+//!   keyword matching
+//! - Words hyphenated across line breaks
+//! - Two-column layouts read row by row, so each line interleaves the left
+//!   and right columns
+//!
+//! Each problem has its own pass; `normalize` runs them all.
+
+/// Minimum run of spaces treated as a gap between columns
+const COLUMN_GAP: usize = 3;
+
+/// How far apart (in characters) rows' right columns may start and still
+/// count as the same column
+const COLUMN_TOLERANCE: usize = 3;
+
+/// Run every normalization pass
+pub fn normalize(text: &str) -> String {
+    reflow_columns(&dehyphenate(&expand_ligatures(text)))
+}
+
+/// Replace ligatures with their letters and drop invisible characters
+pub fn expand_ligatures(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\u{FB00}' => out.push_str("ff"),
+            '\u{FB01}' => out.push_str("fi"),
+            '\u{FB02}' => out.push_str("fl"),
+            '\u{FB03}' => out.push_str("ffi"),
+            '\u{FB04}' => out.push_str("ffl"),
+            '\u{FB05}' | '\u{FB06}' => out.push_str("st"),
+            '\u{00A0}' | '\u{2007}' | '\u{202F}' => out.push(' '),
+            '\u{2010}' | '\u{2011}' | '\u{2212}' => out.push('-'),
+            '\u{00AD}' | '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{FEFF}' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Join words split with a hyphen at the end of a line. Only lowercase
+/// continuations are joined, so codes like `INV-` / `2024` stay apart.
+pub fn dehyphenate(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut out: Vec<String> = Vec::with_capacity(lines.len());
+    let mut carry = String::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        let mut line = if carry.is_empty() {
+            line.to_string()
+        } else {
+            format!("{}{}", std::mem::take(&mut carry), line.trim_start())
+        };
+
+        let trimmed_len = line.trim_end().len();
+        let splits_word = line[..trimmed_len].ends_with('-')
+            && line[..trimmed_len - 1].chars().last().is_some_and(|c| c.is_alphabetic())
+            && lines
+                .get(i + 1)
+                .and_then(|next| next.trim_start().chars().next())
+                .is_some_and(|c| c.is_lowercase());
+        if splits_word {
+            // Carry the word stem onto the next line
+            line.truncate(trimmed_len - 1);
+            let stem_start = line.rfind(' ').map_or(0, |p| p + 1);
+            carry = line[stem_start..].to_string();
+            line.truncate(stem_start);
+            let line = line.trim_end();
+            if !line.is_empty() {
+                out.push(line.to_string());
+            }
+            continue;
+        }
+        out.push(line);
+    }
+    if !carry.is_empty() {
+        out.push(carry);
+    }
+    out.join("\n")
+}
+
+/// Where a line splits into two columns: the character offset the right
+/// column starts at, and the two halves
+fn column_split(line: &str) -> Option<(usize, &str, &str)> {
+    let gap = " ".repeat(COLUMN_GAP);
+    let content_start = line.len() - line.trim_start().len();
+    let gap_start = content_start + line[content_start..].find(&gap)?;
+    let left = line[..gap_start].trim();
+    let right_start = gap_start + (line.len() - gap_start - line[gap_start..].trim_start().len());
+    let right = line[right_start..].trim();
+
+    // Label and value pairs ("Total:    $110.00") are not columns, nor are
+    // table rows with more than two cells
+    let is_column = !left.is_empty()
+        && !left.ends_with(':')
+        && !right.contains(&gap)
+        && right.chars().next().is_some_and(|c| c.is_alphabetic());
+    is_column.then(|| (line[..right_start].chars().count(), left, right))
+}
+
+/// Put two-column blocks back in reading order: the whole left column,
+/// then the whole right column. A block is two or more consecutive lines
+/// whose right columns start at the same position.
+pub fn reflow_columns(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut out: Vec<String> = Vec::with_capacity(lines.len());
+    let mut i = 0;
+
+    while i < lines.len() {
+        let Some((column, _, _)) = column_split(lines[i]) else {
+            out.push(lines[i].to_string());
+            i += 1;
+            continue;
+        };
+        let block: Vec<(&str, &str)> = lines[i..]
+            .iter()
+            .map_while(|line| {
+                column_split(line)
+                    .filter(|(start, _, _)| start.abs_diff(column) <= COLUMN_TOLERANCE)
+                    .map(|(_, left, right)| (left, right))
+            })
+            .collect();
+        if block.len() < 2 {
+            out.push(lines[i].to_string());
+            i += 1;
+            continue;
+        }
+        out.extend(block.iter().map(|(left, _)| left.to_string()));
+        out.extend(block.iter().map(|(_, right)| right.to_string()));
+        i += block.len();
+    }
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalization_passes() {
+        assert_eq!(expand_ligatures("Oﬃce supplies\u{00AD} ﬁle"), "Office supplies file");
+        assert_eq!(
+            dehyphenate("Stationery and sta-\ntionery refills\nINV-\n2024"),
+            "Stationery and\nstationery refills\nINV-\n2024"
+        );
+        assert_eq!(
+            reflow_columns("Invoice Number      Bill To\nINV-001             Acme Pty Ltd\nTotal:     $110.00"),
+            "Invoice Number\nINV-001\nBill To\nAcme Pty Ltd\nTotal:     $110.00"
+        );
+        // A single row with a gap is left alone
+        assert_eq!(reflow_columns("Payment terms      Net 30"), "Payment terms      Net 30");
+        let table = "Description    GST    Amount\nCallout fee    GST    90.00\nLabour    GST    120.00";
+        assert_eq!(reflow_columns(table), table);
+    }
+}
@@ -0,0 +1,395 @@
+//! Text Shaping Module
+//!
+//! Prepares text for the report builder, which places glyphs left to right
+//! one character at a time. Arabic letters are swapped for the contextual
+//! forms (initial, medial, final, isolated) that join them up, and mixed
+//! right-to-left and left-to-right text is put in display order using a
+//! simplified form of the Unicode bidirectional algorithm that covers
+//! single lines without explicit embedding controls.
+
+/// How a letter connects to its neighbours
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Joining {
+    /// Joins on both sides
+    Dual,
+    /// Joins only to the letter before it
+    Right,
+    /// Joins on both sides without changing shape (tatweel)
+    Causing,
+    /// Marks that letters join across
+    Transparent,
+    None,
+}
+
+/// Arabic letters with contextual forms: the letter, its isolated form and
+/// whether it joins on both sides. Dual-joining letters' final, initial and
+/// medial forms follow the isolated form; right-joining letters have only a
+/// final form after it.
+const ARABIC_FORMS: &[(char, u32, bool)] = &[
+    ('\u{0621}', 0xFE80, false),
+    ('\u{0622}', 0xFE81, false),
+    ('\u{0623}', 0xFE83, false),
+    ('\u{0624}', 0xFE85, false),
+    ('\u{0625}', 0xFE87, false),
+    ('\u{0626}', 0xFE89, true),
+    ('\u{0627}', 0xFE8D, false),
+    ('\u{0628}', 0xFE8F, true),
+    ('\u{0629}', 0xFE93, false),
+    ('\u{062A}', 0xFE95, true),
+    ('\u{062B}', 0xFE99, true),
+    ('\u{062C}', 0xFE9D, true),
+    ('\u{062D}', 0xFEA1, true),
+    ('\u{062E}', 0xFEA5, true),
+    ('\u{062F}', 0xFEA9, false),
+    ('\u{0630}', 0xFEAB, false),
+    ('\u{0631}', 0xFEAD, false),
+    ('\u{0632}', 0xFEAF, false),
+    ('\u{0633}', 0xFEB1, true),
+    ('\u{0634}', 0xFEB5, true),
+    ('\u{0635}', 0xFEB9, true),
+    ('\u{0636}', 0xFEBD, true),
+    ('\u{0637}', 0xFEC1, true),
+    ('\u{0638}', 0xFEC5, true),
+    ('\u{0639}', 0xFEC9, true),
+    ('\u{063A}', 0xFECD, true),
+    ('\u{0641}', 0xFED1, true),
+    ('\u{0642}', 0xFED5, true),
+    ('\u{0643}', 0xFED9, true),
+    ('\u{0644}', 0xFEDD, true),
+    ('\u{0645}', 0xFEE1, true),
+    ('\u{0646}', 0xFEE5, true),
+    ('\u{0647}', 0xFEE9, true),
+    ('\u{0648}', 0xFEED, false),
+    ('\u{0649}', 0xFEEF, false),
+    ('\u{064A}', 0xFEF1, true),
+    // Persian letters
+    ('\u{067E}', 0xFB56, true),
+    ('\u{0686}', 0xFB7A, true),
+    ('\u{0698}', 0xFB8A, false),
+    ('\u{06A9}', 0xFB8E, true),
+    ('\u{06AF}', 0xFB92, true),
+    ('\u{06CC}', 0xFBFC, true),
+];
+
+/// Lam followed by these alefs is written as a single ligature, whose
+/// isolated form is given (the final form follows it)
+const LAM_ALEF: &[(char, u32)] = &[
+    ('\u{0622}', 0xFEF5),
+    ('\u{0623}', 0xFEF7),
+    ('\u{0625}', 0xFEF9),
+    ('\u{0627}', 0xFEFB),
+];
+
+const LAM: char = '\u{0644}';
+const TATWEEL: char = '\u{0640}';
+
+fn is_mark(c: char) -> bool {
+    matches!(c, '\u{0591}'..='\u{05C7}' | '\u{064B}'..='\u{065F}' | '\u{0670}' | '\u{06D6}'..='\u{06ED}')
+}
+
+fn joining(c: char) -> Joining {
+    if c == TATWEEL {
+        return Joining::Causing;
+    }
+    if is_mark(c) {
+        return Joining::Transparent;
+    }
+    match ARABIC_FORMS.iter().find(|(letter, _, _)| *letter == c) {
+        Some((_, _, true)) => Joining::Dual,
+        Some((_, _, false)) if c == '\u{0621}' => Joining::None,
+        Some(_) => Joining::Right,
+        None => Joining::None,
+    }
+}
+
+/// Number of presentation forms a letter has
+fn form_count(letter: char, dual: bool) -> u32 {
+    match (joining(letter), dual) {
+        (Joining::None, _) => 1,
+        (_, true) => 4,
+        _ => 2,
+    }
+}
+
+fn joins_forward(c: char) -> bool {
+    matches!(joining(c), Joining::Dual | Joining::Causing)
+}
+
+fn joins_backward(c: char) -> bool {
+    matches!(joining(c), Joining::Dual | Joining::Right | Joining::Causing)
+}
+
+/// Replace Arabic letters with their joined presentation forms. Text is
+/// taken and returned in logical (reading) order.
+pub fn shape_arabic(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let neighbour = |from: usize, step: isize| {
+        let mut i = from as isize + step;
+        while i >= 0 && (i as usize) < chars.len() {
+            if joining(chars[i as usize]) != Joining::Transparent {
+                return Some((i as usize, chars[i as usize]));
+            }
+            i += step;
+        }
+        None
+    };
+
+    let mut shaped = String::with_capacity(text.len());
+    let mut skip = None;
+    for (i, &c) in chars.iter().enumerate() {
+        if skip == Some(i) {
+            continue;
+        }
+        let joins_previous = joins_backward(c) && neighbour(i, -1).is_some_and(|(_, p)| joins_forward(p));
+        let next = neighbour(i, 1);
+
+        if c == LAM {
+            let ligature = next.and_then(|(j, n)| LAM_ALEF.iter().find(|(alef, _)| *alef == n).map(|l| (j, l.1)));
+            if let Some((j, isolated)) = ligature {
+                shaped.extend(char::from_u32(isolated + u32::from(joins_previous)));
+                skip = Some(j);
+                continue;
+            }
+        }
+
+        let Some(&(_, isolated, dual)) = ARABIC_FORMS.iter().find(|(letter, _, _)| *letter == c) else {
+            shaped.push(c);
+            continue;
+        };
+        let joins_next = dual && next.is_some_and(|(_, n)| joins_backward(n));
+        let offset = match (joins_previous, joins_next) {
+            (false, false) => 0,
+            (true, false) => 1,
+            (false, true) => 2,
+            (true, true) => 3,
+        };
+        shaped.extend(char::from_u32(isolated + offset.min(form_count(c, dual) - 1)));
+    }
+    shaped
+}
+
+/// The letters a presentation form was made from, for text extraction
+pub fn unshape(c: char) -> String {
+    let code = c as u32;
+    if let Some((alef, _)) = LAM_ALEF.iter().find(|(_, isolated)| code == *isolated || code == isolated + 1) {
+        return [LAM, *alef].iter().collect();
+    }
+    ARABIC_FORMS
+        .iter()
+        .find(|(letter, isolated, dual)| code >= *isolated && code < isolated + form_count(*letter, *dual))
+        .map_or_else(|| c.to_string(), |(letter, _, _)| letter.to_string())
+}
+
+/// Bidirectional character class, reduced to what single lines need
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BidiClass {
+    Left,
+    Right,
+    Number,
+    /// Separators inside numbers: `1,000.50`
+    NumberSeparator,
+    /// Currency and percent signs next to numbers
+    NumberTerminator,
+    Neutral,
+}
+
+fn is_rtl(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0590}'..='\u{08FF}' | '\u{FB1D}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}' | '\u{10800}'..='\u{10FFF}'
+    ) && !c.is_numeric()
+}
+
+fn bidi_class(c: char) -> BidiClass {
+    match c {
+        '0'..='9' | '\u{0660}'..='\u{0669}' | '\u{06F0}'..='\u{06F9}' => BidiClass::Number,
+        ',' | '.' | ':' | '/' => BidiClass::NumberSeparator,
+        '$' | '%' | '#' | '+' | '-' | '\u{00A2}'..='\u{00A5}' | '\u{20A0}'..='\u{20CF}' => BidiClass::NumberTerminator,
+        c if is_rtl(c) => BidiClass::Right,
+        c if c.is_alphabetic() => BidiClass::Left,
+        _ => BidiClass::Neutral,
+    }
+}
+
+fn mirror(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        '«' => '»',
+        '»' => '«',
+        c => c,
+    }
+}
+
+/// Whether any character in the text is written right to left
+pub fn has_rtl(text: &str) -> bool {
+    text.chars().any(is_rtl)
+}
+
+/// Shape and reorder a line of text into the left-to-right order its
+/// characters are drawn in. Text without right-to-left characters is
+/// returned unchanged.
+pub fn visual_order(text: &str) -> String {
+    if !has_rtl(text) {
+        return text.to_string();
+    }
+    let shaped = shape_arabic(text);
+
+    // Marks stay with the letter before them
+    let mut clusters: Vec<String> = Vec::new();
+    for c in shaped.chars() {
+        match clusters.last_mut() {
+            Some(cluster) if is_mark(c) => cluster.push(c),
+            _ => clusters.push(c.to_string()),
+        }
+    }
+    let mut classes: Vec<BidiClass> = clusters
+        .iter()
+        .map(|cluster| bidi_class(cluster.chars().next().unwrap_or(' ')))
+        .collect();
+
+    let base_rtl = classes
+        .iter()
+        .find(|c| matches!(c, BidiClass::Left | BidiClass::Right))
+        .is_some_and(|c| *c == BidiClass::Right);
+    let base_level: u8 = u8::from(base_rtl);
+
+    // A separator between two digits, and terminators touching digits,
+    // belong to the number
+    for i in 0..classes.len() {
+        let between_digits = i > 0
+            && i + 1 < classes.len()
+            && classes[i - 1] == BidiClass::Number
+            && classes[i + 1] == BidiClass::Number;
+        if classes[i] == BidiClass::NumberSeparator && between_digits {
+            classes[i] = BidiClass::Number;
+        }
+    }
+    for pass in [false, true] {
+        let indices: Vec<usize> = if pass {
+            (0..classes.len()).rev().collect()
+        } else {
+            (0..classes.len()).collect()
+        };
+        let mut after_number = false;
+        for i in indices {
+            match classes[i] {
+                BidiClass::Number => after_number = true,
+                BidiClass::NumberTerminator if after_number => classes[i] = BidiClass::Number,
+                _ => after_number = false,
+            }
+        }
+    }
+
+    // Numbers read left to right but sit in the direction of the text around them
+    let mut previous_strong = if base_rtl { BidiClass::Right } else { BidiClass::Left };
+    let mut numbers_rtl = vec![false; classes.len()];
+    for (i, class) in classes.iter().enumerate() {
+        match class {
+            BidiClass::Left | BidiClass::Right => previous_strong = *class,
+            BidiClass::Number => numbers_rtl[i] = previous_strong == BidiClass::Right,
+            _ => {}
+        }
+    }
+
+    // Resolve each cluster to a direction, with neutrals taking the
+    // direction on both sides of them or else the line's
+    let direction = |i: usize| -> Option<bool> {
+        match classes[i] {
+            BidiClass::Left => Some(false),
+            BidiClass::Right => Some(true),
+            BidiClass::Number => Some(numbers_rtl[i]),
+            _ => None,
+        }
+    };
+    let mut rtl: Vec<Option<bool>> = (0..classes.len()).map(direction).collect();
+    let mut i = 0;
+    while i < rtl.len() {
+        if rtl[i].is_some() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < rtl.len() && rtl[i].is_none() {
+            i += 1;
+        }
+        let before = if start == 0 { base_rtl } else { rtl[start - 1].unwrap_or(base_rtl) };
+        let after = if i == rtl.len() { base_rtl } else { rtl[i].unwrap_or(base_rtl) };
+        let resolved = if before == after { before } else { base_rtl };
+        rtl[start..i].iter_mut().for_each(|d| *d = Some(resolved));
+    }
+
+    let levels: Vec<u8> = (0..clusters.len())
+        .map(|i| {
+            let is_rtl = rtl[i].unwrap_or(base_rtl);
+            match (base_level, classes[i] == BidiClass::Number, is_rtl) {
+                (_, true, true) => 2,
+                (0, _, false) => 0,
+                (0, _, true) => 1,
+                (_, _, false) => 2,
+                (_, _, true) => 1,
+            }
+        })
+        .collect();
+
+    let mut order: Vec<usize> = (0..clusters.len()).collect();
+    let highest = levels.iter().copied().max().unwrap_or(0);
+    for level in (1..=highest).rev() {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] < level {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < order.len() && levels[order[i]] >= level {
+                i += 1;
+            }
+            order[start..i].reverse();
+        }
+    }
+
+    order
+        .into_iter()
+        .flat_map(|i| {
+            let odd = levels[i] % 2 == 1;
+            let mut chars: Vec<char> = clusters[i].chars().collect();
+            if odd {
+                chars[0] = mirror(chars[0]);
+            }
+            chars
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arabic_is_joined_and_mixed_text_reordered() {
+        // Meem, teh, jeem, reh: initial, medial, medial, final
+        assert_eq!(shape_arabic("متجر"), "\u{FEE3}\u{FE98}\u{FEA0}\u{FEAE}");
+        // Lam-alef becomes one ligature; a lone hamza keeps its only form
+        assert_eq!(shape_arabic("لا ء"), "\u{FEFB} \u{FE80}");
+        assert_eq!(unshape('\u{FEFC}'), "لا");
+        assert_eq!(unshape('\u{FE98}'), "ت");
+        assert_eq!(unshape('\u{FE81}'), "آ");
+
+        // Latin and CJK text is untouched
+        assert_eq!(visual_order("北京饭店 Pty Ltd"), "北京饭店 Pty Ltd");
+
+        // Hebrew reads right to left, while the amount keeps its digit order
+        assert_eq!(visual_order("אבג 12.50"), "12.50 גבא");
+        // A Latin line with an Arabic name in it keeps the Latin order
+        assert_eq!(visual_order("Cafe بيت (Sydney)"), "Cafe \u{FE96}\u{FEF4}\u{FE91} (Sydney)");
+        // Brackets are mirrored inside right-to-left text
+        assert_eq!(visual_order("א(ב)"), "(ב)א");
+    }
+}
@@ -0,0 +1,295 @@
+//! Thumbnail Module
+//!
+//! Small JPEG previews of document originals for the list views. Thumbnails
+//! are made on a background worker so a large import doesn't block the UI:
+//! the worker takes the queue in small batches and pauses between them to
+//! leave the CPU to the foreground. Documents the list view is showing jump
+//! to the front of the queue. Each finished thumbnail is announced through
+//! `THUMBNAIL_EVENT`.
+
+use image::{DynamicImage, ImageFormat};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+use crate::documents::{self, DocumentFilter, StoredDocument};
+use crate::imaging::{self, DecodeLimits};
+use crate::pdf_pages;
+use crate::storage;
+
+/// Event emitted as each thumbnail is written or fails
+pub const THUMBNAIL_EVENT: &str = "thumbnail://ready";
+
+/// Longest edge of a thumbnail, in pixels
+pub const THUMBNAIL_EDGE: u32 = 256;
+
+/// Thumbnails made before the worker pauses
+const BATCH_SIZE: usize = 8;
+
+/// Pause between batches so a large import doesn't hog the CPU
+const BATCH_PAUSE: Duration = Duration::from_millis(150);
+
+/// A document waiting for its thumbnail
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThumbnailRequest {
+    pub document_id: String,
+    pub source_path: PathBuf,
+}
+
+/// Payload of `THUMBNAIL_EVENT`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ThumbnailEvent {
+    pub document_id: String,
+    pub thumbnail_path: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Thumbnails already on disk and how many more were queued
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ThumbnailQueueResult {
+    pub ready: Vec<ThumbnailEvent>,
+    pub queued: usize,
+}
+
+type Notify = Arc<dyn Fn(ThumbnailEvent) + Send + Sync>;
+
+/// Pending requests, in the order they will be made
+struct ThumbnailQueue {
+    pending: VecDeque<ThumbnailRequest>,
+    running: bool,
+    notify: Option<Notify>,
+}
+
+static QUEUE: Mutex<ThumbnailQueue> = Mutex::new(ThumbnailQueue::new());
+
+impl ThumbnailQueue {
+    const fn new() -> Self {
+        Self {
+            pending: VecDeque::new(),
+            running: false,
+            notify: None,
+        }
+    }
+
+    /// Add requests at the back of the queue, or at the front in the given
+    /// order when prioritised. Requests already waiting aren't repeated;
+    /// prioritising one moves it forward.
+    fn push(&mut self, requests: Vec<ThumbnailRequest>, prioritise: bool) {
+        if prioritise {
+            self.pending.retain(|p| !requests.iter().any(|r| r.document_id == p.document_id));
+            for request in requests.into_iter().rev() {
+                self.pending.push_front(request);
+            }
+        } else {
+            for request in requests {
+                if !self.pending.iter().any(|p| p.document_id == request.document_id) {
+                    self.pending.push_back(request);
+                }
+            }
+        }
+    }
+
+    fn next_batch(&mut self) -> Vec<ThumbnailRequest> {
+        let count = BATCH_SIZE.min(self.pending.len());
+        self.pending.drain(..count).collect()
+    }
+}
+
+fn with_queue<R>(f: impl FnOnce(&mut ThumbnailQueue) -> R) -> R {
+    let mut queue = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut queue)
+}
+
+/// Directory thumbnails are cached in
+pub fn thumbnails_directory() -> Result<PathBuf, String> {
+    Ok(storage::get_cache_directory()?.join("thumbnails"))
+}
+
+pub fn thumbnail_path(dir: &Path, document_id: &str) -> PathBuf {
+    dir.join(format!("{}.jpg", document_id))
+}
+
+/// A thumbnail on disk that is newer than its original
+pub fn existing_thumbnail(dir: &Path, request: &ThumbnailRequest) -> Option<PathBuf> {
+    let path = thumbnail_path(dir, &request.document_id);
+    let modified = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+    let (thumbnail, source) = (modified(&path)?, modified(&request.source_path)?);
+    (thumbnail >= source).then_some(path)
+}
+
+/// Scale the original (or the first page of a PDF) down and save it as a JPEG
+pub fn render_thumbnail(source: &Path, dest: &Path) -> Result<(), String> {
+    let limits = DecodeLimits::default();
+    let is_pdf = source.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("pdf"));
+    let image = if is_pdf {
+        pdf_pages::render_pages(source, &limits)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("No pages to preview in {}", source.display()))?
+    } else {
+        imaging::load_image(source, &limits)?
+    };
+
+    if let Some(dir) = dest.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create thumbnails directory: {}", e))?;
+    }
+    // JPEG has no alpha channel
+    DynamicImage::ImageRgb8(image.thumbnail(THUMBNAIL_EDGE, THUMBNAIL_EDGE).to_rgb8())
+        .save_with_format(dest, ImageFormat::Jpeg)
+        .map_err(|e| format!("Failed to save thumbnail: {}", e))
+}
+
+/// Make one thumbnail, reusing an up-to-date one
+pub fn make_thumbnail(dir: &Path, request: &ThumbnailRequest) -> ThumbnailEvent {
+    let result = match existing_thumbnail(dir, request) {
+        Some(path) => Ok(path),
+        None => {
+            let path = thumbnail_path(dir, &request.document_id);
+            render_thumbnail(&request.source_path, &path).map(|_| path)
+        }
+    };
+    ThumbnailEvent {
+        document_id: request.document_id.clone(),
+        thumbnail_path: result.as_ref().ok().map(|p| p.to_string_lossy().to_string()),
+        error: result.err(),
+    }
+}
+
+fn run_worker(dir: PathBuf) {
+    loop {
+        let (batch, notify) = with_queue(|queue| {
+            let batch = queue.next_batch();
+            if batch.is_empty() {
+                queue.running = false;
+            }
+            (batch, queue.notify.clone())
+        });
+        if batch.is_empty() {
+            return;
+        }
+        for request in &batch {
+            let event = make_thumbnail(&dir, request);
+            if let Some(error) = &event.error {
+                log::warn!("Thumbnail for {} failed: {}", request.document_id, error);
+            }
+            if let Some(notify) = &notify {
+                notify(event);
+            }
+        }
+        thread::sleep(BATCH_PAUSE);
+    }
+}
+
+/// Queue thumbnails for the background worker, starting it if it is idle
+pub fn enqueue(requests: Vec<ThumbnailRequest>, prioritise: bool, notify: Notify) -> Result<(), String> {
+    let dir = thumbnails_directory()?;
+    let start = with_queue(|queue| {
+        queue.push(requests, prioritise);
+        queue.notify = Some(notify);
+        !std::mem::replace(&mut queue.running, true)
+    });
+    if start {
+        thread::spawn(move || run_worker(dir));
+    }
+    Ok(())
+}
+
+/// Documents with an original file to preview
+pub fn requests_for(documents: &[StoredDocument]) -> Vec<ThumbnailRequest> {
+    documents
+        .iter()
+        .filter_map(|doc| {
+            Some(ThumbnailRequest {
+                document_id: doc.id.clone(),
+                source_path: PathBuf::from(doc.source_path.as_deref()?),
+            })
+        })
+        .collect()
+}
+
+/// Return the thumbnails already made for some documents (or all of them)
+/// and queue the rest. Prioritised requests go ahead of anything queued,
+/// for the documents currently on screen.
+pub fn queue_documents(
+    app: &AppHandle,
+    document_ids: Option<Vec<String>>,
+    prioritise: bool,
+) -> Result<ThumbnailQueueResult, String> {
+    let filter = DocumentFilter {
+        ids: document_ids,
+        ..Default::default()
+    };
+    let documents = documents::with_store(|store| Ok(store.list(&filter)))?;
+    let dir = thumbnails_directory()?;
+
+    let mut ready = Vec::new();
+    let mut missing = Vec::new();
+    for request in requests_for(&documents) {
+        match existing_thumbnail(&dir, &request) {
+            Some(path) => ready.push(ThumbnailEvent {
+                document_id: request.document_id,
+                thumbnail_path: Some(path.to_string_lossy().to_string()),
+                error: None,
+            }),
+            None => missing.push(request),
+        }
+    }
+
+    let queued = missing.len();
+    if queued > 0 {
+        let app = app.clone();
+        let notify: Notify = Arc::new(move |event| {
+            if let Err(e) = app.emit(THUMBNAIL_EVENT, event) {
+                log::warn!("Failed to emit thumbnail event: {}", e);
+            }
+        });
+        enqueue(missing, prioritise, notify)?;
+    }
+    Ok(ThumbnailQueueResult { ready, queued })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(id: &str) -> ThumbnailRequest {
+        ThumbnailRequest {
+            document_id: id.to_string(),
+            source_path: PathBuf::from(format!("{}.png", id)),
+        }
+    }
+
+    #[test]
+    fn test_visible_documents_jump_the_queue() {
+        let mut queue = ThumbnailQueue::new();
+        queue.push((0..20).map(|i| request(&format!("doc-{}", i))).collect(), false);
+        queue.push(vec![request("doc-3")], false);
+        assert_eq!(queue.pending.len(), 20);
+
+        queue.push(vec![request("doc-15"), request("doc-16")], true);
+        let batch = queue.next_batch();
+        assert_eq!(batch.len(), BATCH_SIZE);
+        assert_eq!((batch[0].document_id.as_str(), batch[1].document_id.as_str()), ("doc-15", "doc-16"));
+        assert_eq!(queue.pending.len(), 20 - BATCH_SIZE);
+
+        let dir = storage::test_dir();
+        let photo = dir.join("receipt.png");
+        DynamicImage::new_rgba8(1200, 1600).save(&photo).unwrap();
+        let photo_request = ThumbnailRequest {
+            document_id: "doc-photo".to_string(),
+            source_path: photo,
+        };
+
+        let event = make_thumbnail(&dir.join("thumbnails"), &photo_request);
+        assert_eq!(event.error, None);
+        let path = PathBuf::from(event.thumbnail_path.unwrap());
+        assert_eq!(image::image_dimensions(&path).unwrap(), (192, 256));
+        assert_eq!(existing_thumbnail(&dir.join("thumbnails"), &photo_request), Some(path));
+        assert!(make_thumbnail(&dir, &request("missing")).error.is_some());
+    }
+}
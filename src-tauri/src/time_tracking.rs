@@ -0,0 +1,364 @@
+//! Time Tracking Module
+//!
+//! Records billable time against clients and turns unbilled entries into
+//! draft invoices to issue, with a PDF rendered through the report builder.
+
+use chrono::{Days, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::entities::Entity;
+use crate::money::{round_cents, sum_dollars, Money};
+use crate::periods;
+use crate::report_builder::{Align, Column, ReportBuilder};
+use crate::storage;
+
+static LEDGER_LOCK: Mutex<()> = Mutex::new(());
+
+/// Time worked for a client
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TimeEntry {
+    pub id: String,
+    pub client: String,
+    /// Date worked (YYYY-MM-DD)
+    pub date: String,
+    pub hours: f64,
+    /// Hourly rate excluding GST
+    pub rate: f64,
+    pub description: String,
+    /// Issued invoice the entry was billed on
+    pub invoice_id: Option<String>,
+    pub created_at: String,
+}
+
+/// New time entry from the entry form
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NewTimeEntry {
+    pub client: String,
+    pub date: String,
+    pub hours: f64,
+    pub rate: f64,
+    pub description: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IssuedInvoiceStatus {
+    #[default]
+    Draft,
+    Issued,
+    Paid,
+}
+
+/// One billed line on an issued invoice
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IssuedInvoiceLine {
+    pub time_entry_id: String,
+    pub date: String,
+    pub description: String,
+    pub hours: f64,
+    pub rate: f64,
+    pub amount: f64,
+}
+
+/// An invoice issued to a client
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct IssuedInvoice {
+    pub id: String,
+    pub invoice_number: String,
+    pub client: String,
+    pub issue_date: String,
+    pub due_date: String,
+    pub lines: Vec<IssuedInvoiceLine>,
+    pub subtotal: f64,
+    pub gst: f64,
+    pub total: f64,
+    pub status: IssuedInvoiceStatus,
+    pub created_at: String,
+}
+
+/// Options for converting unbilled time to an invoice
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DraftInvoiceRequest {
+    pub client: String,
+    /// Specific entries to bill; all unbilled entries for the client when unset
+    pub entry_ids: Option<Vec<String>>,
+    /// Issue date (YYYY-MM-DD)
+    pub issue_date: String,
+    pub payment_terms_days: u64,
+    /// Add GST (for GST-registered issuers)
+    pub include_gst: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+struct LedgerData {
+    entries: Vec<TimeEntry>,
+    invoices: Vec<IssuedInvoice>,
+}
+
+/// JSON-backed time entries and issued invoices
+pub struct TimeLedger {
+    path: PathBuf,
+    data: LedgerData,
+}
+
+impl TimeLedger {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            data: storage::load_json(path)?,
+        })
+    }
+
+    pub fn open_default() -> Result<Self, String> {
+        Self::open(&storage::get_data_directory()?.join("time_tracking.json"))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        storage::save_json(&self.path, &self.data)
+    }
+
+    pub fn add_entry(&mut self, entry: NewTimeEntry) -> Result<TimeEntry, String> {
+        let date = periods::parse_date(&entry.date).ok_or_else(|| format!("Invalid date: {}", entry.date))?;
+        if entry.client.trim().is_empty() {
+            return Err("Client is required".to_string());
+        }
+        if entry.hours <= 0.0 || entry.rate < 0.0 {
+            return Err("Hours must be positive and rate cannot be negative".to_string());
+        }
+
+        let entry = TimeEntry {
+            id: storage::generate_id("time"),
+            client: entry.client.trim().to_string(),
+            date: date.format("%Y-%m-%d").to_string(),
+            hours: entry.hours,
+            rate: entry.rate,
+            description: entry.description.trim().to_string(),
+            invoice_id: None,
+            created_at: storage::now_timestamp(),
+        };
+        self.data.entries.push(entry.clone());
+        Ok(entry)
+    }
+
+    /// Entries, optionally for one client and only those not yet billed
+    pub fn entries(&self, client: Option<&str>, unbilled_only: bool) -> Vec<TimeEntry> {
+        let mut entries: Vec<TimeEntry> = self
+            .data
+            .entries
+            .iter()
+            .filter(|e| client.map_or(true, |c| e.client.eq_ignore_ascii_case(c)))
+            .filter(|e| !unbilled_only || e.invoice_id.is_none())
+            .cloned()
+            .collect();
+        entries.sort_by(|a, b| a.date.cmp(&b.date));
+        entries
+    }
+
+    /// Delete an unbilled entry
+    pub fn delete_entry(&mut self, id: &str) -> Result<bool, String> {
+        if let Some(entry) = self.data.entries.iter().find(|e| e.id == id) {
+            if entry.invoice_id.is_some() {
+                return Err("Billed time entries cannot be deleted".to_string());
+            }
+        }
+        let before = self.data.entries.len();
+        self.data.entries.retain(|e| e.id != id);
+        Ok(self.data.entries.len() != before)
+    }
+
+    pub fn invoices(&self) -> Vec<IssuedInvoice> {
+        self.data.invoices.clone()
+    }
+
+    pub fn invoice(&self, id: &str) -> Option<&IssuedInvoice> {
+        self.data.invoices.iter().find(|i| i.id == id)
+    }
+
+    /// Bill unbilled time on a new draft invoice
+    pub fn create_draft_invoice(&mut self, request: &DraftInvoiceRequest) -> Result<IssuedInvoice, String> {
+        let issue_date = periods::parse_date(&request.issue_date)
+            .ok_or_else(|| format!("Invalid issue date: {}", request.issue_date))?;
+        let due_date = issue_date
+            .checked_add_days(Days::new(request.payment_terms_days))
+            .ok_or_else(|| "Invalid payment terms".to_string())?;
+
+        let selected: Vec<TimeEntry> = self
+            .entries(Some(&request.client), true)
+            .into_iter()
+            .filter(|e| request.entry_ids.as_ref().map_or(true, |ids| ids.contains(&e.id)))
+            .collect();
+        if selected.is_empty() {
+            return Err(format!("No unbilled time for {}", request.client));
+        }
+
+        let lines: Vec<IssuedInvoiceLine> = selected
+            .iter()
+            .map(|e| IssuedInvoiceLine {
+                time_entry_id: e.id.clone(),
+                date: e.date.clone(),
+                description: e.description.clone(),
+                hours: e.hours,
+                rate: e.rate,
+                amount: round_cents(e.hours * e.rate),
+            })
+            .collect();
+        let subtotal = Money::from_dollars(sum_dollars(lines.iter().map(|l| l.amount)));
+        let gst = if request.include_gst { subtotal.gst_on_exclusive() } else { Money::ZERO };
+
+        let invoice = IssuedInvoice {
+            id: storage::generate_id("issued"),
+            invoice_number: self.next_invoice_number(),
+            client: selected[0].client.clone(),
+            issue_date: issue_date.format("%Y-%m-%d").to_string(),
+            due_date: due_date.format("%Y-%m-%d").to_string(),
+            lines,
+            subtotal: subtotal.to_dollars(),
+            gst: gst.to_dollars(),
+            total: (subtotal + gst).to_dollars(),
+            status: IssuedInvoiceStatus::Draft,
+            created_at: storage::now_timestamp(),
+        };
+
+        for entry in self.data.entries.iter_mut() {
+            if selected.iter().any(|s| s.id == entry.id) {
+                entry.invoice_id = Some(invoice.id.clone());
+            }
+        }
+        self.data.invoices.push(invoice.clone());
+        Ok(invoice)
+    }
+
+    fn next_invoice_number(&self) -> String {
+        let highest = self
+            .data
+            .invoices
+            .iter()
+            .filter_map(|i| i.invoice_number.strip_prefix("INV-")?.parse::<u32>().ok())
+            .max()
+            .unwrap_or(0);
+        format!("INV-{:04}", highest + 1)
+    }
+}
+
+/// Run a closure against the default ledger while holding its lock
+pub fn with_ledger<R>(f: impl FnOnce(&mut TimeLedger) -> Result<R, String>) -> Result<R, String> {
+    let _guard = LEDGER_LOCK.lock().map_err(|_| "Time ledger lock poisoned".to_string())?;
+    let mut ledger = TimeLedger::open_default()?;
+    f(&mut ledger)
+}
+
+fn display_date(date: &str) -> String {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map(|d| d.format("%d/%m/%Y").to_string())
+        .unwrap_or_else(|_| date.to_string())
+}
+
+/// Render an issued invoice as a PDF
+pub fn render_invoice_pdf(invoice: &IssuedInvoice, issuer: Option<&Entity>) -> Vec<u8> {
+    let title = if invoice.gst > 0.0 { "Tax Invoice" } else { "Invoice" };
+    let mut report = ReportBuilder::new(&format!("{} {}", title, invoice.invoice_number));
+
+    report.heading(title);
+    if let Some(issuer) = issuer {
+        report.text(&issuer.name);
+        if let Some(ref abn) = issuer.abn {
+            report.text(&format!("ABN {}", abn));
+        }
+    }
+    report
+        .space(8.0)
+        .text(&format!("Invoice number: {}", invoice.invoice_number))
+        .text(&format!("Issue date: {}", display_date(&invoice.issue_date)))
+        .text(&format!("Due date: {}", display_date(&invoice.due_date)))
+        .space(8.0)
+        .subheading("Bill To")
+        .text(&invoice.client)
+        .space(12.0);
+
+    let rows: Vec<Vec<String>> = invoice
+        .lines
+        .iter()
+        .map(|l| {
+            vec![
+                display_date(&l.date),
+                l.description.clone(),
+                format!("{:.2}", l.hours),
+                format!("${:.2}", l.rate),
+                format!("${:.2}", l.amount),
+            ]
+        })
+        .collect();
+    report.table(
+        &[
+            Column::new("Date", 70.0, Align::Left),
+            Column::new("Description", 215.0, Align::Left),
+            Column::new("Hours", 50.0, Align::Right),
+            Column::new("Rate", 80.0, Align::Right),
+            Column::new("Amount", 80.0, Align::Right),
+        ],
+        &rows,
+    );
+
+    report
+        .space(12.0)
+        .text_right(&format!("Subtotal ${:.2}", invoice.subtotal), false);
+    if invoice.gst > 0.0 {
+        report.text_right(&format!("GST ${:.2}", invoice.gst), false);
+    }
+    report.text_right(&format!("Total ${:.2}", invoice.total), true);
+
+    report.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(client: &str, date: &str, hours: f64) -> NewTimeEntry {
+        NewTimeEntry {
+            client: client.to_string(),
+            date: date.to_string(),
+            hours,
+            rate: 150.0,
+            description: "Consulting".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_unbilled_time_becomes_draft_invoice() {
+        let dir = storage::test_dir();
+        let path = dir.join("time_tracking.json");
+        let mut ledger = TimeLedger::open(&path).unwrap();
+
+        ledger.add_entry(entry("Acme", "2024-03-04", 2.5)).unwrap();
+        ledger.add_entry(entry("Acme", "2024-03-05", 1.0)).unwrap();
+        ledger.add_entry(entry("Other Co", "2024-03-05", 3.0)).unwrap();
+
+        let request = DraftInvoiceRequest {
+            client: "acme".to_string(),
+            entry_ids: None,
+            issue_date: "2024-03-31".to_string(),
+            payment_terms_days: 14,
+            include_gst: true,
+        };
+        let invoice = ledger.create_draft_invoice(&request).unwrap();
+        assert_eq!(invoice.invoice_number, "INV-0001");
+        assert_eq!(invoice.lines.len(), 2);
+        assert_eq!(invoice.subtotal, 525.0);
+        assert_eq!(invoice.gst, 52.5);
+        assert_eq!(invoice.due_date, "2024-04-14");
+
+        // Billed time is not billed twice or deleted
+        assert!(ledger.create_draft_invoice(&request).is_err());
+        let billed = ledger.entries(Some("Acme"), false)[0].id.clone();
+        assert!(ledger.delete_entry(&billed).is_err());
+        assert_eq!(ledger.entries(None, true).len(), 1);
+
+        let pdf = render_invoice_pdf(&invoice, None);
+        assert!(String::from_utf8_lossy(&pdf).contains("(Tax Invoice)"));
+    }
+}
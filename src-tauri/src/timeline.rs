@@ -0,0 +1,214 @@
+//! Timeline Module
+//!
+//! Activity over a date range for the calendar and timeline views. The
+//! calendar gets a document count and spend for every day, for shading as a
+//! heat map; the timeline merges documents, invoice payments and generated
+//! reports into one feed in date order.
+
+use chrono::{Days, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::documents::{DocumentKind, StoredDocument};
+use crate::money::Money;
+use crate::periods;
+use crate::saved_reports::SavedReport;
+
+/// Longest range the calendar covers, about two years
+pub const MAX_CALENDAR_DAYS: u64 = 731;
+
+/// Documents dated on one day
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CalendarDay {
+    /// YYYY-MM-DD
+    pub date: String,
+    pub count: usize,
+    pub spend: Money,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineEventKind {
+    Document,
+    /// An invoice marked as paid
+    Payment,
+    /// A report written to disk, or a new version of one
+    Report,
+}
+
+/// One entry in the timeline feed
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TimelineEvent {
+    pub kind: TimelineEventKind,
+    /// Document date (YYYY-MM-DD) for documents, otherwise the RFC 3339
+    /// timestamp of when it happened
+    pub at: String,
+    /// Document or saved report ID
+    pub id: String,
+    pub title: String,
+    pub amount: Option<Money>,
+}
+
+/// Parse an inclusive YYYY-MM-DD range
+pub fn parse_range(date_from: &str, date_to: &str) -> Result<(NaiveDate, NaiveDate), String> {
+    let parse = |value: &str| {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|e| format!("Invalid date '{}': {}", value, e))
+    };
+    let (from, to) = (parse(date_from)?, parse(date_to)?);
+    if from > to {
+        return Err("Start date must not be after end date".to_string());
+    }
+    Ok((from, to))
+}
+
+/// Count and spend for every day from `from` to `to`, including days with
+/// no documents
+pub fn calendar_heat(documents: &[StoredDocument], from: NaiveDate, to: NaiveDate) -> Result<Vec<CalendarDay>, String> {
+    if to.signed_duration_since(from).num_days() >= MAX_CALENDAR_DAYS as i64 {
+        return Err(format!("The calendar covers at most {} days", MAX_CALENDAR_DAYS));
+    }
+    let mut totals: BTreeMap<NaiveDate, Vec<Money>> = BTreeMap::new();
+    for doc in documents {
+        if let Some(date) = doc.date.as_deref().and_then(periods::parse_date) {
+            totals.entry(date).or_default().push(doc.total.unwrap_or_default());
+        }
+    }
+
+    let mut days = Vec::new();
+    let mut date = from;
+    while date <= to {
+        let amounts = totals.remove(&date).unwrap_or_default();
+        days.push(CalendarDay {
+            date: date.format("%Y-%m-%d").to_string(),
+            count: amounts.len(),
+            spend: amounts.into_iter().sum(),
+        });
+        date = date + Days::new(1);
+    }
+    Ok(days)
+}
+
+fn document_title(doc: &StoredDocument) -> String {
+    let kind = match doc.kind {
+        DocumentKind::Receipt => "Receipt",
+        DocumentKind::Invoice => "Invoice",
+    };
+    match doc.vendor.as_deref() {
+        Some(vendor) => format!("{} from {}", kind, vendor),
+        None => kind.to_string(),
+    }
+}
+
+/// Documents, payments and report generations between `from` and `to`,
+/// oldest first
+pub fn build(documents: &[StoredDocument], reports: &[SavedReport], from: NaiveDate, to: NaiveDate) -> Vec<TimelineEvent> {
+    let in_range = |at: &str| periods::parse_date(at).is_some_and(|date| from <= date && date <= to);
+    let mut events = Vec::new();
+
+    for doc in documents {
+        if let Some(date) = doc.date.as_deref().filter(|date| in_range(date)) {
+            events.push(TimelineEvent {
+                kind: TimelineEventKind::Document,
+                at: date.to_string(),
+                id: doc.id.clone(),
+                title: document_title(doc),
+                amount: doc.total,
+            });
+        }
+        if let Some(paid_at) = doc.paid_at.as_deref().filter(|at| doc.paid && in_range(at)) {
+            events.push(TimelineEvent {
+                kind: TimelineEventKind::Payment,
+                at: paid_at.to_string(),
+                id: doc.id.clone(),
+                title: format!("Paid {}", doc.vendor.as_deref().unwrap_or("invoice")),
+                amount: doc.total,
+            });
+        }
+    }
+
+    for report in reports {
+        for version in report.versions.iter().filter(|v| in_range(&v.generated_at)) {
+            let title = match version.version {
+                1 => report.title.clone(),
+                n => format!("{} (version {})", report.title, n),
+            };
+            events.push(TimelineEvent {
+                kind: TimelineEventKind::Report,
+                at: version.generated_at.clone(),
+                id: report.id.clone(),
+                title,
+                amount: None,
+            });
+        }
+    }
+
+    // A bare date sorts before timestamps on the same day
+    events.sort_by(|a, b| a.at.cmp(&b.at));
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::saved_reports::{ReportParameters, ReportVersion};
+
+    #[test]
+    fn test_calendar_and_timeline_cover_the_range() {
+        let doc = |id: &str, kind: DocumentKind, date: &str, total: f64, paid_at: Option<&str>| StoredDocument {
+            id: id.to_string(),
+            kind,
+            vendor: Some(id.to_string()),
+            date: Some(date.to_string()),
+            total: Some(Money::from_dollars(total)),
+            paid: paid_at.is_some(),
+            paid_at: paid_at.map(str::to_string),
+            ..Default::default()
+        };
+        let documents = vec![
+            doc("Bunnings", DocumentKind::Receipt, "2024-03-02", 45.5, None),
+            doc("Telstra", DocumentKind::Invoice, "2024-03-02", 89.1, Some("2024-03-04T09:30:00+11:00")),
+            doc("AGL", DocumentKind::Invoice, "2024-02-20", 210.0, Some("2024-03-01T12:00:00+11:00")),
+            doc("Officeworks", DocumentKind::Receipt, "2024-03-09", 12.0, None),
+        ];
+        let version = |version: u32, generated_at: &str| ReportVersion {
+            version,
+            file_path: format!("digest-v{}.html", version),
+            generated_at: generated_at.to_string(),
+            totals: Vec::new(),
+        };
+        let reports = vec![SavedReport {
+            id: "report-1".to_string(),
+            title: "Weekly digest 2024-03-03".to_string(),
+            parameters: ReportParameters::WeeklyDigest {
+                week_ending: "2024-03-03".to_string(),
+            },
+            versions: vec![version(1, "2024-03-04T08:00:00+11:00"), version(2, "2024-03-12T08:00:00+11:00")],
+            created_at: "2024-03-04T08:00:00+11:00".to_string(),
+        }];
+        let (from, to) = parse_range("2024-03-01", "2024-03-05").unwrap();
+
+        let days = calendar_heat(&documents, from, to).unwrap();
+        assert_eq!(days.len(), 5);
+        assert_eq!((days[0].count, days[0].spend), (0, Money::ZERO));
+        assert_eq!((days[1].date.as_str(), days[1].count, days[1].spend), ("2024-03-02", 2, Money::from_cents(13460)));
+
+        let events: Vec<_> = build(&documents, &reports, from, to)
+            .into_iter()
+            .map(|event| (event.kind, event.title))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (TimelineEventKind::Payment, "Paid AGL".to_string()),
+                (TimelineEventKind::Document, "Receipt from Bunnings".to_string()),
+                (TimelineEventKind::Document, "Invoice from Telstra".to_string()),
+                (TimelineEventKind::Report, "Weekly digest 2024-03-03".to_string()),
+                (TimelineEventKind::Payment, "Paid Telstra".to_string()),
+            ]
+        );
+
+        assert!(parse_range("2024-03-05", "2024-03-01").is_err());
+        let (from, to) = parse_range("2020-01-01", "2024-01-01").unwrap();
+        assert!(calendar_heat(&documents, from, to).is_err());
+    }
+}
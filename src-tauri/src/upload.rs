@@ -0,0 +1,557 @@
+//! Portal Upload Module
+//!
+//! Pushes handover packages straight to an accountant's document portal.
+//! Each upload target is a WebDAV folder (`https://...`) or an SFTP server
+//! (`sftp://host:port`). WebDAV is only spoken over HTTPS, and its passwords
+//! are kept in the system keychain; SFTP runs the system `sftp` client in
+//! batch mode, so it signs in with the user's SSH keys or agent. Transient
+//! failures are retried with a growing delay, and every attempt is reported
+//! as an `UPLOAD_EVENT`.
+
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::keychain::Keychain;
+
+/// Event emitted as an upload starts, retries, completes or fails
+pub const UPLOAD_EVENT: &str = "upload://progress";
+
+/// Keychain service upload secrets are stored under, keyed by target ID
+pub const KEYCHAIN_SERVICE: &str = "tally-portal-upload";
+
+/// Attempts per upload, including the first
+pub const MAX_ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry; doubled for each one after
+pub const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadProtocol {
+    WebDav,
+    Sftp,
+}
+
+/// An accountant portal packages can be uploaded to. The password is never
+/// stored here; it lives in the keychain.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct UploadTarget {
+    /// Empty when creating a target
+    pub id: String,
+    pub name: String,
+    /// WebDAV base URL (`https://portal.example.com/dav`) or `sftp://host[:port]`
+    pub url: String,
+    pub username: String,
+    /// Folder under the URL to upload into, created if missing
+    pub remote_dir: String,
+}
+
+impl UploadTarget {
+    pub fn protocol(&self) -> Result<UploadProtocol, String> {
+        let lower = self.url.trim().to_lowercase();
+        if lower.starts_with("https://") {
+            Ok(UploadProtocol::WebDav)
+        } else if lower.starts_with("sftp://") {
+            Ok(UploadProtocol::Sftp)
+        } else if lower.starts_with("http://") {
+            Err("WebDAV uploads must use https:// so the password isn't sent in the clear".to_string())
+        } else {
+            Err(format!("Upload URL must start with https:// or sftp://: {}", self.url))
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("An upload target needs a name".to_string());
+        }
+        if self.username.trim().is_empty() {
+            return Err("An upload target needs a username".to_string());
+        }
+        if self.remote_dir.split('/').any(|segment| segment == "..") {
+            return Err("The remote folder cannot contain '..'".to_string());
+        }
+        if self.protocol()? == UploadProtocol::Sftp {
+            Sftp::destination(self)?;
+        }
+        Ok(())
+    }
+
+    /// Folder names of `remote_dir`, outermost first
+    fn remote_segments(&self) -> Vec<&str> {
+        self.remote_dir.split('/').map(str::trim).filter(|s| !s.is_empty() && *s != ".").collect()
+    }
+
+    /// Path of an uploaded file relative to the URL
+    pub fn remote_path(&self, file_name: &str) -> String {
+        let mut segments = self.remote_segments();
+        segments.push(file_name);
+        segments.join("/")
+    }
+}
+
+/// Add a target to `targets`, or replace the one with the same ID, saving
+/// its password to the keychain when one is given
+pub fn save_target(
+    targets: &mut Vec<UploadTarget>,
+    keychain: &dyn Keychain,
+    mut target: UploadTarget,
+    password: Option<&str>,
+) -> Result<UploadTarget, String> {
+    target.validate()?;
+    if target.id.is_empty() {
+        target.id = crate::storage::generate_id("portal");
+    }
+    if let Some(password) = password.filter(|p| !p.is_empty()) {
+        keychain.set(KEYCHAIN_SERVICE, &target.id, password)?;
+    }
+    targets.retain(|t| t.id != target.id);
+    targets.push(target.clone());
+    Ok(target)
+}
+
+/// Remove a target and its saved password; false if there was no such target
+pub fn delete_target(
+    targets: &mut Vec<UploadTarget>,
+    keychain: &dyn Keychain,
+    target_id: &str,
+) -> Result<bool, String> {
+    let before = targets.len();
+    targets.retain(|t| t.id != target_id);
+    if targets.len() == before {
+        return Ok(false);
+    }
+    keychain.delete(KEYCHAIN_SERVICE, target_id)?;
+    Ok(true)
+}
+
+/// Password saved for a target, if any
+pub fn saved_password(keychain: &dyn Keychain, target_id: &str) -> Result<Option<String>, String> {
+    keychain.get(KEYCHAIN_SERVICE, target_id)
+}
+
+/// Why an attempt failed, and whether trying again could help
+#[derive(Debug, Clone, PartialEq)]
+pub struct UploadError {
+    pub message: String,
+    /// False for problems a retry can't fix, such as a rejected password
+    pub retryable: bool,
+}
+
+impl UploadError {
+    fn retryable(message: String) -> Self {
+        Self {
+            message,
+            retryable: true,
+        }
+    }
+
+    fn permanent(message: String) -> Self {
+        Self {
+            message,
+            retryable: false,
+        }
+    }
+}
+
+/// Sends one file to a target
+pub trait UploadTransport {
+    fn upload(
+        &self,
+        target: &UploadTarget,
+        secret: Option<&str>,
+        file: &Path,
+        file_name: &str,
+    ) -> Result<(), UploadError>;
+}
+
+/// The transport for a target's protocol
+pub fn transport_for(protocol: UploadProtocol) -> Box<dyn UploadTransport + Send> {
+    match protocol {
+        UploadProtocol::WebDav => Box::new(WebDav),
+        UploadProtocol::Sftp => Box::new(Sftp),
+    }
+}
+
+/// Percent-encode one URL path segment
+fn encode_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// WebDAV: `MKCOL` for each folder of `remote_dir`, then `PUT` the file
+struct WebDav;
+
+impl UploadTransport for WebDav {
+    fn upload(
+        &self,
+        target: &UploadTarget,
+        secret: Option<&str>,
+        file: &Path,
+        file_name: &str,
+    ) -> Result<(), UploadError> {
+        use tauri_plugin_http::reqwest;
+
+        let secret = secret.ok_or_else(|| {
+            UploadError::permanent(format!("No password is saved for {}; add it in upload settings", target.name))
+        })?;
+        let bytes = std::fs::read(file)
+            .map_err(|e| UploadError::permanent(format!("Failed to read {}: {}", file.display(), e)))?;
+        let base = target.url.trim().trim_end_matches('/').to_string();
+        let mut folder = base.clone();
+        let segments = target.remote_segments();
+        let file_url = std::iter::once(base)
+            .chain(segments.iter().chain([&file_name]).map(|s| encode_segment(s)))
+            .collect::<Vec<_>>()
+            .join("/");
+
+        tauri::async_runtime::block_on(async {
+            let client = reqwest::Client::new();
+            let mkcol = reqwest::Method::from_bytes(b"MKCOL").map_err(|e| UploadError::permanent(e.to_string()))?;
+            for segment in &segments {
+                folder = format!("{}/{}", folder, encode_segment(segment));
+                // 405 means the folder already exists
+                client
+                    .request(mkcol.clone(), format!("{}/", folder))
+                    .basic_auth(&target.username, Some(secret))
+                    .send()
+                    .await
+                    .map_err(|e| UploadError::retryable(format!("WebDAV request failed: {}", e)))?;
+            }
+            let response = client
+                .put(&file_url)
+                .basic_auth(&target.username, Some(secret))
+                .header("Content-Type", "application/zip")
+                .body(bytes)
+                .send()
+                .await
+                .map_err(|e| UploadError::retryable(format!("WebDAV upload failed: {}", e)))?;
+            let status = response.status().as_u16();
+            match status {
+                200..=299 => Ok(()),
+                401 | 403 => Err(UploadError::permanent(format!("{} rejected the username or password", target.name))),
+                400..=499 => Err(UploadError::permanent(format!("WebDAV upload was refused (HTTP {})", status))),
+                _ => Err(UploadError::retryable(format!("WebDAV upload failed (HTTP {})", status))),
+            }
+        })
+    }
+}
+
+/// SFTP through the system `sftp` client in batch mode
+struct Sftp;
+
+impl Sftp {
+    /// `host` and `port` from `sftp://host[:port][/...]`
+    fn host_and_port(url: &str) -> Result<(String, Option<u16>), String> {
+        let rest = url.trim().get("sftp://".len()..).unwrap_or("");
+        let authority = rest.split('/').next().unwrap_or("");
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => {
+                (host, Some(port.parse::<u16>().map_err(|_| format!("Invalid SFTP port: {}", port))?))
+            }
+            None => (authority, None),
+        };
+        if host.is_empty() {
+            return Err(format!("Invalid SFTP URL: {}", url));
+        }
+        Ok((host.to_string(), port))
+    }
+
+    /// `user@host` for the command line. Names starting with `-` would be
+    /// read as options, such as `-oProxyCommand=...`, so they are refused
+    /// along with whitespace and control characters.
+    fn destination(target: &UploadTarget) -> Result<String, String> {
+        let (host, _) = Self::host_and_port(&target.url)?;
+        let username = target.username.trim();
+        for (kind, value) in [("username", username), ("host", host.as_str())] {
+            if value.starts_with('-') || value.chars().any(|c| c.is_whitespace() || c.is_control()) {
+                return Err(format!("Invalid SFTP {}: {}", kind, value.escape_debug()));
+            }
+        }
+        Ok(format!("{}@{}", username, host))
+    }
+
+    /// Batch commands creating `remote_dir` (ignoring folders that exist)
+    /// and putting the file there
+    fn batch_script(target: &UploadTarget, file: &Path, file_name: &str) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut script = String::new();
+        let mut folder = String::new();
+        for segment in target.remote_segments() {
+            folder = if folder.is_empty() { segment.to_string() } else { format!("{}/{}", folder, segment) };
+            script.push_str(&format!("-mkdir {}\n", quote(&folder)));
+        }
+        let local = file.to_string_lossy();
+        script.push_str(&format!("put {} {}\n", quote(&local), quote(&target.remote_path(file_name))));
+        script
+    }
+}
+
+impl UploadTransport for Sftp {
+    fn upload(
+        &self,
+        target: &UploadTarget,
+        _secret: Option<&str>,
+        file: &Path,
+        file_name: &str,
+    ) -> Result<(), UploadError> {
+        let (_, port) = Self::host_and_port(&target.url).map_err(UploadError::permanent)?;
+        let destination = Self::destination(target).map_err(UploadError::permanent)?;
+        let mut command = Command::new("sftp");
+        command.args(["-b", "-", "-o", "BatchMode=yes"]);
+        if let Some(port) = port {
+            command.args(["-P", &port.to_string()]);
+        }
+        let mut child = command
+            .arg("--")
+            .arg(destination)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| UploadError::permanent(format!("Failed to start sftp: {}", e)))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(Self::batch_script(target, file, file_name).as_bytes())
+                .map_err(|e| UploadError::retryable(format!("Failed to send sftp commands: {}", e)))?;
+        }
+        let output = child
+            .wait_with_output()
+            .map_err(|e| UploadError::retryable(format!("sftp failed: {}", e)))?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let message = format!("SFTP upload failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+        // 255 is a connection or authentication failure from ssh itself
+        match output.status.code() {
+            Some(255) if message.contains("Permission denied") => Err(UploadError::permanent(message)),
+            Some(255) | None => Err(UploadError::retryable(message)),
+            _ => Err(UploadError::permanent(message)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadStatus {
+    Started,
+    Retrying,
+    Completed,
+    Failed,
+}
+
+/// Payload of `UPLOAD_EVENT`. Files are sent in one request, so there is no
+/// progress within an attempt.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct UploadProgress {
+    pub upload_id: String,
+    pub target_id: String,
+    pub file_name: String,
+    pub status: UploadStatus,
+    /// 1-based attempt number
+    pub attempt: u32,
+    pub total_bytes: u64,
+    pub error: Option<String>,
+}
+
+/// A finished upload
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct UploadResult {
+    pub upload_id: String,
+    pub target_id: String,
+    /// Path of the file relative to the target URL
+    pub remote_path: String,
+    pub bytes: u64,
+    pub attempts: u32,
+}
+
+/// Upload a file, retrying transient failures up to `MAX_ATTEMPTS` times
+/// with a delay that starts at `retry_delay` and doubles, and reporting
+/// each step to `progress`
+pub fn upload_with_retry(
+    transport: &dyn UploadTransport,
+    target: &UploadTarget,
+    secret: Option<&str>,
+    file: &Path,
+    upload_id: &str,
+    retry_delay: Duration,
+    progress: &mut dyn FnMut(UploadProgress),
+) -> Result<UploadResult, String> {
+    let file_name = file
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| format!("Invalid file: {}", file.display()))?;
+    let total_bytes = std::fs::metadata(file)
+        .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?
+        .len();
+    let mut report = |status, attempt, error: Option<String>| {
+        progress(UploadProgress {
+            upload_id: upload_id.to_string(),
+            target_id: target.id.clone(),
+            file_name: file_name.clone(),
+            status,
+            attempt,
+            total_bytes,
+            error,
+        })
+    };
+
+    let mut delay = retry_delay;
+    for attempt in 1..=MAX_ATTEMPTS {
+        report(UploadStatus::Started, attempt, None);
+        match transport.upload(target, secret, file, &file_name) {
+            Ok(()) => {
+                report(UploadStatus::Completed, attempt, None);
+                return Ok(UploadResult {
+                    upload_id: upload_id.to_string(),
+                    target_id: target.id.clone(),
+                    remote_path: target.remote_path(&file_name),
+                    bytes: total_bytes,
+                    attempts: attempt,
+                });
+            }
+            Err(e) if e.retryable && attempt < MAX_ATTEMPTS => {
+                log::warn!("Upload to {} failed (attempt {}): {}", target.name, attempt, e.message);
+                report(UploadStatus::Retrying, attempt, Some(e.message));
+                std::thread::sleep(delay);
+                delay *= 2;
+            }
+            Err(e) => {
+                report(UploadStatus::Failed, attempt, Some(e.message.clone()));
+                return Err(e.message);
+            }
+        }
+    }
+    unreachable!("the last attempt either succeeds or fails")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keychain::MemoryKeychain;
+    use crate::storage;
+    use std::cell::RefCell;
+
+    /// Fails with each queued error in turn, then succeeds
+    struct Flaky(RefCell<Vec<UploadError>>);
+
+    impl UploadTransport for Flaky {
+        fn upload(&self, _: &UploadTarget, _: Option<&str>, _: &Path, _: &str) -> Result<(), UploadError> {
+            let mut errors = self.0.borrow_mut();
+            if errors.is_empty() {
+                Ok(())
+            } else {
+                Err(errors.remove(0))
+            }
+        }
+    }
+
+    #[test]
+    fn test_uploads_retry_transient_failures_and_report_progress() {
+        let dir = storage::test_dir();
+        let file = dir.join("handover 2024.zip");
+        std::fs::write(&file, b"PK0123456789").unwrap();
+        let target = UploadTarget {
+            id: "portal".to_string(),
+            name: "Smith & Co".to_string(),
+            url: "sftp://files.smithco.com.au:2222".to_string(),
+            username: "client42".to_string(),
+            remote_dir: "/Clients/Jones/".to_string(),
+        };
+        assert_eq!(target.protocol(), Ok(UploadProtocol::Sftp));
+        assert_eq!(Sftp::host_and_port(&target.url).unwrap(), ("files.smithco.com.au".to_string(), Some(2222)));
+        let script = Sftp::batch_script(&target, &file, "handover 2024.zip");
+        assert!(script.starts_with("-mkdir \"Clients\"\n-mkdir \"Clients/Jones\"\nput "));
+        assert!(script.ends_with(" \"Clients/Jones/handover 2024.zip\"\n"));
+        assert_eq!(encode_segment("handover 2024.zip"), "handover%202024.zip");
+
+        let flaky = Flaky(RefCell::new(vec![UploadError::retryable("timed out".to_string())]));
+        let mut events = Vec::new();
+        let result = upload_with_retry(&flaky, &target, None, &file, "up-1", Duration::ZERO, &mut |p| events.push(p));
+        let result = result.unwrap();
+        assert_eq!((result.attempts, result.bytes), (2, 12));
+        assert_eq!(result.remote_path, "Clients/Jones/handover 2024.zip");
+        let statuses: Vec<UploadStatus> = events.iter().map(|e| e.status).collect();
+        use UploadStatus::*;
+        assert_eq!(statuses, vec![Started, Retrying, Started, Completed]);
+        assert_eq!(events.last().unwrap().total_bytes, 12);
+
+        // A rejected password is not retried
+        let refused = Flaky(RefCell::new(vec![UploadError::permanent("bad password".to_string())]));
+        events.clear();
+        let result = upload_with_retry(&refused, &target, None, &file, "up-2", Duration::ZERO, &mut |p| events.push(p));
+        assert_eq!(result.unwrap_err(), "bad password");
+        assert_eq!(events.len(), 2);
+
+        let bad = UploadTarget {
+            url: "ftp://files.smithco.com.au".to_string(),
+            ..target.clone()
+        };
+        assert!(bad.validate().is_err());
+        let cleartext = UploadTarget {
+            url: "http://portal.smithco.com.au/dav".to_string(),
+            ..target.clone()
+        };
+        assert!(cleartext.validate().is_err());
+    }
+
+    #[test]
+    fn test_sftp_names_cannot_be_read_as_options() {
+        let target = UploadTarget {
+            name: "Portal".to_string(),
+            url: "sftp://files.smithco.com.au".to_string(),
+            username: "client42".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(Sftp::destination(&target).unwrap(), "client42@files.smithco.com.au");
+        for (username, url) in [
+            ("-oProxyCommand=touch /tmp/x", "sftp://files.smithco.com.au"),
+            ("client 42", "sftp://files.smithco.com.au"),
+            ("client42", "sftp://-oProxyCommand=id"),
+            ("client\n42", "sftp://files.smithco.com.au"),
+        ] {
+            let bad = UploadTarget {
+                username: username.to_string(),
+                url: url.to_string(),
+                ..target.clone()
+            };
+            assert!(bad.validate().is_err(), "{} at {}", username, url);
+        }
+    }
+
+    #[test]
+    fn test_saved_targets_keep_their_password_in_the_keychain() {
+        let keychain = MemoryKeychain::default();
+        let mut targets = Vec::new();
+        let target = UploadTarget {
+            name: "Smith & Co".to_string(),
+            url: "https://portal.smithco.com.au/dav".to_string(),
+            username: "client42".to_string(),
+            ..Default::default()
+        };
+        let saved = save_target(&mut targets, &keychain, target, Some("hunter2")).unwrap();
+        assert!(!saved.id.is_empty());
+        assert_eq!(saved_password(&keychain, &saved.id).unwrap().as_deref(), Some("hunter2"));
+
+        // Saving again without a password keeps the stored one
+        let renamed = UploadTarget {
+            name: "Smith and Co".to_string(),
+            ..saved.clone()
+        };
+        save_target(&mut targets, &keychain, renamed, None).unwrap();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(saved_password(&keychain, &saved.id).unwrap().as_deref(), Some("hunter2"));
+
+        assert!(delete_target(&mut targets, &keychain, &saved.id).unwrap());
+        assert_eq!(saved_password(&keychain, &saved.id).unwrap(), None);
+        assert!(!delete_target(&mut targets, &keychain, &saved.id).unwrap());
+    }
+}
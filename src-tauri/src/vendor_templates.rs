@@ -0,0 +1,683 @@
+//! Vendor Templates Module
+//!
+//! Remembers what each vendor's invoices look like so a template can be
+//! picked even when the vendor name and ABN fail to extract. Every parsed
+//! invoice is fingerprinted from its layout: the header keywords it uses,
+//! where they sit and in what order, and the fixed wording of its labels. An
+//! invoice with a known vendor or ABN adds its fingerprint to that vendor's
+//! template; one without is matched against the stored fingerprints
+//! instead. A template can name a scoring profile, which is then used to
+//! parse that vendor's invoices.
+//!
+//! Once a user confirms an invoice, its template also records the label
+//! each field was printed after. Later invoices with the same ABN read those
+//! fields at their learned labels first, falling back to the parser's own
+//! values where a label can't be found.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::currency::CurrencyAmount;
+use crate::dates::{self, DateOrder};
+use crate::documents::StoredDocument;
+use crate::invoice::{ExtractedField, ExtractedInvoice, InvoiceParser};
+use crate::money::Money;
+use crate::settings::AppSettings;
+use crate::storage;
+use crate::text_normalize;
+
+/// Serializes read-modify-write cycles against the templates file
+static TEMPLATES_LOCK: Mutex<()> = Mutex::new(());
+
+/// Only the top of the document is fingerprinted; line items and notes
+/// change from invoice to invoice
+const MAX_LINES: usize = 40;
+
+/// Fingerprints kept per template, newest last
+const MAX_FINGERPRINTS: usize = 5;
+
+/// Fingerprints at least this similar are the same layout
+const MIN_MATCH: f64 = 0.6;
+
+/// A new fingerprint this close to a stored one adds nothing
+const DUPLICATE_MATCH: f64 = 0.95;
+
+/// Field source recorded for values filled in from a template
+const TEMPLATE_SOURCE: &str = "layout_template";
+
+/// Field source recorded for values read at a learned label
+const LOCATION_SOURCE: &str = "vendor_template";
+
+/// Confidence of a value read where the supplier's confirmed invoices had it
+const LOCATION_CONFIDENCE: f64 = 0.95;
+
+/// Labels whose presence and position identify an invoice layout
+const HEADER_KEYWORDS: [&str; 16] = [
+    "tax invoice",
+    "invoice number",
+    "invoice no",
+    "invoice date",
+    "due date",
+    "abn",
+    "bill to",
+    "ship to",
+    "amount due",
+    "balance due",
+    "subtotal",
+    "gst",
+    "payment terms",
+    "bsb",
+    "account",
+    "remittance",
+];
+
+/// Layout features of one document
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct LayoutFingerprint {
+    /// Sorted, e.g. `kw:due date:right`, `seq:invoice date>due date` or
+    /// `word:callout`
+    pub features: Vec<String>,
+}
+
+impl LayoutFingerprint {
+    /// Share of features the two fingerprints have in common
+    pub fn similarity(&self, other: &LayoutFingerprint) -> f64 {
+        let a: BTreeSet<&String> = self.features.iter().collect();
+        let b: BTreeSet<&String> = other.features.iter().collect();
+        let union = a.union(&b).count();
+        if union == 0 {
+            return 0.0;
+        }
+        a.intersection(&b).count() as f64 / union as f64
+    }
+}
+
+/// Fingerprint a document's text. Header keywords are recorded with the
+/// side of the line they sit on and in pairs, in reading order, so the
+/// fingerprint survives lines being added or lost above them. Other words
+/// without digits, which are mostly fixed labels, are recorded as they are.
+pub fn fingerprint(text: &str) -> LayoutFingerprint {
+    let normalized = text_normalize::normalize(text);
+    let mut features = BTreeSet::new();
+    let mut previous: Option<&str> = None;
+    for line in normalized.lines().map(str::trim).filter(|l| !l.is_empty()).take(MAX_LINES) {
+        let lower = line.to_lowercase();
+        let mut found: Vec<(usize, &str)> = HEADER_KEYWORDS
+            .iter()
+            .filter_map(|keyword| lower.find(keyword).map(|offset| (offset, *keyword)))
+            .collect();
+        found.sort();
+        for (offset, keyword) in found {
+            let side = match offset * 3 / lower.len().max(1) {
+                0 => "left",
+                1 => "centre",
+                _ => "right",
+            };
+            features.insert(format!("kw:{}:{}", keyword, side));
+            if let Some(previous) = previous {
+                features.insert(format!("seq:{}>{}", previous, keyword));
+            }
+            previous = Some(keyword);
+        }
+        for word in lower.split(|c: char| !c.is_alphanumeric()) {
+            if word.chars().count() >= 3 && word.chars().all(char::is_alphabetic) {
+                features.insert(format!("word:{}", word));
+            }
+        }
+    }
+    LayoutFingerprint {
+        features: features.into_iter().collect(),
+    }
+}
+
+/// Invoice fields whose labels are learned
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateField {
+    InvoiceNumber,
+    InvoiceDate,
+    DueDate,
+    Subtotal,
+    Gst,
+    Total,
+}
+
+impl TemplateField {
+    fn is_amount(self) -> bool {
+        matches!(self, TemplateField::Subtotal | TemplateField::Gst | TemplateField::Total)
+    }
+
+    /// Text a value of this field may be printed as
+    fn value_pattern(self) -> &'static Regex {
+        static AMOUNT: OnceLock<Regex> = OnceLock::new();
+        static DATE: OnceLock<Regex> = OnceLock::new();
+        static NUMBER: OnceLock<Regex> = OnceLock::new();
+        match self {
+            TemplateField::InvoiceNumber => {
+                NUMBER.get_or_init(|| Regex::new(r"\b[A-Za-z0-9][A-Za-z0-9/_-]*\d[A-Za-z0-9/_-]*\b").unwrap())
+            }
+            TemplateField::InvoiceDate | TemplateField::DueDate => DATE.get_or_init(|| {
+                Regex::new(r"\b\d{1,4}[/.-]\d{1,2}[/.-]\d{2,4}\b|\b\d{1,2}\s+[A-Za-z]{3,9},?\s+\d{4}\b|\b[A-Za-z]{3,9}\s+\d{1,2},?\s+\d{4}\b").unwrap()
+            }),
+            _ => AMOUNT.get_or_init(|| Regex::new(r"\$?\s?(?:\d{1,3}(?:,\d{3})+|\d+)\.\d{2}\b").unwrap()),
+        }
+    }
+}
+
+/// Where a supplier prints a field, learned from a confirmed invoice
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FieldLocation {
+    pub field: TemplateField,
+    /// Words printed before the value, such as `Balance to pay`
+    pub label: String,
+    /// The value sits on the line below its label
+    pub below: bool,
+    /// Pattern the parser had read the field with when it was confirmed
+    pub source: Option<String>,
+}
+
+/// A vendor's invoice layouts
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct VendorTemplate {
+    pub id: String,
+    pub vendor: String,
+    pub abn: Option<String>,
+    /// Scoring profile used for this vendor's invoices; the active profile
+    /// when unset
+    pub heuristic_profile: Option<String>,
+    pub fingerprints: Vec<LayoutFingerprint>,
+    /// Field labels learned from confirmed invoices
+    pub fields: Vec<FieldLocation>,
+    /// Invoices fingerprinted for this template
+    pub documents_seen: usize,
+    pub updated_at: String,
+}
+
+impl VendorTemplate {
+    /// Similarity of the closest stored layout
+    pub fn match_score(&self, fingerprint: &LayoutFingerprint) -> f64 {
+        self.fingerprints
+            .iter()
+            .map(|f| f.similarity(fingerprint))
+            .fold(0.0, f64::max)
+    }
+}
+
+/// How a template was chosen for a document
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateMatchKind {
+    Abn,
+    Vendor,
+    Layout,
+}
+
+/// A template chosen for a document
+#[derive(Debug, Clone)]
+pub struct TemplateMatch {
+    pub template: VendorTemplate,
+    pub kind: TemplateMatchKind,
+    /// 1 for ABN and vendor matches, the layout similarity otherwise
+    pub score: f64,
+}
+
+fn same_vendor(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim())
+}
+
+/// The words just before a value, back to the previous number, without
+/// trailing punctuation. `None` unless they include a letter.
+fn label_before(prefix: &str) -> Option<String> {
+    let words: Vec<&str> = prefix.split_whitespace().collect();
+    let start = words.iter().rposition(|w| w.chars().any(|c| c.is_ascii_digit())).map_or(0, |i| i + 1);
+    let label = words[start..].join(" ");
+    let label = label.trim_end_matches(|c: char| matches!(c, ':' | '$' | '#' | '-') || c.is_whitespace());
+    label.chars().any(char::is_alphabetic).then(|| label.to_string())
+}
+
+/// A value as the user confirmed it
+enum Confirmed {
+    Text(String),
+    /// YYYY-MM-DD
+    Date(String),
+    Amount(Money),
+}
+
+impl Confirmed {
+    fn matches(&self, raw: &str, order: DateOrder) -> bool {
+        match self {
+            Confirmed::Text(text) => raw.eq_ignore_ascii_case(text),
+            Confirmed::Date(iso) => dates::normalize(raw, order).iso.as_ref() == Some(iso),
+            Confirmed::Amount(amount) => Money::parse(raw) == Some(*amount),
+        }
+    }
+}
+
+/// Label of the line a confirmed value is printed on, or of the line above
+/// when the value stands alone. Amounts are looked for from the bottom up,
+/// where totals sit below any line item of the same amount.
+fn locate(text: &str, field: TemplateField, is_value: impl Fn(&str) -> bool) -> Option<(String, bool)> {
+    let lines: Vec<&str> = text.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    let pattern = field.value_pattern();
+    let mut found: Vec<(usize, usize)> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        found.extend(pattern.find_iter(line).filter(|m| is_value(m.as_str())).map(|m| (i, m.start())));
+    }
+    if field.is_amount() {
+        found.reverse();
+    }
+    found.into_iter().find_map(|(i, start)| {
+        let prefix = &lines[i][..start];
+        if !prefix.trim().is_empty() {
+            return label_before(prefix).map(|label| (label, false));
+        }
+        let above = lines.get(i.checked_sub(1)?)?;
+        if pattern.is_match(above) {
+            return None;
+        }
+        label_before(above).map(|label| (label, true))
+    })
+}
+
+/// The value printed at a learned label
+fn read_at(text: &str, location: &FieldLocation) -> Option<String> {
+    let lines: Vec<&str> = text.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    let label = location.label.to_ascii_lowercase();
+    let pattern = location.field.value_pattern();
+    lines.iter().enumerate().find_map(|(i, line)| {
+        let lower = line.to_ascii_lowercase();
+        // Whole words only, so `Total` doesn't match inside `Subtotal`
+        let start = lower.match_indices(&label).map(|(at, _)| at).find(|&at| {
+            !lower[..at].chars().next_back().is_some_and(char::is_alphanumeric)
+        })?;
+        let rest = &line[start + label.len()..];
+        let value = if location.below { *lines.get(i + 1)? } else { rest };
+        pattern.find(value).map(|m| m.as_str().trim().to_string())
+    })
+}
+
+/// Replace fields of a parsed invoice with the values at its supplier's
+/// learned labels. Dates are normalized as the regex parser does.
+fn read_learned_fields(template: &VendorTemplate, text: &str, invoice: &mut ExtractedInvoice, order: DateOrder) {
+    let currency = invoice.currency_code().to_string();
+    for location in &template.fields {
+        let Some(value) = read_at(text, location) else {
+            continue;
+        };
+        let text_field = || Some(ExtractedField::new(value.clone(), LOCATION_CONFIDENCE, LOCATION_SOURCE));
+        let date_field = || {
+            let normalized = dates::normalize(&value, order);
+            Some(ExtractedField {
+                raw: Some(value.clone()),
+                ambiguous: normalized.ambiguous,
+                ..ExtractedField::new(
+                    normalized.iso.unwrap_or_else(|| value.clone()),
+                    LOCATION_CONFIDENCE,
+                    LOCATION_SOURCE,
+                )
+            })
+        };
+        let amount_field = || {
+            Money::parse(&value).map(|amount| {
+                ExtractedField::new(CurrencyAmount::new(amount, &currency), LOCATION_CONFIDENCE, LOCATION_SOURCE)
+            })
+        };
+        match location.field {
+            TemplateField::InvoiceNumber => invoice.invoice_number = text_field(),
+            TemplateField::InvoiceDate => invoice.invoice_date = date_field(),
+            TemplateField::DueDate => invoice.due_date = date_field(),
+            TemplateField::Subtotal => invoice.subtotal_amount = amount_field().or(invoice.subtotal_amount.take()),
+            TemplateField::Gst => invoice.gst_amount = amount_field().or(invoice.gst_amount.take()),
+            TemplateField::Total => invoice.total_amount = amount_field().or(invoice.total_amount.take()),
+        }
+    }
+}
+
+/// JSON-backed list of vendor templates
+pub struct TemplateStore {
+    path: PathBuf,
+    templates: Vec<VendorTemplate>,
+}
+
+impl TemplateStore {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            templates: storage::load_json(path)?,
+        })
+    }
+
+    pub fn open_default() -> Result<Self, String> {
+        Self::open(&storage::get_data_directory()?.join("vendor_templates.json"))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        storage::save_json(&self.path, &self.templates)
+    }
+
+    pub fn list(&self) -> Vec<VendorTemplate> {
+        self.templates.clone()
+    }
+
+    /// Choose a template by ABN, then vendor name, then layout
+    pub fn select(&self, invoice: &ExtractedInvoice, fingerprint: &LayoutFingerprint) -> Option<TemplateMatch> {
+        let found = |template: &VendorTemplate, kind| TemplateMatch {
+            template: template.clone(),
+            kind,
+            score: 1.0,
+        };
+        if let Some(abn) = &invoice.abn {
+            if let Some(template) = self.templates.iter().find(|t| t.abn.as_deref() == Some(abn.value.as_str())) {
+                return Some(found(template, TemplateMatchKind::Abn));
+            }
+        }
+        if let Some(vendor) = &invoice.vendor_name {
+            if let Some(template) = self.templates.iter().find(|t| same_vendor(&t.vendor, &vendor.value)) {
+                return Some(found(template, TemplateMatchKind::Vendor));
+            }
+        }
+        self.templates
+            .iter()
+            .map(|t| (t, t.match_score(fingerprint)))
+            .filter(|(_, score)| *score >= MIN_MATCH)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(template, score)| TemplateMatch {
+                template: template.clone(),
+                kind: TemplateMatchKind::Layout,
+                score,
+            })
+    }
+
+    /// Add a parsed invoice's fingerprint to its vendor's template,
+    /// creating the template when the vendor is new. Invoices without a
+    /// vendor name are not learned from.
+    pub fn learn(&mut self, invoice: &ExtractedInvoice, fingerprint: LayoutFingerprint) -> Option<VendorTemplate> {
+        let vendor = invoice.vendor_name.as_ref().map(|v| v.value.trim()).filter(|v| !v.is_empty())?;
+        let abn = invoice.abn.as_ref().map(|a| a.value.clone());
+        let index = match self
+            .templates
+            .iter()
+            .position(|t| (abn.is_some() && t.abn == abn) || same_vendor(&t.vendor, vendor))
+        {
+            Some(index) => index,
+            None => {
+                self.templates.push(VendorTemplate {
+                    id: storage::generate_id("template"),
+                    vendor: vendor.to_string(),
+                    ..Default::default()
+                });
+                self.templates.len() - 1
+            }
+        };
+
+        let template = &mut self.templates[index];
+        if template.abn.is_none() {
+            template.abn = abn;
+        }
+        if template.match_score(&fingerprint) < DUPLICATE_MATCH {
+            template.fingerprints.push(fingerprint);
+            let excess = template.fingerprints.len().saturating_sub(MAX_FINGERPRINTS);
+            template.fingerprints.drain(..excess);
+        }
+        template.documents_seen += 1;
+        template.updated_at = storage::now_timestamp();
+        Some(template.clone())
+    }
+
+    /// Learn where a confirmed invoice's supplier prints each field, from
+    /// the values on the saved document. Invoices without an ABN are not
+    /// learned from.
+    pub fn learn_fields(&mut self, doc: &StoredDocument, order: DateOrder) -> Option<VendorTemplate> {
+        let invoice = doc.invoice.as_ref()?;
+        let abn = invoice.abn.as_ref()?.value.clone();
+        let iso = |field: &Option<ExtractedField<String>>| {
+            field.as_ref().and_then(|f| dates::normalize(&f.value, order).iso)
+        };
+        let amount = |field: &Option<ExtractedField<CurrencyAmount>>| field.as_ref().map(|f| f.value.value);
+        let confirmed = [
+            (
+                TemplateField::InvoiceNumber,
+                invoice.invoice_number.as_ref().map(|f| Confirmed::Text(f.value.clone())),
+                invoice.invoice_number.as_ref().map(|f| f.source.clone()),
+            ),
+            (
+                TemplateField::InvoiceDate,
+                doc.date.clone().map(Confirmed::Date),
+                invoice.invoice_date.as_ref().map(|f| f.source.clone()),
+            ),
+            (
+                TemplateField::DueDate,
+                iso(&invoice.due_date).map(Confirmed::Date),
+                invoice.due_date.as_ref().map(|f| f.source.clone()),
+            ),
+            (
+                TemplateField::Subtotal,
+                amount(&invoice.subtotal_amount).map(Confirmed::Amount),
+                invoice.subtotal_amount.as_ref().map(|f| f.source.clone()),
+            ),
+            (
+                TemplateField::Gst,
+                doc.gst.map(Confirmed::Amount),
+                invoice.gst_amount.as_ref().map(|f| f.source.clone()),
+            ),
+            (
+                TemplateField::Total,
+                doc.total.map(Confirmed::Amount),
+                invoice.total_amount.as_ref().map(|f| f.source.clone()),
+            ),
+        ];
+        let fields: Vec<FieldLocation> = confirmed
+            .into_iter()
+            .filter_map(|(field, value, source)| {
+                let value = value?;
+                let (label, below) = locate(&invoice.raw_text, field, |raw| value.matches(raw, order))?;
+                Some(FieldLocation {
+                    field,
+                    label,
+                    below,
+                    source,
+                })
+            })
+            .collect();
+        if fields.is_empty() {
+            return None;
+        }
+
+        let index = match self.templates.iter().position(|t| t.abn.as_deref() == Some(abn.as_str())) {
+            Some(index) => index,
+            None => {
+                let vendor = doc.vendor.clone().or_else(|| invoice.vendor_name.as_ref().map(|v| v.value.clone()));
+                self.templates.push(VendorTemplate {
+                    id: storage::generate_id("template"),
+                    vendor: vendor.unwrap_or_else(|| abn.clone()),
+                    abn: Some(abn),
+                    ..Default::default()
+                });
+                self.templates.len() - 1
+            }
+        };
+        let template = &mut self.templates[index];
+        template.fields = fields;
+        template.updated_at = storage::now_timestamp();
+        Some(template.clone())
+    }
+
+    /// Set the scoring profile a template parses with
+    pub fn set_profile(&mut self, id: &str, profile: Option<String>) -> Result<VendorTemplate, String> {
+        let template = self
+            .templates
+            .iter_mut()
+            .find(|t| t.id == id)
+            .ok_or_else(|| format!("Template not found: {}", id))?;
+        template.heuristic_profile = profile;
+        template.updated_at = storage::now_timestamp();
+        Ok(template.clone())
+    }
+
+    pub fn delete(&mut self, id: &str) -> bool {
+        let before = self.templates.len();
+        self.templates.retain(|t| t.id != id);
+        self.templates.len() != before
+    }
+}
+
+/// Run a closure against the default template store while holding its lock
+pub fn with_templates<R>(f: impl FnOnce(&mut TemplateStore) -> Result<R, String>) -> Result<R, String> {
+    let _guard = TEMPLATES_LOCK.lock().map_err(|_| "Template store lock poisoned".to_string())?;
+    let mut store = TemplateStore::open_default()?;
+    f(&mut store)
+}
+
+/// Apply the matching template to a freshly parsed invoice and learn its
+/// layout. The invoice is re-parsed when the template names a different
+/// scoring profile, and a vendor name or ABN that failed to extract is
+/// filled in from a layout match.
+pub fn apply_template(
+    store: &mut TemplateStore,
+    text: &str,
+    mut invoice: ExtractedInvoice,
+    settings: &AppSettings,
+) -> Result<ExtractedInvoice, String> {
+    let layout = fingerprint(text);
+    if let Some(found) = store.select(&invoice, &layout) {
+        let weights = found
+            .template
+            .heuristic_profile
+            .as_deref()
+            .filter(|p| Some(*p) != settings.active_heuristic_profile.as_deref())
+            .and_then(|p| settings.heuristic_weights(p));
+        if let Some(weights) = weights {
+            invoice = InvoiceParser::with_weights(weights)?
+                .with_skip_patterns(&settings.line_item_skip_patterns)?
+                .parse_from_text(text, invoice.document_type.clone())?;
+        }
+        if found.kind == TemplateMatchKind::Abn {
+            read_learned_fields(&found.template, text, &mut invoice, settings.date_order);
+        }
+        if found.kind == TemplateMatchKind::Layout {
+            // Scaled down so a layout guess is always reviewed
+            let confidence = found.score * 0.8;
+            if invoice.vendor_name.is_none() {
+                invoice.vendor_name = Some(ExtractedField::new(found.template.vendor.clone(), confidence, TEMPLATE_SOURCE));
+            }
+            if let (None, Some(abn)) = (&invoice.abn, &found.template.abn) {
+                invoice.abn = Some(ExtractedField::new(abn.clone(), confidence, TEMPLATE_SOURCE));
+            }
+        }
+    }
+    store.learn(&invoice, layout);
+    Ok(invoice)
+}
+
+/// Apply templates from the default store, keeping the invoice as parsed
+/// if the store can't be used
+pub fn apply_default_template(text: &str, invoice: ExtractedInvoice) -> ExtractedInvoice {
+    let settings = crate::settings::load_settings().unwrap_or_default();
+    let fallback = invoice.clone();
+    let applied = with_templates(|store| {
+        let invoice = apply_template(store, text, invoice, &settings)?;
+        store.save()?;
+        Ok(invoice)
+    });
+    applied.unwrap_or_else(|e| {
+        log::warn!("Vendor templates not applied: {}", e);
+        fallback
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::invoice::DocumentType;
+
+    fn plumber_invoice(number: &str, amount: &str, with_header: bool) -> String {
+        let header = if with_header { "Acme Plumbing Pty Ltd\nABN 51 824 753 556\n" } else { "" };
+        format!(
+            "{}TAX INVOICE        Invoice Number: {}\nInvoice Date: 12/03/2024    Due Date: 26/03/2024\n\
+             Bill To: Jane Citizen\nCallout fee 1 x 90.00\nLabour 2 hrs {}\nSubtotal {}\nGST included\n\
+             Amount Due {}\nPayment by BSB 062-000 Account 1234 5678\nRemittance: accounts@acmeplumbing.com.au",
+            header, number, amount, amount, amount
+        )
+    }
+
+    #[test]
+    fn test_layout_match_fills_missing_vendor() {
+        let root = storage::test_dir();
+        let mut store = TemplateStore::open(&root.join("vendor_templates.json")).unwrap();
+        let settings = AppSettings::default();
+        let parser = InvoiceParser::new().unwrap();
+        let parse = |text: &str| parser.parse_from_text(text, DocumentType::Pdf).unwrap();
+
+        let first = plumber_invoice("INV-1001", "240.00", true);
+        let learned = apply_template(&mut store, &first, parse(&first), &settings).unwrap();
+        assert!(learned.vendor_name.is_some());
+        assert_eq!(store.list().len(), 1);
+
+        // The letterhead is an image the text layer doesn't include
+        let second = plumber_invoice("INV-1042", "615.50", false);
+        let layout = fingerprint(&second);
+        assert!(store.list()[0].match_score(&layout) >= MIN_MATCH);
+        let mut bare = parse(&second);
+        bare.vendor_name = None;
+        bare.abn = None;
+        let filled = apply_template(&mut store, &second, bare, &settings).unwrap();
+        let vendor = filled.vendor_name.unwrap();
+        assert_eq!((vendor.value.as_str(), vendor.source.as_str()), (store.list()[0].vendor.as_str(), TEMPLATE_SOURCE));
+        assert!(vendor.confidence < 0.8);
+        assert_eq!(filled.abn.unwrap().value, "51824753556");
+
+        let unrelated = fingerprint("Officeworks\nReceipt\nPaper A4 9.50\nTOTAL 9.50\nEFTPOS");
+        assert!(store.list()[0].match_score(&unrelated) < MIN_MATCH);
+        assert_eq!(store.list()[0].documents_seen, 2);
+    }
+
+    #[test]
+    fn test_confirmed_fields_are_read_at_their_labels() {
+        let root = storage::test_dir();
+        let mut store = TemplateStore::open(&root.join("vendor_templates.json")).unwrap();
+        let settings = AppSettings::default();
+        let parser = InvoiceParser::new().unwrap();
+        let invoice = |reference: &str, total: &str, deposit: &str, balance: &str| {
+            format!(
+                "Acme Plumbing Pty Ltd\nABN 51 824 753 556\nTAX INVOICE\nOur ref\n{}\nIssued 12/03/2024\n\
+                 Callout 90.00\nLabour {}\nTotal {}\nDeposit received {}\nBalance to pay {}",
+                reference, total, total, deposit, balance
+            )
+        };
+
+        // The user confirms the balance as the amount owing
+        let first = invoice("Q-88", "240.00", "100.00", "140.00");
+        let mut doc = StoredDocument {
+            vendor: Some("Acme Plumbing Pty Ltd".to_string()),
+            date: Some("2024-03-12".to_string()),
+            total: Some(Money::from_cents(14000)),
+            invoice: Some(parser.parse_from_text(&first, DocumentType::Pdf).unwrap()),
+            ..Default::default()
+        };
+        doc.invoice.as_mut().unwrap().invoice_number = Some(ExtractedField::new("Q-88".to_string(), 0.5, "test"));
+        let template = store.learn_fields(&doc, DateOrder::DayFirst).unwrap();
+        let labels: Vec<_> = template.fields.iter().map(|f| (f.field, f.label.as_str(), f.below)).collect();
+        assert_eq!(
+            labels,
+            vec![
+                (TemplateField::InvoiceNumber, "Our ref", true),
+                (TemplateField::InvoiceDate, "Issued", false),
+                (TemplateField::Total, "Balance to pay", false),
+            ]
+        );
+
+        let second = invoice("Q-91", "500.00", "200.00", "300.00");
+        let parsed = parser.parse_from_text(&second, DocumentType::Pdf).unwrap();
+        let parsed = apply_template(&mut store, &second, parsed, &settings).unwrap();
+        let total = parsed.total_amount.unwrap();
+        assert_eq!((total.value.value, total.source.as_str()), (Money::from_cents(30000), LOCATION_SOURCE));
+        assert_eq!(parsed.invoice_number.unwrap().value, "Q-91");
+        let date = parsed.invoice_date.unwrap();
+        assert_eq!((date.value.as_str(), date.raw.as_deref()), ("2024-03-12", Some("12/03/2024")));
+    }
+}
@@ -0,0 +1,206 @@
+//! Verification Module
+//!
+//! Lists the documents behind a year's totals whose figures are shaky: ones
+//! nobody has reviewed that were read with low confidence or still carry
+//! warnings, such as totals that don't add up or amounts in a foreign
+//! currency. Reports print them as an appendix so an accountant knows which
+//! amounts to check by hand.
+
+use serde::{Deserialize, Serialize};
+
+use crate::documents::{self, DocumentFilter, DocumentStatus, StoredDocument};
+use crate::invoice;
+use crate::money::Money;
+use crate::ocr::{self, ThresholdConfig};
+use crate::periods;
+use crate::report_builder::{Align, Column, ReportBuilder};
+use crate::settings;
+
+/// A document included in totals that should be checked by hand
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DocumentToCheck {
+    pub document_id: String,
+    pub vendor: Option<String>,
+    pub date: Option<String>,
+    pub amount: Option<Money>,
+    pub confidence: f64,
+    /// Why the document is listed, at least one
+    pub reasons: Vec<String>,
+}
+
+/// Why a document's figures can't be relied on, or nothing when they can.
+/// Reviewed documents and manual entries have been confirmed by a person.
+pub fn reasons_to_check(doc: &StoredDocument, thresholds: &ThresholdConfig) -> Vec<String> {
+    if doc.status != DocumentStatus::PendingReview || doc.manual_entry {
+        return Vec::new();
+    }
+    let mut reasons = Vec::new();
+    if doc.confidence < thresholds.accept {
+        reasons.push(format!(
+            "Confidence {:.0}% is below {:.0}%",
+            doc.confidence * 100.0,
+            thresholds.accept * 100.0
+        ));
+    }
+
+    if let Some(inv) = &doc.invoice {
+        let validation = invoice::validate_invoice(inv);
+        reasons.extend(validation.arithmetic_warnings.into_iter().map(|w| w.message));
+        if let Some(code) = validation.foreign_currency {
+            reasons.push(format!("Amounts are in {}", code));
+        }
+        if validation.missing_fields.iter().any(|field| field == "total_amount") {
+            reasons.push("No total detected".to_string());
+        }
+    } else if let Some(receipt) = &doc.receipt {
+        let validation = ocr::validate_receipt(receipt, thresholds);
+        reasons.extend(validation.discrepancies.iter().map(|d| {
+            format!("Items add up to ${}, not the {} of ${}", d.items_total, d.field.replace('_', " "), d.total_amount)
+        }));
+        if !validation.low_confidence_fields.is_empty() {
+            reasons.push(format!("Unsure of {}", validation.low_confidence_fields.join(", ").replace('_', " ")));
+        }
+        if !validation.handwritten_fields.is_empty() {
+            reasons.push(format!("Handwritten {}", validation.handwritten_fields.join(", ").replace('_', " ")));
+        }
+    }
+    reasons
+}
+
+/// Documents dated in a financial year that should be checked, by date
+pub fn documents_to_check(
+    documents: &[StoredDocument],
+    financial_year: i32,
+    thresholds: &ThresholdConfig,
+) -> Vec<DocumentToCheck> {
+    let mut flagged: Vec<DocumentToCheck> = documents
+        .iter()
+        .filter(|doc| {
+            doc.date
+                .as_deref()
+                .and_then(periods::parse_date)
+                .is_some_and(|date| periods::financial_year_of(date) == financial_year)
+        })
+        .filter_map(|doc| {
+            let reasons = reasons_to_check(doc, thresholds);
+            (!reasons.is_empty()).then(|| DocumentToCheck {
+                document_id: doc.id.clone(),
+                vendor: doc.vendor.clone(),
+                date: doc.date.clone(),
+                amount: doc.total,
+                confidence: doc.confidence,
+                reasons,
+            })
+        })
+        .collect();
+    flagged.sort_by(|a, b| a.date.cmp(&b.date));
+    flagged
+}
+
+/// Documents to check for a financial year using current data and settings
+pub fn for_financial_year(financial_year: i32) -> Result<Vec<DocumentToCheck>, String> {
+    let thresholds = settings::load_settings()?.ocr_thresholds;
+    let documents = documents::with_store(|store| Ok(store.list(&DocumentFilter::default())))?;
+    Ok(documents_to_check(&documents, financial_year, &thresholds))
+}
+
+/// Add an appendix listing the documents to check; nothing when there are none
+pub fn render_appendix(report: &mut ReportBuilder, documents: &[DocumentToCheck]) {
+    if documents.is_empty() {
+        return;
+    }
+    let total: Money = documents.iter().filter_map(|doc| doc.amount).sum();
+    report
+        .space(18.0)
+        .subheading("Appendix: Documents to check")
+        .text(&format!(
+            "{} unreviewed document{} totalling ${} may have been read incorrectly.",
+            documents.len(),
+            if documents.len() == 1 { "" } else { "s" },
+            total
+        ))
+        .space(6.0);
+
+    let rows: Vec<Vec<String>> = documents
+        .iter()
+        .map(|doc| {
+            vec![
+                doc.date.clone().unwrap_or_default(),
+                doc.vendor.clone().unwrap_or_else(|| "Unknown vendor".to_string()),
+                doc.amount.map_or_else(|| "-".to_string(), |amount| format!("${}", amount)),
+                format!("{:.0}%", doc.confidence * 100.0),
+                doc.reasons.join("; "),
+            ]
+        })
+        .collect();
+    report.table(
+        &[
+            Column::new("Date", 70.0, Align::Left),
+            Column::new("Vendor", 130.0, Align::Left),
+            Column::new("Amount", 70.0, Align::Right),
+            Column::new("Conf.", 45.0, Align::Right),
+            Column::new("Reason", 200.0, Align::Left),
+        ],
+        &rows,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::documents::Extraction;
+    use crate::invoice::{DocumentType, InvoiceParser};
+    use crate::storage;
+
+    #[test]
+    fn test_shaky_documents_in_the_year_are_listed() {
+        let parser = InvoiceParser::new().unwrap();
+        let extracted = |text: &str, status: DocumentStatus| {
+            let invoice = parser.parse_from_text(text, DocumentType::Pdf).unwrap();
+            let mut doc = StoredDocument {
+                id: storage::generate_id("doc"),
+                status,
+                ..Default::default()
+            };
+            doc.apply_extraction(Extraction::Invoice(Box::new(invoice)));
+            doc
+        };
+        let header = "Acme Plumbing Pty Ltd\nABN: 51 824 753 556\nTAX INVOICE\nInvoice Number: INV-1001\n";
+        let good = format!("{}Invoice Date: 12/03/2024\nSubtotal 100.00\nGST 10.00\nTotal 110.00", header);
+        let bad = format!("{}Invoice Date: 14/03/2024\nSubtotal 100.00\nGST 10.00\nTotal 120.00", header);
+        let documents = vec![
+            extracted(&bad, DocumentStatus::PendingReview),
+            extracted(&bad, DocumentStatus::Reviewed),
+            extracted(&good, DocumentStatus::PendingReview),
+            StoredDocument {
+                date: Some("2024-03-20".to_string()),
+                total: Some(Money::from_cents(5500)),
+                manual_entry: true,
+                ..Default::default()
+            },
+        ];
+        let thresholds = ThresholdConfig {
+            accept: documents[2].confidence,
+            ..Default::default()
+        };
+
+        let flagged = documents_to_check(&documents, 2024, &thresholds);
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].document_id, documents[0].id);
+        assert_eq!(flagged[0].amount, Some(Money::from_cents(12000)));
+        assert!(!flagged[0].reasons.is_empty());
+        assert!(documents_to_check(&documents, 2025, &thresholds).is_empty());
+
+        let strict = ThresholdConfig {
+            accept: 0.99,
+            ..Default::default()
+        };
+        let flagged = documents_to_check(&documents, 2024, &strict);
+        assert_eq!(flagged.len(), 2);
+        assert!(flagged[1].reasons[0].starts_with("Confidence"));
+
+        let mut report = ReportBuilder::new("Appendix");
+        render_appendix(&mut report, &flagged);
+        assert!(report.build().starts_with(b"%PDF"));
+    }
+}
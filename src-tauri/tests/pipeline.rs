@@ -0,0 +1,112 @@
+//! End-to-end tests over the library crate: parse fixture documents, store
+//! them, and build reports from what was stored, without starting the app.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use app_lib::bundle;
+use app_lib::documents::{DocumentFilter, DocumentKind, DocumentStore, Extraction, StoredDocument};
+use app_lib::invoice::{DocumentType, InvoiceParser};
+use app_lib::money::Money;
+use app_lib::ocr;
+use app_lib::report_builder::ReportBuilder;
+use app_lib::storage;
+use app_lib::summary;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name)
+}
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(storage::generate_id("tally-test"));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn parse_invoice_fixture() -> StoredDocument {
+    let text = fs::read_to_string(fixture("invoice.txt")).unwrap();
+    let invoice = InvoiceParser::new().unwrap().parse_from_text(&text, DocumentType::Pdf).unwrap();
+    let mut doc = StoredDocument {
+        category: Some("Repairs".to_string()),
+        ..Default::default()
+    };
+    doc.apply_extraction(Extraction::Invoice(Box::new(invoice)));
+    doc
+}
+
+fn parse_receipt_fixture() -> StoredDocument {
+    let tsv = fs::read_to_string(fixture("receipt.tsv")).unwrap();
+    let receipt = ocr::receipt_from_lines(&ocr::parse_tsv(&tsv));
+    let mut doc = StoredDocument {
+        category: Some("Groceries".to_string()),
+        ..Default::default()
+    };
+    doc.apply_extraction(Extraction::Receipt(Box::new(receipt)));
+    doc
+}
+
+#[test]
+fn test_fixtures_parse_into_documents() {
+    let invoice = parse_invoice_fixture();
+    assert_eq!(invoice.kind, DocumentKind::Invoice);
+    assert_eq!(invoice.total, Some(Money::from_cents(27500)));
+    assert_eq!(invoice.gst, Some(Money::from_cents(2500)));
+    assert_eq!(invoice.date.as_deref(), Some("2024-03-14"));
+    let extracted = invoice.invoice.as_ref().unwrap();
+    assert_eq!(extracted.abn.as_ref().unwrap().value, "51824753556");
+    assert_eq!(extracted.invoice_number.as_ref().unwrap().value, "INV-2024-017");
+
+    let receipt = parse_receipt_fixture();
+    assert_eq!(receipt.kind, DocumentKind::Receipt);
+    assert_eq!(receipt.vendor.as_deref(), Some("WOOLWORTHS METRO"));
+    assert_eq!(receipt.total, Some(Money::from_cents(760)));
+    assert_eq!(receipt.date.as_deref(), Some("2024-03-12"));
+}
+
+#[test]
+fn test_stored_documents_round_trip_into_reports() {
+    let dir = temp_dir();
+    let store_path = dir.join("documents.json");
+
+    let mut store = DocumentStore::open(&store_path).unwrap();
+    let invoice = store.upsert(parse_invoice_fixture());
+    store.upsert(parse_receipt_fixture());
+    store.save().unwrap();
+
+    // Reopen from disk so the reports only see what was persisted
+    let store = DocumentStore::open(&store_path).unwrap();
+    let documents = store.list(&DocumentFilter::default());
+    assert_eq!(documents.len(), 2);
+    assert_eq!(store.get(&invoice.id).unwrap().total, Some(Money::from_cents(27500)));
+
+    let fy = summary::financial_year_summary(&documents, &[], None, 2024);
+    assert_eq!(fy.document_count, 2);
+    assert_eq!(fy.substantiated_total.to_dollars(), 282.6);
+    assert_eq!(fy.gst_total.to_dollars(), 25.0);
+    assert_eq!(fy.categories.len(), 2);
+
+    // Bundle the originals: a PDF for the invoice and a photo for the receipt
+    let invoice_pdf = dir.join("invoice.pdf");
+    let mut report = ReportBuilder::new("Tax invoice");
+    report.heading("Harbour Plumbing Pty Ltd").text("Total: $275.00");
+    fs::write(&invoice_pdf, report.build()).unwrap();
+    let receipt_photo = dir.join("receipt.png");
+    image::RgbImage::from_pixel(60, 120, image::Rgb([240, 240, 240])).save(&receipt_photo).unwrap();
+
+    let originals: Vec<StoredDocument> = documents
+        .into_iter()
+        .map(|mut doc| {
+            let source = if doc.kind == DocumentKind::Invoice { &invoice_pdf } else { &receipt_photo };
+            doc.source_path = Some(source.to_string_lossy().to_string());
+            doc
+        })
+        .collect();
+    let output = dir.join("bundle.pdf");
+    let result = bundle::create_bundle(&originals, &output).unwrap();
+    assert_eq!(result.document_count, 2);
+    assert!(result.skipped.is_empty());
+    assert_eq!(result.page_count, 3);
+    assert!(output.is_file());
+
+    let _ = fs::remove_dir_all(&dir);
+}
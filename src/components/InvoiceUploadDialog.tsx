@@ -4,7 +4,7 @@ import { Button } from '@/components/ui/button'
 import { Input } from '@/components/ui/input'
 import { Label } from '@/components/ui/label'
 import { FileUpload } from '@/components/FileUpload'
-import { parseInvoicePdf, parseInvoiceImage, validateInvoice, extractedInvoiceToDbInvoice, createInvoice, type ExtractedInvoice, type InvoiceValidationResult } from '@/lib/invoices'
+import { parseInvoicePdf, parseInvoiceImage, validateInvoice, extractedInvoiceToDbInvoice, createInvoice, type CurrencyAmount, type ExtractedInvoice, type InvoiceValidationResult } from '@/lib/invoices'
 import { createReceipt } from '@/lib/db'
 import { toast } from 'sonner'
 import { Loader2, FileText, CheckCircle, AlertTriangle, XCircle } from 'lucide-react'
@@ -90,11 +90,15 @@ export function InvoiceUploadDialog({ open, onOpenChange, onInvoiceCreated }: In
       const invoiceData = extractedInvoiceToDbInvoice(extractedData, filePath)
       await createInvoice(invoiceData)
       
-      // Optionally create linked expense record
-      if (extractedData.vendor_name && extractedData.total_amount) {
+      // Optionally create linked expense record. Expenses are kept in
+      // Australian dollars, so a foreign-currency total is entered by hand.
+      const total = extractedData.total_amount?.value
+      if (total && total.currency !== 'AUD') {
+        toast.info(`Invoice is in ${total.currency}; add the expense in Australian dollars by hand`)
+      } else if (extractedData.vendor_name && total) {
         await createReceipt({
           vendor: extractedData.vendor_name.value,
-          amount: extractedData.total_amount.value,
+          amount: total.value,
           category: 'Other',
           date: extractedData.invoice_date?.value || new Date().toISOString().split('T')[0],
           notes: `Auto-created from invoice #${extractedData.invoice_number?.value || 'unknown'}`,
@@ -118,6 +122,9 @@ export function InvoiceUploadDialog({ open, onOpenChange, onInvoiceCreated }: In
     }
   }
 
+  const formatAmount = (amount: CurrencyAmount) =>
+    amount.currency === 'AUD' ? `$${amount.value.toFixed(2)}` : `${amount.currency} ${amount.value.toFixed(2)}`
+
   const getConfidenceColor = (confidence: number) => {
     if (confidence >= 0.8) return 'bg-green-500'
     if (confidence >= 0.6) return 'bg-yellow-500'
@@ -311,7 +318,7 @@ export function InvoiceUploadDialog({ open, onOpenChange, onInvoiceCreated }: In
                   <Label>Total Amount</Label>
                   <div className="flex items-center gap-2">
                     <Input 
-                      value={extractedData.total_amount?.value ? `$${extractedData.total_amount.value.toFixed(2)}` : ''} 
+                      value={extractedData.total_amount?.value ? formatAmount(extractedData.total_amount.value) : ''} 
                       placeholder="Not detected"
                       readOnly
                     />
@@ -327,7 +334,7 @@ export function InvoiceUploadDialog({ open, onOpenChange, onInvoiceCreated }: In
                 <div className="space-y-2">
                   <Label>GST Amount</Label>
                   <Input 
-                    value={extractedData.gst_amount?.value ? `$${extractedData.gst_amount.value.toFixed(2)}` : ''} 
+                    value={extractedData.gst_amount?.value ? formatAmount(extractedData.gst_amount.value) : ''} 
                     placeholder="Not detected"
                     readOnly
                   />
@@ -99,7 +99,8 @@ export function OcrReviewDialog({
   };
 
   const hasLowConfidence = (field: string) =>
-    validation.low_confidence_fields.includes(field);
+    validation.low_confidence_fields.includes(field) ||
+    validation.discrepancies.some((d) => d.field === field);
 
   return (
     <Dialog open={isOpen} onOpenChange={(open) => !open && onClose()}>
@@ -722,14 +722,8 @@ export async function processCapturedImage(
   try {
     // If we have an image path, use Tauri OCR
     if (imagePath) {
-      const { invoke } = await import('@tauri-apps/api/core');
-      const result = await invoke<{
-        vendor: { value: string; confidence: number };
-        date: { value: string; confidence: number };
-        total_amount: { value: number; confidence: number };
-        raw_text: string;
-        overall_confidence: number;
-      }>('scan_receipt_ocr', { imagePath });
+      const { runReceiptScan } = await import('../lib/ocr');
+      const result = await runReceiptScan(imagePath);
 
       return {
         text: result.raw_text,
@@ -30,13 +30,14 @@ const mockExtractedInvoice: ExtractedInvoice = {
   invoice_date: { value: '2024-01-15', confidence: 0.85, source: 'date_regex' },
   due_date: { value: '2024-02-15', confidence: 0.80, source: 'date_regex' },
   vendor_name: { value: 'ABC Pty Ltd', confidence: 0.88, source: 'vendor_heuristic' },
-  total_amount: { value: 1100.00, confidence: 0.92, source: 'amount_regex' },
-  gst_amount: { value: 100.00, confidence: 0.85, source: 'amount_regex' },
+  total_amount: { value: { value: 1100.00, currency: 'AUD' }, confidence: 0.92, source: 'amount_regex' },
+  gst_amount: { value: { value: 100.00, currency: 'AUD' }, confidence: 0.85, source: 'amount_regex' },
   payment_terms: { value: 'Net 30 days', confidence: 0.75, source: 'payment_terms_regex' },
   line_items: [
     { description: 'Consulting Services', quantity: 10, unit_price: 100.00, total: 1000.00, confidence: 0.80 },
     { description: 'GST', quantity: undefined, unit_price: undefined, total: 100.00, confidence: 0.70 }
   ],
+  page_count: 1,
   raw_text: 'ABC Pty Ltd\nABN: 51 824 753 556\nInvoice #INV-2024-001\nDate: 2024-01-15\n\nConsulting Services x10 @ $100.00 = $1000.00\nGST: $100.00\n\nTotal: $1100.00',
   overall_confidence: 0.87,
   document_type: 'Pdf'
@@ -46,6 +47,7 @@ const mockValidationResult = {
   is_valid: true,
   missing_fields: [] as string[],
   warnings: [] as string[],
+  arithmetic_warnings: [],
   suggested_action: 'accept' as const
 }
 
@@ -57,7 +59,7 @@ describe('Invoice Parser', () => {
       expect(result).toBeDefined()
       expect(result.abn?.value).toBe('51824753556')
       expect(result.invoice_number?.value).toBe('INV-2024-001')
-      expect(result.total_amount?.value).toBe(1100.00)
+      expect(result.total_amount?.value.value).toBe(1100.00)
     })
 
     it('should handle PDF parsing errors', async () => {
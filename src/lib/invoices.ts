@@ -7,6 +7,14 @@ export interface ExtractedField<T> {
   value: T;
   confidence: number;
   source: string;
+  page?: number;
+  raw?: string | null;
+  ambiguous?: boolean;
+}
+
+export interface CurrencyAmount {
+  value: number;
+  currency: string;
 }
 
 export interface LineItem {
@@ -15,27 +23,81 @@ export interface LineItem {
   unit_price?: number;
   total: number;
   confidence: number;
+  page?: number;
+  gst?: number;
+  gst_code?: string;
+}
+
+export interface PaymentDetails {
+  bsb?: ExtractedField<string>;
+  account_number?: ExtractedField<string>;
+  account_name?: ExtractedField<string>;
+  payid?: ExtractedField<string>;
+  bpay_biller_code?: ExtractedField<string>;
+  bpay_reference?: ExtractedField<string>;
+}
+
+export interface VendorAddress {
+  unit?: string;
+  street?: string;
+  suburb?: string;
+  state?: string;
+  postcode?: string;
 }
 
+export interface VendorContact {
+  email?: ExtractedField<string>;
+  phone?: ExtractedField<string>;
+  address?: ExtractedField<VendorAddress>;
+}
+
+export type DocumentSubtype =
+  | "tax_invoice"
+  | "credit_note"
+  | "adjustment_note"
+  | "receipt"
+  | "statement"
+  | "quote";
+
 export interface ExtractedInvoice {
   abn?: ExtractedField<string>;
+  acn?: ExtractedField<string>;
   invoice_number?: ExtractedField<string>;
+  purchase_order_number?: ExtractedField<string>;
+  quote_number?: ExtractedField<string>;
   invoice_date?: ExtractedField<string>;
   due_date?: ExtractedField<string>;
   vendor_name?: ExtractedField<string>;
-  total_amount?: ExtractedField<number>;
-  gst_amount?: ExtractedField<number>;
+  total_amount?: ExtractedField<CurrencyAmount>;
+  gst_amount?: ExtractedField<CurrencyAmount>;
+  subtotal_amount?: ExtractedField<CurrencyAmount>;
+  currency?: ExtractedField<string>;
   payment_terms?: ExtractedField<string>;
+  vendor_contact?: VendorContact;
+  payment_details?: PaymentDetails;
   line_items: LineItem[];
+  page_count: number;
   raw_text: string;
   overall_confidence: number;
   document_type: "Unknown" | "Pdf" | "Image";
+  subtype?: DocumentSubtype;
+}
+
+export type ArithmeticCheck = "gst_inclusive" | "gst_exclusive" | "subtotal_plus_gst" | "line_items";
+
+export interface ArithmeticWarning {
+  check: ArithmeticCheck;
+  expected: number;
+  actual: number;
+  message: string;
 }
 
 export interface InvoiceValidationResult {
   is_valid: boolean;
   missing_fields: string[];
   warnings: string[];
+  arithmetic_warnings: ArithmeticWarning[];
+  foreign_currency?: string;
   suggested_action: "accept" | "review" | "manual_entry";
 }
 
@@ -269,8 +331,8 @@ export function extractedInvoiceToDbInvoice(
     invoice_date: extracted.invoice_date?.value,
     due_date: extracted.due_date?.value,
     vendor_name: extracted.vendor_name?.value,
-    total_amount: extracted.total_amount?.value ?? 0,
-    gst_amount: extracted.gst_amount?.value,
+    total_amount: extracted.total_amount?.value.value ?? 0,
+    gst_amount: extracted.gst_amount?.value.value,
     payment_terms: extracted.payment_terms?.value,
     line_items_json: extracted.line_items.length > 0 
       ? JSON.stringify(extracted.line_items) 
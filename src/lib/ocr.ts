@@ -1,15 +1,32 @@
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 
 export interface ExtractedField<T> {
   value: T;
   confidence: number;
   source: string;
+  /** Read poorly from handwriting; ask for the value to be typed in */
+  handwritten: boolean;
+  /** Text as printed, when `value` was normalized from it */
+  raw?: string | null;
+  /** The printed date could be read day-first or month-first */
+  ambiguous?: boolean;
 }
 
 export interface ExtractedItem {
   name: string;
   amount: number;
   confidence: number;
+  /** Identical consecutive lines collapsed into this item; `amount` is their sum */
+  quantity?: number | null;
+}
+
+export interface ReceiptAdjustment {
+  kind: "tip" | "card_surcharge" | "rounding" | "discount";
+  /** Change to the total; negative for discounts and rounding down */
+  amount: number;
+  confidence: number;
+  line: string;
 }
 
 export interface ExtractedReceipt {
@@ -17,14 +34,29 @@ export interface ExtractedReceipt {
   date: ExtractedField<string>;
   total_amount: ExtractedField<number>;
   items: ExtractedItem[];
+  adjustments: ReceiptAdjustment[];
+  /** Card network and last four digits ("Visa ****1234"), or "Cash" */
+  payment_details: ExtractedField<string> | null;
   raw_text: string;
   overall_confidence: number;
+  /** Copy of the photo kept in the app's receipt archive */
+  archived_path: string | null;
+}
+
+export interface TotalDiscrepancy {
+  field: string;
+  items_total: number;
+  total_amount: number;
+  difference: number;
 }
 
 export interface ValidationResult {
   is_valid: boolean;
   low_confidence_fields: string[];
   suggested_action: "accept" | "review" | "manual_entry";
+  discrepancies: TotalDiscrepancy[];
+  /** Fields to send straight to manual entry */
+  handwritten_fields: string[];
 }
 
 export interface OcrScanResult {
@@ -32,16 +64,162 @@ export interface OcrScanResult {
   validation: ValidationResult;
 }
 
+export interface OcrJobEvent {
+  job_id: string;
+  status: "completed" | "failed" | "cancelled";
+  error: string | null;
+  receipt: ExtractedReceipt | null;
+}
+
+export interface QualityReport {
+  width: number;
+  height: number;
+  estimated_dpi: number;
+  sharpness: number;
+  brightness: number;
+  issues: ("blurry" | "too_dark" | "overexposed" | "low_resolution")[];
+  recommendation: "ok" | "retake";
+}
+
+export interface ThresholdConfig {
+  accept: number;
+  review: number;
+  field_minimums: {
+    vendor: number;
+    date: number;
+    total_amount: number;
+  };
+  review_flagged_fields: boolean;
+}
+
+export interface OcrEngineStatus {
+  engine: "tesseract" | "mock";
+  ready: boolean;
+  languages: string[];
+  idle_engines: number;
+  engines_in_use: number;
+  engines_created: number;
+  last_error: string | null;
+}
+
+/** Extra cleanup for hard-to-read receipts; "thermal" brings up faded print */
+export type Enhancement = "none" | "thermal";
+
 const CONFIDENCE_THRESHOLD = 0.50;
+const OCR_JOB_EVENT = "ocr://job";
 
 /**
- * Scan a receipt image using OCR
+ * Start a background OCR scan and wait for its result. `onStarted`
+ * receives the job id, which can be passed to `cancelOcrJob`.
  */
-export async function scanReceipt(imagePath: string): Promise<OcrScanResult> {
+export async function runReceiptScan(
+  imagePath: string,
+  options: { forceRescan?: boolean; enhancement?: Enhancement; onStarted?: (jobId: string) => void } = {}
+): Promise<ExtractedReceipt> {
+  // Listen before starting so a fast job can't finish unseen
+  const finished = new Map<string, OcrJobEvent>();
+  let waiting: { jobId: string; resolve: (event: OcrJobEvent) => void } | null = null;
+  const unlisten = await listen<OcrJobEvent>(OCR_JOB_EVENT, ({ payload }) => {
+    if (waiting && payload.job_id === waiting.jobId) {
+      waiting.resolve(payload);
+    } else {
+      finished.set(payload.job_id, payload);
+    }
+  });
+
   try {
-    const receipt = await invoke<ExtractedReceipt>("scan_receipt_ocr", {
+    const jobId = await invoke<string>("scan_receipt_ocr", {
       imagePath,
+      forceRescan: options.forceRescan ?? false,
+      enhancement: options.enhancement ?? null,
     });
+    options.onStarted?.(jobId);
+    const event =
+      finished.get(jobId) ??
+      (await new Promise<OcrJobEvent>((resolve) => {
+        waiting = { jobId, resolve };
+      }));
+
+    if (event.status !== "completed" || !event.receipt) {
+      throw new Error(event.error ?? `OCR job ${event.status}`);
+    }
+    return event.receipt;
+  } finally {
+    unlisten();
+  }
+}
+
+/**
+ * Stop a running OCR job
+ */
+export async function cancelOcrJob(jobId: string): Promise<boolean> {
+  return invoke<boolean>("cancel_ocr_job", { jobId });
+}
+
+/**
+ * Check a photo is sharp, well lit and detailed enough before scanning it
+ */
+export async function checkImageQuality(imagePath: string): Promise<QualityReport> {
+  return invoke<QualityReport>("check_image_quality", { imagePath });
+}
+
+/**
+ * Read the confidence thresholds used to validate scans
+ */
+export async function getOcrThresholds(): Promise<ThresholdConfig> {
+  return invoke<ThresholdConfig>("get_ocr_thresholds");
+}
+
+/**
+ * Save the confidence thresholds used to validate scans
+ */
+export async function setOcrThresholds(thresholds: ThresholdConfig): Promise<ThresholdConfig> {
+  return invoke<ThresholdConfig>("set_ocr_thresholds", { thresholds });
+}
+
+/**
+ * Report whether a warm OCR engine is ready for the next scan
+ */
+export async function getOcrEngineStatus(): Promise<OcrEngineStatus> {
+  return invoke<OcrEngineStatus>("ocr_engine_status");
+}
+
+/**
+ * Scan a photo of several receipts, one result per receipt found. A photo
+ * of a single receipt returns one result.
+ */
+export async function scanMultiReceipt(imagePath: string): Promise<ExtractedReceipt[]> {
+  return invoke<ExtractedReceipt[]>("scan_multi_receipt", { imagePath });
+}
+
+export interface BoundingBox {
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+/** A recognised line; fields read from it have source `ocr_line_{index}` */
+export interface RecognizedLine {
+  index: number;
+  text: string;
+  confidence: number;
+  bbox: BoundingBox | null;
+}
+
+/**
+ * Recognise every line of a receipt photo, for assigning lines to fields by hand
+ */
+export async function getOcrLines(imagePath: string): Promise<RecognizedLine[]> {
+  return invoke<RecognizedLine[]>("get_ocr_lines", { imagePath });
+}
+
+/**
+ * Scan a receipt image using OCR
+ */
+export async function scanReceipt(imagePath: string): Promise<OcrScanResult> {
+  try {
+    const receipt = await runReceiptScan(imagePath);
 
     const validation = await invoke<ValidationResult>("validate_ocr_confidence", {
       receipt,
@@ -95,3 +273,28 @@ export function formatExtractedReceipt(
     needsReview: extracted.overall_confidence < CONFIDENCE_THRESHOLD,
   };
 }
+
+interface BatteryStatus extends EventTarget {
+  charging: boolean;
+  level: number;
+}
+
+/**
+ * Keep the backend informed of the battery state so the battery saver can
+ * hold batch scans while the battery is low. Does nothing where the webview
+ * can't read the battery.
+ */
+export async function watchPowerState(): Promise<void> {
+  const nav = navigator as Navigator & { getBattery?: () => Promise<BatteryStatus> };
+  if (!nav.getBattery) return;
+
+  const battery = await nav.getBattery();
+  const report = () =>
+    invoke("report_power_state_command", {
+      state: { on_battery: !battery.charging, battery_percent: Math.round(battery.level * 100) },
+    }).catch((error) => console.warn("Failed to report power state:", error));
+
+  battery.addEventListener("chargingchange", report);
+  battery.addEventListener("levelchange", report);
+  await report();
+}
@@ -0,0 +1,37 @@
+import { invoke } from "@tauri-apps/api/core";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+
+export interface ThumbnailEvent {
+  document_id: string;
+  thumbnail_path: string | null;
+  error: string | null;
+}
+
+export interface ThumbnailQueueResult {
+  ready: ThumbnailEvent[];
+  queued: number;
+}
+
+const THUMBNAIL_EVENT = "thumbnail://ready";
+
+/**
+ * Queue background thumbnails for documents without one, e.g. after an import
+ */
+export async function queueThumbnails(documentIds?: string[]): Promise<ThumbnailQueueResult> {
+  return invoke<ThumbnailQueueResult>("queue_thumbnails_command", { documentIds: documentIds ?? null });
+}
+
+/**
+ * Get thumbnails for the documents on screen; missing ones are made ahead
+ * of the background queue and arrive through `onThumbnail`
+ */
+export async function requestThumbnails(documentIds: string[]): Promise<ThumbnailQueueResult> {
+  return invoke<ThumbnailQueueResult>("request_thumbnails_command", { documentIds });
+}
+
+/**
+ * Listen for thumbnails as the background worker finishes them
+ */
+export function onThumbnail(handler: (event: ThumbnailEvent) => void): Promise<UnlistenFn> {
+  return listen<ThumbnailEvent>(THUMBNAIL_EVENT, ({ payload }) => handler(payload));
+}